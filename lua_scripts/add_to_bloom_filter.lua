@@ -0,0 +1,12 @@
+---
+--- Script to add the given item to the bloom filter at the given key only if the filter exists
+--- Example usage:
+---
+--- EVAL "if redis.call('EXISTS', KEYS[1]) == 1 then return redis.call('BF.ADD', KEYS[1], ARGV[1]) end return 0" 1 "Book_%&bloom_%&_ids" "Book_%&_Oliver Twist"
+---
+
+if redis.call('EXISTS', KEYS[1]) == 1 then
+    return redis.call('BF.ADD', KEYS[1], ARGV[1])
+end
+
+return 0
@@ -0,0 +1,29 @@
+---
+--- Script to add the values of the tracked fields of the hashmaps at KEYS[2], KEYS[3], ... to the HyperLogLogs of the
+--- fields, the tracked fields being those in the set at KEYS[1].
+--- ARGV[1] is the prefix of the keys of the HyperLogLogs, to which the name of each field is appended.
+--- It returns 0 if no fields are tracked, or else 1
+--- Example usage:
+---
+--- EVAL "local fields = redis.call('SMEMBERS', KEYS[1]) if #fields == 0 then return 0 end local table_unpack = table.unpack or unpack for i = 2, #KEYS do local values = redis.call('HMGET', KEYS[i], table_unpack(fields)) for j, field in ipairs(fields) do if values[j] then redis.call('PFADD', ARGV[1] .. field, values[j]) end end end return 1" 3 "Book_%&cardinality_%&_fields" "Book_%&_1" "Book_%&_2" "Book_%&hll_%&_"
+---
+
+local fields = redis.call('SMEMBERS', KEYS[1])
+
+if #fields == 0 then
+    return 0
+end
+
+local table_unpack = table.unpack or unpack
+
+for i = 2, #KEYS do
+    local values = redis.call('HMGET', KEYS[i], table_unpack(fields))
+
+    for j, field in ipairs(fields) do
+        if values[j] then
+            redis.call('PFADD', ARGV[1] .. field, values[j])
+        end
+    end
+end
+
+return 1
\ No newline at end of file
@@ -0,0 +1,47 @@
+---
+--- Script to compute the sum, average, minimum or maximum of the numeric values of a given field of all hashmaps
+--- that have a given pattern.
+--- ARGV has the pattern, the field and the operation i.e. "sum", "avg", "min" or "max".
+--- It returns the number of values found and the result, empty if no values were found
+--- Example usage:
+---
+--- EVAL "local op = ARGV[3] local cursor = '0' local count = 0 local result = nil repeat local scan = redis.call('SCAN', cursor, 'MATCH', ARGV[1]) for _, key in ipairs(scan[2]) do if redis.call('TYPE', key).ok == 'hash' then local value = tonumber(redis.call('HGET', key, ARGV[2])) if value then count = count + 1 if op == 'sum' or op == 'avg' then result = (result or 0) + value elseif op == 'min' and (result == nil or value < result) then result = value elseif op == 'max' and (result == nil or value > result) then result = value end end end end cursor = scan[1] until (cursor == '0') if result == nil then return {count, ''} end if op == 'avg' then result = result / count end return {count, string.format('%.17g', result)}" 0 "Book_%&_*" rating avg
+---
+
+local op = ARGV[3]
+local cursor = '0'
+local count = 0
+local result = nil
+
+repeat
+    local scan = redis.call('SCAN', cursor, 'MATCH', ARGV[1])
+    for _, key in ipairs(scan[2]) do
+        if redis.call('TYPE', key).ok == 'hash' then
+            -- values that are not numbers e.g. None, are left out
+            local value = tonumber(redis.call('HGET', key, ARGV[2]))
+
+            if value then
+                count = count + 1
+                if op == 'sum' or op == 'avg' then
+                    result = (result or 0) + value
+                elseif op == 'min' and (result == nil or value < result) then
+                    result = value
+                elseif op == 'max' and (result == nil or value > result) then
+                    result = value
+                end
+            end
+        end
+    end
+    cursor = scan[1]
+until (cursor == '0')
+
+if result == nil then
+    return {count, ''}
+end
+
+if op == 'avg' then
+    result = result / count
+end
+
+-- numbers are returned as strings as redis would otherwise truncate them to integers
+return {count, string.format('%.17g', result)}
\ No newline at end of file
@@ -0,0 +1,36 @@
+---
+--- Script to count the ttls of the hashmaps in one SCAN batch of the keys matching a pattern, so that the audit of a large
+--- collection does not block redis. It returns the next cursor, the number of hashmaps, the number without a ttl,
+--- the number whose ttl is below the threshold, and the number whose ttl is below the imminent expiry
+--- ARGV has the cursor, the pattern, the SCAN count, the threshold and the imminent expiry, both in milliseconds
+--- Example usage:
+---
+--- EVAL "local result = redis.call('SCAN', ARGV[1], 'MATCH', ARGV[2], 'COUNT', ARGV[3]) local threshold = tonumber(ARGV[4]) local imminent = tonumber(ARGV[5]) local records = 0 local no_ttl = 0 local below_threshold = 0 local imminent_expiry = 0 for _, key in ipairs(result[2]) do if redis.call('TYPE', key).ok == 'hash' then records = records + 1 local ttl = redis.call('PTTL', key) if ttl == -1 then no_ttl = no_ttl + 1 elseif ttl >= 0 then if ttl < threshold then below_threshold = below_threshold + 1 end if ttl < imminent then imminent_expiry = imminent_expiry + 1 end end end end return {result[1], records, no_ttl, below_threshold, imminent_expiry}" 0 0 "Book_%&_*" 1000 3600000 60000
+---
+
+local result = redis.call('SCAN', ARGV[1], 'MATCH', ARGV[2], 'COUNT', ARGV[3])
+local threshold = tonumber(ARGV[4])
+local imminent = tonumber(ARGV[5])
+local records = 0
+local no_ttl = 0
+local below_threshold = 0
+local imminent_expiry = 0
+
+for _, key in ipairs(result[2]) do
+    if redis.call('TYPE', key).ok == 'hash' then
+        records = records + 1
+        local ttl = redis.call('PTTL', key)
+        if ttl == -1 then
+            no_ttl = no_ttl + 1
+        elseif ttl >= 0 then
+            if ttl < threshold then
+                below_threshold = below_threshold + 1
+            end
+            if ttl < imminent then
+                imminent_expiry = imminent_expiry + 1
+            end
+        end
+    end
+end
+
+return {result[1], records, no_ttl, below_threshold, imminent_expiry}
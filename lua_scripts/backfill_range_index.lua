@@ -0,0 +1,31 @@
+---
+--- Script to update the range index of a field at KEYS[2] and the set of the ids without a score at KEYS[3] for the
+--- record whose hashmap is at KEYS[1], from the value of the field read from the record earlier, while the index is
+--- built. The index is left as it is if the record has been deleted or its value of the field has changed since it was
+--- read, as the write that changed it updated the index itself.
+--- ARGV has the field, the id of the record, its score or an empty string if the value has no score, and the value
+--- read, which is left out if the record had no such field.
+--- It returns 1 if the index was updated, or else 0
+--- Example usage:
+---
+--- EVAL "if redis.call('EXISTS', KEYS[1]) == 0 then return 0 end if redis.call('HGET', KEYS[1], ARGV[1]) ~= (ARGV[4] or false) then return 0 end if ARGV[3] == '' then redis.call('ZREM', KEYS[2], ARGV[2]) redis.call('ZADD', KEYS[3], 0, ARGV[2]) else redis.call('ZADD', KEYS[2], ARGV[3], ARGV[2]) redis.call('ZREM', KEYS[3], ARGV[2]) end return 1" 3 "Book_%&_Oliver Twist" "Book_%&zset_%&_rating" "Book_%&unscored_%&_rating" rating "Oliver Twist" 2 2
+---
+
+if redis.call('EXISTS', KEYS[1]) == 0 then
+    return 0
+end
+
+-- HGET replies false if the record has no such field
+if redis.call('HGET', KEYS[1], ARGV[1]) ~= (ARGV[4] or false) then
+    return 0
+end
+
+if ARGV[3] == '' then
+    redis.call('ZREM', KEYS[2], ARGV[2])
+    redis.call('ZADD', KEYS[3], 0, ARGV[2])
+else
+    redis.call('ZADD', KEYS[2], ARGV[3], ARGV[2])
+    redis.call('ZREM', KEYS[3], ARGV[2])
+end
+
+return 1
\ No newline at end of file
@@ -0,0 +1,56 @@
+---
+--- Script to get a page of at most a given number of the keys of the hashmaps that have a given pattern, starting
+--- from a given SCAN cursor after skipping a given number of hashmaps of its batch, with the total number of such
+--- hashmaps. ARGV has the pattern, the SCAN cursor, the number of hashmaps to skip and the limit.
+--- It returns the cursor of the next page, empty if there is none, the total and the keys of the page
+--- Example usage:
+---
+--- EVAL "local cursor = ARGV[2] local skip = tonumber(ARGV[3]) local limit = tonumber(ARGV[4]) local page = {} local next_cursor = '' local total = 0 repeat local result = redis.call('SCAN', cursor, 'MATCH', ARGV[1], 'COUNT', limit) local hashes = {} for _, key in ipairs(result[2]) do if redis.call('TYPE', key).ok == 'hash' then table.insert(hashes, key) end end for i = skip + 1, #hashes do table.insert(page, hashes[i]) if #page == limit then if i < #hashes then next_cursor = cursor .. ':' .. i elseif result[1] ~= '0' then next_cursor = result[1] end break end end skip = 0 cursor = result[1] until (cursor == '0' or #page == limit) cursor = '0' repeat local result = redis.call('SCAN', cursor, 'MATCH', ARGV[1], 'COUNT', 1000) for _, key in ipairs(result[2]) do if redis.call('TYPE', key).ok == 'hash' then total = total + 1 end end cursor = result[1] until (cursor == '0') return {next_cursor, total, page}" 0 "Book_%&_*" 0 0 20
+---
+
+local cursor = ARGV[2]
+local skip = tonumber(ARGV[3])
+local limit = tonumber(ARGV[4])
+local page = {}
+local next_cursor = ''
+local total = 0
+
+repeat
+    local result = redis.call('SCAN', cursor, 'MATCH', ARGV[1], 'COUNT', limit)
+    local hashes = {}
+    for _, key in ipairs(result[2]) do
+        if redis.call('TYPE', key).ok == 'hash' then
+            table.insert(hashes, key)
+        end
+    end
+
+    for i = skip + 1, #hashes do
+        table.insert(page, hashes[i])
+        if #page == limit then
+            -- the rest of the batch is skipped when the page is resumed from the cursor of the batch
+            if i < #hashes then
+                next_cursor = cursor .. ':' .. i
+            elseif result[1] ~= '0' then
+                next_cursor = result[1]
+            end
+            break
+        end
+    end
+
+    skip = 0
+    cursor = result[1]
+until (cursor == '0' or #page == limit)
+
+-- the total is counted from the keys alone, without reading the records
+cursor = '0'
+repeat
+    local result = redis.call('SCAN', cursor, 'MATCH', ARGV[1], 'COUNT', 1000)
+    for _, key in ipairs(result[2]) do
+        if redis.call('TYPE', key).ok == 'hash' then
+            total = total + 1
+        end
+    end
+    cursor = result[1]
+until (cursor == '0')
+
+return {next_cursor, total, page}
\ No newline at end of file
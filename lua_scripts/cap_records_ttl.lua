@@ -0,0 +1,26 @@
+---
+--- Script to set the expiry of the hashmaps in one SCAN batch of the keys that have a given pattern to a given
+--- number of milliseconds if they have no expiry or their expiry is beyond that number of milliseconds.
+--- ARGV has the cursor, the SCAN count, the pattern and the number of milliseconds.
+--- It returns the next cursor, the number of keys scanned and the number of hashmaps whose expiry was set
+--- Example usage:
+---
+--- EVAL "local result = redis.call('SCAN', ARGV[1], 'MATCH', ARGV[3], 'COUNT', ARGV[2]) local max_ttl = tonumber(ARGV[4]) local capped = 0 for _, key in ipairs(result[2]) do if redis.call('TYPE', key).ok == 'hash' then local ttl = redis.call('PTTL', key) if ttl == -1 or ttl > max_ttl then redis.call('PEXPIRE', key, max_ttl) capped = capped + 1 end end end return {result[1], #result[2], capped}" 0 0 1000 "Session_%&_*" 2592000000
+---
+
+local result = redis.call('SCAN', ARGV[1], 'MATCH', ARGV[3], 'COUNT', ARGV[2])
+local max_ttl = tonumber(ARGV[4])
+local capped = 0
+
+for _, key in ipairs(result[2]) do
+    if redis.call('TYPE', key).ok == 'hash' then
+        local ttl = redis.call('PTTL', key)
+
+        if ttl == -1 or ttl > max_ttl then
+            redis.call('PEXPIRE', key, max_ttl)
+            capped = capped + 1
+        end
+    end
+end
+
+return {result[1], #result[2], capped}
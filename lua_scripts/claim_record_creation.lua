@@ -0,0 +1,18 @@
+---
+--- Script to claim the creation of the record at KEYS[1], setting the claim at KEYS[2] if the record does not exist and
+--- no other creation of it is claimed, so that of many concurrent creations of the same record only one goes ahead. The
+--- claim expires after the given time in case its creator stops before releasing it.
+--- ARGV has the time in milliseconds after which the claim expires. It returns 1 if the creation is claimed, else 0
+--- Example usage:
+---
+--- EVAL "if redis.call('EXISTS', KEYS[1]) == 1 then return 0 end if redis.call('SET', KEYS[2], '1', 'NX', 'PX', ARGV[1]) then return 1 end return 0" 2 "Book_%&_Emma" "Book_%&claim_%&_Emma" 10000
+---
+
+if redis.call('EXISTS', KEYS[1]) == 1 then
+    return 0
+end
+
+if redis.call('SET', KEYS[2], '1', 'NX', 'PX', ARGV[1]) then
+    return 1
+end
+return 0
\ No newline at end of file
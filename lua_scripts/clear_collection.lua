@@ -0,0 +1,44 @@
+---
+--- Script to remove the keys of a collection in one SCAN batch of the keys that have the given pattern, i.e. its
+--- records and the keys derived from them, except those that start with any of the given prefixes of the keys to keep.
+--- The usage of the quota of the namespace of the collection, if it has one, is released for the records removed.
+--- ARGV has the cursor, the SCAN count, the pattern, the key prefix of the records, the key of the usage of the quota
+--- or "", then the prefixes of the keys to keep. It returns the next cursor, the number of keys scanned and the number
+--- of records removed
+--- Example usage:
+---
+--- EVAL "local result = redis.call('SCAN', ARGV[1], 'MATCH', ARGV[3], 'COUNT', ARGV[2]) local removed, bytes = 0, 0 for _, key in ipairs(result[2]) do local kept = false for i = 6, #ARGV do if string.sub(key, 1, #ARGV[i]) == ARGV[i] then kept = true break end end if not kept then if string.sub(key, 1, #ARGV[4]) == ARGV[4] and redis.call('TYPE', key).ok == 'hash' then removed = removed + 1 if ARGV[5] ~= '' then local fields = redis.call('HGETALL', key) for j = 1, #fields do bytes = bytes + #fields[j] end end end redis.call('DEL', key) end end if ARGV[5] ~= '' and removed > 0 then redis.call('HINCRBY', ARGV[5], 'records', -removed) redis.call('HINCRBY', ARGV[5], 'bytes', -bytes) end return {result[1], #result[2], removed}" 0 0 1000 "Book_%&*" "Book_%&_" "" "Book_%&snapshot_%&_" "Book_%&changes_%&_stream" "Book_%&schema_%&_snapshot" "Book_%&compression_%&_dictionaries"
+---
+
+local result = redis.call('SCAN', ARGV[1], 'MATCH', ARGV[3], 'COUNT', ARGV[2])
+local removed, bytes = 0, 0
+
+for _, key in ipairs(result[2]) do
+    local kept = false
+    for i = 6, #ARGV do
+        if string.sub(key, 1, #ARGV[i]) == ARGV[i] then
+            kept = true
+            break
+        end
+    end
+
+    if not kept then
+        if string.sub(key, 1, #ARGV[4]) == ARGV[4] and redis.call('TYPE', key).ok == 'hash' then
+            removed = removed + 1
+            if ARGV[5] ~= '' then
+                local fields = redis.call('HGETALL', key)
+                for j = 1, #fields do
+                    bytes = bytes + #fields[j]
+                end
+            end
+        end
+        redis.call('DEL', key)
+    end
+end
+
+if ARGV[5] ~= '' and removed > 0 then
+    redis.call('HINCRBY', ARGV[5], 'records', -removed)
+    redis.call('HINCRBY', ARGV[5], 'bytes', -bytes)
+end
+
+return {result[1], #result[2], removed}
\ No newline at end of file
@@ -0,0 +1,16 @@
+---
+--- Script to remove the keys of a view in one SCAN batch of the keys that have a given pattern.
+--- ARGV has the cursor, the SCAN count and the pattern of the keys of the view.
+--- It returns the next cursor, the number of keys scanned and the number of keys removed
+--- Example usage:
+---
+--- EVAL "local result = redis.call('SCAN', ARGV[1], 'MATCH', ARGV[3], 'COUNT', ARGV[2]) for _, key in ipairs(result[2]) do redis.call('DEL', key) end return {result[1], #result[2], #result[2]}" 0 0 1000 "Book_%&view_%&_books_in_stock_%&_*"
+---
+
+local result = redis.call('SCAN', ARGV[1], 'MATCH', ARGV[3], 'COUNT', ARGV[2])
+
+for _, key in ipairs(result[2]) do
+    redis.call('DEL', key)
+end
+
+return {result[1], #result[2], #result[2]}
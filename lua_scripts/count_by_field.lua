@@ -0,0 +1,38 @@
+---
+--- Script to count the hashmaps that have a given pattern by the values of a given field, hashmaps without
+--- the field being left out.
+--- ARGV has the pattern and the field.
+--- It returns each value followed by its count, in the order the values are first found
+--- Example usage:
+---
+--- EVAL "local counts = {} local values = {} local cursor = '0' repeat local result = redis.call('SCAN', cursor, 'MATCH', ARGV[1]) for _, key in ipairs(result[2]) do if redis.call('TYPE', key).ok == 'hash' then local value = redis.call('HGET', key, ARGV[2]) if value then if not counts[value] then counts[value] = 0 table.insert(values, value) end counts[value] = counts[value] + 1 end end end cursor = result[1] until (cursor == '0') local grouped = {} for _, value in ipairs(values) do table.insert(grouped, value) table.insert(grouped, counts[value]) end return grouped" 0 "Book_%&_*" in_stock
+---
+
+local counts = {}
+local values = {}
+local cursor = '0'
+
+repeat
+    local result = redis.call('SCAN', cursor, 'MATCH', ARGV[1])
+    for _, key in ipairs(result[2]) do
+        if redis.call('TYPE', key).ok == 'hash' then
+            local value = redis.call('HGET', key, ARGV[2])
+
+            if value then
+                if not counts[value] then
+                    counts[value] = 0
+                    table.insert(values, value)
+                end
+                counts[value] = counts[value] + 1
+            end
+        end
+    end
+    cursor = result[1]
+until (cursor == '0')
+
+local grouped = {}
+for _, value in ipairs(values) do
+    table.insert(grouped, value)
+    table.insert(grouped, counts[value])
+end
+return grouped
\ No newline at end of file
@@ -0,0 +1,46 @@
+---
+--- Script to count the records of a collection whose fields are equal to the given values, scanning all the keys that
+--- match the pattern of the keys of the collection. All the records are counted if no values are given.
+--- ARGV has the key pattern of the collection, then each field of the filter followed by its value
+--- Example usage:
+---
+--- EVAL "local table_unpack = table.unpack or unpack local count = 0 local seen = {} local cursor = '0' local filter_fields = {} local filter_values = {} for i = 2, #ARGV, 2 do table.insert(filter_fields, ARGV[i]) table.insert(filter_values, ARGV[i + 1]) end repeat local result = redis.call('SCAN', cursor, 'MATCH', ARGV[1]) for _, key in ipairs(result[2]) do if not seen[key] and redis.call('TYPE', key).ok == 'hash' then seen[key] = true local matches = true if #filter_fields > 0 then local values = redis.call('HMGET', key, table_unpack(filter_fields)) for i, v in ipairs(filter_values) do if values[i] ~= v then matches = false break end end end if matches then count = count + 1 end end end cursor = result[1] until (cursor == '0') return count" 0 "Book_%&_*" author "Jane Austen" in_stock true
+---
+
+local table_unpack = table.unpack or unpack
+local count = 0
+local seen = {}
+local cursor = '0'
+
+local filter_fields = {}
+local filter_values = {}
+for i = 2, #ARGV, 2 do
+    table.insert(filter_fields, ARGV[i])
+    table.insert(filter_values, ARGV[i + 1])
+end
+
+repeat
+    local result = redis.call('SCAN', cursor, 'MATCH', ARGV[1])
+    for _, key in ipairs(result[2]) do
+        if not seen[key] and redis.call('TYPE', key).ok == 'hash' then
+            seen[key] = true
+            local matches = true
+            if #filter_fields > 0 then
+                local values = redis.call('HMGET', key, table_unpack(filter_fields))
+                for i, v in ipairs(filter_values) do
+                    if values[i] ~= v then
+                        matches = false
+                        break
+                    end
+                end
+            end
+
+            if matches then
+                count = count + 1
+            end
+        end
+    end
+    cursor = result[1]
+until (cursor == '0')
+
+return count
\ No newline at end of file
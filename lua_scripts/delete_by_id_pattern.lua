@@ -0,0 +1,61 @@
+---
+--- Script to find the records of a collection whose ids match a glob pattern in one SCAN batch of the keys that match
+--- the pattern of their keys, and to unlink them with the keys related to them if asked to. The related keys are those
+--- of their counters, copies in views and packed vectors, made of a prefix and the id, and their entries in the range
+--- indexes and the insertion order index are removed. A delete event is added to the change stream of the collection
+--- for each record deleted, if it has one.
+--- ARGV has the cursor, the SCAN count, the key pattern of the records to find, the key prefix of the collection, "1"
+--- to delete the records or "0" to only find them, the key of the change stream or "", the maximum length of the
+--- stream, the number of related key prefixes followed by the prefixes, then the number of sorted sets followed by
+--- their keys. It returns the next cursor, the number of keys scanned and the ids of the records found
+--- Example usage:
+---
+--- EVAL "local table_unpack = table.unpack or unpack local result = redis.call('SCAN', ARGV[1], 'MATCH', ARGV[3], 'COUNT', ARGV[2]) local i = 8 local n = tonumber(ARGV[i]) local related = {} for j = i + 1, i + n do table.insert(related, ARGV[j]) end i = i + n + 1 n = tonumber(ARGV[i]) local zsets = {} for j = i + 1, i + n do table.insert(zsets, ARGV[j]) end local matched = {} for _, key in ipairs(result[2]) do if redis.call('TYPE', key).ok == 'hash' then local id = string.sub(key, #ARGV[4] + 1) table.insert(matched, id) if ARGV[5] == '1' then if ARGV[6] ~= '' then local before = redis.call('HGETALL', key) local entry = {'op', 'delete', 'id', id} for k = 1, #before, 2 do table.insert(entry, 'before.' .. before[k]) table.insert(entry, before[k + 1]) end redis.call('XADD', ARGV[6], 'MAXLEN', '~', ARGV[7], '*', table_unpack(entry)) end for _, prefix in ipairs(related) do redis.call('UNLINK', prefix .. id) end for _, zset in ipairs(zsets) do redis.call('ZREM', zset, id) end redis.call('UNLINK', key) end end end return {result[1], #result[2], matched}" 0 0 1000 "Order_%&_order-2023-*" "Order_%&_" 1 "" 100000 0 0
+---
+
+local table_unpack = table.unpack or unpack
+local result = redis.call('SCAN', ARGV[1], 'MATCH', ARGV[3], 'COUNT', ARGV[2])
+
+local i = 8
+local n = tonumber(ARGV[i])
+local related = {}
+for j = i + 1, i + n do
+    table.insert(related, ARGV[j])
+end
+i = i + n + 1
+
+n = tonumber(ARGV[i])
+local zsets = {}
+for j = i + 1, i + n do
+    table.insert(zsets, ARGV[j])
+end
+
+local matched = {}
+for _, key in ipairs(result[2]) do
+    if redis.call('TYPE', key).ok == 'hash' then
+        local id = string.sub(key, #ARGV[4] + 1)
+        table.insert(matched, id)
+
+        if ARGV[5] == '1' then
+            if ARGV[6] ~= '' then
+                local before = redis.call('HGETALL', key)
+                local entry = {'op', 'delete', 'id', id}
+                for k = 1, #before, 2 do
+                    table.insert(entry, 'before.' .. before[k])
+                    table.insert(entry, before[k + 1])
+                end
+                redis.call('XADD', ARGV[6], 'MAXLEN', '~', ARGV[7], '*', table_unpack(entry))
+            end
+
+            for _, prefix in ipairs(related) do
+                redis.call('UNLINK', prefix .. id)
+            end
+            for _, zset in ipairs(zsets) do
+                redis.call('ZREM', zset, id)
+            end
+            redis.call('UNLINK', key)
+        end
+    end
+end
+
+return {result[1], #result[2], matched}
\ No newline at end of file
@@ -0,0 +1,86 @@
+---
+--- Script to find the records of a collection whose fields are equal to the given values, scanning all the keys that
+--- match the pattern of the keys of the collection, and to delete them with the keys related to them if asked to. The
+--- related keys are those of their counters, copies in views and packed vectors, made of a prefix and the id, and their
+--- entries in the range indexes and the insertion order index are removed. A delete event is added to the change stream
+--- of the collection for each record deleted, if it has one.
+--- ARGV has the key pattern of the collection, its key prefix, "1" to delete the records or "0" to only find them, the
+--- key of the change stream or "", the maximum length of the stream, the number of related key prefixes followed by the
+--- prefixes, the number of sorted sets followed by their keys, then each field of the filter followed by its value. It
+--- returns the ids of the records found
+--- Example usage:
+---
+--- EVAL "local table_unpack = table.unpack or unpack local matched = {} local seen = {} local cursor = '0' local i = 6 local n = tonumber(ARGV[i]) local related = {} for j = i + 1, i + n do table.insert(related, ARGV[j]) end i = i + n + 1 n = tonumber(ARGV[i]) local zsets = {} for j = i + 1, i + n do table.insert(zsets, ARGV[j]) end i = i + n + 1 local filter_fields = {} local filter_values = {} for j = i, #ARGV, 2 do table.insert(filter_fields, ARGV[j]) table.insert(filter_values, ARGV[j + 1]) end repeat local result = redis.call('SCAN', cursor, 'MATCH', ARGV[1]) for _, key in ipairs(result[2]) do if not seen[key] and redis.call('TYPE', key).ok == 'hash' then seen[key] = true local values = redis.call('HMGET', key, table_unpack(filter_fields)) local matches = true for k, v in ipairs(filter_values) do if values[k] ~= v then matches = false break end end if matches then local id = string.sub(key, #ARGV[2] + 1) table.insert(matched, id) if ARGV[3] == '1' then if ARGV[4] ~= '' then local before = redis.call('HGETALL', key) local entry = {'op', 'delete', 'id', id} for k = 1, #before, 2 do table.insert(entry, 'before.' .. before[k]) table.insert(entry, before[k + 1]) end redis.call('XADD', ARGV[4], 'MAXLEN', '~', ARGV[5], '*', table_unpack(entry)) end for _, prefix in ipairs(related) do redis.call('DEL', prefix .. id) end for _, zset in ipairs(zsets) do redis.call('ZREM', zset, id) end redis.call('DEL', key) end end end end cursor = result[1] until (cursor == '0') return matched" 0 "Book_%&_*" "Book_%&_" 1 "" 100000 0 1 "Book_%&zset_%&_rating" author "Jane Austen"
+---
+
+local table_unpack = table.unpack or unpack
+local matched = {}
+local seen = {}
+local cursor = '0'
+
+local i = 6
+local n = tonumber(ARGV[i])
+local related = {}
+for j = i + 1, i + n do
+    table.insert(related, ARGV[j])
+end
+i = i + n + 1
+
+n = tonumber(ARGV[i])
+local zsets = {}
+for j = i + 1, i + n do
+    table.insert(zsets, ARGV[j])
+end
+i = i + n + 1
+
+local filter_fields = {}
+local filter_values = {}
+for j = i, #ARGV, 2 do
+    table.insert(filter_fields, ARGV[j])
+    table.insert(filter_values, ARGV[j + 1])
+end
+
+repeat
+    local result = redis.call('SCAN', cursor, 'MATCH', ARGV[1])
+    for _, key in ipairs(result[2]) do
+        if not seen[key] and redis.call('TYPE', key).ok == 'hash' then
+            seen[key] = true
+            local values = redis.call('HMGET', key, table_unpack(filter_fields))
+            local matches = true
+            for k, v in ipairs(filter_values) do
+                if values[k] ~= v then
+                    matches = false
+                    break
+                end
+            end
+
+            if matches then
+                local id = string.sub(key, #ARGV[2] + 1)
+                table.insert(matched, id)
+
+                if ARGV[3] == '1' then
+                    if ARGV[4] ~= '' then
+                        local before = redis.call('HGETALL', key)
+                        local entry = {'op', 'delete', 'id', id}
+                        for k = 1, #before, 2 do
+                            table.insert(entry, 'before.' .. before[k])
+                            table.insert(entry, before[k + 1])
+                        end
+                        redis.call('XADD', ARGV[4], 'MAXLEN', '~', ARGV[5], '*', table_unpack(entry))
+                    end
+
+                    for _, prefix in ipairs(related) do
+                        redis.call('DEL', prefix .. id)
+                    end
+                    for _, zset in ipairs(zsets) do
+                        redis.call('ZREM', zset, id)
+                    end
+                    redis.call('DEL', key)
+                end
+            end
+        end
+    end
+    cursor = result[1]
+until (cursor == '0')
+
+return matched
\ No newline at end of file
@@ -0,0 +1,14 @@
+---
+--- Script to set the expiry of the reservations of the normalized values of the unique fields of a record, whose keys
+--- are made of the reservation key prefix, the field, "_%&_" and the normalized value. `normalize(field, value)` is
+--- defined by the normalizers of the collection, prepended to the script.
+--- ARGV has the reservation key prefix, the expiry command e.g. "PEXPIRE" and its time, then each field followed by its
+--- value
+--- Example usage:
+---
+--- EVAL "local function normalize(field, value) return value end for i = 4, #ARGV, 2 do redis.call(ARGV[2], ARGV[1] .. ARGV[i] .. '_%&_' .. normalize(ARGV[i], ARGV[i + 1]), ARGV[3]) end" 0 "User_%&unique_%&_" PEXPIRE 60000 email Ann@Example.com
+---
+
+for i = 4, #ARGV, 2 do
+    redis.call(ARGV[2], ARGV[1] .. ARGV[i] .. '_%&_' .. normalize(ARGV[i], ARGV[i + 1]), ARGV[3])
+end
\ No newline at end of file
@@ -0,0 +1,16 @@
+---
+--- Script to get the ids of the records whose indexed fields have the given values once they are normalized, from the
+--- set at the key made of the index key prefix and the normalized values joined by "_%&_". `normalize(field, value)` is
+--- defined by the normalizers of the collection, prepended to the script.
+--- ARGV has the index key prefix, made of the fields of the index joined by ",", then each field followed by its value
+--- Example usage:
+---
+--- EVAL "local function normalize(field, value) return value end local values = {} for i = 2, #ARGV, 2 do table.insert(values, normalize(ARGV[i], ARGV[i + 1])) end return redis.call('SMEMBERS', ARGV[1] .. table.concat(values, '_%&_'))" 0 "User_%&index_%&_email_%&_" email Ann@Example.com
+---
+
+local values = {}
+for i = 2, #ARGV, 2 do
+    table.insert(values, normalize(ARGV[i], ARGV[i + 1]))
+end
+
+return redis.call('SMEMBERS', ARGV[1] .. table.concat(values, '_%&_'))
\ No newline at end of file
@@ -0,0 +1,112 @@
+---
+--- Script to get the ids of a page of the records of a collection in the order of the range index of a field at
+--- KEYS[1], followed by the ids of the records without a score in the set at KEYS[2]. Records with the same score are
+--- in the order of their ids, even when the order is descending.
+--- ARGV has the prefix of the keys of the records, '1' if the order is descending or else '0', the number of records to
+--- skip and the maximum number of records to return, or -1 for all of them, followed by the pairs of fields and values
+--- the records should have. Without such pairs, the page is read straight from the index, or else the index is walked
+--- till the page is full.
+--- It returns the list of ids
+--- Example usage:
+---
+--- EVAL "local table_unpack = table.unpack or unpack local desc = ARGV[2] == '1' local skip, limit = tonumber(ARGV[3]), tonumber(ARGV[4]) local ids = {} if limit == 0 then return ids end local function scored_range(first, stop) if not desc then return redis.call('ZRANGE', KEYS[1], first, stop) end local scored = redis.call('ZCARD', KEYS[1]) if stop < 0 or stop >= scored then stop = scored - 1 end local range = {} local rank = first while rank <= stop do local score = redis.call('ZREVRANGE', KEYS[1], rank, rank, 'WITHSCORES')[2] local before = redis.call('ZCOUNT', KEYS[1], '(' .. score, '+inf') local ties = redis.call('ZRANGEBYSCORE', KEYS[1], score, score, 'LIMIT', rank - before, stop - rank + 1) if #ties == 0 then break end for _, id in ipairs(ties) do table.insert(range, id) end rank = rank + #ties end return range end local function unscored_range(first, stop) return redis.call('ZRANGE', KEYS[2], first, stop) end if #ARGV == 4 then local scored = redis.call('ZCARD', KEYS[1]) local last = limit > 0 and skip + limit - 1 or -1 if skip < scored then ids = scored_range(skip, last >= 0 and math.min(last, scored - 1) or -1) end if last < 0 or last >= scored then local stop = last >= 0 and last - scored or -1 for _, id in ipairs(unscored_range(math.max(skip - scored, 0), stop)) do table.insert(ids, id) end end return ids end local fields, values = {}, {} for i = 5, #ARGV, 2 do table.insert(fields, ARGV[i]) table.insert(values, ARGV[i + 1]) end local matched = 0 for _, range in ipairs({ scored_range, unscored_range }) do local start = 0 repeat local batch = range(start, start + 999) for _, id in ipairs(batch) do local record = redis.call('HMGET', ARGV[1] .. id, table_unpack(fields)) local matches = true for i, v in ipairs(values) do if record[i] ~= v then matches = false break end end if matches then matched = matched + 1 if matched > skip then table.insert(ids, id) if #ids == limit then return ids end end end end start = start + 1000 until #batch < 1000 end return ids" 2 "Book_%&zset_%&_rating" "Book_%&unscored_%&_rating" "Book_%&_" 1 0 10 author "Charles Dickens"
+---
+
+local table_unpack = table.unpack or unpack
+local desc = ARGV[2] == '1'
+local skip, limit = tonumber(ARGV[3]), tonumber(ARGV[4])
+local ids = {}
+
+if limit == 0 then
+    return ids
+end
+
+-- the ids ranked first to stop in the index, the ties in a descending order being read in ascending order
+local function scored_range(first, stop)
+    if not desc then
+        return redis.call('ZRANGE', KEYS[1], first, stop)
+    end
+
+    local scored = redis.call('ZCARD', KEYS[1])
+    if stop < 0 or stop >= scored then
+        stop = scored - 1
+    end
+
+    local range = {}
+    local rank = first
+    while rank <= stop do
+        local score = redis.call('ZREVRANGE', KEYS[1], rank, rank, 'WITHSCORES')[2]
+        -- the number of ids with greater scores, ranked before the ids with this score
+        local before = redis.call('ZCOUNT', KEYS[1], '(' .. score, '+inf')
+        local ties = redis.call('ZRANGEBYSCORE', KEYS[1], score, score, 'LIMIT', rank - before, stop - rank + 1)
+        if #ties == 0 then
+            break
+        end
+
+        for _, id in ipairs(ties) do
+            table.insert(range, id)
+        end
+        rank = rank + #ties
+    end
+
+    return range
+end
+
+local function unscored_range(first, stop)
+    return redis.call('ZRANGE', KEYS[2], first, stop)
+end
+
+if #ARGV == 4 then
+    local scored = redis.call('ZCARD', KEYS[1])
+    local last = limit > 0 and skip + limit - 1 or -1
+
+    if skip < scored then
+        ids = scored_range(skip, last >= 0 and math.min(last, scored - 1) or -1)
+    end
+
+    if last < 0 or last >= scored then
+        local stop = last >= 0 and last - scored or -1
+        for _, id in ipairs(unscored_range(math.max(skip - scored, 0), stop)) do
+            table.insert(ids, id)
+        end
+    end
+
+    return ids
+end
+
+local fields, values = {}, {}
+for i = 5, #ARGV, 2 do
+    table.insert(fields, ARGV[i])
+    table.insert(values, ARGV[i + 1])
+end
+
+local matched = 0
+for _, range in ipairs({ scored_range, unscored_range }) do
+    local start = 0
+    repeat
+        local batch = range(start, start + 999)
+        for _, id in ipairs(batch) do
+            local record = redis.call('HMGET', ARGV[1] .. id, table_unpack(fields))
+            local matches = true
+            for i, v in ipairs(values) do
+                if record[i] ~= v then
+                    matches = false
+                    break
+                end
+            end
+
+            if matches then
+                matched = matched + 1
+                if matched > skip then
+                    table.insert(ids, id)
+                    if #ids == limit then
+                        return ids
+                    end
+                end
+            end
+        end
+        start = start + 1000
+    until #batch < 1000
+end
+
+return ids
\ No newline at end of file
@@ -0,0 +1,30 @@
+---
+--- Script to create a bloom filter at the given key if it does not exist yet, adding to it the keys of
+--- all hashmaps that have a given pattern. It returns 0 if the RedisBloom module is not loaded else 1
+--- Example usage:
+---
+--- EVAL "if redis.call('EXISTS', KEYS[1]) == 1 then return 1 end local reserved = redis.pcall('BF.RESERVE', KEYS[1], ARGV[2], ARGV[3]) if type(reserved) == 'table' and reserved.err then return 0 end local cursor = '0' repeat local result = redis.call('SCAN', cursor, 'MATCH', ARGV[1]) for _, key in ipairs(result[2]) do if redis.call('TYPE', key).ok == 'hash' then redis.call('BF.ADD', KEYS[1], key) end end cursor = result[1] until (cursor == '0') return 1" 1 "Book_%&bloom_%&_ids" "Book_%&_*" 0.001 10000
+---
+
+if redis.call('EXISTS', KEYS[1]) == 1 then
+    return 1
+end
+
+local reserved = redis.pcall('BF.RESERVE', KEYS[1], ARGV[2], ARGV[3])
+if type(reserved) == 'table' and reserved.err then
+    return 0
+end
+
+local cursor = '0'
+
+repeat
+    local result = redis.call('SCAN', cursor, 'MATCH', ARGV[1])
+    for _, key in ipairs(result[2]) do
+        if redis.call('TYPE', key).ok == 'hash' then
+            redis.call('BF.ADD', KEYS[1], key)
+        end
+    end
+    cursor = result[1]
+until (cursor == '0')
+
+return 1
@@ -0,0 +1,41 @@
+---
+--- Script to get the ids of a page of the records after a keyset cursor, in the order of their values of a field, from
+--- the range index of the field at KEYS[1], a sorted set of the ids scored by their values, where ties are ordered by
+--- id. The page starts after the ids whose scores are below the score of the cursor, or equal to it with ids that do
+--- not sort after the id of the cursor, found by a binary search among the ids of that score.
+--- ARGV has the score of the cursor, or "" to start from the first record, the id of the cursor, or "" to start after
+--- all the records of its score, and the number of ids in the page
+--- Example usage:
+---
+--- EVAL "local function sorts_after(a, b) for i = 1, math.min(#a, #b) do local x, y = string.byte(a, i), string.byte(b, i) if x ~= y then return x > y end end return #a > #b end local first = 0 if ARGV[1] ~= '' then first = redis.call('ZCOUNT', KEYS[1], '-inf', ARGV[1]) end if ARGV[2] ~= '' then local last = first first = redis.call('ZCOUNT', KEYS[1], '-inf', '(' .. ARGV[1]) while first < last do local middle = math.floor((first + last) / 2) if sorts_after(redis.call('ZRANGE', KEYS[1], middle, middle)[1], ARGV[2]) then last = middle else first = middle + 1 end end end return redis.call('ZRANGE', KEYS[1], first, first + tonumber(ARGV[3]) - 1)" 1 "Book_%&zset_%&_rating" 4 "Emma" 10
+---
+
+local function sorts_after(a, b)
+    for i = 1, math.min(#a, #b) do
+        local x, y = string.byte(a, i), string.byte(b, i)
+        if x ~= y then
+            return x > y
+        end
+    end
+    return #a > #b
+end
+
+local first = 0
+if ARGV[1] ~= '' then
+    first = redis.call('ZCOUNT', KEYS[1], '-inf', ARGV[1])
+end
+
+if ARGV[2] ~= '' then
+    local last = first
+    first = redis.call('ZCOUNT', KEYS[1], '-inf', '(' .. ARGV[1])
+    while first < last do
+        local middle = math.floor((first + last) / 2)
+        if sorts_after(redis.call('ZRANGE', KEYS[1], middle, middle)[1], ARGV[2]) then
+            last = middle
+        else
+            first = middle + 1
+        end
+    end
+end
+
+return redis.call('ZRANGE', KEYS[1], first, first + tonumber(ARGV[3]) - 1)
\ No newline at end of file
@@ -0,0 +1,17 @@
+---
+--- Script defining `normalize(field, value)`, prepended to the scripts of the indexes of a collection with normalizers,
+--- after the `normalizers` table of the lua function of each normalized field. The value of a field without a
+--- normalizer, or a missing value, is returned as is. Collections without normalizers get the identity function instead
+--- i.e. "local function normalize(field, value) return value end "
+--- Example usage:
+---
+--- EVAL "local normalizers = {} normalizers[\"email\"] = function(value) value = string.lower(value) return value end local function normalize(field, value) local f = normalizers[field] if f and value then return (f(value)) end return value end return normalize(ARGV[1], ARGV[2])" 0 email Ann@Example.com
+---
+
+local function normalize(field, value)
+    local f = normalizers[field]
+    if f and value then
+        return (f(value))
+    end
+    return value
+end
\ No newline at end of file
@@ -0,0 +1,54 @@
+---
+--- Script to copy into a view the hashmaps in one SCAN batch of the keys that have a given pattern. Each hashmap
+--- that matches the filter is copied with only the projected fields, taking on its expiry.
+--- The keys of the view are first removed with clear_view.lua.
+--- ARGV has the cursor, the SCAN count, the pattern of the collection, the key prefix of the collection, the key prefix
+--- of the view, the number of projected fields, the projected fields, then the filter as field, value pairs.
+--- It returns the next cursor, the number of keys scanned and the number of records copied
+--- Example usage:
+---
+--- EVAL "local table_unpack = table.unpack or unpack local n = tonumber(ARGV[6]) local result = redis.call('SCAN', ARGV[1], 'MATCH', ARGV[3], 'COUNT', ARGV[2]) local count = 0 for _, key in ipairs(result[2]) do if redis.call('TYPE', key).ok == 'hash' then local matches = true local i = n + 7 while matches and i < #ARGV do if redis.call('HGET', key, ARGV[i]) ~= ARGV[i + 1] then matches = false end i = i + 2 end if matches then local args = {} for j = 7, n + 6 do local v = redis.call('HGET', key, ARGV[j]) if v then table.insert(args, ARGV[j]) table.insert(args, v) end end if #args > 0 then local view_key = ARGV[5] .. string.sub(key, #ARGV[4] + 1) redis.call('HSET', view_key, table_unpack(args)) local ttl = redis.call('PTTL', key) if ttl > 0 then redis.call('PEXPIRE', view_key, ttl) end count = count + 1 end end end end return {result[1], #result[2], count}" 0 0 1000 "Book_%&_*" "Book_%&_" "Book_%&view_%&_books_in_stock_%&_" 2 title rating in_stock true
+---
+
+local table_unpack = table.unpack or unpack
+local n = tonumber(ARGV[6])
+local result = redis.call('SCAN', ARGV[1], 'MATCH', ARGV[3], 'COUNT', ARGV[2])
+local count = 0
+
+for _, key in ipairs(result[2]) do
+    if redis.call('TYPE', key).ok == 'hash' then
+        local matches = true
+        local i = n + 7
+
+        while matches and i < #ARGV do
+            if redis.call('HGET', key, ARGV[i]) ~= ARGV[i + 1] then
+                matches = false
+            end
+            i = i + 2
+        end
+
+        if matches then
+            local args = {}
+            for j = 7, n + 6 do
+                local v = redis.call('HGET', key, ARGV[j])
+                if v then
+                    table.insert(args, ARGV[j])
+                    table.insert(args, v)
+                end
+            end
+
+            if #args > 0 then
+                local view_key = ARGV[5] .. string.sub(key, #ARGV[4] + 1)
+                redis.call('HSET', view_key, table_unpack(args))
+
+                local ttl = redis.call('PTTL', key)
+                if ttl > 0 then
+                    redis.call('PEXPIRE', view_key, ttl)
+                end
+                count = count + 1
+            end
+        end
+    end
+end
+
+return {result[1], #result[2], count}
@@ -0,0 +1,30 @@
+---
+--- Script to set the given fields of the hashmap at KEYS[1] and record the change in the stream at KEYS[2]
+--- with the fields of the hashmap before and after the change, prefixed with "before." and "after." respectively.
+--- ARGV has the id of the record, the approximate maximum length of the stream, then the field, value pairs
+--- Example usage:
+---
+--- EVAL "local table_unpack = table.unpack or unpack local before = redis.call('HGETALL', KEYS[1]) redis.call('HSET', KEYS[1], table_unpack(ARGV, 3)) local after = redis.call('HGETALL', KEYS[1]) local op = 'update' if #before == 0 then op = 'insert' end local entry = {'op', op, 'id', ARGV[1]} for i = 1, #before, 2 do table.insert(entry, 'before.' .. before[i]) table.insert(entry, before[i + 1]) end for i = 1, #after, 2 do table.insert(entry, 'after.' .. after[i]) table.insert(entry, after[i + 1]) end return redis.call('XADD', KEYS[2], 'MAXLEN', '~', ARGV[2], '*', table_unpack(entry))" 2 "Task_%&_foo" "Task_%&changes_%&_stream" foo 100000 name foo done true
+---
+
+local table_unpack = table.unpack or unpack
+local before = redis.call('HGETALL', KEYS[1])
+redis.call('HSET', KEYS[1], table_unpack(ARGV, 3))
+local after = redis.call('HGETALL', KEYS[1])
+
+local op = 'update'
+if #before == 0 then
+    op = 'insert'
+end
+
+local entry = { 'op', op, 'id', ARGV[1] }
+for i = 1, #before, 2 do
+    table.insert(entry, 'before.' .. before[i])
+    table.insert(entry, before[i + 1])
+end
+for i = 1, #after, 2 do
+    table.insert(entry, 'after.' .. after[i])
+    table.insert(entry, after[i + 1])
+end
+
+return redis.call('XADD', KEYS[2], 'MAXLEN', '~', ARGV[2], '*', table_unpack(entry))
@@ -0,0 +1,24 @@
+---
+--- Script to delete the hashmap at KEYS[1] if it exists and record the deletion in the stream at KEYS[2]
+--- with the fields of the hashmap before the deletion, prefixed with "before."
+--- ARGV has the id of the record and the approximate maximum length of the stream
+--- Example usage:
+---
+--- EVAL "local table_unpack = table.unpack or unpack local before = redis.call('HGETALL', KEYS[1]) if #before == 0 then return false end redis.call('DEL', KEYS[1]) local entry = {'op', 'delete', 'id', ARGV[1]} for i = 1, #before, 2 do table.insert(entry, 'before.' .. before[i]) table.insert(entry, before[i + 1]) end return redis.call('XADD', KEYS[2], 'MAXLEN', '~', ARGV[2], '*', table_unpack(entry))" 2 "Task_%&_foo" "Task_%&changes_%&_stream" foo 100000
+---
+
+local table_unpack = table.unpack or unpack
+local before = redis.call('HGETALL', KEYS[1])
+if #before == 0 then
+    return false
+end
+
+redis.call('DEL', KEYS[1])
+
+local entry = { 'op', 'delete', 'id', ARGV[1] }
+for i = 1, #before, 2 do
+    table.insert(entry, 'before.' .. before[i])
+    table.insert(entry, before[i + 1])
+end
+
+return redis.call('XADD', KEYS[2], 'MAXLEN', '~', ARGV[2], '*', table_unpack(entry))
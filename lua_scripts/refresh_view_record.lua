@@ -0,0 +1,47 @@
+---
+--- Script to refresh the copy of a record in a view. The copy, at KEYS[2], is removed and if the record at KEYS[1]
+--- exists and matches the filter, it is copied with only the projected fields, taking on the record's expiry.
+--- ARGV has the number of projected fields, the projected fields, then the filter as field, value pairs
+--- Example usage:
+---
+--- EVAL "local table_unpack = table.unpack or unpack local n = tonumber(ARGV[1]) local matches = redis.call('EXISTS', KEYS[1]) == 1 local i = n + 2 while matches and i < #ARGV do if redis.call('HGET', KEYS[1], ARGV[i]) ~= ARGV[i + 1] then matches = false end i = i + 2 end redis.call('DEL', KEYS[2]) if not matches then return 0 end local args = {} for j = 2, n + 1 do local v = redis.call('HGET', KEYS[1], ARGV[j]) if v then table.insert(args, ARGV[j]) table.insert(args, v) end end if #args == 0 then return 0 end redis.call('HSET', KEYS[2], table_unpack(args)) local ttl = redis.call('PTTL', KEYS[1]) if ttl > 0 then redis.call('PEXPIRE', KEYS[2], ttl) end return 1" 2 "Book_%&_Oliver Twist" "Book_%&view_%&_books_in_stock_%&_Oliver Twist" 2 title rating in_stock true
+---
+
+local table_unpack = table.unpack or unpack
+local n = tonumber(ARGV[1])
+local matches = redis.call('EXISTS', KEYS[1]) == 1
+local i = n + 2
+
+while matches and i < #ARGV do
+    if redis.call('HGET', KEYS[1], ARGV[i]) ~= ARGV[i + 1] then
+        matches = false
+    end
+    i = i + 2
+end
+
+redis.call('DEL', KEYS[2])
+if not matches then
+    return 0
+end
+
+local args = {}
+for j = 2, n + 1 do
+    local v = redis.call('HGET', KEYS[1], ARGV[j])
+    if v then
+        table.insert(args, ARGV[j])
+        table.insert(args, v)
+    end
+end
+
+if #args == 0 then
+    return 0
+end
+
+redis.call('HSET', KEYS[2], table_unpack(args))
+
+local ttl = redis.call('PTTL', KEYS[1])
+if ttl > 0 then
+    redis.call('PEXPIRE', KEYS[2], ttl)
+end
+
+return 1
@@ -0,0 +1,21 @@
+---
+--- Script to subtract the hashmaps at KEYS[2..] from the usage of the quota of their namespace, whose hashmap is at KEYS[1],
+--- before they are deleted i.e. one record and the total length of its field names and values for each of them that exists
+--- Example usage:
+---
+--- EVAL "local records, bytes = 0, 0 for i = 2, #KEYS do local fields = redis.call('HGETALL', KEYS[i]) if #fields > 0 then records = records + 1 for j = 1, #fields do bytes = bytes + #fields[j] end end end redis.call('HINCRBY', KEYS[1], 'records', -records) redis.call('HINCRBY', KEYS[1], 'bytes', -bytes)" 3 "team_a_%&quota_%&_usage" "Task_%&_foo" "Task_%&_bar"
+---
+
+local records, bytes = 0, 0
+for i = 2, #KEYS do
+    local fields = redis.call('HGETALL', KEYS[i])
+    if #fields > 0 then
+        records = records + 1
+        for j = 1, #fields do
+            bytes = bytes + #fields[j]
+        end
+    end
+end
+
+redis.call('HINCRBY', KEYS[1], 'records', -records)
+redis.call('HINCRBY', KEYS[1], 'bytes', -bytes)
\ No newline at end of file
@@ -0,0 +1,37 @@
+---
+--- Script to remove the hashmaps in one SCAN batch of the keys that have a given pattern whose timestamp field
+--- is less than a given cutoff, so that the retention of a large collection does not block redis.
+--- The comparison is numeric if the sixth argument is 'number' otherwise it is lexicographic.
+--- ARGV has the cursor, the SCAN count, the pattern, the timestamp field, the cutoff and the kind of comparison.
+--- It returns the next cursor, the number of keys scanned and the number of hashmaps removed
+--- Example usage:
+---
+--- EVAL "local result = redis.call('SCAN', ARGV[1], 'MATCH', ARGV[3], 'COUNT', ARGV[2]) local removed = 0 for _, key in ipairs(result[2]) do if redis.call('TYPE', key).ok == 'hash' then local value = redis.call('HGET', key, ARGV[4]) if value then local is_expired = false if ARGV[6] == 'number' then local num = tonumber(value) is_expired = num ~= nil and num < tonumber(ARGV[5]) else is_expired = value < ARGV[5] end if is_expired then redis.call('DEL', key) removed = removed + 1 end end end end return {result[1], #result[2], removed}" 0 0 1000 "Log_%&_*" created_at "2022-09-17 01:30:00+00:00" string
+---
+
+local result = redis.call('SCAN', ARGV[1], 'MATCH', ARGV[3], 'COUNT', ARGV[2])
+local removed = 0
+
+for _, key in ipairs(result[2]) do
+    if redis.call('TYPE', key).ok == 'hash' then
+        local value = redis.call('HGET', key, ARGV[4])
+
+        if value then
+            local is_expired = false
+
+            if ARGV[6] == 'number' then
+                local num = tonumber(value)
+                is_expired = num ~= nil and num < tonumber(ARGV[5])
+            else
+                is_expired = value < ARGV[5]
+            end
+
+            if is_expired then
+                redis.call('DEL', key)
+                removed = removed + 1
+            end
+        end
+    end
+end
+
+return {result[1], #result[2], removed}
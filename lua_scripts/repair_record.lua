@@ -0,0 +1,28 @@
+---
+--- Script to repair the fields of the hashmap at KEYS[1] whose values are still the broken ones they were read with,
+--- returning 1 if any field was repaired, else 0
+--- ARGV has the number of fields to remove, the field-value pairs to remove, and then the field-value-new value
+--- triples to set
+--- Example usage:
+---
+--- EVAL "local repaired = 0 local n = tonumber(ARGV[1]) for i = 2, 2 * n, 2 do if redis.call('HGET', KEYS[1], ARGV[i]) == ARGV[i + 1] then redis.call('HDEL', KEYS[1], ARGV[i]) repaired = 1 end end for i = 2 * n + 2, #ARGV, 3 do if redis.call('HGET', KEYS[1], ARGV[i]) == ARGV[i + 1] then redis.call('HSET', KEYS[1], ARGV[i], ARGV[i + 2]) repaired = 1 end end return repaired" 1 "Member_%&_john" 1 age None is_active True true
+---
+
+local repaired = 0
+local n = tonumber(ARGV[1])
+
+for i = 2, 2 * n, 2 do
+    if redis.call('HGET', KEYS[1], ARGV[i]) == ARGV[i + 1] then
+        redis.call('HDEL', KEYS[1], ARGV[i])
+        repaired = 1
+    end
+end
+
+for i = 2 * n + 2, #ARGV, 3 do
+    if redis.call('HGET', KEYS[1], ARGV[i]) == ARGV[i + 1] then
+        redis.call('HSET', KEYS[1], ARGV[i], ARGV[i + 2])
+        repaired = 1
+    end
+end
+
+return repaired
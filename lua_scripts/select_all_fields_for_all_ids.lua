@@ -1,16 +1,23 @@
 ---
---- Script to get all hashmaps that have a given pattern, and include their nested data, to only one level of nesting
+--- Script to get all hashmaps that have a given pattern, and include their nested data, to only one level of nesting.
+--- The given number of hashmaps are skipped, and at most the limit are returned, or all of them if the limit is -1
 --- Example usage:
 ---
---- EVAL "local filtered = {} local cursor = '0' local nested_fields = {} for i, key in ipairs(ARGV) do if i > 1 then nested_fields[key] = true end end repeat local result = redis.call('SCAN', cursor, 'MATCH', ARGV[1]) for _, key in ipairs(result[2]) do if redis.call('TYPE', key).ok == 'hash' then local parent = redis.call('HGETALL', key) for i, k in ipairs(parent) do if nested_fields[k] then local nested = redis.call('HGETALL', parent[i + 1]) parent[i + 1] = nested end end table.insert(filtered, parent) end end cursor = result[1] until (cursor == '0') return filtered" 0 "book_*" author
+--- EVAL "local filtered = {} local cursor = '0' local skip = tonumber(ARGV[2]) local limit = tonumber(ARGV[3]) local nested_fields = {} if limit == 0 then return filtered end for i, key in ipairs(ARGV) do if i > 3 then nested_fields[key] = true end end repeat local result = redis.call('SCAN', cursor, 'MATCH', ARGV[1]) for _, key in ipairs(result[2]) do if redis.call('TYPE', key).ok == 'hash' then if skip > 0 then skip = skip - 1 else local parent = redis.call('HGETALL', key) for i, k in ipairs(parent) do if nested_fields[k] then local nested = redis.call('HGETALL', parent[i + 1]) parent[i + 1] = nested end end table.insert(filtered, parent) if #filtered == limit then return filtered end end end end cursor = result[1] until (cursor == '0') return filtered" 0 "book_*" 0 10 author
 ---
 
 local filtered = {}
 local cursor = '0'
+local skip = tonumber(ARGV[2])
+local limit = tonumber(ARGV[3])
 local nested_fields = {}
 
+if limit == 0 then
+    return filtered
+end
+
 for i, key in ipairs(ARGV) do
-    if i > 1 then
+    if i > 3 then
         nested_fields[key] = true
     end
 end
@@ -19,16 +26,23 @@ repeat
     local result = redis.call('SCAN', cursor, 'MATCH', ARGV[1])
     for _, key in ipairs(result[2]) do
         if redis.call('TYPE', key).ok == 'hash' then
-            local parent = redis.call('HGETALL', key)
+            if skip > 0 then
+                skip = skip - 1
+            else
+                local parent = redis.call('HGETALL', key)
 
-            for i, k in ipairs(parent) do
-                if nested_fields[k] then
-                    local nested = redis.call('HGETALL', parent[i + 1])
-                    parent[i + 1] = nested
+                for i, k in ipairs(parent) do
+                    if nested_fields[k] then
+                        local nested = redis.call('HGETALL', parent[i + 1])
+                        parent[i + 1] = nested
+                    end
                 end
-            end
 
-            table.insert(filtered, parent)
+                table.insert(filtered, parent)
+                if #filtered == limit then
+                    return filtered
+                end
+            end
         end
     end
     cursor = result[1]
@@ -0,0 +1,29 @@
+---
+--- Script to get the distinct values of a given field of all hashmaps that have a given pattern, in the order
+--- they are first found, so that only the unique values are returned.
+--- ARGV has the pattern and the field
+--- Example usage:
+---
+--- EVAL "local distinct = {} local seen = {} local cursor = '0' repeat local result = redis.call('SCAN', cursor, 'MATCH', ARGV[1]) for _, key in ipairs(result[2]) do if redis.call('TYPE', key).ok == 'hash' then local value = redis.call('HGET', key, ARGV[2]) if value and not seen[value] then seen[value] = true table.insert(distinct, value) end end end cursor = result[1] until (cursor == '0') return distinct" 0 "Book_%&_*" rating
+---
+
+local distinct = {}
+local seen = {}
+local cursor = '0'
+
+repeat
+    local result = redis.call('SCAN', cursor, 'MATCH', ARGV[1])
+    for _, key in ipairs(result[2]) do
+        if redis.call('TYPE', key).ok == 'hash' then
+            local value = redis.call('HGET', key, ARGV[2])
+
+            -- records without the field e.g. those saved before it was added to the model, are left out
+            if value and not seen[value] then
+                seen[value] = true
+                table.insert(distinct, value)
+            end
+        end
+    end
+    cursor = result[1]
+until (cursor == '0')
+return distinct
\ No newline at end of file
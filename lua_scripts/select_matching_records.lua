@@ -0,0 +1,69 @@
+---
+--- Script to get all hashmaps that have a given pattern and whose fields are equal to the given values, and include
+--- their nested data, to only one level of nesting. The nested fields are preceded by their number, and followed
+--- by the pairs of fields and values of the filter. The given number of matching hashmaps are skipped, and at most
+--- the limit are returned, or all of them if the limit is -1
+--- Example usage:
+---
+--- EVAL "local filtered = {} local cursor = '0' local table_unpack = table.unpack or unpack local skip = tonumber(ARGV[2]) local limit = tonumber(ARGV[3]) local n = tonumber(ARGV[4]) local nested_fields = {} local filter_fields = {} local filter_values = {} if limit == 0 then return filtered end for i = 5, n + 4 do nested_fields[ARGV[i]] = true end for i = n + 5, #ARGV, 2 do table.insert(filter_fields, ARGV[i]) table.insert(filter_values, ARGV[i + 1]) end repeat local result = redis.call('SCAN', cursor, 'MATCH', ARGV[1]) for _, key in ipairs(result[2]) do if redis.call('TYPE', key).ok == 'hash' then local values = redis.call('HMGET', key, table_unpack(filter_fields)) local matches = true for i, v in ipairs(filter_values) do if values[i] ~= v then matches = false break end end if matches and skip > 0 then skip = skip - 1 elseif matches then local parent = redis.call('HGETALL', key) for i, k in ipairs(parent) do if nested_fields[k] then local nested = redis.call('HGETALL', parent[i + 1]) parent[i + 1] = nested end end table.insert(filtered, parent) if #filtered == limit then return filtered end end end end cursor = result[1] until (cursor == '0') return filtered" 0 "book_*" 0 10 1 author in_stock true
+---
+
+local filtered = {}
+local cursor = '0'
+local table_unpack = table.unpack or unpack
+local skip = tonumber(ARGV[2])
+local limit = tonumber(ARGV[3])
+local n = tonumber(ARGV[4])
+local nested_fields = {}
+local filter_fields = {}
+local filter_values = {}
+
+if limit == 0 then
+    return filtered
+end
+
+for i = 5, n + 4 do
+    nested_fields[ARGV[i]] = true
+end
+
+for i = n + 5, #ARGV, 2 do
+    table.insert(filter_fields, ARGV[i])
+    table.insert(filter_values, ARGV[i + 1])
+end
+
+repeat
+    local result = redis.call('SCAN', cursor, 'MATCH', ARGV[1])
+    for _, key in ipairs(result[2]) do
+        if redis.call('TYPE', key).ok == 'hash' then
+            -- only the fields of the filter are read before the record is known to match
+            local values = redis.call('HMGET', key, table_unpack(filter_fields))
+            local matches = true
+            for i, v in ipairs(filter_values) do
+                if values[i] ~= v then
+                    matches = false
+                    break
+                end
+            end
+
+            if matches and skip > 0 then
+                skip = skip - 1
+            elseif matches then
+                local parent = redis.call('HGETALL', key)
+
+                for i, k in ipairs(parent) do
+                    if nested_fields[k] then
+                        local nested = redis.call('HGETALL', parent[i + 1])
+                        parent[i + 1] = nested
+                    end
+                end
+
+                table.insert(filtered, parent)
+                if #filtered == limit then
+                    return filtered
+                end
+            end
+        end
+    end
+    cursor = result[1]
+until (cursor == '0')
+return filtered
\ No newline at end of file
@@ -1,21 +1,75 @@
 ---
 --- Script to get all hashmaps that have a given pattern but only get a handful of columns, with nested columns specified by repetition
+--- and some columns of nested hashmaps specified by their dotted paths e.g. "author.name". The given number of hashmaps
+--- are skipped, and at most the limit are returned, or all of them if the limit is -1
 --- Example usage:
 ---
---- EVAL "local filtered = {} local cursor = '0' local table_unpack = table.unpack or unpack local columns = {  } local nested_columns = {} local args_tracker = {} for i, k in ipairs(ARGV) do    if i > 1 then        if args_tracker[k] then            nested_columns[k] = true        else            table.insert(columns, k)            args_tracker[k] = true        end    end end repeat    local result = redis.call('SCAN', cursor, 'MATCH', ARGV[1])    for _, key in ipairs(result[2]) do        if redis.call('TYPE', key).ok == 'hash' then            local data = redis.call('HMGET', key, table_unpack(columns))            local parsed_data = {}            for i, v in ipairs(data) do                table.insert(parsed_data, columns[i])                if nested_columns[columns[i]] then                    v = redis.call('HGETALL', v)                end                table.insert(parsed_data, v)            end            table.insert(filtered, parsed_data)        end    end    cursor = result[1] until (cursor == '0') return filtered" 0 "book_*" tags author title author
+--- EVAL "local filtered = {} local cursor = '0' local skip = tonumber(ARGV[2]) local limit = tonumber(ARGV[3]) local table_unpack = table.unpack or unpack local columns = {  } local nested_columns = {} local args_tracker = {} local projections = {} local function project(key, paths) local fields = {} local is_field = {} local nested = {} for _, path in ipairs(paths) do local head, rest = string.match(path, '^([^.]+)%.(.+)$') if head then if not nested[head] then nested[head] = {} end table.insert(nested[head], rest) else table.insert(fields, path) is_field[path] = true end end local projected = {} if #fields > 0 then local values = redis.call('HMGET', key, table_unpack(fields)) for i, v in ipairs(values) do if v then table.insert(projected, fields[i]) table.insert(projected, v) end end end for head, rest in pairs(nested) do local nested_key = redis.call('HGET', key, head) if nested_key and not is_field[head] then table.insert(projected, head) table.insert(projected, project(nested_key, rest)) end end return projected end if limit == 0 then return filtered end for i, k in ipairs(ARGV) do if i > 3 then local head, rest = string.match(k, '^([^.]+)%.(.+)$') if head then if not projections[head] then projections[head] = {} end table.insert(projections[head], rest) elseif args_tracker[k] then nested_columns[k] = true else table.insert(columns, k) args_tracker[k] = true end end end for head, _ in pairs(projections) do if not args_tracker[head] then table.insert(columns, head) args_tracker[head] = true end end repeat local result = redis.call('SCAN', cursor, 'MATCH', ARGV[1]) for _, key in ipairs(result[2]) do if redis.call('TYPE', key).ok == 'hash' then if skip > 0 then skip = skip - 1 else local data = redis.call('HMGET', key, table_unpack(columns)) local parsed_data = {} for i, v in ipairs(data) do table.insert(parsed_data, columns[i]) if nested_columns[columns[i]] and v then v = redis.call('HGETALL', v) elseif projections[columns[i]] and v then v = project(v, projections[columns[i]]) end table.insert(parsed_data, v) end table.insert(filtered, parsed_data) if #filtered == limit then return filtered end end end end cursor = result[1] until (cursor == '0') return filtered" 0 "book_*" 0 10 tags author title author
 ---
 
 
 local filtered = {}
 local cursor = '0'
+local skip = tonumber(ARGV[2])
+local limit = tonumber(ARGV[3])
 local table_unpack = table.unpack or unpack
 local columns = {  }
 local nested_columns = {}
 local args_tracker = {}
+local projections = {}
+
+local function project(key, paths)
+    local fields = {}
+    local is_field = {}
+    local nested = {}
+    for _, path in ipairs(paths) do
+        local head, rest = string.match(path, '^([^.]+)%.(.+)$')
+        if head then
+            if not nested[head] then
+                nested[head] = {}
+            end
+            table.insert(nested[head], rest)
+        else
+            table.insert(fields, path)
+            is_field[path] = true
+        end
+    end
+
+    local projected = {}
+    if #fields > 0 then
+        local values = redis.call('HMGET', key, table_unpack(fields))
+        for i, v in ipairs(values) do
+            if v then
+                table.insert(projected, fields[i])
+                table.insert(projected, v)
+            end
+        end
+    end
+
+    for head, rest in pairs(nested) do
+        local nested_key = redis.call('HGET', key, head)
+        if nested_key and not is_field[head] then
+            table.insert(projected, head)
+            table.insert(projected, project(nested_key, rest))
+        end
+    end
+
+    return projected
+end
+
+if limit == 0 then
+    return filtered
+end
 
 for i, k in ipairs(ARGV) do
-    if i > 1 then
-        if args_tracker[k] then
+    if i > 3 then
+        local head, rest = string.match(k, '^([^.]+)%.(.+)$')
+        if head then
+            if not projections[head] then
+                projections[head] = {}
+            end
+            table.insert(projections[head], rest)
+        elseif args_tracker[k] then
             nested_columns[k] = true
         else
             table.insert(columns, k)
@@ -24,24 +78,40 @@ for i, k in ipairs(ARGV) do
     end
 end
 
+for head, _ in pairs(projections) do
+    if not args_tracker[head] then
+        table.insert(columns, head)
+        args_tracker[head] = true
+    end
+end
+
 repeat
     local result = redis.call('SCAN', cursor, 'MATCH', ARGV[1])
     for _, key in ipairs(result[2]) do
         if redis.call('TYPE', key).ok == 'hash' then
-            local data = redis.call('HMGET', key, table_unpack(columns))
-            local parsed_data = {}
+            if skip > 0 then
+                skip = skip - 1
+            else
+                local data = redis.call('HMGET', key, table_unpack(columns))
+                local parsed_data = {}
+
+                for i, v in ipairs(data) do
+                    table.insert(parsed_data, columns[i])
 
-            for i, v in ipairs(data) do
-                table.insert(parsed_data, columns[i])
+                    if nested_columns[columns[i]] and v then
+                        v = redis.call('HGETALL', v)
+                    elseif projections[columns[i]] and v then
+                        v = project(v, projections[columns[i]])
+                    end
 
-                if nested_columns[columns[i]] then
-                    v = redis.call('HGETALL', v)
+                    table.insert(parsed_data, v)
                 end
 
-                table.insert(parsed_data, v)
+                table.insert(filtered, parsed_data)
+                if #filtered == limit then
+                    return filtered
+                end
             end
-
-            table.insert(filtered, parsed_data)
         end
     end
     cursor = result[1]
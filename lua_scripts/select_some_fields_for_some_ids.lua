@@ -1,8 +1,9 @@
 ---
 --- Script to get some hashmaps of given keys but only get a handful of columns, with nested columns specified by repetition
+--- and some columns of nested hashmaps specified by their dotted paths e.g. "author.name"
 --- Example usage:
 ---
---- EVAL "local result = {} local table_unpack = table.unpack or unpack local columns = { } local nested_columns = {} local args_tracker = {} for i, k in ipairs(ARGV) do if args_tracker[k] then nested_columns[k] = true else table.insert(columns, k) args_tracker[k] = true end end for _, key in ipairs(KEYS) do local data = redis.call('HMGET', key, table_unpack(columns)) local parsed_data = {} for i, v in ipairs(data) do if v then table.insert(parsed_data, columns[i]) if nested_columns[columns[i]] then v = redis.call('HGETALL', v) end table.insert(parsed_data, v) end end table.insert(result, parsed_data) end return result" 2 "book_%&_Oliver Twist" "book_%&_Wuthering Heights" tags title rating author author
+--- EVAL "local result = {} local table_unpack = table.unpack or unpack local columns = {  } local nested_columns = {} local args_tracker = {} local projections = {} local function project(key, paths) local fields = {} local is_field = {} local nested = {} for _, path in ipairs(paths) do local head, rest = string.match(path, '^([^.]+)%.(.+)$') if head then if not nested[head] then nested[head] = {} end table.insert(nested[head], rest) else table.insert(fields, path) is_field[path] = true end end local projected = {} if #fields > 0 then local values = redis.call('HMGET', key, table_unpack(fields)) for i, v in ipairs(values) do if v then table.insert(projected, fields[i]) table.insert(projected, v) end end end for head, rest in pairs(nested) do local nested_key = redis.call('HGET', key, head) if nested_key and not is_field[head] then table.insert(projected, head) table.insert(projected, project(nested_key, rest)) end end return projected end for i, k in ipairs(ARGV) do local head, rest = string.match(k, '^([^.]+)%.(.+)$') if head then if not projections[head] then projections[head] = {} end table.insert(projections[head], rest) elseif args_tracker[k] then nested_columns[k] = true else table.insert(columns, k) args_tracker[k] = true end end for head, _ in pairs(projections) do if not args_tracker[head] then table.insert(columns, head) args_tracker[head] = true end end for _, key in ipairs(KEYS) do local data = redis.call('HMGET', key, table_unpack(columns)) local parsed_data = {} for i, v in ipairs(data) do if v then table.insert(parsed_data, columns[i]) if nested_columns[columns[i]] then v = redis.call('HGETALL', v) elseif projections[columns[i]] then v = project(v, projections[columns[i]]) end table.insert(parsed_data, v) end end table.insert(result, parsed_data) end return result" 2 "book_%&_Oliver Twist" "book_%&_Wuthering Heights" tags title rating author author
 ---
 
 
@@ -11,9 +12,55 @@ local table_unpack = table.unpack or unpack
 local columns = {  }
 local nested_columns = {}
 local args_tracker = {}
+local projections = {}
+
+local function project(key, paths)
+    local fields = {}
+    local is_field = {}
+    local nested = {}
+    for _, path in ipairs(paths) do
+        local head, rest = string.match(path, '^([^.]+)%.(.+)$')
+        if head then
+            if not nested[head] then
+                nested[head] = {}
+            end
+            table.insert(nested[head], rest)
+        else
+            table.insert(fields, path)
+            is_field[path] = true
+        end
+    end
+
+    local projected = {}
+    if #fields > 0 then
+        local values = redis.call('HMGET', key, table_unpack(fields))
+        for i, v in ipairs(values) do
+            if v then
+                table.insert(projected, fields[i])
+                table.insert(projected, v)
+            end
+        end
+    end
+
+    for head, rest in pairs(nested) do
+        local nested_key = redis.call('HGET', key, head)
+        if nested_key and not is_field[head] then
+            table.insert(projected, head)
+            table.insert(projected, project(nested_key, rest))
+        end
+    end
+
+    return projected
+end
 
 for i, k in ipairs(ARGV) do
-    if args_tracker[k] then
+    local head, rest = string.match(k, '^([^.]+)%.(.+)$')
+    if head then
+        if not projections[head] then
+            projections[head] = {}
+        end
+        table.insert(projections[head], rest)
+    elseif args_tracker[k] then
         nested_columns[k] = true
     else
         table.insert(columns, k)
@@ -21,6 +68,13 @@ for i, k in ipairs(ARGV) do
     end
 end
 
+for head, _ in pairs(projections) do
+    if not args_tracker[head] then
+        table.insert(columns, head)
+        args_tracker[head] = true
+    end
+end
+
 for _, key in ipairs(KEYS) do
     local data = redis.call('HMGET', key, table_unpack(columns))
     local parsed_data = {}
@@ -31,6 +85,8 @@ for _, key in ipairs(KEYS) do
 
             if nested_columns[columns[i]] then
                 v = redis.call('HGETALL', v)
+            elseif projections[columns[i]] then
+                v = project(v, projections[columns[i]])
             end
 
             table.insert(parsed_data, v)
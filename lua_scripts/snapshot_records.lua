@@ -0,0 +1,27 @@
+---
+--- Script to copy into a snapshot the hashmaps in one SCAN batch of the keys that have a given pattern, with all
+--- their fields. The copies do not expire even if the hashmaps do, so that the snapshot keeps the records as they were.
+--- The keys of the snapshot are first removed with clear_view.lua.
+--- ARGV has the cursor, the SCAN count, the pattern of the collection, the key prefix of the collection and the key
+--- prefix of the snapshot.
+--- It returns the next cursor, the number of keys scanned and the number of records copied
+--- Example usage:
+---
+--- EVAL "local table_unpack = table.unpack or unpack local result = redis.call('SCAN', ARGV[1], 'MATCH', ARGV[3], 'COUNT', ARGV[2]) local count = 0 for _, key in ipairs(result[2]) do if redis.call('TYPE', key).ok == 'hash' then local fields = redis.call('HGETALL', key) if #fields > 0 then redis.call('HSET', ARGV[5] .. string.sub(key, #ARGV[4] + 1), table_unpack(fields)) count = count + 1 end end end return {result[1], #result[2], count}" 0 0 1000 "Book_%&_*" "Book_%&_" "Book_%&snapshot_%&_before-migration_%&_"
+---
+
+local table_unpack = table.unpack or unpack
+local result = redis.call('SCAN', ARGV[1], 'MATCH', ARGV[3], 'COUNT', ARGV[2])
+local count = 0
+
+for _, key in ipairs(result[2]) do
+    if redis.call('TYPE', key).ok == 'hash' then
+        local fields = redis.call('HGETALL', key)
+        if #fields > 0 then
+            redis.call('HSET', ARGV[5] .. string.sub(key, #ARGV[4] + 1), table_unpack(fields))
+            count = count + 1
+        end
+    end
+end
+
+return {result[1], #result[2], count}
\ No newline at end of file
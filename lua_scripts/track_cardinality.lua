@@ -0,0 +1,27 @@
+---
+--- Script to add the values of a given field of the hashmaps in one SCAN batch of the keys that have a given pattern to
+--- the HyperLogLog at KEYS[1], adding the field to the set of the tracked fields of the collection at KEYS[2].
+--- ARGV has the cursor, the SCAN count, the pattern and the field.
+--- It returns the next cursor, the number of keys scanned and the number of values added
+--- Example usage:
+---
+--- EVAL "redis.call('SADD', KEYS[2], ARGV[4]) local result = redis.call('SCAN', ARGV[1], 'MATCH', ARGV[3], 'COUNT', ARGV[2]) local added = 0 for _, key in ipairs(result[2]) do if redis.call('TYPE', key).ok == 'hash' then local value = redis.call('HGET', key, ARGV[4]) if value then redis.call('PFADD', KEYS[1], value) added = added + 1 end end end return {result[1], #result[2], added}" 2 "Book_%&hll_%&_rating" "Book_%&cardinality_%&_fields" 0 1000 "Book_%&_*" rating
+---
+
+redis.call('SADD', KEYS[2], ARGV[4])
+
+local result = redis.call('SCAN', ARGV[1], 'MATCH', ARGV[3], 'COUNT', ARGV[2])
+local added = 0
+
+for _, key in ipairs(result[2]) do
+    if redis.call('TYPE', key).ok == 'hash' then
+        local value = redis.call('HGET', key, ARGV[4])
+
+        if value then
+            redis.call('PFADD', KEYS[1], value)
+            added = added + 1
+        end
+    end
+end
+
+return {result[1], #result[2], added}
\ No newline at end of file
@@ -0,0 +1,61 @@
+---
+--- Script to update the field indexes of the record at KEYS[1] before it is written or deleted. Each index is a set
+--- of the ids of the records whose indexed fields have given values, at the key made of the index key prefix,
+--- the fields joined by ",", "_%&_" and the values joined by "_%&_". The record is moved from the set of the values
+--- it had, if it had all of them, to that of its new values, which are the written values or else the values it had,
+--- or only removed from the former if it is being deleted. The values are normalized with `normalize(field, value)`,
+--- defined by the normalizers of the collection, prepended to the script.
+--- ARGV has the id of the record, the index key prefix, the mode i.e. "set" or "remove", the number of written fields
+--- followed by the written fields with their values, then each index as the number of its fields followed by the fields
+--- Example usage:
+---
+--- EVAL "local function normalize(field, value) return value end local table_unpack = table.unpack or unpack local id, prefix, mode = ARGV[1], ARGV[2], ARGV[3] local written = {} local i = 5 + 2 * tonumber(ARGV[4]) for j = 5, i - 1, 2 do written[ARGV[j]] = ARGV[j + 1] end while i <= #ARGV do local n = tonumber(ARGV[i]) local fields = { table_unpack(ARGV, i + 1, i + n) } i = i + n + 1 local current = redis.call('HMGET', KEYS[1], table_unpack(fields)) local old, new = {}, {} local has_old, has_new = true, mode == 'set' for j, field in ipairs(fields) do if current[j] then old[j] = normalize(field, current[j]) else has_old = false end local value = written[field] or current[j] if value then new[j] = normalize(field, value) else has_new = false end end local name = table.concat(fields, ',') .. '_%&_' local old_key, new_key = false, false if has_old then old_key = prefix .. name .. table.concat(old, '_%&_') end if has_new then new_key = prefix .. name .. table.concat(new, '_%&_') end if old_key and old_key ~= new_key then redis.call('SREM', old_key, id) end if new_key then redis.call('SADD', new_key, id) end end" 1 "Shop_%&_s1" s1 "Shop_%&index_%&_" set 1 city Kampala 2 country city
+---
+
+local table_unpack = table.unpack or unpack
+local id, prefix, mode = ARGV[1], ARGV[2], ARGV[3]
+local written = {}
+local i = 5 + 2 * tonumber(ARGV[4])
+for j = 5, i - 1, 2 do
+    written[ARGV[j]] = ARGV[j + 1]
+end
+
+while i <= #ARGV do
+    local n = tonumber(ARGV[i])
+    local fields = { table_unpack(ARGV, i + 1, i + n) }
+    i = i + n + 1
+
+    local current = redis.call('HMGET', KEYS[1], table_unpack(fields))
+    local old, new = {}, {}
+    local has_old, has_new = true, mode == 'set'
+    for j, field in ipairs(fields) do
+        if current[j] then
+            old[j] = normalize(field, current[j])
+        else
+            has_old = false
+        end
+
+        local value = written[field] or current[j]
+        if value then
+            new[j] = normalize(field, value)
+        else
+            has_new = false
+        end
+    end
+
+    local name = table.concat(fields, ',') .. '_%&_'
+    local old_key, new_key = false, false
+    if has_old then
+        old_key = prefix .. name .. table.concat(old, '_%&_')
+    end
+    if has_new then
+        new_key = prefix .. name .. table.concat(new, '_%&_')
+    end
+
+    if old_key and old_key ~= new_key then
+        redis.call('SREM', old_key, id)
+    end
+    if new_key then
+        redis.call('SADD', new_key, id)
+    end
+end
\ No newline at end of file
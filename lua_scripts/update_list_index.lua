@@ -0,0 +1,32 @@
+---
+--- Script to update the index of the elements of a list field of a record before it is written. Each element has a set
+--- of the ids of the records whose list has it, at the key made of the index key prefix and the element. The elements
+--- the record had, kept in the set at KEYS[1], that are no longer in its list are removed from the index, those that
+--- are new are added, and the set of the elements of the record is replaced by the new elements.
+--- ARGV has the id of the record, the index key prefix, then the elements of its list
+--- Example usage:
+---
+--- EVAL "local id, prefix = ARGV[1], ARGV[2] local new = {} for i = 3, #ARGV do new[ARGV[i]] = true end for _, element in ipairs(redis.call('SMEMBERS', KEYS[1])) do if new[element] then new[element] = nil else redis.call('SREM', prefix .. element, id) end end for element in pairs(new) do redis.call('SADD', prefix .. element, id) end redis.call('DEL', KEYS[1]) for i = 3, #ARGV do redis.call('SADD', KEYS[1], ARGV[i]) end" 1 "Post_%&list_elements_%&_tags_%&_p1" p1 "Post_%&list_index_%&_tags_%&_" rust python
+---
+
+local id, prefix = ARGV[1], ARGV[2]
+local new = {}
+for i = 3, #ARGV do
+    new[ARGV[i]] = true
+end
+
+for _, element in ipairs(redis.call('SMEMBERS', KEYS[1])) do
+    if new[element] then
+        new[element] = nil
+    else
+        redis.call('SREM', prefix .. element, id)
+    end
+end
+for element in pairs(new) do
+    redis.call('SADD', prefix .. element, id)
+end
+
+redis.call('DEL', KEYS[1])
+for i = 3, #ARGV do
+    redis.call('SADD', KEYS[1], ARGV[i])
+end
\ No newline at end of file
@@ -0,0 +1,89 @@
+---
+--- Script to find the records of a collection whose fields are equal to the given values, scanning all the keys that
+--- match the pattern of the keys of the collection, and to make the given changes to them if asked to. An update event
+--- with the fields of the record before and after the changes is added to the change stream of the collection for each
+--- record updated, if it has one. The changed values of the fields whose cardinality is tracked, kept in the set at the
+--- key of the tracked fields, are added to their HyperLogLogs, at the keys made of their prefix and the field.
+--- ARGV has the key pattern of the collection, its key prefix, "1" to update the records or "0" to only find them, the
+--- key of the change stream or "", the maximum length of the stream, the key of the tracked fields, the prefix of the
+--- keys of the HyperLogLogs, the number of changes followed by each changed field and its new value, then each field of
+--- the filter followed by its value. It returns the ids of the records found
+--- Example usage:
+---
+--- EVAL "local table_unpack = table.unpack or unpack local matched = {} local seen = {} local cursor = '0' local n = tonumber(ARGV[8]) local changes = {} for j = 9, 8 + 2 * n do table.insert(changes, ARGV[j]) end local filter_fields = {} local filter_values = {} for j = 9 + 2 * n, #ARGV, 2 do table.insert(filter_fields, ARGV[j]) table.insert(filter_values, ARGV[j + 1]) end repeat local result = redis.call('SCAN', cursor, 'MATCH', ARGV[1]) for _, key in ipairs(result[2]) do if not seen[key] and redis.call('TYPE', key).ok == 'hash' then seen[key] = true local values = redis.call('HMGET', key, table_unpack(filter_fields)) local matches = true for k, v in ipairs(filter_values) do if values[k] ~= v then matches = false break end end if matches then local id = string.sub(key, #ARGV[2] + 1) table.insert(matched, id) if ARGV[3] == '1' then if ARGV[4] ~= '' then local before = redis.call('HGETALL', key) redis.call('HSET', key, table_unpack(changes)) local after = redis.call('HGETALL', key) local entry = {'op', 'update', 'id', id} for k = 1, #before, 2 do table.insert(entry, 'before.' .. before[k]) table.insert(entry, before[k + 1]) end for k = 1, #after, 2 do table.insert(entry, 'after.' .. after[k]) table.insert(entry, after[k + 1]) end redis.call('XADD', ARGV[4], 'MAXLEN', '~', ARGV[5], '*', table_unpack(entry)) else redis.call('HSET', key, table_unpack(changes)) end end end end end cursor = result[1] until (cursor == '0') if ARGV[3] == '1' and #matched > 0 then local tracked = {} for _, field in ipairs(redis.call('SMEMBERS', ARGV[6])) do tracked[field] = true end for j = 1, #changes, 2 do if tracked[changes[j]] then redis.call('PFADD', ARGV[7] .. changes[j], changes[j + 1]) end end end return matched" 0 "Book_%&_*" "Book_%&_" 1 "" 100000 "Book_%&cardinality_%&_fields" "Book_%&hll_%&_" 1 in_stock false author "Jane Austen"
+---
+
+local table_unpack = table.unpack or unpack
+local matched = {}
+local seen = {}
+local cursor = '0'
+
+local n = tonumber(ARGV[8])
+local changes = {}
+for j = 9, 8 + 2 * n do
+    table.insert(changes, ARGV[j])
+end
+
+local filter_fields = {}
+local filter_values = {}
+for j = 9 + 2 * n, #ARGV, 2 do
+    table.insert(filter_fields, ARGV[j])
+    table.insert(filter_values, ARGV[j + 1])
+end
+
+repeat
+    local result = redis.call('SCAN', cursor, 'MATCH', ARGV[1])
+    for _, key in ipairs(result[2]) do
+        if not seen[key] and redis.call('TYPE', key).ok == 'hash' then
+            seen[key] = true
+            local values = redis.call('HMGET', key, table_unpack(filter_fields))
+            local matches = true
+            for k, v in ipairs(filter_values) do
+                if values[k] ~= v then
+                    matches = false
+                    break
+                end
+            end
+
+            if matches then
+                local id = string.sub(key, #ARGV[2] + 1)
+                table.insert(matched, id)
+
+                if ARGV[3] == '1' then
+                    if ARGV[4] ~= '' then
+                        local before = redis.call('HGETALL', key)
+                        redis.call('HSET', key, table_unpack(changes))
+                        local after = redis.call('HGETALL', key)
+                        local entry = {'op', 'update', 'id', id}
+                        for k = 1, #before, 2 do
+                            table.insert(entry, 'before.' .. before[k])
+                            table.insert(entry, before[k + 1])
+                        end
+                        for k = 1, #after, 2 do
+                            table.insert(entry, 'after.' .. after[k])
+                            table.insert(entry, after[k + 1])
+                        end
+                        redis.call('XADD', ARGV[4], 'MAXLEN', '~', ARGV[5], '*', table_unpack(entry))
+                    else
+                        redis.call('HSET', key, table_unpack(changes))
+                    end
+                end
+            end
+        end
+    end
+    cursor = result[1]
+until (cursor == '0')
+
+if ARGV[3] == '1' and #matched > 0 then
+    local tracked = {}
+    for _, field in ipairs(redis.call('SMEMBERS', ARGV[6])) do
+        tracked[field] = true
+    end
+    for j = 1, #changes, 2 do
+        if tracked[changes[j]] then
+            redis.call('PFADD', ARGV[7] .. changes[j], changes[j + 1])
+        end
+    end
+end
+
+return matched
\ No newline at end of file
@@ -0,0 +1,26 @@
+---
+--- Script to update some fields of a record nested in the hashmap at KEYS[1], following the path of nested fields
+--- from the hashmap to the nested record. It does nothing if the nested record does not exist.
+--- ARGV has the number of nested fields in the path, the nested fields, and then the field-value pairs to set
+--- Example usage:
+---
+--- EVAL "local table_unpack = table.unpack or unpack local key = KEYS[1] local n = tonumber(ARGV[1]) for i = 2, n + 1 do key = redis.call('HGET', key, ARGV[i]) if not key then return 0 end end if redis.call('EXISTS', key) == 0 then return 0 end redis.call('HSET', key, table_unpack(ARGV, n + 2)) return 1" 1 "Publisher_%&_Penguin" 1 address number 81
+---
+
+local table_unpack = table.unpack or unpack
+local key = KEYS[1]
+local n = tonumber(ARGV[1])
+
+for i = 2, n + 1 do
+    key = redis.call('HGET', key, ARGV[i])
+    if not key then
+        return 0
+    end
+end
+
+if redis.call('EXISTS', key) == 0 then
+    return 0
+end
+
+redis.call('HSET', key, table_unpack(ARGV, n + 2))
+return 1
@@ -0,0 +1,31 @@
+---
+--- Script to add the id of the record whose hashmap is at KEYS[1] to the null indexes at KEYS[2], KEYS[3], ... of the
+--- fields it has no value for i.e. that it lacks or whose value is None, and remove it from the null indexes of the
+--- others. It runs after the fields of the record are written, and while a null index is built, reading the values
+--- itself so that it is not misled by writes made meanwhile.
+--- ARGV has the id of the record then the fields whose null indexes are at KEYS[2], KEYS[3], ... in order.
+--- It returns the number of the fields the record has no value for, or 0 if the record does not exist
+--- Example usage:
+---
+--- EVAL "local id = ARGV[1] local nulls = 0 if redis.call('EXISTS', KEYS[1]) == 0 then return nulls end for i = 2, #ARGV do local value = redis.call('HGET', KEYS[1], ARGV[i]) if not value or value == 'None' then redis.call('SADD', KEYS[i], id) nulls = nulls + 1 else redis.call('SREM', KEYS[i], id) end end return nulls" 2 "Member_%&_john" "Member_%&null_index_%&_email" john email
+---
+
+local id = ARGV[1]
+local nulls = 0
+
+if redis.call('EXISTS', KEYS[1]) == 0 then
+    return nulls
+end
+
+for i = 2, #ARGV do
+    local value = redis.call('HGET', KEYS[1], ARGV[i])
+
+    if not value or value == 'None' then
+        redis.call('SADD', KEYS[i], id)
+        nulls = nulls + 1
+    else
+        redis.call('SREM', KEYS[i], id)
+    end
+end
+
+return nulls
\ No newline at end of file
@@ -0,0 +1,54 @@
+---
+--- Script to add to the usage of the quota of a namespace, whose hashmap is at KEYS[1], the change that writing the
+--- given fields to the hashmaps at KEYS[4..] makes i.e. the number of those hashmaps that do not exist yet and the
+--- change in the total length of their field names and values. It runs right before the transaction of the write, which
+--- watches the guard key at KEYS[2], so that the check of the quota and the change of its usage are atomic. The change
+--- is saved in the hashmap at KEYS[3] so that the checks of the write made after this one can take it back if they
+--- fail.
+--- ARGV[1] is the namespace and ARGV[2..] has, for each hashmap, the number of its fields followed by the name and the
+--- length of the value of each field.
+--- It returns 1 once the usage is changed. If the change would take the usage beyond any of the limits of the quota,
+--- the usage is left as it is, the guard key is set so that the transaction of the write is aborted, and an error with
+--- the code QUOTAEXCEEDED is returned instead
+--- Example usage:
+---
+--- EVAL "local records, bytes = 0, 0 local pos = 2 for i = 4, #KEYS do local n = tonumber(ARGV[pos]) pos = pos + 1 if n > 0 and redis.call('EXISTS', KEYS[i]) == 0 then records = records + 1 end for _ = 1, n do local field, length = ARGV[pos], tonumber(ARGV[pos + 1]) pos = pos + 2 if redis.call('HEXISTS', KEYS[i], field) == 1 then bytes = bytes + length - redis.call('HSTRLEN', KEYS[i], field) else bytes = bytes + #field + length end end end local quota = redis.call('HMGET', KEYS[1], 'records', 'bytes', 'max_records', 'max_bytes') local new_records = (tonumber(quota[1]) or 0) + records local new_bytes = (tonumber(quota[2]) or 0) + bytes if (quota[3] and records > 0 and new_records > tonumber(quota[3])) or (quota[4] and bytes > 0 and new_bytes > tonumber(quota[4])) then redis.call('SET', KEYS[2], 1, 'PX', 60000) return redis.error_reply('QUOTAEXCEEDED the write would exceed the quota of namespace ' .. string.format('%q', ARGV[1]) .. ', taking it to ' .. new_records .. ' records (max: ' .. (quota[3] or 'no limit') .. ') and ' .. new_bytes .. ' bytes (max: ' .. (quota[4] or 'no limit') .. ')') end redis.call('HINCRBY', KEYS[1], 'records', records) redis.call('HINCRBY', KEYS[1], 'bytes', bytes) redis.call('HSET', KEYS[3], 'quota', KEYS[1], 'records', records, 'bytes', bytes) redis.call('PEXPIRE', KEYS[3], 60000) return 1" 5 "team_a_%&quota_%&_usage" "Task_%&guard_%&_4f1c" "Task_%&undo_%&_4f1c" "Task_%&_foo" "Task_%&_bar" team_a 2 name 3 done 5 1 name 3
+---
+
+local records, bytes = 0, 0
+local pos = 2
+for i = 4, #KEYS do
+    local n = tonumber(ARGV[pos])
+    pos = pos + 1
+    if n > 0 and redis.call('EXISTS', KEYS[i]) == 0 then
+        records = records + 1
+    end
+
+    for _ = 1, n do
+        local field, length = ARGV[pos], tonumber(ARGV[pos + 1])
+        pos = pos + 2
+        if redis.call('HEXISTS', KEYS[i], field) == 1 then
+            bytes = bytes + length - redis.call('HSTRLEN', KEYS[i], field)
+        else
+            bytes = bytes + #field + length
+        end
+    end
+end
+
+local quota = redis.call('HMGET', KEYS[1], 'records', 'bytes', 'max_records', 'max_bytes')
+local new_records = (tonumber(quota[1]) or 0) + records
+local new_bytes = (tonumber(quota[2]) or 0) + bytes
+if (quota[3] and records > 0 and new_records > tonumber(quota[3]))
+        or (quota[4] and bytes > 0 and new_bytes > tonumber(quota[4])) then
+    -- changing the watched guard key aborts the transaction of the write
+    redis.call('SET', KEYS[2], 1, 'PX', 60000)
+    return redis.error_reply('QUOTAEXCEEDED the write would exceed the quota of namespace '
+            .. string.format('%q', ARGV[1]) .. ', taking it to ' .. new_records .. ' records (max: '
+            .. (quota[3] or 'no limit') .. ') and ' .. new_bytes .. ' bytes (max: ' .. (quota[4] or 'no limit') .. ')')
+end
+
+redis.call('HINCRBY', KEYS[1], 'records', records)
+redis.call('HINCRBY', KEYS[1], 'bytes', bytes)
+redis.call('HSET', KEYS[3], 'quota', KEYS[1], 'records', records, 'bytes', bytes)
+redis.call('PEXPIRE', KEYS[3], 60000)
+return 1
\ No newline at end of file
@@ -0,0 +1,85 @@
+---
+--- Script to check and update the reservations of the values of the unique fields of records before they are written or
+--- deleted. Each reservation is a string holding the id of the record whose unique field has a given value, at the key
+--- made of the reservation key prefix, the field, "_%&_" and the value. The value a record had, if any, is released and
+--- its new value is reserved, taking the ttl of the record, or the former is only released if it is being deleted. The
+--- values are normalized with `normalize(field, value)`, defined by the normalizers of the collection, prepended to the
+--- script.
+--- In "reserve" mode, the script runs right before the transaction of the write, after its other checks e.g. of the
+--- quota, and the keys of the guard of the write follow the keys of the records: the guard key, watched by the
+--- transaction, and the hashmap of the changes made by the other checks. Nothing is done if the guard key is set i.e.
+--- another check failed. If any of the values is reserved by another record, nothing is reserved, the change in the
+--- usage of the quota saved by the check of the quota is taken back, the guard key is set so that the transaction is
+--- aborted, and an error with the code UNIQUEVIOLATION is returned.
+--- KEYS has the key of the record of each entry, followed by the keys of the guard in "reserve" mode. ARGV has the
+--- reservation key prefix, the mode i.e. "reserve", "set" or "remove", the name of the collection in "reserve" mode,
+--- then the entries i.e. the id, field and new value if the mode is "reserve" or "set", or only the id and field if it
+--- is "remove"
+--- Example usage:
+---
+--- EVAL "local function normalize(field, value) return value end local prefix, mode = ARGV[1], ARGV[2] local first, step = 3, 3 if mode == 'remove' then step = 2 end if mode == 'reserve' then first = 4 local guard, undo = KEYS[#KEYS - 1], KEYS[#KEYS] if redis.call('EXISTS', guard) == 1 then return {} end local pending = {} for i = first, #ARGV, step do local id, field, value = ARGV[i], ARGV[i + 1], ARGV[i + 2] local key = prefix .. field .. '_%&_' .. normalize(field, value) local owner = pending[key] or redis.call('GET', key) if owner and owner ~= id then local usage = redis.call('HMGET', undo, 'quota', 'records', 'bytes') if usage[1] then redis.call('HINCRBY', usage[1], 'records', -tonumber(usage[2])) redis.call('HINCRBY', usage[1], 'bytes', -tonumber(usage[3])) end redis.call('SET', guard, 1, 'PX', 60000) return redis.error_reply(string.format('UNIQUEVIOLATION the value %q of the unique field %q is already taken by the record %q of %s', value, field, owner, ARGV[3])) end pending[key] = id end end local j = 0 for i = first, #ARGV, step do j = j + 1 local id, field = ARGV[i], ARGV[i + 1] local new = false if mode ~= 'remove' then new = normalize(field, ARGV[i + 2]) end local old = normalize(field, redis.call('HGET', KEYS[j], field)) if old and old ~= new then local old_key = prefix .. field .. '_%&_' .. old if redis.call('GET', old_key) == id then redis.call('DEL', old_key) end end if new then local key = prefix .. field .. '_%&_' .. new if redis.call('GET', key) ~= id then redis.call('SET', key, id) local ttl = redis.call('PTTL', KEYS[j]) if ttl > 0 then redis.call('PEXPIRE', key, ttl) end end end end return {}" 3 "User_%&_u1" "User_%&guard_%&_4f1c" "User_%&undo_%&_4f1c" "User_%&unique_%&_" reserve User u1 email ann@example.com
+---
+
+local prefix, mode = ARGV[1], ARGV[2]
+local first, step = 3, 3
+if mode == 'remove' then
+    step = 2
+end
+
+if mode == 'reserve' then
+    first = 4
+    local guard, undo = KEYS[#KEYS - 1], KEYS[#KEYS]
+    if redis.call('EXISTS', guard) == 1 then
+        return {}
+    end
+
+    local pending = {}
+    for i = first, #ARGV, step do
+        local id, field, value = ARGV[i], ARGV[i + 1], ARGV[i + 2]
+        local key = prefix .. field .. '_%&_' .. normalize(field, value)
+        local owner = pending[key] or redis.call('GET', key)
+        if owner and owner ~= id then
+            local usage = redis.call('HMGET', undo, 'quota', 'records', 'bytes')
+            if usage[1] then
+                redis.call('HINCRBY', usage[1], 'records', -tonumber(usage[2]))
+                redis.call('HINCRBY', usage[1], 'bytes', -tonumber(usage[3]))
+            end
+
+            -- changing the watched guard key aborts the transaction of the write
+            redis.call('SET', guard, 1, 'PX', 60000)
+            return redis.error_reply(string.format(
+                    'UNIQUEVIOLATION the value %q of the unique field %q is already taken by the record %q of %s',
+                    value, field, owner, ARGV[3]))
+        end
+        pending[key] = id
+    end
+end
+
+local j = 0
+for i = first, #ARGV, step do
+    j = j + 1
+    local id, field = ARGV[i], ARGV[i + 1]
+    local new = false
+    if mode ~= 'remove' then
+        new = normalize(field, ARGV[i + 2])
+    end
+
+    local old = normalize(field, redis.call('HGET', KEYS[j], field))
+    if old and old ~= new then
+        local old_key = prefix .. field .. '_%&_' .. old
+        if redis.call('GET', old_key) == id then
+            redis.call('DEL', old_key)
+        end
+    end
+    if new then
+        local key = prefix .. field .. '_%&_' .. new
+        if redis.call('GET', key) ~= id then
+            redis.call('SET', key, id)
+            local ttl = redis.call('PTTL', KEYS[j])
+            if ttl > 0 then
+                redis.call('PEXPIRE', key, ttl)
+            end
+        end
+    end
+end
+return {}
\ No newline at end of file
@@ -0,0 +1,28 @@
+---
+--- Script to save the upgraded fields of the hashmap at KEYS[1] with its new format version, if the hashmap
+--- exists and its format version is still the one it was upgraded from
+--- ARGV has the name of the version field, the version the record was upgraded from ('' if it had none),
+--- the number of fields to remove, the fields to remove, and then the field-value pairs to set,
+--- starting with the version field and the new version
+--- Example usage:
+---
+--- EVAL "local table_unpack = table.unpack or unpack if redis.call('EXISTS', KEYS[1]) == 0 then return 0 end local current = redis.call('HGET', KEYS[1], ARGV[1]) or '' if current ~= ARGV[2] then return 0 end local n = tonumber(ARGV[3]) for i = 4, n + 3 do redis.call('HDEL', KEYS[1], ARGV[i]) end redis.call('HSET', KEYS[1], table_unpack(ARGV, n + 4)) return 1" 1 "Member_%&_john" _version "" 1 fullname _version 1 name john
+---
+
+local table_unpack = table.unpack or unpack
+if redis.call('EXISTS', KEYS[1]) == 0 then
+    return 0
+end
+
+local current = redis.call('HGET', KEYS[1], ARGV[1]) or ''
+if current ~= ARGV[2] then
+    return 0
+end
+
+local n = tonumber(ARGV[3])
+for i = 4, n + 3 do
+    redis.call('HDEL', KEYS[1], ARGV[i])
+end
+
+redis.call('HSET', KEYS[1], table_unpack(ARGV, n + 4))
+return 1
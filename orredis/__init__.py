@@ -1,9 +1,19 @@
-from orredis.orredis import Store, AsyncStore
+from orredis.orredis import (Store, AsyncStore, PoolExhaustedError, QuotaExceededError, UniqueViolationError,
+                             DeadlineExceededError, CircuitOpenError, deadline, register_backup_sink)
 
-from .abstract import Model
+from .abstract import Model, Reference, Vector
 
 __all__ = [
     AsyncStore,
     Store,
     Model,
+    Reference,
+    Vector,
+    PoolExhaustedError,
+    QuotaExceededError,
+    UniqueViolationError,
+    DeadlineExceededError,
+    CircuitOpenError,
+    deadline,
+    register_backup_sink,
 ]
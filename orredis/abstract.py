@@ -1,6 +1,6 @@
 """Module with abstract classes for orredis"""
 from datetime import datetime, timezone
-from typing import Dict, Any
+from typing import Dict, Any, Optional, Type
 
 from pydantic import BaseModel
 
@@ -72,3 +72,78 @@ class Model(BaseModel):
                 return False
 
         return True
+
+
+class Reference(str):
+    """
+    The primary key of a record of another collection, saved as it is instead of the record being nested.
+    Annotate a field with Reference[OtherModel] and read the record it refers to, only when it is needed,
+    with the resolve() method of the collection of OtherModel
+    """
+    referenced_model: Optional[Type[Model]] = None
+    __subclasses: Dict[Type[Model], Type["Reference"]] = {}
+
+    def __class_getitem__(cls, model: Type[Model]) -> Type["Reference"]:
+        if model not in cls.__subclasses:
+            cls.__subclasses[model] = type(f"Reference[{model.__name__}]", (cls,), {"referenced_model": model})
+        return cls.__subclasses[model]
+
+    @classmethod
+    def __get_validators__(cls):
+        yield cls.validate
+
+    @classmethod
+    def validate(cls, value):
+        if isinstance(value, Model):
+            raise TypeError(f"a reference should be the primary key of the record, got a {type(value).__name__}")
+        if not isinstance(value, (str, int)):
+            raise TypeError(f"a reference should be a string or an int, got {type(value).__name__}")
+        return cls(value)
+
+    @classmethod
+    def __modify_schema__(cls, field_schema: Dict[str, Any]):
+        field_schema["type"] = "string"
+        if cls.referenced_model is not None:
+            field_schema["reference"] = cls.referenced_model.__name__
+
+    def __repr__(self):
+        return f"{type(self).__name__}({super().__repr__()})"
+
+
+class Vector(list):
+    """
+    A list of floats of a fixed dimension e.g. an embedding, annotated as Vector[384]. Its values are also saved
+    as packed 32-bit floats in a hash indexed by RediSearch, so that the records whose vectors are nearest to
+    a given vector are found with the knn() method of their collection
+    """
+    dimension: Optional[int] = None
+    __subclasses: Dict[int, Type["Vector"]] = {}
+
+    def __class_getitem__(cls, dimension: int) -> Type["Vector"]:
+        if not isinstance(dimension, int) or dimension < 1:
+            raise TypeError(f"the dimension of a vector should be a positive int, got {dimension!r}")
+        if dimension not in cls.__subclasses:
+            cls.__subclasses[dimension] = type(f"Vector[{dimension}]", (cls,), {"dimension": dimension})
+        return cls.__subclasses[dimension]
+
+    @classmethod
+    def __get_validators__(cls):
+        yield cls.validate
+
+    @classmethod
+    def validate(cls, value):
+        if cls.dimension is None:
+            raise TypeError("a vector field should be annotated with its dimension e.g. Vector[384]")
+        if not isinstance(value, (list, tuple)):
+            raise TypeError(f"a vector should be a list of floats, got {type(value).__name__}")
+        if len(value) != cls.dimension:
+            raise ValueError(f"a vector should have {cls.dimension} items, got {len(value)}")
+        if not all(isinstance(v, (int, float)) and not isinstance(v, bool) for v in value):
+            raise TypeError("a vector should be a list of floats")
+        return cls(float(v) for v in value)
+
+    @classmethod
+    def __modify_schema__(cls, field_schema: Dict[str, Any]):
+        field_schema.update(type="array", items={"type": "number"}, format="vector")
+        if cls.dimension is not None:
+            field_schema["dimension"] = cls.dimension
@@ -0,0 +1,67 @@
+"""Module with utilities for Change Data Capture (CDC) i.e. exporting the changes to collections to external sinks"""
+import asyncio
+from typing import Any, Awaitable, Callable, Dict
+
+from orredis.orredis import AsyncCollection
+
+ChangeEvent = Dict[str, Any]
+
+
+class ChangeConsumer:
+    """
+    Tails the change stream of an AsyncCollection created with `track_changes=True`, passing each event,
+    in order, to the given async sink e.g. one that sends it to a Kafka producer.
+
+    Each event is a dictionary with the keys:
+    - "event_id": the id of the event in the stream e.g. "1664532000000-0". Events are ordered by it.
+    - "op": the kind of change i.e. "insert", "update" or "delete"
+    - "id": the primary key of the record that changed
+    - "before": the fields of the record before the change; empty for inserts
+    - "after": the fields of the record after the change; empty for deletes
+
+    The id of the last event handled by the sink is kept in `last_event_id`. Persist it and pass it back as
+    `last_event_id` to resume from where the consumer stopped, or pass an older id to replay events.
+    If the sink raises an error, the consumer stops and the event is not marked as handled.
+
+    :param collection: the async collection whose changes are to be consumed
+    :param sink: the async function called with each event
+    :param last_event_id: the id of the event after which to start consuming; default: "0-0" i.e. from the start
+    :param batch_size: the maximum number of events to fetch from redis at a time; default: 100
+    :param poll_interval: the number of seconds to wait before polling again when there are no new events;
+                        default: 0.1
+    """
+
+    def __init__(self,
+                 collection: AsyncCollection,
+                 sink: Callable[[ChangeEvent], Awaitable[Any]],
+                 last_event_id: str = "0-0",
+                 batch_size: int = 100,
+                 poll_interval: float = 0.1):
+        self.collection = collection
+        self.sink = sink
+        self.last_event_id = last_event_id
+        self.batch_size = batch_size
+        self.poll_interval = poll_interval
+
+    async def consume_once(self) -> int:
+        """
+        Passes the events that are currently available in the change stream to the sink
+
+        :return: the number of events handled
+        """
+        count = 0
+        while True:
+            events = await self.collection.get_changes(after=self.last_event_id, count=self.batch_size)
+            for event in events:
+                await self.sink(event)
+                self.last_event_id = event["event_id"]
+                count += 1
+
+            if len(events) < self.batch_size:
+                return count
+
+    async def run(self):
+        """Keeps passing new events to the sink until it is cancelled"""
+        while True:
+            if await self.consume_once() == 0:
+                await asyncio.sleep(self.poll_interval)
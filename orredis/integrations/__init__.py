@@ -0,0 +1 @@
+"""Package with the adapters that plug orredis stores into web frameworks e.g. FastAPI and Django"""
@@ -0,0 +1,72 @@
+"""Module with the result backend that lets Celery keep the results of its tasks in the redis of an orredis store"""
+from typing import Any, List, Optional
+
+from celery.backends.base import KeyValueStoreBackend
+from kombu.utils.encoding import bytes_to_str, str_to_bytes
+from kombu.utils.imports import symbol_by_name
+from kombu.utils.objects import cached_property
+
+from orredis.orredis import KeyValueNamespace, Store
+
+
+class OrredisBackend(KeyValueStoreBackend):
+    """
+    A Celery result backend that keeps the results of tasks in a raw key-value namespace of an orredis Store,
+    sharing the pool of the collections of the store.
+
+    The store is given in the configuration of the app as "orredis_store", either as is or as the dotted path
+    of a module-level store, otherwise a lazy store is created from the url of the backend.
+    The keys in redis are those of the redis backend of Celery e.g. "celery-task-meta-<task id>", unless
+    "orredis_namespace" is configured, in which case they start with it.
+
+    Example:
+        app = Celery("myapp", backend="orredis.integrations.celery:OrredisBackend")
+        app.conf.orredis_store = "myapp.stores.store"
+
+    :param url: the redis url of the store to create if none is configured
+    :param expires: the time after which the results expire; default: the result_expires of the app
+    """
+
+    key_t = bytes_to_str
+    supports_autoexpire = True
+    supports_native_join = False
+    implements_incr = True
+
+    def __init__(self, url: Optional[str] = None, expires: Any = None, **kwargs):
+        super().__init__(url=url, **kwargs)
+        self.expires = self.prepare_expires(expires, type=int)
+
+    @cached_property
+    def store(self) -> Store:
+        """The store configured as "orredis_store", or else a lazy store created from the url of the backend"""
+        store = self.app.conf.get("orredis_store")
+        if isinstance(store, str):
+            store = symbol_by_name(store)
+        if store is None:
+            if not self.url:
+                raise ValueError("either configure orredis_store or give the url of the redis of the store")
+            store = Store(url=self.url, lazy=True)
+        return store
+
+    @cached_property
+    def kv(self) -> KeyValueNamespace:
+        """The key-value namespace of the store in which the results of tasks are kept"""
+        return self.store.kv(self.app.conf.get("orredis_namespace") or "")
+
+    def get(self, key: str) -> Optional[bytes]:
+        return self.kv.get(key)
+
+    def mget(self, keys: List[str]) -> List[Optional[bytes]]:
+        return self.kv.mget(list(keys))
+
+    def set(self, key: str, value: Any, **kwargs):
+        self.kv.set(key, str_to_bytes(value), ttl=self.expires or None)
+
+    def delete(self, key: str):
+        self.kv.delete(key)
+
+    def incr(self, key: str) -> int:
+        return self.kv.incr(key)
+
+    def expire(self, key: str, value: Any):
+        self.kv.expire(key, value)
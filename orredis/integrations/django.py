@@ -0,0 +1,113 @@
+"""Module with the cache backend that lets Django cache its data and sessions in the redis of an orredis store"""
+import pickle
+from typing import Any, Dict, Iterable, List, Optional
+
+from django.core.cache.backends.base import DEFAULT_TIMEOUT, BaseCache
+from django.utils.module_loading import import_string
+
+from orredis.orredis import Store
+
+
+class OrredisCache(BaseCache):
+    """
+    A Django cache backend that keeps its values, pickled, in the redis of an orredis Store, sharing the pool
+    of the collections of the store.
+
+    The store is given in OPTIONS as "STORE", either as is or as the dotted path of a module-level store,
+    otherwise a lazy store is created from the LOCATION url. The keys in redis are "<KEY_PREFIX>:<version>:<key>"
+    as in the other cache backends of Django, and `clear()` removes only those starting with the KEY_PREFIX.
+
+    Example:
+        CACHES = {
+            "default": {
+                "BACKEND": "orredis.integrations.django.OrredisCache",
+                "KEY_PREFIX": "myapp",
+                "OPTIONS": {"STORE": "myapp.stores.store"},
+            }
+        }
+        SESSION_ENGINE = "django.contrib.sessions.backends.cache"
+
+    With SESSION_ENGINE set to the cache (or cached_db) engine of Django, the sessions are kept in it too.
+
+    :param location: the redis url of the store to create if none is given in OPTIONS
+    :param params: the settings of the cache
+    """
+
+    def __init__(self, location: str, params: Dict[str, Any]):
+        super().__init__(params)
+        options = params.get("OPTIONS", {})
+        store = options.get("STORE")
+        if isinstance(store, str):
+            store = import_string(store)
+        if store is None:
+            store = Store(url=location, lazy=True)
+
+        self._cache = store.get_cache(prefix=self.key_prefix)
+        self._pickle_protocol = options.get("PICKLE_PROTOCOL", pickle.HIGHEST_PROTOCOL)
+
+    def add(self, key: str, value: Any, timeout: Optional[float] = DEFAULT_TIMEOUT,
+            version: Optional[int] = None) -> bool:
+        self.validate_key(key)
+        return self._cache.set(key, self._dumps(value), ttl=self._get_ttl(timeout), version=self._get_version(version),
+                               only_if_missing=True)
+
+    def get(self, key: str, default: Any = None, version: Optional[int] = None) -> Any:
+        self.validate_key(key)
+        value = self._cache.get(key, version=self._get_version(version))
+        return default if value is None else pickle.loads(value)
+
+    def set(self, key: str, value: Any, timeout: Optional[float] = DEFAULT_TIMEOUT, version: Optional[int] = None):
+        self.validate_key(key)
+        self._cache.set(key, self._dumps(value), ttl=self._get_ttl(timeout), version=self._get_version(version))
+
+    def touch(self, key: str, timeout: Optional[float] = DEFAULT_TIMEOUT, version: Optional[int] = None) -> bool:
+        self.validate_key(key)
+        return self._cache.touch(key, ttl=self._get_ttl(timeout), version=self._get_version(version))
+
+    def delete(self, key: str, version: Optional[int] = None) -> bool:
+        self.validate_key(key)
+        return self._cache.delete(key, version=self._get_version(version))
+
+    def has_key(self, key: str, version: Optional[int] = None) -> bool:
+        self.validate_key(key)
+        return self._cache.has_key(key, version=self._get_version(version))
+
+    def get_many(self, keys: Iterable[str], version: Optional[int] = None) -> Dict[str, Any]:
+        keys = list(keys)
+        for key in keys:
+            self.validate_key(key)
+        values = self._cache.get_many(keys, version=self._get_version(version))
+        return {key: pickle.loads(value) for key, value in values.items()}
+
+    def set_many(self, data: Dict[str, Any], timeout: Optional[float] = DEFAULT_TIMEOUT,
+                 version: Optional[int] = None) -> List[str]:
+        for key in data:
+            self.validate_key(key)
+        items = {key: self._dumps(value) for key, value in data.items()}
+        self._cache.set_many(items, ttl=self._get_ttl(timeout), version=self._get_version(version))
+        return []
+
+    def delete_many(self, keys: Iterable[str], version: Optional[int] = None):
+        keys = list(keys)
+        for key in keys:
+            self.validate_key(key)
+        self._cache.delete_many(keys, version=self._get_version(version))
+
+    def clear(self):
+        self._cache.clear()
+
+    def _get_version(self, version: Optional[int]) -> int:
+        """Gets the given version of a key, or the VERSION of the cache if none is given"""
+        return self.version if version is None else version
+
+    def _get_ttl(self, timeout: Optional[float]) -> Optional[float]:
+        """
+        Converts the timeout given to a method of the cache into the ttl of its keys: None for keys that never
+        expire, and 0 for keys that expire at once i.e. are removed
+        """
+        if timeout is DEFAULT_TIMEOUT:
+            timeout = self.default_timeout
+        return None if timeout is None else max(0, timeout)
+
+    def _dumps(self, value: Any) -> bytes:
+        return pickle.dumps(value, self._pickle_protocol)
@@ -0,0 +1,129 @@
+"""Module with the providers of orredis stores and collections for the dependency injection of FastAPI"""
+import inspect
+from contextlib import asynccontextmanager
+from typing import Any, AsyncIterator, Callable, Dict, Optional, Type, Union
+
+from orredis.orredis import AsyncCollection, AsyncStore, Collection, Store
+
+AnyStore = Union[Store, AsyncStore]
+AnyCollection = Union[Collection, AsyncCollection]
+
+
+class StoreProvider:
+    """
+    Provides a store, and the collections in it, to the routes of a FastAPI app as dependencies i.e. callables
+    to pass to `fastapi.Depends`. The store is connected to redis when the app starts up and closed when it shuts down.
+
+    The store can be given as is e.g. `Store(url, lazy=True)` which does not connect to redis till it is needed,
+    or as a function that creates it when the app starts up. Either way, `setup` is called with it on startup,
+    if given, to create its collections.
+
+    Example:
+        provider = StoreProvider(lambda: AsyncStore(url="redis://localhost:6379/0"),
+                                 setup=lambda store: store.create_collection(Book, primary_key_field="title"))
+        app = FastAPI(lifespan=provider.lifespan)
+
+        @app.get("/books/{title}")
+        async def get_book(title: str, books: AsyncCollection = Depends(provider.collection(Book))):
+            return await books.get_one(title)
+
+    With versions of FastAPI that have no `lifespan`, call `provider.install(app)` instead.
+
+    :param store: the store, or the function that creates it on startup
+    :param setup: the function, sync or async, called with the store on startup e.g. to create its collections;
+                default: None
+    :param connect_on_startup: whether to PING redis on startup so that the app fails to start if redis
+                cannot be reached; default: True
+    """
+
+    def __init__(self,
+                 store: Union[AnyStore, Callable[[], AnyStore]],
+                 setup: Optional[Callable[[AnyStore], Any]] = None,
+                 connect_on_startup: bool = True):
+        self._store_factory = None if isinstance(store, (Store, AsyncStore)) else store
+        self._store = None if self._store_factory else store
+        self.setup = setup
+        self.connect_on_startup = connect_on_startup
+        self._collections: Dict[type, AnyCollection] = {}
+        self._is_started = False
+
+    async def startup(self):
+        """Creates the store if need be, sets it up and connects it to redis"""
+        if self._store_factory is not None:
+            self._store = self._store_factory()
+
+        if self.setup is not None:
+            result = self.setup(self._store)
+            if inspect.isawaitable(result):
+                await result
+
+        if self.connect_on_startup:
+            result = self._store.connect()
+            if inspect.isawaitable(result):
+                await result
+
+        self._is_started = True
+
+    async def shutdown(self):
+        """Closes the store, and forgets it if it was created by the provider"""
+        self._is_started = False
+        self._collections.clear()
+        if isinstance(self._store, AsyncStore):
+            await self._store.aclose()
+        elif self._store is not None:
+            self._store.close()
+
+        if self._store_factory is not None:
+            self._store = None
+
+    @asynccontextmanager
+    async def lifespan(self, app: Any) -> AsyncIterator[None]:
+        """
+        The lifespan of a FastAPI app using the store i.e. `FastAPI(lifespan=provider.lifespan)`
+
+        :param app: the FastAPI app
+        """
+        await self.startup()
+        try:
+            yield
+        finally:
+            await self.shutdown()
+
+    def install(self, app: Any):
+        """
+        Wires the startup and shutdown of the store into the given FastAPI app with its event handlers
+
+        :param app: the FastAPI app
+        """
+        app.add_event_handler("startup", self.startup)
+        app.add_event_handler("shutdown", self.shutdown)
+
+    def get_store(self) -> AnyStore:
+        """
+        The dependency that provides the store i.e. `Depends(provider.get_store)`
+
+        :return: the store
+        :raises RuntimeError: if the app has not started up or has shut down
+        """
+        if not self._is_started:
+            raise RuntimeError("the store is not started. Pass provider.lifespan to FastAPI() "
+                               "or call provider.install(app)")
+        return self._store
+
+    def collection(self, model: Type) -> Callable[[], AnyCollection]:
+        """
+        Creates the dependency that provides the collection of the given model i.e.
+        `Depends(provider.collection(Book))`
+
+        :param model: the model whose collection was created in the store, usually by `setup`
+        :return: the dependency, which gets the collection from the store once and then reuses it
+        """
+
+        def get_collection() -> AnyCollection:
+            store = self.get_store()
+            if model not in self._collections:
+                self._collections[model] = store.get_collection(model)
+            return self._collections[model]
+
+        get_collection.__name__ = f"get_{model.__name__.lower()}_collection"
+        return get_collection
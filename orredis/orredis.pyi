@@ -1,8 +1,83 @@
-from typing import Optional, Type, List, Dict, Any
+from contextvars import ContextVar
+from datetime import datetime, timedelta
+from typing import Optional, Type, List, Dict, Any, Union, Callable, Tuple, Awaitable, BinaryIO
 
 from .abstract import Model
 
 
+class PoolExhaustedError(ConnectionError):
+    """
+    Raised when no connection of the pool of a store becomes free before the timeout of the store
+    because all connections are in use e.g. by many concurrent calls
+
+    :param pool_size: the maximum number of connections in the pool
+    :param in_use: the number of connections that were in use when the error was raised
+    :param waited_ms: the time in milliseconds spent waiting for a connection
+    :param suggestion: a hint on how to avoid the error
+    """
+    pool_size: int
+    in_use: int
+    waited_ms: int
+    suggestion: str
+
+
+class QuotaExceededError(Exception):
+    """
+    Raised when an insert or update through a collection would take the usage of the quota of its namespace
+    beyond the limits set with `set_quota()`
+    """
+
+
+class UniqueViolationError(Exception):
+    """
+    Raised when an insert or update through a collection would give a unique field of a record a value
+    that another record of the collection already has
+    """
+
+
+class DeadlineExceededError(TimeoutError):
+    """
+    Raised when the deadline set in `deadline` passes before a call gets a connection to redis
+    """
+
+
+class CircuitOpenError(ConnectionError):
+    """
+    Raised when the circuit breaker of a store is open i.e. as many calls in a row as its `circuit_breaker_threshold`
+    failed to get a connection to redis, so that calls fail fast during an outage instead of each waiting for
+    the timeout of the store
+    """
+
+
+deadline: ContextVar[Optional[Union[datetime, float]]]
+"""
+The deadline, a datetime or unix timestamp in seconds, by which the calls to stores and collections made in the current
+context e.g. a web request, should be done. The wait for a connection to redis is cut short at the deadline, and
+calls made after it raise a `DeadlineExceededError` without reaching redis. Commands already sent to redis are
+not interrupted so that their connections are not left with unread replies. Async calls use the deadline of the
+context in which they are called; default: None i.e. calls have no deadline e.g.
+
+    token = deadline.set(time.time() + 0.5)
+    try:
+        book = collection.get_one("foo")
+    finally:
+        deadline.reset(token)
+"""
+
+
+def register_backup_sink(scheme: str, opener: Callable[[str], BinaryIO]) -> None:
+    """
+    Registers the function that opens the sinks of the backups made with `Collection.backup()` to urls of the given
+    scheme, in place of any registered before e.g.
+
+        register_backup_sink("s3", lambda url: s3fs.S3FileSystem().open(url, "wb"))
+
+    :param scheme: the scheme of the urls e.g. "s3"
+    :param opener: the function that takes the url and returns a writable binary file-like object, which is closed
+            once the backup is written into it
+    """
+
+
 class Collection:
     """
     The Collection represents a group of similar records within redis
@@ -11,284 +86,3020 @@ class Collection:
     from redis
     """
 
-    def add_one(self, item: Model, ttl: Optional[int]) -> None:
+    def add_one(self, item: Model, ttl: Optional[Union[timedelta, datetime, float]] = None,
+                profile: bool = False) -> Union[str, Tuple[str, Dict[str, float]]]:
         """
         Adds a single Model instance to the collection. The model instance should
         be of the same type as the model used to get this collection.
 
         :param item: the model object to add
         :param ttl: the optional time-to-live for this item in redis; default: None (i.e. never expires).
-                    if however, the default_ttl was set on the store, it will default to that
+                    if however, the default_ttl was set on the store, it will default to that.
+                    It is a timedelta or a number of seconds, or a datetime at which to expire,
+                    applied with millisecond precision. Naive datetimes are in the local timezone
+        :param profile: whether to also return the timing breakdown of the call, to find out whether its time is
+               spent in the models or in redis: a dictionary of "serialize_ms" spent turning models into commands,
+               "network_ms" spent getting connections and waiting for redis, "parse_ms" spent parsing the replies,
+               "construct_ms" spent constructing models, and "total_ms" of the whole call; default: False
+        :return: the primary key of the record added, as saved in redis, e.g. the one generated by the id_generator
+                of the collection if the item had none, or if profile is True, a tuple of it and the timing breakdown
+        """
+
+    def add_one_if_not_exists(self, item: Model, ttl: Optional[Union[timedelta, datetime, float]] = None) -> bool:
+        """
+        Adds a single Model instance to the collection only if no record has its primary key, e.g. for create-only
+        flows. The creation of the record is claimed atomically first, so of many concurrent calls for the same
+        primary key, only one adds it, with no need to call get_one() before.
+
+        :param item: the model object to add
+        :param ttl: the optional time-to-live for this item in redis; default: None (i.e. never expires).
+                    if however, the default_ttl was set on the store, it will default to that.
+                    It is a timedelta or a number of seconds, or a datetime at which to expire,
+                    applied with millisecond precision. Naive datetimes are in the local timezone
+        :return: True if the record was added, or False if a record with its primary key exists or is being added
         """
 
-    def add_many(self, items: List[Model], ttl: Optional[int]) -> None:
+    def add_many(self, items: List[Model], ttl: Optional[Union[timedelta, datetime, float]] = None,
+                 profile: bool = False) -> Union[List[str], Tuple[List[str], Dict[str, float]]]:
         """
         Adds a list of Model instances into this collection. The model instances should
-        be of the same type as the model used to get this collection.
+        be of the same type as the model used to get this collection. They are written in batches of
+        at most the max_batch_bytes of the store, so if a batch fails, the batches before it are kept.
 
         :param items: the list of model objects to add
         :param ttl: the optional time-to-live for all these items in redis; default: None (i.e. never expires)
-                    if however, the default_ttl was set on the store, it will default to that
+                    if however, the default_ttl was set on the store, it will default to that.
+                    It is a timedelta or a number of seconds, or a datetime at which to expire,
+                    applied with millisecond precision. Naive datetimes are in the local timezone
+        :param profile: whether to also return the timing breakdown of the call, to find out whether its time is
+               spent in the models or in redis: a dictionary of "serialize_ms" spent turning models into commands,
+               "network_ms" spent getting connections and waiting for redis, "parse_ms" spent parsing the replies,
+               "construct_ms" spent constructing models, and "total_ms" of the whole call; default: False
+        :return: the list of the primary keys of the records added, as saved in redis, in the order of the items,
+                or if profile is True, a tuple of it and the timing breakdown
+        """
+
+    def generate_fake(self,
+                      n: int,
+                      seed: Optional[int] = None,
+                      ttl: Optional[Union[timedelta, datetime, float]] = None) -> None:
+        """
+        Adds `n` records of random but valid values generated from the schema of this collection's model e.g. to fill
+        the collection for load tests or demos. Strings are letters and digits, ints are below 1000000, dates
+        and datetimes are between 2000 and 2030, lists and dicts have at most 3 items, and nested models are
+        generated too, up to the nested_depth of the collection, beyond which they are None. Primary keys are unique
+        among the generated records. The records are added in batches of 1000.
+
+        :param n: the number of records to add
+        :param seed: the seed of the random values, so that the same records are generated each time it is given;
+                    default: None i.e. different records each time
+        :param ttl: the optional time-to-live for all these records in redis, as in `add_many()`
         """
 
-    def update_one(self, id: str, data: Dict[str, Any], ttl: Optional[int]) -> None:
+    def update_one(self, id: str, data: Dict[str, Any], ttl: Optional[Union[timedelta, datetime, float]] = None) -> None:
         """
         Updates the model instance in redis that has the given id
 
         :param id: the id of the record to update
-        :param data: the new changes to add to the record
+        :param data: the new changes to add to the record. Fields of nested records can be addressed by their dotted
+                    paths e.g. {"address.city": "Kigali"} to update them in the nested record directly; nothing
+                    is updated through a path whose nested record does not exist
+        :param ttl: the optional time-to-live for all these items in redis; default: None (i.e. never expires)
+                    if however, the default_ttl was set on the store, it will default to that.
+                    It is a timedelta or a number of seconds, or a datetime at which to expire,
+                    applied with millisecond precision. Naive datetimes are in the local timezone
+        """
+
+    def update_many(self, updates: Dict[str, Dict[str, Any]],
+                    ttl: Optional[Union[timedelta, datetime, float]] = None) -> None:
+        """
+        Updates the model instances in redis that have the given ids, each with its own changes, in one network
+        request. This is more efficient than calling update_one() for each of them
+
+        :param updates: the map of the id of each record to update to the new changes to add to it. As in
+                    update_one(), fields of nested records can be addressed by their dotted paths
         :param ttl: the optional time-to-live for all these items in redis; default: None (i.e. never expires)
-                    if however, the default_ttl was set on the store, it will default to that
+                    if however, the default_ttl was set on the store, it will default to that.
+                    It is a timedelta or a number of seconds, or a datetime at which to expire,
+                    applied with millisecond precision. Naive datetimes are in the local timezone
+        """
+
+    def exists(self, id: str) -> bool:
         """
+        Checks whether the record of the given id exists in the collection
 
-    def get_one(self, id: str) -> Model:
+        :param id: the primary key of the record
+        :return: True if the record exists else False
+        """
+
+    def exists_many(self, ids: List[str]) -> List[bool]:
+        """
+        Checks whether the records of the given ids exist in the collection with one EXISTS per id in one round trip,
+        e.g. to reconcile an external list of ids with the collection
+
+        :param ids: the primary keys of the records
+        :return: a boolean per id, in the order of the ids, that is True if its record exists else False
+        """
+
+    def get_one(self, id: str, profile: bool = False) -> Union[Model, Tuple[Model, Dict[str, float]]]:
         """
         Retrieves one record of the given id or None if it does not exist
 
         :param id: the id of the model record to return
-        :return: the model object for the given id in this collection
+        :param profile: whether to also return the timing breakdown of the call, to find out whether its time is
+               spent in the models or in redis: a dictionary of "serialize_ms" spent turning models into commands,
+               "network_ms" spent getting connections and waiting for redis, "parse_ms" spent parsing the replies,
+               "construct_ms" spent constructing models, and "total_ms" of the whole call; default: False
+        :return: the model object for the given id in this collection, or if profile is True,
+               a tuple of it and the timing breakdown
         """
 
-    def get_many(self, ids: List[str]) -> List[Model]:
+    def get_many(self, ids: List[str],
+                 profile: bool = False) -> Union[List[Model], Tuple[List[Model], Dict[str, float]]]:
         """
         Retrieves a list of records in this collection corresponding to the ids passed
 
         :param ids: the list of ids whose records are to be returned
+        :param profile: whether to also return the timing breakdown of the call, to find out whether its time is
+               spent in the models or in redis: a dictionary of "serialize_ms" spent turning models into commands,
+               "network_ms" spent getting connections and waiting for redis, "parse_ms" spent parsing the replies,
+               "construct_ms" spent constructing models, and "total_ms" of the whole call; default: False
         :return: the list of model objects that correspond to the list of ids. Non-existent ids
+               are simply skipped. If profile is True, it is returned in a tuple with the timing breakdown
+        """
+
+    def repair(self, fix: Optional[List[str]] = None, max_ops_per_sec: Optional[float] = None) -> int:
+        """
+        Rewrites the values of the records of this collection that are in known broken or legacy formats
+        in the format orredis writes
+
+        :param fix: the fixes to apply: "bool_case" lowercases booleans e.g. "True", "datetime_format" rewrites
+               ISO 8601 datetimes e.g. "2022-10-01T08:30:00Z" and those without a utc offset (taken to be in UTC),
+               and "none_sentinel" removes "None", "null", "nil" and empty values of fields that are neither strings
+               nor nested models, so that they get their defaults. All of them are applied if none are given
+        :param max_ops_per_sec: the maximum number of records to read and repair per second, so that other clients
+                of a busy redis are not slowed down; default: None i.e. as fast as possible
+        :return: the number of records repaired
+        """
+
+    def compact(self, max_ops_per_sec: Optional[float] = None, background: bool = False) -> Optional[int]:
+        """
+        Removes the fields that are no longer in the model of this collection from its records e.g. after many
+        schema changes. Fields that another writer still saves with a newer version of the model are removed too,
+        so it should be run once all writers use the current model.
+
+        :param max_ops_per_sec: the maximum number of records to compact per second, so that other clients
+                of a busy redis are not slowed down; default: None i.e. as fast as possible
+        :param background: whether to compact the records in a background task of the store named
+                "compact:<collection>" e.g. "compact:Book", returning right away. Its status, and the error that
+                stopped it if any, are got with the `background_tasks()` of the store. A compaction cancelled with
+                `cancel_background()` is left to complete; default: False
+        :return: the number of records compacted, or None if they are compacted in the background
+        """
+
+    def get_many_raw(self, ids: List[str]) -> List[Dict[str, Any]]:
+        """
+        Retrieves the records of the given ids as dictionaries, with the records nested in them also
+        as dictionaries, without constructing any models. This is the fastest way to read records
+        that are e.g. encoded as JSON right away
+
+        :param ids: the list of ids whose records are to be returned
+        :return: the list of dictionaries of the records that correspond to the list of ids. Non-existent ids
                are simply skipped
         """
 
-    def get_all(self) -> List[Model]:
+    def resolve(self, reference: Optional[str]) -> Optional[Model]:
+        """
+        Retrieves the record of this collection that the given reference refers to i.e. the value of a field
+        annotated with Reference[Model] in another collection, which only holds the primary key of the record.
+        Referenced records are thus only read when they are needed, unlike nested records
+
+        :param reference: the reference, or a primary key of this collection
+        :return: the model object referred to, or None if it does not exist or the reference is None
+        :raises ValueError: if the reference refers to a record of another model
+        """
+
+    def children_of(self, parent_id: str, child_collection: "Collection", fk_field: str) -> List[Model]:
+        """
+        Retrieves the records of the child collection whose foreign key field holds the given primary key of
+        a record of this collection e.g. user_collection.children_of("u1", order_collection, "user_id") gets
+        all the orders of the user "u1". The cost depends on the number of children, not the size of
+        the child collection.
+
+        :param parent_id: the primary key of the parent record in this collection
+        :param child_collection: the collection of the child records
+        :param fk_field: the field of the child records that holds the primary key of their parent.
+                It should be among the foreign_keys of the child collection set in create_collection()
+        :return: the list of model objects of the children, in no particular order
+        """
+
+    def find_by(self, field: str, value: Any) -> List[Model]:
+        """
+        Retrieves the records of this collection whose field holds the given value e.g.
+        user_collection.find_by("email", "jane@example.com"). The field should be among the indexed_fields
+        or the foreign_keys set in create_collection(). The cost depends on the number of matching records,
+        not the size of the collection.
+
+        :param field: the indexed field
+        :param value: the value of the field in the records to get
+        :return: the list of model objects whose field holds the value, in no particular order
+        :raises ValueError: if the field is not indexed
+        """
+
+    def find_by_index(self, fields: Tuple[str, ...], values: Tuple[Any, ...]) -> List[Model]:
+        """
+        Retrieves the records of this collection whose fields hold the given values e.g.
+        shop_collection.find_by_index(("country", "city"), ("UG", "Kampala")). The fields should be those of one of
+        the compound_indexes set in create_collection(), in any order. The cost depends on the number of matching
+        records, not the size of the collection.
+
+        :param fields: the fields of the compound index
+        :param values: the values of the fields in the records to get, in the order of the fields
+        :return: the list of model objects whose fields hold the values, in no particular order
+        :raises ValueError: if there is no compound index of the fields or the numbers of fields and values differ
+        """
+
+    def find(self, cache_ttl: Optional[Union[timedelta, float]] = None, **lookups: Any) -> List[Model]:
+        """
+        Retrieves the records of this collection that match all the given lookups e.g.
+        post_collection.find(tags__contains="rust", author="jane"). Each lookup is either `field=value` for one of
+        the indexed_fields, `field__contains=value` for one of the indexed_list_fields set in create_collection(),
+        matching the records whose list holds the value, or `field__isnull=True` for a field indexed with
+        create_null_index(), matching the records that do not have the field, e.g. those saved before it was added
+        to the model, or whose value is None. `field__isnull=False` excludes those records instead.
+        The cost depends on the number of matching records, not the size of the collection, except when all
+        the lookups are `field__isnull=False`, in which case all the ids of the collection are read.
+        If cache_ttl is given, the ids of the matching records are cached in redis for that time, so that identical
+        calls within it skip the indexes and only read the records, which are thus up to date, but may no longer
+        match the lookups, while the records added since then are left out.
+
+        :param cache_ttl: the time, as a timedelta or a number of seconds, for which the ids of the matching records
+                are cached, for the identical calls with a cache_ttl; default: None i.e. they are neither cached
+                nor read from the cache. Empty results are not cached
+        :param lookups: the lookups, by field or by field followed by "__contains" or "__isnull", to the values
+                to match
+        :return: the list of model objects that match all the lookups, in no particular order
+        :raises ValueError: if no lookup is given or a field is not indexed for its lookup
+        """
+
+    def explain_find(self, **lookups: Any) -> Dict[str, Any]:
+        """
+        Explains how find() gets the records matching the given lookups e.g.
+        post_collection.explain_find(tags__contains="rust", author="jane"), to diagnose slow ones, without reading
+        the records. Each lookup is served by its index, whose ids are the candidates. The lookup with the fewest
+        candidates is chosen to drive the matching, its candidates being checked against the ids of the others,
+        so a slow find() usually lacks a selective lookup. When all the lookups are `field__isnull=False`,
+        there is no index to drive the matching, so it falls back to scanning the ids of the whole collection.
+
+        :param lookups: the lookups, as given to find()
+        :return: a dictionary with the keys: "lookups", the lookups, each as a dictionary of its "lookup" as text,
+                the kind of "index" serving it i.e. "field", "list" or "null", its "role" i.e. "match", or "exclude"
+                for `field__isnull=False`, and the number of "candidates" in its index; "scan", whether the ids
+                of the whole collection are scanned; "chosen_index", the lookup chosen to drive the matching, or None
+                if the ids are scanned; "candidates", the number of ids checked against the lookups; and "matches",
+                the number of records matching all of them
+        :raises ValueError: if no lookup is given or a field is not indexed for its lookup
+        """
+
+    def ids(self) -> List[str]:
+        """
+        Retrieves the ids of all records in this collection, scanning their keys without reading the records,
+        so that they can be listed cheaply before deciding which to get
+
+        :return: the list of the primary keys of the records, in no particular order
+        """
+
+    def sample(self, n: int) -> List[Model]:
+        """
+        Picks n records of this collection at random e.g. to spot-check the quality of the data of a big collection.
+        The ids of all the records are scanned without reading the records, and only the records picked are read.
+
+        :param n: the number of records to pick
+        :return: the list of the records picked, which has all the records in a random order if there are not more
+                than n
+        """
+
+    def get_all(self, on_error: str = "raise",
+                filter: Optional[Dict[str, Any]] = None, skip: int = 0,
+                limit: Optional[int] = None, profile: bool = False, order_by: Optional[str] = None,
+                desc: bool = False) -> Union[List[Model], Tuple[Any, ...]]:
         """
         Retrieves a list of all records in this collection at ago
 
-        :return: the list of model objects in this collection
+        :param on_error: what to do with records that cannot be read e.g. because they are corrupt:
+               "raise" their error, "skip" them, or "collect" them with their errors
+        :param filter: the values that the fields of the records returned should be equal to e.g. {"in_stock": True}.
+               The records are filtered in redis, comparing the values as saved, so only the matching records are
+               transferred. Only non-nested, non-counter fields can be filtered on; default: None i.e. all records
+        :param skip: the number of matching records to skip, in the order in which they are scanned, which is stable
+               as long as the collection does not change, or in that of order_by; default: 0
+        :param limit: the maximum number of records to return; default: None i.e. all of them. Only the records
+               returned are read, so large collections can be read a page at a time
+        :param profile: whether to also return the timing breakdown of the call, to find out whether its time is
+               spent in the models or in redis: a dictionary of "serialize_ms" spent turning models into commands,
+               "network_ms" spent getting connections and waiting for redis, "parse_ms" spent parsing the replies,
+               "construct_ms" spent constructing models, and "total_ms" of the whole call; default: False
+        :param order_by: the non-nested field by whose values the records are sorted, in redis, before they are
+               skipped and limited. Int and float fields are compared as numbers, and the others as saved e.g. dates
+               in time, the records without a value for the field coming last. If the field has an index made with
+               create_index(), the page is read from the index instead of sorting the whole collection;
+               default: None i.e. the order in which they are scanned
+        :param desc: whether the records are in the descending order of the values of order_by; default: False
+        :return: the list of model objects in this collection, or if on_error is "collect",
+               a tuple of that list and a list of dictionaries of the "id" (None if unknown) and the "error"
+               of each record that could not be read. If profile is True, it is returned in a tuple with
+               the timing breakdown
+        :raises ValueError: if order_by is a nested field, or desc is given without order_by
+        """
+
+    def snapshot(self, tag: str, max_ops_per_sec: Optional[float] = None) -> int:
+        """
+        Takes a snapshot of this collection under the given tag i.e. copies all its records as they currently are,
+        replacing any snapshot of the same tag, so that they can be read later with read_snapshot() e.g. to debug
+        or to compare them with later records. The copies do not expire and are kept until delete_snapshot()
+        is called. The records are copied one SCAN batch at a time, so writes made during the copy may or may not
+        be in the snapshot.
+
+        :param tag: the tag of the snapshot. It should be non-empty and have none of the characters *?[]\\%
+        :param max_ops_per_sec: the maximum number of records to scan per second; default: None i.e. no limit
+        :return: the number of records in the snapshot
+        """
+
+    def read_snapshot(self, tag: str,
+                on_error: str = "raise") -> Union[List[Model], Tuple[List[Model], List[Dict[str, Any]]]]:
+        """
+        Retrieves a list of all records in the snapshot of the given tag of this collection, as they were when
+        the snapshot was taken. Only the records of this collection are copied into a snapshot, so their nested
+        records are as they currently are
+
+        :param tag: the tag of the snapshot
+        :param on_error: what to do with records that cannot be read e.g. because they are corrupt:
+               "raise" their error, "skip" them, or "collect" them with their errors
+        :return: the list of model objects in the snapshot, which is empty if there is no such snapshot,
+               or if on_error is "collect", a tuple of that list and a list of dictionaries of the "id"
+               (None if unknown) and the "error" of each record that could not be read
+        """
+
+    def delete_snapshot(self, tag: str) -> int:
+        """
+        Deletes the snapshot of the given tag of this collection
+
+        :param tag: the tag of the snapshot
+        :return: the number of records the snapshot had
+        """
+
+    def export_to(self, path: str, chunk_size: int = 1000) -> int:
+        """
+        Exports the records of this collection, with their counters, into a file at the given path e.g. to back it up
+        or to move it to another store. The records are written in chunks, each with the SHA-1 checksum of its
+        records, so that a corrupted export is detected when it is imported. Nested records are exported with their
+        own collections; ttls are not exported. Compressed values are exported decompressed.
+
+        :param path: the path of the file, which is replaced if it exists
+        :param chunk_size: the number of records in each chunk, at least 1; default: 1000
+        :return: the number of records exported
+        """
+
+    def import_from(self, path: str, resume: bool = True) -> int:
+        """
+        Imports the records of an export of this collection made by `export_to()` one chunk at a time, as if they were
+        added with add_many(). Each chunk is checked against its checksum before it is imported. The number of chunks
+        imported is recorded in a file next to the export, "<path>.progress", which is removed once the import is
+        complete, so that an interrupted import of a large export can carry on where it stopped.
+
+        :param path: the path of the export
+        :param resume: whether to skip the chunks imported before the import was interrupted, if it was;
+                default: True
+        :return: the number of records imported
+        :raises ValueError: if the file is not an export of this collection, or it is truncated or corrupted
+        """
+
+    def backup(self, dest: Union[str, BinaryIO], chunk_size: int = 1000) -> int:
+        """
+        Backs this collection up i.e. exports its records as `export_to()` does, streaming the export in pieces into
+        the destination without writing it into a local file first. A url e.g. "s3://bucket/path", is opened with
+        the function registered for its scheme with `register_backup_sink()`, or with fsspec if none is, and closed
+        once the backup is written. The backup can be downloaded and restored with `import_from()`.
+
+        :param dest: a writable binary file-like object, which is left open; a url of object storage; or the path
+                of a local file
+        :param chunk_size: the number of records in each chunk, at least 1; default: 1000
+        :return: the number of records backed up
+        :raises ValueError: if no sink can be opened for the scheme of the url
+        """
+
+    def iter_all(self, batch_size: int = 100) -> "RecordIterator":
+        """
+        Iterates over the records of this collection, scanning the collection one batch at a time as the iterator
+        is advanced, so that only a batch of records is held in memory at a time. Unlike get_all(), this can go
+        through collections of hundreds of thousands of records. Records added, changed or deleted during the
+        iteration may be missed or returned twice, as with SCAN.
+
+        :param batch_size: the number of keys looked at by each SCAN, at least 1; default: 100
+        :return: the iterator of the model objects in this collection, in the order in which SCAN finds them
+        """
+
+    def parallel_iter(self, workers: int = 4) -> List[Model]:
+        """
+        Retrieves a list of all records in this collection, splitting the ids found by scanning the collection
+        into a shard for each worker, which reads its shard in batches on its own connection. This is faster than
+        get_all() for large collections and does not block redis for the whole read. Records added or deleted
+        during the read may or may not be included.
+
+        :param workers: the number of workers, at least 1 and at most the pool_size of the store; default: 4
+        :return: the list of model objects in this collection, in no particular order
         """
 
-    def get_one_partially(self, id: str, fields: List[str]) -> Dict[str, Any]:
+    def get_one_partially(self, id: str, fields: List[str], nested_as_models: bool = False) -> Dict[str, Any]:
         """
         Retrieves a dictionary containing the provided fields from the record of the given id
 
         :param id: the id of the record whose data is to be retrieved
-        :param fields: the list of fields to be returned in the data
+        :param fields: the list of fields to be returned in the data. Fields of nested records can be given by their
+                    dotted paths e.g. "address.city", returning the nested record as a dict of only those fields
+        :param nested_as_models: whether the nested records requested by dotted paths are returned as instances of
+                    their models, constructed without validation, instead of dicts. Defaults to False
         :return: the dict with the given fields as keys and the values got from the record of the given id
         """
 
-    def get_many_partially(self, ids: List[str], fields: List[str]) -> List[Dict[str, Any]]:
+    def get_field(self, id: str, field: str) -> Any:
+        """
+        Retrieves the value of one field of the record of the given id e.g. `get_field("Jane Eyre", "in_stock")`,
+        reading only that value with HGET and converting it to the type of the field. It is cheaper than
+        get_one_partially() for a single field
+
+        :param id: the id of the record
+        :param field: the name of the field; only non-nested fields are supported
+        :return: the value of the field, or None if the record or its value is not in redis
+        :raises KeyError: if the field is not a field of the model
+        :raises ValueError: if the field is a nested field
+        """
+
+    def get_many_partially(self,
+                           ids: List[str],
+                           fields: List[str],
+                           nested_as_models: bool = False) -> List[Dict[str, Any]]:
         """
         Retrieves a list of dictionaries for records of the given ids,
         only returning the specified fields for each record
 
         :param ids: the list of ids of the records to be queried
-        :param fields: the fields to be returned in each item
+        :param fields: the fields to be returned in each item. Fields of nested records can be given by their
+                    dotted paths e.g. "address.city", returning the nested record as a dict of only those fields
+        :param nested_as_models: whether the nested records requested by dotted paths are returned as instances of
+                    their models, constructed without validation, instead of dicts. Defaults to False
         :return: the list of dicts, each with the given fields as keys and the values for each record returned.
                non-existent ids are ignored
         """
 
-    def get_all_partially(self, fields: List[str]) -> List[Dict[str, Any]]:
+    def get_all_partially(self, fields: List[str], nested_as_models: bool = False, skip: int = 0,
+                          limit: Optional[int] = None, order_by: Optional[str] = None,
+                          desc: bool = False) -> List[Dict[str, Any]]:
         """
         Retrieves a list of dictionaries for all records in the store,
         only returning the specified fields for each record
 
-        :param fields: the fields to be returned in each item
+        :param fields: the fields to be returned in each item. Fields of nested records can be given by their
+                    dotted paths e.g. "address.city", returning the nested record as a dict of only those fields
+        :param nested_as_models: whether the nested records requested by dotted paths are returned as instances of
+                    their models, constructed without validation, instead of dicts. Defaults to False
+        :param skip: the number of records to skip, in the order in which they are scanned, which is stable as long
+                    as the collection does not change, or in that of order_by; default: 0
+        :param limit: the maximum number of records to return; default: None i.e. all of them. Only the records
+                    returned are read, so large collections can be read a page at a time
+        :param order_by: the non-nested field by whose values the records are sorted, in redis, before they are
+                    skipped and limited, as in get_all(); default: None i.e. the order in which they are scanned
+        :param desc: whether the records are in the descending order of the values of order_by; default: False
         :return: the list of dicts, each with the given fields as keys and the values for each record returned
+        :raises ValueError: if order_by is a nested field, or desc is given without order_by
         """
 
-    def delete_many(self, ids: List[str]) -> None:
+    def track_cardinality(self, field: str, max_ops_per_sec: Optional[float] = None) -> None:
         """
-        Removes all records belonging to the given ids
+        Starts tracking the approximate number of distinct values of the given field
+        using a redis HyperLogLog that is updated on every write, by any store.
+        The values of the records already in the collection are added to it.
 
-        :param ids: the ids of the records to be removed
+        :param field: the name of the non-nested field to track
+        :param max_ops_per_sec: the maximum number of existing records to scan per second while adding their values,
+                so that other clients of a busy redis are not slowed down; default: None i.e. as fast as possible
         """
 
-class AsyncCollection:
-    """
-    The AsyncCollection represents a group of similar records within redis
-    e.g. records of model Car will be in the Car collection.
-    This has all the methods for manipulating data as well as retrieving it
-    from redis but asynchronously. For the synchronous API, use Collection
-    """
+    def approx_distinct(self, field: str) -> int:
+        """
+        Returns the approximate number of distinct values the given field has had in this collection
+        since it started being tracked by track_cardinality(). The standard error is about 0.81%.
+        Deleted records are still counted.
 
-    async def add_one(self, item: Model, ttl: Optional[int]) -> None:
+        :param field: the name of the tracked field
+        :return: the approximate number of distinct values, 0 if the field is not tracked
         """
-        Adds a single Model instance to the collection. The model instance should
-        be of the same type as the model used to get this collection.
 
-        :param item: the model object to add
-        :param ttl: the optional time-to-live for this item in redis; default: None (i.e. never expires).
-                    if however, the default_ttl was set on the store, it will default to that
+    def distinct(self, field: str) -> List[Any]:
         """
+        Returns the distinct values of the given field across this collection, in no particular order.
+        They are collected by a Lua script as it scans the collection in redis, so only the distinct values
+        are sent back, yet the cost of the call still grows with the size of the collection.
+        Records that have no value for the field are left out.
 
-    async def add_many(self, items: List[Model], ttl: Optional[int]) -> None:
+        :param field: the name of the field, which should not be nested
+        :return: the list of the distinct values of the field
+        :raises KeyError: if the field is not a field of the model
+        :raises ValueError: if the field is a nested field
         """
-        Adds a list of Model instances into this collection. The model instances should
-        be of the same type as the model used to get this collection.
 
-        :param items: the list of model objects to add
-        :param ttl: the optional time-to-live for all these items in redis; default: None (i.e. never expires)
-                    if however, the default_ttl was set on the store, it will default to that
+    def aggregate(self, field: str, op: str) -> Optional[Union[int, float]]:
         """
+        Computes the sum, average, minimum or maximum of the values of the given int or float field across
+        this collection, in a Lua script as it scans the collection in redis, so that only the result is sent back
+        instead of every record. Records whose value of the field is None are left out.
 
-    async def update_one(self, id: str, data: Dict[str, Any], ttl: Optional[int]) -> None:
+        :param field: the name of the int or float field
+        :param op: the operation i.e. "sum", "avg", "min" or "max"
+        :return: the result, which is an int for the sum, minimum or maximum of an int field and a float otherwise.
+                The sum of no values is 0 while their average, minimum and maximum are None
+        :raises KeyError: if the field is not a field of the model
+        :raises ValueError: if the field is not an int or float field or the op is unknown
         """
-        Updates the model instance in redis that has the given id
 
-        :param id: the id of the record to update
-        :param data: the new changes to add to the record
-        :param ttl: the optional time-to-live for all these items in redis; default: None (i.e. never expires)
-                    if however, the default_ttl was set on the store, it will default to that
+    def count_by(self, field: str) -> Dict[Any, int]:
         """
+        Counts the records of this collection by the values of the given field e.g. to get a histogram of statuses
+        or categories. The records are grouped in a Lua script as it scans the collection in redis, so that only
+        the counts are sent back instead of every record. Records that have no value for the field are left out.
 
-    async def get_one(self, id: str) -> Model:
+        :param field: the name of the field, which should not be a nested, list, vector or dict field
+        :return: a dictionary of each value of the field to the number of records that have it
+        :raises KeyError: if the field is not a field of the model
+        :raises ValueError: if the field is a nested, list, vector or dict field
         """
-        Retrieves one record of the given id or None if it does not exist
 
-        :param id: the id of the model record to return
-        :return: the model object for the given id in this collection
+    def count_where(self, **filters: Any) -> int:
         """
+        Counts the records whose fields are equal to the given values e.g. `count_where(author="Jane")`.
+        The records are counted in a Lua script as it scans the collection in redis, so that only the count
+        is sent back instead of every record.
 
-    async def get_many(self, ids: List[str]) -> List[Model]:
+        :param filters: the values the fields of the records to count should be equal to; only non-nested,
+            non-counter fields can be filtered on. All records are counted if none is given
+        :return: the number of records whose fields are equal to the values
+        :raises KeyError: if a field is not a field of the model
+        :raises ValueError: if a field cannot be filtered on
         """
-        Retrieves a list of records in this collection corresponding to the ids passed
 
-        :param ids: the list of ids whose records are to be returned
-        :return: the list of model objects that correspond to the list of ids. Non-existent ids
-               are simply skipped
+    def create_index(self, field: str, max_ops_per_sec: Optional[float] = None) -> int:
         """
+        Starts maintaining an index of the records of this collection by the values of the given field,
+        using a redis sorted set that is updated on every write, so that find_between() can get the records
+        whose values are in a range. The index is registered in redis, so that the writes of all stores and
+        processes update it, then the records already in the collection are indexed, an earlier index of the field
+        being brought up to date. The index cannot be read until this returns. Records removed by their ttl are
+        left in the index but are never returned.
 
-    async def get_all(self) -> List[Model]:
+        :param field: the name of the int, float, date or datetime field to index. It cannot be a counter field
+        :param max_ops_per_sec: the maximum number of existing records to read per second while indexing them,
+                so that other clients of a busy redis are not slowed down; default: None i.e. as fast as possible
+        :return: the number of existing records indexed
         """
-        Retrieves a list of all records in this collection at ago
 
-        :return: the list of model objects in this collection
+    def create_null_index(self, field: str, max_ops_per_sec: Optional[float] = None) -> int:
         """
+        Starts maintaining an index of the records of this collection that do not have the given field,
+        or whose value of it is None, using a redis set that is updated on every write, so that
+        find(field__isnull=True) can get them e.g. to backfill the records saved before the field was added
+        to the model. The index is registered in redis, so that the writes of all stores and processes update
+        it, then the records already in the collection are indexed, an earlier null index of the field being
+        brought up to date. The index cannot be read until this returns.
 
-    async def get_one_partially(self, id: str, fields: List[str]) -> Dict[str, Any]:
+        :param field: the name of the non-nested field to index. It cannot be a counter field
+        :param max_ops_per_sec: the maximum number of existing records to read per second while indexing them,
+                so that other clients of a busy redis are not slowed down; default: None i.e. as fast as possible
+        :return: the number of existing records that do not have the field
         """
-        Retrieves a dictionary containing the provided fields from the record of the given id
 
-        :param id: the id of the record whose data is to be retrieved
-        :param fields: the list of fields to be returned in the data
-        :return: the dict with the given fields as keys and the values got from the record of the given id
+    def find_between(self, field: str, lo: Optional[Any], hi: Optional[Any]) -> List[Model]:
         """
+        Retrieves the records of this collection whose values of the given indexed field are between
+        lo and hi, inclusive, e.g. find_between("age", 18, 30). Dates and datetimes are compared
+        by their timestamps.
 
-    async def get_many_partially(self, ids: List[str], fields: List[str]) -> List[Dict[str, Any]]:
+        :param field: the name of the field, which should have been indexed with create_index()
+        :param lo: the lowest value, or None for no lower bound
+        :param hi: the highest value, or None for no upper bound
+        :return: the list of model objects whose values are in the range, in the order of their values
         """
-        Retrieves a list of dictionaries for records of the given ids,
-        only returning the specified fields for each record
 
-        :param ids: the list of ids of the records to be queried
-        :param fields: the fields to be returned in each item
-        :return: the list of dicts, each with the given fields as keys and the values for each record returned.
-               non-existent ids are ignored
+    def get_page(self, field: str, limit: int = 20, after_value: Optional[Any] = None,
+                 after_id: Optional[str] = None) -> List[Model]:
         """
+        Retrieves a page of the records of this collection in the order of their values of the given indexed field,
+        records with the same value being in the order of their ids, starting after the last record of the previous
+        page, given by its value and id e.g. get_page("age", 20, after_value=last.age, after_id=last.name).
+        Unlike the skip of get_all(), getting a page costs the same however deep it is, and records inserted
+        or deleted meanwhile do not shift the later pages, so no record is skipped or repeated. Records without
+        a value for the field are not in the index, and records removed by their ttl are left out, so a page may
+        have fewer than limit records before the last page.
 
-    async def get_all_partially(self, fields: List[str]) -> List[Dict[str, Any]]:
+        :param field: the name of the field, which should have been indexed with create_index()
+        :param limit: the maximum number of records in the page, greater than 0; default: 20
+        :param after_value: the value of the field of the last record of the previous page; default: None
+                i.e. the first page
+        :param after_id: the primary key of the last record of the previous page, so that the records with
+                the same value as it that were not on that page are on this one; default: None i.e. all the
+                records with after_value were on the previous pages
+        :return: the list of model objects of the page, in the order of their values
+        :raises ValueError: if the field has no index, the limit is 0 or after_id is given without after_value
         """
-        Retrieves a list of dictionaries for all records in the store,
-        only returning the specified fields for each record
 
-        :param fields: the fields to be returned in each item
-        :return: the list of dicts, each with the given fields as keys and the values for each record returned
+    def get_latest(self, n: int = 10) -> List[Model]:
         """
+        Retrieves the records of this collection inserted last. Updates do not change when a record was inserted.
+        Records that expired, or were removed otherwise than with the methods of the collection, are left out.
 
-    async def delete_many(self, ids: List[str]) -> None:
+        :param n: the maximum number of records to retrieve; default: 10
+        :return: the list of model objects inserted last, latest first
+        :raises ValueError: if the collection was not created with track_insertion_order=True
         """
-        Removes all records belonging to the given ids
 
-        :param ids: the ids of the records to be removed
+    def get_oldest(self, n: int = 10) -> List[Model]:
         """
+        Retrieves the records of this collection inserted first. Updates do not change when a record was inserted.
+        Records that expired, or were removed otherwise than with the methods of the collection, are left out.
 
-class Store:
-    """
-    The Store containing all collections that are stored in redis.
+        :param n: the maximum number of records to retrieve; default: 10
+        :return: the list of model objects inserted first, oldest first
+        :raises ValueError: if the collection was not created with track_insertion_order=True
+        """
 
-    :param url: the redis url e.g. redis://localhost:6379/0
-    :param pool_size: the maximum number of connections in the connection pool to redis; default: 5
-    :param default_ttl: the default time-to-live for each record in milliseconds; default: None i.e. no expiry
-    :param timeout: the time in milliseconds beyond which a timeout error is raised on failure to
-                    get a connection to redis from the connection pool; default is 30000 (30 seconds)
-    :param max_lifetime: the maximum lifetime in milliseconds connections in the pool; default is 1800000 (30 minutes)
-    """
+    def knn(self, field: str, query_vector: List[float], k: int = 10) -> List[Model]:
+        """
+        Retrieves the k records of this collection whose values of the given field, annotated with Vector[N],
+        are nearest to the query vector by cosine distance e.g. knn("embedding", [0.1, 0.3, ...], k=5).
+        It needs the RediSearch module of redis. The index of the field is created the first time it is searched,
+        after which the records already saved are indexed in the background, so the first searches of a large
+        collection may miss some of them.
 
-    def __init__(self,
-                 url: str,
-                 pool_size: int,
-                 default_ttl: Optional[int],
-                 timeout: Optional[int],
-                 max_lifetime: Optional[int]) -> None: ...
+        :param field: the name of the vector field
+        :param query_vector: the vector to find the nearest neighbours of, with as many items as the field
+        :param k: the maximum number of records to return; default: 10
+        :return: the list of model objects nearest to the query vector, nearest first
+        :raises KeyError: if the field is not a field of the model
+        :raises ValueError: if the field is not a vector field or the query vector has the wrong number of items
+        :raises ConnectionError: if the RediSearch module is not loaded in redis
+        """
 
-    def clear(self, asynchronous: bool = False) -> None:
+    def memory_usage(self, sample: int = 1000, top: int = 10) -> Dict[str, Any]:
         """
-        Removes all records in the redis store
+        Measures the memory used in redis by a random sample of the records of this collection with MEMORY USAGE,
+        estimating from it the memory used by the whole collection e.g. for capacity planning. Only the hashes of
+        the records are counted, not the records nested in them, nor counter fields, indices or change streams.
 
-        :param asynchronous: whether the FLUSHALL should be done asynchronously or synchronously. default: False
+        :param sample: the number of records to sample, at least 1; default: 1000
+        :param top: the number of the largest sampled records to list; default: 10
+        :return: a dictionary with the keys: "records", the number of records; "sampled", the number of records
+                sampled; "sampled_bytes" and "avg_bytes", the total and average bytes used by the sampled records;
+                "estimated_total_bytes", the bytes the whole collection is estimated to use; and "largest", the
+                largest sampled records, largest first, as dictionaries of their "id" and "bytes"
         """
 
-    def create_collection(self,
-                          model: Type[Model],
-                          primary_key_field: str) -> None:
+    def train_compression(self, sample: int = 1000) -> Dict[str, Any]:
         """
-        Creates a new Collection within the store for the given model supplied
+        Trains a zstd dictionary on the string values of a random sample of the records of this collection, saving
+        it in redis and compressing with it the string fields of the records written from then on. Records written
+        before are left as they are. Small, similar values compress much better with a dictionary than on their
+        own. The primary key, and the fields that are indexed, unique or filtered on by a view, are not compressed.
+        Compressed fields cannot be filtered on. Other processes read them with the dictionary, which they load
+        from redis the first time they read a value compressed with it, and should call `load_compression()`
+        to compress the values they write too.
 
-        :param model: the Model schema to be used for this collection
-        :param primary_key_field: the field that contains the unique primary key for each model instance e.g.
-                                a book's primary key might be its ISBN
+        :param sample: the number of records to sample, at least 1; default: 1000
+        :return: a dictionary with the keys: "dictionary_id"; "dictionary_bytes", the size of the dictionary;
+                "samples", the number of values it was trained on; "sampled_bytes" and "compressed_bytes", their
+                size before and after compression; and "ratio", the first over the second
+        :raises ValueError: if the collection has no string fields that can be compressed, or if the sampled
+                values are too few to train a dictionary on
         """
 
-    def get_collection(self, model: Type[Model]) -> Collection:
+    def load_compression(self) -> bool:
         """
-        Retrieves a handle on the collection for a given model to manipulate the data within or
-        query it
+        Loads the compression dictionaries of this collection saved in redis by `train_compression()` e.g. after
+        a restart, so that new values are compressed with the latest one. Values compressed with a dictionary
+        that is not loaded are read all the same, the dictionary being loaded from redis the first time.
+        Reading a value compressed with a dictionary that is not in redis raises a ValueError.
 
-        :param model: the Model schema whose collection is to be retrieved
-        :return: the collection instance to be used to manipulate data or query it using collection.add_one() etc.
+        :return: whether a dictionary was trained for this collection
         """
 
+    def hot_keys(self, top: int = 20) -> List[Tuple[str, float]]:
+        """
+        Gets the most accessed ids of this collection e.g. to find skewed access patterns that may need caching.
+        Only the reads and updates by id done through this collection in this process are counted, on the sample
+        of them set by `hot_key_sampling` in `create_collection()`; get_all() and the like are not counted.
 
-class AsyncStore:
-    """
-    The AsyncStore containing all async_collections that are stored in redis. It is meant to be used
-    where async-await is used
+        :param top: the number of ids to return; default: 20
+        :return: the ids, hottest first, with their estimated number of recent accesses. The count of an id halves
+                for every minute it is not accessed
+        :raises ValueError: if hot_key_sampling was not set for this collection
+        """
 
-    :param url: the redis url e.g. redis://localhost:6379/0
-    :param pool_size: the maximum number of connections in the connection pool to redis; default: 5
-    :param default_ttl: the default time-to-live for each record in milliseconds; default: None i.e. no expiry
-    :param timeout: the time in milliseconds beyond which a timeout error is raised on failure to
-                    get a connection to redis from the connection pool; default is 30000 (30 seconds)
-    :param max_lifetime: the maximum lifetime in milliseconds connections in the pool; default is 1800000 (30 minutes)
-    """
+    def increment(self, id: str, field: str, amount: Union[int, float] = 1) -> Union[int, float]:
+        """
+        Atomically increments the given counter field of the record of the given id without rewriting the record.
+        Counter fields are int or float fields marked with `counter=True` e.g. `views: int = Field(0, counter=True)`.
+        They are stored in their own keys and merged into the records when they are retrieved
 
-    def __init__(self,
-                 url: str,
-                 pool_size: int,
-                 default_ttl: Optional[int],
-                 timeout: Optional[int],
-                 max_lifetime: Optional[int]) -> None: ...
+        :param id: the primary key of the record
+        :param field: the name of the counter field
+        :param amount: the amount to increment by; it can be negative; default: 1
+        :return: the new value of the counter
+        """
 
-    async def clear(self, asynchronous: bool = False) -> None:
+    def get_changes(self, after: str = "0-0", count: int = 100) -> List[Dict[str, Any]]:
         """
-        Removes all records in the redis store
+        Retrieves the changes to the records of this collection, oldest first. The collection must have been created
+        with `track_changes=True`. Each change is a dictionary with the keys "event_id", "op" (i.e. "insert",
+        "update" or "delete"), "id", "before" and "after", the latter two being dictionaries of the fields of the
+        record before and after the change
 
-        :param asynchronous: whether the FLUSHALL should be done asynchronously or synchronously. default: False
+        :param after: the event_id of the change after which to start; default: "0-0" i.e. from the oldest change
+        :param count: the maximum number of changes to return; default: 100
+        :return: the list of changes
         """
 
-    def create_collection(self,
-                          model: Type[Model],
-                          primary_key_field: str) -> None:
+    def delete_many(self, ids: List[str], strict: bool = False) -> int:
         """
-        Creates a new Collection within the store for the given model supplied
+        Removes all records belonging to the given ids
 
-        :param model: the Model schema to be used for this collection
-        :param primary_key_field: the field that contains the unique primary key for each model instance e.g.
-                                a book's primary key might be its ISBN
+        :param ids: the ids of the records to be removed
+        :param strict: whether to raise a KeyError if any of the ids has no record, the records of the others
+                being removed all the same; default: False
+        :return: the number of the ids that had records, or the number of distinct ids if the removal was buffered
+                as redis was unreachable
+        :raises KeyError: if strict and any of the ids has no record
         """
 
-    def get_collection(self, model: Type[Model]) -> AsyncCollection:
+    def delete_where(self, **field_filters: Any) -> int:
         """
-        Retrieves a handle on the collection for a given model to manipulate the data within or
-        query it
+        Removes the records whose fields are equal to the given values e.g. `delete_where(author="Jane")`.
+        The records are found and removed in redis by one script that scans the collection, so none of them is read
 
-        :param model: the Model schema whose collection is to be retrieved
-        :return: the collection instance to be used to manipulate data or query it using collection.add_one() etc.
+        :param field_filters: the values the fields of the records to remove should be equal to; only non-nested,
+            non-counter fields can be filtered on
+        :return: the number of records removed
+        :raises ValueError: if no field filter is given or a field cannot be filtered on
+        :raises KeyError: if a field is not a field of the model
+        """
+
+    def clear(self, max_ops_per_sec: Optional[float] = None) -> int:
+        """
+        Removes all the records of this collection, with their indexes, unique values, counters, copies in views,
+        cached lookups and the other data derived from them, leaving the other collections in the redis database
+        as they are, unlike `Store.clear()` which flushes it all. The keys of the collection are scanned and
+        removed a batch at a time, so the clearing is not atomic: records added while it runs may or may not be
+        removed. The usage of the quota of the namespace of the collection is released for the records removed.
+
+        The snapshots, the snapshot of the schema and the compression dictionaries of the collection are kept,
+        as is its change stream, which gets no events for the records removed. The records nested in the records
+        removed are kept in their own collections, and the mirrors of the store are not cleared.
+
+        :param max_ops_per_sec: the maximum number of keys to scan and remove per second, so that other clients
+                of a busy redis are not slowed down; default: None i.e. as fast as possible
+        :return: the number of records removed
+        :raises ValueError: if max_ops_per_sec is not positive
+        """
+
+    def delete_matching(self, id_pattern: str, max_ops_per_sec: Optional[float] = None) -> int:
+        """
+        Removes the records whose ids match the given glob pattern e.g. `delete_matching("order-2023-*")`, as
+        matched by the MATCH option of SCAN. The keys of the collection are scanned a batch at a time, each batch
+        being removed by the script that scans it, with UNLINK, unless the records have indexes, unique values,
+        null indexes or a quota to update, in which case they are removed with those right after. The removal is
+        thus not atomic: records added while it runs may or may not be removed.
+
+        :param id_pattern: the glob pattern the ids of the records to remove should match, in which "*", "?",
+                "[...]" and "\\" are special
+        :param max_ops_per_sec: the maximum number of keys to scan per second, so that other clients
+                of a busy redis are not slowed down; default: None i.e. as fast as possible
+        :return: the number of records removed
+        :raises ValueError: if id_pattern is empty or max_ops_per_sec is not positive
+        """
+
+    def update_where(self, filters: Dict[str, Any], changes: Dict[str, Any]) -> int:
+        """
+        Makes the given changes to the records whose fields are equal to the values in the filters
+        e.g. `update_where({"author": "Jane"}, {"in_stock": False})`. The records are found and updated in redis
+        by one script that scans the collection, so none of them is read. Their ttls are left as they are.
+
+        :param filters: the values the fields of the records to update should be equal to; only non-nested,
+            non-counter fields can be filtered on
+        :param changes: the new values of some non-nested fields, other than the primary key and counter fields
+        :return: the number of records updated
+        :raises ValueError: if the filters or the changes are empty, or a field cannot be filtered on or changed
+        :raises KeyError: if a field is not a field of the model
+        """
+
+class AsyncCollection:
+    """
+    The AsyncCollection represents a group of similar records within redis
+    e.g. records of model Car will be in the Car collection.
+    This has all the methods for manipulating data as well as retrieving it
+    from redis but asynchronously. For the synchronous API, use Collection
+    """
+
+    async def add_one(self, item: Model, ttl: Optional[Union[timedelta, datetime, float]] = None,
+                      profile: bool = False) -> Union[str, Tuple[str, Dict[str, float]]]:
+        """
+        Adds a single Model instance to the collection. The model instance should
+        be of the same type as the model used to get this collection.
+
+        :param item: the model object to add
+        :param ttl: the optional time-to-live for this item in redis; default: None (i.e. never expires).
+                    if however, the default_ttl was set on the store, it will default to that.
+                    It is a timedelta or a number of seconds, or a datetime at which to expire,
+                    applied with millisecond precision. Naive datetimes are in the local timezone
+        :param profile: whether to also return the timing breakdown of the call, to find out whether its time is
+               spent in the models or in redis: a dictionary of "serialize_ms" spent turning models into commands,
+               "network_ms" spent getting connections and waiting for redis, "parse_ms" spent parsing the replies,
+               "construct_ms" spent constructing models, and "total_ms" of the whole call; default: False
+        :return: the primary key of the record added, as saved in redis, e.g. the one generated by the id_generator
+                of the collection if the item had none, or if profile is True, a tuple of it and the timing breakdown
+        """
+
+    async def add_one_if_not_exists(self, item: Model, ttl: Optional[Union[timedelta, datetime, float]] = None) -> bool:
+        """
+        Adds a single Model instance to the collection only if no record has its primary key, e.g. for create-only
+        flows. The creation of the record is claimed atomically first, so of many concurrent calls for the same
+        primary key, only one adds it, with no need to call get_one() before.
+
+        :param item: the model object to add
+        :param ttl: the optional time-to-live for this item in redis; default: None (i.e. never expires).
+                    if however, the default_ttl was set on the store, it will default to that.
+                    It is a timedelta or a number of seconds, or a datetime at which to expire,
+                    applied with millisecond precision. Naive datetimes are in the local timezone
+        :return: True if the record was added, or False if a record with its primary key exists or is being added
+        """
+
+    async def add_many(self, items: List[Model], ttl: Optional[Union[timedelta, datetime, float]] = None,
+                       profile: bool = False) -> Union[List[str], Tuple[List[str], Dict[str, float]]]:
+        """
+        Adds a list of Model instances into this collection. The model instances should
+        be of the same type as the model used to get this collection. They are written in batches of
+        at most the max_batch_bytes of the store, so if a batch fails, the batches before it are kept.
+
+        :param items: the list of model objects to add
+        :param ttl: the optional time-to-live for all these items in redis; default: None (i.e. never expires)
+                    if however, the default_ttl was set on the store, it will default to that.
+                    It is a timedelta or a number of seconds, or a datetime at which to expire,
+                    applied with millisecond precision. Naive datetimes are in the local timezone
+        :param profile: whether to also return the timing breakdown of the call, to find out whether its time is
+               spent in the models or in redis: a dictionary of "serialize_ms" spent turning models into commands,
+               "network_ms" spent getting connections and waiting for redis, "parse_ms" spent parsing the replies,
+               "construct_ms" spent constructing models, and "total_ms" of the whole call; default: False
+        :return: the list of the primary keys of the records added, as saved in redis, in the order of the items,
+                or if profile is True, a tuple of it and the timing breakdown
+        """
+
+    async def generate_fake(self,
+                            n: int,
+                            seed: Optional[int] = None,
+                            ttl: Optional[Union[timedelta, datetime, float]] = None) -> None:
+        """
+        Adds `n` records of random but valid values generated from the schema of this collection's model e.g. to fill
+        the collection for load tests or demos. Strings are letters and digits, ints are below 1000000, dates
+        and datetimes are between 2000 and 2030, lists and dicts have at most 3 items, and nested models are
+        generated too, up to the nested_depth of the collection, beyond which they are None. Primary keys are unique
+        among the generated records. The records are added in batches of 1000.
+
+        :param n: the number of records to add
+        :param seed: the seed of the random values, so that the same records are generated each time it is given;
+                    default: None i.e. different records each time
+        :param ttl: the optional time-to-live for all these records in redis, as in `add_many()`
+        """
+
+    async def update_one(self, id: str, data: Dict[str, Any], ttl: Optional[Union[timedelta, datetime, float]] = None) -> None:
+        """
+        Updates the model instance in redis that has the given id
+
+        :param id: the id of the record to update
+        :param data: the new changes to add to the record. Fields of nested records can be addressed by their dotted
+                    paths e.g. {"address.city": "Kigali"} to update them in the nested record directly; nothing
+                    is updated through a path whose nested record does not exist
+        :param ttl: the optional time-to-live for all these items in redis; default: None (i.e. never expires)
+                    if however, the default_ttl was set on the store, it will default to that.
+                    It is a timedelta or a number of seconds, or a datetime at which to expire,
+                    applied with millisecond precision. Naive datetimes are in the local timezone
+        """
+
+    async def update_many(self, updates: Dict[str, Dict[str, Any]],
+                          ttl: Optional[Union[timedelta, datetime, float]] = None) -> None:
+        """
+        Updates the model instances in redis that have the given ids, each with its own changes, in one network
+        request. This is more efficient than calling update_one() for each of them
+
+        :param updates: the map of the id of each record to update to the new changes to add to it. As in
+                    update_one(), fields of nested records can be addressed by their dotted paths
+        :param ttl: the optional time-to-live for all these items in redis; default: None (i.e. never expires)
+                    if however, the default_ttl was set on the store, it will default to that.
+                    It is a timedelta or a number of seconds, or a datetime at which to expire,
+                    applied with millisecond precision. Naive datetimes are in the local timezone
+        """
+
+    async def exists(self, id: str) -> bool:
+        """
+        Checks whether the record of the given id exists in the collection
+
+        :param id: the primary key of the record
+        :return: True if the record exists else False
+        """
+
+    async def exists_many(self, ids: List[str]) -> List[bool]:
+        """
+        Checks whether the records of the given ids exist in the collection with one EXISTS per id in one round trip,
+        e.g. to reconcile an external list of ids with the collection
+
+        :param ids: the primary keys of the records
+        :return: a boolean per id, in the order of the ids, that is True if its record exists else False
+        """
+
+    async def get_one(self, id: str, profile: bool = False) -> Union[Model, Tuple[Model, Dict[str, float]]]:
+        """
+        Retrieves one record of the given id or None if it does not exist
+
+        :param id: the id of the model record to return
+        :param profile: whether to also return the timing breakdown of the call, to find out whether its time is
+               spent in the models or in redis: a dictionary of "serialize_ms" spent turning models into commands,
+               "network_ms" spent getting connections and waiting for redis, "parse_ms" spent parsing the replies,
+               "construct_ms" spent constructing models, and "total_ms" of the whole call; default: False
+        :return: the model object for the given id in this collection, or if profile is True,
+               a tuple of it and the timing breakdown
+        """
+
+    async def get_many(self, ids: List[str],
+                       profile: bool = False) -> Union[List[Model], Tuple[List[Model], Dict[str, float]]]:
+        """
+        Retrieves a list of records in this collection corresponding to the ids passed
+
+        :param ids: the list of ids whose records are to be returned
+        :param profile: whether to also return the timing breakdown of the call, to find out whether its time is
+               spent in the models or in redis: a dictionary of "serialize_ms" spent turning models into commands,
+               "network_ms" spent getting connections and waiting for redis, "parse_ms" spent parsing the replies,
+               "construct_ms" spent constructing models, and "total_ms" of the whole call; default: False
+        :return: the list of model objects that correspond to the list of ids. Non-existent ids
+               are simply skipped. If profile is True, it is returned in a tuple with the timing breakdown
+        """
+
+    async def repair(self, fix: Optional[List[str]] = None, max_ops_per_sec: Optional[float] = None) -> int:
+        """
+        Rewrites the values of the records of this collection that are in known broken or legacy formats
+        in the format orredis writes
+
+        :param fix: the fixes to apply: "bool_case" lowercases booleans e.g. "True", "datetime_format" rewrites
+               ISO 8601 datetimes e.g. "2022-10-01T08:30:00Z" and those without a utc offset (taken to be in UTC),
+               and "none_sentinel" removes "None", "null", "nil" and empty values of fields that are neither strings
+               nor nested models, so that they get their defaults. All of them are applied if none are given
+        :param max_ops_per_sec: the maximum number of records to read and repair per second, so that other clients
+                of a busy redis are not slowed down; default: None i.e. as fast as possible
+        :return: the number of records repaired
+        """
+
+    def compact(self, max_ops_per_sec: Optional[float] = None,
+                background: bool = False) -> Optional[Awaitable[int]]:
+        """
+        Removes the fields that are no longer in the model of this collection from its records e.g. after many
+        schema changes. Fields that another writer still saves with a newer version of the model are removed too,
+        so it should be run once all writers use the current model.
+
+        :param max_ops_per_sec: the maximum number of records to compact per second, so that other clients
+                of a busy redis are not slowed down; default: None i.e. as fast as possible
+        :param background: whether to compact the records in a background task of the store named
+                "compact:<collection>" e.g. "compact:Book" instead of returning an awaitable. Its status, and
+                the error that stopped it if any, are got with the `background_tasks()` of the store. It needs
+                no running event loop; default: False
+        :return: an awaitable of the number of records compacted, or None if they are compacted in the background
+        """
+
+    async def get_many_raw(self, ids: List[str]) -> List[Dict[str, Any]]:
+        """
+        Retrieves the records of the given ids as dictionaries, with the records nested in them also
+        as dictionaries, without constructing any models. This is the fastest way to read records
+        that are e.g. encoded as JSON right away
+
+        :param ids: the list of ids whose records are to be returned
+        :return: the list of dictionaries of the records that correspond to the list of ids. Non-existent ids
+               are simply skipped
+        """
+
+    async def resolve(self, reference: Optional[str]) -> Optional[Model]:
+        """
+        Retrieves the record of this collection that the given reference refers to i.e. the value of a field
+        annotated with Reference[Model] in another collection, which only holds the primary key of the record.
+        Referenced records are thus only read when they are needed, unlike nested records
+
+        :param reference: the reference, or a primary key of this collection
+        :return: the model object referred to, or None if it does not exist or the reference is None
+        :raises ValueError: if the reference refers to a record of another model
+        """
+
+    async def children_of(self, parent_id: str, child_collection: "AsyncCollection", fk_field: str) -> List[Model]:
+        """
+        Retrieves the records of the child collection whose foreign key field holds the given primary key of
+        a record of this collection e.g. user_collection.children_of("u1", order_collection, "user_id") gets
+        all the orders of the user "u1". The cost depends on the number of children, not the size of
+        the child collection.
+
+        :param parent_id: the primary key of the parent record in this collection
+        :param child_collection: the collection of the child records
+        :param fk_field: the field of the child records that holds the primary key of their parent.
+                It should be among the foreign_keys of the child collection set in create_collection()
+        :return: the list of model objects of the children, in no particular order
+        """
+
+    async def find_by(self, field: str, value: Any) -> List[Model]:
+        """
+        Retrieves the records of this collection whose field holds the given value e.g.
+        user_collection.find_by("email", "jane@example.com"). The field should be among the indexed_fields
+        or the foreign_keys set in create_collection(). The cost depends on the number of matching records,
+        not the size of the collection.
+
+        :param field: the indexed field
+        :param value: the value of the field in the records to get
+        :return: the list of model objects whose field holds the value, in no particular order
+        :raises ValueError: if the field is not indexed
+        """
+
+    async def find_by_index(self, fields: Tuple[str, ...], values: Tuple[Any, ...]) -> List[Model]:
+        """
+        Retrieves the records of this collection whose fields hold the given values e.g.
+        shop_collection.find_by_index(("country", "city"), ("UG", "Kampala")). The fields should be those of one of
+        the compound_indexes set in create_collection(), in any order. The cost depends on the number of matching
+        records, not the size of the collection.
+
+        :param fields: the fields of the compound index
+        :param values: the values of the fields in the records to get, in the order of the fields
+        :return: the list of model objects whose fields hold the values, in no particular order
+        :raises ValueError: if there is no compound index of the fields or the numbers of fields and values differ
+        """
+
+    async def find(self, cache_ttl: Optional[Union[timedelta, float]] = None, **lookups: Any) -> List[Model]:
+        """
+        Retrieves the records of this collection that match all the given lookups e.g.
+        post_collection.find(tags__contains="rust", author="jane"). Each lookup is either `field=value` for one of
+        the indexed_fields, `field__contains=value` for one of the indexed_list_fields set in create_collection(),
+        matching the records whose list holds the value, or `field__isnull=True` for a field indexed with
+        create_null_index(), matching the records that do not have the field, e.g. those saved before it was added
+        to the model, or whose value is None. `field__isnull=False` excludes those records instead.
+        The cost depends on the number of matching records, not the size of the collection, except when all
+        the lookups are `field__isnull=False`, in which case all the ids of the collection are read.
+        If cache_ttl is given, the ids of the matching records are cached in redis for that time, so that identical
+        calls within it skip the indexes and only read the records, which are thus up to date, but may no longer
+        match the lookups, while the records added since then are left out.
+
+        :param cache_ttl: the time, as a timedelta or a number of seconds, for which the ids of the matching records
+                are cached, for the identical calls with a cache_ttl; default: None i.e. they are neither cached
+                nor read from the cache. Empty results are not cached
+        :param lookups: the lookups, by field or by field followed by "__contains" or "__isnull", to the values
+                to match
+        :return: the list of model objects that match all the lookups, in no particular order
+        :raises ValueError: if no lookup is given or a field is not indexed for its lookup
+        """
+
+    async def explain_find(self, **lookups: Any) -> Dict[str, Any]:
+        """
+        Explains how find() gets the records matching the given lookups e.g.
+        post_collection.explain_find(tags__contains="rust", author="jane"), to diagnose slow ones, without reading
+        the records. Each lookup is served by its index, whose ids are the candidates. The lookup with the fewest
+        candidates is chosen to drive the matching, its candidates being checked against the ids of the others,
+        so a slow find() usually lacks a selective lookup. When all the lookups are `field__isnull=False`,
+        there is no index to drive the matching, so it falls back to scanning the ids of the whole collection.
+
+        :param lookups: the lookups, as given to find()
+        :return: a dictionary with the keys: "lookups", the lookups, each as a dictionary of its "lookup" as text,
+                the kind of "index" serving it i.e. "field", "list" or "null", its "role" i.e. "match", or "exclude"
+                for `field__isnull=False`, and the number of "candidates" in its index; "scan", whether the ids
+                of the whole collection are scanned; "chosen_index", the lookup chosen to drive the matching, or None
+                if the ids are scanned; "candidates", the number of ids checked against the lookups; and "matches",
+                the number of records matching all of them
+        :raises ValueError: if no lookup is given or a field is not indexed for its lookup
+        """
+
+    async def ids(self) -> List[str]:
+        """
+        Retrieves the ids of all records in this collection, scanning their keys without reading the records,
+        so that they can be listed cheaply before deciding which to get
+
+        :return: the list of the primary keys of the records, in no particular order
+        """
+
+    async def sample(self, n: int) -> List[Model]:
+        """
+        Picks n records of this collection at random e.g. to spot-check the quality of the data of a big collection.
+        The ids of all the records are scanned without reading the records, and only the records picked are read.
+
+        :param n: the number of records to pick
+        :return: the list of the records picked, which has all the records in a random order if there are not more
+                than n
+        """
+
+    async def get_all(self, on_error: str = "raise",
+                      filter: Optional[Dict[str, Any]] = None, skip: int = 0,
+                      limit: Optional[int] = None, profile: bool = False, order_by: Optional[str] = None,
+                      desc: bool = False) -> Union[List[Model], Tuple[Any, ...]]:
+        """
+        Retrieves a list of all records in this collection at ago
+
+        :param on_error: what to do with records that cannot be read e.g. because they are corrupt:
+               "raise" their error, "skip" them, or "collect" them with their errors
+        :param filter: the values that the fields of the records returned should be equal to e.g. {"in_stock": True}.
+               The records are filtered in redis, comparing the values as saved, so only the matching records are
+               transferred. Only non-nested, non-counter fields can be filtered on; default: None i.e. all records
+        :param skip: the number of matching records to skip, in the order in which they are scanned, which is stable
+               as long as the collection does not change, or in that of order_by; default: 0
+        :param limit: the maximum number of records to return; default: None i.e. all of them. Only the records
+               returned are read, so large collections can be read a page at a time
+        :param profile: whether to also return the timing breakdown of the call, to find out whether its time is
+               spent in the models or in redis: a dictionary of "serialize_ms" spent turning models into commands,
+               "network_ms" spent getting connections and waiting for redis, "parse_ms" spent parsing the replies,
+               "construct_ms" spent constructing models, and "total_ms" of the whole call; default: False
+        :param order_by: the non-nested field by whose values the records are sorted, in redis, before they are
+               skipped and limited. Int and float fields are compared as numbers, and the others as saved e.g. dates
+               in time, the records without a value for the field coming last. If the field has an index made with
+               create_index(), the page is read from the index instead of sorting the whole collection;
+               default: None i.e. the order in which they are scanned
+        :param desc: whether the records are in the descending order of the values of order_by; default: False
+        :return: the list of model objects in this collection, or if on_error is "collect",
+               a tuple of that list and a list of dictionaries of the "id" (None if unknown) and the "error"
+               of each record that could not be read. If profile is True, it is returned in a tuple with
+               the timing breakdown
+        :raises ValueError: if order_by is a nested field, or desc is given without order_by
+        """
+
+    async def snapshot(self, tag: str, max_ops_per_sec: Optional[float] = None) -> int:
+        """
+        Takes a snapshot of this collection under the given tag i.e. copies all its records as they currently are,
+        replacing any snapshot of the same tag, so that they can be read later with read_snapshot() e.g. to debug
+        or to compare them with later records. The copies do not expire and are kept until delete_snapshot()
+        is called. The records are copied one SCAN batch at a time, so writes made during the copy may or may not
+        be in the snapshot.
+
+        :param tag: the tag of the snapshot. It should be non-empty and have none of the characters *?[]\\%
+        :param max_ops_per_sec: the maximum number of records to scan per second; default: None i.e. no limit
+        :return: the number of records in the snapshot
+        """
+
+    async def read_snapshot(self, tag: str,
+                      on_error: str = "raise") -> Union[List[Model], Tuple[List[Model], List[Dict[str, Any]]]]:
+        """
+        Retrieves a list of all records in the snapshot of the given tag of this collection, as they were when
+        the snapshot was taken. Only the records of this collection are copied into a snapshot, so their nested
+        records are as they currently are
+
+        :param tag: the tag of the snapshot
+        :param on_error: what to do with records that cannot be read e.g. because they are corrupt:
+               "raise" their error, "skip" them, or "collect" them with their errors
+        :return: the list of model objects in the snapshot, which is empty if there is no such snapshot,
+               or if on_error is "collect", a tuple of that list and a list of dictionaries of the "id"
+               (None if unknown) and the "error" of each record that could not be read
+        """
+
+    async def delete_snapshot(self, tag: str) -> int:
+        """
+        Deletes the snapshot of the given tag of this collection
+
+        :param tag: the tag of the snapshot
+        :return: the number of records the snapshot had
+        """
+
+    async def parallel_iter(self, workers: int = 4) -> List[Model]:
+        """
+        Retrieves a list of all records in this collection, splitting the ids found by scanning the collection
+        into a shard for each worker, which reads its shard in batches on its own connection. This is faster than
+        get_all() for large collections and does not block redis for the whole read. Records added or deleted
+        during the read may or may not be included.
+
+        :param workers: the number of workers, at least 1 and at most the pool_size of the store; default: 4
+        :return: the list of model objects in this collection, in no particular order
+        """
+
+    async def get_one_partially(self, id: str, fields: List[str], nested_as_models: bool = False) -> Dict[str, Any]:
+        """
+        Retrieves a dictionary containing the provided fields from the record of the given id
+
+        :param id: the id of the record whose data is to be retrieved
+        :param fields: the list of fields to be returned in the data. Fields of nested records can be given by their
+                    dotted paths e.g. "address.city", returning the nested record as a dict of only those fields
+        :param nested_as_models: whether the nested records requested by dotted paths are returned as instances of
+                    their models, constructed without validation, instead of dicts. Defaults to False
+        :return: the dict with the given fields as keys and the values got from the record of the given id
+        """
+
+    async def get_field(self, id: str, field: str) -> Any:
+        """
+        Retrieves the value of one field of the record of the given id e.g. `get_field("Jane Eyre", "in_stock")`,
+        reading only that value with HGET and converting it to the type of the field. It is cheaper than
+        get_one_partially() for a single field
+
+        :param id: the id of the record
+        :param field: the name of the field; only non-nested fields are supported
+        :return: the value of the field, or None if the record or its value is not in redis
+        :raises KeyError: if the field is not a field of the model
+        :raises ValueError: if the field is a nested field
+        """
+
+    async def get_many_partially(self,
+                                 ids: List[str],
+                                 fields: List[str],
+                                 nested_as_models: bool = False) -> List[Dict[str, Any]]:
+        """
+        Retrieves a list of dictionaries for records of the given ids,
+        only returning the specified fields for each record
+
+        :param ids: the list of ids of the records to be queried
+        :param fields: the fields to be returned in each item. Fields of nested records can be given by their
+                    dotted paths e.g. "address.city", returning the nested record as a dict of only those fields
+        :param nested_as_models: whether the nested records requested by dotted paths are returned as instances of
+                    their models, constructed without validation, instead of dicts. Defaults to False
+        :return: the list of dicts, each with the given fields as keys and the values for each record returned.
+               non-existent ids are ignored
+        """
+
+    async def get_all_partially(self, fields: List[str], nested_as_models: bool = False, skip: int = 0,
+                                limit: Optional[int] = None, order_by: Optional[str] = None,
+                                desc: bool = False) -> List[Dict[str, Any]]:
+        """
+        Retrieves a list of dictionaries for all records in the store,
+        only returning the specified fields for each record
+
+        :param fields: the fields to be returned in each item. Fields of nested records can be given by their
+                    dotted paths e.g. "address.city", returning the nested record as a dict of only those fields
+        :param nested_as_models: whether the nested records requested by dotted paths are returned as instances of
+                    their models, constructed without validation, instead of dicts. Defaults to False
+        :param skip: the number of records to skip, in the order in which they are scanned, which is stable as long
+                    as the collection does not change, or in that of order_by; default: 0
+        :param limit: the maximum number of records to return; default: None i.e. all of them. Only the records
+                    returned are read, so large collections can be read a page at a time
+        :param order_by: the non-nested field by whose values the records are sorted, in redis, before they are
+                    skipped and limited, as in get_all(); default: None i.e. the order in which they are scanned
+        :param desc: whether the records are in the descending order of the values of order_by; default: False
+        :return: the list of dicts, each with the given fields as keys and the values for each record returned
+        :raises ValueError: if order_by is a nested field, or desc is given without order_by
+        """
+
+    async def track_cardinality(self, field: str, max_ops_per_sec: Optional[float] = None) -> None:
+        """
+        Starts tracking the approximate number of distinct values of the given field
+        using a redis HyperLogLog that is updated on every write, by any store.
+        The values of the records already in the collection are added to it.
+
+        :param field: the name of the non-nested field to track
+        :param max_ops_per_sec: the maximum number of existing records to scan per second while adding their values,
+                so that other clients of a busy redis are not slowed down; default: None i.e. as fast as possible
+        """
+
+    async def approx_distinct(self, field: str) -> int:
+        """
+        Returns the approximate number of distinct values the given field has had in this collection
+        since it started being tracked by track_cardinality(). The standard error is about 0.81%.
+        Deleted records are still counted.
+
+        :param field: the name of the tracked field
+        :return: the approximate number of distinct values, 0 if the field is not tracked
+        """
+
+    async def distinct(self, field: str) -> List[Any]:
+        """
+        Returns the distinct values of the given field across this collection, in no particular order.
+        They are collected by a Lua script as it scans the collection in redis, so only the distinct values
+        are sent back, yet the cost of the call still grows with the size of the collection.
+        Records that have no value for the field are left out.
+
+        :param field: the name of the field, which should not be nested
+        :return: the list of the distinct values of the field
+        :raises KeyError: if the field is not a field of the model
+        :raises ValueError: if the field is a nested field
+        """
+
+    async def aggregate(self, field: str, op: str) -> Optional[Union[int, float]]:
+        """
+        Computes the sum, average, minimum or maximum of the values of the given int or float field across
+        this collection, in a Lua script as it scans the collection in redis, so that only the result is sent back
+        instead of every record. Records whose value of the field is None are left out.
+
+        :param field: the name of the int or float field
+        :param op: the operation i.e. "sum", "avg", "min" or "max"
+        :return: the result, which is an int for the sum, minimum or maximum of an int field and a float otherwise.
+                The sum of no values is 0 while their average, minimum and maximum are None
+        :raises KeyError: if the field is not a field of the model
+        :raises ValueError: if the field is not an int or float field or the op is unknown
+        """
+
+    async def count_by(self, field: str) -> Dict[Any, int]:
+        """
+        Counts the records of this collection by the values of the given field e.g. to get a histogram of statuses
+        or categories. The records are grouped in a Lua script as it scans the collection in redis, so that only
+        the counts are sent back instead of every record. Records that have no value for the field are left out.
+
+        :param field: the name of the field, which should not be a nested, list, vector or dict field
+        :return: a dictionary of each value of the field to the number of records that have it
+        :raises KeyError: if the field is not a field of the model
+        :raises ValueError: if the field is a nested, list, vector or dict field
+        """
+
+    async def count_where(self, **filters: Any) -> int:
+        """
+        Counts the records whose fields are equal to the given values e.g. `count_where(author="Jane")`.
+        The records are counted in a Lua script as it scans the collection in redis, so that only the count
+        is sent back instead of every record.
+
+        :param filters: the values the fields of the records to count should be equal to; only non-nested,
+            non-counter fields can be filtered on. All records are counted if none is given
+        :return: the number of records whose fields are equal to the values
+        :raises KeyError: if a field is not a field of the model
+        :raises ValueError: if a field cannot be filtered on
+        """
+
+    async def create_index(self, field: str, max_ops_per_sec: Optional[float] = None) -> int:
+        """
+        Starts maintaining an index of the records of this collection by the values of the given field,
+        using a redis sorted set that is updated on every write, so that find_between() can get the records
+        whose values are in a range. The index is registered in redis, so that the writes of all stores and
+        processes update it, then the records already in the collection are indexed, an earlier index of the field
+        being brought up to date. The index cannot be read until this returns. Records removed by their ttl are
+        left in the index but are never returned.
+
+        :param field: the name of the int, float, date or datetime field to index. It cannot be a counter field
+        :param max_ops_per_sec: the maximum number of existing records to read per second while indexing them,
+                so that other clients of a busy redis are not slowed down; default: None i.e. as fast as possible
+        :return: the number of existing records indexed
+        """
+
+    async def create_null_index(self, field: str, max_ops_per_sec: Optional[float] = None) -> int:
+        """
+        Starts maintaining an index of the records of this collection that do not have the given field,
+        or whose value of it is None, using a redis set that is updated on every write, so that
+        find(field__isnull=True) can get them e.g. to backfill the records saved before the field was added
+        to the model. The index is registered in redis, so that the writes of all stores and processes update
+        it, then the records already in the collection are indexed, an earlier null index of the field being
+        brought up to date. The index cannot be read until this returns.
+
+        :param field: the name of the non-nested field to index. It cannot be a counter field
+        :param max_ops_per_sec: the maximum number of existing records to read per second while indexing them,
+                so that other clients of a busy redis are not slowed down; default: None i.e. as fast as possible
+        :return: the number of existing records that do not have the field
+        """
+
+    async def find_between(self, field: str, lo: Optional[Any], hi: Optional[Any]) -> List[Model]:
+        """
+        Retrieves the records of this collection whose values of the given indexed field are between
+        lo and hi, inclusive, e.g. find_between("age", 18, 30). Dates and datetimes are compared
+        by their timestamps.
+
+        :param field: the name of the field, which should have been indexed with create_index()
+        :param lo: the lowest value, or None for no lower bound
+        :param hi: the highest value, or None for no upper bound
+        :return: the list of model objects whose values are in the range, in the order of their values
+        """
+
+    async def get_page(self, field: str, limit: int = 20, after_value: Optional[Any] = None,
+                       after_id: Optional[str] = None) -> List[Model]:
+        """
+        Retrieves a page of the records of this collection in the order of their values of the given indexed field,
+        records with the same value being in the order of their ids, starting after the last record of the previous
+        page, given by its value and id e.g. get_page("age", 20, after_value=last.age, after_id=last.name).
+        Unlike the skip of get_all(), getting a page costs the same however deep it is, and records inserted
+        or deleted meanwhile do not shift the later pages, so no record is skipped or repeated. Records without
+        a value for the field are not in the index, and records removed by their ttl are left out, so a page may
+        have fewer than limit records before the last page.
+
+        :param field: the name of the field, which should have been indexed with create_index()
+        :param limit: the maximum number of records in the page, greater than 0; default: 20
+        :param after_value: the value of the field of the last record of the previous page; default: None
+                i.e. the first page
+        :param after_id: the primary key of the last record of the previous page, so that the records with
+                the same value as it that were not on that page are on this one; default: None i.e. all the
+                records with after_value were on the previous pages
+        :return: the list of model objects of the page, in the order of their values
+        :raises ValueError: if the field has no index, the limit is 0 or after_id is given without after_value
+        """
+
+    async def get_latest(self, n: int = 10) -> List[Model]:
+        """
+        Retrieves the records of this collection inserted last. Updates do not change when a record was inserted.
+        Records that expired, or were removed otherwise than with the methods of the collection, are left out.
+
+        :param n: the maximum number of records to retrieve; default: 10
+        :return: the list of model objects inserted last, latest first
+        :raises ValueError: if the collection was not created with track_insertion_order=True
+        """
+
+    async def get_oldest(self, n: int = 10) -> List[Model]:
+        """
+        Retrieves the records of this collection inserted first. Updates do not change when a record was inserted.
+        Records that expired, or were removed otherwise than with the methods of the collection, are left out.
+
+        :param n: the maximum number of records to retrieve; default: 10
+        :return: the list of model objects inserted first, oldest first
+        :raises ValueError: if the collection was not created with track_insertion_order=True
+        """
+
+    async def knn(self, field: str, query_vector: List[float], k: int = 10) -> List[Model]:
+        """
+        Retrieves the k records of this collection whose values of the given field, annotated with Vector[N],
+        are nearest to the query vector by cosine distance e.g. knn("embedding", [0.1, 0.3, ...], k=5).
+        It needs the RediSearch module of redis. The index of the field is created the first time it is searched,
+        after which the records already saved are indexed in the background, so the first searches of a large
+        collection may miss some of them.
+
+        :param field: the name of the vector field
+        :param query_vector: the vector to find the nearest neighbours of, with as many items as the field
+        :param k: the maximum number of records to return; default: 10
+        :return: the list of model objects nearest to the query vector, nearest first
+        :raises KeyError: if the field is not a field of the model
+        :raises ValueError: if the field is not a vector field or the query vector has the wrong number of items
+        :raises ConnectionError: if the RediSearch module is not loaded in redis
+        """
+
+    async def memory_usage(self, sample: int = 1000, top: int = 10) -> Dict[str, Any]:
+        """
+        Measures the memory used in redis by a random sample of the records of this collection with MEMORY USAGE,
+        estimating from it the memory used by the whole collection e.g. for capacity planning. Only the hashes of
+        the records are counted, not the records nested in them, nor counter fields, indices or change streams.
+
+        :param sample: the number of records to sample, at least 1; default: 1000
+        :param top: the number of the largest sampled records to list; default: 10
+        :return: a dictionary with the keys: "records", the number of records; "sampled", the number of records
+                sampled; "sampled_bytes" and "avg_bytes", the total and average bytes used by the sampled records;
+                "estimated_total_bytes", the bytes the whole collection is estimated to use; and "largest", the
+                largest sampled records, largest first, as dictionaries of their "id" and "bytes"
+        """
+
+    async def train_compression(self, sample: int = 1000) -> Dict[str, Any]:
+        """
+        Trains a zstd dictionary on the string values of a random sample of the records of this collection, saving
+        it in redis and compressing with it the string fields of the records written from then on. Records written
+        before are left as they are. Small, similar values compress much better with a dictionary than on their
+        own. The primary key, and the fields that are indexed, unique or filtered on by a view, are not compressed.
+        Compressed fields cannot be filtered on. Other processes read them with the dictionary, which they load
+        from redis the first time they read a value compressed with it, and should call `load_compression()`
+        to compress the values they write too.
+
+        :param sample: the number of records to sample, at least 1; default: 1000
+        :return: a dictionary with the keys: "dictionary_id"; "dictionary_bytes", the size of the dictionary;
+                "samples", the number of values it was trained on; "sampled_bytes" and "compressed_bytes", their
+                size before and after compression; and "ratio", the first over the second
+        :raises ValueError: if the collection has no string fields that can be compressed, or if the sampled
+                values are too few to train a dictionary on
+        """
+
+    async def load_compression(self) -> bool:
+        """
+        Loads the compression dictionaries of this collection saved in redis by `train_compression()` e.g. after
+        a restart, so that new values are compressed with the latest one. Values compressed with a dictionary
+        that is not loaded are read all the same, the dictionary being loaded from redis the first time.
+        Reading a value compressed with a dictionary that is not in redis raises a ValueError.
+
+        :return: whether a dictionary was trained for this collection
+        """
+
+    def hot_keys(self, top: int = 20) -> List[Tuple[str, float]]:
+        """
+        Gets the most accessed ids of this collection e.g. to find skewed access patterns that may need caching.
+        Only the reads and updates by id done through this collection in this process are counted, on the sample
+        of them set by `hot_key_sampling` in `create_collection()`; get_all() and the like are not counted.
+
+        :param top: the number of ids to return; default: 20
+        :return: the ids, hottest first, with their estimated number of recent accesses. The count of an id halves
+                for every minute it is not accessed
+        :raises ValueError: if hot_key_sampling was not set for this collection
+        """
+
+    async def increment(self, id: str, field: str, amount: Union[int, float] = 1) -> Union[int, float]:
+        """
+        Atomically increments the given counter field of the record of the given id without rewriting the record.
+        Counter fields are int or float fields marked with `counter=True` e.g. `views: int = Field(0, counter=True)`.
+        They are stored in their own keys and merged into the records when they are retrieved
+
+        :param id: the primary key of the record
+        :param field: the name of the counter field
+        :param amount: the amount to increment by; it can be negative; default: 1
+        :return: the new value of the counter
+        """
+
+    async def get_changes(self, after: str = "0-0", count: int = 100) -> List[Dict[str, Any]]:
+        """
+        Retrieves the changes to the records of this collection, oldest first. The collection must have been created
+        with `track_changes=True`. Each change is a dictionary with the keys "event_id", "op" (i.e. "insert",
+        "update" or "delete"), "id", "before" and "after", the latter two being dictionaries of the fields of the
+        record before and after the change
+
+        :param after: the event_id of the change after which to start; default: "0-0" i.e. from the oldest change
+        :param count: the maximum number of changes to return; default: 100
+        :return: the list of changes
+        """
+
+    async def delete_many(self, ids: List[str], strict: bool = False) -> int:
+        """
+        Removes all records belonging to the given ids
+
+        :param ids: the ids of the records to be removed
+        :param strict: whether to raise a KeyError if any of the ids has no record, the records of the others
+                being removed all the same; default: False
+        :return: the number of the ids that had records, or the number of distinct ids if the removal was buffered
+                as redis was unreachable
+        :raises KeyError: if strict and any of the ids has no record
+        """
+
+    async def delete_where(self, **field_filters: Any) -> int:
+        """
+        Removes the records whose fields are equal to the given values e.g. `delete_where(author="Jane")`.
+        The records are found and removed in redis by one script that scans the collection, so none of them is read
+
+        :param field_filters: the values the fields of the records to remove should be equal to; only non-nested,
+            non-counter fields can be filtered on
+        :return: the number of records removed
+        :raises ValueError: if no field filter is given or a field cannot be filtered on
+        :raises KeyError: if a field is not a field of the model
+        """
+
+    async def clear(self, max_ops_per_sec: Optional[float] = None) -> int:
+        """
+        Removes all the records of this collection, with their indexes, unique values, counters, copies in views,
+        cached lookups and the other data derived from them, leaving the other collections in the redis database
+        as they are, unlike `AsyncStore.clear()` which flushes it all. The keys of the collection are scanned and
+        removed a batch at a time, so the clearing is not atomic: records added while it runs may or may not be
+        removed. The usage of the quota of the namespace of the collection is released for the records removed.
+
+        The snapshots, the snapshot of the schema and the compression dictionaries of the collection are kept,
+        as is its change stream, which gets no events for the records removed. The records nested in the records
+        removed are kept in their own collections, and the mirrors of the store are not cleared.
+
+        :param max_ops_per_sec: the maximum number of keys to scan and remove per second, so that other clients
+                of a busy redis are not slowed down; default: None i.e. as fast as possible
+        :return: the number of records removed
+        :raises ValueError: if max_ops_per_sec is not positive
+        """
+
+    async def delete_matching(self, id_pattern: str, max_ops_per_sec: Optional[float] = None) -> int:
+        """
+        Removes the records whose ids match the given glob pattern e.g. `delete_matching("order-2023-*")`, as
+        matched by the MATCH option of SCAN. The keys of the collection are scanned a batch at a time, each batch
+        being removed by the script that scans it, with UNLINK, unless the records have indexes, unique values,
+        null indexes or a quota to update, in which case they are removed with those right after. The removal is
+        thus not atomic: records added while it runs may or may not be removed.
+
+        :param id_pattern: the glob pattern the ids of the records to remove should match, in which "*", "?",
+                "[...]" and "\\" are special
+        :param max_ops_per_sec: the maximum number of keys to scan per second, so that other clients
+                of a busy redis are not slowed down; default: None i.e. as fast as possible
+        :return: the number of records removed
+        :raises ValueError: if id_pattern is empty or max_ops_per_sec is not positive
+        """
+
+    async def update_where(self, filters: Dict[str, Any], changes: Dict[str, Any]) -> int:
+        """
+        Makes the given changes to the records whose fields are equal to the values in the filters
+        e.g. `update_where({"author": "Jane"}, {"in_stock": False})`. The records are found and updated in redis
+        by one script that scans the collection, so none of them is read. Their ttls are left as they are.
+
+        :param filters: the values the fields of the records to update should be equal to; only non-nested,
+            non-counter fields can be filtered on
+        :param changes: the new values of some non-nested fields, other than the primary key and counter fields
+        :return: the number of records updated
+        :raises ValueError: if the filters or the changes are empty, or a field cannot be filtered on or changed
+        :raises KeyError: if a field is not a field of the model
+        """
+
+
+class RecordIterator:
+    """
+    The iterator returned by `Collection.iter_all()`, which reads the next batch of records when the records
+    of the current one have all been returned
+    """
+
+    def __iter__(self) -> "RecordIterator": ...
+
+    def __next__(self) -> Model: ...
+
+
+class View:
+    """
+    A read-only materialized view of a collection i.e. the records of the collection that match a filter,
+    with only some of their fields. It is kept up to date on every write to the collection
+    """
+
+    def get_one(self, id: str) -> Optional[Dict[str, Any]]:
+        """
+        Retrieves the record of the given id in the view or None if it is not in the view
+
+        :param id: the id of the record to return
+        :return: the dictionary of the projected fields of the record
+        """
+
+    def get_many(self, ids: List[str]) -> List[Dict[str, Any]]:
+        """
+        Retrieves the records of the given ids that are in the view
+
+        :param ids: the ids of the records to return
+        :return: the list of dictionaries of the projected fields of the records
+        """
+
+    def get_all(self) -> List[Dict[str, Any]]:
+        """
+        Retrieves all the records in the view
+
+        :return: the list of dictionaries of the projected fields of the records
+        """
+
+    def rebuild(self, max_ops_per_sec: Optional[float] = None) -> int:
+        """
+        Rebuilds the view from all the records of its source collection.
+        This is needed for records that were added to the collection before the view was created.
+        The view is cleared and refilled in batches, so it is incomplete while it is being rebuilt
+
+        :param max_ops_per_sec: the maximum number of records to scan per second, so that other clients
+                of a busy redis are not slowed down; default: None i.e. as fast as possible
+        :return: the number of records in the view
+        """
+
+
+class Link:
+    """
+    A many-to-many link between the records of two collections e.g. students and the courses they attend.
+    Each side of a pair of linked records can be found from the other in a single read
+    """
+
+    def link(self, left_id: str, right_id: str) -> None:
+        """
+        Links the record of the given id in the left collection to the record of the given id in the right collection.
+        Linking records that are already linked does nothing
+
+        :param left_id: the primary key of the record in the left collection
+        :param right_id: the primary key of the record in the right collection
+        """
+
+    def unlink(self, left_id: str, right_id: str) -> None:
+        """
+        Unlinks the record of the given id in the left collection from the record of the given id in the right
+        collection. Unlinking records that are not linked does nothing
+
+        :param left_id: the primary key of the record in the left collection
+        :param right_id: the primary key of the record in the right collection
+        """
+
+    def left_of(self, right_id: str) -> List[Model]:
+        """
+        Retrieves the records of the left collection linked to the given record of the right collection.
+        Deleting a record does not unlink it, but the records that no longer exist are skipped
+
+        :param right_id: the primary key of the record in the right collection
+        :return: the list of model objects of the linked records, in no particular order
+        """
+
+    def right_of(self, left_id: str) -> List[Model]:
+        """
+        Retrieves the records of the right collection linked to the given record of the left collection.
+        Deleting a record does not unlink it, but the records that no longer exist are skipped
+
+        :param left_id: the primary key of the record in the left collection
+        :return: the list of model objects of the linked records, in no particular order
+        """
+
+
+class Cache:
+    """
+    A binary-safe key-value cache in the redis of a store, sharing the pool of the collections of the store
+    e.g. as the cache or session backend of a web framework. Its keys in redis are "<prefix>:<version>:<key>"
+    like those of Django. See `orredis.integrations.django.OrredisCache` for a Django cache backend based on it.
+    """
+
+    def get(self, key: str, version: int = 1) -> Optional[bytes]:
+        """
+        Gets the value of the given key
+
+        :param key: the key
+        :param version: the version of the key; default: 1
+        :return: the value, or None if the key is not in the cache
+        """
+
+    def get_many(self, keys: List[str], version: int = 1) -> Dict[str, bytes]:
+        """
+        Gets the values of the given keys in one round trip
+
+        :param keys: the keys
+        :param version: the version of the keys; default: 1
+        :return: the values of the keys that are in the cache, by key
+        """
+
+    def set(self, key: str, value: bytes, ttl: Optional[Union[timedelta, float]] = None, version: int = 1,
+            only_if_missing: bool = False) -> bool:
+        """
+        Sets the value of the given key
+
+        :param key: the key
+        :param value: the value
+        :param ttl: the time-to-live of the key as a timedelta or a number of seconds. A zero ttl removes the key
+            instead; default: None i.e. no expiry
+        :param version: the version of the key; default: 1
+        :param only_if_missing: whether to set the value only if the key is not in the cache; default: False
+        :return: True if the value was set else False
+        """
+
+    def set_many(self, items: Dict[str, bytes], ttl: Optional[Union[timedelta, float]] = None,
+                 version: int = 1) -> None:
+        """
+        Sets the values of the given keys in one round trip
+
+        :param items: the values by key
+        :param ttl: the time-to-live of the keys as a timedelta or a number of seconds. A zero ttl removes the keys
+            instead; default: None i.e. no expiry
+        :param version: the version of the keys; default: 1
+        """
+
+    def delete(self, key: str, version: int = 1) -> bool:
+        """
+        Removes the given key from the cache
+
+        :param key: the key
+        :param version: the version of the key; default: 1
+        :return: True if the key was in the cache else False
+        """
+
+    def delete_many(self, keys: List[str], version: int = 1) -> int:
+        """
+        Removes the given keys from the cache in one round trip
+
+        :param keys: the keys
+        :param version: the version of the keys; default: 1
+        :return: the number of the keys that were in the cache
+        """
+
+    def has_key(self, key: str, version: int = 1) -> bool:
+        """
+        Checks whether the given key is in the cache
+
+        :param key: the key
+        :param version: the version of the key; default: 1
+        :return: True if the key is in the cache else False
+        """
+
+    def touch(self, key: str, ttl: Optional[Union[timedelta, float]] = None, version: int = 1) -> bool:
+        """
+        Changes the expiry of the given key to the ttl from now
+
+        :param key: the key
+        :param ttl: the new time-to-live of the key as a timedelta or a number of seconds. A zero ttl removes
+            the key; default: None i.e. the key no longer expires
+        :param version: the version of the key; default: 1
+        :return: True if the key is in the cache else False
+        """
+
+    def clear(self) -> int:
+        """
+        Removes all keys of the cache, of all versions, scanning redis for them. The other keys in redis
+        e.g. the records of collections, are left as they are
+
+        :return: the number of keys removed
+        """
+
+
+class KeyValueNamespace:
+    """
+    A raw key-value namespace in the redis of a store, sharing the pool of the collections of the store,
+    with the commands the result backends of task queues like Celery need. Its keys in redis are "<namespace><key>",
+    the namespace being prepended as is. See `orredis.integrations.celery.OrredisBackend` for a Celery result backend
+    based on it.
+    """
+
+    def get(self, key: str) -> Optional[bytes]:
+        """
+        Gets the value of the given key
+
+        :param key: the key
+        :return: the value, or None if the key is not set
+        """
+
+    def mget(self, keys: List[str]) -> List[Optional[bytes]]:
+        """
+        Gets the values of the given keys in one round trip
+
+        :param keys: the keys
+        :return: the values in the order of the keys, with None for the keys that are not set
+        """
+
+    def set(self, key: str, value: bytes, ttl: Optional[Union[timedelta, float]] = None):
+        """
+        Sets the value of the given key
+
+        :param key: the key
+        :param value: the value
+        :param ttl: the time-to-live of the key as a timedelta or a number of seconds; default: None i.e. never expires
+        :raises ValueError: if the ttl is zero
+        """
+
+    def delete(self, key: str) -> bool:
+        """
+        Removes the given key
+
+        :param key: the key
+        :return: True if the key was set else False
+        """
+
+    def expire(self, key: str, ttl: Union[timedelta, float]) -> bool:
+        """
+        Makes the given key expire after the given time from now
+
+        :param key: the key
+        :param ttl: the new time-to-live of the key as a timedelta or a number of seconds
+        :return: True if the key is set else False
+        """
+
+    def incr(self, key: str, amount: int = 1) -> int:
+        """
+        Atomically increments the integer value of the given key, starting from 0 if the key is not set
+
+        :param key: the key
+        :param amount: the amount to increment by; default: 1
+        :return: the new value
+        """
+
+
+class Store:
+    """
+    The Store containing all collections that are stored in redis.
+
+    :param url: the redis url e.g. redis://localhost:6379/0 or redis://[::1]:6379/0 for an IPv6 host. With a
+                "redis+srv" scheme e.g. redis+srv://_redis._tcp.example.com/0, the host and port of redis are
+                looked up in the DNS SRV records of the url's host, prefixed with "_redis._tcp." if it has no service
+                label, when the store is created, using the nameservers and search domains of /etc/resolv.conf
+    :param pool_size: the maximum number of connections in the connection pool to redis; default: 5
+    :param default_ttl: the default time-to-live for each record as a timedelta or a number of seconds, or
+                    a datetime at which records expire, applied with millisecond precision; default: None i.e. no expiry
+    :param timeout: the time in milliseconds beyond which a timeout error is raised on failure to
+                    get a connection to redis from the connection pool; default is 30000 (30 seconds).
+                    A PoolExhaustedError is raised if all connections of the pool stay in use till then
+    :param max_lifetime: the maximum lifetime in milliseconds connections in the pool; default is 1800000 (30 minutes)
+    :param defaults: the options of `create_collection()` to use for all collections of this store unless they are
+                    given to `create_collection()` i.e. any of "id_generator", "snowflake_worker_id", "retention",
+                    "bloom_filter", "track_changes", "register_nested_models", "nested_depth", "namespace" and
+                    "enforce_constraints"
+                    e.g. dict(track_changes=True, nested_depth=5); default: None
+    :param write_buffer: the maximum number of writes (inserts, updates and deletes) to hold in memory while redis is
+                    unreachable i.e. refuses or drops connections. They are applied in order once redis is reachable
+                    again, and any writes made before then are queued behind them. Those that fail then are dropped,
+                    their errors got by `take_failed_writes()`. It is meant for non-critical data e.g. telemetry,
+                    since buffered writes are lost if the process exits. Writes with values of unique fields are not
+                    buffered, failing with a ConnectionError instead; default: None i.e. writes fail with a
+                    ConnectionError
+    :param write_buffer_overflow: what to do with a write when the write buffer is full: "drop_oldest" drops the
+                    oldest buffered write, "drop_newest" drops the new write and "raise" raises a ConnectionError;
+                    default: "drop_oldest"
+    :param circuit_breaker_threshold: the number of calls in a row that should fail to get a connection to redis
+                    e.g. during an outage, before the circuit breaker of the store opens and calls raise a
+                    CircuitOpenError without waiting for a connection. Calls whose connections were all in use are
+                    not counted; default: None i.e. no circuit breaker
+    :param circuit_breaker_reset: the time in milliseconds after which an open circuit breaker lets one call through
+                    to probe redis, closing if it gets a connection and opening again otherwise; default: 30000
+    :param blocking_pool_size: the maximum number of connections in a second pool dedicated to the long operations
+                    that scan whole collections i.e. ids(), snapshot(), repair(), compact(), track_cardinality(),
+                    create_index(), rebuild(), ttl_audit() and apply_retention(), so that they cannot starve other
+                    calls of connections. It has the timeout and max_lifetime of the main pool;
+                    default: None i.e. long operations share the main pool
+    :param max_batch_bytes: the maximum number of bytes of the records written by add_many() or read by get_many()
+                    in one round trip to redis, so that big records do not overrun the buffers of redis. The records
+                    written are split by their sizes, and those read by the average size of the records of their
+                    collection read before; default: 8388608 i.e. 8 MiB
+    :param lazy: whether to open the connections of the pool as they are needed instead of when the store is
+                    created, so that the store can be created before redis is up e.g. on the import of an app.
+                    Call connect() to check that redis can be reached; default: False
+    """
+
+    def __init__(self,
+                 url: str,
+                 pool_size: int,
+                 default_ttl: Optional[Union[timedelta, datetime, float]],
+                 timeout: Optional[int],
+                 max_lifetime: Optional[int],
+                 *,
+                 defaults: Optional[Dict[str, Any]] = None,
+                 write_buffer: Optional[int] = None,
+                 write_buffer_overflow: str = "drop_oldest",
+                 circuit_breaker_threshold: Optional[int] = None,
+                 circuit_breaker_reset: Optional[int] = None,
+                 blocking_pool_size: Optional[int] = None,
+                 max_batch_bytes: Optional[int] = None,
+                 lazy: bool = False) -> None: ...
+
+    def clear(self, asynchronous: bool = False) -> None:
+        """
+        Removes all records in the redis store
+
+        :param asynchronous: whether the FLUSHALL should be done asynchronously or synchronously. default: False
+        """
+
+    def latency(self, samples: int = 10) -> Dict[str, float]:
+        """
+        Measures the round-trip latency of PINGs to redis, sent one after the other on one connection of the pool,
+        e.g. for health dashboards. Comparing "checkout_ms" with the latencies tells a busy pool from a slow network.
+
+        :param samples: the number of PINGs to send, at least 1; default: 10
+        :return: a dictionary with the keys: "samples", the number of PINGs sent; "min_ms", "avg_ms", "p99_ms" and
+                "max_ms", the statistics of their latencies in milliseconds; and "checkout_ms", the time in
+                milliseconds the connection took to check out of the pool
+        """
+
+    def connect(self) -> None:
+        """
+        Checks out a connection and PINGs redis on it e.g. on the startup of an app whose store was created
+        with `lazy=True`, so that the app fails to start if redis cannot be reached
+
+        :raises ConnectionError: if redis cannot be reached
+        """
+
+    def close(self) -> None:
+        """
+        Closes this store and all its collections, cancelling its background tasks and scheduled backups e.g. on
+        the shutdown of an app. Any later call that needs a connection to redis raises a ConnectionError
+        """
+
+    def metrics(self) -> Dict[str, Dict[str, Dict[str, int]]]:
+        """
+        Gets the counts of the operations on each collection of this store since the collections were created,
+        e.g. to break down the read and write volume by model on a dashboard. The counts are kept in this process
+        only and cover the operations that succeeded: add_one, add_many, update_one, increment, delete_many, get_one,
+        get_many, get_all, parallel_iter, get_one_partially, get_many_partially and get_all_partially.
+
+        :return: a dictionary of the name of each collection to a dictionary of each operation called on it to a
+                dictionary with the keys: "calls", the number of calls, and "records", the number of records they read
+                or wrote e.g. {"Book": {"add_many": {"calls": 1, "records": 4}}}
+        """
+
+    def circuit_breaker(self) -> Optional[Dict[str, Any]]:
+        """
+        Describes the state of the circuit breaker of this store e.g. for health checks.
+
+        :return: a dictionary with the keys: "state", one of "closed", "open" and "half_open" i.e. a call is probing
+                redis; "consecutive_failures", the number of calls in a row that failed to get a connection;
+                "failure_threshold", the circuit_breaker_threshold of the store; and "retry_in_ms", the time in
+                milliseconds till a call is let through to probe redis, None unless it is open.
+                None if the store has no circuit breaker
+        """
+
+    def ttl_audit(self, threshold: Optional[Union[timedelta, float]] = None,
+                  imminent: Optional[Union[timedelta, float]] = None,
+                  max_ops_per_sec: Optional[float] = None) -> Dict[str, Dict[str, int]]:
+        """
+        Counts the records of each collection by their time-to-live e.g. to catch collections whose records were
+        written without a ttl by mistake, or that are about to expire en masse. The keys are scanned in batches
+        so that redis is not blocked by large collections.
+
+        :param threshold: the ttl below which records are counted in "below_threshold"; default: 1 hour
+        :param imminent: the ttl below which records are counted in "imminent_expiry"; default: 60 seconds
+        :param max_ops_per_sec: the maximum number of records to scan per second, so that other clients
+                of a busy redis are not slowed down; default: None i.e. as fast as possible
+        :return: a dictionary of the name of each collection to a dictionary with the keys: "records", the number
+                of records; "no_ttl", the number that never expire; "below_threshold" and "imminent_expiry", the
+                numbers whose ttl is below the threshold and the imminent expiry respectively
+        """
+
+    def apply_retention(self, max_ops_per_sec: Optional[float] = None) -> Dict[str, int]:
+        """
+        Applies the retention policies of all collections, removing records that are older than their policy allows
+        or capping their time-to-live to the retention period if the collection has no retention_field.
+        The keys are scanned in batches so that redis is not blocked by large collections.
+
+        :param max_ops_per_sec: the maximum number of records to scan per second, so that other clients
+                of a busy redis are not slowed down; default: None i.e. as fast as possible
+        :return: the number of records affected in each collection that has a retention policy
+        """
+
+    def schedule_backup(self, interval: Union[timedelta, float, str], target: str, keep: int = 7,
+                        chunk_size: int = 1000) -> None:
+        """
+        Backs up all the collections created so far every `interval`
+        e.g. `store.schedule_backup("@daily", "s3://bucket/orredis")`.
+        Each backup is a new directory under `target`, named after the time of the backup e.g. "20240105T000000.000Z",
+        with a file for each collection named after it e.g. "Book.export", written as by `Collection.backup()`
+        and restored with `Collection.import_from()`. Only the `keep` latest backups made by the schedule are kept;
+        older ones are removed, with fsspec if they are at urls. A backup that fails is removed and the next one
+        is made as scheduled. The backups are made in a background task of this store, without any event loop,
+        until the schedule is cancelled with cancel_backup_schedule() or the store is closed.
+
+        :param interval: the time (a timedelta or number of seconds) between backups, or a cron-like shorthand:
+                "@hourly", "@daily", "@weekly" or "@every <n><s|m|h|d>" e.g. "@every 15m"
+        :param target: the local directory or the url e.g. "s3://bucket/path" under which to make the backups;
+                urls are opened as in `Collection.backup()`
+        :param keep: the number of latest backups to keep, at least 1; default: 7
+        :param chunk_size: the number of records in each chunk of the exports; default: 1000
+        :raises ValueError: if the interval is invalid or a backup into the target is already scheduled
+        """
+
+    def backup_schedules(self) -> Dict[str, Dict[str, Any]]:
+        """
+        Gets the status of the scheduled backups of this store
+
+        :return: a dictionary of the target of each schedule to a dictionary with the keys: "interval_secs", the
+                seconds between backups; "keep", the number of backups kept; "runs" and "failures", the numbers of
+                backups that succeeded and failed; "in_progress", whether a backup is being made; "last_error", the
+                message of the error of the latest backup if it failed, or None; and "backups", the paths of
+                the backups kept, oldest first
+        """
+
+    def cancel_backup_schedule(self, target: str) -> bool:
+        """
+        Cancels the scheduled backup into the given target. A backup that is in progress is left to complete.
+
+        :param target: the target given to schedule_backup()
+        :return: True if there was a backup scheduled into that target, else False
+        """
+
+    def background_tasks(self) -> Dict[str, Dict[str, Any]]:
+        """
+        Gets the status of the background tasks of this store e.g. the compactions started with
+        `compact(background=True)` and the scheduled backups
+
+        :return: a dictionary of the name of each task to a dictionary with the keys: "running", whether it is still
+                running; "runs", the number of runs that have completed; and "error", the message of the error
+                that stopped it, or None
+        """
+
+    def cancel_background(self, task_name: str) -> bool:
+        """
+        Cancels the background task of the given name. A run that is in progress is left to complete.
+
+        :param task_name: the name of the task e.g. "compact:Book"
+        :return: True if there was a task of that name, else False
+        """
+
+    def add_mirror(self, url: str) -> None:
+        """
+        Adds another redis instance to which every write (insert, update, increment and delete) made through this
+        store is also applied. The writes are queued and applied in the background, in order, retrying a few times
+        with a growing delay if the mirror is unreachable. This is best-effort: writes that still fail, or that are
+        made while the queue of the mirror is full, are dropped. It should be called before any writes are made.
+
+        :param url: the redis url of the mirror e.g. redis://replica:6379/0, which may have an IPv6 host or
+                    a "redis+srv" scheme as the url of the store can
+        """
+
+    def pending_writes(self) -> int:
+        """
+        Gets the number of writes made while redis was unreachable that are still in the write buffer, waiting to be
+        applied e.g. for monitoring. It is always 0 if the store has no write buffer.
+        """
+
+    def take_failed_writes(self) -> List[str]:
+        """
+        Takes the errors of the buffered writes that failed when they were applied once redis was reachable again
+        e.g. because they exceeded a quota or redis was out of memory. Such writes are dropped rather than retried,
+        since they would fail again, or be applied twice if redis applied them before failing e.g. on a timeout.
+        The errors are returned oldest first and removed from the store, which keeps at most as many as the
+        capacity of its write buffer. It is always empty if the store has no write buffer.
+        """
+
+    def set_quota(self, namespace: str, max_records: Optional[int] = None, max_bytes: Optional[int] = None) -> None:
+        """
+        Sets the limits of the quota of the given namespace, shared by all collections created with that namespace.
+        Inserts and updates that would take the usage beyond a limit raise a QuotaExceededError. The limits are saved
+        in redis so that they apply to every store with collections in the namespace.
+
+        :param namespace: the namespace whose quota is set
+        :param max_records: the maximum number of records in the collections of the namespace;
+                    default: None i.e. no limit
+        :param max_bytes: the maximum total length in bytes of the field names and values of the records in the
+                    collections of the namespace; default: None i.e. no limit
+        """
+
+    def get_quota(self, namespace: str) -> Dict[str, Optional[int]]:
+        """
+        Gets the usage of the quota of the given namespace, with its limits
+
+        :param namespace: the namespace whose quota is got
+        :return: a dictionary with the keys: "records", the number of records in the collections of the namespace;
+                "bytes", the total length in bytes of their field names and values; and "max_records" and
+                "max_bytes", the limits of the quota, which are None if not set
+        """
+
+    def verify_against(self, other: "Store", collection: Type[Model], sample: float = 0.01) -> Dict[str, Any]:
+        """
+        Compares a random sample of the records of the given collection in this store with those in the other store,
+        field by field, to report the drift between them e.g. to confirm a mirror is in sync before switching to it.
+        Records are sampled from both stores so that records missing from either are detected.
+        The other store does not need to have the collection created on it.
+
+        :param other: the store to compare this store with
+        :param collection: the Model whose collection is to be compared
+        :param sample: the fraction of the records in each store to compare, greater than 0 and at most 1;
+                    default: 0.01 i.e. about 1%
+        :return: a dictionary with the keys: "sampled", the number of records compared; "missing", the ids of the
+                records only found in this store; "extra", the ids of the records only found in the other store;
+                and "mismatched", a dictionary mapping the id of each record that differs to a dictionary of
+                field to (value in this store, value in the other store)
+        """
+
+    def describe(self) -> Dict[str, Any]:
+        """
+        Describes the collections, views and links of this store e.g. to render admin UIs or to validate
+        the store against external contracts. It reads nothing from redis.
+
+        :return: a dictionary with the keys: "collections", a dictionary of the name of each collection to its
+                description i.e. a dictionary with the keys: "primary_key_field"; "fields", a dictionary of
+                the fields to their types; "counter_fields", "indexed_fields", "indexed_list_fields",
+                "compound_indexes", "range_indexes" and "unique_fields"; "relationships", a list of dictionaries
+                with the keys: "field", "kind" i.e. "nested", "reference" or "foreign_key", and "collection", the
+                name of the related collection or None for foreign keys; "version" and "namespace", which are None
+                if not set; "views", a dictionary of the name of each view to a dictionary with the keys: "source",
+                "projection" and "filter"; and "links", a dictionary of the name of each link to a dictionary with
+                the keys: "left" and "right", the names of the collections it links
+        """
+
+    def browse(
+        self,
+        collection: Type[Model],
+        cursor: Optional[str] = None,
+        limit: int = 20,
+        fields: Optional[List[str]] = None,
+    ) -> Dict[str, Any]:
+        """
+        Gets a page of the records of the given collection as dictionaries of only the given fields, e.g. those
+        the user of an admin panel is allowed to see, with the total number of records and the cursor of the next
+        page, all in one call. At most `limit` records are read whatever the size of the collection, while the
+        total is counted from the keys of the collection without reading the records.
+        Records are in the order in which SCAN finds them, which is stable as long as the collection is not changed.
+
+        :param collection: the Model whose records are browsed
+        :param cursor: the next_cursor returned with the previous page; default: None i.e. the first page
+        :param limit: the maximum number of records in the page, greater than 0; default: 20
+        :param fields: the fields of the records to return, nested fields being given as dotted paths
+                    e.g. "author.name"; default: None i.e. all fields
+        :return: a dictionary with the keys: "records", the list of dictionaries of the fields of the records;
+                "total", the number of records in the collection; and "next_cursor", the cursor to pass to get
+                the next page, which is None if this is the last page
+        :raises KeyError: if the collection has not been created or any of the fields is not a field of the model
+        :raises ValueError: if the cursor was not returned by browse() or the limit is 0
+        """
+
+    def plan_migration(self, model: Type[Model]) -> Dict[str, Any]:
+        """
+        Compares the schema of the collection of the given model with the snapshot of it last saved in redis
+        by save_schema_snapshot(), without changing any records, to plan the migration of the records saved
+        in the older schema e.g. as a dry-run before deploying a changed model.
+
+        :param model: the Model whose collection is to be compared
+        :return: a dictionary with the keys: "collection", the name of the collection; "has_snapshot", whether a
+                snapshot was saved, all fields being reported as added if not; "added" and "removed", dictionaries
+                of the fields added to or removed from the schema to their types; and "type_changed", a dictionary
+                of the fields whose types changed to (saved type, current type)
+        """
+
+    def save_schema_snapshot(self, model: Type[Model]) -> None:
+        """
+        Saves the snapshot of the current schema of the collection of the given model in redis, replacing the one
+        that plan_migration() compares with. Call it once the records have been migrated to the current schema.
+
+        :param model: the Model whose collection's schema is to be saved
+        """
+
+    def create_collection(self,
+                          model: Type[Model],
+                          primary_key_field: str,
+                          *,
+                          id_generator: Optional[str] = None,
+                          snowflake_worker_id: Optional[int] = None,
+                          retention: Optional[Union[timedelta, float]] = None,
+                          retention_field: Optional[str] = None,
+                          bloom_filter: Optional[bool] = None,
+                          track_changes: Optional[bool] = None,
+                          coercions: Optional[Dict[str, List[str]]] = None,
+                          version: Optional[int] = None,
+                          upgrades: Optional[Dict[int, Callable[[Dict[str, str]], Dict[str, Any]]]] = None,
+                          persist_upgrades: bool = False,
+                          register_nested_models: Optional[bool] = None,
+                          nested_depth: Optional[int] = None,
+                          hot_key_sampling: Optional[float] = None,
+                          namespace: Optional[str] = None,
+                          foreign_keys: Optional[List[str]] = None,
+                          indexed_fields: Optional[List[str]] = None,
+                          unique_fields: Optional[List[str]] = None,
+                          compound_indexes: Optional[List[Tuple[str, ...]]] = None,
+                          enforce_constraints: Optional[bool] = None,
+                          index_normalizers: Optional[Dict[str, str]] = None,
+                          case_insensitive_keys: bool = False,
+                          track_insertion_order: bool = False,
+                          indexed_list_fields: Optional[List[str]] = None) -> None:
+        """
+        Creates a new Collection within the store for the given model supplied
+
+        Options that are not given, or are None, fall back to the defaults of the store.
+
+        :param model: the Model schema to be used for this collection. String type hints e.g. in modules using
+                    `from __future__ import annotations`, are resolved against the modules of the model and the models
+                    nested in it, and the models of the collections already created in this store
+        :param primary_key_field: the field that contains the unique primary key for each model instance e.g.
+                                a book's primary key might be its ISBN
+        :param id_generator: the algorithm used to generate the primary key of records added without one
+                            i.e. "uuid4", "ulid", "snowflake" or "ksuid". All but "uuid4" produce ids that are
+                            lexicographically sortable by creation time; default: None i.e. the primary key is required
+        :param snowflake_worker_id: the worker id, from 0 to 1023, in the ids generated by the "snowflake" id_generator.
+                            Processes generating ids for the same collection should have different worker ids, else
+                            they may generate the same id in the same millisecond; default: None i.e. one hashed
+                            from the host name and the process id, which processes rarely, but may, share
+        :param retention: the maximum age (a timedelta or number of seconds) of records in this collection.
+                        If no retention_field is given, the age is counted from the last write i.e. the ttl of
+                        records is capped to this period; default: None i.e. records are kept indefinitely
+        :param retention_field: the date, datetime, int or float (unix timestamp) field that holds the time
+                        each record was created. Records whose field is older than the retention period are removed
+                        when `apply_retention()` is called; default: None
+        :param bloom_filter: whether to keep a bloom filter of the primary keys of this collection so that lookups of
+                        nonexistent ids in `get_one()` and `exists()` do not hit the records. It requires the RedisBloom
+                        module and is ignored if it is not loaded. All writers of this collection should enable it;
+                        default: False
+        :param track_changes: whether to record every insert, update and delete done through this collection, with
+                        the fields of the record before and after the change, in a redis stream that can be read with
+                        `get_changes()` or tailed with `orredis.cdc.ChangeConsumer`. Only the latest 100,000 or so
+                        changes are kept; default: False
+        :param coercions: the rules, per field, for reading values saved in formats other than the ones orredis writes
+                        e.g. by legacy systems, so that such data can be read without first rewriting it. The rules are
+                        "empty_to_none" (an empty string is None), "float_to_int" (a whole float e.g. "3.0" in an int
+                        field), "loose_bool" ("1"/"0", "yes"/"no", "on"/"off" or any casing of "true"/"false" in a bool
+                        field) and "timestamp_to_datetime" (unix timestamps in seconds in date or datetime fields).
+                        The first rule that matches a value is applied e.g. {"age": ["empty_to_none", "float_to_int"]};
+                        default: None i.e. values are read as they are written by orredis
+        :param version: the format version of the records of this collection. It is saved in each record written by
+                        `add_one()` or `add_many()`, and records saved without one are at version 0. Records of older
+                        versions are upgraded with `upgrades` when they are read; default: None i.e. no versioning
+        :param upgrades: a dictionary mapping each older version to the function that upgrades a record from it to the
+                        next version. Each function receives the non-nested fields of the record as saved in redis i.e.
+                        as strings, and returns the fields in the next version's format, as strings or python values.
+                        Versions without an upgrade function are skipped. Nested records are not upgraded when read
+                        through their parent; default: None
+        :param persist_upgrades: whether records upgraded on read are saved back to redis in the current version so that
+                        they are not upgraded again. A record is not saved if it was changed in the meantime;
+                        default: False
+        :param register_nested_models: whether to create the collections of the models nested in this model that have
+                        none yet, instead of raising a KeyError. The primary key field of each is got from its schema:
+                        the "primary_key_field" key of its `schema_extra`, or the field declared with
+                        `Field(..., primary_key=True)`. Models that refer to each other e.g. a Team whose captain
+                        is a Player of that Team, are registered this way; default: False
+        :param nested_depth: the number of levels of nested records read with each record. Models may nest themselves
+                        e.g. an Employee whose manager is an Employee, directly or through other models. Nested records
+                        deeper than this are read as None so such fields should be Optional; default: 3
+        :param hot_key_sampling: the fraction, greater than 0 and at most 1, of the reads and updates by id done
+                        through this collection in this process that are counted to find its most accessed ids with
+                        `hot_keys()`. The counts halve every minute so that they reflect recent accesses;
+                        default: None i.e. accesses are not counted
+        :param namespace: the namespace e.g. the team owning this collection, whose quota set by `set_quota()` limits
+                        the records inserted through it, including its nested records. Its usage is updated on every
+                        insert, update and delete through the collection, but not when records expire or are removed
+                        by retention; default: None i.e. the collection has no quota
+        :param foreign_keys: the non-nested fields that hold the primary keys of records of other collections e.g.
+                        the "user_id" of an Order. An index of each is updated on every write so that `children_of()`
+                        gets the records that refer to a given record without scanning this collection. Records
+                        saved before the field was a foreign key are not indexed until they are saved again;
+                        default: None
+        :param indexed_fields: other non-nested fields whose records are looked up by their exact values e.g.
+                        the "email" of a User. An index of each is updated on every write so that `find_by()` gets
+                        the records with a given value without scanning this collection. Records saved before
+                        the field was indexed are not indexed until they are saved again; default: None
+        :param unique_fields: the non-nested fields whose values no two records can share e.g. the "email" of a User.
+                        Inserts and updates reserve the values atomically and raise a `UniqueViolationError` if
+                        another record has any of them. They are never buffered by the write buffer of the store, as
+                        the values would not be checked till later. Records saved before the field was unique are not
+                        checked against until they are saved again; default: None
+        :param compound_indexes: the groups of non-nested fields whose records are looked up by their exact values
+                        together e.g. ("country", "city"). An index of each is updated on every write so that
+                        `find_by_index()` gets the records with given values without scanning this collection.
+                        Records saved before the index was added are not indexed until they are saved again;
+                        default: None
+        :param enforce_constraints: whether to check the non-nested fields of constrained types e.g. `conint(ge=0)` or
+                        `constr(max_length=10)` against their minimum, maximum, length and pattern on every insert and
+                        update, raising a ValueError for the values that break them. This catches invalid values that
+                        bypass the validation of the model e.g. in `update_one()`; default: False
+        :param index_normalizers: the normalizers of the values of indexed, compound-indexed and unique fields, by
+                        field, applied before the values are indexed or looked up e.g. {"email": "strip,lower"} so
+                        that "Email@X.com " and "email@x.com" are the same unique value. Each is "lower", "strip",
+                        a comma-separated list of them applied in order, or the body of a lua function of `value`
+                        returning the normalized value e.g. "return (string.gsub(value, '%.', ''))". The records keep
+                        their values as they were given; default: None i.e. values are indexed as they are
+        :param case_insensitive_keys: whether the primary keys are case-insensitive i.e. lowercased in the keys
+                        of the records in redis on both writes and reads, so that `get_one("Alice")` and
+                        `get_one("alice")` get the same record. The records keep their primary keys as they were
+                        given; default: False
+        :param track_insertion_order: whether the ids of the records are kept in a sorted set by the time they were
+                        first inserted, so that the latest and oldest records are got with `get_latest()` and
+                        `get_oldest()`; default: False
+        :param indexed_list_fields: the list fields of non-nested items whose records are looked up by any of their
+                        elements e.g. the "tags" of a Post. An index of each element is updated on every write,
+                        moving the record from the indexes of the elements it no longer has to those it now has, so
+                        that `find(tags__contains="rust")` gets the records holding an element without scanning this
+                        collection. Records saved before the field was indexed are not indexed until they are saved
+                        again; default: None
+        """
+
+    def get_collection(self, model: Type[Model]) -> Collection:
+        """
+        Retrieves a handle on the collection for a given model to manipulate the data within or
+        query it
+
+        :param model: the Model schema whose collection is to be retrieved
+        :return: the collection instance to be used to manipulate data or query it using collection.add_one() etc.
+        """
+
+    def create_view(self,
+                    name: str,
+                    source_collection: Type[Model],
+                    projection: List[str],
+                    filter: Optional[Dict[str, Any]] = None) -> None:
+        """
+        Creates a materialized view of the collection of the given model i.e. the records that match the filter,
+        with only the fields in the projection. The view is kept up to date on every write to the collection
+        so that read-heavy code can get pre-filtered, pre-projected dictionaries. It must be called after the
+        collection is created and before any call to `get_collection()` or `get_view()`
+
+        :param name: the unique name of the view
+        :param source_collection: the Model of the collection the view is got from
+        :param projection: the non-nested fields of the records to keep in the view
+        :param filter: the values that the non-nested fields of a record should be equal to for it to be in the view;
+                    default: None i.e. all records are in the view
+        """
+
+    def get_view(self, name: str) -> View:
+        """
+        Retrieves a read-only handle on the view of the given name
+
+        :param name: the name of the view
+        :return: the view instance to be used to query it using view.get_all() etc.
+        """
+
+    def create_link(self, name: str, left_collection: Type[Model], right_collection: Type[Model]) -> None:
+        """
+        Creates a many-to-many link between the records of the collections of the given models e.g. students
+        and courses, each side being found from the other without scanning either collection.
+        Unlike views, links can be created at any time after the collections are created
+
+        :param name: the unique name of the link
+        :param left_collection: the Model of the collection on the left side of the link
+        :param right_collection: the Model of the collection on the right side of the link
+        """
+
+    def get_link(self, name: str) -> Link:
+        """
+        Retrieves a handle on the link of the given name
+
+        :param name: the name of the link
+        :return: the link instance to be used to link records using link.link() or query it using link.left_of() etc.
+        """
+
+    def get_cache(self, prefix: str = "") -> Cache:
+        """
+        Instantiates a binary-safe key-value cache in the redis of this store, sharing the pool of its collections
+        e.g. as the cache or session backend of a web framework
+
+        :param prefix: the prefix of the keys of the cache in redis; default: ""
+        :return: the cache
+        """
+
+    def kv(self, namespace: str) -> KeyValueNamespace:
+        """
+        Instantiates a raw key-value namespace in the redis of this store, sharing the pool of its collections
+        e.g. as the result backend of Celery
+
+        :param namespace: the string prepended to the keys given to the namespace to get their keys in redis
+        :return: the key-value namespace
+        """
+
+
+class AsyncView:
+    """
+    A read-only materialized view of a collection i.e. the records of the collection that match a filter,
+    with only some of their fields. It is kept up to date on every write to the collection
+    but asynchronously. For the synchronous API, use View
+    """
+
+    async def get_one(self, id: str) -> Optional[Dict[str, Any]]:
+        """
+        Retrieves the record of the given id in the view or None if it is not in the view
+
+        :param id: the id of the record to return
+        :return: the dictionary of the projected fields of the record
+        """
+
+    async def get_many(self, ids: List[str]) -> List[Dict[str, Any]]:
+        """
+        Retrieves the records of the given ids that are in the view
+
+        :param ids: the ids of the records to return
+        :return: the list of dictionaries of the projected fields of the records
+        """
+
+    async def get_all(self) -> List[Dict[str, Any]]:
+        """
+        Retrieves all the records in the view
+
+        :return: the list of dictionaries of the projected fields of the records
+        """
+
+    async def rebuild(self, max_ops_per_sec: Optional[float] = None) -> int:
+        """
+        Rebuilds the view from all the records of its source collection.
+        This is needed for records that were added to the collection before the view was created.
+        The view is cleared and refilled in batches, so it is incomplete while it is being rebuilt
+
+        :param max_ops_per_sec: the maximum number of records to scan per second, so that other clients
+                of a busy redis are not slowed down; default: None i.e. as fast as possible
+        :return: the number of records in the view
+        """
+
+
+class AsyncLink:
+    """
+    A many-to-many link between the records of two collections e.g. students and the courses they attend.
+    Each side of a pair of linked records can be found from the other in a single read
+    but asynchronously. For the synchronous API, use Link
+    """
+
+    async def link(self, left_id: str, right_id: str) -> None:
+        """
+        Links the record of the given id in the left collection to the record of the given id in the right collection.
+        Linking records that are already linked does nothing
+
+        :param left_id: the primary key of the record in the left collection
+        :param right_id: the primary key of the record in the right collection
+        """
+
+    async def unlink(self, left_id: str, right_id: str) -> None:
+        """
+        Unlinks the record of the given id in the left collection from the record of the given id in the right
+        collection. Unlinking records that are not linked does nothing
+
+        :param left_id: the primary key of the record in the left collection
+        :param right_id: the primary key of the record in the right collection
+        """
+
+    async def left_of(self, right_id: str) -> List[Model]:
+        """
+        Retrieves the records of the left collection linked to the given record of the right collection.
+        Deleting a record does not unlink it, but the records that no longer exist are skipped
+
+        :param right_id: the primary key of the record in the right collection
+        :return: the list of model objects of the linked records, in no particular order
+        """
+
+    async def right_of(self, left_id: str) -> List[Model]:
+        """
+        Retrieves the records of the right collection linked to the given record of the left collection.
+        Deleting a record does not unlink it, but the records that no longer exist are skipped
+
+        :param left_id: the primary key of the record in the left collection
+        :return: the list of model objects of the linked records, in no particular order
+        """
+
+
+class AsyncStore:
+    """
+    The AsyncStore containing all async_collections that are stored in redis. It is meant to be used
+    where async-await is used
+
+    :param url: the redis url e.g. redis://localhost:6379/0 or redis://[::1]:6379/0 for an IPv6 host. With a
+                "redis+srv" scheme e.g. redis+srv://_redis._tcp.example.com/0, the host and port of redis are
+                looked up in the DNS SRV records of the url's host, prefixed with "_redis._tcp." if it has no service
+                label, when the store is created, using the nameservers and search domains of /etc/resolv.conf
+    :param pool_size: the maximum number of connections in the connection pool to redis; default: 5
+    :param default_ttl: the default time-to-live for each record as a timedelta or a number of seconds, or
+                    a datetime at which records expire, applied with millisecond precision; default: None i.e. no expiry
+    :param timeout: the time in milliseconds beyond which a timeout error is raised on failure to
+                    get a connection to redis from the connection pool; default is 30000 (30 seconds).
+                    A PoolExhaustedError is raised if all connections of the pool stay in use till then
+    :param max_lifetime: the maximum lifetime in milliseconds connections in the pool; default is 1800000 (30 minutes)
+    :param defaults: the options of `create_collection()` to use for all collections of this store unless they are
+                    given to `create_collection()` i.e. any of "id_generator", "snowflake_worker_id", "retention",
+                    "bloom_filter", "track_changes", "register_nested_models", "nested_depth", "namespace" and
+                    "enforce_constraints"
+                    e.g. dict(track_changes=True, nested_depth=5); default: None
+    :param max_connecting: the maximum number of connections to redis being established at any one time, which
+                    smooths the spike of new connections when many tasks start at once; default: None i.e. no limit
+    :param write_buffer: the maximum number of writes (inserts, updates and deletes) to hold in memory while redis is
+                    unreachable i.e. refuses or drops connections. They are applied in order once redis is reachable
+                    again, and any writes made before then are queued behind them. Those that fail then are dropped,
+                    their errors got by `take_failed_writes()`. It is meant for non-critical data e.g. telemetry,
+                    since buffered writes are lost if the process exits. Writes with values of unique fields are not
+                    buffered, failing with a ConnectionError instead; default: None i.e. writes fail with a
+                    ConnectionError
+    :param write_buffer_overflow: what to do with a write when the write buffer is full: "drop_oldest" drops the
+                    oldest buffered write, "drop_newest" drops the new write and "raise" raises a ConnectionError;
+                    default: "drop_oldest"
+    :param circuit_breaker_threshold: the number of calls in a row that should fail to get a connection to redis
+                    e.g. during an outage, before the circuit breaker of the store opens and calls raise a
+                    CircuitOpenError without waiting for a connection. Calls whose connections were all in use are
+                    not counted; default: None i.e. no circuit breaker
+    :param circuit_breaker_reset: the time in milliseconds after which an open circuit breaker lets one call through
+                    to probe redis, closing if it gets a connection and opening again otherwise; default: 30000
+    :param blocking_pool_size: the maximum number of connections in a second pool dedicated to the long operations
+                    that scan whole collections i.e. ids(), snapshot(), repair(), compact(), track_cardinality(),
+                    create_index(), rebuild(), ttl_audit() and apply_retention(), so that they cannot starve other
+                    calls of connections. It has the timeout and max_lifetime of the main pool;
+                    default: None i.e. long operations share the main pool
+    :param max_batch_bytes: the maximum number of bytes of the records written by add_many() or read by get_many()
+                    in one round trip to redis, so that big records do not overrun the buffers of redis. The records
+                    written are split by their sizes, and those read by the average size of the records of their
+                    collection read before; default: 8388608 i.e. 8 MiB
+
+    The connections of the pool are opened as they are needed, so the store can be created before redis is up
+    e.g. on the import of an app. Call connect() to check that redis can be reached.
+    """
+
+    def __init__(self,
+                 url: str,
+                 pool_size: int,
+                 default_ttl: Optional[Union[timedelta, datetime, float]],
+                 timeout: Optional[int],
+                 max_lifetime: Optional[int],
+                 *,
+                 defaults: Optional[Dict[str, Any]] = None,
+                 max_connecting: Optional[int] = None,
+                 write_buffer: Optional[int] = None,
+                 write_buffer_overflow: str = "drop_oldest",
+                 circuit_breaker_threshold: Optional[int] = None,
+                 circuit_breaker_reset: Optional[int] = None,
+                 blocking_pool_size: Optional[int] = None,
+                 max_batch_bytes: Optional[int] = None) -> None: ...
+
+    async def clear(self, asynchronous: bool = False) -> None:
+        """
+        Removes all records in the redis store
+
+        :param asynchronous: whether the FLUSHALL should be done asynchronously or synchronously. default: False
+        """
+
+    async def latency(self, samples: int = 10) -> Dict[str, float]:
+        """
+        Measures the round-trip latency of PINGs to redis, sent one after the other on one connection of the pool,
+        e.g. for health dashboards. Comparing "checkout_ms" with the latencies tells a busy pool from a slow network.
+
+        :param samples: the number of PINGs to send, at least 1; default: 10
+        :return: a dictionary with the keys: "samples", the number of PINGs sent; "min_ms", "avg_ms", "p99_ms" and
+                "max_ms", the statistics of their latencies in milliseconds; and "checkout_ms", the time in
+                milliseconds the connection took to check out of the pool
+        """
+
+    async def connect(self) -> None:
+        """
+        Checks out a connection and PINGs redis on it e.g. on the startup of an app, so that the app fails to start
+        if redis cannot be reached
+
+        :raises ConnectionError: if redis cannot be reached
+        """
+
+    async def aclose(self) -> None:
+        """
+        Closes this store and all its collections, cancelling its background tasks and closing its idle connections
+        e.g. on the shutdown of an app. The connections in use are closed once they are released. Any later call
+        that needs a connection to redis raises a ConnectionError
+        """
+
+    def metrics(self) -> Dict[str, Dict[str, Dict[str, int]]]:
+        """
+        Gets the counts of the operations on each collection of this store since the collections were created,
+        e.g. to break down the read and write volume by model on a dashboard. The counts are kept in this process
+        only and cover the operations that succeeded: add_one, add_many, update_one, increment, delete_many, get_one,
+        get_many, get_all, parallel_iter, get_one_partially, get_many_partially and get_all_partially.
+
+        :return: a dictionary of the name of each collection to a dictionary of each operation called on it to a
+                dictionary with the keys: "calls", the number of calls, and "records", the number of records they read
+                or wrote e.g. {"Book": {"add_many": {"calls": 1, "records": 4}}}
+        """
+
+    def circuit_breaker(self) -> Optional[Dict[str, Any]]:
+        """
+        Describes the state of the circuit breaker of this store e.g. for health checks.
+
+        :return: a dictionary with the keys: "state", one of "closed", "open" and "half_open" i.e. a call is probing
+                redis; "consecutive_failures", the number of calls in a row that failed to get a connection;
+                "failure_threshold", the circuit_breaker_threshold of the store; and "retry_in_ms", the time in
+                milliseconds till a call is let through to probe redis, None unless it is open.
+                None if the store has no circuit breaker
+        """
+
+    async def ttl_audit(self, threshold: Optional[Union[timedelta, float]] = None,
+                        imminent: Optional[Union[timedelta, float]] = None,
+                        max_ops_per_sec: Optional[float] = None) -> Dict[str, Dict[str, int]]:
+        """
+        Counts the records of each collection by their time-to-live e.g. to catch collections whose records were
+        written without a ttl by mistake, or that are about to expire en masse. The keys are scanned in batches
+        so that redis is not blocked by large collections.
+
+        :param threshold: the ttl below which records are counted in "below_threshold"; default: 1 hour
+        :param imminent: the ttl below which records are counted in "imminent_expiry"; default: 60 seconds
+        :param max_ops_per_sec: the maximum number of records to scan per second, so that other clients
+                of a busy redis are not slowed down; default: None i.e. as fast as possible
+        :return: a dictionary of the name of each collection to a dictionary with the keys: "records", the number
+                of records; "no_ttl", the number that never expire; "below_threshold" and "imminent_expiry", the
+                numbers whose ttl is below the threshold and the imminent expiry respectively
+        """
+
+    async def apply_retention(self, max_ops_per_sec: Optional[float] = None) -> Dict[str, int]:
+        """
+        Applies the retention policies of all collections, removing records that are older than their policy allows
+        or capping their time-to-live to the retention period if the collection has no retention_field.
+        The keys are scanned in batches so that redis is not blocked by large collections.
+
+        :param max_ops_per_sec: the maximum number of records to scan per second, so that other clients
+                of a busy redis are not slowed down; default: None i.e. as fast as possible
+        :return: the number of records affected in each collection that has a retention policy
+        """
+
+    async def run_retention(self, interval: Union[timedelta, float],
+                            max_ops_per_sec: Optional[float] = None) -> None:
+        """
+        Applies the retention policies of all collections every `interval` until cancelled.
+        It is meant to be run as a background task e.g. `task = asyncio.ensure_future(store.run_retention(3600))`
+        and stopped with `task.cancel()`
+
+        :param interval: the time (a timedelta or number of seconds) to wait between successive runs
+        :param max_ops_per_sec: the maximum number of records to scan in each run per second, so that other clients
+                of a busy redis are not slowed down; default: None i.e. as fast as possible
+        """
+
+    def spawn_background(self, task_name: str, coroutine_factory: Callable[[], Awaitable[Any]],
+                         interval: Union[timedelta, float]) -> None:
+        """
+        Runs a periodic job in the background of this store e.g.
+        `store.spawn_background("retention", store.apply_retention, 3600)`. The awaitable returned by
+        `coroutine_factory` is awaited, then the job waits for `interval` before calling the factory again,
+        until the job is cancelled with cancel_background() or one of the awaitables raises an error.
+        It should be called while the event loop is running.
+
+        :param task_name: the name of the job, which should not be that of another job that is still running
+        :param coroutine_factory: the function called with no arguments to get the awaitable of each run
+        :param interval: the time (a timedelta or number of seconds) to wait between successive runs
+        """
+
+    def background_tasks(self) -> Dict[str, Dict[str, Any]]:
+        """
+        Gets the status of the background jobs of this store
+
+        :return: a dictionary of the name of each job to a dictionary with the keys: "running", whether it is still
+                running; "runs", the number of runs that have completed; and "error", the message of the error
+                that stopped it, or None
+        """
+
+    def cancel_background(self, task_name: str) -> bool:
+        """
+        Cancels the background job of the given name. A run that is in progress is left to complete.
+
+        :param task_name: the name of the job
+        :return: True if there was a job of that name, else False
+        """
+
+    def schedule_backup(self, interval: Union[timedelta, float, str], target: str, keep: int = 7,
+                        chunk_size: int = 1000) -> None:
+        """
+        Backs up all the collections created so far every `interval`, as `Store.schedule_backup()` does
+        e.g. `store.schedule_backup("@daily", "s3://bucket/orredis")`. The backups are made in a background job
+        of this store named "backup:<target>", listed by background_tasks(), until the schedule is cancelled
+        with cancel_backup_schedule() or the store is closed with aclose().
+
+        :param interval: the time (a timedelta or number of seconds) between backups, or a cron-like shorthand:
+                "@hourly", "@daily", "@weekly" or "@every <n><s|m|h|d>" e.g. "@every 15m"
+        :param target: the local directory or the url e.g. "s3://bucket/path" under which to make the backups;
+                urls are opened as in `Collection.backup()`
+        :param keep: the number of latest backups to keep, at least 1; default: 7
+        :param chunk_size: the number of records in each chunk of the exports; default: 1000
+        :raises ValueError: if the interval is invalid or a backup into the target is already scheduled
+        """
+
+    def backup_schedules(self) -> Dict[str, Dict[str, Any]]:
+        """
+        Gets the status of the scheduled backups of this store
+
+        :return: a dictionary of the target of each schedule to a dictionary with the keys as in
+                `Store.backup_schedules()`
+        """
+
+    def cancel_backup_schedule(self, target: str) -> bool:
+        """
+        Cancels the scheduled backup into the given target. A backup that is in progress is left to complete.
+
+        :param target: the target given to schedule_backup()
+        :return: True if there was a backup scheduled into that target, else False
+        """
+
+    def add_mirror(self, url: str) -> None:
+        """
+        Adds another redis instance to which every write (insert, update, increment and delete) made through this
+        store is also applied. The writes are queued and applied in the background, in order, retrying a few times
+        with a growing delay if the mirror is unreachable. This is best-effort: writes that still fail, or that are
+        made while the queue of the mirror is full, are dropped. It should be called before any writes are made.
+
+        :param url: the redis url of the mirror e.g. redis://replica:6379/0, which may have an IPv6 host or
+                    a "redis+srv" scheme as the url of the store can
+        """
+
+    def pending_writes(self) -> int:
+        """
+        Gets the number of writes made while redis was unreachable that are still in the write buffer, waiting to be
+        applied e.g. for monitoring. It is always 0 if the store has no write buffer.
+        """
+
+    def take_failed_writes(self) -> List[str]:
+        """
+        Takes the errors of the buffered writes that failed when they were applied once redis was reachable again
+        e.g. because they exceeded a quota or redis was out of memory. Such writes are dropped rather than retried,
+        since they would fail again, or be applied twice if redis applied them before failing e.g. on a timeout.
+        The errors are returned oldest first and removed from the store, which keeps at most as many as the
+        capacity of its write buffer. It is always empty if the store has no write buffer.
+        """
+
+    async def set_quota(self,
+                        namespace: str,
+                        max_records: Optional[int] = None,
+                        max_bytes: Optional[int] = None) -> None:
+        """
+        Sets the limits of the quota of the given namespace, shared by all collections created with that namespace.
+        Inserts and updates that would take the usage beyond a limit raise a QuotaExceededError. The limits are saved
+        in redis so that they apply to every store with collections in the namespace.
+
+        :param namespace: the namespace whose quota is set
+        :param max_records: the maximum number of records in the collections of the namespace;
+                    default: None i.e. no limit
+        :param max_bytes: the maximum total length in bytes of the field names and values of the records in the
+                    collections of the namespace; default: None i.e. no limit
+        """
+
+    async def get_quota(self, namespace: str) -> Dict[str, Optional[int]]:
+        """
+        Gets the usage of the quota of the given namespace, with its limits
+
+        :param namespace: the namespace whose quota is got
+        :return: a dictionary with the keys: "records", the number of records in the collections of the namespace;
+                "bytes", the total length in bytes of their field names and values; and "max_records" and
+                "max_bytes", the limits of the quota, which are None if not set
+        """
+
+    async def verify_against(self, other: "AsyncStore", collection: Type[Model], sample: float = 0.01) -> Dict[str, Any]:
+        """
+        Compares a random sample of the records of the given collection in this store with those in the other store,
+        field by field, to report the drift between them e.g. to confirm a mirror is in sync before switching to it.
+        Records are sampled from both stores so that records missing from either are detected.
+        The other store does not need to have the collection created on it.
+
+        :param other: the store to compare this store with
+        :param collection: the Model whose collection is to be compared
+        :param sample: the fraction of the records in each store to compare, greater than 0 and at most 1;
+                    default: 0.01 i.e. about 1%
+        :return: a dictionary with the keys: "sampled", the number of records compared; "missing", the ids of the
+                records only found in this store; "extra", the ids of the records only found in the other store;
+                and "mismatched", a dictionary mapping the id of each record that differs to a dictionary of
+                field to (value in this store, value in the other store)
+        """
+
+    def describe(self) -> Dict[str, Any]:
+        """
+        Describes the collections, views and links of this store e.g. to render admin UIs or to validate
+        the store against external contracts. It reads nothing from redis.
+
+        :return: a dictionary with the keys: "collections", a dictionary of the name of each collection to its
+                description i.e. a dictionary with the keys: "primary_key_field"; "fields", a dictionary of
+                the fields to their types; "counter_fields", "indexed_fields", "indexed_list_fields",
+                "compound_indexes", "range_indexes" and "unique_fields"; "relationships", a list of dictionaries
+                with the keys: "field", "kind" i.e. "nested", "reference" or "foreign_key", and "collection", the
+                name of the related collection or None for foreign keys; "version" and "namespace", which are None
+                if not set; "views", a dictionary of the name of each view to a dictionary with the keys: "source",
+                "projection" and "filter"; and "links", a dictionary of the name of each link to a dictionary with
+                the keys: "left" and "right", the names of the collections it links
+        """
+
+    async def browse(
+        self,
+        collection: Type[Model],
+        cursor: Optional[str] = None,
+        limit: int = 20,
+        fields: Optional[List[str]] = None,
+    ) -> Dict[str, Any]:
+        """
+        Gets a page of the records of the given collection as dictionaries of only the given fields, e.g. those
+        the user of an admin panel is allowed to see, with the total number of records and the cursor of the next
+        page, all in one call. At most `limit` records are read whatever the size of the collection, while the
+        total is counted from the keys of the collection without reading the records.
+        Records are in the order in which SCAN finds them, which is stable as long as the collection is not changed.
+
+        :param collection: the Model whose records are browsed
+        :param cursor: the next_cursor returned with the previous page; default: None i.e. the first page
+        :param limit: the maximum number of records in the page, greater than 0; default: 20
+        :param fields: the fields of the records to return, nested fields being given as dotted paths
+                    e.g. "author.name"; default: None i.e. all fields
+        :return: a dictionary with the keys: "records", the list of dictionaries of the fields of the records;
+                "total", the number of records in the collection; and "next_cursor", the cursor to pass to get
+                the next page, which is None if this is the last page
+        :raises KeyError: if the collection has not been created or any of the fields is not a field of the model
+        :raises ValueError: if the cursor was not returned by browse() or the limit is 0
+        """
+
+    async def plan_migration(self, model: Type[Model]) -> Dict[str, Any]:
+        """
+        Compares the schema of the collection of the given model with the snapshot of it last saved in redis
+        by save_schema_snapshot(), without changing any records, to plan the migration of the records saved
+        in the older schema e.g. as a dry-run before deploying a changed model.
+
+        :param model: the Model whose collection is to be compared
+        :return: a dictionary with the keys: "collection", the name of the collection; "has_snapshot", whether a
+                snapshot was saved, all fields being reported as added if not; "added" and "removed", dictionaries
+                of the fields added to or removed from the schema to their types; and "type_changed", a dictionary
+                of the fields whose types changed to (saved type, current type)
+        """
+
+    async def save_schema_snapshot(self, model: Type[Model]) -> None:
+        """
+        Saves the snapshot of the current schema of the collection of the given model in redis, replacing the one
+        that plan_migration() compares with. Call it once the records have been migrated to the current schema.
+
+        :param model: the Model whose collection's schema is to be saved
+        """
+
+    def create_collection(self,
+                          model: Type[Model],
+                          primary_key_field: str,
+                          *,
+                          id_generator: Optional[str] = None,
+                          snowflake_worker_id: Optional[int] = None,
+                          retention: Optional[Union[timedelta, float]] = None,
+                          retention_field: Optional[str] = None,
+                          bloom_filter: Optional[bool] = None,
+                          track_changes: Optional[bool] = None,
+                          coercions: Optional[Dict[str, List[str]]] = None,
+                          version: Optional[int] = None,
+                          upgrades: Optional[Dict[int, Callable[[Dict[str, str]], Dict[str, Any]]]] = None,
+                          persist_upgrades: bool = False,
+                          register_nested_models: Optional[bool] = None,
+                          nested_depth: Optional[int] = None,
+                          hot_key_sampling: Optional[float] = None,
+                          namespace: Optional[str] = None,
+                          foreign_keys: Optional[List[str]] = None,
+                          indexed_fields: Optional[List[str]] = None,
+                          unique_fields: Optional[List[str]] = None,
+                          compound_indexes: Optional[List[Tuple[str, ...]]] = None,
+                          enforce_constraints: Optional[bool] = None,
+                          index_normalizers: Optional[Dict[str, str]] = None,
+                          case_insensitive_keys: bool = False,
+                          track_insertion_order: bool = False,
+                          indexed_list_fields: Optional[List[str]] = None) -> None:
+        """
+        Creates a new Collection within the store for the given model supplied
+
+        Options that are not given, or are None, fall back to the defaults of the store.
+
+        :param model: the Model schema to be used for this collection. String type hints e.g. in modules using
+                    `from __future__ import annotations`, are resolved against the modules of the model and the models
+                    nested in it, and the models of the collections already created in this store
+        :param primary_key_field: the field that contains the unique primary key for each model instance e.g.
+                                a book's primary key might be its ISBN
+        :param id_generator: the algorithm used to generate the primary key of records added without one
+                            i.e. "uuid4", "ulid", "snowflake" or "ksuid". All but "uuid4" produce ids that are
+                            lexicographically sortable by creation time; default: None i.e. the primary key is required
+        :param snowflake_worker_id: the worker id, from 0 to 1023, in the ids generated by the "snowflake" id_generator.
+                            Processes generating ids for the same collection should have different worker ids, else
+                            they may generate the same id in the same millisecond; default: None i.e. one hashed
+                            from the host name and the process id, which processes rarely, but may, share
+        :param retention: the maximum age (a timedelta or number of seconds) of records in this collection.
+                        If no retention_field is given, the age is counted from the last write i.e. the ttl of
+                        records is capped to this period; default: None i.e. records are kept indefinitely
+        :param retention_field: the date, datetime, int or float (unix timestamp) field that holds the time
+                        each record was created. Records whose field is older than the retention period are removed
+                        when `apply_retention()` is called; default: None
+        :param bloom_filter: whether to keep a bloom filter of the primary keys of this collection so that lookups of
+                        nonexistent ids in `get_one()` and `exists()` do not hit the records. It requires the RedisBloom
+                        module and is ignored if it is not loaded. All writers of this collection should enable it;
+                        default: False
+        :param track_changes: whether to record every insert, update and delete done through this collection, with
+                        the fields of the record before and after the change, in a redis stream that can be read with
+                        `get_changes()` or tailed with `orredis.cdc.ChangeConsumer`. Only the latest 100,000 or so
+                        changes are kept; default: False
+        :param coercions: the rules, per field, for reading values saved in formats other than the ones orredis writes
+                        e.g. by legacy systems, so that such data can be read without first rewriting it. The rules are
+                        "empty_to_none" (an empty string is None), "float_to_int" (a whole float e.g. "3.0" in an int
+                        field), "loose_bool" ("1"/"0", "yes"/"no", "on"/"off" or any casing of "true"/"false" in a bool
+                        field) and "timestamp_to_datetime" (unix timestamps in seconds in date or datetime fields).
+                        The first rule that matches a value is applied e.g. {"age": ["empty_to_none", "float_to_int"]};
+                        default: None i.e. values are read as they are written by orredis
+        :param version: the format version of the records of this collection. It is saved in each record written by
+                        `add_one()` or `add_many()`, and records saved without one are at version 0. Records of older
+                        versions are upgraded with `upgrades` when they are read; default: None i.e. no versioning
+        :param upgrades: a dictionary mapping each older version to the function that upgrades a record from it to the
+                        next version. Each function receives the non-nested fields of the record as saved in redis i.e.
+                        as strings, and returns the fields in the next version's format, as strings or python values.
+                        Versions without an upgrade function are skipped. Nested records are not upgraded when read
+                        through their parent; default: None
+        :param persist_upgrades: whether records upgraded on read are saved back to redis in the current version so that
+                        they are not upgraded again. A record is not saved if it was changed in the meantime;
+                        default: False
+        :param register_nested_models: whether to create the collections of the models nested in this model that have
+                        none yet, instead of raising a KeyError. The primary key field of each is got from its schema:
+                        the "primary_key_field" key of its `schema_extra`, or the field declared with
+                        `Field(..., primary_key=True)`. Models that refer to each other e.g. a Team whose captain
+                        is a Player of that Team, are registered this way; default: False
+        :param nested_depth: the number of levels of nested records read with each record. Models may nest themselves
+                        e.g. an Employee whose manager is an Employee, directly or through other models. Nested records
+                        deeper than this are read as None so such fields should be Optional; default: 3
+        :param hot_key_sampling: the fraction, greater than 0 and at most 1, of the reads and updates by id done
+                        through this collection in this process that are counted to find its most accessed ids with
+                        `hot_keys()`. The counts halve every minute so that they reflect recent accesses;
+                        default: None i.e. accesses are not counted
+        :param namespace: the namespace e.g. the team owning this collection, whose quota set by `set_quota()` limits
+                        the records inserted through it, including its nested records. Its usage is updated on every
+                        insert, update and delete through the collection, but not when records expire or are removed
+                        by retention; default: None i.e. the collection has no quota
+        :param foreign_keys: the non-nested fields that hold the primary keys of records of other collections e.g.
+                        the "user_id" of an Order. An index of each is updated on every write so that `children_of()`
+                        gets the records that refer to a given record without scanning this collection. Records
+                        saved before the field was a foreign key are not indexed until they are saved again;
+                        default: None
+        :param indexed_fields: other non-nested fields whose records are looked up by their exact values e.g.
+                        the "email" of a User. An index of each is updated on every write so that `find_by()` gets
+                        the records with a given value without scanning this collection. Records saved before
+                        the field was indexed are not indexed until they are saved again; default: None
+        :param unique_fields: the non-nested fields whose values no two records can share e.g. the "email" of a User.
+                        Inserts and updates reserve the values atomically and raise a `UniqueViolationError` if
+                        another record has any of them. They are never buffered by the write buffer of the store, as
+                        the values would not be checked till later. Records saved before the field was unique are not
+                        checked against until they are saved again; default: None
+        :param compound_indexes: the groups of non-nested fields whose records are looked up by their exact values
+                        together e.g. ("country", "city"). An index of each is updated on every write so that
+                        `find_by_index()` gets the records with given values without scanning this collection.
+                        Records saved before the index was added are not indexed until they are saved again;
+                        default: None
+        :param enforce_constraints: whether to check the non-nested fields of constrained types e.g. `conint(ge=0)` or
+                        `constr(max_length=10)` against their minimum, maximum, length and pattern on every insert and
+                        update, raising a ValueError for the values that break them. This catches invalid values that
+                        bypass the validation of the model e.g. in `update_one()`; default: False
+        :param index_normalizers: the normalizers of the values of indexed, compound-indexed and unique fields, by
+                        field, applied before the values are indexed or looked up e.g. {"email": "strip,lower"} so
+                        that "Email@X.com " and "email@x.com" are the same unique value. Each is "lower", "strip",
+                        a comma-separated list of them applied in order, or the body of a lua function of `value`
+                        returning the normalized value e.g. "return (string.gsub(value, '%.', ''))". The records keep
+                        their values as they were given; default: None i.e. values are indexed as they are
+        :param case_insensitive_keys: whether the primary keys are case-insensitive i.e. lowercased in the keys
+                        of the records in redis on both writes and reads, so that `get_one("Alice")` and
+                        `get_one("alice")` get the same record. The records keep their primary keys as they were
+                        given; default: False
+        :param track_insertion_order: whether the ids of the records are kept in a sorted set by the time they were
+                        first inserted, so that the latest and oldest records are got with `get_latest()` and
+                        `get_oldest()`; default: False
+        :param indexed_list_fields: the list fields of non-nested items whose records are looked up by any of their
+                        elements e.g. the "tags" of a Post. An index of each element is updated on every write,
+                        moving the record from the indexes of the elements it no longer has to those it now has, so
+                        that `find(tags__contains="rust")` gets the records holding an element without scanning this
+                        collection. Records saved before the field was indexed are not indexed until they are saved
+                        again; default: None
+        """
+
+    def get_collection(self, model: Type[Model]) -> AsyncCollection:
+        """
+        Retrieves a handle on the collection for a given model to manipulate the data within or
+        query it
+
+        :param model: the Model schema whose collection is to be retrieved
+        :return: the collection instance to be used to manipulate data or query it using collection.add_one() etc.
+        """
+
+    def create_view(self,
+                    name: str,
+                    source_collection: Type[Model],
+                    projection: List[str],
+                    filter: Optional[Dict[str, Any]] = None) -> None:
+        """
+        Creates a materialized view of the collection of the given model i.e. the records that match the filter,
+        with only the fields in the projection. The view is kept up to date on every write to the collection
+        so that read-heavy code can get pre-filtered, pre-projected dictionaries. It must be called after the
+        collection is created and before any call to `get_collection()` or `get_view()`
+
+        :param name: the unique name of the view
+        :param source_collection: the Model of the collection the view is got from
+        :param projection: the non-nested fields of the records to keep in the view
+        :param filter: the values that the non-nested fields of a record should be equal to for it to be in the view;
+                    default: None i.e. all records are in the view
+        """
+
+    def get_view(self, name: str) -> AsyncView:
+        """
+        Retrieves a read-only handle on the view of the given name
+
+        :param name: the name of the view
+        :return: the view instance to be used to query it using view.get_all() etc.
+        """
+
+    def create_link(self, name: str, left_collection: Type[Model], right_collection: Type[Model]) -> None:
+        """
+        Creates a many-to-many link between the records of the collections of the given models e.g. students
+        and courses, each side being found from the other without scanning either collection.
+        Unlike views, links can be created at any time after the collections are created
+
+        :param name: the unique name of the link
+        :param left_collection: the Model of the collection on the left side of the link
+        :param right_collection: the Model of the collection on the right side of the link
+        """
+
+    def get_link(self, name: str) -> AsyncLink:
+        """
+        Retrieves a handle on the link of the given name
+
+        :param name: the name of the link
+        :return: the link instance to be used to link records using link.link() or query it using link.left_of() etc.
         """
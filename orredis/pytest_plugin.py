@@ -0,0 +1,182 @@
+"""
+Module with the pytest plugin of orredis, with fixtures that give each test a store on an ephemeral redis whose
+data is removed after the test. The plugin is not loaded on its own: load it with `-p orredis.pytest_plugin`
+on the command line or in the `addopts` ini option, or with `pytest_plugins = ["orredis.pytest_plugin"]`
+in the conftest.py at the root of the tests.
+
+By default, a redislite server is started for the session, so the tests need no redis of their own. It is
+installed with `pip install orredis[pytest]`. Each test gets a database of its own on that server, which is flushed
+after the test. Give `--orredis-url` (or the `orredis_url` ini option) to use another redis instead. Its data is
+left as it is, except for the records of the collections of the fixtures, which are removed with
+`Collection.clear()` before and after each test.
+
+The collections of the stores are created from the models given to the `orredis_models` marker, on the test,
+its class or its module. Each model is given as is, if its primary key field is marked with `primary_key=True`
+or is the "primary_key_field" of its schema extra, or else as a (model, primary_key_field) tuple.
+
+Example:
+    @pytest.mark.orredis_models(Book, (Author, "name"))
+    def test_add_book(orredis_store):
+        books = orredis_store.get_collection(Book)
+        ...
+"""
+import itertools
+import socket
+from typing import Iterator, List, Optional, Tuple, Type, Union
+
+import pytest
+
+from orredis.orredis import AsyncStore, Store
+from .abstract import Model
+
+ModelSpec = Union[Type[Model], Tuple[Type[Model], str]]
+
+# the number of databases of the redislite server, each test getting the next one in turn
+_DATABASES = 16
+_databases = itertools.cycle(range(_DATABASES))
+
+
+def pytest_addoption(parser: pytest.Parser):
+    group = parser.getgroup("orredis")
+    group.addoption("--orredis-url", dest="orredis_url", default=None,
+                    help="the url of the redis used by the orredis fixtures, from which only the records of the "
+                         "collections of the fixtures are removed; default: that of a redislite server started "
+                         "for the session")
+    parser.addini("orredis_url", "the url of the redis used by the orredis fixtures", default=None)
+
+
+def pytest_configure(config: pytest.Config):
+    config.addinivalue_line(
+        "markers",
+        "orredis_models(*models): the models whose collections are created in the orredis_store "
+        "and orredis_async_store fixtures, each a model or a (model, primary_key_field) tuple")
+
+
+@pytest.fixture(scope="session")
+def orredis_server(pytestconfig: pytest.Config) -> Iterator[Optional[int]]:
+    """
+    The port of the redislite server started for the session, or None if `--orredis-url` or the `orredis_url`
+    ini option is given
+    """
+    if _get_given_url(pytestconfig):
+        yield None
+        return
+
+    try:
+        import redislite
+    except ImportError as exc:
+        raise pytest.UsageError("the orredis fixtures need redislite, installed with `pip install orredis[pytest]`, "
+                                "or the url of a redis given with --orredis-url") from exc
+
+    port = _get_unused_tcp_port()
+    server = redislite.Redis(serverconfig={"port": str(port), "databases": str(_DATABASES)})
+    yield port
+    server.shutdown()
+
+
+@pytest.fixture()
+def orredis_url(pytestconfig: pytest.Config, orredis_server: Optional[int]) -> Iterator[str]:
+    """
+    The url of the redis used by the orredis fixtures in the test: that of a database of its own on the redislite
+    server, flushed after the test, unless `--orredis-url` or the `orredis_url` ini option is given
+    """
+    if orredis_server is None:
+        yield _get_given_url(pytestconfig)
+        return
+
+    import redis
+
+    db = next(_databases)
+    client = redis.Redis(port=orredis_server, db=db)
+    client.flushdb()
+    yield f"redis://localhost:{orredis_server}/{db}"
+    client.flushdb()
+    client.close()
+
+
+@pytest.fixture()
+def orredis_store(request: pytest.FixtureRequest, orredis_server: Optional[int],
+                  orredis_url: str) -> Iterator[Store]:
+    """A store with the collections of the models in the orredis_models marker, whose data is removed after the test"""
+    store = Store(url=orredis_url)
+    models = _create_collections(store, request)
+    is_shared = orredis_server is None
+    if is_shared:
+        _clear_collections(store, models)
+    yield store
+    if is_shared:
+        _clear_collections(store, models)
+    store.close()
+
+
+@pytest.fixture()
+def orredis_async_store(request: pytest.FixtureRequest, orredis_server: Optional[int],
+                        orredis_url: str) -> Iterator[AsyncStore]:
+    """
+    An async store with the collections of the models in the orredis_models marker, whose data is removed
+    after the test
+    """
+    store = AsyncStore(url=orredis_url)
+    _create_collections(store, request)
+    # the collections are cleared through a sync store so that the fixture needs no event loop
+    sync_store = Store(url=orredis_url)
+    models = _create_collections(sync_store, request)
+    is_shared = orredis_server is None
+    if is_shared:
+        _clear_collections(sync_store, models)
+    yield store
+    if is_shared:
+        _clear_collections(sync_store, models)
+    sync_store.close()
+
+
+def _get_given_url(config: pytest.Config) -> Optional[str]:
+    """Gets the url of the redis given with `--orredis-url` or the `orredis_url` ini option, if any"""
+    return config.getoption("orredis_url") or config.getini("orredis_url") or None
+
+
+def _clear_collections(store: Store, models: List[Type[Model]]):
+    """Removes the records of the collections of the given models, leaving the other keys in redis as they are"""
+    for model in models:
+        store.get_collection(model).clear()
+
+
+def _create_collections(store: Union[Store, AsyncStore], request: pytest.FixtureRequest) -> List[Type[Model]]:
+    """
+    Creates the collections of the models in the orredis_models markers of the test, its class and its module,
+    returning the models
+    """
+    models = []
+    for marker in reversed(list(request.node.iter_markers("orredis_models"))):
+        for spec in marker.args:
+            model, primary_key_field = _parse_model_spec(spec)
+            if model not in models:
+                models.append(model)
+                store.create_collection(model, primary_key_field=primary_key_field)
+    return models
+
+
+def _parse_model_spec(spec: ModelSpec) -> Tuple[Type[Model], str]:
+    """
+    Gets the model and its primary key field from a model, or a (model, primary_key_field) tuple,
+    given to the orredis_models marker
+    """
+    if isinstance(spec, tuple):
+        return spec
+
+    schema = spec.schema()
+    primary_key_field = schema.get("primary_key_field")
+    if primary_key_field is None:
+        properties = schema.get("properties", {})
+        primary_key_field = next((k for k, v in properties.items() if v.get("primary_key")), None)
+    if primary_key_field is None:
+        raise ValueError(f"no primary key found for {spec.__name__}. Mark one of its fields with `primary_key=True` "
+                         f"or pass ({spec.__name__}, primary_key_field) to orredis_models")
+    return spec, primary_key_field
+
+
+def _get_unused_tcp_port() -> int:
+    """Gets a TCP port that is not in use"""
+    with socket.socket(socket.AF_INET, socket.SOCK_STREAM) as sock:
+        sock.bind(("", 0))
+        return sock.getsockname()[1]
@@ -0,0 +1,162 @@
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+
+use crate::field_types::FieldType;
+use crate::store::CollectionMeta;
+use crate::utils;
+
+const AGGREGATE_FIELD_SCRIPT: &str = r"local op = ARGV[3] local cursor = '0' local count = 0 local result = nil repeat local scan = redis.call('SCAN', cursor, 'MATCH', ARGV[1]) for _, key in ipairs(scan[2]) do if redis.call('TYPE', key).ok == 'hash' then local value = tonumber(redis.call('HGET', key, ARGV[2])) if value then count = count + 1 if op == 'sum' or op == 'avg' then result = (result or 0) + value elseif op == 'min' and (result == nil or value < result) then result = value elseif op == 'max' and (result == nil or value > result) then result = value end end end end cursor = scan[1] until (cursor == '0') if result == nil then return {count, ''} end if op == 'avg' then result = result / count end return {count, string.format('%.17g', result)}";
+const COUNT_BY_FIELD_SCRIPT: &str = r"local counts = {} local values = {} local cursor = '0' repeat local result = redis.call('SCAN', cursor, 'MATCH', ARGV[1]) for _, key in ipairs(result[2]) do if redis.call('TYPE', key).ok == 'hash' then local value = redis.call('HGET', key, ARGV[2]) if value then if not counts[value] then counts[value] = 0 table.insert(values, value) end counts[value] = counts[value] + 1 end end end cursor = result[1] until (cursor == '0') local grouped = {} for _, value in ipairs(values) do table.insert(grouped, value) table.insert(grouped, counts[value]) end return grouped";
+const COUNT_MATCHING_RECORDS_SCRIPT: &str = r"local table_unpack = table.unpack or unpack local count = 0 local seen = {} local cursor = '0' local filter_fields = {} local filter_values = {} for i = 2, #ARGV, 2 do table.insert(filter_fields, ARGV[i]) table.insert(filter_values, ARGV[i + 1]) end repeat local result = redis.call('SCAN', cursor, 'MATCH', ARGV[1]) for _, key in ipairs(result[2]) do if not seen[key] and redis.call('TYPE', key).ok == 'hash' then seen[key] = true local matches = true if #filter_fields > 0 then local values = redis.call('HMGET', key, table_unpack(filter_fields)) for i, v in ipairs(filter_values) do if values[i] ~= v then matches = false break end end end if matches then count = count + 1 end end end cursor = result[1] until (cursor == '0') return count";
+
+/// The operation with which the values of a numeric field of a collection are aggregated into one value
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum AggregateOp {
+    Sum,
+    Avg,
+    Min,
+    Max,
+}
+
+impl AggregateOp {
+    /// Gets the operation of the given name
+    pub(crate) fn from_name(name: &str) -> PyResult<Self> {
+        match name {
+            "sum" => Ok(Self::Sum),
+            "avg" => Ok(Self::Avg),
+            "min" => Ok(Self::Min),
+            "max" => Ok(Self::Max),
+            _ => Err(PyValueError::new_err(format!(
+                "unknown op {:?}. Expected one of \"sum\", \"avg\", \"min\" or \"max\"",
+                name
+            ))),
+        }
+    }
+
+    /// Gets the name of the operation as the aggregation script expects it
+    fn name(self) -> &'static str {
+        match self {
+            Self::Sum => "sum",
+            Self::Avg => "avg",
+            Self::Min => "min",
+            Self::Max => "max",
+        }
+    }
+}
+
+/// Ensures the values of the given field can be aggregated i.e. it is an int or float field
+pub(crate) fn ensure_numeric_field(meta: &CollectionMeta, field: &str) -> PyResult<()> {
+    meta.ensure_scalar_field(field)?;
+    match meta.schema.get_type(field) {
+        Some(FieldType::Int | FieldType::Float) => Ok(()),
+        _ => Err(PyValueError::new_err(format!(
+            "{:?} is not an int or float field yet only such fields can be aggregated",
+            field
+        ))),
+    }
+}
+
+/// Generates the command that aggregates the values of the given field of the records in the given collection
+/// with the given operation, returning the number of values aggregated and the result
+pub(crate) fn generate_aggregate_cmd(
+    collection_name: &str,
+    field: &str,
+    op: AggregateOp,
+) -> redis::Cmd {
+    let mut cmd = redis::cmd("EVAL");
+    cmd.arg(AGGREGATE_FIELD_SCRIPT)
+        .arg(0)
+        .arg(utils::generate_collection_key_pattern(collection_name))
+        .arg(field)
+        .arg(op.name());
+    cmd
+}
+
+/// Converts the result of the aggregation of the values of a field of the given type into python.
+/// The sum, minimum and maximum of an int field are ints while averages are floats. The sum of no values is 0
+/// and their average, minimum and maximum are None
+pub(crate) fn to_py(type_: &FieldType, op: AggregateOp, result: &str) -> PyResult<Py<PyAny>> {
+    let is_int = matches!(type_, FieldType::Int) && op != AggregateOp::Avg;
+    let value = match result {
+        "" if op == AggregateOp::Sum => 0.0,
+        "" => return Ok(Python::with_gil(|py| py.None())),
+        result => result.parse::<f64>().map_err(|_| {
+            PyValueError::new_err(format!(
+                "{:?} is not a valid result of an aggregation",
+                result
+            ))
+        })?,
+    };
+
+    Python::with_gil(|py| {
+        if is_int {
+            Ok((value as i64).into_py(py))
+        } else {
+            Ok(value.into_py(py))
+        }
+    })
+}
+
+/// Ensures the records of a collection can be grouped by the values of the given field i.e. its values are
+/// hashable in python, unlike those of list, vector and dict fields
+pub(crate) fn ensure_groupable_field(meta: &CollectionMeta, field: &str) -> PyResult<()> {
+    meta.ensure_scalar_field(field)?;
+    match meta.schema.get_type(field) {
+        Some(FieldType::List { .. } | FieldType::Vector { .. } | FieldType::Dict { .. }) => {
+            Err(PyValueError::new_err(format!(
+            "{:?} is a list, vector or dict field yet only fields whose values are hashable can be grouped",
+            field
+        )))
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Generates the command that counts the records in the given collection by the values of the given field,
+/// returning each value as saved in redis followed by its count
+pub(crate) fn generate_count_by_cmd(collection_name: &str, field: &str) -> redis::Cmd {
+    let mut cmd = redis::cmd("EVAL");
+    cmd.arg(COUNT_BY_FIELD_SCRIPT)
+        .arg(0)
+        .arg(utils::generate_collection_key_pattern(collection_name))
+        .arg(field);
+    cmd
+}
+
+/// Generates the command that counts the records in the given collection whose fields are equal to the values
+/// in the given filter, which are compared as saved in redis. All records are counted if the filter is empty
+pub(crate) fn generate_count_where_cmd(
+    collection_name: &str,
+    filter: &[(String, String)],
+) -> redis::Cmd {
+    let mut cmd = redis::cmd("EVAL");
+    cmd.arg(COUNT_MATCHING_RECORDS_SCRIPT)
+        .arg(0)
+        .arg(utils::generate_collection_key_pattern(collection_name));
+    for (field, value) in filter {
+        cmd.arg(field).arg(value);
+    }
+    cmd
+}
+
+/// Converts the counts of the records of the given collection by the values of a field of the given type
+/// into a python dictionary of each value to its count
+pub(crate) fn counts_to_py(
+    collection_name: &str,
+    type_: &FieldType,
+    counts: &[(redis::Value, usize)],
+) -> PyResult<Py<PyAny>> {
+    let values = counts
+        .iter()
+        .map(|(value, _)| type_.redis_to_py(collection_name, value))
+        .collect::<PyResult<Vec<Py<PyAny>>>>()?;
+
+    Python::with_gil(|py| {
+        let grouped = PyDict::new(py);
+        for (value, (_, count)) in values.into_iter().zip(counts) {
+            grouped.set_item(value, count)?;
+        }
+        Ok(grouped.into_py(py))
+    })
+}
@@ -1,65 +1,158 @@
 use std::collections::HashMap;
+use std::sync::atomic;
+use std::sync::{Arc, RwLock, RwLockReadGuard, RwLockWriteGuard};
 use std::time::Duration;
 
+use async_std::task;
 use mobc;
-use pyo3::exceptions::{PyConnectionError, PyKeyError};
+use pyo3::exceptions::{PyConnectionError, PyKeyError, PyValueError};
 use pyo3::prelude::*;
-use pyo3::types::PyType;
+use pyo3::types::{PyDict, PyType};
 use redis::aio::Connection;
 
-use crate::schema::Schema;
-use crate::{async_utils, asyncio, mobc_redis, store, utils};
+use crate::aggregations::{self, AggregateOp};
+use crate::background::BackgroundTasks;
+use crate::backup_schedules::{self, BackupJob, BackupPool, BackupSchedules};
+use crate::batching::{BatchSizer, InsertRecords};
+use crate::bloom_filters;
+use crate::circuit_breakers::CircuitBreaker;
+use crate::compaction;
+use crate::compression;
+use crate::conditional_writes;
+use crate::constraints;
+use crate::defaults::CollectionDefaults;
+use crate::descriptions;
+use crate::expiry::Expiry;
+use crate::fake_data::{FakeRecords, FAKE_BATCH_SIZE};
+use crate::field_indexes;
+use crate::field_types::NestedModel;
+use crate::links::LinkDefinition;
+use crate::lookups::Lookups;
+use crate::migrations::MigrationPlan;
+use crate::mirrors::Mirrors;
+use crate::null_indexes;
+use crate::options::{CollectionOptions, StoreOptions};
+use crate::pagination::{self, BrowseCursor, KeysetCursor, Ordering, Page};
+use crate::profiling::{self, Phase, Profile};
+use crate::range_indexes;
+use crate::read_errors::OnError;
+use crate::repairs;
+use crate::retention::RetentionPolicy;
+use crate::schema::{self, Schema, SchemaContext, SchemaRegistry};
+use crate::snapshots;
+use crate::throttle::Throttle;
+use crate::ttl_audit::TtlThresholds;
+use crate::versioning;
+use crate::views::ViewDefinition;
+use crate::write_buffer::WriteBuffer;
+use crate::{
+    async_utils, asyncio, endpoints, mobc_redis, nested_updates, parsers, pools, store, utils,
+    vectors, verification,
+};
 
-#[pyclass(subclass)]
-pub(crate) struct AsyncStore {
+/// The collections, views and links of an async store. Async tasks may still be running when collections
+/// are created so they read snapshots of it, cloned from behind a lock, instead of borrowing it
+#[derive(Default)]
+struct Registry {
     collections_meta: HashMap<String, store::CollectionMeta>,
     primary_key_field_map: HashMap<String, String>,
     model_type_map: HashMap<String, Py<PyType>>,
-    pool: mobc::Pool<mobc_redis::RedisConnectionManager>,
-    default_ttl: Option<u64>,
+    views: HashMap<String, (String, ViewDefinition)>,
+    links: HashMap<String, LinkDefinition>,
     is_in_use: bool,
 }
 
+#[pyclass(subclass)]
+pub(crate) struct AsyncStore {
+    registry: Arc<RwLock<Registry>>,
+    pool: pools::AsyncRedisPool,
+    default_ttl: Option<Expiry>,
+    defaults: CollectionDefaults,
+    client: redis::Client,
+    mirrors: Mirrors,
+    write_buffer: WriteBuffer,
+    max_batch_bytes: usize,
+    schema_registry: SchemaRegistry,
+    pending_models: Vec<String>,
+    background_tasks: BackgroundTasks,
+    backup_schedules: BackupSchedules,
+}
+
 #[pymethods]
 impl AsyncStore {
-    /// Initializes the Store
+    /// Initializes the Store, with the options other than those of its pool given as keyword arguments
+    /// e.g. write_buffer or max_connecting
     #[args(
         url,
         pool_size = 5,
         default_ttl = "None",
         timeout = "None",
-        max_lifetime = "None"
+        max_lifetime = "None",
+        options = "**"
     )]
     #[new]
     pub fn new(
+        py: Python,
         url: String,
         pool_size: u64,
-        default_ttl: Option<u64>,
+        default_ttl: Option<&PyAny>,
         timeout: Option<u64>,
         max_lifetime: Option<u64>,
+        options: Option<&PyDict>,
     ) -> PyResult<Self> {
-        let client =
-            redis::Client::open(url).map_err(|e| PyConnectionError::new_err(e.to_string()))?;
-        let manager = mobc_redis::RedisConnectionManager::new(client);
-        let mut pool = mobc::Pool::builder().max_open(pool_size);
+        let default_ttl = default_ttl.map(Expiry::from_py).transpose()?;
+        let StoreOptions {
+            defaults,
+            write_buffer,
+            write_buffer_overflow,
+            circuit_breaker,
+            blocking_pool_size,
+            max_batch_bytes,
+            max_connecting,
+            ..
+        } = StoreOptions::from_py(options, true)?;
+        let client = endpoints::open_client(py, &url)?;
+        let write_buffer = WriteBuffer::new(client.clone(), write_buffer, &write_buffer_overflow)?;
 
-        if let Some(timeout) = timeout {
-            pool = pool.get_timeout(Some(Duration::from_millis(timeout)));
-        }
+        let build_pool = |size: u64| {
+            let mut manager = mobc_redis::RedisConnectionManager::new(client.clone());
 
-        if let Some(max_lifetime) = max_lifetime {
-            pool = pool.max_lifetime(Some(Duration::from_millis(max_lifetime)));
-        }
+            if let Some(max_connecting) = max_connecting {
+                manager = manager.with_max_connecting(max_connecting);
+            }
 
-        let pool = pool.build(manager);
+            let mut pool = mobc::Pool::builder().max_open(size);
 
+            if let Some(timeout) = timeout {
+                pool = pool.get_timeout(Some(Duration::from_millis(timeout)));
+            }
+
+            if let Some(max_lifetime) = max_lifetime {
+                pool = pool.max_lifetime(Some(Duration::from_millis(max_lifetime)));
+            }
+
+            pool.build(manager)
+        };
+        let pool = pools::AsyncRedisPool::new(
+            build_pool(pool_size),
+            blocking_pool_size.map(|size| build_pool(size as u64)),
+            circuit_breaker,
+        );
+
+        let background_tasks = BackgroundTasks::default();
         Ok(AsyncStore {
-            collections_meta: Default::default(),
+            registry: Default::default(),
             pool,
             default_ttl,
-            primary_key_field_map: Default::default(),
-            model_type_map: Default::default(),
-            is_in_use: false,
+            defaults,
+            client,
+            mirrors: Default::default(),
+            write_buffer,
+            max_batch_bytes,
+            schema_registry: Default::default(),
+            pending_models: Default::default(),
+            backup_schedules: BackupSchedules::new(background_tasks.clone()),
+            background_tasks,
         })
     }
 
@@ -75,10 +168,7 @@ impl AsyncStore {
             locals.clone(),
             // Store the current locals in task-local data
             asyncio::async_std::scope(locals.clone(), async move {
-                let mut conn = pool
-                    .get()
-                    .await
-                    .map_err(|e| PyConnectionError::new_err(e.to_string()))?;
+                let mut conn = pools::get_connection_async(&pool).await?;
                 let arg = if asynchronous { "ASYNC" } else { "SYNC" };
 
                 redis::cmd("FLUSHALL")
@@ -91,82 +181,57 @@ impl AsyncStore {
         )
     }
 
-    /// Creates a new collection for the given model and adds it to the store instance
-    pub(crate) fn create_collection(
-        &mut self,
-        model: Py<PyType>,
-        primary_key_field: String,
-    ) -> PyResult<()> {
-        if self.is_in_use {
-            return Err(PyConnectionError::new_err(
-                "a call to 'create_collection()' cannot come after a call to 'get_collection()'.",
-            ));
+    /// Measures the round-trip latency of `samples` PINGs to redis on one connection of the pool, returning
+    /// its statistics and the time the connection took to check out of the pool, all in milliseconds
+    #[args(samples = 10)]
+    #[pyo3(text_signature = "($self, samples)")]
+    pub fn latency<'a>(&self, py: Python<'a>, samples: usize) -> PyResult<&'a PyAny> {
+        if samples == 0 {
+            return Err(PyValueError::new_err("samples should be at least 1"));
         }
 
-        Python::with_gil(|py| {
-            let schema = model.getattr(py, "schema")?.call0(py)?;
-            let schema =
-                Schema::from_py_schema(schema, &self.primary_key_field_map, &self.model_type_map)?;
-            let nested_fields = schema.extract_nested_fields();
-            let model_name: String = model.getattr(py, "__qualname__")?.extract(py)?;
-            let meta = store::CollectionMeta::new(
-                Box::new(schema),
-                model.clone(),
-                primary_key_field.clone(),
-                nested_fields,
-            );
-            self.collections_meta.insert(model_name.clone(), meta);
-            self.primary_key_field_map
-                .insert(model_name.clone(), primary_key_field);
-            self.model_type_map.insert(model_name, model);
-            Ok(())
-        })
-    }
+        let locals = asyncio::async_std::get_current_locals(py)?;
+        let pool = self.pool.clone();
 
-    /// Instantiates an independent collection from the store for the given model
-    pub(crate) fn get_collection(&mut self, model: Py<PyType>) -> PyResult<AsyncCollection> {
-        let model_name: String =
-            Python::with_gil(|py| model.getattr(py, "__qualname__")?.extract(py))?;
-        if let Some(meta) = self.collections_meta.get(&model_name) {
-            self.is_in_use = true;
-            let pool = self.pool.clone();
-            Ok(AsyncCollection::new(
-                model_name,
-                pool,
-                meta.clone(),
-                self.default_ttl,
-            ))
-        } else {
-            Err(PyKeyError::new_err(format!(
-                "{} has not yet been created on the store",
-                model_name
-            )))
-        }
+        asyncio::async_std::future_into_py_with_locals(
+            py,
+            locals.clone(),
+            // Store the current locals in task-local data
+            asyncio::async_std::scope(locals.clone(), async move {
+                async_utils::measure_latency_async(&pool, samples)
+                    .await?
+                    .to_py()
+            }),
+        )
     }
-}
 
-#[pyclass(subclass)]
-pub(crate) struct AsyncCollection {
-    pub(crate) name: String,
-    pub(crate) meta: store::CollectionMeta,
-    pub(crate) pool: mobc::Pool<mobc_redis::RedisConnectionManager>,
-    pub(crate) default_ttl: Option<u64>,
-}
+    /// Checks out a connection and PINGs redis on it, failing with a ConnectionError if redis cannot be reached
+    /// e.g. on the startup of an app. The connections of an async store are otherwise opened as they are needed
+    pub fn connect<'a>(&self, py: Python<'a>) -> PyResult<&'a PyAny> {
+        let locals = asyncio::async_std::get_current_locals(py)?;
+        let pool = self.pool.clone();
 
-#[pymethods]
-impl AsyncCollection {
-    /// inserts one model instance into the redis store for this collection
-    pub(crate) fn add_one<'a>(
-        &self,
-        py: Python<'a>,
-        item: Py<PyAny>,
-        ttl: Option<u64>,
-    ) -> PyResult<&'a PyAny> {
+        asyncio::async_std::future_into_py_with_locals(
+            py,
+            locals.clone(),
+            // Store the current locals in task-local data
+            asyncio::async_std::scope(locals.clone(), async move {
+                let mut conn = pools::get_connection_async(&pool).await?;
+                redis::cmd("PING")
+                    .query_async(&mut conn as &mut Connection)
+                    .await
+                    .map_err(|e| PyConnectionError::new_err(e.to_string()))?;
+                Ok(Python::with_gil(|py| py.None()))
+            }),
+        )
+    }
+
+    /// Closes this store and all its collections, cancelling its background tasks and scheduled backups and
+    /// closing its idle connections. Any later call that needs a connection to redis fails with a ConnectionError
+    pub fn aclose<'a>(&self, py: Python<'a>) -> PyResult<&'a PyAny> {
+        self.backup_schedules.cancel_all(py)?;
+        self.background_tasks.cancel_all(py)?;
         let locals = asyncio::async_std::get_current_locals(py)?;
-        let name = self.name.clone();
-        let schema = self.meta.schema.clone();
-        let pk_field = self.meta.primary_key_field.clone();
-        let default_ttl = self.default_ttl.clone();
         let pool = self.pool.clone();
 
         asyncio::async_std::future_into_py_with_locals(
@@ -174,93 +239,136 @@ impl AsyncCollection {
             locals.clone(),
             // Store the current locals in task-local data
             asyncio::async_std::scope(locals.clone(), async move {
-                let records =
-                    utils::prepare_record_to_insert(&name, &schema, &item, &pk_field, None)?;
-                let ttl = match ttl {
-                    None => default_ttl,
-                    Some(v) => Some(v),
-                };
-                async_utils::insert_records_async(&pool, &records, &ttl).await
+                pool.close().await;
+                Ok(Python::with_gil(|py| py.None()))
             }),
         )
     }
 
-    /// Inserts many model instances into the redis store for this collection all in a batch.
-    /// This is more efficient than repeatedly calling add_one() because only one network request is made to redis
-    pub(crate) fn add_many<'a>(
+    /// Returns the counts of the calls of each operation on each collection of this store,
+    /// and of the records they read or wrote, since the collections were created
+    pub fn metrics(&self) -> PyResult<HashMap<String, Py<PyAny>>> {
+        self.read_registry()
+            .collections_meta
+            .iter()
+            .map(|(name, meta)| Ok((name.clone(), meta.metrics.to_py()?)))
+            .collect()
+    }
+
+    /// Describes the state of the circuit breaker of this store, set with `circuit_breaker_threshold`,
+    /// or returns None if it has none
+    pub fn circuit_breaker(&self) -> PyResult<Option<Py<PyAny>>> {
+        self.pool
+            .circuit_breaker
+            .as_ref()
+            .map(CircuitBreaker::to_py)
+            .transpose()
+    }
+
+    /// Counts the records of each collection of this store that have no ttl, whose ttl is below the threshold
+    /// (an hour by default), and whose ttl is below the imminent expiry (a minute by default). Both are
+    /// timedeltas or numbers of seconds. At most `max_ops_per_sec` records are scanned per second if given
+    #[args(threshold = "None", imminent = "None", max_ops_per_sec = "None")]
+    pub fn ttl_audit<'a>(
         &self,
         py: Python<'a>,
-        items: Vec<Py<PyAny>>,
-        ttl: Option<u64>,
+        threshold: Option<&PyAny>,
+        imminent: Option<&PyAny>,
+        max_ops_per_sec: Option<f64>,
     ) -> PyResult<&'a PyAny> {
+        let thresholds = TtlThresholds::from_py(threshold, imminent)?;
+        let mut throttle = Throttle::new(max_ops_per_sec)?;
         let locals = asyncio::async_std::get_current_locals(py)?;
-        let name = self.name.clone();
-        let schema = self.meta.schema.clone();
-        let pk_field = self.meta.primary_key_field.clone();
-        let default_ttl = self.default_ttl.clone();
         let pool = self.pool.clone();
+        let names: Vec<String> = self
+            .read_registry()
+            .collections_meta
+            .keys()
+            .cloned()
+            .collect();
 
         asyncio::async_std::future_into_py_with_locals(
             py,
             locals.clone(),
             // Store the current locals in task-local data
             asyncio::async_std::scope(locals.clone(), async move {
-                let mut records: Vec<(String, Vec<(String, String)>)> =
-                    Vec::with_capacity(2 * items.len());
-                for item in items {
-                    let mut records_to_insert =
-                        utils::prepare_record_to_insert(&name, &schema, &item, &pk_field, None)?;
-                    records.append(&mut records_to_insert);
+                let mut results: HashMap<String, Py<PyAny>> = HashMap::with_capacity(names.len());
+                for name in names {
+                    let audit =
+                        async_utils::audit_ttls_async(&pool, &name, &thresholds, &mut throttle)
+                            .await?;
+                    results.insert(name, audit.to_py()?);
                 }
-
-                let ttl = match ttl {
-                    None => default_ttl,
-                    Some(v) => Some(v),
-                };
-
-                async_utils::insert_records_async(&pool, &records, &ttl).await
+                Ok(results)
             }),
         )
     }
 
-    /// Updates the record of the given id with the provided data
-    pub(crate) fn update_one<'a>(
+    /// Applies the retention policies of all collections, removing records that are older than their
+    /// policy allows. It returns the number of records affected in each collection that has a retention policy.
+    /// At most `max_ops_per_sec` records are scanned per second if given
+    #[args(max_ops_per_sec = "None")]
+    pub fn apply_retention<'a>(
         &self,
         py: Python<'a>,
-        id: &str,
-        data: Py<PyAny>,
-        ttl: Option<u64>,
+        max_ops_per_sec: Option<f64>,
     ) -> PyResult<&'a PyAny> {
+        let mut throttle = Throttle::new(max_ops_per_sec)?;
         let locals = asyncio::async_std::get_current_locals(py)?;
-        let name = self.name.clone();
-        let schema = self.meta.schema.clone();
-        let pk_field = self.meta.primary_key_field.clone();
-        let default_ttl = self.default_ttl.clone();
         let pool = self.pool.clone();
-        let id = id.to_owned();
+        let policies = get_retention_policies(&self.registry);
 
         asyncio::async_std::future_into_py_with_locals(
             py,
             locals.clone(),
             // Store the current locals in task-local data
             asyncio::async_std::scope(locals.clone(), async move {
-                let records =
-                    utils::prepare_record_to_insert(&name, &schema, &data, &pk_field, Some(&id))?;
-
-                let ttl = match ttl {
-                    None => default_ttl,
-                    Some(v) => Some(v),
-                };
-
-                async_utils::insert_records_async(&pool, &records, &ttl).await
+                let mut results: HashMap<String, u64> = HashMap::with_capacity(policies.len());
+                for (name, policy) in policies {
+                    let affected =
+                        async_utils::apply_retention_async(&pool, &name, &policy, &mut throttle)
+                            .await?;
+                    results.insert(name, affected);
+                }
+                Ok(results)
             }),
         )
     }
 
-    /// Deletes the records that correspond to the given ids for this collection
-    pub(crate) fn delete_many<'a>(&self, py: Python<'a>, ids: Vec<String>) -> PyResult<&'a PyAny> {
+    /// Adds the redis instance of the given url as a mirror to which every write made through this
+    /// store is also applied, in the background and on a best-effort basis
+    #[pyo3(text_signature = "($self, url)")]
+    pub fn add_mirror(&self, py: Python, url: &str) -> PyResult<()> {
+        self.mirrors.add(py, url)
+    }
+
+    /// Gets the number of writes made while redis was unreachable that are still waiting in the write
+    /// buffer to be applied. It is always 0 if the store has no write buffer
+    #[pyo3(text_signature = "($self)")]
+    pub fn pending_writes(&self) -> usize {
+        self.write_buffer.pending()
+    }
+
+    /// Takes the errors of the buffered writes that failed when they were applied once redis was reachable
+    /// again e.g. because they exceeded a quota, and were thus dropped, oldest first
+    #[pyo3(text_signature = "($self)")]
+    pub fn take_failed_writes(&self) -> Vec<String> {
+        self.write_buffer.take_failures()
+    }
+
+    /// Sets the limits of the quota of the given namespace i.e. the maximum number of records in the collections
+    /// created with that namespace and the maximum total length of their field names and values. The limits not
+    /// given are removed. Quotas are saved in redis so that they apply to all stores of the namespace
+    #[args(namespace, max_records = "None", max_bytes = "None")]
+    #[pyo3(text_signature = "($self, namespace, max_records, max_bytes)")]
+    pub fn set_quota<'a>(
+        &self,
+        py: Python<'a>,
+        namespace: String,
+        max_records: Option<u64>,
+        max_bytes: Option<u64>,
+    ) -> PyResult<&'a PyAny> {
         let locals = asyncio::async_std::get_current_locals(py)?;
-        let name = self.name.clone();
         let pool = self.pool.clone();
 
         asyncio::async_std::future_into_py_with_locals(
@@ -268,171 +376,2770 @@ impl AsyncCollection {
             locals.clone(),
             // Store the current locals in task-local data
             asyncio::async_std::scope(locals.clone(), async move {
-                let primary_keys: Vec<String> = ids
-                    .iter()
-                    .map(|id| utils::generate_hash_key(&name, id))
-                    .collect();
-                async_utils::remove_records_async(&pool, &primary_keys).await
+                async_utils::set_quota_async(&pool, &namespace, max_records, max_bytes).await
             }),
         )
     }
 
-    /// Gets the record that corresponds to the given id
-    pub(crate) fn get_one<'a>(&self, py: Python<'a>, id: &str) -> PyResult<&'a PyAny> {
+    /// Gets the usage of the quota of the given namespace, with its limits
+    #[pyo3(text_signature = "($self, namespace)")]
+    pub fn get_quota<'a>(&self, py: Python<'a>, namespace: String) -> PyResult<&'a PyAny> {
         let locals = asyncio::async_std::get_current_locals(py)?;
         let pool = self.pool.clone();
-        let name = self.name.clone();
-        let meta = self.meta.clone();
-        let id = id.to_owned();
 
         asyncio::async_std::future_into_py_with_locals(
             py,
             locals.clone(),
             // Store the current locals in task-local data
             asyncio::async_std::scope(locals.clone(), async move {
-                let mut records: Vec<Py<PyAny>> =
-                    async_utils::get_records_by_id_async(&pool, &name, &meta, &vec![id]).await?;
-                match records.pop() {
-                    None => Python::with_gil(|py| Ok(py.None())),
-                    Some(record) => Ok(record),
-                }
+                async_utils::get_quota_async(&pool, &namespace)
+                    .await?
+                    .to_py()
             }),
         )
     }
 
-    /// Returns all the records found in this collection; returning them as models
-    pub(crate) fn get_all<'a>(&self, py: Python<'a>) -> PyResult<&'a PyAny> {
+    /// Applies the retention policies of all collections every `interval` (a timedelta or number of seconds)
+    /// until the returned awaitable is cancelled. It is meant to be run as a background task. At most
+    /// `max_ops_per_sec` records are scanned per second in each round if given
+    #[args(interval, max_ops_per_sec = "None")]
+    pub fn run_retention<'a>(
+        &self,
+        py: Python<'a>,
+        interval: &PyAny,
+        max_ops_per_sec: Option<f64>,
+    ) -> PyResult<&'a PyAny> {
+        let mut throttle = Throttle::new(max_ops_per_sec)?;
         let locals = asyncio::async_std::get_current_locals(py)?;
         let pool = self.pool.clone();
-        let name = self.name.clone();
-        let meta = self.meta.clone();
+        let registry = self.registry.clone();
+        let interval = parsers::extract_duration(interval)?;
 
         asyncio::async_std::future_into_py_with_locals(
             py,
             locals.clone(),
             // Store the current locals in task-local data
             asyncio::async_std::scope(locals.clone(), async move {
-                async_utils::get_all_records_in_collection_async(&pool, &name, &meta).await
+                loop {
+                    // collections created since the last round are included
+                    let policies = get_retention_policies(&registry);
+                    throttle.restart();
+                    for (name, policy) in &policies {
+                        async_utils::apply_retention_async(&pool, name, policy, &mut throttle)
+                            .await?;
+                    }
+                    task::sleep(interval).await;
+                }
+                #[allow(unreachable_code)]
+                Ok(())
             }),
         )
     }
 
-    /// Returns the records whose ids are as given for this collection
-    pub(crate) fn get_many<'a>(&self, py: Python<'a>, ids: Vec<String>) -> PyResult<&'a PyAny> {
+    /// Runs the awaitable returned by calling `coroutine_factory` every `interval` (a timedelta or number of seconds)
+    /// in the background under the given name, until it is cancelled with cancel_background() or one of the
+    /// awaitables raises an error
+    #[pyo3(text_signature = "($self, task_name, coroutine_factory, interval)")]
+    pub fn spawn_background(
+        &self,
+        py: Python,
+        task_name: String,
+        coroutine_factory: Py<PyAny>,
+        interval: &PyAny,
+    ) -> PyResult<()> {
+        let interval = parsers::extract_duration(interval)?;
+        self.background_tasks
+            .spawn(py, task_name, coroutine_factory, interval)
+    }
+
+    /// Returns the status of each of the background tasks of this store by name e.g. the compactions
+    /// started with `compact(background=True)`
+    pub fn background_tasks(&self, py: Python) -> PyResult<Py<PyAny>> {
+        self.background_tasks.to_py(py)
+    }
+
+    /// Cancels the background task of the given name, returning whether there was such a task
+    #[pyo3(text_signature = "($self, task_name)")]
+    pub fn cancel_background(&self, py: Python, task_name: &str) -> PyResult<bool> {
+        self.background_tasks.cancel(py, task_name)
+    }
+
+    /// Backs all the collections created so far up every `interval`, a timedelta, a number of seconds or
+    /// a cron-like shorthand e.g. "@daily", into a new directory under `target`, a local directory or a url
+    /// e.g. "s3://bucket/path", named after the time of the backup. Each collection is exported as by
+    /// `Collection.backup()` into a file named after it. Only the `keep` latest backups made by the schedule
+    /// are kept. The backups are made in a background task named "backup:<target>" until `cancel_backup_schedule()`
+    /// is called, and their status is got with `backup_schedules()`
+    #[args(interval, target, keep = 7, chunk_size = 1000)]
+    pub fn schedule_backup(
+        &self,
+        py: Python,
+        interval: &PyAny,
+        target: String,
+        keep: usize,
+        chunk_size: usize,
+    ) -> PyResult<()> {
+        let interval = backup_schedules::extract_interval(interval)?;
+        let job = BackupJob {
+            pool: BackupPool::Async(self.pool.clone()),
+            collections: self
+                .read_registry()
+                .collections_meta
+                .iter()
+                .map(|(name, meta)| (name.clone(), meta.clone()))
+                .collect(),
+            target,
+            chunk_size,
+        };
+        self.backup_schedules.spawn(py, job, interval, keep)
+    }
+
+    /// Returns the status of each of the scheduled backups of this store by target
+    pub fn backup_schedules(&self, py: Python) -> PyResult<Py<PyAny>> {
+        self.backup_schedules.to_py(py)
+    }
+
+    /// Cancels the scheduled backup into the given target, returning whether there was such a schedule
+    #[pyo3(text_signature = "($self, target)")]
+    pub fn cancel_backup_schedule(&self, py: Python, target: &str) -> PyResult<bool> {
+        self.backup_schedules.cancel(py, target)
+    }
+
+    /// Compares a random sample of the records of the collection of the given model in this store
+    /// with those in the other store field by field, returning a report of the drift between them.
+    /// Records are sampled from both stores with a probability of `sample` each
+    #[args(other, collection, sample = "0.01")]
+    pub fn verify_against<'a>(
+        &self,
+        py: Python<'a>,
+        other: PyRef<AsyncStore>,
+        collection: Py<PyType>,
+        sample: f64,
+    ) -> PyResult<&'a PyAny> {
         let locals = asyncio::async_std::get_current_locals(py)?;
+        let sample = verification::validate_sample_rate(sample)?;
+        let model_name: String = collection.getattr(py, "__qualname__")?.extract(py)?;
+        let meta = self
+            .read_registry()
+            .collections_meta
+            .get(&model_name)
+            .ok_or_else(|| {
+                PyKeyError::new_err(format!(
+                    "{} has not yet been created on the store",
+                    model_name
+                ))
+            })?
+            .clone();
         let pool = self.pool.clone();
-        let name = self.name.clone();
-        let meta = self.meta.clone();
+        let other_pool = other.pool.clone();
 
         asyncio::async_std::future_into_py_with_locals(
             py,
             locals.clone(),
             // Store the current locals in task-local data
             asyncio::async_std::scope(locals.clone(), async move {
-                async_utils::get_records_by_id_async(&pool, &name, &meta, &ids).await
+                let ids = verification::merge_sampled_ids(
+                    async_utils::sample_record_ids_async(&pool, &model_name, sample).await?,
+                    async_utils::sample_record_ids_async(&other_pool, &model_name, sample).await?,
+                );
+                let records =
+                    async_utils::get_raw_records_async(&pool, &model_name, &meta, &ids).await?;
+                let other_records =
+                    async_utils::get_raw_records_async(&other_pool, &model_name, &meta, &ids)
+                        .await?;
+                verification::generate_drift_report(
+                    &model_name,
+                    &meta,
+                    &ids,
+                    &records,
+                    &other_records,
+                )
             }),
         )
     }
 
-    /// Returns the record that corresponds to the given id in this collection
-    /// returning it as a dictionary with only the fields specified
-    pub(crate) fn get_one_partially<'a>(
+    /// Describes the collections, views and links of this store i.e. the fields of each collection with their
+    /// types, its indexes and its relationships to other collections, e.g. to render admin UIs
+    #[pyo3(text_signature = "($self)")]
+    pub fn describe(&self) -> PyResult<Py<PyAny>> {
+        let (collections_meta, views, links) = {
+            let registry = self.read_registry();
+            (
+                registry.collections_meta.clone(),
+                registry.views.clone(),
+                registry.links.clone(),
+            )
+        };
+        descriptions::describe_store(&collections_meta, &views, &links)
+    }
+
+    /// Gets a page of at most `limit` records of the collection of the given model as dictionaries of only
+    /// the given fields, or of all fields if none are given, e.g. those a user of an admin panel may see.
+    /// It returns a dictionary of the "records", the "total" number of records in the collection and the
+    /// "next_cursor" to pass to get the next page, None if this is the last page. The first page is got
+    /// without a cursor. Records are in the order in which SCAN finds them
+    #[args(collection, cursor = "None", limit = 20, fields = "None")]
+    pub fn browse<'a>(
         &self,
         py: Python<'a>,
-        id: &str,
-        fields: Vec<String>,
+        collection: Py<PyType>,
+        cursor: Option<&str>,
+        limit: usize,
+        fields: Option<Vec<String>>,
     ) -> PyResult<&'a PyAny> {
         let locals = asyncio::async_std::get_current_locals(py)?;
+        let model_name: String = collection.getattr(py, "__qualname__")?.extract(py)?;
+        let meta = self
+            .read_registry()
+            .collections_meta
+            .get(&model_name)
+            .ok_or_else(|| {
+                PyKeyError::new_err(format!(
+                    "{} has not yet been created on the store",
+                    model_name
+                ))
+            })?
+            .clone();
+        let cursor = BrowseCursor::parse(cursor)?;
+        let limit = pagination::validate_browse_limit(limit)?;
+        let fields = pagination::resolve_browse_fields(&meta, fields)?;
         let pool = self.pool.clone();
-        let name = self.name.clone();
-        let meta = self.meta.clone();
-        let id = id.to_owned();
 
         asyncio::async_std::future_into_py_with_locals(
             py,
             locals.clone(),
             // Store the current locals in task-local data
             asyncio::async_std::scope(locals.clone(), async move {
-                let mut records: Vec<Py<PyAny>> = async_utils::get_partial_records_by_id_async(
+                let (page, records) = async_utils::browse_records_async(
                     &pool,
-                    &name,
+                    &model_name,
                     &meta,
-                    &vec![id],
+                    cursor,
+                    limit,
                     &fields,
                 )
                 .await?;
-                match records.pop() {
-                    None => Python::with_gil(|py| Ok(py.None())),
-                    Some(record) => Ok(record),
-                }
+                meta.metrics.record("browse", records.len());
+                page.to_py(records)
             }),
         )
     }
 
-    /// Retrieves the all records in this collection, only returning the specified fields
-    /// for each given record
-    pub(crate) fn get_all_partially<'a>(
-        &self,
-        py: Python<'a>,
-        fields: Vec<String>,
-    ) -> PyResult<&'a PyAny> {
+    /// Compares the schema of the collection of the given model with the snapshot of it last saved by
+    /// save_schema_snapshot(), returning the plan of the migration of the records from the saved schema
+    #[pyo3(text_signature = "($self, model)")]
+    pub fn plan_migration<'a>(&self, py: Python<'a>, model: Py<PyType>) -> PyResult<&'a PyAny> {
         let locals = asyncio::async_std::get_current_locals(py)?;
+        let (model_name, meta) = self.get_collection_meta(py, &model)?;
         let pool = self.pool.clone();
-        let name = self.name.clone();
-        let meta = self.meta.clone();
 
         asyncio::async_std::future_into_py_with_locals(
             py,
             locals.clone(),
             // Store the current locals in task-local data
             asyncio::async_std::scope(locals.clone(), async move {
-                async_utils::get_all_partial_records_in_collection_async(
-                    &pool, &name, &meta, &fields,
-                )
-                .await
+                let snapshot = async_utils::get_schema_snapshot_async(&pool, &model_name).await?;
+                MigrationPlan::new(&meta.schema, snapshot).to_py(&model_name)
             }),
         )
     }
 
-    /// Retrieves the records with the given ids in this collection, only returning
-    /// the specified fields for each record
-    pub(crate) fn get_many_partially<'a>(
+    /// Saves the snapshot of the current schema of the collection of the given model in redis, replacing
+    /// the one plan_migration() compares with. It is meant to be called once the records are migrated
+    #[pyo3(text_signature = "($self, model)")]
+    pub fn save_schema_snapshot<'a>(
         &self,
         py: Python<'a>,
-        ids: Vec<String>,
-        fields: Vec<String>,
+        model: Py<PyType>,
     ) -> PyResult<&'a PyAny> {
         let locals = asyncio::async_std::get_current_locals(py)?;
+        let (model_name, meta) = self.get_collection_meta(py, &model)?;
         let pool = self.pool.clone();
-        let name = self.name.clone();
-        let meta = self.meta.clone();
 
         asyncio::async_std::future_into_py_with_locals(
             py,
             locals.clone(),
             // Store the current locals in task-local data
             asyncio::async_std::scope(locals.clone(), async move {
-                async_utils::get_partial_records_by_id_async(&pool, &name, &meta, &ids, &fields)
-                    .await
+                async_utils::save_schema_snapshot_async(&pool, &model_name, &meta).await
             }),
         )
     }
-}
 
-impl AsyncCollection {
-    /// Instantiates a new collection. This is not accessible to python and thus a collection
-    /// cannot be directly instantiated in python
-    pub(crate) fn new(
+    /// Creates a new collection for the given model and adds it to the store instance, with the options given
+    /// as keyword arguments e.g. id_generator or indexed_fields
+    #[args(model, primary_key_field, options = "**")]
+    pub(crate) fn create_collection(
+        &mut self,
+        model: Py<PyType>,
+        primary_key_field: String,
+        options: Option<&PyDict>,
+    ) -> PyResult<()> {
+        let options = CollectionOptions::from_py(options, &self.defaults)?;
+        self.add_collection(model, primary_key_field, options)
+    }
+
+    /// Instantiates an independent collection from the store for the given model
+    pub(crate) fn get_collection(&mut self, model: Py<PyType>) -> PyResult<AsyncCollection> {
+        let model_name: String =
+            Python::with_gil(|py| model.getattr(py, "__qualname__")?.extract(py))?;
+        let mut registry = self.write_registry();
+        if let Some(meta) = registry.collections_meta.get(&model_name).cloned() {
+            registry.is_in_use = true;
+            let pool = self.pool.clone();
+            Ok(AsyncCollection::new(
+                model_name,
+                pool,
+                meta,
+                self.default_ttl,
+            ))
+        } else {
+            Err(PyKeyError::new_err(format!(
+                "{} has not yet been created on the store",
+                model_name
+            )))
+        }
+    }
+
+    /// Creates a materialized view of the collection of the given model i.e. the records that match
+    /// the filter, with only the fields in the projection. The view is kept up to date on every write
+    /// to the collection
+    #[args(name, source_collection, projection, filter = "None")]
+    pub(crate) fn create_view(
+        &mut self,
         name: String,
-        pool: mobc::Pool<mobc_redis::RedisConnectionManager>,
-        meta: store::CollectionMeta,
-        default_ttl: Option<u64>,
-    ) -> Self {
-        Self {
-            name,
-            meta,
-            pool,
-            default_ttl,
+        source_collection: Py<PyType>,
+        projection: Vec<String>,
+        filter: Option<HashMap<String, Py<PyAny>>>,
+    ) -> PyResult<()> {
+        let model_name: String =
+            Python::with_gil(|py| source_collection.getattr(py, "__qualname__")?.extract(py))?;
+        let mut registry = self.write_registry();
+        if registry.is_in_use {
+            return Err(PyConnectionError::new_err(
+                "a call to 'create_view()' cannot come after a call to 'get_collection()' or 'get_view()'.",
+            ));
+        }
+
+        if registry.views.contains_key(&name) {
+            return Err(PyValueError::new_err(format!(
+                "view {:?} already exists",
+                name
+            )));
+        }
+
+        let meta = registry
+            .collections_meta
+            .get_mut(&model_name)
+            .ok_or_else(|| {
+                PyKeyError::new_err(format!(
+                    "{} has not yet been created on the store",
+                    model_name
+                ))
+            })?;
+
+        let view = ViewDefinition::from_py(name.clone(), meta, projection, filter)?;
+        meta.views.push(view.clone());
+        registry.views.insert(name, (model_name, view));
+        Ok(())
+    }
+
+    /// Instantiates a read-only handle on the view of the given name
+    pub(crate) fn get_view(&mut self, name: &str) -> PyResult<AsyncView> {
+        let mut registry = self.write_registry();
+        if let Some((source, view)) = registry.views.get(name) {
+            let view = AsyncView::new(
+                source.clone(),
+                view.clone(),
+                view.generate_read_meta(&registry.collections_meta[source])?,
+                self.pool.clone(),
+            );
+            registry.is_in_use = true;
+            Ok(view)
+        } else {
+            Err(PyKeyError::new_err(format!(
+                "view {:?} has not yet been created on the store",
+                name
+            )))
+        }
+    }
+
+    /// Creates a many-to-many link between the records of the collections of the given models e.g. students
+    /// and courses. Unlike views, links can be created at any time since they do not change the collections
+    pub(crate) fn create_link(
+        &mut self,
+        name: String,
+        left_collection: Py<PyType>,
+        right_collection: Py<PyType>,
+    ) -> PyResult<()> {
+        let (left, right) = Python::with_gil(|py| -> PyResult<(String, String)> {
+            Ok((
+                left_collection.getattr(py, "__qualname__")?.extract(py)?,
+                right_collection.getattr(py, "__qualname__")?.extract(py)?,
+            ))
+        })?;
+        let mut registry = self.write_registry();
+        if registry.links.contains_key(&name) {
+            return Err(PyValueError::new_err(format!(
+                "link {:?} already exists",
+                name
+            )));
+        }
+
+        for model_name in [&left, &right] {
+            if !registry.collections_meta.contains_key(model_name) {
+                return Err(PyKeyError::new_err(format!(
+                    "{} has not yet been created on the store",
+                    model_name
+                )));
+            }
+        }
+
+        registry
+            .links
+            .insert(name.clone(), LinkDefinition::new(name, left, right));
+        Ok(())
+    }
+
+    /// Instantiates a handle on the link of the given name
+    pub(crate) fn get_link(&self, name: &str) -> PyResult<AsyncLink> {
+        let registry = self.read_registry();
+        if let Some(link) = registry.links.get(name) {
+            Ok(AsyncLink::new(
+                link.clone(),
+                registry.collections_meta[&link.left].clone(),
+                registry.collections_meta[&link.right].clone(),
+                self.pool.clone(),
+            ))
+        } else {
+            Err(PyKeyError::new_err(format!(
+                "link {:?} has not yet been created on the store",
+                name
+            )))
+        }
+    }
+}
+
+impl AsyncStore {
+    /// Creates the collection of the given model with the given options, and those of its nested models
+    /// if they are to be registered with it
+    fn add_collection(
+        &mut self,
+        model: Py<PyType>,
+        primary_key_field: String,
+        options: CollectionOptions,
+    ) -> PyResult<()> {
+        self.ensure_not_in_use()?;
+
+        Python::with_gil(|py| {
+            schema::resolve_forward_refs(model.as_ref(py), &self.read_registry().model_type_map)
+        })?;
+
+        // schemas refer to models by their names, not their qualified names
+        let schema_name: String =
+            Python::with_gil(|py| model.getattr(py, "__name__")?.extract(py))?;
+
+        if options.register_nested_models {
+            let nested_models = Python::with_gil(|py| {
+                schema::find_unregistered_nested_models(
+                    model.as_ref(py),
+                    &self.read_registry().primary_key_field_map,
+                )
+            })?;
+            // the nested models may refer back to this model, whose collection does not exist yet
+            self.pending_models.push(schema_name.clone());
+            let result =
+                nested_models
+                    .into_iter()
+                    .try_for_each(|(nested_model, primary_key_field)| {
+                        let nested_options = options.for_nested_model(&self.defaults);
+                        self.add_collection(nested_model, primary_key_field, nested_options)
+                    });
+            self.pending_models.pop();
+            result?;
+        }
+
+        let enforce_constraints = options.enforce_constraints;
+        Python::with_gil(|py| {
+            let py_schema = model.getattr(py, "schema")?.call0(py)?;
+            let mut resolving = self.pending_models.clone();
+            resolving.push(schema_name.clone());
+            let context = SchemaContext::new(&self.schema_registry, resolving);
+            let mut schema = Schema::from_py_schema(py_schema.clone_ref(py), &context)?;
+            schema.refine_with_model_fields(model.as_ref(py))?;
+            let nested_fields = schema.extract_nested_fields();
+            let counter_fields = schema.extract_counter_fields(py_schema.as_ref(py))?;
+            let model_name: String = model.getattr(py, "__qualname__")?.extract(py)?;
+            let mut meta = store::CollectionMeta::new(
+                Box::new(schema),
+                model.clone(),
+                primary_key_field.clone(),
+                nested_fields,
+                counter_fields,
+                options,
+            )?;
+            let mut registry = self.write_registry();
+            // the store may have been put in use since the call started
+            if registry.is_in_use {
+                return Err(in_use_error());
+            }
+
+            meta.bloom_filtered_collections = bloom_filters::get_bloom_filtered_collections(
+                &model_name,
+                &meta,
+                &registry.collections_meta,
+            );
+            if enforce_constraints {
+                meta.constraints =
+                    constraints::extract_constraint_rules(py_schema.as_ref(py), &meta.schema)?;
+            }
+            meta.record_versions =
+                versioning::get_record_versions(&model_name, &meta, &registry.collections_meta);
+            meta.mirrors = self.mirrors.clone();
+            meta.write_buffer = self.write_buffer.clone();
+            meta.background_tasks = self.background_tasks.clone();
+            meta.batch_sizer = BatchSizer::new(self.max_batch_bytes);
+            compression::register_source(&model_name, &self.client);
+            self.schema_registry.register(
+                schema_name,
+                NestedModel {
+                    model_name: model_name.clone(),
+                    schema: (*meta.schema).clone(),
+                    primary_key_field: primary_key_field.clone(),
+                    model_type: model.clone(),
+                    case_insensitive_keys: meta.case_insensitive_keys,
+                },
+            );
+            registry.collections_meta.insert(model_name.clone(), meta);
+            registry
+                .primary_key_field_map
+                .insert(model_name.clone(), primary_key_field);
+            registry.model_type_map.insert(model_name, model);
+            Ok(())
+        })
+    }
+
+    /// Locks the registry of collections and views for reading. The lock should only be held
+    /// while copying out of it, never across an await
+    fn read_registry(&self) -> RwLockReadGuard<'_, Registry> {
+        self.registry.read().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Locks the registry of collections and views for writing
+    fn write_registry(&self) -> RwLockWriteGuard<'_, Registry> {
+        self.registry.write().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Gets the name and a snapshot of the meta of the collection of the given model
+    fn get_collection_meta(
+        &self,
+        py: Python,
+        model: &Py<PyType>,
+    ) -> PyResult<(String, store::CollectionMeta)> {
+        let model_name: String = model.getattr(py, "__qualname__")?.extract(py)?;
+        let meta = self
+            .read_registry()
+            .collections_meta
+            .get(&model_name)
+            .ok_or_else(|| {
+                PyKeyError::new_err(format!(
+                    "{} has not yet been created on the store",
+                    model_name
+                ))
+            })?
+            .clone();
+        Ok((model_name, meta))
+    }
+
+    /// Fails if a collection or view has been got from this store, after which no more can be created
+    fn ensure_not_in_use(&self) -> PyResult<()> {
+        if self.read_registry().is_in_use {
+            return Err(in_use_error());
+        }
+
+        Ok(())
+    }
+}
+
+/// The error raised when a collection is created after the store is put in use
+fn in_use_error() -> PyErr {
+    PyConnectionError::new_err(
+        "a call to 'create_collection()' cannot come after a call to 'get_collection()'.",
+    )
+}
+
+/// Gets a snapshot of the (collection name, retention policy) pairs for all collections
+/// that have a retention policy
+fn get_retention_policies(registry: &RwLock<Registry>) -> Vec<(String, RetentionPolicy)> {
+    registry
+        .read()
+        .unwrap_or_else(|e| e.into_inner())
+        .collections_meta
+        .iter()
+        .filter_map(|(name, meta)| {
+            meta.retention
+                .as_ref()
+                .map(|policy| (name.clone(), policy.clone()))
+        })
+        .collect()
+}
+
+#[pyclass(subclass)]
+pub(crate) struct AsyncCollection {
+    pub(crate) name: String,
+    pub(crate) meta: store::CollectionMeta,
+    pub(crate) pool: pools::AsyncRedisPool,
+    pub(crate) default_ttl: Option<Expiry>,
+}
+
+#[pymethods]
+impl AsyncCollection {
+    /// inserts one model instance into the redis store for this collection, returning its primary key e.g.
+    /// the one generated by the id_generator. If profile is true, it returns the timing breakdown of the call
+    /// as (primary key, profile)
+    #[args(item, ttl = "None", profile = "false")]
+    pub(crate) fn add_one<'a>(
+        &self,
+        py: Python<'a>,
+        item: Py<PyAny>,
+        ttl: Option<&PyAny>,
+        profile: bool,
+    ) -> PyResult<&'a PyAny> {
+        let locals = asyncio::async_std::get_current_locals(py)?;
+        let ttl = ttl.map(Expiry::from_py).transpose()?;
+        let name = self.name.clone();
+        let meta = self.meta.clone();
+        let default_ttl = self.default_ttl;
+        let pool = self.pool.clone();
+        let profile = Profile::start(profile);
+
+        asyncio::async_std::future_into_py_with_locals(
+            py,
+            locals.clone(),
+            // Store the current locals in task-local data
+            asyncio::async_std::scope(locals.clone(), async move {
+                let id = profiling::run_async(&profile, async {
+                    let mut records = profiling::measure(Phase::Serialize, || {
+                        utils::prepare_record_to_insert(
+                            &name,
+                            &meta.schema,
+                            &item,
+                            &meta.primary_key_field,
+                            None,
+                            &meta.id_generator,
+                            meta.case_insensitive_keys,
+                        )
+                    })?;
+                    versioning::stamp_versions(&mut records, &meta.record_versions);
+                    let ttl = utils::resolve_ttl(ttl, default_ttl, &meta.retention);
+                    async_utils::insert_records_async(&pool, &name, &meta, &records, &[], &ttl)
+                        .await?;
+                    Ok::<_, PyErr>(utils::get_inserted_id(&name, &records))
+                })
+                .await?;
+                meta.metrics.record("add_one", 1);
+                profiling::attach(profile, id)
+            }),
+        )
+    }
+
+    /// Inserts one model instance into the redis store for this collection only if no record has its primary key,
+    /// returning whether it was inserted. Of many concurrent calls for the same primary key, only one inserts it,
+    /// so create-only flows need no get_one() before the insert
+    #[args(item, ttl = "None")]
+    pub(crate) fn add_one_if_not_exists<'a>(
+        &self,
+        py: Python<'a>,
+        item: Py<PyAny>,
+        ttl: Option<&PyAny>,
+    ) -> PyResult<&'a PyAny> {
+        let locals = asyncio::async_std::get_current_locals(py)?;
+        let ttl = ttl.map(Expiry::from_py).transpose()?;
+        let name = self.name.clone();
+        let meta = self.meta.clone();
+        let default_ttl = self.default_ttl;
+        let pool = self.pool.clone();
+
+        asyncio::async_std::future_into_py_with_locals(
+            py,
+            locals.clone(),
+            // Store the current locals in task-local data
+            asyncio::async_std::scope(locals.clone(), async move {
+                let mut records = utils::prepare_record_to_insert(
+                    &name,
+                    &meta.schema,
+                    &item,
+                    &meta.primary_key_field,
+                    None,
+                    &meta.id_generator,
+                    meta.case_insensitive_keys,
+                )?;
+                versioning::stamp_versions(&mut records, &meta.record_versions);
+                let ttl = utils::resolve_ttl(ttl, default_ttl, &meta.retention);
+
+                let is_inserted = async_utils::insert_record_if_missing_async(
+                    &pool, &name, &meta, &records, &ttl,
+                )
+                .await?;
+                meta.metrics
+                    .record("add_one_if_not_exists", is_inserted as usize);
+                Ok(is_inserted)
+            }),
+        )
+    }
+
+    /// Inserts many model instances into the redis store for this collection in batches of at most the
+    /// `max_batch_bytes` of the store, each written in one network request. This is more efficient than repeatedly
+    /// calling add_one(). It returns their primary keys, in the order of the items. If profile is true,
+    /// it returns the timing breakdown of the call as (primary keys, profile)
+    #[args(items, ttl = "None", profile = "false")]
+    pub(crate) fn add_many<'a>(
+        &self,
+        py: Python<'a>,
+        items: Vec<Py<PyAny>>,
+        ttl: Option<&PyAny>,
+        profile: bool,
+    ) -> PyResult<&'a PyAny> {
+        let locals = asyncio::async_std::get_current_locals(py)?;
+        let ttl = ttl.map(Expiry::from_py).transpose()?;
+        let name = self.name.clone();
+        let meta = self.meta.clone();
+        let default_ttl = self.default_ttl;
+        let pool = self.pool.clone();
+        let profile = Profile::start(profile);
+
+        asyncio::async_std::future_into_py_with_locals(
+            py,
+            locals.clone(),
+            // Store the current locals in task-local data
+            asyncio::async_std::scope(locals.clone(), async move {
+                let number_of_items = items.len();
+                let ids = profiling::run_async(&profile, async {
+                    let mut records: Vec<InsertRecords> = Vec::with_capacity(items.len());
+                    for item in items {
+                        records.push(profiling::measure(Phase::Serialize, || {
+                            utils::prepare_record_to_insert(
+                                &name,
+                                &meta.schema,
+                                &item,
+                                &meta.primary_key_field,
+                                None,
+                                &meta.id_generator,
+                                meta.case_insensitive_keys,
+                            )
+                        })?);
+                    }
+
+                    let ttl = utils::resolve_ttl(ttl, default_ttl, &meta.retention);
+                    let ids: Vec<String> = records
+                        .iter()
+                        .map(|v| utils::get_inserted_id(&name, v))
+                        .collect();
+
+                    for mut batch in meta.batch_sizer.split_writes(records) {
+                        versioning::stamp_versions(&mut batch, &meta.record_versions);
+                        async_utils::insert_records_async(&pool, &name, &meta, &batch, &[], &ttl)
+                            .await?;
+                    }
+                    Ok::<_, PyErr>(ids)
+                })
+                .await?;
+                meta.metrics.record("add_many", number_of_items);
+                profiling::attach(profile, ids)
+            }),
+        )
+    }
+
+    /// Inserts `n` records of random but valid values generated from the schema of this collection, with their
+    /// nested records, e.g. for load tests or demos. Records generated with the same seed are the same
+    #[args(n, seed = "None", ttl = "None")]
+    pub(crate) fn generate_fake<'a>(
+        &self,
+        py: Python<'a>,
+        n: usize,
+        seed: Option<u64>,
+        ttl: Option<&PyAny>,
+    ) -> PyResult<&'a PyAny> {
+        let locals = asyncio::async_std::get_current_locals(py)?;
+        let ttl = ttl.map(Expiry::from_py).transpose()?;
+        let name = self.name.clone();
+        let meta = self.meta.clone();
+        let default_ttl = self.default_ttl;
+        let pool = self.pool.clone();
+
+        asyncio::async_std::future_into_py_with_locals(
+            py,
+            locals.clone(),
+            // Store the current locals in task-local data
+            asyncio::async_std::scope(locals.clone(), async move {
+                let ttl = utils::resolve_ttl(ttl, default_ttl, &meta.retention);
+                let mut generator = FakeRecords::new(seed, meta.nested_depth);
+
+                let mut remaining = n;
+                while remaining > 0 {
+                    let batch_size = remaining.min(FAKE_BATCH_SIZE);
+                    let records = Python::with_gil(|py| {
+                        generator.generate_batch(py, &name, &meta, batch_size)
+                    })?;
+                    async_utils::insert_records_async(&pool, &name, &meta, &records, &[], &ttl)
+                        .await?;
+                    remaining -= batch_size;
+                }
+
+                meta.metrics.record("generate_fake", n);
+                Ok(())
+            }),
+        )
+    }
+
+    /// Updates the record of the given id with the provided data
+    pub(crate) fn update_one<'a>(
+        &self,
+        py: Python<'a>,
+        id: &str,
+        data: Py<PyAny>,
+        ttl: Option<&PyAny>,
+    ) -> PyResult<&'a PyAny> {
+        self.meta.record_accesses([id]);
+        let locals = asyncio::async_std::get_current_locals(py)?;
+        let ttl = ttl.map(Expiry::from_py).transpose()?;
+        let name = self.name.clone();
+        let meta = self.meta.clone();
+        let default_ttl = self.default_ttl;
+        let pool = self.pool.clone();
+        let id = id.to_owned();
+
+        asyncio::async_std::future_into_py_with_locals(
+            py,
+            locals.clone(),
+            // Store the current locals in task-local data
+            asyncio::async_std::scope(locals.clone(), async move {
+                let (data, nested_updates) =
+                    nested_updates::extract_nested_updates(&name, &meta, &id, data)?;
+                let mut records = utils::prepare_record_to_insert(
+                    &name,
+                    &meta.schema,
+                    &data,
+                    &meta.primary_key_field,
+                    Some(&id),
+                    &meta.id_generator,
+                    meta.case_insensitive_keys,
+                )?;
+                // the record being updated keeps its version as only some of its fields may be updated
+                let nested_records = records.len() - 1;
+                versioning::stamp_versions(&mut records[..nested_records], &meta.record_versions);
+
+                let ttl = utils::resolve_ttl(ttl, default_ttl, &meta.retention);
+
+                async_utils::insert_records_async(
+                    &pool,
+                    &name,
+                    &meta,
+                    &records,
+                    &nested_updates,
+                    &ttl,
+                )
+                .await?;
+                meta.metrics.record("update_one", 1);
+                Ok(())
+            }),
+        )
+    }
+
+    /// Updates the records of the given ids with the provided data, by id, in one network request.
+    /// This is more efficient than repeatedly calling update_one()
+    #[args(updates, ttl = "None")]
+    pub(crate) fn update_many<'a>(
+        &self,
+        py: Python<'a>,
+        updates: &PyDict,
+        ttl: Option<&PyAny>,
+    ) -> PyResult<&'a PyAny> {
+        let locals = asyncio::async_std::get_current_locals(py)?;
+        let ttl = ttl.map(Expiry::from_py).transpose()?;
+        let updates = updates
+            .iter()
+            .map(|(id, data)| Ok((id.extract::<String>()?, data.into())))
+            .collect::<PyResult<Vec<(String, Py<PyAny>)>>>()?;
+        let name = self.name.clone();
+        let meta = self.meta.clone();
+        let default_ttl = self.default_ttl;
+        let pool = self.pool.clone();
+
+        asyncio::async_std::future_into_py_with_locals(
+            py,
+            locals.clone(),
+            // Store the current locals in task-local data
+            asyncio::async_std::scope(locals.clone(), async move {
+                let number_of_updates = updates.len();
+                let (records, nested_updates) =
+                    utils::prepare_records_to_update(&name, &meta, updates)?;
+                let ttl = utils::resolve_ttl(ttl, default_ttl, &meta.retention);
+
+                async_utils::insert_records_async(
+                    &pool,
+                    &name,
+                    &meta,
+                    &records,
+                    &nested_updates,
+                    &ttl,
+                )
+                .await?;
+                meta.metrics.record("update_many", number_of_updates);
+                Ok(())
+            }),
+        )
+    }
+
+    /// Starts maintaining a HyperLogLog of the distinct values of the given field on every write,
+    /// populating it with the values of the records already in this collection, scanning at most
+    /// `max_ops_per_sec` records per second if given
+    #[args(field, max_ops_per_sec = "None")]
+    pub(crate) fn track_cardinality<'a>(
+        &self,
+        py: Python<'a>,
+        field: &str,
+        max_ops_per_sec: Option<f64>,
+    ) -> PyResult<&'a PyAny> {
+        self.meta.ensure_scalar_field(field)?;
+        let mut throttle = Throttle::new(max_ops_per_sec)?;
+
+        let locals = asyncio::async_std::get_current_locals(py)?;
+        let name = self.name.clone();
+        let meta = self.meta.clone();
+        let pool = self.pool.clone();
+        let field = field.to_owned();
+
+        asyncio::async_std::future_into_py_with_locals(
+            py,
+            locals.clone(),
+            // Store the current locals in task-local data
+            asyncio::async_std::scope(locals.clone(), async move {
+                async_utils::track_cardinality_async(&pool, &name, &meta, &field, &mut throttle)
+                    .await
+            }),
+        )
+    }
+
+    /// Returns the approximate number of distinct values the given field has had in this collection
+    pub(crate) fn approx_distinct<'a>(&self, py: Python<'a>, field: &str) -> PyResult<&'a PyAny> {
+        self.meta.ensure_scalar_field(field)?;
+
+        let locals = asyncio::async_std::get_current_locals(py)?;
+        let name = self.name.clone();
+        let pool = self.pool.clone();
+        let field = field.to_owned();
+
+        asyncio::async_std::future_into_py_with_locals(
+            py,
+            locals.clone(),
+            // Store the current locals in task-local data
+            asyncio::async_std::scope(locals.clone(), async move {
+                async_utils::count_distinct_async(&pool, &name, &field).await
+            }),
+        )
+    }
+
+    /// Returns the distinct values of the given field in this collection, in no particular order. They are
+    /// collected by a script as it scans the collection in redis, so only the distinct values are sent back
+    pub(crate) fn distinct<'a>(&self, py: Python<'a>, field: String) -> PyResult<&'a PyAny> {
+        self.meta.ensure_scalar_field(&field)?;
+
+        let locals = asyncio::async_std::get_current_locals(py)?;
+        let name = self.name.clone();
+        let pool = self.pool.clone();
+        let meta = self.meta.clone();
+
+        asyncio::async_std::future_into_py_with_locals(
+            py,
+            locals.clone(),
+            // Store the current locals in task-local data
+            asyncio::async_std::scope(locals.clone(), async move {
+                meta.metrics.record_result(
+                    "distinct",
+                    async_utils::get_distinct_values_async(&pool, &name, &meta, &field).await,
+                )
+            }),
+        )
+    }
+
+    /// Returns the sum, average, minimum or maximum, as op is "sum", "avg", "min" or "max", of the values of
+    /// the given int or float field in this collection. They are aggregated by a script as it scans the collection
+    /// in redis, so only the result is sent back. Records whose value of the field is None are left out
+    pub(crate) fn aggregate<'a>(
+        &self,
+        py: Python<'a>,
+        field: String,
+        op: &str,
+    ) -> PyResult<&'a PyAny> {
+        aggregations::ensure_numeric_field(&self.meta, &field)?;
+        let op = AggregateOp::from_name(op)?;
+
+        let locals = asyncio::async_std::get_current_locals(py)?;
+        let name = self.name.clone();
+        let pool = self.pool.clone();
+        let meta = self.meta.clone();
+
+        asyncio::async_std::future_into_py_with_locals(
+            py,
+            locals.clone(),
+            // Store the current locals in task-local data
+            asyncio::async_std::scope(locals.clone(), async move {
+                let (count, result) =
+                    async_utils::aggregate_field_async(&pool, &name, &field, op).await?;
+                meta.metrics.record("aggregate", count);
+                aggregations::to_py(meta.schema.get_type(&field).unwrap(), op, &result)
+            }),
+        )
+    }
+
+    /// Returns a dictionary of each value of the given field in this collection to the number of records
+    /// that have it e.g. to get a histogram of statuses. The records are counted by a script as it scans the
+    /// collection in redis, so only the counts are sent back
+    pub(crate) fn count_by<'a>(&self, py: Python<'a>, field: String) -> PyResult<&'a PyAny> {
+        aggregations::ensure_groupable_field(&self.meta, &field)?;
+
+        let locals = asyncio::async_std::get_current_locals(py)?;
+        let name = self.name.clone();
+        let pool = self.pool.clone();
+        let meta = self.meta.clone();
+
+        asyncio::async_std::future_into_py_with_locals(
+            py,
+            locals.clone(),
+            // Store the current locals in task-local data
+            asyncio::async_std::scope(locals.clone(), async move {
+                let counts = async_utils::count_by_field_async(&pool, &name, &field).await?;
+                meta.metrics
+                    .record("count_by", counts.iter().map(|(_, count)| count).sum());
+                aggregations::counts_to_py(&name, meta.schema.get_type(&field).unwrap(), &counts)
+            }),
+        )
+    }
+
+    /// Returns the number of records of this collection whose fields are equal to the given values
+    /// e.g. `count_where(author="Jane")`, or of all its records if none is given. The records are counted by
+    /// a script as it scans the collection in redis, so only the count is sent back
+    #[args(filters = "**")]
+    pub(crate) fn count_where<'a>(
+        &self,
+        py: Python<'a>,
+        filters: Option<&PyDict>,
+    ) -> PyResult<&'a PyAny> {
+        let filters = filters.map(|v| v.extract()).transpose()?;
+        let filter = self.meta.extract_filter(filters)?;
+
+        let locals = asyncio::async_std::get_current_locals(py)?;
+        let name = self.name.clone();
+        let pool = self.pool.clone();
+        let meta = self.meta.clone();
+
+        asyncio::async_std::future_into_py_with_locals(
+            py,
+            locals.clone(),
+            // Store the current locals in task-local data
+            asyncio::async_std::scope(locals.clone(), async move {
+                let count =
+                    async_utils::count_matching_records_async(&pool, &name, &filter).await?;
+                meta.metrics.record("count_where", count);
+                Ok(count)
+            }),
+        )
+    }
+
+    /// Starts maintaining an index of the records of this collection by the values of the given int, float,
+    /// date or datetime field on every write, building it from the records already in this collection and
+    /// scanning at most `max_ops_per_sec` records per second if given. It returns the number of records indexed
+    #[args(field, max_ops_per_sec = "None")]
+    pub(crate) fn create_index<'a>(
+        &self,
+        py: Python<'a>,
+        field: &str,
+        max_ops_per_sec: Option<f64>,
+    ) -> PyResult<&'a PyAny> {
+        range_indexes::ensure_indexable_field(&self.meta, field)?;
+        let mut throttle = Throttle::new(max_ops_per_sec)?;
+
+        let locals = asyncio::async_std::get_current_locals(py)?;
+        let pool = self.pool.clone();
+        let name = self.name.clone();
+        let meta = self.meta.clone();
+        let field = field.to_owned();
+
+        asyncio::async_std::future_into_py_with_locals(
+            py,
+            locals.clone(),
+            // Store the current locals in task-local data
+            asyncio::async_std::scope(locals.clone(), async move {
+                async_utils::build_range_index_async(&pool, &name, &meta, &field, &mut throttle)
+                    .await
+            }),
+        )
+    }
+
+    /// Starts maintaining an index of the records of this collection that have no value for the given field,
+    /// i.e. that lack it or whose value is None, on every write, building it from the records already in this
+    /// collection and scanning at most `max_ops_per_sec` records per second if given. It returns the number of
+    /// records without a value, which are found with find(field__isnull=True) e.g. to backfill the field
+    #[args(field, max_ops_per_sec = "None")]
+    pub(crate) fn create_null_index<'a>(
+        &self,
+        py: Python<'a>,
+        field: &str,
+        max_ops_per_sec: Option<f64>,
+    ) -> PyResult<&'a PyAny> {
+        null_indexes::ensure_indexable_field(&self.meta, field)?;
+        let mut throttle = Throttle::new(max_ops_per_sec)?;
+
+        let locals = asyncio::async_std::get_current_locals(py)?;
+        let pool = self.pool.clone();
+        let name = self.name.clone();
+        let meta = self.meta.clone();
+        let field = field.to_owned();
+
+        asyncio::async_std::future_into_py_with_locals(
+            py,
+            locals.clone(),
+            // Store the current locals in task-local data
+            asyncio::async_std::scope(locals.clone(), async move {
+                async_utils::build_null_index_async(&pool, &name, &meta, &field, &mut throttle)
+                    .await
+            }),
+        )
+    }
+
+    /// Returns the records of this collection whose values of the given indexed field are between `lo` and `hi`,
+    /// inclusive, in the order of those values. A bound that is None leaves the range open on its side
+    pub(crate) fn find_between<'a>(
+        &self,
+        py: Python<'a>,
+        field: &str,
+        lo: Option<Py<PyAny>>,
+        hi: Option<Py<PyAny>>,
+    ) -> PyResult<&'a PyAny> {
+        self.meta.ensure_range_indexable(field)?;
+        let min = range_indexes::bound_to_score(&self.meta, field, lo, "-inf")?;
+        let max = range_indexes::bound_to_score(&self.meta, field, hi, "+inf")?;
+
+        let locals = asyncio::async_std::get_current_locals(py)?;
+        let pool = self.pool.clone();
+        let name = self.name.clone();
+        let meta = self.meta.clone();
+        let field = field.to_owned();
+
+        asyncio::async_std::future_into_py_with_locals(
+            py,
+            locals.clone(),
+            // Store the current locals in task-local data
+            asyncio::async_std::scope(locals.clone(), async move {
+                async_utils::refresh_registered_fields_async(&pool, &name, &meta).await?;
+                meta.ensure_range_index(&field)?;
+                let ids =
+                    async_utils::get_ids_in_range_async(&pool, &name, &field, &min, &max).await?;
+                if ids.is_empty() {
+                    meta.metrics.record("find_between", 0);
+                    return Ok(vec![]);
+                }
+
+                meta.metrics.record_result(
+                    "find_between",
+                    async_utils::get_records_by_id_async(&pool, &name, &meta, &ids).await,
+                )
+            }),
+        )
+    }
+
+    /// Returns a page of at most `limit` records of this collection in the order of their values of the given
+    /// indexed field, ties being in the order of their ids, starting after the record whose value and id are
+    /// `after_value` and `after_id`, usually the last record of the previous page. Unlike skipping records,
+    /// getting a page costs the same however deep it is, and records inserted meanwhile do not shift the pages
+    #[args(field, limit = 20, after_value = "None", after_id = "None")]
+    pub(crate) fn get_page<'a>(
+        &self,
+        py: Python<'a>,
+        field: &str,
+        limit: usize,
+        after_value: Option<Py<PyAny>>,
+        after_id: Option<&str>,
+    ) -> PyResult<&'a PyAny> {
+        self.meta.ensure_range_indexable(field)?;
+        let limit = pagination::validate_browse_limit(limit)?;
+        let cursor = KeysetCursor::new(&self.meta, field, after_value, after_id)?;
+
+        let locals = asyncio::async_std::get_current_locals(py)?;
+        let pool = self.pool.clone();
+        let name = self.name.clone();
+        let meta = self.meta.clone();
+        let field = field.to_owned();
+
+        asyncio::async_std::future_into_py_with_locals(
+            py,
+            locals.clone(),
+            // Store the current locals in task-local data
+            asyncio::async_std::scope(locals.clone(), async move {
+                async_utils::refresh_registered_fields_async(&pool, &name, &meta).await?;
+                meta.ensure_range_index(&field)?;
+                let ids =
+                    async_utils::get_page_ids_async(&pool, &name, &field, &cursor, limit).await?;
+                if ids.is_empty() {
+                    meta.metrics.record("get_page", 0);
+                    return Ok(vec![]);
+                }
+
+                meta.metrics.record_result(
+                    "get_page",
+                    async_utils::get_records_by_id_async(&pool, &name, &meta, &ids).await,
+                )
+            }),
+        )
+    }
+
+    /// Returns the `n` records of this collection inserted last, latest first. Records that expired,
+    /// or were removed otherwise than with the methods of this collection, are left out
+    #[args(n = 10)]
+    pub(crate) fn get_latest<'a>(&self, py: Python<'a>, n: usize) -> PyResult<&'a PyAny> {
+        self.get_by_insertion_order(py, "get_latest", n, true)
+    }
+
+    /// Returns the `n` records of this collection inserted first, oldest first. Records that expired,
+    /// or were removed otherwise than with the methods of this collection, are left out
+    #[args(n = 10)]
+    pub(crate) fn get_oldest<'a>(&self, py: Python<'a>, n: usize) -> PyResult<&'a PyAny> {
+        self.get_by_insertion_order(py, "get_oldest", n, false)
+    }
+
+    /// Returns the `k` records of this collection whose values of the given vector field are nearest to
+    /// `query_vector` by cosine distance, nearest first. It needs the RediSearch module of redis, and creates
+    /// the index of the field the first time it is searched, after which records already saved are indexed
+    /// in the background
+    #[args(field, query_vector, k = 10)]
+    pub(crate) fn knn<'a>(
+        &self,
+        py: Python<'a>,
+        field: &str,
+        query_vector: Vec<f32>,
+        k: usize,
+    ) -> PyResult<&'a PyAny> {
+        let dimension = vectors::ensure_vector_field(&self.meta, field)?;
+        let vector = vectors::pack_query_vector(field, dimension, query_vector)?;
+
+        let locals = asyncio::async_std::get_current_locals(py)?;
+        let pool = self.pool.clone();
+        let name = self.name.clone();
+        let meta = self.meta.clone();
+        let field = field.to_owned();
+
+        asyncio::async_std::future_into_py_with_locals(
+            py,
+            locals.clone(),
+            // Store the current locals in task-local data
+            asyncio::async_std::scope(locals.clone(), async move {
+                if k == 0 {
+                    meta.metrics.record("knn", 0);
+                    return Ok(vec![]);
+                }
+
+                let ids =
+                    async_utils::knn_async(&pool, &name, &field, dimension, &vector, k).await?;
+                if ids.is_empty() {
+                    meta.metrics.record("knn", 0);
+                    return Ok(vec![]);
+                }
+
+                meta.metrics.record_result(
+                    "knn",
+                    async_utils::get_records_by_id_async(&pool, &name, &meta, &ids).await,
+                )
+            }),
+        )
+    }
+
+    /// Measures the memory used in redis by `sample` records of this collection picked at random,
+    /// estimating from them that of the whole collection and listing the `top` largest of them
+    #[args(sample = 1000, top = 10)]
+    pub(crate) fn memory_usage<'a>(
+        &self,
+        py: Python<'a>,
+        sample: usize,
+        top: usize,
+    ) -> PyResult<&'a PyAny> {
+        if sample == 0 {
+            return Err(PyValueError::new_err("sample should be at least 1"));
+        }
+
+        let locals = asyncio::async_std::get_current_locals(py)?;
+        let name = self.name.clone();
+        let pool = self.pool.clone();
+
+        asyncio::async_std::future_into_py_with_locals(
+            py,
+            locals.clone(),
+            // Store the current locals in task-local data
+            asyncio::async_std::scope(locals.clone(), async move {
+                async_utils::measure_memory_usage_async(&pool, &name, sample)
+                    .await?
+                    .to_py(top)
+            }),
+        )
+    }
+
+    /// Trains a zstd dictionary on the string values of `sample` records of this collection picked at random,
+    /// saving it in redis and compressing the string fields of the records written from then on with it.
+    /// Records written before are left as they are. It returns the statistics of the training
+    #[args(sample = 1000)]
+    pub(crate) fn train_compression<'a>(
+        &self,
+        py: Python<'a>,
+        sample: usize,
+    ) -> PyResult<&'a PyAny> {
+        if sample == 0 {
+            return Err(PyValueError::new_err("sample should be at least 1"));
+        }
+
+        let locals = asyncio::async_std::get_current_locals(py)?;
+        let name = self.name.clone();
+        let meta = self.meta.clone();
+        let pool = self.pool.clone();
+
+        asyncio::async_std::future_into_py_with_locals(
+            py,
+            locals.clone(),
+            // Store the current locals in task-local data
+            asyncio::async_std::scope(locals.clone(), async move {
+                async_utils::train_compression_async(&pool, &name, &meta, sample)
+                    .await?
+                    .to_py()
+            }),
+        )
+    }
+
+    /// Loads the compression dictionaries of this collection saved in redis e.g. after a restart, so that
+    /// the values compressed with them can be read and new values are compressed with the latest one.
+    /// It returns whether a dictionary was trained for this collection
+    pub(crate) fn load_compression<'a>(&self, py: Python<'a>) -> PyResult<&'a PyAny> {
+        let locals = asyncio::async_std::get_current_locals(py)?;
+        let name = self.name.clone();
+        let meta = self.meta.clone();
+        let pool = self.pool.clone();
+
+        asyncio::async_std::future_into_py_with_locals(
+            py,
+            locals.clone(),
+            // Store the current locals in task-local data
+            asyncio::async_std::scope(locals.clone(), async move {
+                async_utils::load_compression_async(&pool, &name, &meta).await
+            }),
+        )
+    }
+
+    /// Returns the `top` most accessed ids of this collection, hottest first, with their estimated number
+    /// of recent accesses. The accesses are counted only if hot_key_sampling was set in create_collection()
+    #[args(top = 20)]
+    pub(crate) fn hot_keys(&self, top: usize) -> PyResult<Vec<(String, f64)>> {
+        Ok(self.meta.get_hot_keys()?.top(top))
+    }
+
+    /// Atomically increments the given counter field of the record of the given id by the given amount,
+    /// returning the new value
+    #[args(id, field, amount = "None")]
+    pub(crate) fn increment<'a>(
+        &self,
+        py: Python<'a>,
+        id: &str,
+        field: &str,
+        amount: Option<&PyAny>,
+    ) -> PyResult<&'a PyAny> {
+        self.meta.record_accesses([id]);
+        let cmd = utils::generate_increment_cmd(&self.name, &self.meta, id, field, amount)?;
+        let locals = asyncio::async_std::get_current_locals(py)?;
+        let pool = self.pool.clone();
+        let name = self.name.clone();
+        let meta = self.meta.clone();
+        let field = field.to_owned();
+
+        asyncio::async_std::future_into_py_with_locals(
+            py,
+            locals.clone(),
+            // Store the current locals in task-local data
+            asyncio::async_std::scope(locals.clone(), async move {
+                let value =
+                    async_utils::increment_counter_async(&pool, &name, &meta, &field, &cmd).await?;
+                meta.metrics.record("increment", 1);
+                Ok(value)
+            }),
+        )
+    }
+
+    /// Deletes the records that correspond to the given ids for this collection, returning the number of them
+    /// that existed. If strict, it raises a KeyError if any of them did not exist, after deleting the others
+    #[args(ids, strict = "false")]
+    pub(crate) fn delete_many<'a>(
+        &self,
+        py: Python<'a>,
+        ids: Vec<String>,
+        strict: bool,
+    ) -> PyResult<&'a PyAny> {
+        let locals = asyncio::async_std::get_current_locals(py)?;
+        let pool = self.pool.clone();
+        let name = self.name.clone();
+        let meta = self.meta.clone();
+
+        asyncio::async_std::future_into_py_with_locals(
+            py,
+            locals.clone(),
+            // Store the current locals in task-local data
+            asyncio::async_std::scope(locals.clone(), async move {
+                async_utils::refresh_registered_fields_async(&pool, &name, &meta).await?;
+                let pipe = utils::generate_delete_pipeline(&name, &meta, &ids);
+                let deleted = async_utils::remove_records_async(&pool, &meta, &pipe).await?;
+                meta.metrics.record("delete_many", ids.len());
+                utils::to_deleted_count(&name, &meta, &ids, deleted, strict)
+            }),
+        )
+    }
+
+    /// Deletes the records of this collection whose fields are equal to the given values e.g.
+    /// `delete_where(author="Jane")`, returning the number deleted. The records are found and deleted in redis
+    /// by one script that scans the collection, so none of them is read first
+    #[args(field_filters = "**")]
+    pub(crate) fn delete_where<'a>(
+        &self,
+        py: Python<'a>,
+        field_filters: Option<&PyDict>,
+    ) -> PyResult<&'a PyAny> {
+        let locals = asyncio::async_std::get_current_locals(py)?;
+        let filter = conditional_writes::extract_field_filters(&self.meta, field_filters)?;
+        let name = self.name.clone();
+        let pool = self.pool.clone();
+        let meta = self.meta.clone();
+
+        asyncio::async_std::future_into_py_with_locals(
+            py,
+            locals.clone(),
+            // Store the current locals in task-local data
+            asyncio::async_std::scope(locals.clone(), async move {
+                let deleted =
+                    async_utils::delete_matching_records_async(&pool, &name, &meta, &filter)
+                        .await?;
+                meta.metrics.record("delete_where", deleted);
+                Ok(deleted)
+            }),
+        )
+    }
+
+    /// Deletes all the records of this collection, with their indexes and the other data derived from them,
+    /// leaving the other collections of the redis database as they are, unlike `AsyncStore.clear()`. The keys
+    /// are scanned and deleted a batch at a time, at most `max_ops_per_sec` keys per second if given.
+    /// It returns the number of records deleted
+    #[args(max_ops_per_sec = "None")]
+    pub(crate) fn clear<'a>(
+        &self,
+        py: Python<'a>,
+        max_ops_per_sec: Option<f64>,
+    ) -> PyResult<&'a PyAny> {
+        let mut throttle = Throttle::new(max_ops_per_sec)?;
+
+        let locals = asyncio::async_std::get_current_locals(py)?;
+        let name = self.name.clone();
+        let pool = self.pool.clone();
+        let meta = self.meta.clone();
+
+        asyncio::async_std::future_into_py_with_locals(
+            py,
+            locals.clone(),
+            // Store the current locals in task-local data
+            asyncio::async_std::scope(locals.clone(), async move {
+                let deleted =
+                    async_utils::clear_collection_async(&pool, &name, &meta, &mut throttle).await?;
+                meta.metrics.record("clear", deleted as usize);
+                Ok(deleted)
+            }),
+        )
+    }
+
+    /// Deletes the records of this collection whose ids match the given glob pattern e.g. "order-2023-*",
+    /// returning the number deleted. The keys are scanned and the matching records deleted a batch at a time,
+    /// at most `max_ops_per_sec` keys per second if given
+    #[args(id_pattern, max_ops_per_sec = "None")]
+    pub(crate) fn delete_matching<'a>(
+        &self,
+        py: Python<'a>,
+        id_pattern: &str,
+        max_ops_per_sec: Option<f64>,
+    ) -> PyResult<&'a PyAny> {
+        if id_pattern.is_empty() {
+            return Err(PyValueError::new_err("id_pattern should not be empty"));
+        }
+
+        let mut throttle = Throttle::new(max_ops_per_sec)?;
+
+        let locals = asyncio::async_std::get_current_locals(py)?;
+        let name = self.name.clone();
+        let pool = self.pool.clone();
+        let meta = self.meta.clone();
+        let id_pattern = id_pattern.to_owned();
+
+        asyncio::async_std::future_into_py_with_locals(
+            py,
+            locals.clone(),
+            // Store the current locals in task-local data
+            asyncio::async_std::scope(locals.clone(), async move {
+                let deleted = async_utils::delete_records_by_id_pattern_async(
+                    &pool,
+                    &name,
+                    &meta,
+                    &id_pattern,
+                    &mut throttle,
+                )
+                .await?;
+                meta.metrics.record("delete_matching", deleted);
+                Ok(deleted)
+            }),
+        )
+    }
+
+    /// Makes the given changes, the new values of some non-nested fields, to the records of this collection
+    /// whose fields are equal to the values in the given filters e.g.
+    /// `update_where({"author": "Jane"}, {"in_stock": False})`, returning the number updated. The records are
+    /// found and updated in redis by one script that scans the collection
+    pub(crate) fn update_where<'a>(
+        &self,
+        py: Python<'a>,
+        filters: HashMap<String, Py<PyAny>>,
+        changes: HashMap<String, Py<PyAny>>,
+    ) -> PyResult<&'a PyAny> {
+        let locals = asyncio::async_std::get_current_locals(py)?;
+        let filter = conditional_writes::extract_filters(&self.meta, filters)?;
+        let changes = conditional_writes::extract_changes(&self.name, &self.meta, changes)?;
+        let name = self.name.clone();
+        let pool = self.pool.clone();
+        let meta = self.meta.clone();
+
+        asyncio::async_std::future_into_py_with_locals(
+            py,
+            locals.clone(),
+            // Store the current locals in task-local data
+            asyncio::async_std::scope(locals.clone(), async move {
+                let updated = async_utils::update_matching_records_async(
+                    &pool, &name, &meta, &filter, &changes,
+                )
+                .await?;
+                meta.metrics.record("update_where", updated);
+                Ok(updated)
+            }),
+        )
+    }
+
+    /// Returns the events in the change stream of this collection that come after the event
+    /// of the given id, oldest first
+    #[args(after = "\"0-0\"", count = 100)]
+    pub(crate) fn get_changes<'a>(
+        &self,
+        py: Python<'a>,
+        after: &str,
+        count: usize,
+    ) -> PyResult<&'a PyAny> {
+        self.meta.ensure_tracks_changes()?;
+
+        let locals = asyncio::async_std::get_current_locals(py)?;
+        let pool = self.pool.clone();
+        let name = self.name.clone();
+        let meta = self.meta.clone();
+        let after = after.to_owned();
+
+        asyncio::async_std::future_into_py_with_locals(
+            py,
+            locals.clone(),
+            // Store the current locals in task-local data
+            asyncio::async_std::scope(locals.clone(), async move {
+                async_utils::get_changes_async(&pool, &name, &meta, &after, count).await
+            }),
+        )
+    }
+
+    /// Checks whether the record of the given id exists in this collection
+    pub(crate) fn exists<'a>(&self, py: Python<'a>, id: &str) -> PyResult<&'a PyAny> {
+        self.meta.record_accesses([id]);
+        let locals = asyncio::async_std::get_current_locals(py)?;
+        let pool = self.pool.clone();
+        let name = self.name.clone();
+        let meta = self.meta.clone();
+        let id = id.to_owned();
+
+        asyncio::async_std::future_into_py_with_locals(
+            py,
+            locals.clone(),
+            // Store the current locals in task-local data
+            asyncio::async_std::scope(locals.clone(), async move {
+                async_utils::record_exists_async(&pool, &name, &meta, &id).await
+            }),
+        )
+    }
+
+    /// Checks whether the records of the given ids exist in this collection, returning a boolean per id in order
+    pub(crate) fn exists_many<'a>(&self, py: Python<'a>, ids: Vec<String>) -> PyResult<&'a PyAny> {
+        self.meta.record_accesses(ids.iter().map(String::as_str));
+        let locals = asyncio::async_std::get_current_locals(py)?;
+        let pool = self.pool.clone();
+        let name = self.name.clone();
+        let meta = self.meta.clone();
+
+        asyncio::async_std::future_into_py_with_locals(
+            py,
+            locals.clone(),
+            // Store the current locals in task-local data
+            asyncio::async_std::scope(locals.clone(), async move {
+                async_utils::records_exist_async(&pool, &name, &meta, &ids).await
+            }),
+        )
+    }
+
+    /// Gets the record that corresponds to the given id.
+    /// If profile is true, it returns the timing breakdown of the call as (record, profile)
+    #[args(id, profile = "false")]
+    pub(crate) fn get_one<'a>(
+        &self,
+        py: Python<'a>,
+        id: &str,
+        profile: bool,
+    ) -> PyResult<&'a PyAny> {
+        self.meta.record_accesses([id]);
+        let locals = asyncio::async_std::get_current_locals(py)?;
+        let pool = self.pool.clone();
+        let name = self.name.clone();
+        let meta = self.meta.clone();
+        let id = id.to_owned();
+        let profile = Profile::start(profile);
+
+        asyncio::async_std::future_into_py_with_locals(
+            py,
+            locals.clone(),
+            // Store the current locals in task-local data
+            asyncio::async_std::scope(locals.clone(), async move {
+                let record = profiling::run_async(&profile, async {
+                    let might_exist = profiling::measure_async(
+                        Phase::Network,
+                        async_utils::might_exist_async(&pool, &name, &meta, &id),
+                    )
+                    .await?;
+                    if !might_exist {
+                        meta.metrics.record("get_one", 0);
+                        return Ok(None);
+                    }
+
+                    let mut records: Vec<Py<PyAny>> = meta.metrics.record_result(
+                        "get_one",
+                        async_utils::get_records_by_id_async(
+                            &pool,
+                            &name,
+                            &meta,
+                            &vec![id.clone()],
+                        )
+                        .await,
+                    )?;
+                    Ok::<_, PyErr>(records.pop())
+                })
+                .await?;
+                profiling::attach(profile, record)
+            }),
+        )
+    }
+
+    /// Returns the record of this collection that the given reference i.e. the value of a field annotated with
+    /// `Reference[Model]` in another collection, refers to, or None if there is no such record or no reference.
+    /// Referenced records are thus only read when they are needed, unlike nested records
+    pub(crate) fn resolve<'a>(
+        &self,
+        py: Python<'a>,
+        reference: Option<&PyAny>,
+    ) -> PyResult<&'a PyAny> {
+        if let Some(reference) = reference {
+            return self.get_one(
+                py,
+                &utils::extract_reference_id(&self.name, reference)?,
+                false,
+            );
+        }
+
+        let locals = asyncio::async_std::get_current_locals(py)?;
+        asyncio::async_std::future_into_py_with_locals(
+            py,
+            locals.clone(),
+            // Store the current locals in task-local data
+            asyncio::async_std::scope(locals.clone(), async move {
+                Python::with_gil(|py| Ok(py.None()))
+            }),
+        )
+    }
+
+    /// Returns the records of the given child collection whose foreign key field `fk_field` holds the given id
+    /// of a record of this collection e.g. all the orders of a user, in no particular order. They are found with
+    /// the index of the foreign key, which is updated on every write to the child collection, so the cost of
+    /// the call depends on the number of children rather than on the size of the child collection
+    pub(crate) fn children_of<'a>(
+        &self,
+        py: Python<'a>,
+        parent_id: String,
+        child_collection: PyRef<AsyncCollection>,
+        fk_field: String,
+    ) -> PyResult<&'a PyAny> {
+        child_collection.meta.ensure_foreign_key(&fk_field)?;
+
+        let locals = asyncio::async_std::get_current_locals(py)?;
+        let pool = child_collection.pool.clone();
+        let name = child_collection.name.clone();
+        let child_meta = child_collection.meta.clone();
+
+        asyncio::async_std::future_into_py_with_locals(
+            py,
+            locals.clone(),
+            // Store the current locals in task-local data
+            asyncio::async_std::scope(locals.clone(), async move {
+                let ids = async_utils::get_ids_by_value_async(
+                    &pool,
+                    &name,
+                    &child_meta,
+                    &[fk_field],
+                    &[parent_id],
+                )
+                .await?;
+                if ids.is_empty() {
+                    child_meta.metrics.record("children_of", 0);
+                    return Ok(vec![]);
+                }
+
+                child_meta.metrics.record_result(
+                    "children_of",
+                    async_utils::get_records_by_id_async(&pool, &name, &child_meta, &ids).await,
+                )
+            }),
+        )
+    }
+
+    /// Returns the records of this collection whose indexed field holds the given value, in no particular order.
+    /// They are found with the index of the field, set with indexed_fields in create_collection(), so the cost
+    /// of the call depends on the number of matching records rather than on the size of the collection
+    pub(crate) fn find_by<'a>(
+        &self,
+        py: Python<'a>,
+        field: String,
+        value: Py<PyAny>,
+    ) -> PyResult<&'a PyAny> {
+        self.meta.ensure_indexed_field(&field)?;
+
+        let value =
+            utils::field_value_to_redis(self.meta.schema.get_type(&field).unwrap(), &value)?;
+        let locals = asyncio::async_std::get_current_locals(py)?;
+        let pool = self.pool.clone();
+        let name = self.name.clone();
+        let meta = self.meta.clone();
+
+        asyncio::async_std::future_into_py_with_locals(
+            py,
+            locals.clone(),
+            // Store the current locals in task-local data
+            asyncio::async_std::scope(locals.clone(), async move {
+                let ids =
+                    async_utils::get_ids_by_value_async(&pool, &name, &meta, &[field], &[value])
+                        .await?;
+                if ids.is_empty() {
+                    meta.metrics.record("find_by", 0);
+                    return Ok(vec![]);
+                }
+
+                meta.metrics.record_result(
+                    "find_by",
+                    async_utils::get_records_by_id_async(&pool, &name, &meta, &ids).await,
+                )
+            }),
+        )
+    }
+
+    /// Returns the records of this collection whose fields hold the given values e.g. a country and a city,
+    /// in no particular order, using the compound index of the fields set with compound_indexes in create_collection().
+    /// The fields may be given in any order, each value being that of the field at the same position
+    pub(crate) fn find_by_index<'a>(
+        &self,
+        py: Python<'a>,
+        fields: Vec<String>,
+        values: Vec<Py<PyAny>>,
+    ) -> PyResult<&'a PyAny> {
+        let (index, values) =
+            field_indexes::to_index_fields_and_values(&self.meta, &fields, &values)?;
+        let locals = asyncio::async_std::get_current_locals(py)?;
+        let pool = self.pool.clone();
+        let name = self.name.clone();
+        let meta = self.meta.clone();
+
+        asyncio::async_std::future_into_py_with_locals(
+            py,
+            locals.clone(),
+            // Store the current locals in task-local data
+            asyncio::async_std::scope(locals.clone(), async move {
+                let ids = async_utils::get_ids_by_value_async(&pool, &name, &meta, &index, &values)
+                    .await?;
+                if ids.is_empty() {
+                    meta.metrics.record("find_by_index", 0);
+                    return Ok(vec![]);
+                }
+
+                meta.metrics.record_result(
+                    "find_by_index",
+                    async_utils::get_records_by_id_async(&pool, &name, &meta, &ids).await,
+                )
+            }),
+        )
+    }
+
+    /// Returns the records of this collection that match all the given lookups, in no particular order:
+    /// `field=value` for an indexed field holding the value, `field__contains=value` for a list field,
+    /// set with indexed_list_fields in create_collection(), holding the value among its elements
+    /// e.g. find(tags__contains="rust"), and `field__isnull=True` (or False) for a field with a null index,
+    /// made with create_null_index(), that is missing or None (or not). The ids matching the lookups
+    /// are got in one network request, or, if `cache_ttl` is given, from the ids cached by an identical call
+    /// within that time, the records themselves being read afresh
+    #[args(cache_ttl = "None", lookups = "**")]
+    pub(crate) fn find<'a>(
+        &self,
+        py: Python<'a>,
+        cache_ttl: Option<&PyAny>,
+        lookups: Option<&PyDict>,
+    ) -> PyResult<&'a PyAny> {
+        let cache_ttl = cache_ttl.map(Expiry::from_py).transpose()?;
+        let lookups = Lookups::from_py(&self.name, &self.meta, lookups)?;
+        let locals = asyncio::async_std::get_current_locals(py)?;
+        let pool = self.pool.clone();
+        let name = self.name.clone();
+        let meta = self.meta.clone();
+
+        asyncio::async_std::future_into_py_with_locals(
+            py,
+            locals.clone(),
+            // Store the current locals in task-local data
+            asyncio::async_std::scope(locals.clone(), async move {
+                async_utils::refresh_registered_fields_async(&pool, &name, &meta).await?;
+                lookups.ensure_indexes(&meta)?;
+                let ids = match &cache_ttl {
+                    None => async_utils::get_ids_by_lookups_async(&pool, &name, &lookups).await?,
+                    Some(cache_ttl) => {
+                        async_utils::get_cached_ids_by_lookups_async(
+                            &pool, &name, &lookups, cache_ttl,
+                        )
+                        .await?
+                    }
+                };
+                if ids.is_empty() {
+                    meta.metrics.record("find", 0);
+                    return Ok(vec![]);
+                }
+
+                meta.metrics.record_result(
+                    "find",
+                    async_utils::get_records_by_id_async(&pool, &name, &meta, &ids).await,
+                )
+            }),
+        )
+    }
+
+    /// Explains how `find()` gets the ids of the records matching the given lookups, to diagnose slow ones:
+    /// the number of candidates in the index of each lookup, the index chosen to drive the matching i.e. the one
+    /// with the fewest candidates, and whether all the ids are scanned instead as the lookups only exclude
+    /// records. The records themselves are not read
+    #[args(lookups = "**")]
+    pub(crate) fn explain_find<'a>(
+        &self,
+        py: Python<'a>,
+        lookups: Option<&PyDict>,
+    ) -> PyResult<&'a PyAny> {
+        let lookups = Lookups::from_py(&self.name, &self.meta, lookups)?;
+        let locals = asyncio::async_std::get_current_locals(py)?;
+        let pool = self.pool.clone();
+        let name = self.name.clone();
+        let meta = self.meta.clone();
+
+        asyncio::async_std::future_into_py_with_locals(
+            py,
+            locals.clone(),
+            // Store the current locals in task-local data
+            asyncio::async_std::scope(locals.clone(), async move {
+                async_utils::refresh_registered_fields_async(&pool, &name, &meta).await?;
+                lookups.ensure_indexes(&meta)?;
+                let ids = async_utils::get_lookup_candidates_async(&pool, &name, &lookups).await?;
+                lookups.explain(ids)
+            }),
+        )
+    }
+
+    /// Returns the ids of all the records in this collection, scanning its keys without reading the records,
+    /// so that they can be listed cheaply before deciding which to get. Their order is arbitrary
+    pub(crate) fn ids<'a>(&self, py: Python<'a>) -> PyResult<&'a PyAny> {
+        let locals = asyncio::async_std::get_current_locals(py)?;
+        let pool = self.pool.clone();
+        let name = self.name.clone();
+        let meta = self.meta.clone();
+
+        asyncio::async_std::future_into_py_with_locals(
+            py,
+            locals.clone(),
+            // Store the current locals in task-local data
+            asyncio::async_std::scope(locals.clone(), async move {
+                meta.metrics
+                    .record_result("ids", async_utils::get_record_ids_async(&pool, &name).await)
+            }),
+        )
+    }
+
+    /// Returns `n` records of this collection picked at random, or all of them in a random order if there are
+    /// not more than `n`. The ids of all the records are scanned without reading the records, and only the records
+    /// picked are read, e.g. to spot-check the quality of the data of a big collection
+    pub(crate) fn sample<'a>(&self, py: Python<'a>, n: usize) -> PyResult<&'a PyAny> {
+        let locals = asyncio::async_std::get_current_locals(py)?;
+        let pool = self.pool.clone();
+        let name = self.name.clone();
+        let meta = self.meta.clone();
+
+        asyncio::async_std::future_into_py_with_locals(
+            py,
+            locals.clone(),
+            // Store the current locals in task-local data
+            asyncio::async_std::scope(locals.clone(), async move {
+                let ids = verification::pick_random_ids(
+                    async_utils::get_record_ids_async(&pool, &name).await?,
+                    n,
+                );
+                if ids.is_empty() {
+                    meta.metrics.record("sample", 0);
+                    return Ok(vec![]);
+                }
+
+                meta.metrics.record_result(
+                    "sample",
+                    async_utils::get_records_by_id_async(&pool, &name, &meta, &ids).await,
+                )
+            }),
+        )
+    }
+
+    /// Returns all the records found in this collection; returning them as models.
+    /// Records that cannot be read e.g. because they are corrupt, fail the call if on_error is "raise",
+    /// are left out if it is "skip", or are returned separately with their errors as (records, errors)
+    /// if it is "collect". If a filter is given, only the records whose fields are equal to its values are
+    /// returned, the filtering being done in redis. At most `limit` records, or all of them if
+    /// no limit is given, are returned after skipping the first `skip` records, in the order in which they are scanned,
+    /// or in the order of their values of the field `order_by`, descending if desc, the records without a value
+    /// for it coming last. If profile is true, it returns the timing breakdown of the call as (records, profile)
+    #[args(
+        on_error = "\"raise\"",
+        filter = "None",
+        skip = 0,
+        limit = "None",
+        profile = "false",
+        order_by = "None",
+        desc = "false"
+    )]
+    #[allow(clippy::too_many_arguments)]
+    pub(crate) fn get_all<'a>(
+        &self,
+        py: Python<'a>,
+        on_error: &str,
+        filter: Option<HashMap<String, Py<PyAny>>>,
+        skip: usize,
+        limit: Option<usize>,
+        profile: bool,
+        order_by: Option<String>,
+        desc: bool,
+    ) -> PyResult<&'a PyAny> {
+        let on_error = OnError::from_name(on_error)?;
+        let filter = self.meta.extract_filter(filter)?;
+        let ordering = Ordering::new(&self.meta, order_by, desc)?;
+        let page = Page::new(skip, limit);
+        let locals = asyncio::async_std::get_current_locals(py)?;
+        let pool = self.pool.clone();
+        let name = self.name.clone();
+        let meta = self.meta.clone();
+        let profile = Profile::start(profile);
+
+        asyncio::async_std::future_into_py_with_locals(
+            py,
+            locals.clone(),
+            // Store the current locals in task-local data
+            asyncio::async_std::scope(locals.clone(), async move {
+                let (records, errors) = profiling::run_async(&profile, async {
+                    match &ordering {
+                        None => {
+                            async_utils::get_all_records_in_collection_async(
+                                &pool, &name, &meta, &filter, page, on_error,
+                            )
+                            .await
+                        }
+                        Some(ordering) => {
+                            async_utils::get_ordered_records_in_collection_async(
+                                &pool, &name, &meta, ordering, &filter, page, on_error,
+                            )
+                            .await
+                        }
+                    }
+                })
+                .await?;
+                meta.metrics.record("get_all", records.len());
+                profiling::attach(profile, on_error.to_py(records, errors)?)
+            }),
+        )
+    }
+
+    /// Takes a snapshot of this collection under the given tag i.e. copies all its records as they currently are,
+    /// in place of any snapshot of the same tag, so that they can be read later with `read_snapshot()` e.g. to debug
+    /// or compare them with later records. Snapshots do not expire and are kept until `delete_snapshot()` is called.
+    /// It returns the number of records in the snapshot. At most `max_ops_per_sec` records are scanned per second if given
+    #[args(max_ops_per_sec = "None")]
+    pub(crate) fn snapshot<'a>(
+        &self,
+        py: Python<'a>,
+        tag: String,
+        max_ops_per_sec: Option<f64>,
+    ) -> PyResult<&'a PyAny> {
+        snapshots::validate_tag(&tag)?;
+        let mut throttle = Throttle::new(max_ops_per_sec)?;
+        let locals = asyncio::async_std::get_current_locals(py)?;
+        let pool = self.pool.clone();
+        let name = self.name.clone();
+        let meta = self.meta.clone();
+
+        asyncio::async_std::future_into_py_with_locals(
+            py,
+            locals.clone(),
+            // Store the current locals in task-local data
+            asyncio::async_std::scope(locals.clone(), async move {
+                let copied =
+                    async_utils::take_snapshot_async(&pool, &name, &tag, &mut throttle).await?;
+                meta.metrics.record("snapshot", copied as usize);
+                Ok(copied)
+            }),
+        )
+    }
+
+    /// Returns the records of the snapshot of the given tag of this collection as models, as they were when
+    /// the snapshot was taken. Only the records of this collection are copied into a snapshot, so their nested
+    /// records are as they currently are. Records that cannot be read are handled as in `get_all()`
+    #[args(on_error = "\"raise\"")]
+    pub(crate) fn read_snapshot<'a>(
+        &self,
+        py: Python<'a>,
+        tag: String,
+        on_error: &str,
+    ) -> PyResult<&'a PyAny> {
+        snapshots::validate_tag(&tag)?;
+        let on_error = OnError::from_name(on_error)?;
+        let locals = asyncio::async_std::get_current_locals(py)?;
+        let pool = self.pool.clone();
+        let name = self.name.clone();
+        let meta = self.meta.clone();
+
+        asyncio::async_std::future_into_py_with_locals(
+            py,
+            locals.clone(),
+            // Store the current locals in task-local data
+            asyncio::async_std::scope(locals.clone(), async move {
+                let (records, errors) = async_utils::get_all_records_in_collection_async(
+                    &pool,
+                    &snapshots::get_key_space(&name, &tag),
+                    &meta,
+                    &[],
+                    Page::default(),
+                    on_error,
+                )
+                .await?;
+                meta.metrics.record("read_snapshot", records.len());
+                on_error.to_py(records, errors)
+            }),
+        )
+    }
+
+    /// Deletes the snapshot of the given tag of this collection, returning the number of records it had
+    pub(crate) fn delete_snapshot<'a>(&self, py: Python<'a>, tag: String) -> PyResult<&'a PyAny> {
+        snapshots::validate_tag(&tag)?;
+        let mut throttle = Throttle::new(None)?;
+        let locals = asyncio::async_std::get_current_locals(py)?;
+        let pool = self.pool.clone();
+        let name = self.name.clone();
+
+        asyncio::async_std::future_into_py_with_locals(
+            py,
+            locals.clone(),
+            // Store the current locals in task-local data
+            asyncio::async_std::scope(locals.clone(), async move {
+                async_utils::delete_snapshot_async(&pool, &name, &tag, &mut throttle).await
+            }),
+        )
+    }
+
+    /// Returns all the records found in this collection as models, reading them concurrently across
+    /// the given number of workers, each on its own connection. The order of the records is arbitrary
+    #[args(workers = 4)]
+    pub(crate) fn parallel_iter<'a>(&self, py: Python<'a>, workers: usize) -> PyResult<&'a PyAny> {
+        if workers == 0 {
+            return Err(PyValueError::new_err("workers should be at least 1"));
+        }
+
+        let locals = asyncio::async_std::get_current_locals(py)?;
+        let pool = self.pool.clone();
+        let name = self.name.clone();
+        let meta = self.meta.clone();
+
+        asyncio::async_std::future_into_py_with_locals(
+            py,
+            locals.clone(),
+            // Store the current locals in task-local data
+            asyncio::async_std::scope(locals.clone(), async move {
+                meta.metrics.record_result(
+                    "parallel_iter",
+                    async_utils::get_all_records_in_parallel_async(&pool, &name, &meta, workers)
+                        .await,
+                )
+            }),
+        )
+    }
+
+    /// Returns the records whose ids are as given for this collection.
+    /// If profile is true, it returns the timing breakdown of the call as (records, profile)
+    #[args(ids, profile = "false")]
+    pub(crate) fn get_many<'a>(
+        &self,
+        py: Python<'a>,
+        ids: Vec<String>,
+        profile: bool,
+    ) -> PyResult<&'a PyAny> {
+        self.meta.record_accesses(ids.iter().map(String::as_str));
+        let locals = asyncio::async_std::get_current_locals(py)?;
+        let pool = self.pool.clone();
+        let name = self.name.clone();
+        let meta = self.meta.clone();
+        let profile = Profile::start(profile);
+
+        asyncio::async_std::future_into_py_with_locals(
+            py,
+            locals.clone(),
+            // Store the current locals in task-local data
+            asyncio::async_std::scope(locals.clone(), async move {
+                let records = meta.metrics.record_result(
+                    "get_many",
+                    profiling::run_async(
+                        &profile,
+                        async_utils::get_records_by_id_async(&pool, &name, &meta, &ids),
+                    )
+                    .await,
+                )?;
+                profiling::attach(profile, records)
+            }),
+        )
+    }
+
+    /// Rewrites the values of the records of this collection that are in known broken or legacy formats
+    /// in the format orredis writes, with the given fixes: "bool_case", "datetime_format" and "none_sentinel",
+    /// or all of them if none are given. It returns the number of records repaired. At most `max_ops_per_sec`
+    /// records are repaired per second if given
+    #[args(fix = "None", max_ops_per_sec = "None")]
+    pub(crate) fn repair<'a>(
+        &self,
+        py: Python<'a>,
+        fix: Option<Vec<String>>,
+        max_ops_per_sec: Option<f64>,
+    ) -> PyResult<&'a PyAny> {
+        let repairs = repairs::extract_repairs(fix)?;
+        let mut throttle = Throttle::new(max_ops_per_sec)?;
+        let locals = asyncio::async_std::get_current_locals(py)?;
+        let pool = self.pool.clone();
+        let name = self.name.clone();
+        let meta = self.meta.clone();
+
+        asyncio::async_std::future_into_py_with_locals(
+            py,
+            locals.clone(),
+            // Store the current locals in task-local data
+            asyncio::async_std::scope(locals.clone(), async move {
+                let repaired =
+                    async_utils::repair_records_async(&pool, &name, &meta, &repairs, &mut throttle)
+                        .await?;
+                meta.metrics.record("repair", repaired as usize);
+                Ok(repaired)
+            }),
+        )
+    }
+
+    /// Removes the fields that are no longer in the model of this collection from its records e.g. after
+    /// schema changes, compacting at most `max_ops_per_sec` records per second if given. It returns an awaitable
+    /// of the number of records compacted, or None if `background` is set, in which case the records are compacted
+    /// in a background task of the store named "compact:<collection>", whose status and error are got with
+    /// `background_tasks()`
+    #[args(max_ops_per_sec = "None", background = "false")]
+    pub(crate) fn compact<'a>(
+        &self,
+        py: Python<'a>,
+        max_ops_per_sec: Option<f64>,
+        background: bool,
+    ) -> PyResult<&'a PyAny> {
+        let mut throttle = Throttle::new(max_ops_per_sec)?;
+        let pool = self.pool.clone();
+        let name = self.name.clone();
+        let meta = self.meta.clone();
+
+        if background {
+            let task_name = compaction::get_task_name(&self.name);
+            self.meta
+                .background_tasks
+                .spawn_native(py, task_name, |runs| async move {
+                    let compacted =
+                        async_utils::compact_records_async(&pool, &name, &meta, &mut throttle)
+                            .await?;
+                    meta.metrics.record("compact", compacted as usize);
+                    runs.fetch_add(1, atomic::Ordering::Relaxed);
+                    Ok(())
+                })?;
+            return Ok(py.None().into_ref(py));
+        }
+
+        let locals = asyncio::async_std::get_current_locals(py)?;
+
+        asyncio::async_std::future_into_py_with_locals(
+            py,
+            locals.clone(),
+            // Store the current locals in task-local data
+            asyncio::async_std::scope(locals.clone(), async move {
+                let compacted =
+                    async_utils::compact_records_async(&pool, &name, &meta, &mut throttle).await?;
+                meta.metrics.record("compact", compacted as usize);
+                Ok(compacted)
+            }),
+        )
+    }
+
+    /// Returns the records whose ids are as given for this collection as dictionaries, with the records
+    /// nested in them also as dictionaries, without constructing any models
+    pub(crate) fn get_many_raw<'a>(&self, py: Python<'a>, ids: Vec<String>) -> PyResult<&'a PyAny> {
+        self.meta.record_accesses(ids.iter().map(String::as_str));
+        let locals = asyncio::async_std::get_current_locals(py)?;
+        let pool = self.pool.clone();
+        let name = self.name.clone();
+        let meta = self.meta.clone();
+
+        asyncio::async_std::future_into_py_with_locals(
+            py,
+            locals.clone(),
+            // Store the current locals in task-local data
+            asyncio::async_std::scope(locals.clone(), async move {
+                meta.metrics.record_result(
+                    "get_many_raw",
+                    async_utils::get_raw_records_by_id_async(&pool, &name, &meta, &ids).await,
+                )
+            }),
+        )
+    }
+
+    /// Returns the record that corresponds to the given id in this collection
+    /// returning it as a dictionary with only the fields specified
+    #[args(id, fields, nested_as_models = "false")]
+    pub(crate) fn get_one_partially<'a>(
+        &self,
+        py: Python<'a>,
+        id: &str,
+        fields: Vec<String>,
+        nested_as_models: bool,
+    ) -> PyResult<&'a PyAny> {
+        self.meta.record_accesses([id]);
+        let locals = asyncio::async_std::get_current_locals(py)?;
+        let pool = self.pool.clone();
+        let name = self.name.clone();
+        let meta = self.meta.clone();
+        let id = id.to_owned();
+
+        asyncio::async_std::future_into_py_with_locals(
+            py,
+            locals.clone(),
+            // Store the current locals in task-local data
+            asyncio::async_std::scope(locals.clone(), async move {
+                let records = async_utils::get_partial_records_by_id_async(
+                    &pool,
+                    &name,
+                    &meta,
+                    &vec![id],
+                    &fields,
+                    nested_as_models,
+                )
+                .await;
+                let mut records: Vec<Py<PyAny>> =
+                    meta.metrics.record_result("get_one_partially", records)?;
+                match records.pop() {
+                    None => Python::with_gil(|py| Ok(py.None())),
+                    Some(record) => Ok(record),
+                }
+            }),
+        )
+    }
+
+    /// Returns the value of the given non-nested field of the record of the given id in this collection,
+    /// or None if the record or the value is not in redis. Only that one value is read, with HGET
+    pub(crate) fn get_field<'a>(
+        &self,
+        py: Python<'a>,
+        id: &str,
+        field: &str,
+    ) -> PyResult<&'a PyAny> {
+        self.meta.ensure_scalar_field(field)?;
+        self.meta.record_accesses([id]);
+        let locals = asyncio::async_std::get_current_locals(py)?;
+        let pool = self.pool.clone();
+        let name = self.name.clone();
+        let meta = self.meta.clone();
+        let id = id.to_owned();
+        let field = field.to_owned();
+
+        asyncio::async_std::future_into_py_with_locals(
+            py,
+            locals.clone(),
+            // Store the current locals in task-local data
+            asyncio::async_std::scope(locals.clone(), async move {
+                let value =
+                    async_utils::get_field_value_async(&pool, &name, &meta, &id, &field).await?;
+                meta.metrics.record("get_field", value.is_some() as usize);
+                Ok(value)
+            }),
+        )
+    }
+
+    /// Retrieves the all records in this collection, only returning the specified fields
+    /// for each given record. At most `limit` records, or all of them if
+    /// no limit is given, are returned after skipping the first `skip` records, in the order in which they are scanned,
+    /// or in the order of their values of the field `order_by`, descending if desc, as in `get_all()`
+    #[args(
+        fields,
+        nested_as_models = "false",
+        skip = 0,
+        limit = "None",
+        order_by = "None",
+        desc = "false"
+    )]
+    #[allow(clippy::too_many_arguments)]
+    pub(crate) fn get_all_partially<'a>(
+        &self,
+        py: Python<'a>,
+        fields: Vec<String>,
+        nested_as_models: bool,
+        skip: usize,
+        limit: Option<usize>,
+        order_by: Option<String>,
+        desc: bool,
+    ) -> PyResult<&'a PyAny> {
+        let ordering = Ordering::new(&self.meta, order_by, desc)?;
+        let page = Page::new(skip, limit);
+        let locals = asyncio::async_std::get_current_locals(py)?;
+        let pool = self.pool.clone();
+        let name = self.name.clone();
+        let meta = self.meta.clone();
+
+        asyncio::async_std::future_into_py_with_locals(
+            py,
+            locals.clone(),
+            // Store the current locals in task-local data
+            asyncio::async_std::scope(locals.clone(), async move {
+                let records = match &ordering {
+                    None => {
+                        async_utils::get_all_partial_records_in_collection_async(
+                            &pool,
+                            &name,
+                            &meta,
+                            &fields,
+                            nested_as_models,
+                            page,
+                        )
+                        .await
+                    }
+                    Some(ordering) => {
+                        async {
+                            let ids = async_utils::get_ordered_ids_async(
+                                &pool,
+                                &name,
+                                &meta,
+                                ordering,
+                                &[],
+                                page,
+                            )
+                            .await?;
+                            async_utils::get_partial_records_by_id_async(
+                                &pool,
+                                &name,
+                                &meta,
+                                &ids,
+                                &fields,
+                                nested_as_models,
+                            )
+                            .await
+                        }
+                        .await
+                    }
+                };
+                meta.metrics.record_result("get_all_partially", records)
+            }),
+        )
+    }
+
+    /// Retrieves the records with the given ids in this collection, only returning
+    /// the specified fields for each record
+    #[args(ids, fields, nested_as_models = "false")]
+    pub(crate) fn get_many_partially<'a>(
+        &self,
+        py: Python<'a>,
+        ids: Vec<String>,
+        fields: Vec<String>,
+        nested_as_models: bool,
+    ) -> PyResult<&'a PyAny> {
+        self.meta.record_accesses(ids.iter().map(String::as_str));
+        let locals = asyncio::async_std::get_current_locals(py)?;
+        let pool = self.pool.clone();
+        let name = self.name.clone();
+        let meta = self.meta.clone();
+
+        asyncio::async_std::future_into_py_with_locals(
+            py,
+            locals.clone(),
+            // Store the current locals in task-local data
+            asyncio::async_std::scope(locals.clone(), async move {
+                let records = async_utils::get_partial_records_by_id_async(
+                    &pool,
+                    &name,
+                    &meta,
+                    &ids,
+                    &fields,
+                    nested_as_models,
+                )
+                .await;
+                meta.metrics.record_result("get_many_partially", records)
+            }),
+        )
+    }
+}
+
+impl AsyncCollection {
+    /// Instantiates a new collection. This is not accessible to python and thus a collection
+    /// cannot be directly instantiated in python
+    pub(crate) fn new(
+        name: String,
+        pool: pools::AsyncRedisPool,
+        meta: store::CollectionMeta,
+        default_ttl: Option<Expiry>,
+    ) -> Self {
+        Self {
+            name,
+            meta,
+            pool,
+            default_ttl,
+        }
+    }
+
+    /// Gets the `n` records of this collection inserted last if `latest`, or else first, for the given operation
+    fn get_by_insertion_order<'a>(
+        &self,
+        py: Python<'a>,
+        op: &'static str,
+        n: usize,
+        latest: bool,
+    ) -> PyResult<&'a PyAny> {
+        self.meta.ensure_tracks_insertion_order()?;
+
+        let locals = asyncio::async_std::get_current_locals(py)?;
+        let pool = self.pool.clone();
+        let name = self.name.clone();
+        let meta = self.meta.clone();
+
+        asyncio::async_std::future_into_py_with_locals(
+            py,
+            locals.clone(),
+            // Store the current locals in task-local data
+            asyncio::async_std::scope(locals.clone(), async move {
+                if n == 0 {
+                    meta.metrics.record(op, 0);
+                    return Ok(vec![]);
+                }
+
+                let ids =
+                    async_utils::get_ids_by_insertion_order_async(&pool, &name, n, latest).await?;
+                if ids.is_empty() {
+                    meta.metrics.record(op, 0);
+                    return Ok(vec![]);
+                }
+
+                meta.metrics.record_result(
+                    op,
+                    async_utils::get_records_by_id_async(&pool, &name, &meta, &ids).await,
+                )
+            }),
+        )
+    }
+}
+
+#[pyclass(subclass)]
+pub(crate) struct AsyncView {
+    pub(crate) source: String,
+    pub(crate) view: ViewDefinition,
+    pub(crate) meta: store::CollectionMeta,
+    pub(crate) pool: pools::AsyncRedisPool,
+}
+
+#[pymethods]
+impl AsyncView {
+    /// Gets the record of the view that corresponds to the given id as a dictionary
+    pub(crate) fn get_one<'a>(&self, py: Python<'a>, id: &str) -> PyResult<&'a PyAny> {
+        let locals = asyncio::async_std::get_current_locals(py)?;
+        let pool = self.pool.clone();
+        let source = self.source.clone();
+        let view = self.view.clone();
+        let meta = self.meta.clone();
+        let id = id.to_owned();
+
+        asyncio::async_std::future_into_py_with_locals(
+            py,
+            locals.clone(),
+            // Store the current locals in task-local data
+            asyncio::async_std::scope(locals.clone(), async move {
+                let mut records: Vec<Py<PyAny>> = async_utils::get_partial_records_by_id_async(
+                    &pool,
+                    &view.get_key_space(&source),
+                    &meta,
+                    &vec![id],
+                    &view.projection,
+                    false,
+                )
+                .await?;
+                match records.pop() {
+                    None => Python::with_gil(|py| Ok(py.None())),
+                    Some(record) => Ok(record),
+                }
+            }),
+        )
+    }
+
+    /// Returns the records of the view whose ids are as given, as dictionaries
+    pub(crate) fn get_many<'a>(&self, py: Python<'a>, ids: Vec<String>) -> PyResult<&'a PyAny> {
+        let locals = asyncio::async_std::get_current_locals(py)?;
+        let pool = self.pool.clone();
+        let source = self.source.clone();
+        let view = self.view.clone();
+        let meta = self.meta.clone();
+
+        asyncio::async_std::future_into_py_with_locals(
+            py,
+            locals.clone(),
+            // Store the current locals in task-local data
+            asyncio::async_std::scope(locals.clone(), async move {
+                async_utils::get_partial_records_by_id_async(
+                    &pool,
+                    &view.get_key_space(&source),
+                    &meta,
+                    &ids,
+                    &view.projection,
+                    false,
+                )
+                .await
+            }),
+        )
+    }
+
+    /// Returns all the records in the view, as dictionaries
+    pub(crate) fn get_all<'a>(&self, py: Python<'a>) -> PyResult<&'a PyAny> {
+        let locals = asyncio::async_std::get_current_locals(py)?;
+        let pool = self.pool.clone();
+        let source = self.source.clone();
+        let view = self.view.clone();
+        let meta = self.meta.clone();
+
+        asyncio::async_std::future_into_py_with_locals(
+            py,
+            locals.clone(),
+            // Store the current locals in task-local data
+            asyncio::async_std::scope(locals.clone(), async move {
+                async_utils::get_all_partial_records_in_collection_async(
+                    &pool,
+                    &view.get_key_space(&source),
+                    &meta,
+                    &view.projection,
+                    false,
+                    Page::default(),
+                )
+                .await
+            }),
+        )
+    }
+
+    /// Rebuilds the view from all the records of its source collection, returning the number of
+    /// records in the view. This is needed for records that were added before the view was created.
+    /// At most `max_ops_per_sec` records are scanned per second if given
+    #[args(max_ops_per_sec = "None")]
+    pub(crate) fn rebuild<'a>(
+        &self,
+        py: Python<'a>,
+        max_ops_per_sec: Option<f64>,
+    ) -> PyResult<&'a PyAny> {
+        let mut throttle = Throttle::new(max_ops_per_sec)?;
+        let locals = asyncio::async_std::get_current_locals(py)?;
+        let pool = self.pool.clone();
+        let source = self.source.clone();
+        let view = self.view.clone();
+
+        asyncio::async_std::future_into_py_with_locals(
+            py,
+            locals.clone(),
+            // Store the current locals in task-local data
+            asyncio::async_std::scope(locals.clone(), async move {
+                async_utils::rebuild_view_async(&pool, &source, &view, &mut throttle).await
+            }),
+        )
+    }
+}
+
+impl AsyncView {
+    /// Instantiates a new view. This is not accessible to python and thus a view
+    /// cannot be directly instantiated in python
+    pub(crate) fn new(
+        source: String,
+        view: ViewDefinition,
+        meta: store::CollectionMeta,
+        pool: pools::AsyncRedisPool,
+    ) -> Self {
+        Self {
+            source,
+            view,
+            meta,
+            pool,
+        }
+    }
+}
+
+#[pyclass(subclass)]
+pub(crate) struct AsyncLink {
+    pub(crate) link: LinkDefinition,
+    pub(crate) left_meta: store::CollectionMeta,
+    pub(crate) right_meta: store::CollectionMeta,
+    pub(crate) pool: pools::AsyncRedisPool,
+}
+
+#[pymethods]
+impl AsyncLink {
+    /// Links the record of the given id in the left collection to that of the given id in the right
+    /// collection. Linking records that are already linked does nothing
+    pub(crate) fn link<'a>(
+        &self,
+        py: Python<'a>,
+        left_id: &str,
+        right_id: &str,
+    ) -> PyResult<&'a PyAny> {
+        let locals = asyncio::async_std::get_current_locals(py)?;
+        let pool = self.pool.clone();
+        let meta = self.left_meta.clone();
+        let pipe = self.link.generate_link_pipeline(left_id, right_id);
+
+        asyncio::async_std::future_into_py_with_locals(
+            py,
+            locals.clone(),
+            // Store the current locals in task-local data
+            asyncio::async_std::scope(locals.clone(), async move {
+                async_utils::update_links_async(&pool, &meta, &pipe).await
+            }),
+        )
+    }
+
+    /// Unlinks the record of the given id in the left collection from that of the given id in the right
+    /// collection. Unlinking records that are not linked does nothing
+    pub(crate) fn unlink<'a>(
+        &self,
+        py: Python<'a>,
+        left_id: &str,
+        right_id: &str,
+    ) -> PyResult<&'a PyAny> {
+        let locals = asyncio::async_std::get_current_locals(py)?;
+        let pool = self.pool.clone();
+        let meta = self.left_meta.clone();
+        let pipe = self.link.generate_unlink_pipeline(left_id, right_id);
+
+        asyncio::async_std::future_into_py_with_locals(
+            py,
+            locals.clone(),
+            // Store the current locals in task-local data
+            asyncio::async_std::scope(locals.clone(), async move {
+                async_utils::update_links_async(&pool, &meta, &pipe).await
+            }),
+        )
+    }
+
+    /// Returns the records of the left collection linked to the record of the given id in the right
+    /// collection, in no particular order. Linked records that no longer exist are skipped
+    pub(crate) fn left_of<'a>(&self, py: Python<'a>, right_id: &str) -> PyResult<&'a PyAny> {
+        let locals = asyncio::async_std::get_current_locals(py)?;
+        let pool = self.pool.clone();
+        let name = self.link.left.clone();
+        let meta = self.left_meta.clone();
+        let key = self.link.generate_left_key(right_id);
+
+        asyncio::async_std::future_into_py_with_locals(
+            py,
+            locals.clone(),
+            // Store the current locals in task-local data
+            asyncio::async_std::scope(locals.clone(), async move {
+                let ids = async_utils::get_linked_ids_async(&pool, &key).await?;
+                if ids.is_empty() {
+                    meta.metrics.record("left_of", 0);
+                    return Ok(vec![]);
+                }
+
+                meta.metrics.record_result(
+                    "left_of",
+                    async_utils::get_records_by_id_async(&pool, &name, &meta, &ids).await,
+                )
+            }),
+        )
+    }
+
+    /// Returns the records of the right collection linked to the record of the given id in the left
+    /// collection, in no particular order. Linked records that no longer exist are skipped
+    pub(crate) fn right_of<'a>(&self, py: Python<'a>, left_id: &str) -> PyResult<&'a PyAny> {
+        let locals = asyncio::async_std::get_current_locals(py)?;
+        let pool = self.pool.clone();
+        let name = self.link.right.clone();
+        let meta = self.right_meta.clone();
+        let key = self.link.generate_right_key(left_id);
+
+        asyncio::async_std::future_into_py_with_locals(
+            py,
+            locals.clone(),
+            // Store the current locals in task-local data
+            asyncio::async_std::scope(locals.clone(), async move {
+                let ids = async_utils::get_linked_ids_async(&pool, &key).await?;
+                if ids.is_empty() {
+                    meta.metrics.record("right_of", 0);
+                    return Ok(vec![]);
+                }
+
+                meta.metrics.record_result(
+                    "right_of",
+                    async_utils::get_records_by_id_async(&pool, &name, &meta, &ids).await,
+                )
+            }),
+        )
+    }
+}
+
+impl AsyncLink {
+    /// Instantiates a new link handle. This is not accessible to python and thus a link
+    /// cannot be directly instantiated in python
+    pub(crate) fn new(
+        link: LinkDefinition,
+        left_meta: store::CollectionMeta,
+        right_meta: store::CollectionMeta,
+        pool: pools::AsyncRedisPool,
+    ) -> Self {
+        AsyncLink {
+            link,
+            left_meta,
+            right_meta,
+            pool,
         }
     }
 }
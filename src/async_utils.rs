@@ -1,93 +1,1490 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::io::Write;
+use std::time::{Duration, Instant};
 
-use pyo3::exceptions::{PyConnectionError, PyKeyError, PyValueError};
+use async_std::task;
+use pyo3::exceptions::PyConnectionError;
 use pyo3::prelude::*;
-use pyo3::types::IntoPyDict;
+use pyo3::types::PyDict;
 use redis::aio::Connection;
 
-use crate::parsers::redis_to_py;
+use crate::aggregations::{self, AggregateOp};
+use crate::batching::InsertRecords;
+use crate::bloom_filters;
+use crate::compaction::{self, COMPACT_BATCH_SIZE};
+use crate::compression::{self, TrainingStats};
+use crate::conditional_writes;
+use crate::constraints;
+use crate::expiry::Expiry;
+use crate::exports::{ExportWriter, EXPORT_BUFFER_SIZE};
+use crate::field_indexes;
+use crate::latency::LatencyStats;
+use crate::lookups::Lookups;
+use crate::memory_usage::{self, MemoryUsage};
+use crate::migrations::{self, SchemaSnapshot};
+use crate::null_indexes;
+use crate::pagination::{BrowseCursor, BrowsedPage, KeysetCursor, Ordering, Page};
+use crate::profiling::{self, Phase};
+use crate::quotas::{self, Quota};
+use crate::range_indexes::{self, INDEX_BATCH_SIZE};
+use crate::read_errors::{OnError, ReadError};
+use crate::registries::{self, Registry};
+use crate::repairs::{self, Repair, REPAIR_BATCH_SIZE};
+use crate::retention::RetentionPolicy;
+use crate::snapshots;
 use crate::store::CollectionMeta;
-use crate::{mobc_redis, utils};
+use crate::throttle::Throttle;
+use crate::ttl_audit::{TtlAudit, TtlThresholds};
+use crate::unique_fields;
+use crate::views::ViewDefinition;
+use crate::write_buffer::WriteError;
+use crate::{pools, utils, vectors, verification};
 
-const SELECT_SOME_FIELDS_FOR_ALL_IDS_SCRIPT: &str = r"local filtered = {} local cursor = '0' local table_unpack = table.unpack or unpack local columns = {} local nested_columns = {} local args_tracker = {} for i, k in ipairs(ARGV) do if i > 1 then if args_tracker[k] then nested_columns[k] = true else  table.insert(columns, k) args_tracker[k] = true end end end repeat local result = redis.call('SCAN', cursor, 'MATCH', ARGV[1]) for _, key in ipairs(result[2]) do if redis.call('TYPE', key).ok == 'hash' then  local data = redis.call('HMGET', key, table_unpack(columns)) local parsed_data = {} for i, v in ipairs(data) do table.insert(parsed_data, columns[i]) if nested_columns[columns[i]] then v = redis.call('HGETALL', v) end table.insert(parsed_data, v) end table.insert(filtered, parsed_data) end end cursor = result[1] until (cursor == '0') return filtered";
-const SELECT_ALL_FIELDS_FOR_ALL_IDS_SCRIPT: &str = r"local filtered = {} local cursor = '0' local nested_fields = {} for i, key in ipairs(ARGV) do if i > 1 then nested_fields[key] = true end end repeat local result = redis.call('SCAN', cursor, 'MATCH', ARGV[1]) for _, key in ipairs(result[2]) do if redis.call('TYPE', key).ok == 'hash' then local parent = redis.call('HGETALL', key) for i, k in ipairs(parent) do if nested_fields[k] then local nested = redis.call('HGETALL', parent[i + 1]) parent[i + 1] = nested end end table.insert(filtered, parent) end end cursor = result[1] until (cursor == '0') return filtered";
+const SELECT_SOME_FIELDS_FOR_ALL_IDS_SCRIPT: &str = r"local filtered = {} local cursor = '0' local skip = tonumber(ARGV[2]) local limit = tonumber(ARGV[3]) local table_unpack = table.unpack or unpack local columns = {  } local nested_columns = {} local args_tracker = {} local projections = {} local function project(key, paths) local fields = {} local is_field = {} local nested = {} for _, path in ipairs(paths) do local head, rest = string.match(path, '^([^.]+)%.(.+)$') if head then if not nested[head] then nested[head] = {} end table.insert(nested[head], rest) else table.insert(fields, path) is_field[path] = true end end local projected = {} if #fields > 0 then local values = redis.call('HMGET', key, table_unpack(fields)) for i, v in ipairs(values) do if v then table.insert(projected, fields[i]) table.insert(projected, v) end end end for head, rest in pairs(nested) do local nested_key = redis.call('HGET', key, head) if nested_key and not is_field[head] then table.insert(projected, head) table.insert(projected, project(nested_key, rest)) end end return projected end if limit == 0 then return filtered end for i, k in ipairs(ARGV) do if i > 3 then local head, rest = string.match(k, '^([^.]+)%.(.+)$') if head then if not projections[head] then projections[head] = {} end table.insert(projections[head], rest) elseif args_tracker[k] then nested_columns[k] = true else table.insert(columns, k) args_tracker[k] = true end end end for head, _ in pairs(projections) do if not args_tracker[head] then table.insert(columns, head) args_tracker[head] = true end end repeat local result = redis.call('SCAN', cursor, 'MATCH', ARGV[1]) for _, key in ipairs(result[2]) do if redis.call('TYPE', key).ok == 'hash' then if skip > 0 then skip = skip - 1 else local data = redis.call('HMGET', key, table_unpack(columns)) local parsed_data = {} for i, v in ipairs(data) do table.insert(parsed_data, columns[i]) if nested_columns[columns[i]] and v then v = redis.call('HGETALL', v) elseif projections[columns[i]] and v then v = project(v, projections[columns[i]]) end table.insert(parsed_data, v) end table.insert(filtered, parsed_data) if #filtered == limit then return filtered end end end end cursor = result[1] until (cursor == '0') return filtered";
+const SELECT_ALL_FIELDS_FOR_ALL_IDS_SCRIPT: &str = r"local filtered = {} local cursor = '0' local skip = tonumber(ARGV[2]) local limit = tonumber(ARGV[3]) local nested_fields = {} if limit == 0 then return filtered end for i, key in ipairs(ARGV) do if i > 3 then nested_fields[key] = true end end repeat local result = redis.call('SCAN', cursor, 'MATCH', ARGV[1]) for _, key in ipairs(result[2]) do if redis.call('TYPE', key).ok == 'hash' then if skip > 0 then skip = skip - 1 else local parent = redis.call('HGETALL', key) for i, k in ipairs(parent) do if nested_fields[k] then local nested = redis.call('HGETALL', parent[i + 1]) parent[i + 1] = nested end end table.insert(filtered, parent) if #filtered == limit then return filtered end end end end cursor = result[1] until (cursor == '0') return filtered";
 const SELECT_ALL_FIELDS_FOR_SOME_IDS_SCRIPT: &str = r"local result = {} local nested_fields = {} for _, key in ipairs(ARGV) do nested_fields[key] = true end for _, key in ipairs(KEYS) do local parent = redis.call('HGETALL', key) for i, k in ipairs(parent) do if nested_fields[k] then local nested = redis.call('HGETALL', parent[i + 1]) parent[i + 1] = nested end end table.insert(result, parent) end return result";
-const SELECT_SOME_FIELDS_FOR_SOME_IDS_SCRIPT: &str = r"local result = {} local table_unpack = table.unpack or unpack local columns = { } local nested_columns = {} local args_tracker = {} for i, k in ipairs(ARGV) do if args_tracker[k] then nested_columns[k] = true else table.insert(columns, k) args_tracker[k] = true end end for _, key in ipairs(KEYS) do local data = redis.call('HMGET', key, table_unpack(columns)) local parsed_data = {} for i, v in ipairs(data) do if v then table.insert(parsed_data, columns[i]) if nested_columns[columns[i]] then v = redis.call('HGETALL', v) end table.insert(parsed_data, v) end end table.insert(result, parsed_data) end return result";
+const SELECT_SOME_FIELDS_FOR_SOME_IDS_SCRIPT: &str = r"local result = {} local table_unpack = table.unpack or unpack local columns = {  } local nested_columns = {} local args_tracker = {} local projections = {} local function project(key, paths) local fields = {} local is_field = {} local nested = {} for _, path in ipairs(paths) do local head, rest = string.match(path, '^([^.]+)%.(.+)$') if head then if not nested[head] then nested[head] = {} end table.insert(nested[head], rest) else table.insert(fields, path) is_field[path] = true end end local projected = {} if #fields > 0 then local values = redis.call('HMGET', key, table_unpack(fields)) for i, v in ipairs(values) do if v then table.insert(projected, fields[i]) table.insert(projected, v) end end end for head, rest in pairs(nested) do local nested_key = redis.call('HGET', key, head) if nested_key and not is_field[head] then table.insert(projected, head) table.insert(projected, project(nested_key, rest)) end end return projected end for i, k in ipairs(ARGV) do local head, rest = string.match(k, '^([^.]+)%.(.+)$') if head then if not projections[head] then projections[head] = {} end table.insert(projections[head], rest) elseif args_tracker[k] then nested_columns[k] = true else table.insert(columns, k) args_tracker[k] = true end end for head, _ in pairs(projections) do if not args_tracker[head] then table.insert(columns, head) args_tracker[head] = true end end for _, key in ipairs(KEYS) do local data = redis.call('HMGET', key, table_unpack(columns)) local parsed_data = {} for i, v in ipairs(data) do if v then table.insert(parsed_data, columns[i]) if nested_columns[columns[i]] then v = redis.call('HGETALL', v) elseif projections[columns[i]] then v = project(v, projections[columns[i]]) end table.insert(parsed_data, v) end end table.insert(result, parsed_data) end return result";
 
-macro_rules! py_value_error {
-    ($v:expr, $det:expr) => {
-        PyValueError::new_err(format!("{:?} (value was {:?})", $det, $v))
-    };
+/// Inserts the (primary key, record) tuples passed to it in a batch into the redis store,
+/// along with the given updates of nested records
+pub(crate) async fn insert_records_async(
+    pool: &pools::AsyncRedisPool,
+    collection_name: &str,
+    meta: &CollectionMeta,
+    records: &Vec<(String, Vec<(String, String)>)>,
+    nested_updates: &[redis::Cmd],
+    ttl: &Option<Expiry>,
+) -> PyResult<()> {
+    constraints::enforce_constraints(collection_name, meta, records)?;
+    refresh_registered_fields_async(pool, collection_name, meta).await?;
+
+    let pipe = profiling::measure(Phase::Serialize, || {
+        utils::generate_insert_pipeline(collection_name, meta, records, nested_updates, ttl)
+    });
+    if unique_fields::has_unique_values(collection_name, meta, records) {
+        apply_unbuffered_write_async(pool, meta, &pipe).await
+    } else {
+        apply_write_async(pool, meta, &pipe).await
+    }
 }
 
-macro_rules! py_key_error {
-    ($v:expr, $det:expr) => {
-        PyKeyError::new_err(format!("{:?} (key was {:?})", $det, $v))
+/// Inserts the given (primary key, record) tuples of a record and its nested records, the record being the last,
+/// only if the record does not exist yet, returning whether it was created. The creation of the record is claimed
+/// atomically first, as in HSETNX, so that of many concurrent calls for the same record, only one creates it
+pub(crate) async fn insert_record_if_missing_async(
+    pool: &pools::AsyncRedisPool,
+    collection_name: &str,
+    meta: &CollectionMeta,
+    records: &Vec<(String, Vec<(String, String)>)>,
+    ttl: &Option<Expiry>,
+) -> PyResult<bool> {
+    let (claim_cmd, release_cmd) = utils::generate_creation_claim_cmds(collection_name, records);
+    let is_claimed: bool = {
+        let mut conn = pools::get_connection_async(pool).await?;
+        claim_cmd
+            .query_async(&mut conn as &mut Connection)
+            .await
+            .map_err(|e| PyConnectionError::new_err(e.to_string()))?
     };
+    if !is_claimed {
+        return Ok(false);
+    }
+
+    // the claim is released in the transaction that creates the record, or at once if the creation fails
+    let release_cmds = [release_cmd];
+    if let Err(e) =
+        insert_records_async(pool, collection_name, meta, records, &release_cmds, ttl).await
+    {
+        if let Ok(mut conn) = pools::get_connection_async(pool).await {
+            let _ = release_cmds[0]
+                .query_async::<_, ()>(&mut conn as &mut Connection)
+                .await;
+        }
+        return Err(e);
+    }
+    Ok(true)
 }
 
-/// Inserts the (primary key, record) tuples passed to it in a batch into the redis store
-pub(crate) async fn insert_records_async(
-    pool: &mobc::Pool<mobc_redis::RedisConnectionManager>,
-    records: &Vec<(String, Vec<(String, String)>)>,
-    ttl: &Option<u64>,
+/// Applies the retention policy of the given collection, removing records that are older than
+/// the policy allows or capping the ttl of records to the retention period if the policy
+/// has no timestamp field. It returns the number of records affected
+pub(crate) async fn apply_retention_async(
+    pool: &pools::AsyncRedisPool,
+    collection_name: &str,
+    policy: &RetentionPolicy,
+    throttle: &mut Throttle,
+) -> PyResult<u64> {
+    let cutoff = policy.get_cutoff();
+    run_scan_script_async(pool, throttle, |cursor, count| {
+        utils::generate_retention_cmd(collection_name, policy, &cutoff, cursor, count)
+    })
+    .await
+}
+
+/// Runs a maintenance script on one SCAN batch at a time, so that redis is not blocked for long, until
+/// the scan is complete, doing no more operations per second than the throttle allows. The command of
+/// each batch is generated from its cursor and SCAN count, and returns the next cursor, the number of
+/// keys scanned and the number of records affected. It returns the total number of records affected
+pub(crate) async fn run_scan_script_async(
+    pool: &pools::AsyncRedisPool,
+    throttle: &mut Throttle,
+    generate_cmd: impl Fn(&str, usize) -> redis::Cmd,
+) -> PyResult<u64> {
+    let mut conn = pools::get_blocking_connection_async(pool).await?;
+    let mut affected: u64 = 0;
+    let mut cursor = "0".to_string();
+
+    loop {
+        let (next_cursor, scanned, count): (String, usize, u64) =
+            generate_cmd(&cursor, throttle.batch_size(utils::SCAN_BATCH_SIZE))
+                .query_async(&mut conn as &mut Connection)
+                .await
+                .map_err(|e| PyConnectionError::new_err(e.to_string()))?;
+        affected += count;
+        cursor = next_cursor;
+        if cursor == "0" {
+            return Ok(affected);
+        }
+
+        if let Some(delay) = throttle.throttle(scanned) {
+            task::sleep(delay).await;
+        }
+    }
+}
+
+/// Adds the given field to the tracked fields of the collection in redis, so that every write updates
+/// its HyperLogLog, and populates the HyperLogLog with the values of all records in the collection
+pub(crate) async fn track_cardinality_async(
+    pool: &pools::AsyncRedisPool,
+    collection_name: &str,
+    meta: &CollectionMeta,
+    field: &str,
+    throttle: &mut Throttle,
 ) -> PyResult<()> {
-    let mut conn = pool
-        .get()
+    run_scan_script_async(pool, throttle, |cursor, count| {
+        utils::generate_track_cardinality_cmd(collection_name, field, cursor, count)
+    })
+    .await?;
+    meta.registered_fields.add(Registry::Cardinality, field);
+    Ok(())
+}
+
+/// Gets the approximate number of distinct values the given field has had in the collection
+pub(crate) async fn count_distinct_async(
+    pool: &pools::AsyncRedisPool,
+    collection_name: &str,
+    field: &str,
+) -> PyResult<u64> {
+    let mut conn = pools::get_connection_async(pool).await?;
+
+    redis::cmd("PFCOUNT")
+        .arg(utils::generate_cardinality_key(collection_name, field))
+        .query_async(&mut conn as &mut Connection)
+        .await
+        .map_err(|e| PyConnectionError::new_err(e.to_string()))
+}
+
+/// Gets the distinct values of the given field of the records in the given collection, in no particular order
+pub(crate) async fn get_distinct_values_async(
+    pool: &pools::AsyncRedisPool,
+    collection_name: &str,
+    meta: &CollectionMeta,
+    field: &str,
+) -> PyResult<Vec<Py<PyAny>>> {
+    let mut conn = pools::get_connection_async(pool).await?;
+
+    let values: Vec<redis::Value> = utils::generate_select_distinct_cmd(collection_name, field)
+        .query_async(&mut conn as &mut Connection)
         .await
         .map_err(|e| PyConnectionError::new_err(e.to_string()))?;
-    let mut pipe = redis::pipe();
+    utils::distinct_values_to_py(collection_name, meta, field, &values)
+}
+
+/// Aggregates the values of the given field of the records in the given collection with the given operation,
+/// returning the number of values aggregated and the result as saved in redis, empty if there were no values
+pub(crate) async fn aggregate_field_async(
+    pool: &pools::AsyncRedisPool,
+    collection_name: &str,
+    field: &str,
+    op: AggregateOp,
+) -> PyResult<(usize, String)> {
+    let mut conn = pools::get_connection_async(pool).await?;
+
+    aggregations::generate_aggregate_cmd(collection_name, field, op)
+        .query_async(&mut conn as &mut Connection)
+        .await
+        .map_err(|e| PyConnectionError::new_err(e.to_string()))
+}
+
+/// Counts the records in the given collection by the values of the given field, returning each value
+/// as saved in redis with its count
+pub(crate) async fn count_by_field_async(
+    pool: &pools::AsyncRedisPool,
+    collection_name: &str,
+    field: &str,
+) -> PyResult<Vec<(redis::Value, usize)>> {
+    let mut conn = pools::get_connection_async(pool).await?;
+
+    aggregations::generate_count_by_cmd(collection_name, field)
+        .query_async(&mut conn as &mut Connection)
+        .await
+        .map_err(|e| PyConnectionError::new_err(e.to_string()))
+}
+
+/// Counts the records in the given collection whose fields are equal to the values in the given filter
+pub(crate) async fn count_matching_records_async(
+    pool: &pools::AsyncRedisPool,
+    collection_name: &str,
+    filter: &[(String, String)],
+) -> PyResult<usize> {
+    let mut conn = pools::get_connection_async(pool).await?;
+
+    aggregations::generate_count_where_cmd(collection_name, filter)
+        .query_async(&mut conn as &mut Connection)
+        .await
+        .map_err(|e| PyConnectionError::new_err(e.to_string()))
+}
 
-    // start transaction
-    pipe.cmd("MULTI");
-    for (pk, record) in records {
-        pipe.hset_multiple(pk, &record);
+/// Checks whether the record of the given id might exist in the collection using its bloom filter.
+/// It returns false only if the record certainly does not exist. If the collection has no bloom filter
+/// or the RedisBloom module is not loaded, it always returns true
+pub(crate) async fn might_exist_async(
+    pool: &pools::AsyncRedisPool,
+    collection_name: &str,
+    meta: &CollectionMeta,
+    id: &str,
+) -> PyResult<bool> {
+    let bloom_filter = match &meta.bloom_filter {
+        None => return Ok(true),
+        Some(bloom_filter) => bloom_filter,
+    };
+    let mut conn = pools::get_connection_async(pool).await?;
 
-        if let Some(life_span) = ttl {
-            pipe.expire(pk, *life_span as usize);
+    let is_available = match bloom_filter.is_available() {
+        Some(is_available) => is_available,
+        None => {
+            let is_available: bool = bloom_filters::generate_init_cmd(collection_name)
+                .query_async(&mut conn as &mut Connection)
+                .await
+                .map_err(|e| PyConnectionError::new_err(e.to_string()))?;
+            bloom_filter.set_available(is_available);
+            is_available
         }
+    };
+
+    if !is_available {
+        return Ok(true);
     }
-    // end transaction
-    pipe.cmd("EXEC");
 
-    pipe.query_async(&mut conn as &mut Connection)
+    redis::cmd("BF.EXISTS")
+        .arg(utils::generate_bloom_filter_key(collection_name))
+        .arg(utils::generate_hash_key(
+            collection_name,
+            id,
+            meta.case_insensitive_keys,
+        ))
+        .query_async(&mut conn as &mut Connection)
+        .await
+        .map_err(|e| PyConnectionError::new_err(e.to_string()))
+}
+
+/// Checks whether the record of the given id exists in the collection
+pub(crate) async fn record_exists_async(
+    pool: &pools::AsyncRedisPool,
+    collection_name: &str,
+    meta: &CollectionMeta,
+    id: &str,
+) -> PyResult<bool> {
+    if !might_exist_async(pool, collection_name, meta, id).await? {
+        return Ok(false);
+    }
+
+    let mut conn = pools::get_connection_async(pool).await?;
+
+    redis::cmd("EXISTS")
+        .arg(utils::generate_hash_key(
+            collection_name,
+            id,
+            meta.case_insensitive_keys,
+        ))
+        .query_async(&mut conn as &mut Connection)
+        .await
+        .map_err(|e| PyConnectionError::new_err(e.to_string()))
+}
+
+/// Checks whether the records of the given ids exist in the collection, in one round trip
+pub(crate) async fn records_exist_async(
+    pool: &pools::AsyncRedisPool,
+    collection_name: &str,
+    meta: &CollectionMeta,
+    ids: &[String],
+) -> PyResult<Vec<bool>> {
+    if ids.is_empty() {
+        return Ok(vec![]);
+    }
+
+    let mut conn = pools::get_connection_async(pool).await?;
+
+    utils::generate_exists_pipeline(collection_name, meta, ids)
+        .query_async(&mut conn as &mut Connection)
         .await
         .map_err(|e| PyConnectionError::new_err(e.to_string()))
 }
 
-/// Removes the given keys from the redis store
+/// Increments a counter field using the command generated by `utils::generate_increment_cmd`,
+/// returning the new value
+pub(crate) async fn increment_counter_async(
+    pool: &pools::AsyncRedisPool,
+    collection_name: &str,
+    meta: &CollectionMeta,
+    field: &str,
+    cmd: &redis::Cmd,
+) -> PyResult<Py<PyAny>> {
+    let mut conn = pools::get_connection_async(pool).await?;
+
+    let value: redis::Value = cmd
+        .query_async(&mut conn as &mut Connection)
+        .await
+        .map_err(|e| PyConnectionError::new_err(e.to_string()))?;
+    meta.mirrors.replicate_cmd(cmd);
+    utils::parse_counter_value(collection_name, meta, field, &value)
+}
+
+/// Rebuilds the given view of the given collection from all its records, returning the number
+/// of records in the view
+pub(crate) async fn rebuild_view_async(
+    pool: &pools::AsyncRedisPool,
+    collection_name: &str,
+    view: &ViewDefinition,
+    throttle: &mut Throttle,
+) -> PyResult<u64> {
+    run_scan_script_async(pool, throttle, |cursor, count| {
+        view.generate_clear_cmd(collection_name, cursor, count)
+    })
+    .await?;
+    run_scan_script_async(pool, throttle, |cursor, count| {
+        view.generate_rebuild_cmd(collection_name, cursor, count)
+    })
+    .await
+}
+
+/// Takes the snapshot of the given tag of the given collection, copying all its records, with their fields
+/// as they currently are, in place of those of any snapshot of the same tag. It returns the number of records
+/// in the snapshot
+pub(crate) async fn take_snapshot_async(
+    pool: &pools::AsyncRedisPool,
+    collection_name: &str,
+    tag: &str,
+    throttle: &mut Throttle,
+) -> PyResult<u64> {
+    delete_snapshot_async(pool, collection_name, tag, throttle).await?;
+    run_scan_script_async(pool, throttle, |cursor, count| {
+        snapshots::generate_copy_cmd(collection_name, tag, cursor, count)
+    })
+    .await
+}
+
+/// Removes all the records of the given collection, with the data derived from them, a SCAN batch at a time,
+/// like `utils::clear_collection()`. It returns the number of records removed
+pub(crate) async fn clear_collection_async(
+    pool: &pools::AsyncRedisPool,
+    collection_name: &str,
+    meta: &CollectionMeta,
+    throttle: &mut Throttle,
+) -> PyResult<u64> {
+    run_scan_script_async(pool, throttle, |cursor, count| {
+        utils::generate_clear_collection_cmd(collection_name, meta, cursor, count)
+    })
+    .await
+}
+
+/// Deletes the snapshot of the given tag of the given collection, returning the number of records it had
+pub(crate) async fn delete_snapshot_async(
+    pool: &pools::AsyncRedisPool,
+    collection_name: &str,
+    tag: &str,
+    throttle: &mut Throttle,
+) -> PyResult<u64> {
+    run_scan_script_async(pool, throttle, |cursor, count| {
+        snapshots::generate_clear_cmd(collection_name, tag, cursor, count)
+    })
+    .await
+}
+
+/// Removes records from the redis store using the pipeline generated by `utils::generate_delete_pipeline`,
+/// returning the number of them that existed, or None if the removal was buffered
 pub(crate) async fn remove_records_async(
-    pool: &mobc::Pool<mobc_redis::RedisConnectionManager>,
-    keys: &Vec<String>,
+    pool: &pools::AsyncRedisPool,
+    meta: &CollectionMeta,
+    pipe: &redis::Pipeline,
+) -> PyResult<Option<usize>> {
+    let reply: Option<(usize,)> = apply_write_with_reply_async(pool, meta, pipe).await?;
+    Ok(reply.map(|(deleted,)| deleted))
+}
+
+/// Deletes the records of the given collection whose fields are equal to the values in the given filter,
+/// returning the number deleted, like `utils::delete_matching_records()`
+pub(crate) async fn delete_matching_records_async(
+    pool: &pools::AsyncRedisPool,
+    collection_name: &str,
+    meta: &CollectionMeta,
+    filter: &[(String, String)],
+) -> PyResult<usize> {
+    refresh_registered_fields_async(pool, collection_name, meta).await?;
+    let cmd = conditional_writes::generate_delete_matching_cmd(collection_name, meta, filter);
+    let mut conn = pools::get_blocking_connection_async(pool).await?;
+    let ids: Vec<String> = cmd
+        .query_async(&mut conn as &mut Connection)
+        .await
+        .map_err(|e| PyConnectionError::new_err(e.to_string()))?;
+    drop(conn);
+
+    if conditional_writes::is_deleted_in_script(meta) {
+        meta.mirrors.replicate_cmd(&cmd);
+    } else if !ids.is_empty() {
+        let pipe = utils::generate_delete_pipeline(collection_name, meta, &ids);
+        remove_records_async(pool, meta, &pipe).await?;
+    }
+    Ok(ids.len())
+}
+
+/// Deletes the records of the given collection whose ids match the given glob pattern, returning the number
+/// deleted, like `utils::delete_records_by_id_pattern()`
+pub(crate) async fn delete_records_by_id_pattern_async(
+    pool: &pools::AsyncRedisPool,
+    collection_name: &str,
+    meta: &CollectionMeta,
+    id_pattern: &str,
+    throttle: &mut Throttle,
+) -> PyResult<usize> {
+    refresh_registered_fields_async(pool, collection_name, meta).await?;
+    let is_deleted_in_script = conditional_writes::is_deleted_in_script(meta);
+    let mut deleted = 0;
+    let mut cursor = "0".to_string();
+
+    loop {
+        let cmd = conditional_writes::generate_delete_by_id_pattern_cmd(
+            collection_name,
+            meta,
+            id_pattern,
+            &cursor,
+            throttle.batch_size(utils::SCAN_BATCH_SIZE),
+        );
+        let mut conn = pools::get_blocking_connection_async(pool).await?;
+        let (next_cursor, scanned, ids): (String, usize, Vec<String>) = cmd
+            .query_async(&mut conn as &mut Connection)
+            .await
+            .map_err(|e| PyConnectionError::new_err(e.to_string()))?;
+        drop(conn);
+
+        // the cursor of the scan is only valid on this redis, so the mirrors delete the ids instead
+        if is_deleted_in_script && !ids.is_empty() && !meta.mirrors.is_empty() {
+            meta.mirrors.replicate(&utils::generate_delete_pipeline(
+                collection_name,
+                meta,
+                &ids,
+            ));
+        } else if !is_deleted_in_script && !ids.is_empty() {
+            let pipe = utils::generate_delete_pipeline(collection_name, meta, &ids);
+            remove_records_async(pool, meta, &pipe).await?;
+        }
+        deleted += ids.len();
+
+        cursor = next_cursor;
+        if cursor == "0" {
+            return Ok(deleted);
+        }
+
+        if let Some(delay) = throttle.throttle(scanned) {
+            task::sleep(delay).await;
+        }
+    }
+}
+
+/// Makes the given changes to the records of the given collection whose fields are equal to the values in
+/// the given filter, returning the number updated, like `utils::update_matching_records()`
+pub(crate) async fn update_matching_records_async(
+    pool: &pools::AsyncRedisPool,
+    collection_name: &str,
+    meta: &CollectionMeta,
+    filter: &[(String, String)],
+    changes: &[(String, String)],
+) -> PyResult<usize> {
+    refresh_registered_fields_async(pool, collection_name, meta).await?;
+    let cmd =
+        conditional_writes::generate_update_matching_cmd(collection_name, meta, filter, changes);
+    let mut conn = pools::get_blocking_connection_async(pool).await?;
+    let ids: Vec<String> = cmd
+        .query_async(&mut conn as &mut Connection)
+        .await
+        .map_err(|e| PyConnectionError::new_err(e.to_string()))?;
+    drop(conn);
+
+    if conditional_writes::is_updated_in_script(meta, changes) {
+        meta.mirrors.replicate_cmd(&cmd);
+    } else if !ids.is_empty() {
+        let records: InsertRecords = ids
+            .iter()
+            .map(|id| {
+                (
+                    utils::generate_hash_key(collection_name, id, meta.case_insensitive_keys),
+                    changes.to_vec(),
+                )
+            })
+            .collect();
+        insert_records_async(pool, collection_name, meta, &records, &[], &None).await?;
+    }
+    Ok(ids.len())
+}
+
+/// Links or unlinks records using the pipeline generated by `LinkDefinition::generate_link_pipeline`
+/// or `LinkDefinition::generate_unlink_pipeline`
+pub(crate) async fn update_links_async(
+    pool: &pools::AsyncRedisPool,
+    meta: &CollectionMeta,
+    pipe: &redis::Pipeline,
+) -> PyResult<()> {
+    apply_write_async(pool, meta, pipe).await
+}
+
+/// Reads the fields registered in redis for the given collection again if they are stale, as
+/// `utils::refresh_registered_fields()` does
+pub(crate) async fn refresh_registered_fields_async(
+    pool: &pools::AsyncRedisPool,
+    collection_name: &str,
+    meta: &CollectionMeta,
+) -> PyResult<()> {
+    if !meta.registered_fields.is_stale() {
+        return Ok(());
+    }
+
+    let pipe = registries::generate_refresh_pipeline(collection_name);
+    let fields = match pools::get_connection_async(pool).await {
+        Ok(mut conn) => pipe
+            .query_async(&mut conn as &mut Connection)
+            .await
+            .map_err(|e| PyConnectionError::new_err(e.to_string())),
+        Err(e) => Err(e),
+    };
+    match fields {
+        Ok(fields) => meta.registered_fields.update(fields),
+        Err(e) if !meta.write_buffer.is_enabled() => return Err(e),
+        Err(_) => {}
+    }
+    Ok(())
+}
+
+/// Applies the given write to redis and queues it for the mirrors. If the store has a write buffer,
+/// the write is buffered instead when redis is unreachable or earlier writes are still buffered
+async fn apply_write_async(
+    pool: &pools::AsyncRedisPool,
+    meta: &CollectionMeta,
+    pipe: &redis::Pipeline,
+) -> PyResult<()> {
+    apply_write_with_reply_async::<()>(pool, meta, pipe)
+        .await
+        .map(|_| ())
+}
+
+/// Applies the given write to redis and queues it for the mirrors, failing instead of being buffered e.g. when
+/// redis is unreachable, since the write has checks whose failure should be raised to the caller e.g. of unique values
+async fn apply_unbuffered_write_async(
+    pool: &pools::AsyncRedisPool,
+    meta: &CollectionMeta,
+    pipe: &redis::Pipeline,
 ) -> PyResult<()> {
-    let mut conn = pool
-        .get()
+    meta.write_buffer.ensure_none_pending()?;
+    profiling::measure_async(Phase::Network, async {
+        let mut conn = pools::get_connection_async(pool).await?;
+        pipe.query_async::<_, ()>(&mut conn as &mut Connection)
+            .await
+            .map_err(|e| WriteError::from_redis(e).into_err())
+    })
+    .await?;
+    meta.mirrors.replicate(pipe);
+    Ok(())
+}
+
+/// Applies the given write like `apply_write_async()`, returning the reply of redis, or None if the write
+/// was buffered
+async fn apply_write_with_reply_async<T: redis::FromRedisValue>(
+    pool: &pools::AsyncRedisPool,
+    meta: &CollectionMeta,
+    pipe: &redis::Pipeline,
+) -> PyResult<Option<T>> {
+    let reply = if meta.write_buffer.has_pending() {
+        meta.write_buffer.push(pipe)?;
+        None
+    } else {
+        let result = profiling::measure_async(Phase::Network, async {
+            match pools::get_connection_async(pool).await {
+                Ok(mut conn) => pipe
+                    .query_async(&mut conn as &mut Connection)
+                    .await
+                    .map_err(WriteError::from_redis),
+                Err(e) => Err(WriteError::from_checkout(e)),
+            }
+        })
+        .await;
+        result
+            .map(Some)
+            .or_else(|e| meta.write_buffer.handle_failure(pipe, e).map(|_| None))?
+    };
+    meta.mirrors.replicate(pipe);
+    Ok(reply)
+}
+
+/// Measures the memory used by the given number of records of the given collection picked at random,
+/// with MEMORY USAGE
+pub(crate) async fn measure_memory_usage_async(
+    pool: &pools::AsyncRedisPool,
+    collection_name: &str,
+    sample: usize,
+) -> PyResult<MemoryUsage> {
+    let ids = get_record_ids_async(pool, collection_name).await?;
+    let records = ids.len();
+    let ids = memory_usage::sample_ids(ids, sample);
+    let mut conn = pools::get_connection_async(pool).await?;
+
+    let usages: Vec<Option<u64>> =
+        memory_usage::generate_memory_usage_pipeline(collection_name, &ids)
+            .query_async(&mut conn as &mut Connection)
+            .await
+            .map_err(|e| PyConnectionError::new_err(e.to_string()))?;
+    Ok(MemoryUsage::new(records, ids, usages))
+}
+
+/// Trains the compression dictionary of the given collection on the string values of `sample` of its records
+/// picked at random, saving it in redis and compressing new values with it. It returns the statistics of
+/// the training
+pub(crate) async fn train_compression_async(
+    pool: &pools::AsyncRedisPool,
+    collection_name: &str,
+    meta: &CollectionMeta,
+    sample: usize,
+) -> PyResult<TrainingStats> {
+    let ids = memory_usage::sample_ids(get_record_ids_async(pool, collection_name).await?, sample);
+    let mut conn = pools::get_connection_async(pool).await?;
+
+    let records: Vec<HashMap<String, Vec<u8>>> =
+        compression::generate_sample_pipeline(collection_name, &ids)
+            .query_async(&mut conn as &mut Connection)
+            .await
+            .map_err(|e| PyConnectionError::new_err(e.to_string()))?;
+    let (dictionary, bytes, stats) = compression::train(
+        collection_name,
+        compression::extract_samples(meta, records)?,
+    )?;
+
+    compression::generate_save_cmd(collection_name, dictionary.id(), &bytes)
+        .query_async(&mut conn as &mut Connection)
         .await
         .map_err(|e| PyConnectionError::new_err(e.to_string()))?;
-    let mut pipe = redis::pipe();
+    meta.compression.set_dictionary(dictionary);
+    Ok(stats)
+}
+
+/// Loads the compression dictionaries of the given collection saved in redis, so that the values compressed
+/// with them can be read and new values are compressed with the current one. It returns whether the
+/// collection has a current dictionary
+pub(crate) async fn load_compression_async(
+    pool: &pools::AsyncRedisPool,
+    collection_name: &str,
+    meta: &CollectionMeta,
+) -> PyResult<bool> {
+    let mut conn = pools::get_connection_async(pool).await?;
+
+    let dictionaries: HashMap<String, Vec<u8>> = redis::cmd("HGETALL")
+        .arg(compression::generate_dictionaries_key(collection_name))
+        .query_async(&mut conn as &mut Connection)
+        .await
+        .map_err(|e| PyConnectionError::new_err(e.to_string()))?;
+    meta.compression.load(collection_name, dictionaries)
+}
+
+/// Gets the ids of a random sample of the records of the given collection, each record
+/// being picked with a probability of `sample`
+pub(crate) async fn sample_record_ids_async(
+    pool: &pools::AsyncRedisPool,
+    collection_name: &str,
+    sample: f64,
+) -> PyResult<Vec<String>> {
+    Ok(get_record_ids_async(pool, collection_name)
+        .await?
+        .into_iter()
+        .filter(|_| verification::is_sampled(sample))
+        .collect())
+}
+
+/// Gets the ids of all the records of the given collection, scanning its keys with SCAN.
+/// Each id is returned once even if SCAN finds its key more than once
+pub(crate) async fn get_record_ids_async(
+    pool: &pools::AsyncRedisPool,
+    collection_name: &str,
+) -> PyResult<Vec<String>> {
+    let mut conn = pools::get_blocking_connection_async(pool).await?;
+    let key_prefix = utils::generate_key_prefix(collection_name);
+
+    let mut cmd = redis::cmd("SCAN");
+    cmd.cursor_arg(0)
+        .arg("MATCH")
+        .arg(utils::generate_collection_key_pattern(collection_name));
+
+    let mut keys = cmd
+        .iter_async::<String>(&mut conn as &mut Connection)
+        .await
+        .map_err(|e| PyConnectionError::new_err(e.to_string()))?;
+
+    let mut ids: Vec<String> = vec![];
+    let mut seen: HashSet<String> = HashSet::new();
+    while let Some(key) = keys.next_item().await {
+        if let Some(id) = key.strip_prefix(&key_prefix) {
+            if seen.insert(id.to_string()) {
+                ids.push(id.to_string());
+            }
+        }
+    }
+
+    Ok(ids)
+}
+
+/// Applies the given repairs to the records of the given collection whose values are in the broken formats
+/// they handle, reading and repairing the records in batches at no more operations per second than the
+/// throttle allows. It returns the number of records repaired
+pub(crate) async fn repair_records_async(
+    pool: &pools::AsyncRedisPool,
+    collection_name: &str,
+    meta: &CollectionMeta,
+    repairs: &[Repair],
+    throttle: &mut Throttle,
+) -> PyResult<u64> {
+    let ids = get_record_ids_async(pool, collection_name).await?;
+    let mut conn = pools::get_blocking_connection_async(pool).await?;
+    let mut repaired: u64 = 0;
+
+    for batch in ids.chunks(throttle.batch_size(REPAIR_BATCH_SIZE)) {
+        let keys: Vec<String> = batch
+            .iter()
+            .map(|id| utils::generate_hash_key(collection_name, id, meta.case_insensitive_keys))
+            .collect();
+        let mut read_pipe = redis::pipe();
+        for key in &keys {
+            read_pipe.hgetall(key);
+        }
+        let records: Vec<HashMap<String, String>> = read_pipe
+            .query_async(&mut conn as &mut Connection)
+            .await
+            .map_err(|e| PyConnectionError::new_err(e.to_string()))?;
+
+        let cmds: Vec<redis::Cmd> = keys
+            .iter()
+            .zip(&records)
+            .filter_map(|(key, fields)| {
+                repairs::generate_repair_cmd(key, &meta.schema, repairs, fields)
+            })
+            .collect();
+        if !cmds.is_empty() {
+            let mut repair_pipe = redis::pipe();
+            for cmd in cmds {
+                repair_pipe.add_command(cmd);
+            }
+
+            let results: Vec<u64> = repair_pipe
+                .query_async(&mut conn as &mut Connection)
+                .await
+                .map_err(|e| PyConnectionError::new_err(e.to_string()))?;
+            meta.mirrors.replicate(&repair_pipe);
+            repaired += results.iter().sum::<u64>();
+        }
+
+        if let Some(delay) = throttle.throttle(batch.len()) {
+            task::sleep(delay).await;
+        }
+    }
+
+    Ok(repaired)
+}
+
+/// Removes the fields that are not in the schema of the given collection from its records, reading and
+/// compacting the records in batches at no more operations per second than the throttle allows. It returns
+/// the number of records compacted
+pub(crate) async fn compact_records_async(
+    pool: &pools::AsyncRedisPool,
+    collection_name: &str,
+    meta: &CollectionMeta,
+    throttle: &mut Throttle,
+) -> PyResult<u64> {
+    let ids = get_record_ids_async(pool, collection_name).await?;
+    let mut conn = pools::get_blocking_connection_async(pool).await?;
+    let mut compacted: u64 = 0;
+
+    for batch in ids.chunks(throttle.batch_size(COMPACT_BATCH_SIZE)) {
+        let keys: Vec<String> = batch
+            .iter()
+            .map(|id| utils::generate_hash_key(collection_name, id, meta.case_insensitive_keys))
+            .collect();
+        let mut read_pipe = redis::pipe();
+        for key in &keys {
+            read_pipe.hkeys(key);
+        }
+        let fields: Vec<Vec<String>> = read_pipe
+            .query_async(&mut conn as &mut Connection)
+            .await
+            .map_err(|e| PyConnectionError::new_err(e.to_string()))?;
+
+        if let Some((pipe, count)) =
+            compaction::generate_compact_pipeline(&meta.schema, &keys, &fields)
+        {
+            pipe.query_async(&mut conn as &mut Connection)
+                .await
+                .map_err(|e| PyConnectionError::new_err(e.to_string()))?;
+            meta.mirrors.replicate(&pipe);
+            compacted += count;
+        }
+
+        if let Some(delay) = throttle.throttle(batch.len()) {
+            task::sleep(delay).await;
+        }
+    }
+
+    Ok(compacted)
+}
+
+/// Gets the ids of the records of the given collection whose indexed fields have the given values, as saved
+/// in redis, using the index of the fields
+pub(crate) async fn get_ids_by_value_async(
+    pool: &pools::AsyncRedisPool,
+    collection_name: &str,
+    meta: &CollectionMeta,
+    fields: &[String],
+    values: &[String],
+) -> PyResult<Vec<String>> {
+    let mut conn = pools::get_connection_async(pool).await?;
+
+    field_indexes::generate_lookup_cmd(collection_name, meta, fields, values)
+        .query_async(&mut conn as &mut Connection)
+        .await
+        .map_err(|e| PyConnectionError::new_err(e.to_string()))
+}
+
+/// Gets the ids of the records of the given collection that match all the given lookups of `find()`, using
+/// their indexes in one network request, unless they only exclude records, in which case all ids are scanned first
+pub(crate) async fn get_ids_by_lookups_async(
+    pool: &pools::AsyncRedisPool,
+    collection_name: &str,
+    lookups: &Lookups,
+) -> PyResult<Vec<String>> {
+    let ids = get_lookup_candidates_async(pool, collection_name, lookups).await?;
+    Ok(lookups.filter_ids(ids))
+}
+
+/// Gets the ids in the index of each of the given lookups of `find()`, preceded by the ids of all the records
+/// of the given collection if the lookups only exclude records, as expected by `Lookups::filter_ids()`
+pub(crate) async fn get_lookup_candidates_async(
+    pool: &pools::AsyncRedisPool,
+    collection_name: &str,
+    lookups: &Lookups,
+) -> PyResult<Vec<Vec<String>>> {
+    let mut ids: Vec<Vec<String>> = match lookups.needs_all_ids() {
+        true => vec![get_record_ids_async(pool, collection_name).await?],
+        false => vec![],
+    };
+    let mut conn = pools::get_connection_async(pool).await?;
+
+    let replies: Vec<Vec<String>> = lookups
+        .to_pipeline()
+        .query_async(&mut conn as &mut Connection)
+        .await
+        .map_err(|e| PyConnectionError::new_err(e.to_string()))?;
+    ids.extend(replies);
+    Ok(ids)
+}
+
+/// Gets the ids of the records of the given collection that match the given lookups, as cached by an identical
+/// call that is not older than the given ttl, or else as got by `get_ids_by_lookups_async()`, caching them.
+/// Empty results are not cached
+pub(crate) async fn get_cached_ids_by_lookups_async(
+    pool: &pools::AsyncRedisPool,
+    collection_name: &str,
+    lookups: &Lookups,
+    cache_ttl: &Expiry,
+) -> PyResult<Vec<String>> {
+    let cache_key = lookups.generate_cache_key(collection_name);
+    let cached: Vec<String> = redis::cmd("LRANGE")
+        .arg(&cache_key)
+        .arg(0)
+        .arg(-1)
+        .query_async(&mut pools::get_connection_async(pool).await? as &mut Connection)
+        .await
+        .map_err(|e| PyConnectionError::new_err(e.to_string()))?;
+    if !cached.is_empty() {
+        return Ok(cached);
+    }
+
+    let ids = get_ids_by_lookups_async(pool, collection_name, lookups).await?;
+    if !ids.is_empty() {
+        let mut conn = pools::get_connection_async(pool).await?;
+        let mut pipe = redis::pipe();
+        pipe.atomic()
+            .del(&cache_key)
+            .ignore()
+            .rpush(&cache_key, &ids)
+            .ignore();
+        cache_ttl.add_cmd(&mut pipe, &cache_key);
+        pipe.ignore()
+            .query_async::<_, ()>(&mut conn as &mut Connection)
+            .await
+            .map_err(|e| PyConnectionError::new_err(e.to_string()))?;
+    }
+    Ok(ids)
+}
+
+/// Gets the ids of the records in the set of linked records at the given key
+pub(crate) async fn get_linked_ids_async(
+    pool: &pools::AsyncRedisPool,
+    link_key: &str,
+) -> PyResult<Vec<String>> {
+    let mut conn = pools::get_connection_async(pool).await?;
+
+    redis::cmd("SMEMBERS")
+        .arg(link_key)
+        .query_async(&mut conn as &mut Connection)
+        .await
+        .map_err(|e| PyConnectionError::new_err(e.to_string()))
+}
+
+/// Registers the range index of the given field in redis and builds it from the values of the records of
+/// the given collection, like `utils::build_range_index()`. It returns the number of records indexed
+pub(crate) async fn build_range_index_async(
+    pool: &pools::AsyncRedisPool,
+    collection_name: &str,
+    meta: &CollectionMeta,
+    field: &str,
+    throttle: &mut Throttle,
+) -> PyResult<u64> {
+    let type_ = meta.schema.get_type(field).unwrap();
+    let mut conn = pools::get_blocking_connection_async(pool).await?;
+    let mut indexed: u64 = 0;
+
+    let register_cmd = Registry::RangeIndexes.generate_register_cmd(collection_name, field);
+    let is_new: bool = register_cmd
+        .query_async(&mut conn as &mut Connection)
+        .await
+        .map_err(|e| PyConnectionError::new_err(e.to_string()))?;
+    meta.mirrors.replicate_cmd(&register_cmd);
+    meta.registered_fields.add(Registry::RangeIndexes, field);
+    if is_new {
+        // any index left over from before the field was registered is stale
+        let mut cmd = redis::cmd("DEL");
+        cmd.arg(utils::generate_range_index_key(collection_name, field))
+            .arg(utils::generate_unscored_ids_key(collection_name, field))
+            .query_async(&mut conn as &mut Connection)
+            .await
+            .map_err(|e| PyConnectionError::new_err(e.to_string()))?;
+        meta.mirrors.replicate_cmd(&cmd);
+        task::sleep(registries::REGISTRATION_DELAY).await;
+    }
+
+    let ids = get_record_ids_async(pool, collection_name).await?;
+    for batch in ids.chunks(throttle.batch_size(INDEX_BATCH_SIZE)) {
+        let keys: Vec<String> = batch
+            .iter()
+            .map(|id| utils::generate_hash_key(collection_name, id, meta.case_insensitive_keys))
+            .collect();
+        let mut read_pipe = redis::pipe();
+        for key in &keys {
+            read_pipe.hget(key, field);
+        }
+        let values: Vec<Option<String>> = read_pipe
+            .query_async(&mut conn as &mut Connection)
+            .await
+            .map_err(|e| PyConnectionError::new_err(e.to_string()))?;
+
+        let mut pipe = redis::pipe();
+        for ((id, key), value) in batch.iter().zip(&keys).zip(values) {
+            // records without the field have no score, like those whose value is None
+            if value.is_some() {
+                indexed += 1;
+            }
+            range_indexes::add_backfill_cmd(
+                &mut pipe,
+                collection_name,
+                field,
+                type_,
+                key,
+                id,
+                value.as_deref(),
+            );
+        }
+        pipe.query_async(&mut conn as &mut Connection)
+            .await
+            .map_err(|e| PyConnectionError::new_err(e.to_string()))?;
+        meta.mirrors.replicate(&pipe);
+
+        if let Some(delay) = throttle.throttle(batch.len()) {
+            task::sleep(delay).await;
+        }
+    }
+
+    let built_cmd = Registry::BuiltRangeIndexes.generate_register_cmd(collection_name, field);
+    built_cmd
+        .query_async::<_, ()>(&mut conn as &mut Connection)
+        .await
+        .map_err(|e| PyConnectionError::new_err(e.to_string()))?;
+    meta.mirrors.replicate_cmd(&built_cmd);
+    meta.registered_fields
+        .add(Registry::BuiltRangeIndexes, field);
+    Ok(indexed)
+}
+
+/// Registers the null index of the given field in redis and builds it from the records of the given collection,
+/// like `utils::build_null_index()`. It returns the number of records that have no value for the field
+pub(crate) async fn build_null_index_async(
+    pool: &pools::AsyncRedisPool,
+    collection_name: &str,
+    meta: &CollectionMeta,
+    field: &str,
+    throttle: &mut Throttle,
+) -> PyResult<u64> {
+    let mut conn = pools::get_blocking_connection_async(pool).await?;
+    let fields = [field.to_string()];
+
+    let register_cmd = Registry::NullIndexes.generate_register_cmd(collection_name, field);
+    let is_new: bool = register_cmd
+        .query_async(&mut conn as &mut Connection)
+        .await
+        .map_err(|e| PyConnectionError::new_err(e.to_string()))?;
+    meta.mirrors.replicate_cmd(&register_cmd);
+    meta.registered_fields.add(Registry::NullIndexes, field);
+    if is_new {
+        // any index left over from before the field was registered is stale
+        let mut cmd = redis::cmd("DEL");
+        cmd.arg(null_indexes::generate_index_key(collection_name, field))
+            .query_async(&mut conn as &mut Connection)
+            .await
+            .map_err(|e| PyConnectionError::new_err(e.to_string()))?;
+        meta.mirrors.replicate_cmd(&cmd);
+        task::sleep(registries::REGISTRATION_DELAY).await;
+    }
+
+    let ids = get_record_ids_async(pool, collection_name).await?;
+    let mut indexed: u64 = 0;
+    for batch in ids.chunks(throttle.batch_size(INDEX_BATCH_SIZE)) {
+        let mut pipe = redis::pipe();
+        for id in batch {
+            let pk = utils::generate_hash_key(collection_name, id, meta.case_insensitive_keys);
+            null_indexes::add_update_cmd(&mut pipe, collection_name, &fields, &pk, id);
+        }
+        let nulls: Vec<u64> = pipe
+            .query_async(&mut conn as &mut Connection)
+            .await
+            .map_err(|e| PyConnectionError::new_err(e.to_string()))?;
+        meta.mirrors.replicate(&pipe);
+        indexed += nulls.iter().sum::<u64>();
+
+        if let Some(delay) = throttle.throttle(batch.len()) {
+            task::sleep(delay).await;
+        }
+    }
+
+    let built_cmd = Registry::BuiltNullIndexes.generate_register_cmd(collection_name, field);
+    built_cmd
+        .query_async::<_, ()>(&mut conn as &mut Connection)
+        .await
+        .map_err(|e| PyConnectionError::new_err(e.to_string()))?;
+    meta.mirrors.replicate_cmd(&built_cmd);
+    meta.registered_fields
+        .add(Registry::BuiltNullIndexes, field);
+    Ok(indexed)
+}
+
+/// Gets the ids of the records of the given collection whose values of the given field are between
+/// the given scores, inclusive, in the order of their values
+pub(crate) async fn get_ids_in_range_async(
+    pool: &pools::AsyncRedisPool,
+    collection_name: &str,
+    field: &str,
+    min: &str,
+    max: &str,
+) -> PyResult<Vec<String>> {
+    let mut conn = pools::get_connection_async(pool).await?;
+
+    redis::cmd("ZRANGEBYSCORE")
+        .arg(utils::generate_range_index_key(collection_name, field))
+        .arg(min)
+        .arg(max)
+        .query_async(&mut conn as &mut Connection)
+        .await
+        .map_err(|e| PyConnectionError::new_err(e.to_string()))
+}
+
+/// Gets the ids of the page of at most `limit` records of the given collection after the given cursor,
+/// in the order of their values of the given field
+pub(crate) async fn get_page_ids_async(
+    pool: &pools::AsyncRedisPool,
+    collection_name: &str,
+    field: &str,
+    cursor: &KeysetCursor,
+    limit: usize,
+) -> PyResult<Vec<String>> {
+    let mut conn = pools::get_connection_async(pool).await?;
+
+    cursor
+        .generate_page_cmd(collection_name, field, limit)
+        .query_async(&mut conn as &mut Connection)
+        .await
+        .map_err(|e| PyConnectionError::new_err(e.to_string()))
+}
+
+/// Gets the ids of the `n` records of the given collection inserted last, latest first, if `latest`,
+/// or else those inserted first, oldest first
+pub(crate) async fn get_ids_by_insertion_order_async(
+    pool: &pools::AsyncRedisPool,
+    collection_name: &str,
+    n: usize,
+    latest: bool,
+) -> PyResult<Vec<String>> {
+    let mut conn = pools::get_connection_async(pool).await?;
+
+    utils::generate_insertion_order_range_cmd(collection_name, n, latest)
+        .query_async(&mut conn as &mut Connection)
+        .await
+        .map_err(|e| PyConnectionError::new_err(e.to_string()))
+}
+
+/// Gets the ids of the `k` records of the given collection whose given vector field is nearest to the given
+/// packed vector, nearest first. The RediSearch index of the field is created the first time it is searched
+pub(crate) async fn knn_async(
+    pool: &pools::AsyncRedisPool,
+    collection_name: &str,
+    field: &str,
+    dimension: usize,
+    vector: &[u8],
+    k: usize,
+) -> PyResult<Vec<String>> {
+    let mut conn = pools::get_connection_async(pool).await?;
+    let search_cmd = vectors::generate_knn_cmd(collection_name, field, vector, k);
+
+    let reply = match search_cmd
+        .query_async::<_, redis::Value>(&mut conn as &mut Connection)
+        .await
+    {
+        Err(e) if vectors::is_missing_index(&e) => {
+            match vectors::generate_create_index_cmd(collection_name, field, dimension)
+                .query_async::<_, ()>(&mut conn as &mut Connection)
+                .await
+            {
+                Err(e) if !vectors::is_existing_index(&e) => return Err(vectors::to_py_err(e)),
+                _ => search_cmd.query_async(&mut conn as &mut Connection).await,
+            }
+        }
+        reply => reply,
+    }
+    .map_err(vectors::to_py_err)?;
+
+    vectors::parse_knn_ids(collection_name, reply)
+}
+
+/// Counts the records of the given collection without a ttl, with a ttl below the thresholds, running
+/// the audit script on one SCAN batch at a time so that redis is not blocked for long, at no more
+/// operations per second than the throttle allows
+pub(crate) async fn audit_ttls_async(
+    pool: &pools::AsyncRedisPool,
+    collection_name: &str,
+    thresholds: &TtlThresholds,
+    throttle: &mut Throttle,
+) -> PyResult<TtlAudit> {
+    let mut conn = pools::get_blocking_connection_async(pool).await?;
+    let mut audit = TtlAudit::default();
+    let mut cursor = "0".to_string();
+
+    loop {
+        let (next_cursor, records, no_ttl, below_threshold, imminent_expiry) = thresholds
+            .generate_audit_cmd(
+                collection_name,
+                &cursor,
+                throttle.batch_size(utils::SCAN_BATCH_SIZE),
+            )
+            .query_async(&mut conn as &mut Connection)
+            .await
+            .map_err(|e| PyConnectionError::new_err(e.to_string()))?;
+        audit.add((records, no_ttl, below_threshold, imminent_expiry));
+        cursor = next_cursor;
+        if cursor == "0" {
+            return Ok(audit);
+        }
+
+        if let Some(delay) = throttle.throttle(records as usize) {
+            task::sleep(delay).await;
+        }
+    }
+}
+
+/// Gets all the records in the collection of the given name, splitting the ids got by one SCAN
+/// into a shard for each worker. The workers read their shards concurrently in batches, each on
+/// its own connection, so there are at most as many workers as connections in the pool.
+/// The records are returned in the order of the shards
+pub(crate) async fn get_all_records_in_parallel_async(
+    pool: &pools::AsyncRedisPool,
+    collection_name: &str,
+    meta: &CollectionMeta,
+    workers: usize,
+) -> PyResult<Vec<Py<PyAny>>> {
+    let ids = get_record_ids_async(pool, collection_name).await?;
+    let pool_size = pool.state().await.max_open as usize;
+    let shard_size = utils::get_shard_size(ids.len(), workers.min(pool_size));
 
-    pipe.del(keys);
+    let shards = ids.chunks(shard_size).map(|shard| async move {
+        let mut records: Vec<Py<PyAny>> = Vec::with_capacity(shard.len());
+        for batch in shard.chunks(utils::PARALLEL_READ_BATCH_SIZE) {
+            records.extend(
+                get_records_by_id_async(pool, collection_name, meta, &batch.to_vec()).await?,
+            );
+        }
+        Ok::<_, PyErr>(records)
+    });
+
+    Ok(futures::future::try_join_all(shards)
+        .await?
+        .into_iter()
+        .flatten()
+        .collect())
+}
+
+/// Gets the records of the given ids as they are saved in redis, for comparison with another store
+pub(crate) async fn get_raw_records_async(
+    pool: &pools::AsyncRedisPool,
+    collection_name: &str,
+    meta: &CollectionMeta,
+    ids: &[String],
+) -> PyResult<Vec<verification::RawRecord>> {
+    let mut conn = pools::get_connection_async(pool).await?;
+
+    let values: Vec<redis::Value> =
+        verification::generate_read_pipeline(collection_name, meta, ids)
+            .query_async(&mut conn as &mut Connection)
+            .await
+            .map_err(|e| PyConnectionError::new_err(e.to_string()))?;
+    verification::parse_raw_records(meta, values)
+}
+
+/// Gets the ids of the records of the given collection found by one SCAN call from the given cursor,
+/// which looks at about `count` keys, with the cursor of the next call, 0 once the scan is complete
+pub(crate) async fn scan_record_ids_async(
+    pool: &pools::AsyncRedisPool,
+    collection_name: &str,
+    cursor: u64,
+    count: usize,
+) -> PyResult<(u64, Vec<String>)> {
+    let mut conn = pools::get_connection_async(pool).await?;
+    let (next_cursor, keys): (u64, Vec<String>) =
+        utils::generate_scan_record_keys_cmd(collection_name, cursor, count)
+            .query_async(&mut conn as &mut Connection)
+            .await
+            .map_err(|e| PyConnectionError::new_err(e.to_string()))?;
+    Ok((next_cursor, utils::keys_to_ids(collection_name, keys)))
+}
+
+/// Exports the records of the given collection, with their counters, into the given sink e.g. a file, in chunks
+/// of `chunk_size` records. Compressed values are exported decompressed. It returns the number of records exported
+/// with the sink, once all of the export is written into it
+pub(crate) async fn export_records_async<W: Write>(
+    pool: &pools::AsyncRedisPool,
+    collection_name: &str,
+    meta: &CollectionMeta,
+    sink: W,
+    chunk_size: usize,
+) -> PyResult<(u64, W)> {
+    let mut writer = ExportWriter::new(sink, collection_name, EXPORT_BUFFER_SIZE)?;
+    let mut chunk: InsertRecords = Vec::with_capacity(chunk_size);
+    let mut exported = 0;
+    let mut cursor = 0;
+
+    loop {
+        let (next_cursor, ids) =
+            scan_record_ids_async(pool, collection_name, cursor, chunk_size).await?;
+        for ids in ids.chunks(chunk_size) {
+            let records = get_raw_records_async(pool, collection_name, meta, ids).await?;
+            exported += utils::add_to_export(
+                collection_name,
+                &mut writer,
+                &mut chunk,
+                chunk_size,
+                ids,
+                records,
+            )?;
+        }
+
+        if next_cursor == 0 {
+            break;
+        }
+        cursor = next_cursor;
+    }
+
+    writer.write_chunk(&chunk)?;
+    let sink = writer.finish()?;
+    Ok((exported + chunk.len() as u64, sink))
+}
+
+/// Gets the snapshot of the schema of the given collection last saved in redis,
+/// which is empty if none was saved
+pub(crate) async fn get_schema_snapshot_async(
+    pool: &pools::AsyncRedisPool,
+    collection_name: &str,
+) -> PyResult<SchemaSnapshot> {
+    let mut conn = pools::get_connection_async(pool).await?;
 
+    redis::cmd("HGETALL")
+        .arg(utils::generate_schema_snapshot_key(collection_name))
+        .query_async(&mut conn as &mut Connection)
+        .await
+        .map_err(|e| PyConnectionError::new_err(e.to_string()))
+}
+
+/// Saves the snapshot of the given schema of the given collection in redis, replacing any older snapshot
+pub(crate) async fn save_schema_snapshot_async(
+    pool: &pools::AsyncRedisPool,
+    collection_name: &str,
+    meta: &CollectionMeta,
+) -> PyResult<()> {
+    let mut conn = pools::get_connection_async(pool).await?;
+
+    let pipe = migrations::generate_save_snapshot_pipeline(collection_name, &meta.schema);
     pipe.query_async(&mut conn as &mut Connection)
         .await
+        .map_err(|e| PyConnectionError::new_err(e.to_string()))?;
+    meta.mirrors.replicate(&pipe);
+    Ok(())
+}
+
+/// Gets the usage and the limits of the quota of the given namespace
+pub(crate) async fn get_quota_async(
+    pool: &pools::AsyncRedisPool,
+    namespace: &str,
+) -> PyResult<Quota> {
+    let mut conn = pools::get_connection_async(pool).await?;
+    quotas::generate_get_cmd(&utils::generate_quota_key(namespace))
+        .query_async(&mut conn as &mut Connection)
+        .await
+        .map(Quota::from_reply)
         .map_err(|e| PyConnectionError::new_err(e.to_string()))
 }
 
+/// Sets the limits of the quota of the given namespace, removing those that are not given
+pub(crate) async fn set_quota_async(
+    pool: &pools::AsyncRedisPool,
+    namespace: &str,
+    max_records: Option<u64>,
+    max_bytes: Option<u64>,
+) -> PyResult<()> {
+    let mut conn = pools::get_connection_async(pool).await?;
+    quotas::generate_set_pipeline(
+        &utils::generate_quota_key(namespace),
+        max_records,
+        max_bytes,
+    )
+    .query_async(&mut conn as &mut Connection)
+    .await
+    .map_err(|e| PyConnectionError::new_err(e.to_string()))
+}
+
+/// Measures the round-trip latencies of the given number of PINGs to redis, made one after the other
+/// on one connection of the pool
+pub(crate) async fn measure_latency_async(
+    pool: &pools::AsyncRedisPool,
+    samples: usize,
+) -> PyResult<LatencyStats> {
+    let start = Instant::now();
+    let mut conn = pools::get_connection_async(pool).await?;
+    let checkout = start.elapsed();
+
+    let mut latencies: Vec<Duration> = Vec::with_capacity(samples);
+    for _ in 0..samples {
+        let start = Instant::now();
+        redis::cmd("PING")
+            .query_async::<_, ()>(&mut conn as &mut Connection)
+            .await
+            .map_err(|e| PyConnectionError::new_err(e.to_string()))?;
+        latencies.push(start.elapsed());
+    }
+
+    Ok(LatencyStats::new(checkout, latencies))
+}
+
+/// Gets the events in the change stream of the given collection that come after the event
+/// of the given id, as dictionaries
+pub(crate) async fn get_changes_async(
+    pool: &pools::AsyncRedisPool,
+    collection_name: &str,
+    meta: &CollectionMeta,
+    after: &str,
+    count: usize,
+) -> PyResult<Vec<Py<PyAny>>> {
+    let mut conn = pools::get_connection_async(pool).await?;
+
+    let result: redis::Value = utils::generate_read_changes_cmd(collection_name, after, count)
+        .query_async(&mut conn as &mut Connection)
+        .await
+        .map_err(|e| PyConnectionError::new_err(e.to_string()))?;
+    utils::parse_change_events(collection_name, meta, &result)
+}
+
 /// Gets the records for the given collection name in redis, with the given ids
 pub(crate) async fn get_records_by_id_async(
-    pool: &mobc::Pool<mobc_redis::RedisConnectionManager>,
+    pool: &pools::AsyncRedisPool,
     collection_name: &str,
     meta: &CollectionMeta,
     ids: &Vec<String>,
 ) -> PyResult<Vec<Py<PyAny>>> {
-    let ids: Vec<String> = ids
+    let keys: Vec<String> = ids
         .into_iter()
-        .map(|k| utils::generate_hash_key(collection_name, &k.to_string()))
+        .map(|k| {
+            utils::generate_hash_key(collection_name, &k.to_string(), meta.case_insensitive_keys)
+        })
+        .collect();
+
+    let mut records: Vec<Py<PyAny>> = Vec::with_capacity(keys.len());
+    for batch in meta.batch_sizer.split_reads(&keys) {
+        records.extend(
+            run_script(
+                pool,
+                collection_name,
+                meta,
+                |pipe| {
+                    pipe.cmd("EVAL")
+                        .arg(SELECT_ALL_FIELDS_FOR_SOME_IDS_SCRIPT)
+                        .arg(batch.len())
+                        .arg(batch)
+                        .arg(&meta.nested_fields);
+                    Ok(())
+                },
+                |data| Python::with_gil(|py| meta.model_type.call(py, (), Some(data))),
+            )
+            .await?,
+        );
+    }
+    Ok(records)
+}
+
+/// Gets the records in the collection of the given name from redis with the given ids as dictionaries,
+/// with the records nested in them also as dictionaries, without constructing any models
+pub(crate) async fn get_raw_records_by_id_async(
+    pool: &pools::AsyncRedisPool,
+    collection_name: &str,
+    meta: &CollectionMeta,
+    ids: &[String],
+) -> PyResult<Vec<Py<PyAny>>> {
+    let ids: Vec<String> = ids
+        .iter()
+        .map(|k| {
+            utils::generate_hash_key(collection_name, &k.to_string(), meta.case_insensitive_keys)
+        })
         .collect();
 
     run_script(
         pool,
-        meta,
+        collection_name,
+        &utils::generate_raw_read_meta(meta),
         |pipe| {
             pipe.cmd("EVAL")
                 .arg(SELECT_ALL_FIELDS_FOR_SOME_IDS_SCRIPT)
@@ -96,148 +1493,380 @@ pub(crate) async fn get_records_by_id_async(
                 .arg(&meta.nested_fields);
             Ok(())
         },
-        |data| Python::with_gil(|py| meta.model_type.call(py, (), Some(data.into_py_dict(py)))),
+        |data| Ok(data.into()),
     )
     .await
 }
 
+/// Gets the page of at most `limit` records of the given collection at the given cursor, with only the given
+/// fields, and the total number of records in the collection
+pub(crate) async fn browse_records_async(
+    pool: &pools::AsyncRedisPool,
+    collection_name: &str,
+    meta: &CollectionMeta,
+    cursor: BrowseCursor,
+    limit: usize,
+    fields: &Vec<String>,
+) -> PyResult<(BrowsedPage, Vec<Py<PyAny>>)> {
+    // the connection is given back before the records are read on another one
+    let reply = {
+        let mut conn = pools::get_connection_async(pool).await?;
+        cursor
+            .generate_browse_cmd(collection_name, limit)
+            .query_async(&mut conn as &mut Connection)
+            .await
+            .map_err(|e| PyConnectionError::new_err(e.to_string()))?
+    };
+
+    let page = BrowsedPage::from_reply(collection_name, reply);
+    if page.ids.is_empty() {
+        return Ok((page, vec![]));
+    }
+
+    let records =
+        get_partial_records_by_id_async(pool, collection_name, meta, &page.ids, fields, false)
+            .await?;
+    Ok((page, records))
+}
+
+/// Gets the value of the given non-nested field of the record of the given id, converted to the type of the field,
+/// or None if the record or its value is not in redis. Records of older format versions are read in full
+/// by `get_partial_records_by_id_async()` instead so that they are upgraded
+pub(crate) async fn get_field_value_async(
+    pool: &pools::AsyncRedisPool,
+    collection_name: &str,
+    meta: &CollectionMeta,
+    id: &str,
+    field: &str,
+) -> PyResult<Option<Py<PyAny>>> {
+    if meta.versioning.is_some() {
+        let ids = vec![id.to_string()];
+        let fields = vec![field.to_string()];
+        let records =
+            get_partial_records_by_id_async(pool, collection_name, meta, &ids, &fields, false)
+                .await?;
+        return utils::extract_field_value(records, field);
+    }
+
+    let mut conn = pools::get_connection_async(pool).await?;
+    let values: Vec<redis::Value> =
+        utils::generate_get_field_pipeline(collection_name, meta, id, field)
+            .query_async(&mut conn as &mut Connection)
+            .await
+            .map_err(|e| PyConnectionError::new_err(e.to_string()))?;
+    utils::convert_field_value(collection_name, meta, id, field, &values)
+}
+
 /// Gets records in the collection of the given name from redis with the given ids,
 /// returning a vector of dictionaries with only the fields specified for each record
 pub(crate) async fn get_partial_records_by_id_async(
-    pool: &mobc::Pool<mobc_redis::RedisConnectionManager>,
+    pool: &pools::AsyncRedisPool,
     collection_name: &str,
     meta: &CollectionMeta,
     ids: &Vec<String>,
     fields: &Vec<String>,
+    nested_as_models: bool,
 ) -> PyResult<Vec<Py<PyAny>>> {
     let ids: Vec<String> = ids
         .into_iter()
-        .map(|k| utils::generate_hash_key(collection_name, &k.to_string()))
+        .map(|k| {
+            utils::generate_hash_key(collection_name, &k.to_string(), meta.case_insensitive_keys)
+        })
         .collect();
 
+    let fields_to_request = utils::get_fields_to_request(meta, fields);
+    let read_meta = utils::generate_partial_read_meta(meta, fields, nested_as_models)?;
+
     run_script(
         pool,
-        meta,
+        collection_name,
+        &read_meta,
         |pipe| {
-            pipe.cmd("EVAL")
-                .arg(SELECT_SOME_FIELDS_FOR_SOME_IDS_SCRIPT)
-                .arg(ids.len())
-                .arg(ids)
-                .arg(fields)
-                .arg(&meta.nested_fields);
+            // records of older format versions are upgraded from all their fields
+            let script = if meta.versioning.is_some() {
+                pipe.cmd("EVAL")
+                    .arg(SELECT_ALL_FIELDS_FOR_SOME_IDS_SCRIPT)
+                    .arg(ids.len())
+                    .arg(ids)
+            } else {
+                pipe.cmd("EVAL")
+                    .arg(SELECT_SOME_FIELDS_FOR_SOME_IDS_SCRIPT)
+                    .arg(ids.len())
+                    .arg(ids)
+                    .arg(&fields_to_request)
+            };
+            script.arg(&meta.nested_fields);
             Ok(())
         },
-        |data| Ok(Python::with_gil(|py| data.into_py(py))),
+        |data| {
+            // the keys are a copy so deleting from the record while going through them is safe
+            for key in data.keys() {
+                if !utils::is_requested(fields, key.extract()?) {
+                    data.del_item(key)?;
+                }
+            }
+            Ok(data.into())
+        },
     )
     .await
 }
 
-/// Gets all records in the collection of the given name from redis,
+/// Gets the given page of the records in the collection of the given name from redis,
 /// returning a vector of dictionaries with only the fields specified for each record
 pub(crate) async fn get_all_partial_records_in_collection_async(
-    pool: &mobc::Pool<mobc_redis::RedisConnectionManager>,
+    pool: &pools::AsyncRedisPool,
     collection_name: &str,
     meta: &CollectionMeta,
     fields: &Vec<String>,
+    nested_as_models: bool,
+    page: Page,
 ) -> PyResult<Vec<Py<PyAny>>> {
+    let fields_to_request = utils::get_fields_to_request(meta, fields);
+    let read_meta = utils::generate_partial_read_meta(meta, fields, nested_as_models)?;
+
     run_script(
         pool,
-        meta,
+        collection_name,
+        &read_meta,
         |pipe| {
+            // records of older format versions are upgraded from all their fields
+            let is_versioned = meta.versioning.is_some();
+            let script = if is_versioned {
+                SELECT_ALL_FIELDS_FOR_ALL_IDS_SCRIPT
+            } else {
+                SELECT_SOME_FIELDS_FOR_ALL_IDS_SCRIPT
+            };
             pipe.cmd("EVAL")
-                .arg(SELECT_SOME_FIELDS_FOR_ALL_IDS_SCRIPT)
+                .arg(script)
                 .arg(0)
-                .arg(utils::generate_collection_key_pattern(collection_name))
-                .arg(fields)
-                .arg(&meta.nested_fields);
+                .arg(utils::generate_collection_key_pattern(collection_name));
+            page.add_args(pipe);
+            if !is_versioned {
+                pipe.arg(&fields_to_request);
+            }
+            pipe.arg(&meta.nested_fields);
             Ok(())
         },
-        |data| Ok(Python::with_gil(|py| data.into_py(py))),
+        |data| {
+            // the keys are a copy so deleting from the record while going through them is safe
+            for key in data.keys() {
+                if !utils::is_requested(fields, key.extract()?) {
+                    data.del_item(key)?;
+                }
+            }
+            Ok(data.into())
+        },
     )
     .await
 }
 
-/// Gets all the records that are in the given collection
+/// Gets the given page of the records that are in the given collection whose fields are equal to the values in
+/// the given filter, handling those that cannot be read as on_error says. It returns the records read with the errors
+/// of those that were not, if they are collected
 pub(crate) async fn get_all_records_in_collection_async(
-    pool: &mobc::Pool<mobc_redis::RedisConnectionManager>,
+    pool: &pools::AsyncRedisPool,
     collection_name: &str,
     meta: &CollectionMeta,
-) -> PyResult<Vec<Py<PyAny>>> {
-    run_script(
+    filter: &[(String, String)],
+    page: Page,
+    on_error: OnError,
+) -> PyResult<(Vec<Py<PyAny>>, Vec<ReadError>)> {
+    run_script_with_errors(
+        pool,
+        collection_name,
+        meta,
+        |pipe| {
+            utils::add_get_all_cmd(pipe, collection_name, meta, filter, page);
+            Ok(())
+        },
+        |data| Python::with_gil(|py| meta.model_type.call(py, (), Some(data))),
+        on_error,
+    )
+    .await
+}
+
+/// Gets the given page of the records of the given collection whose fields are equal to the values in the given
+/// filter, in the order of their values of the given field, like `utils::get_ordered_records_in_collection()`
+pub(crate) async fn get_ordered_records_in_collection_async(
+    pool: &pools::AsyncRedisPool,
+    collection_name: &str,
+    meta: &CollectionMeta,
+    ordering: &Ordering,
+    filter: &[(String, String)],
+    page: Page,
+    on_error: OnError,
+) -> PyResult<(Vec<Py<PyAny>>, Vec<ReadError>)> {
+    let keys: Vec<String> =
+        get_ordered_ids_async(pool, collection_name, meta, ordering, filter, page)
+            .await?
+            .iter()
+            .map(|id| utils::generate_hash_key(collection_name, id, meta.case_insensitive_keys))
+            .collect();
+
+    run_script_with_errors(
         pool,
+        collection_name,
         meta,
         |pipe| {
             pipe.cmd("EVAL")
-                .arg(SELECT_ALL_FIELDS_FOR_ALL_IDS_SCRIPT)
-                .arg(0)
-                .arg(utils::generate_collection_key_pattern(collection_name))
+                .arg(SELECT_ALL_FIELDS_FOR_SOME_IDS_SCRIPT)
+                .arg(keys.len())
+                .arg(&keys)
                 .arg(&meta.nested_fields);
             Ok(())
         },
-        |data| Python::with_gil(|py| meta.model_type.call(py, (), Some(data.into_py_dict(py)))),
+        |data| Python::with_gil(|py| meta.model_type.call(py, (), Some(data))),
+        on_error,
     )
     .await
 }
 
+/// Gets the ids of the given page of the records of the given collection whose fields are equal to the values
+/// in the given filter, in the order of their values of the given field, descending if desc
+pub(crate) async fn get_ordered_ids_async(
+    pool: &pools::AsyncRedisPool,
+    collection_name: &str,
+    meta: &CollectionMeta,
+    ordering: &Ordering,
+    filter: &[(String, String)],
+    page: Page,
+) -> PyResult<Vec<String>> {
+    refresh_registered_fields_async(pool, collection_name, meta).await?;
+    let mut conn = pools::get_connection_async(pool).await?;
+
+    ordering
+        .generate_ids_cmd(collection_name, meta, filter, page)
+        .query_async(&mut conn as &mut Connection)
+        .await
+        .map_err(|e| PyConnectionError::new_err(e.to_string()))
+}
+
 /// Runs a lua script, and handles the response, transforming it into a list of hashmaps which
 /// is then transformed into a list of Py<PyAny> using the item_parser function
 pub(crate) async fn run_script<T, F>(
-    pool: &mobc::Pool<mobc_redis::RedisConnectionManager>,
+    pool: &pools::AsyncRedisPool,
+    collection_name: &str,
     meta: &CollectionMeta,
     script: T,
     item_parser: F,
 ) -> PyResult<Vec<Py<PyAny>>>
 where
     T: FnOnce(&mut redis::Pipeline) -> PyResult<()>,
-    F: FnOnce(HashMap<String, Py<PyAny>>) -> PyResult<Py<PyAny>> + Copy,
+    F: FnOnce(&PyDict) -> PyResult<Py<PyAny>> + Copy,
 {
-    let mut conn = pool
-        .get()
-        .await
-        .map_err(|e| PyConnectionError::new_err(e.to_string()))?;
+    let (records, _) = run_script_with_errors(
+        pool,
+        collection_name,
+        meta,
+        script,
+        item_parser,
+        OnError::Raise,
+    )
+    .await?;
+    Ok(records)
+}
+
+/// Runs a lua script like run_script, handling the records that cannot be read as on_error says.
+/// It returns the records read with the errors of those that were not, if they are collected
+pub(crate) async fn run_script_with_errors<T, F>(
+    pool: &pools::AsyncRedisPool,
+    collection_name: &str,
+    meta: &CollectionMeta,
+    script: T,
+    item_parser: F,
+    on_error: OnError,
+) -> PyResult<(Vec<Py<PyAny>>, Vec<ReadError>)>
+where
+    T: FnOnce(&mut redis::Pipeline) -> PyResult<()>,
+    F: FnOnce(&PyDict) -> PyResult<Py<PyAny>> + Copy,
+{
+    let mut errors: Vec<ReadError> = vec![];
+    let mut conn =
+        profiling::measure_async(Phase::Network, pools::get_connection_async(pool)).await?;
     let mut pipe = redis::pipe();
 
-    script(&mut pipe)?;
+    profiling::measure(Phase::Serialize, || script(&mut pipe))?;
 
-    let result: redis::Value = pipe
-        .query_async(&mut conn as &mut Connection)
-        .await
-        .or_else(|e| Err(PyConnectionError::new_err(e.to_string())))?;
+    let result: redis::Value = profiling::measure_async(
+        Phase::Network,
+        pipe.query_async(&mut conn as &mut Connection),
+    )
+    .await
+    .or_else(|e| Err(PyConnectionError::new_err(e.to_string())))?;
 
-    let results = result
-        .as_sequence()
-        .ok_or_else(|| py_value_error!(result, "Response from redis is of unexpected shape"))?
-        .get(0)
-        .ok_or_else(|| py_value_error!(result, "Response from redis is of unexpected shape"))?
-        .as_sequence()
-        .ok_or_else(|| py_value_error!(result, "Response from redis is of unexpected shape"))?;
+    let mut results = profiling::measure(Phase::Parse, || utils::extract_script_results(result))?;
+
+    // the script reads the records nested directly in the records; deeper ones are read level by level
+    for level in 2..=meta.nested_depth {
+        let keys = utils::collect_unhydrated_keys(&mut results, &meta.schema, level)?;
+        if keys.is_empty() {
+            break;
+        }
+
+        let values: Vec<redis::Value> = profiling::measure_async(
+            Phase::Network,
+            utils::generate_hydration_pipeline(&keys).query_async(&mut conn as &mut Connection),
+        )
+        .await
+        .map_err(|e| PyConnectionError::new_err(e.to_string()))?;
+        utils::fill_unhydrated_records(&mut results, &meta.schema, level, values)?;
+    }
+    meta.batch_sizer.observe(&results);
 
     let empty_value = redis::Value::Bulk(vec![]);
-    let mut list_of_results: Vec<Py<PyAny>> = Vec::with_capacity(results.len());
-
-    for item in results {
-        if *item != empty_value {
-            match item.as_map_iter() {
-                None => return Err(py_value_error!(item, "redis value is not a map")),
-                Some(item) => {
-                    let data = item
-                        .map(|(k, v)| {
-                            let key = redis_to_py::<String>(k)?;
-                            let value = match meta.schema.get_type(&key) {
-                                Some(field_type) => field_type.redis_to_py(v),
-                                None => {
-                                    Err(py_key_error!(&key, "key found in data but not in schema"))
-                                }
-                            }?;
-                            Ok((key, value))
-                        })
-                        .collect::<PyResult<HashMap<String, Py<PyAny>>>>()?;
-                    let data = item_parser(data)?;
-                    list_of_results.push(data);
+    let mut records: Vec<utils::ParsedRecord> = Vec::with_capacity(results.len());
+
+    let mut upgrade_cmds: Vec<redis::Cmd> = vec![];
+
+    profiling::measure(Phase::Parse, || -> PyResult<()> {
+        for item in &results {
+            if *item != empty_value {
+                match utils::parse_record(collection_name, meta, item) {
+                    Ok((record, upgrade_cmd)) => {
+                        upgrade_cmds.extend(upgrade_cmd);
+                        records.push(record);
+                    }
+                    Err(e) => on_error.handle(&mut errors, None, e)?,
                 }
             }
         }
+        Ok(())
+    })?;
+
+    if !upgrade_cmds.is_empty() {
+        let mut upgrade_pipe = redis::pipe();
+        for cmd in upgrade_cmds {
+            upgrade_pipe.add_command(cmd).ignore();
+        }
+        profiling::measure_async(
+            Phase::Network,
+            upgrade_pipe.query_async::<_, ()>(&mut conn as &mut Connection),
+        )
+        .await
+        .map_err(|e| PyConnectionError::new_err(e.to_string()))?;
+        meta.mirrors.replicate(&upgrade_pipe);
     }
 
-    Ok(list_of_results)
+    let counter_keys = utils::generate_counter_keys(collection_name, meta, &records);
+    if !counter_keys.is_empty() {
+        let values: Vec<redis::Value> = profiling::measure_async(
+            Phase::Network,
+            redis::cmd("MGET")
+                .arg(counter_keys)
+                .query_async(&mut conn as &mut Connection),
+        )
+        .await
+        .map_err(|e| PyConnectionError::new_err(e.to_string()))?;
+        utils::merge_counter_values(meta, &mut records, &values)?;
+    }
+
+    let records = utils::convert_records(
+        collection_name,
+        meta,
+        records,
+        item_parser,
+        on_error,
+        &mut errors,
+    )?;
+    Ok((records, errors))
 }
@@ -2,6 +2,8 @@
 //! I had to get some of the methods because it was conflicting with the pyo3 version of this project
 //! But all the code is copied as is from [pyo3-asyncio version 1.16.0](https://docs.rs/pyo3-asyncio/)
 
+use std::future::Future;
+
 use futures::channel::oneshot;
 use once_cell::sync::OnceCell;
 use pyo3::{
@@ -71,6 +73,8 @@ fn copy_context(py: Python) -> PyResult<&PyAny> {
 pub struct TaskLocals {
     /// Track the event loop of the Python task
     event_loop: PyObject,
+    /// Track the contextvars of the Python task
+    context: PyObject,
 }
 
 impl TaskLocals {
@@ -78,6 +82,7 @@ impl TaskLocals {
     pub fn new(event_loop: &PyAny) -> Self {
         Self {
             event_loop: event_loop.into(),
+            context: event_loop.py().None(),
         }
     }
 
@@ -87,8 +92,11 @@ impl TaskLocals {
     }
 
     /// Manually provide the contextvars for the current task.
-    pub fn with_context(self, _context: &PyAny) -> Self {
-        Self { ..self }
+    pub fn with_context(self, context: &PyAny) -> Self {
+        Self {
+            context: context.into(),
+            ..self
+        }
     }
 
     /// Capture the current task's contextvars
@@ -100,6 +108,11 @@ impl TaskLocals {
     pub fn event_loop<'p>(&self, py: Python<'p>) -> &'p PyAny {
         self.event_loop.clone().into_ref(py)
     }
+
+    /// Get a reference to the python context
+    pub fn context<'p>(&self, py: Python<'p>) -> &'p PyAny {
+        self.context.clone().into_ref(py)
+    }
 }
 
 #[pyclass]
@@ -172,3 +185,40 @@ fn dump_err(py: Python<'_>) -> impl FnOnce(PyErr) + '_ {
         e.print_and_set_sys_last_vars(py);
     }
 }
+
+/// Convert a Python `awaitable` into a Rust Future
+///
+/// This function simply forwards the future and the task locals to the Python event loop, and waits
+/// for the result of the awaitable. As the event loop is not on the current thread, the awaitable is
+/// scheduled on it with `call_soon_threadsafe`.
+///
+/// # Arguments
+/// * `locals` - The task locals of the event loop on which to run the awaitable
+/// * `awaitable` - The Python `awaitable` to be converted
+pub fn into_future_with_locals(
+    locals: &TaskLocals,
+    awaitable: &PyAny,
+) -> PyResult<impl Future<Output = PyResult<PyObject>> + Send> {
+    let py = awaitable.py();
+    let (tx, rx) = oneshot::channel();
+
+    call_soon_threadsafe(
+        locals.event_loop(py),
+        py.None().into_ref(py),
+        (PyEnsureFuture {
+            awaitable: awaitable.into(),
+            tx: Some(tx),
+        },),
+    )?;
+
+    Ok(async move {
+        match rx.await {
+            Ok(item) => item,
+            Err(_) => Python::with_gil(|py| {
+                Err(PyErr::from_value(
+                    asyncio(py)?.call_method0("CancelledError")?,
+                ))
+            }),
+        }
+    })
+}
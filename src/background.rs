@@ -0,0 +1,245 @@
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use async_std::task;
+use once_cell::sync::OnceCell;
+use pyo3::exceptions::{PyTypeError, PyValueError};
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+
+use crate::asyncio::{self, TaskLocals};
+
+/// The periodic jobs running in the background of a store e.g. retention, change consumers or scheduled backups,
+/// by their names. This is shared by all handles of the store
+#[derive(Clone, Default)]
+pub(crate) struct BackgroundTasks {
+    tasks: Arc<Mutex<HashMap<String, BackgroundTask>>>,
+}
+
+/// A periodic job running in the background, with the number of times it has run
+struct BackgroundTask {
+    handle: TaskHandle,
+    runs: Arc<AtomicU64>,
+}
+
+/// The handle of a job running in the background
+enum TaskHandle {
+    /// The asyncio future of a job that awaits python awaitables on the event loop of the store
+    Future(Py<PyAny>),
+    /// The task of a job of orredis itself, which runs on the async-std executor without any event loop
+    /// e.g. a scheduled backup of a sync store, with the error that stopped it, if any, once it is done
+    Native {
+        task: task::JoinHandle<()>,
+        outcome: Arc<OnceCell<Option<String>>>,
+    },
+}
+
+impl BackgroundTasks {
+    /// Starts a job of the given name that awaits the awaitable returned by the coroutine factory, then waits
+    /// for the interval before doing so again, until it is cancelled or one of the awaitables raises an error.
+    /// A job whose name is that of one that is still running is rejected
+    pub(crate) fn spawn(
+        &self,
+        py: Python,
+        name: String,
+        coroutine_factory: Py<PyAny>,
+        interval: Duration,
+    ) -> PyResult<()> {
+        let mut tasks = self.tasks.lock().unwrap_or_else(|e| e.into_inner());
+        ensure_not_running(py, &tasks, &name)?;
+
+        let locals = asyncio::async_std::get_current_locals(py)?;
+        let runs: Arc<AtomicU64> = Default::default();
+        let future = asyncio::async_std::future_into_py_with_locals(
+            py,
+            locals.clone(),
+            // Store the current locals in task-local data
+            asyncio::async_std::scope(
+                locals.clone(),
+                run_periodically(locals, coroutine_factory, interval, runs.clone()),
+            ),
+        )?;
+
+        tasks.insert(
+            name,
+            BackgroundTask {
+                handle: TaskHandle::Future(future.into()),
+                runs,
+            },
+        );
+        Ok(())
+    }
+
+    /// Starts a job of the given name that runs the future returned by `job` on the async-std executor, so that
+    /// it needs no event loop, until it is cancelled or the future is done. The future is given the counter
+    /// of the runs of the job. A job whose name is that of one that is still running is rejected
+    pub(crate) fn spawn_native<F, Fut>(&self, py: Python, name: String, job: F) -> PyResult<()>
+    where
+        F: FnOnce(Arc<AtomicU64>) -> Fut,
+        Fut: Future<Output = PyResult<()>> + Send + 'static,
+    {
+        let mut tasks = self.tasks.lock().unwrap_or_else(|e| e.into_inner());
+        ensure_not_running(py, &tasks, &name)?;
+
+        let runs: Arc<AtomicU64> = Default::default();
+        let outcome: Arc<OnceCell<Option<String>>> = Default::default();
+        let future = job(runs.clone());
+        let task = task::spawn({
+            let outcome = outcome.clone();
+            async move {
+                let _ = outcome.set(future.await.err().map(|e| e.to_string()));
+            }
+        });
+
+        tasks.insert(
+            name,
+            BackgroundTask {
+                handle: TaskHandle::Native { task, outcome },
+                runs,
+            },
+        );
+        Ok(())
+    }
+
+    /// Whether a job of the given name is still running
+    pub(crate) fn is_running(&self, py: Python, name: &str) -> PyResult<bool> {
+        match self
+            .tasks
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .get(name)
+        {
+            Some(task) => task.handle.is_running(py),
+            None => Ok(false),
+        }
+    }
+
+    /// Gets a dictionary of the name of each job to a dictionary with the keys: "running", whether it is still
+    /// running; "runs", the number of times its awaitable has completed; and "error", the error that stopped it if any
+    pub(crate) fn to_py(&self, py: Python) -> PyResult<Py<PyAny>> {
+        let tasks = self.tasks.lock().unwrap_or_else(|e| e.into_inner());
+        let result = PyDict::new(py);
+        for (name, task) in tasks.iter() {
+            let item = PyDict::new(py);
+            item.set_item("running", task.handle.is_running(py)?)?;
+            item.set_item("runs", task.runs.load(Ordering::Relaxed))?;
+            item.set_item("error", task.handle.get_error(py)?)?;
+            result.set_item(name, item)?;
+        }
+        Ok(result.into_py(py))
+    }
+
+    /// Cancels the job of the given name and forgets it, returning whether there was such a job
+    pub(crate) fn cancel(&self, py: Python, name: &str) -> PyResult<bool> {
+        let task = self
+            .tasks
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .remove(name);
+        match task {
+            Some(task) => {
+                task.handle.cancel(py)?;
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    /// Cancels all jobs and forgets them, as when the store is closed
+    pub(crate) fn cancel_all(&self, py: Python) -> PyResult<()> {
+        let tasks: Vec<BackgroundTask> = self
+            .tasks
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .drain()
+            .map(|(_, task)| task)
+            .collect();
+        for task in tasks {
+            task.handle.cancel(py)?;
+        }
+        Ok(())
+    }
+}
+
+impl TaskHandle {
+    /// Whether the job is still running
+    fn is_running(&self, py: Python) -> PyResult<bool> {
+        match self {
+            Self::Future(future) => Ok(!future.call_method0(py, "done")?.extract::<bool>(py)?),
+            Self::Native { outcome, .. } => Ok(outcome.get().is_none()),
+        }
+    }
+
+    /// Gets the error that stopped the job, if it is done because of one
+    fn get_error(&self, py: Python) -> PyResult<Option<String>> {
+        match self {
+            Self::Future(future) => {
+                let future = future.as_ref(py);
+                if !future.call_method0("done")?.extract::<bool>()?
+                    || future.call_method0("cancelled")?.extract::<bool>()?
+                {
+                    return Ok(None);
+                }
+                let exception = future.call_method0("exception")?;
+                Ok((!exception.is_none()).then(|| exception.to_string()))
+            }
+            Self::Native { outcome, .. } => Ok(outcome.get().cloned().flatten()),
+        }
+    }
+
+    /// Cancels the job. A native job is cancelled in the background, at the point it is waiting at
+    fn cancel(self, py: Python) -> PyResult<()> {
+        match self {
+            Self::Future(future) => {
+                future.call_method0(py, "cancel")?;
+            }
+            Self::Native { task, .. } => {
+                task::spawn(task.cancel());
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Fails if the job of the given name among the given ones is still running
+fn ensure_not_running(
+    py: Python,
+    tasks: &HashMap<String, BackgroundTask>,
+    name: &str,
+) -> PyResult<()> {
+    match tasks.get(name) {
+        Some(task) if task.handle.is_running(py)? => Err(PyValueError::new_err(format!(
+            "a background task named {:?} is already running. Cancel it first",
+            name
+        ))),
+        _ => Ok(()),
+    }
+}
+
+/// Awaits the awaitable returned by the coroutine factory every `interval` on the event loop of the given locals,
+/// counting the runs, until one of the awaitables raises an error
+async fn run_periodically(
+    locals: TaskLocals,
+    coroutine_factory: Py<PyAny>,
+    interval: Duration,
+    runs: Arc<AtomicU64>,
+) -> PyResult<()> {
+    loop {
+        let run = Python::with_gil(|py| {
+            let awaitable = coroutine_factory.call0(py)?.into_ref(py);
+            if !awaitable.hasattr("__await__")? {
+                return Err(PyTypeError::new_err(format!(
+                    "coroutine_factory should return an awaitable, got {}",
+                    awaitable.get_type().name()?
+                )));
+            }
+            asyncio::into_future_with_locals(&locals, awaitable)
+        })?;
+        run.await?;
+        runs.fetch_add(1, Ordering::Relaxed);
+        task::sleep(interval).await;
+    }
+}
@@ -0,0 +1,340 @@
+use std::collections::{HashMap, VecDeque};
+use std::fs;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use async_std::task;
+use chrono::Utc;
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use pyo3::types::{PyDict, PyString};
+
+use crate::async_utils;
+use crate::background::BackgroundTasks;
+use crate::backups::{self, BackupTarget};
+use crate::parsers;
+use crate::pools;
+use crate::store::CollectionMeta;
+use crate::utils;
+
+/// The backups scheduled on a store, by their targets. Each schedule is a job among the background tasks
+/// of the store, named "backup:<target>", and keeps running until it is cancelled
+#[derive(Clone, Default)]
+pub(crate) struct BackupSchedules {
+    schedules: Arc<Mutex<HashMap<String, Arc<BackupSchedule>>>>,
+    tasks: BackgroundTasks,
+}
+
+/// A backup scheduled every `interval`, keeping the `keep` latest backups it made
+struct BackupSchedule {
+    interval: Duration,
+    keep: usize,
+    status: Mutex<ScheduleStatus>,
+}
+
+/// The outcome of the runs of a scheduled backup so far
+#[derive(Default)]
+struct ScheduleStatus {
+    runs: u64,
+    failures: u64,
+    in_progress: bool,
+    last_error: Option<String>,
+    /// The backups made by the schedule that are kept, oldest first
+    backups: VecDeque<String>,
+}
+
+/// The pool of the store whose collections are backed up
+pub(crate) enum BackupPool {
+    Sync(pools::RedisPool),
+    Async(pools::AsyncRedisPool),
+}
+
+/// The collections backed up by a scheduled backup, with where and how they are backed up
+pub(crate) struct BackupJob {
+    pub(crate) pool: BackupPool,
+    pub(crate) collections: Vec<(String, CollectionMeta)>,
+    pub(crate) target: String,
+    pub(crate) chunk_size: usize,
+}
+
+impl BackupSchedules {
+    /// Creates the schedules of a store, running them among the given background tasks of the store
+    pub(crate) fn new(tasks: BackgroundTasks) -> Self {
+        Self {
+            schedules: Default::default(),
+            tasks,
+        }
+    }
+
+    /// Starts backing up the collections of the given job into its target every `interval`, keeping the `keep`
+    /// latest backups. A target that another schedule still backs up into is rejected
+    pub(crate) fn spawn(
+        &self,
+        py: Python,
+        job: BackupJob,
+        interval: Duration,
+        keep: usize,
+    ) -> PyResult<()> {
+        if keep == 0 {
+            return Err(PyValueError::new_err("keep should be at least 1"));
+        }
+        if job.chunk_size == 0 {
+            return Err(PyValueError::new_err("chunk_size should be at least 1"));
+        }
+
+        let mut schedules = self.schedules.lock().unwrap_or_else(|e| e.into_inner());
+        let task_name = get_task_name(&job.target);
+        if self.tasks.is_running(py, &task_name)? {
+            return Err(PyValueError::new_err(format!(
+                "a backup into {:?} is already scheduled. Cancel it first",
+                job.target
+            )));
+        }
+
+        let schedule = Arc::new(BackupSchedule {
+            interval,
+            keep,
+            status: Default::default(),
+        });
+        let target = job.target.clone();
+        self.tasks.spawn_native(py, task_name, |runs| {
+            run_schedule(schedule.clone(), Arc::new(job), runs)
+        })?;
+        schedules.insert(target, schedule);
+        Ok(())
+    }
+
+    /// Gets a dictionary of the target of each scheduled backup to a dictionary with the keys: "interval_secs",
+    /// the seconds between backups; "keep", the number of backups kept; "runs" and "failures", the numbers
+    /// of backups that succeeded and failed; "in_progress", whether a backup is being made; "last_error",
+    /// the error of the latest backup if it failed; and "backups", the paths of the backups kept, oldest first
+    pub(crate) fn to_py(&self, py: Python) -> PyResult<Py<PyAny>> {
+        let mut schedules = self.schedules.lock().unwrap_or_else(|e| e.into_inner());
+        // the schedules cancelled among the background tasks of the store are forgotten
+        let mut cancelled = vec![];
+        for target in schedules.keys() {
+            if !self.tasks.is_running(py, &get_task_name(target))? {
+                cancelled.push(target.clone());
+            }
+        }
+        for target in cancelled {
+            schedules.remove(&target);
+        }
+
+        let result = PyDict::new(py);
+        for (target, schedule) in schedules.iter() {
+            let status = schedule.status.lock().unwrap_or_else(|e| e.into_inner());
+            let item = PyDict::new(py);
+            item.set_item("interval_secs", schedule.interval.as_secs_f64())?;
+            item.set_item("keep", schedule.keep)?;
+            item.set_item("runs", status.runs)?;
+            item.set_item("failures", status.failures)?;
+            item.set_item("in_progress", status.in_progress)?;
+            item.set_item("last_error", &status.last_error)?;
+            item.set_item("backups", status.backups.iter().collect::<Vec<_>>())?;
+            result.set_item(target, item)?;
+        }
+        Ok(result.into_py(py))
+    }
+
+    /// Cancels the scheduled backup into the given target and forgets it, returning whether there was such
+    /// a schedule. A backup in progress is completed first, in the background
+    pub(crate) fn cancel(&self, py: Python, target: &str) -> PyResult<bool> {
+        let schedule = self
+            .schedules
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .remove(target);
+        let is_cancelled = self.tasks.cancel(py, &get_task_name(target))?;
+        Ok(schedule.is_some() && is_cancelled)
+    }
+
+    /// Cancels all scheduled backups and forgets them, as when the store is closed
+    pub(crate) fn cancel_all(&self, py: Python) -> PyResult<()> {
+        let targets: Vec<String> = self
+            .schedules
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .keys()
+            .cloned()
+            .collect();
+        for target in targets {
+            self.cancel(py, &target)?;
+        }
+        Ok(())
+    }
+}
+
+/// Makes a backup with the given job every `interval` of the schedule until it is cancelled, counting the runs.
+/// Each backup is made in a task of its own, so that one in progress is completed if the schedule is cancelled
+async fn run_schedule(
+    schedule: Arc<BackupSchedule>,
+    job: Arc<BackupJob>,
+    runs: Arc<AtomicU64>,
+) -> PyResult<()> {
+    loop {
+        task::sleep(schedule.interval).await;
+        let (schedule, job) = (schedule.clone(), job.clone());
+        task::spawn(async move { schedule.make_backup(&job).await }).await;
+        runs.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+impl BackupSchedule {
+    /// Makes a backup with the given job, removing the oldest backups beyond the `keep` latest.
+    /// A backup that fails is removed, and the next one is made as scheduled
+    async fn make_backup(&self, job: &Arc<BackupJob>) {
+        self.update(|status| status.in_progress = true);
+        let dir = format!(
+            "{}/{}",
+            job.target.trim_end_matches('/'),
+            Utc::now().format("%Y%m%dT%H%M%S%.3fZ")
+        );
+
+        match job.run(&dir).await {
+            Ok(()) => {
+                let expired = self.update(|status| {
+                    status.runs += 1;
+                    status.last_error = None;
+                    status.backups.push_back(dir);
+                    let excess = status.backups.len().saturating_sub(self.keep);
+                    status.backups.drain(..excess).collect::<Vec<_>>()
+                });
+                for backup in expired {
+                    if let Err(e) = remove_backup(&backup) {
+                        self.update(|status| status.last_error = Some(e.to_string()));
+                    }
+                }
+            }
+            Err(e) => {
+                let _ = remove_backup(&dir);
+                self.update(|status| {
+                    status.failures += 1;
+                    status.last_error = Some(e.to_string());
+                });
+            }
+        }
+        self.update(|status| status.in_progress = false);
+    }
+
+    /// Updates the status of the schedule with the given function, returning what it returns
+    fn update<T>(&self, f: impl FnOnce(&mut ScheduleStatus) -> T) -> T {
+        f(&mut self.status.lock().unwrap_or_else(|e| e.into_inner()))
+    }
+}
+
+impl BackupJob {
+    /// Backs each collection up into a file named after it, in the directory at the given path or url.
+    /// The collections of a sync store are exported in a blocking task, not to block the executor
+    async fn run(self: &Arc<Self>, dir: &str) -> PyResult<()> {
+        if !dir.contains("://") {
+            fs::create_dir_all(dir)?;
+        }
+
+        for (name, meta) in &self.collections {
+            let dest = format!("{}/{}.export", dir, name);
+            let backed_up = match &self.pool {
+                BackupPool::Sync(pool) => {
+                    let (pool, name, meta) = (pool.clone(), name.clone(), meta.clone());
+                    let chunk_size = self.chunk_size;
+                    task::spawn_blocking(move || {
+                        Python::with_gil(|py| {
+                            BackupTarget::from_url(py, &dest)?.write(py, |sink| {
+                                utils::export_records(&pool, &name, &meta, sink, chunk_size)
+                                    .map(|(backed_up, _)| backed_up)
+                            })
+                        })
+                    })
+                    .await?
+                }
+                BackupPool::Async(pool) => {
+                    let mut sink =
+                        Python::with_gil(|py| BackupTarget::from_url(py, &dest))?.open()?;
+                    let backed_up = async_utils::export_records_async(
+                        pool,
+                        name,
+                        meta,
+                        &mut sink,
+                        self.chunk_size,
+                    )
+                    .await
+                    .map(|(backed_up, _)| backed_up);
+                    // the sink is finished even if the backup failed, so that it is never left open
+                    let finished = sink.finish();
+                    let backed_up = backed_up?;
+                    finished?;
+                    backed_up
+                }
+            };
+            meta.metrics.record("backup", backed_up as usize);
+        }
+        Ok(())
+    }
+}
+
+/// Gets the name of the background task of the scheduled backup into the given target
+#[inline]
+fn get_task_name(target: &str) -> String {
+    format!("backup:{}", target)
+}
+
+/// Extracts the interval of a scheduled backup: a timedelta or number of seconds, or a cron-like shorthand
+/// i.e. "@hourly", "@daily", "@weekly" or "@every <n><s|m|h|d>" e.g. "@every 15m"
+pub(crate) fn extract_interval(value: &PyAny) -> PyResult<Duration> {
+    let interval = match value.downcast::<PyString>() {
+        Ok(shorthand) => parse_shorthand(shorthand.to_str()?)?,
+        Err(_) => parsers::extract_duration(value)?,
+    };
+
+    if interval.is_zero() {
+        return Err(PyValueError::new_err(
+            "the interval of the backups should be more than zero",
+        ));
+    }
+    Ok(interval)
+}
+
+/// Parses a cron-like shorthand for the interval of a scheduled backup
+fn parse_shorthand(shorthand: &str) -> PyResult<Duration> {
+    let secs = match shorthand.trim() {
+        "@hourly" => Some(60 * 60),
+        "@daily" | "@midnight" => Some(24 * 60 * 60),
+        "@weekly" => Some(7 * 24 * 60 * 60),
+        v => v.strip_prefix("@every ").and_then(|every| {
+            let every = every.trim();
+            let unit = match every.chars().last()? {
+                's' => 1,
+                'm' => 60,
+                'h' => 60 * 60,
+                'd' => 24 * 60 * 60,
+                _ => return None,
+            };
+            every[..every.len() - 1]
+                .parse::<u64>()
+                .ok()
+                .map(|n| n * unit)
+        }),
+    };
+
+    secs.map(Duration::from_secs).ok_or_else(|| {
+        PyValueError::new_err(format!(
+            "expected a timedelta, a number of seconds, \"@hourly\", \"@daily\", \"@weekly\" \
+            or \"@every <n><s|m|h|d>\", got {:?}",
+            shorthand
+        ))
+    })
+}
+
+/// Removes the backup at the given path or url, a directory of the backups of the collections
+fn remove_backup(path: &str) -> PyResult<()> {
+    match path.split_once("://") {
+        Some((scheme, _)) => {
+            Python::with_gil(|py| backups::remove_with_fsspec(py, path, &scheme.to_lowercase()))
+        }
+        None => match fs::remove_dir_all(path) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+            _ => Ok(()),
+        },
+    }
+}
@@ -0,0 +1,218 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, Write};
+use std::sync::Mutex;
+
+use once_cell::sync::Lazy;
+use pyo3::exceptions::{PyImportError, PyValueError};
+use pyo3::prelude::*;
+use pyo3::types::{PyBytes, PyString};
+
+/// The functions that open the sinks of the backups whose destinations are urls of the given schemes
+/// e.g. "s3", each taking the url and returning a writable binary file-like object
+static SINK_OPENERS: Lazy<Mutex<HashMap<String, Py<PyAny>>>> = Lazy::new(Default::default);
+
+/// Registers the function that opens the sinks of the backups whose destinations are urls of the given scheme
+/// e.g. `register_backup_sink("s3", lambda url: s3fs.S3FileSystem().open(url, "wb"))`, in place of any
+/// function registered for the scheme before
+#[pyfunction]
+#[pyo3(text_signature = "(scheme, opener)")]
+pub(crate) fn register_backup_sink(scheme: &str, opener: Py<PyAny>) -> PyResult<()> {
+    if scheme.is_empty() || scheme.contains("://") {
+        return Err(PyValueError::new_err(format!(
+            "the scheme should be non-empty and without \"://\" e.g. \"s3\", got {:?}",
+            scheme
+        )));
+    }
+
+    SINK_OPENERS
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .insert(scheme.to_lowercase(), opener);
+    Ok(())
+}
+
+/// A python binary file-like object that a backup is written into, e.g. one that uploads it to object storage
+pub(crate) struct PySink(Py<PyAny>);
+
+impl Write for PySink {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        Python::with_gil(|py| {
+            self.0
+                .call_method1(py, "write", (PyBytes::new(py, buf),))
+                .map_err(to_io_err)
+        })?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Python::with_gil(|py| {
+            if self.0.as_ref(py).hasattr("flush").unwrap_or(false) {
+                self.0.call_method0(py, "flush").map_err(to_io_err)?;
+            }
+            Ok(())
+        })
+    }
+}
+
+impl PySink {
+    /// Closes the underlying file-like object e.g. completing the upload of the backup
+    fn close(self) -> PyResult<()> {
+        Python::with_gil(|py| self.0.call_method0(py, "close").map(|_| ()))
+    }
+}
+
+/// The destination of a backup
+pub(crate) enum BackupTarget {
+    /// A local file at the given path
+    File(String),
+    /// A file-like object, which is closed once the backup is written if orredis opened it
+    Sink { sink: PySink, is_owned: bool },
+}
+
+impl BackupTarget {
+    /// Extracts the destination of a backup from the `dest` given to `backup()`: a writable binary file-like
+    /// object; a url e.g. "s3://bucket/path", whose sink is opened by the function registered for its scheme,
+    /// or by fsspec if none is; or else the path of a local file
+    pub(crate) fn from_py(dest: &PyAny) -> PyResult<Self> {
+        match dest.downcast::<PyString>() {
+            Ok(url) => Self::from_url(dest.py(), url.to_str()?),
+            Err(_) if dest.hasattr("write")? => Ok(Self::Sink {
+                sink: PySink(dest.into()),
+                is_owned: false,
+            }),
+            Err(_) => Err(PyValueError::new_err(
+                "dest should be a path, a url or a writable binary file-like object",
+            )),
+        }
+    }
+
+    /// Gets the destination of a backup to the given url e.g. "s3://bucket/path", whose sink is opened
+    /// by the function registered for its scheme, or by fsspec if none is; or to the local file at the given path
+    pub(crate) fn from_url(py: Python, url: &str) -> PyResult<Self> {
+        let scheme = match url.split_once("://") {
+            Some((scheme, _)) => scheme.to_lowercase(),
+            None => return Ok(Self::File(url.to_string())),
+        };
+        let opener = SINK_OPENERS
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .get(&scheme)
+            .cloned();
+        let sink = match opener {
+            Some(opener) => opener.call1(py, (url,))?,
+            None => open_with_fsspec(py, url, &scheme)?,
+        };
+
+        Ok(Self::Sink {
+            sink: PySink(sink),
+            is_owned: true,
+        })
+    }
+
+    /// Writes a backup into this destination with the given function, which returns the number of records it
+    /// wrote. The sink is finished as by `BackupSink::finish()` whether or not the backup succeeded
+    pub(crate) fn write<F>(self, py: Python, write: F) -> PyResult<u64>
+    where
+        F: FnOnce(&mut BackupSink) -> PyResult<u64> + Send,
+    {
+        let mut sink = self.open()?;
+        // the GIL is released so that other threads, e.g. one uploading the backup, are not blocked
+        let written = py.allow_threads(|| write(&mut sink));
+        let finished = sink.finish();
+        let written = written?;
+        finished?;
+        Ok(written)
+    }
+
+    /// Opens the sink of this destination, creating the local file if it is one
+    pub(crate) fn open(self) -> PyResult<BackupSink> {
+        match self {
+            Self::File(path) => Ok(BackupSink::File(File::create(path)?)),
+            Self::Sink { sink, is_owned } => Ok(BackupSink::Py { sink, is_owned }),
+        }
+    }
+}
+
+/// The sink a backup is written into
+pub(crate) enum BackupSink {
+    File(File),
+    /// A file-like object, which is closed once the backup is written if orredis opened it
+    Py {
+        sink: PySink,
+        is_owned: bool,
+    },
+}
+
+impl BackupSink {
+    /// Finishes the writing of a backup into this sink, whether or not it succeeded, so that the sink is never
+    /// left open: a file is synced to the disk, and a file-like object is closed if it was opened for the backup
+    /// e.g. completing its upload, or else flushed
+    pub(crate) fn finish(self) -> PyResult<()> {
+        match self {
+            Self::File(file) => file.sync_all()?,
+            Self::Py {
+                sink,
+                is_owned: true,
+            } => sink.close()?,
+            Self::Py { mut sink, .. } => sink.flush()?,
+        }
+        Ok(())
+    }
+}
+
+impl Write for BackupSink {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            Self::File(file) => file.write(buf),
+            Self::Py { sink, .. } => sink.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Self::File(file) => file.flush(),
+            Self::Py { sink, .. } => sink.flush(),
+        }
+    }
+}
+
+/// Opens the sink of a backup to the given url with fsspec, which supports most object storages e.g. s3
+/// if their own packages e.g. s3fs, are installed
+fn open_with_fsspec(py: Python, url: &str, scheme: &str) -> PyResult<Py<PyAny>> {
+    Ok(import_fsspec(py, scheme)?
+        .call_method1("open", (url, "wb"))?
+        .call_method0("open")?
+        .into())
+}
+
+/// Removes the backup at the given url, a file or a directory of files, with fsspec
+pub(crate) fn remove_with_fsspec(py: Python, url: &str, scheme: &str) -> PyResult<()> {
+    let (fs, path): (&PyAny, &PyAny) = import_fsspec(py, scheme)?
+        .getattr("core")?
+        .call_method1("url_to_fs", (url,))?
+        .extract()?;
+    fs.call_method1("rm", (path, true))?;
+    Ok(())
+}
+
+/// Imports fsspec, failing with a ValueError that suggests registering a sink for the given scheme
+/// if it is not installed
+fn import_fsspec<'py>(py: Python<'py>, scheme: &str) -> PyResult<&'py PyModule> {
+    py.import("fsspec").map_err(|e| {
+        if e.is_instance_of::<PyImportError>(py) {
+            PyValueError::new_err(format!(
+                "no backup sink is registered for the scheme {:?}. Register one with register_backup_sink() \
+                or install fsspec",
+                scheme
+            ))
+        } else {
+            e
+        }
+    })
+}
+
+/// Converts the given python error, raised by a sink, into an io error
+fn to_io_err(err: PyErr) -> io::Error {
+    io::Error::new(io::ErrorKind::Other, err.to_string())
+}
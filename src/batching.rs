@@ -0,0 +1,127 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+
+/// The default maximum number of bytes of the records written or read in one round trip to redis
+pub(crate) const DEFAULT_MAX_BATCH_BYTES: usize = 8 * 1024 * 1024;
+
+/// The (key, fields) tuples of the records to insert, e.g. those of an item and of the records nested in it
+pub(crate) type InsertRecords = Vec<(String, Vec<(String, String)>)>;
+
+/// Splits the records written or read in one call into batches of at most a given number of bytes, so that
+/// the pipeline of each batch does not overrun the buffers of redis when records are big. The sizes of the
+/// records written are known; those of the records to read are estimated from the records read before
+#[derive(Clone, Debug)]
+pub(crate) struct BatchSizer {
+    max_bytes: usize,
+    /// The average size in bytes of the records of the collection read so far, or 0 if none were
+    record_bytes: Arc<AtomicUsize>,
+}
+
+impl Default for BatchSizer {
+    fn default() -> Self {
+        Self::new(DEFAULT_MAX_BATCH_BYTES)
+    }
+}
+
+impl BatchSizer {
+    /// Creates the batch sizer of a collection whose batches are at most `max_bytes`
+    pub(crate) fn new(max_bytes: usize) -> Self {
+        Self {
+            max_bytes,
+            record_bytes: Default::default(),
+        }
+    }
+
+    /// Extracts the maximum number of bytes of a batch from the `max_batch_bytes` option of a store
+    pub(crate) fn extract_max_bytes(max_batch_bytes: Option<usize>) -> PyResult<usize> {
+        match max_batch_bytes {
+            None => Ok(DEFAULT_MAX_BATCH_BYTES),
+            Some(0) => Err(PyValueError::new_err(
+                "max_batch_bytes should be at least 1",
+            )),
+            Some(v) => Ok(v),
+        }
+    }
+
+    /// Splits the records to insert, grouped by the item they were prepared from together with its nested
+    /// records, into batches of whole items of at most `max_bytes` each, unless an item alone is bigger
+    pub(crate) fn split_writes(&self, items: Vec<InsertRecords>) -> Vec<InsertRecords> {
+        let mut batches: Vec<InsertRecords> = vec![];
+        let mut batch: InsertRecords = vec![];
+        let mut batch_bytes = 0;
+
+        for records in items {
+            let bytes = estimate_records_bytes(&records);
+            if !batch.is_empty() && batch_bytes + bytes > self.max_bytes {
+                batches.push(std::mem::take(&mut batch));
+                batch_bytes = 0;
+            }
+            batch.extend(records);
+            batch_bytes += bytes;
+        }
+
+        if !batch.is_empty() || batches.is_empty() {
+            batches.push(batch);
+        }
+        batches
+    }
+
+    /// Splits the ids of the records to read into batches whose records are expected to be at most `max_bytes`.
+    /// All ids are in one batch till the size of the records of the collection is known
+    pub(crate) fn split_reads<'a>(&self, ids: &'a [String]) -> std::slice::Chunks<'a, String> {
+        let batch_size = match self.record_bytes.load(Ordering::Relaxed) {
+            0 => ids.len(),
+            record_bytes => self.max_bytes / record_bytes,
+        };
+        ids.chunks(batch_size.max(1))
+    }
+
+    /// Records the size of the given records read from redis, so that later reads are batched by it.
+    /// Records that were not found are left out. The average is weighted towards the latest reads so that
+    /// it follows changes in the sizes of records
+    pub(crate) fn observe(&self, records: &[redis::Value]) {
+        let (count, bytes) = records
+            .iter()
+            .filter(|v| !matches!(v, redis::Value::Bulk(items) if items.is_empty()))
+            .fold((0, 0), |(count, bytes), v| {
+                (count + 1, bytes + estimate_reply_bytes(v))
+            });
+        if count == 0 {
+            return;
+        }
+
+        let size = (bytes / count).max(1);
+        let _ = self
+            .record_bytes
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |average| {
+                Some(match average {
+                    0 => size,
+                    average => (average.saturating_mul(3) + size) / 4,
+                })
+            });
+    }
+}
+
+/// Estimates the bytes of the given records to insert by the lengths of their keys, fields and values
+fn estimate_records_bytes(records: &[(String, Vec<(String, String)>)]) -> usize {
+    records
+        .iter()
+        .map(|(key, fields)| {
+            key.len() + fields.iter().map(|(k, v)| k.len() + v.len()).sum::<usize>()
+        })
+        .sum()
+}
+
+/// Estimates the bytes of the given reply of redis by the lengths of the strings in it
+fn estimate_reply_bytes(value: &redis::Value) -> usize {
+    match value {
+        redis::Value::Data(data) => data.len(),
+        redis::Value::Bulk(items) => items.iter().map(estimate_reply_bytes).sum(),
+        redis::Value::Status(status) => status.len(),
+        redis::Value::Int(_) => 8,
+        redis::Value::Nil | redis::Value::Okay => 0,
+    }
+}
@@ -0,0 +1,73 @@
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, RwLock};
+
+use crate::field_types::FieldType;
+use crate::store::CollectionMeta;
+use crate::utils;
+
+pub(crate) const INIT_BLOOM_FILTER_SCRIPT: &str = r"if redis.call('EXISTS', KEYS[1]) == 1 then return 1 end local reserved = redis.pcall('BF.RESERVE', KEYS[1], ARGV[2], ARGV[3]) if type(reserved) == 'table' and reserved.err then return 0 end local cursor = '0' repeat local result = redis.call('SCAN', cursor, 'MATCH', ARGV[1]) for _, key in ipairs(result[2]) do if redis.call('TYPE', key).ok == 'hash' then redis.call('BF.ADD', KEYS[1], key) end end cursor = result[1] until (cursor == '0') return 1";
+pub(crate) const ADD_TO_BLOOM_FILTER_SCRIPT: &str = r"if redis.call('EXISTS', KEYS[1]) == 1 then return redis.call('BF.ADD', KEYS[1], ARGV[1]) end return 0";
+/// The probability of a false positive in the bloom filter of a collection
+const BLOOM_FILTER_ERROR_RATE: f64 = 0.001;
+/// The number of ids the bloom filter of a collection initially holds before it scales up
+const BLOOM_FILTER_INITIAL_CAPACITY: u64 = 10_000;
+
+/// The bloom filter of the primary keys of a collection. It is only used
+/// if the RedisBloom module is loaded on the redis server
+#[derive(Clone, Debug, Default)]
+pub(crate) struct BloomFilter {
+    /// Whether the RedisBloom module is loaded and the filter is initialized; None if not yet checked.
+    /// This is shared by all collections got from the same store
+    is_available: Arc<RwLock<Option<bool>>>,
+}
+
+impl BloomFilter {
+    /// Whether the bloom filter can be used; None if it has not yet been initialized
+    pub(crate) fn is_available(&self) -> Option<bool> {
+        *self.is_available.read().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Records whether the bloom filter can be used, after it has been initialized
+    pub(crate) fn set_available(&self, value: bool) {
+        *self.is_available.write().unwrap_or_else(|e| e.into_inner()) = Some(value);
+    }
+}
+
+/// Generates the command that creates the bloom filter of the given collection if it does not exist,
+/// adding the keys of all records in the collection to it. It returns 0 if the RedisBloom module is not loaded
+pub(crate) fn generate_init_cmd(collection_name: &str) -> redis::Cmd {
+    let mut cmd = redis::cmd("EVAL");
+    cmd.arg(INIT_BLOOM_FILTER_SCRIPT)
+        .arg(1)
+        .arg(utils::generate_bloom_filter_key(collection_name))
+        .arg(utils::generate_collection_key_pattern(collection_name))
+        .arg(BLOOM_FILTER_ERROR_RATE)
+        .arg(BLOOM_FILTER_INITIAL_CAPACITY);
+    cmd
+}
+
+/// Gets the names of the collections, among this one and those nested in it, whose bloom filters
+/// should be updated whenever a record of this collection is written
+pub(crate) fn get_bloom_filtered_collections(
+    model_name: &str,
+    meta: &CollectionMeta,
+    collections_meta: &HashMap<String, CollectionMeta>,
+) -> HashSet<String> {
+    let mut collections: HashSet<String> = meta
+        .nested_fields
+        .iter()
+        .filter_map(|field| {
+            meta.schema
+                .get_type(field)
+                .and_then(FieldType::get_nested_model_name)
+                .and_then(|model_name| collections_meta.get(model_name))
+        })
+        .flat_map(|nested_meta| nested_meta.bloom_filtered_collections.iter().cloned())
+        .collect();
+
+    if meta.bloom_filter.is_some() {
+        collections.insert(model_name.to_string());
+    }
+
+    collections
+}
@@ -0,0 +1,351 @@
+use std::collections::HashMap;
+use std::ops::DerefMut;
+use std::time::Duration;
+
+use pyo3::exceptions::{PyConnectionError, PyValueError};
+use pyo3::prelude::*;
+use pyo3::types::PyBytes;
+
+use crate::parsers;
+use crate::pools;
+use crate::utils;
+
+/// The number of keys a SCAN of `clear()` is asked to go through at a time
+const CLEAR_BATCH_SIZE: usize = 1000;
+/// The characters of a prefix that are escaped in the pattern of its keys so that they are matched literally
+const GLOB_CHARS: [char; 5] = ['*', '?', '[', ']', '\\'];
+
+/// A binary-safe key-value cache in the redis of a store, sharing the pool of its collections, e.g. as the cache
+/// or session backend of a web framework. Its keys are "<prefix>:<version>:<key>" like those of Django
+#[pyclass(subclass)]
+pub(crate) struct Cache {
+    pool: pools::RedisPool,
+    prefix: String,
+}
+
+impl Cache {
+    pub(crate) fn new(pool: pools::RedisPool, prefix: String) -> Self {
+        Self { pool, prefix }
+    }
+
+    /// Generates the redis key of the given key of the given version in this cache
+    #[inline]
+    fn generate_key(&self, key: &str, version: i64) -> String {
+        format!("{}:{}:{}", self.prefix, version, key)
+    }
+
+    /// Generates the pattern that matches all keys in this cache, of all versions
+    fn generate_key_pattern(&self) -> String {
+        let mut pattern = String::with_capacity(self.prefix.len() + 2);
+        for c in self.prefix.chars() {
+            if GLOB_CHARS.contains(&c) {
+                pattern.push('\\');
+            }
+            pattern.push(c);
+        }
+        pattern.push_str(":*");
+        pattern
+    }
+
+    /// Runs the given command or pipeline on a connection of the pool of this cache
+    fn query<T: redis::FromRedisValue>(
+        &self,
+        f: impl FnOnce(&mut redis::Connection) -> redis::RedisResult<T>,
+    ) -> PyResult<T> {
+        query(&self.pool, f)
+    }
+}
+
+#[pymethods]
+impl Cache {
+    /// Gets the value of the given key, or None if it is not in the cache
+    #[args(key, version = 1)]
+    pub(crate) fn get(&self, py: Python, key: &str, version: i64) -> PyResult<Option<Py<PyBytes>>> {
+        let value: Option<Vec<u8>> = self.query(|conn| {
+            redis::cmd("GET")
+                .arg(self.generate_key(key, version))
+                .query(conn)
+        })?;
+        Ok(value.map(|v| PyBytes::new(py, &v).into()))
+    }
+
+    /// Gets the values of the given keys that are in the cache, by key
+    #[args(keys, version = 1)]
+    pub(crate) fn get_many(
+        &self,
+        py: Python,
+        keys: Vec<String>,
+        version: i64,
+    ) -> PyResult<HashMap<String, Py<PyBytes>>> {
+        if keys.is_empty() {
+            return Ok(Default::default());
+        }
+
+        let redis_keys: Vec<String> = keys.iter().map(|k| self.generate_key(k, version)).collect();
+        let values: Vec<Option<Vec<u8>>> =
+            self.query(|conn| redis::cmd("MGET").arg(&redis_keys[..]).query(conn))?;
+        Ok(keys
+            .into_iter()
+            .zip(values)
+            .filter_map(|(k, v)| v.map(|v| (k, PyBytes::new(py, &v).into())))
+            .collect())
+    }
+
+    /// Sets the value of the given key, expiring after `ttl` (a timedelta or number of seconds) if given.
+    /// If `only_if_missing` is true, the value is only set if the key is not in the cache.
+    /// Returns whether the value was set. A zero `ttl` removes the key instead, returning false
+    #[args(key, value, ttl = "None", version = 1, only_if_missing = "false")]
+    pub(crate) fn set(
+        &self,
+        key: &str,
+        value: &[u8],
+        ttl: Option<&PyAny>,
+        version: i64,
+        only_if_missing: bool,
+    ) -> PyResult<bool> {
+        let ttl = ttl.map(parsers::extract_duration).transpose()?;
+        if ttl == Some(Duration::ZERO) {
+            self.delete(key, version)?;
+            return Ok(false);
+        }
+
+        let mut cmd = redis::cmd("SET");
+        cmd.arg(self.generate_key(key, version)).arg(value);
+        if let Some(ttl) = ttl {
+            cmd.arg("PX").arg(utils::duration_in_millis(&ttl));
+        }
+        if only_if_missing {
+            cmd.arg("NX");
+        }
+        let reply: Option<String> = self.query(|conn| cmd.query(conn))?;
+        Ok(reply.is_some())
+    }
+
+    /// Sets the values of the given keys, by key, in one round trip, expiring after `ttl` if given
+    #[args(items, ttl = "None", version = 1)]
+    pub(crate) fn set_many(
+        &self,
+        items: HashMap<String, Vec<u8>>,
+        ttl: Option<&PyAny>,
+        version: i64,
+    ) -> PyResult<()> {
+        let ttl = ttl.map(parsers::extract_duration).transpose()?;
+        if items.is_empty() {
+            return Ok(());
+        }
+        if ttl == Some(Duration::ZERO) {
+            self.delete_many(items.into_keys().collect(), version)?;
+            return Ok(());
+        }
+
+        let mut pipe = redis::pipe();
+        for (key, value) in &items {
+            let cmd = pipe
+                .cmd("SET")
+                .arg(self.generate_key(key, version))
+                .arg(value);
+            if let Some(ttl) = ttl {
+                cmd.arg("PX").arg(utils::duration_in_millis(&ttl));
+            }
+            cmd.ignore();
+        }
+        self.query(|conn| pipe.query(conn))
+    }
+
+    /// Removes the given key from the cache, returning whether it was in the cache
+    #[args(key, version = 1)]
+    pub(crate) fn delete(&self, key: &str, version: i64) -> PyResult<bool> {
+        let removed: u64 = self.query(|conn| {
+            redis::cmd("DEL")
+                .arg(self.generate_key(key, version))
+                .query(conn)
+        })?;
+        Ok(removed > 0)
+    }
+
+    /// Removes the given keys from the cache, returning the number of them that were in the cache
+    #[args(keys, version = 1)]
+    pub(crate) fn delete_many(&self, keys: Vec<String>, version: i64) -> PyResult<u64> {
+        if keys.is_empty() {
+            return Ok(0);
+        }
+
+        let redis_keys: Vec<String> = keys.iter().map(|k| self.generate_key(k, version)).collect();
+        self.query(|conn| redis::cmd("DEL").arg(redis_keys).query(conn))
+    }
+
+    /// Checks whether the given key is in the cache
+    #[args(key, version = 1)]
+    pub(crate) fn has_key(&self, key: &str, version: i64) -> PyResult<bool> {
+        self.query(|conn| {
+            redis::cmd("EXISTS")
+                .arg(self.generate_key(key, version))
+                .query(conn)
+        })
+    }
+
+    /// Changes the expiry of the given key to `ttl` from now, or removes its expiry if `ttl` is None.
+    /// Returns whether the key is in the cache
+    #[args(key, ttl = "None", version = 1)]
+    pub(crate) fn touch(&self, key: &str, ttl: Option<&PyAny>, version: i64) -> PyResult<bool> {
+        let ttl = ttl.map(parsers::extract_duration).transpose()?;
+        let key = self.generate_key(key, version);
+        match ttl {
+            Some(ttl) if ttl.is_zero() => self.query(|conn| redis::cmd("DEL").arg(key).query(conn)),
+            Some(ttl) => self.query(|conn| {
+                redis::cmd("PEXPIRE")
+                    .arg(key)
+                    .arg(utils::duration_in_millis(&ttl))
+                    .query(conn)
+            }),
+            // PERSIST replies 0 for keys without an expiry too, so whether the key exists is checked apart
+            None => {
+                let (exists, _): (bool, u8) = self.query(|conn| {
+                    redis::pipe()
+                        .cmd("EXISTS")
+                        .arg(&key)
+                        .cmd("PERSIST")
+                        .arg(&key)
+                        .query(conn)
+                })?;
+                Ok(exists)
+            }
+        }
+    }
+
+    /// Removes all keys of this cache, of all versions, leaving the other keys in redis e.g. the records
+    /// of collections. Returns the number of keys removed
+    pub(crate) fn clear(&self) -> PyResult<u64> {
+        let pattern = self.generate_key_pattern();
+        let mut conn = pools::get_blocking_connection(&self.pool)?;
+        let mut cursor = 0u64;
+        let mut removed = 0u64;
+        loop {
+            let (next_cursor, keys): (u64, Vec<String>) = redis::cmd("SCAN")
+                .arg(cursor)
+                .arg("MATCH")
+                .arg(&pattern)
+                .arg("COUNT")
+                .arg(CLEAR_BATCH_SIZE)
+                .query(conn.deref_mut())
+                .map_err(|e| PyConnectionError::new_err(e.to_string()))?;
+            if !keys.is_empty() {
+                let count: u64 = redis::cmd("UNLINK")
+                    .arg(keys)
+                    .query(conn.deref_mut())
+                    .map_err(|e| PyConnectionError::new_err(e.to_string()))?;
+                removed += count;
+            }
+
+            if next_cursor == 0 {
+                return Ok(removed);
+            }
+            cursor = next_cursor;
+        }
+    }
+}
+
+/// A raw key-value namespace in the redis of a store, sharing the pool of its collections, with the commands
+/// the result backends of task queues like Celery need. Its keys are "<namespace><key>", the namespace
+/// being prepended as is, so an empty namespace gives access to the keys such backends already use
+#[pyclass(subclass)]
+pub(crate) struct KeyValueNamespace {
+    pool: pools::RedisPool,
+    namespace: String,
+}
+
+impl KeyValueNamespace {
+    pub(crate) fn new(pool: pools::RedisPool, namespace: String) -> Self {
+        Self { pool, namespace }
+    }
+
+    /// Generates the redis key of the given key in this namespace
+    #[inline]
+    fn generate_key(&self, key: &str) -> String {
+        format!("{}{}", self.namespace, key)
+    }
+}
+
+#[pymethods]
+impl KeyValueNamespace {
+    /// Gets the value of the given key, or None if it is not set
+    pub(crate) fn get(&self, py: Python, key: &str) -> PyResult<Option<Py<PyBytes>>> {
+        let value: Option<Vec<u8>> = query(&self.pool, |conn| {
+            redis::cmd("GET").arg(self.generate_key(key)).query(conn)
+        })?;
+        Ok(value.map(|v| PyBytes::new(py, &v).into()))
+    }
+
+    /// Gets the values of the given keys in one round trip, in the order of the keys, with None for keys not set
+    pub(crate) fn mget(&self, py: Python, keys: Vec<String>) -> PyResult<Vec<Option<Py<PyBytes>>>> {
+        if keys.is_empty() {
+            return Ok(vec![]);
+        }
+
+        let redis_keys: Vec<String> = keys.iter().map(|k| self.generate_key(k)).collect();
+        let values: Vec<Option<Vec<u8>>> = query(&self.pool, |conn| {
+            redis::cmd("MGET").arg(&redis_keys[..]).query(conn)
+        })?;
+        Ok(values
+            .into_iter()
+            .map(|v| v.map(|v| PyBytes::new(py, &v).into()))
+            .collect())
+    }
+
+    /// Sets the value of the given key, expiring after `ttl` (a timedelta or number of seconds) if given
+    #[args(key, value, ttl = "None")]
+    pub(crate) fn set(&self, key: &str, value: &[u8], ttl: Option<&PyAny>) -> PyResult<()> {
+        let ttl = ttl.map(parsers::extract_duration).transpose()?;
+        if ttl == Some(Duration::ZERO) {
+            return Err(PyValueError::new_err("ttl should be more than zero"));
+        }
+
+        let mut cmd = redis::cmd("SET");
+        cmd.arg(self.generate_key(key)).arg(value);
+        if let Some(ttl) = ttl {
+            cmd.arg("PX").arg(utils::duration_in_millis(&ttl));
+        }
+        query(&self.pool, |conn| cmd.query(conn))
+    }
+
+    /// Removes the given key, returning whether it was set
+    pub(crate) fn delete(&self, key: &str) -> PyResult<bool> {
+        let removed: u64 = query(&self.pool, |conn| {
+            redis::cmd("DEL").arg(self.generate_key(key)).query(conn)
+        })?;
+        Ok(removed > 0)
+    }
+
+    /// Makes the given key expire after `ttl` (a timedelta or number of seconds) from now,
+    /// returning whether the key is set
+    pub(crate) fn expire(&self, key: &str, ttl: &PyAny) -> PyResult<bool> {
+        let ttl = parsers::extract_duration(ttl)?;
+        query(&self.pool, |conn| {
+            redis::cmd("PEXPIRE")
+                .arg(self.generate_key(key))
+                .arg(utils::duration_in_millis(&ttl))
+                .query(conn)
+        })
+    }
+
+    /// Atomically increments the integer value of the given key by the given amount, starting from 0
+    /// if it is not set, and returns the new value
+    #[args(key, amount = 1)]
+    pub(crate) fn incr(&self, key: &str, amount: i64) -> PyResult<i64> {
+        query(&self.pool, |conn| {
+            redis::cmd("INCRBY")
+                .arg(self.generate_key(key))
+                .arg(amount)
+                .query(conn)
+        })
+    }
+}
+
+/// Runs the given command or pipeline on a connection of the given pool
+fn query<T: redis::FromRedisValue>(
+    pool: &pools::RedisPool,
+    f: impl FnOnce(&mut redis::Connection) -> redis::RedisResult<T>,
+) -> PyResult<T> {
+    let mut conn = pools::get_connection(pool)?;
+    f(conn.deref_mut()).map_err(|e| PyConnectionError::new_err(e.to_string()))
+}
@@ -0,0 +1,144 @@
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use pyo3::create_exception;
+use pyo3::exceptions::{PyConnectionError, PyValueError};
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+
+// Raised when the circuit breaker of a store is open i.e. too many calls in a row failed to get a connection
+// to redis, so that calls fail fast during an outage instead of each waiting for the timeout of the pool.
+// It is a ConnectionError so that code handling the errors of redis connections also handles it
+create_exception!(orredis, CircuitOpenError, PyConnectionError);
+
+/// The time, in milliseconds, an open circuit waits before letting a call through to probe redis
+pub(crate) const DEFAULT_RESET_TIMEOUT_MS: u64 = 30_000;
+
+/// The state of the circuit of a circuit breaker
+#[derive(Clone, Copy, Debug)]
+enum CircuitState {
+    /// Calls go through
+    Closed,
+    /// Calls fail fast till the reset timeout has passed since the circuit opened
+    Open { since: Instant },
+    /// One call, the probe, goes through while others fail fast. The circuit closes if the probe succeeds
+    /// and opens again if it fails. Another call probes if the outcome of the probe is not known by the
+    /// reset timeout e.g. it was cancelled
+    HalfOpen { since: Instant },
+}
+
+/// The state of a circuit breaker i.e. that of its circuit and the number of calls that failed in a row
+#[derive(Clone, Copy, Debug)]
+struct BreakerState {
+    circuit: CircuitState,
+    failures: u32,
+}
+
+/// Guards the pool of a store so that, after `failure_threshold` calls in a row failed to get a connection,
+/// the calls of the store fail fast with a CircuitOpenError till redis is found to be back
+#[derive(Clone, Debug)]
+pub(crate) struct CircuitBreaker {
+    state: Arc<Mutex<BreakerState>>,
+    failure_threshold: u32,
+    reset_timeout: Duration,
+}
+
+impl CircuitBreaker {
+    /// Creates the circuit breaker of a store given the options of the store, or None if its
+    /// failure threshold is not set
+    pub(crate) fn from_options(
+        failure_threshold: Option<u32>,
+        reset_timeout: Option<u64>,
+    ) -> PyResult<Option<Self>> {
+        let failure_threshold = match failure_threshold {
+            None => return Ok(None),
+            Some(0) => {
+                return Err(PyValueError::new_err(
+                    "circuit_breaker_threshold should be at least 1",
+                ))
+            }
+            Some(v) => v,
+        };
+
+        Ok(Some(Self {
+            state: Arc::new(Mutex::new(BreakerState {
+                circuit: CircuitState::Closed,
+                failures: 0,
+            })),
+            failure_threshold,
+            reset_timeout: Duration::from_millis(reset_timeout.unwrap_or(DEFAULT_RESET_TIMEOUT_MS)),
+        }))
+    }
+
+    /// Lets a call through if the circuit is closed, or if it is time to probe redis, otherwise fails
+    /// with a CircuitOpenError
+    pub(crate) fn acquire(&self) -> PyResult<()> {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        match state.circuit {
+            CircuitState::Closed => Ok(()),
+            CircuitState::Open { since } | CircuitState::HalfOpen { since } => {
+                let elapsed = since.elapsed();
+                if elapsed >= self.reset_timeout {
+                    state.circuit = CircuitState::HalfOpen {
+                        since: Instant::now(),
+                    };
+                    Ok(())
+                } else {
+                    Err(CircuitOpenError::new_err(format!(
+                        "the circuit breaker is open after {} calls in a row failed to get a connection to redis. \
+                        The next call will be let through in {}ms",
+                        state.failures,
+                        (self.reset_timeout - elapsed).as_millis()
+                    )))
+                }
+            }
+        }
+    }
+
+    /// Records that a call got a connection, closing the circuit
+    pub(crate) fn record_success(&self) {
+        *self.state.lock().unwrap_or_else(|e| e.into_inner()) = BreakerState {
+            circuit: CircuitState::Closed,
+            failures: 0,
+        };
+    }
+
+    /// Records that a call failed to get a connection, opening the circuit if the probe failed or
+    /// if as many calls as the failure threshold have failed in a row
+    pub(crate) fn record_failure(&self) {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        state.failures = state.failures.saturating_add(1);
+        if state.failures >= self.failure_threshold
+            || !matches!(state.circuit, CircuitState::Closed)
+        {
+            state.circuit = CircuitState::Open {
+                since: Instant::now(),
+            };
+        }
+    }
+
+    /// Describes the state of this circuit breaker as a python dictionary with the keys: "state", one of
+    /// "closed", "open" and "half_open"; "consecutive_failures", the number of calls that failed in a row;
+    /// "failure_threshold"; and "retry_in_ms", the time till a call is let through to probe redis,
+    /// None unless it is open
+    pub(crate) fn to_py(&self) -> PyResult<Py<PyAny>> {
+        let state = *self.state.lock().unwrap_or_else(|e| e.into_inner());
+        let (name, retry_in) = match state.circuit {
+            CircuitState::Closed => ("closed", None),
+            CircuitState::Open { since } => (
+                "open",
+                Some(self.reset_timeout.saturating_sub(since.elapsed())),
+            ),
+            CircuitState::HalfOpen { .. } => ("half_open", None),
+        };
+
+        Python::with_gil(|py| {
+            let description = PyDict::new(py);
+            description.set_item("state", name)?;
+            description.set_item("consecutive_failures", state.failures)?;
+            description.set_item("failure_threshold", self.failure_threshold)?;
+            description.set_item("retry_in_ms", retry_in.map(|v| v.as_millis() as u64))?;
+            Ok(description.into_py(py))
+        })
+    }
+}
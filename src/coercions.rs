@@ -0,0 +1,131 @@
+use std::collections::HashMap;
+
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+
+use crate::field_types::FieldType;
+use crate::parsers;
+use crate::schema::Schema;
+use crate::utils;
+
+/// A rule for converting a value saved in redis in a format other than the one orredis writes
+/// e.g. by a legacy system, into the python value of its field. It is applied when the value is read
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum Coercion {
+    /// An empty string is read as None, for any non-nested field
+    EmptyToNone,
+    /// A whole number saved as a float string e.g. "3.0" is read as an int, for int fields
+    FloatToInt,
+    /// "1", "0", "yes", "no", "on", "off" and any casing of "true" and "false" are read as booleans, for bool fields
+    LooseBool,
+    /// A unix timestamp in seconds is read as a date or datetime, for date and datetime fields
+    TimestampToDatetime,
+}
+
+/// The coercion rules of the fields of a collection
+pub(crate) type CoercionRules = HashMap<String, Vec<Coercion>>;
+
+impl Coercion {
+    /// Gets the coercion of the given name
+    pub(crate) fn from_name(name: &str) -> PyResult<Self> {
+        match name {
+            "empty_to_none" => Ok(Self::EmptyToNone),
+            "float_to_int" => Ok(Self::FloatToInt),
+            "loose_bool" => Ok(Self::LooseBool),
+            "timestamp_to_datetime" => Ok(Self::TimestampToDatetime),
+            _ => Err(PyValueError::new_err(format!(
+                "unknown coercion {:?}. Expected one of \"empty_to_none\", \"float_to_int\", \"loose_bool\" or \"timestamp_to_datetime\"",
+                name
+            ))),
+        }
+    }
+
+    /// Whether this coercion can be applied to fields of the given type
+    fn is_applicable(&self, type_: &FieldType) -> bool {
+        match self {
+            Self::EmptyToNone => !type_.is_nested(),
+            Self::FloatToInt => matches!(type_, FieldType::Int),
+            Self::LooseBool => matches!(type_, FieldType::Bool),
+            Self::TimestampToDatetime => matches!(type_, FieldType::Datetime | FieldType::Date),
+        }
+    }
+
+    /// Converts the given value saved in redis into the python value of a field of the given type
+    /// if the value is in the format this coercion handles, else returns None
+    fn apply(&self, type_: &FieldType, value: &str) -> Option<PyResult<Py<PyAny>>> {
+        match self {
+            Self::EmptyToNone if value.is_empty() => Some(Ok(Python::with_gil(|py| py.None()))),
+            Self::FloatToInt if value.parse::<i64>().is_err() => match value.parse::<f64>() {
+                Ok(v) if v.fract() == 0.0 && v.is_finite() => {
+                    Some(Ok(Python::with_gil(|py| (v as i64).into_py(py))))
+                }
+                _ => None,
+            },
+            Self::LooseBool => match value.to_lowercase().as_str() {
+                "true" | "1" | "yes" | "on" => Some(Ok(Python::with_gil(|py| true.into_py(py)))),
+                "false" | "0" | "no" | "off" => Some(Ok(Python::with_gil(|py| false.into_py(py)))),
+                _ => None,
+            },
+            Self::TimestampToDatetime => match value.parse::<f64>() {
+                Ok(v) if v.is_finite() => match type_ {
+                    FieldType::Date => Some(utils::timestamp_to_py_date(v as i64)),
+                    _ => Some(utils::timestamp_to_py_datetime(v as i64)),
+                },
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+}
+
+/// Extracts the coercion rules from a dictionary of field name to list of coercion names,
+/// ensuring each coercion can be applied to its field in the given schema
+pub(crate) fn extract_coercion_rules(
+    coercions: HashMap<String, Vec<String>>,
+    schema: &Schema,
+) -> PyResult<CoercionRules> {
+    coercions
+        .into_iter()
+        .map(|(field, names)| {
+            let type_ = schema.get_type(&field).ok_or_else(|| {
+                PyValueError::new_err(format!("{:?} is not a field of the model", field))
+            })?;
+            let rules = names
+                .iter()
+                .map(|name| {
+                    let rule = Coercion::from_name(name)?;
+                    if rule.is_applicable(type_) {
+                        Ok(rule)
+                    } else {
+                        Err(PyValueError::new_err(format!(
+                            "coercion {:?} cannot be applied to field {:?}",
+                            name, field
+                        )))
+                    }
+                })
+                .collect::<PyResult<Vec<Coercion>>>()?;
+            Ok((field, rules))
+        })
+        .collect()
+}
+
+/// Converts data of the collection of the given name got from redis into the python value of a field of
+/// the given type, applying the first of the given coercions that handles the data's format, if any
+pub(crate) fn redis_to_py(
+    collection_name: &str,
+    type_: &FieldType,
+    data: &redis::Value,
+    coercions: &[Coercion],
+) -> PyResult<Py<PyAny>> {
+    if !coercions.is_empty() {
+        if let Ok(value) = parsers::redis_to_py::<String>(data) {
+            for coercion in coercions {
+                if let Some(result) = coercion.apply(type_, &value) {
+                    return result;
+                }
+            }
+        }
+    }
+
+    type_.redis_to_py(collection_name, data)
+}
@@ -0,0 +1,35 @@
+use crate::schema::Schema;
+use crate::versioning::VERSION_FIELD;
+
+/// The number of records read and compacted in each round trip to redis
+pub(crate) const COMPACT_BATCH_SIZE: usize = 500;
+
+/// Gets the name of the background task that compacts the records of the given collection
+pub(crate) fn get_task_name(collection_name: &str) -> String {
+    format!("compact:{}", collection_name)
+}
+
+/// Generates the pipeline that removes the fields that are not in the given schema from the records
+/// of the given keys, given the fields each record has. It returns the pipeline with the number of
+/// records it compacts, or None if no record has such fields
+pub(crate) fn generate_compact_pipeline(
+    schema: &Schema,
+    keys: &[String],
+    fields: &[Vec<String>],
+) -> Option<(redis::Pipeline, u64)> {
+    let mut pipe = redis::pipe();
+    let mut compacted: u64 = 0;
+
+    for (key, fields) in keys.iter().zip(fields) {
+        let stale: Vec<&String> = fields
+            .iter()
+            .filter(|field| field.as_str() != VERSION_FIELD && schema.get_type(field).is_none())
+            .collect();
+        if !stale.is_empty() {
+            pipe.hdel(key, stale).ignore();
+            compacted += 1;
+        }
+    }
+
+    (compacted > 0).then_some((pipe, compacted))
+}
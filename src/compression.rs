@@ -0,0 +1,401 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+use once_cell::sync::Lazy;
+use pyo3::exceptions::{PyConnectionError, PyValueError};
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+use zstd::dict::{DecoderDictionary, EncoderDictionary};
+use zstd::zstd_safe::{self, CCtx, DCtx};
+
+use crate::field_types::FieldType;
+use crate::parsers;
+use crate::store::CollectionMeta;
+use crate::utils;
+
+/// The magic number every zstd frame starts with. It is not valid utf-8, so values that start with it
+/// are known to be compressed without any marker
+const ZSTD_MAGIC: [u8; 4] = [0x28, 0xB5, 0x2F, 0xFD];
+/// The level at which values are compressed
+const COMPRESSION_LEVEL: i32 = 3;
+/// The maximum size of a trained dictionary
+const MAX_DICTIONARY_BYTES: usize = 16 * 1024;
+/// The minimum length of the values that are compressed, as the header of a frame outweighs any saving
+/// on shorter values
+const MIN_COMPRESSED_LEN: usize = 16;
+/// The maximum size of a decompressed value i.e. the maximum size of a string in redis
+const MAX_DECOMPRESSED_BYTES: u64 = 512 * 1024 * 1024;
+/// The field of the hash of the dictionaries of a collection that holds the id of the dictionary new
+/// values are compressed with
+const CURRENT_DICTIONARY_FIELD: &str = "current";
+/// The time waited for a connection to redis when loading a dictionary a value was compressed with
+const DICTIONARY_LOAD_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// The dictionaries that compressed values can be decompressed with, by their collections and ids
+type Decoders = HashMap<(String, u32), Arc<DecoderDictionary<'static>>>;
+
+/// The dictionaries of all collections, kept together since values are decompressed when they are read,
+/// wherever they are nested. The ids are random, so dictionaries of different collections may share one
+static DECODERS: Lazy<RwLock<Decoders>> = Lazy::new(Default::default);
+
+/// The redis instances the dictionaries of the collections are loaded from, by collection, when a value
+/// compressed with a dictionary that is not in DECODERS is read e.g. one trained by another client
+static SOURCES: Lazy<RwLock<HashMap<String, redis::Client>>> = Lazy::new(Default::default);
+
+thread_local! {
+    /// The contexts reused by the compressions and decompressions run on this thread
+    static COMPRESSION_CONTEXT: RefCell<CCtx<'static>> = RefCell::new(CCtx::create());
+    static DECOMPRESSION_CONTEXT: RefCell<DCtx<'static>> = RefCell::new(DCtx::create());
+}
+
+/// A zstd dictionary trained on the values of a collection, with which new values are compressed
+pub(crate) struct Dictionary {
+    id: u32,
+    encoder: EncoderDictionary<'static>,
+}
+
+impl Dictionary {
+    /// Prepares the given dictionary of the given collection for compression, registering it for decompression
+    fn new(collection_name: &str, id: u32, dictionary: &[u8]) -> Self {
+        register_decoder(collection_name, id, dictionary);
+        Self {
+            id,
+            encoder: EncoderDictionary::copy(dictionary, COMPRESSION_LEVEL),
+        }
+    }
+
+    /// Gets the id of the dictionary, which is saved in the frame of each value compressed with it
+    #[inline]
+    pub(crate) fn id(&self) -> u32 {
+        self.id
+    }
+
+    /// Compresses the given value, returning None if it is not made any shorter
+    fn compress(&self, value: &[u8]) -> Option<Vec<u8>> {
+        if value.len() < MIN_COMPRESSED_LEN {
+            return None;
+        }
+
+        let mut compressed = Vec::with_capacity(zstd_safe::compress_bound(value.len()));
+        COMPRESSION_CONTEXT
+            .with(|context| {
+                context.borrow_mut().compress_using_cdict(
+                    &mut compressed,
+                    value,
+                    self.encoder.as_cdict(),
+                )
+            })
+            .ok()?;
+        (compressed.len() < value.len()).then_some(compressed)
+    }
+
+    /// Converts the given fields of a record of the collection into the values saved in redis,
+    /// compressing those of the fields that can be compressed
+    pub(crate) fn compress_fields<'a>(
+        &self,
+        meta: &CollectionMeta,
+        fields: &[(&'a String, &'a String)],
+    ) -> Vec<(&'a String, Vec<u8>)> {
+        fields
+            .iter()
+            .map(|(field, value)| {
+                let compressed = match is_compressible(meta, field) {
+                    true => self.compress(value.as_bytes()),
+                    false => None,
+                };
+                (
+                    *field,
+                    compressed.unwrap_or_else(|| value.as_bytes().to_vec()),
+                )
+            })
+            .collect()
+    }
+}
+
+/// The compression of the string fields of a collection with a dictionary trained on its values.
+/// It is shared by all collections got from the same store
+#[derive(Clone, Default)]
+pub(crate) struct Compression {
+    dictionary: Arc<RwLock<Option<Arc<Dictionary>>>>,
+}
+
+impl Compression {
+    /// Whether new values of the collection are compressed
+    pub(crate) fn is_enabled(&self) -> bool {
+        self.get_dictionary().is_some()
+    }
+
+    /// Gets the dictionary new values of the collection are compressed with, if any
+    pub(crate) fn get_dictionary(&self) -> Option<Arc<Dictionary>> {
+        self.dictionary
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+    }
+
+    /// Compresses new values of the collection with the given dictionary
+    pub(crate) fn set_dictionary(&self, dictionary: Dictionary) {
+        *self.dictionary.write().unwrap_or_else(|e| e.into_inner()) = Some(Arc::new(dictionary));
+    }
+
+    /// Registers the given dictionaries of the collection of the given name, as saved in redis by their ids,
+    /// compressing new values with the current one. It returns whether there was a current dictionary
+    pub(crate) fn load(
+        &self,
+        collection_name: &str,
+        dictionaries: HashMap<String, Vec<u8>>,
+    ) -> PyResult<bool> {
+        let current = dictionaries
+            .get(CURRENT_DICTIONARY_FIELD)
+            .map(|v| parsers::parse_str::<u32>(&String::from_utf8_lossy(v)))
+            .transpose()?;
+
+        for (id, dictionary) in dictionaries.iter() {
+            if let Ok(id) = id.parse::<u32>() {
+                let dictionary = Dictionary::new(collection_name, id, dictionary);
+                if Some(id) == current {
+                    self.set_dictionary(dictionary);
+                }
+            }
+        }
+        Ok(current.is_some())
+    }
+}
+
+/// Trains a dictionary on the given values of the fields of the given collection that can be compressed.
+/// It returns the dictionary, the bytes of it to save in redis and the statistics of the training
+pub(crate) fn train(
+    collection_name: &str,
+    samples: Vec<Vec<u8>>,
+) -> PyResult<(Dictionary, Vec<u8>, TrainingStats)> {
+    let dictionary = zstd::dict::from_samples(&samples, MAX_DICTIONARY_BYTES).map_err(|e| {
+        PyValueError::new_err(format!(
+            "could not train a compression dictionary on the {} values sampled: {}",
+            samples.len(),
+            e
+        ))
+    })?;
+    let id = zstd_safe::get_dict_id(&dictionary)
+        .ok_or_else(|| PyValueError::new_err("the trained compression dictionary has no id"))?;
+
+    let trained = Dictionary::new(collection_name, id, &dictionary);
+    let stats = TrainingStats {
+        dictionary_id: id,
+        dictionary_bytes: dictionary.len(),
+        samples: samples.len(),
+        sampled_bytes: samples.iter().map(|v| v.len()).sum(),
+        compressed_bytes: samples
+            .iter()
+            .map(|v| trained.compress(v).map_or(v.len(), |c| c.len()))
+            .sum(),
+    };
+    Ok((trained, dictionary, stats))
+}
+
+/// The statistics of the training of a compression dictionary
+#[derive(Debug)]
+pub(crate) struct TrainingStats {
+    dictionary_id: u32,
+    dictionary_bytes: usize,
+    samples: usize,
+    sampled_bytes: usize,
+    compressed_bytes: usize,
+}
+
+impl TrainingStats {
+    /// Converts the statistics into a python dictionary with the keys: "dictionary_id"; "dictionary_bytes",
+    /// the size of the dictionary; "samples", the number of values it was trained on; "sampled_bytes" and
+    /// "compressed_bytes", their size before and after compression; and "ratio", the first over the second
+    pub(crate) fn to_py(&self) -> PyResult<Py<PyAny>> {
+        Python::with_gil(|py| {
+            let stats = PyDict::new(py);
+            stats.set_item("dictionary_id", self.dictionary_id)?;
+            stats.set_item("dictionary_bytes", self.dictionary_bytes)?;
+            stats.set_item("samples", self.samples)?;
+            stats.set_item("sampled_bytes", self.sampled_bytes)?;
+            stats.set_item("compressed_bytes", self.compressed_bytes)?;
+            stats.set_item(
+                "ratio",
+                self.sampled_bytes as f64 / self.compressed_bytes.max(1) as f64,
+            )?;
+            Ok(stats.into_py(py))
+        })
+    }
+}
+
+/// Whether the given field of the collection is compressed when a dictionary is trained for it i.e. it is
+/// a string field that is neither the primary key, nor indexed, nor unique, nor filtered on by a view,
+/// as those are compared as they are saved in redis
+pub(crate) fn is_compressible(meta: &CollectionMeta, field: &str) -> bool {
+    let contains = |fields: &[String]| fields.iter().any(|v| v == field);
+    matches!(meta.schema.get_type(field), Some(FieldType::Str))
+        && meta.primary_key_field != field
+        && !contains(&meta.indexed_fields)
+        && !contains(&meta.foreign_keys)
+        && !contains(&meta.unique_fields)
+        && !meta.compound_indexes.iter().any(|index| contains(index))
+        && !meta
+            .views
+            .iter()
+            .any(|view| view.filter.iter().any(|(k, _)| k == field))
+}
+
+/// Extracts the values to train the dictionary of the collection on, from its records as got from redis
+pub(crate) fn extract_samples(
+    meta: &CollectionMeta,
+    records: Vec<HashMap<String, Vec<u8>>>,
+) -> PyResult<Vec<Vec<u8>>> {
+    if !meta
+        .schema
+        .mapping
+        .keys()
+        .any(|field| is_compressible(meta, field))
+    {
+        return Err(PyValueError::new_err(
+            "the collection has no string fields that can be compressed i.e. that are not the primary key, \
+            indexed, unique or filtered on by a view",
+        ));
+    }
+
+    Ok(records
+        .into_iter()
+        .flat_map(|record| record.into_iter())
+        .filter(|(field, value)| {
+            is_compressible(meta, field) && !value.is_empty() && !value.starts_with(&ZSTD_MAGIC)
+        })
+        .map(|(_, value)| value)
+        .collect())
+}
+
+/// Generates the pipeline that gets the records of the given ids, as saved in redis, to train on
+pub(crate) fn generate_sample_pipeline(collection_name: &str, ids: &[String]) -> redis::Pipeline {
+    let mut pipe = redis::pipe();
+    // the ids are got from the keys of the records, so they are already normalized
+    for id in ids {
+        pipe.hgetall(utils::generate_hash_key(collection_name, id, false));
+    }
+    pipe
+}
+
+/// Constructs the key of the hash holding the dictionaries of the given collection by their ids,
+/// and the id of the current one
+#[inline]
+pub(crate) fn generate_dictionaries_key(collection_name: &str) -> String {
+    utils::generate_auxiliary_key(collection_name, "compression", "dictionaries")
+}
+
+/// Generates the command that saves the given dictionary of the given collection as its current one
+pub(crate) fn generate_save_cmd(collection_name: &str, id: u32, dictionary: &[u8]) -> redis::Cmd {
+    let mut cmd = redis::cmd("HSET");
+    cmd.arg(generate_dictionaries_key(collection_name))
+        .arg(id)
+        .arg(dictionary)
+        .arg(CURRENT_DICTIONARY_FIELD)
+        .arg(id);
+    cmd
+}
+
+/// Sets the redis instance the dictionaries of the given collection are loaded from when values compressed
+/// with them are read before they are loaded
+pub(crate) fn register_source(collection_name: &str, client: &redis::Client) {
+    SOURCES
+        .write()
+        .unwrap_or_else(|e| e.into_inner())
+        .insert(collection_name.to_string(), client.clone());
+}
+
+/// Registers the given dictionary of the given collection for decompression, returning its decoder
+fn register_decoder(
+    collection_name: &str,
+    id: u32,
+    dictionary: &[u8],
+) -> Arc<DecoderDictionary<'static>> {
+    let decoder = Arc::new(DecoderDictionary::copy(dictionary));
+    DECODERS
+        .write()
+        .unwrap_or_else(|e| e.into_inner())
+        .insert((collection_name.to_string(), id), decoder.clone());
+    decoder
+}
+
+/// Gets the decoder of the dictionary of the given id of the given collection, loading the dictionary from
+/// redis if it is not loaded yet. The GIL is released while it is loaded
+fn get_decoder(collection_name: &str, id: u32) -> PyResult<Arc<DecoderDictionary<'static>>> {
+    let key = (collection_name.to_string(), id);
+    if let Some(decoder) = DECODERS.read().unwrap_or_else(|e| e.into_inner()).get(&key) {
+        return Ok(decoder.clone());
+    }
+
+    let client = SOURCES
+        .read()
+        .unwrap_or_else(|e| e.into_inner())
+        .get(collection_name)
+        .cloned()
+        .ok_or_else(|| {
+            PyValueError::new_err(format!(
+                "the value was compressed with the dictionary {} of {} which is not a collection of any store",
+                id, collection_name
+            ))
+        })?;
+    let dictionary: Option<Vec<u8>> = Python::with_gil(|py| {
+        py.allow_threads(|| {
+            let mut conn = client.get_connection_with_timeout(DICTIONARY_LOAD_TIMEOUT)?;
+            redis::cmd("HGET")
+                .arg(generate_dictionaries_key(collection_name))
+                .arg(id)
+                .query(&mut conn)
+        })
+    })
+    .map_err(|e| PyConnectionError::new_err(e.to_string()))?;
+
+    match dictionary {
+        Some(dictionary) => Ok(register_decoder(collection_name, id, &dictionary)),
+        None => Err(PyValueError::new_err(format!(
+            "the value was compressed with the dictionary {} which is not among the dictionaries of {} in redis",
+            id, collection_name
+        ))),
+    }
+}
+
+/// Converts a string value of the given collection got from redis into a string, decompressing it if it was
+/// compressed
+pub(crate) fn redis_to_string(collection_name: &str, data: &redis::Value) -> PyResult<String> {
+    match data {
+        redis::Value::Data(bytes) if bytes.starts_with(&ZSTD_MAGIC) => {
+            decompress(collection_name, bytes)
+        }
+        _ => parsers::redis_to_py::<String>(data),
+    }
+}
+
+/// Decompresses the given value of the given collection with the dictionary it was compressed with
+fn decompress(collection_name: &str, value: &[u8]) -> PyResult<String> {
+    let id = zstd_safe::get_dict_id_from_frame(value);
+    let decoder = get_decoder(collection_name, id)?;
+
+    let size = zstd_safe::get_frame_content_size(value);
+    if size > MAX_DECOMPRESSED_BYTES {
+        return Err(PyValueError::new_err(
+            "the compressed value is corrupted: its size is unknown",
+        ));
+    }
+
+    let mut decompressed = Vec::with_capacity(size as usize);
+    DECOMPRESSION_CONTEXT
+        .with(|context| {
+            context.borrow_mut().decompress_using_ddict(
+                &mut decompressed,
+                value,
+                decoder.as_ddict(),
+            )
+        })
+        .map_err(|code| {
+            PyValueError::new_err(format!(
+                "the compressed value is corrupted: {}",
+                zstd_safe::get_error_name(code)
+            ))
+        })?;
+    String::from_utf8(decompressed).map_err(|e| PyValueError::new_err(e.to_string()))
+}
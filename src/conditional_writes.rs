@@ -0,0 +1,266 @@
+use std::collections::HashMap;
+
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+
+use crate::constraints;
+use crate::store::CollectionMeta;
+use crate::{utils, vectors};
+
+const DELETE_MATCHING_RECORDS_SCRIPT: &str = r"local table_unpack = table.unpack or unpack local matched = {} local seen = {} local cursor = '0' local i = 6 local n = tonumber(ARGV[i]) local related = {} for j = i + 1, i + n do table.insert(related, ARGV[j]) end i = i + n + 1 n = tonumber(ARGV[i]) local zsets = {} for j = i + 1, i + n do table.insert(zsets, ARGV[j]) end i = i + n + 1 local filter_fields = {} local filter_values = {} for j = i, #ARGV, 2 do table.insert(filter_fields, ARGV[j]) table.insert(filter_values, ARGV[j + 1]) end repeat local result = redis.call('SCAN', cursor, 'MATCH', ARGV[1]) for _, key in ipairs(result[2]) do if not seen[key] and redis.call('TYPE', key).ok == 'hash' then seen[key] = true local values = redis.call('HMGET', key, table_unpack(filter_fields)) local matches = true for k, v in ipairs(filter_values) do if values[k] ~= v then matches = false break end end if matches then local id = string.sub(key, #ARGV[2] + 1) table.insert(matched, id) if ARGV[3] == '1' then if ARGV[4] ~= '' then local before = redis.call('HGETALL', key) local entry = {'op', 'delete', 'id', id} for k = 1, #before, 2 do table.insert(entry, 'before.' .. before[k]) table.insert(entry, before[k + 1]) end redis.call('XADD', ARGV[4], 'MAXLEN', '~', ARGV[5], '*', table_unpack(entry)) end for _, prefix in ipairs(related) do redis.call('DEL', prefix .. id) end for _, zset in ipairs(zsets) do redis.call('ZREM', zset, id) end redis.call('DEL', key) end end end end cursor = result[1] until (cursor == '0') return matched";
+const UPDATE_MATCHING_RECORDS_SCRIPT: &str = r"local table_unpack = table.unpack or unpack local matched = {} local seen = {} local cursor = '0' local n = tonumber(ARGV[8]) local changes = {} for j = 9, 8 + 2 * n do table.insert(changes, ARGV[j]) end local filter_fields = {} local filter_values = {} for j = 9 + 2 * n, #ARGV, 2 do table.insert(filter_fields, ARGV[j]) table.insert(filter_values, ARGV[j + 1]) end repeat local result = redis.call('SCAN', cursor, 'MATCH', ARGV[1]) for _, key in ipairs(result[2]) do if not seen[key] and redis.call('TYPE', key).ok == 'hash' then seen[key] = true local values = redis.call('HMGET', key, table_unpack(filter_fields)) local matches = true for k, v in ipairs(filter_values) do if values[k] ~= v then matches = false break end end if matches then local id = string.sub(key, #ARGV[2] + 1) table.insert(matched, id) if ARGV[3] == '1' then if ARGV[4] ~= '' then local before = redis.call('HGETALL', key) redis.call('HSET', key, table_unpack(changes)) local after = redis.call('HGETALL', key) local entry = {'op', 'update', 'id', id} for k = 1, #before, 2 do table.insert(entry, 'before.' .. before[k]) table.insert(entry, before[k + 1]) end for k = 1, #after, 2 do table.insert(entry, 'after.' .. after[k]) table.insert(entry, after[k + 1]) end redis.call('XADD', ARGV[4], 'MAXLEN', '~', ARGV[5], '*', table_unpack(entry)) else redis.call('HSET', key, table_unpack(changes)) end end end end end cursor = result[1] until (cursor == '0') if ARGV[3] == '1' and #matched > 0 then local tracked = {} for _, field in ipairs(redis.call('SMEMBERS', ARGV[6])) do tracked[field] = true end for j = 1, #changes, 2 do if tracked[changes[j]] then redis.call('PFADD', ARGV[7] .. changes[j], changes[j + 1]) end end end return matched";
+const DELETE_BY_ID_PATTERN_SCRIPT: &str = r"local table_unpack = table.unpack or unpack local result = redis.call('SCAN', ARGV[1], 'MATCH', ARGV[3], 'COUNT', ARGV[2]) local i = 8 local n = tonumber(ARGV[i]) local related = {} for j = i + 1, i + n do table.insert(related, ARGV[j]) end i = i + n + 1 n = tonumber(ARGV[i]) local zsets = {} for j = i + 1, i + n do table.insert(zsets, ARGV[j]) end local matched = {} for _, key in ipairs(result[2]) do if redis.call('TYPE', key).ok == 'hash' then local id = string.sub(key, #ARGV[4] + 1) table.insert(matched, id) if ARGV[5] == '1' then if ARGV[6] ~= '' then local before = redis.call('HGETALL', key) local entry = {'op', 'delete', 'id', id} for k = 1, #before, 2 do table.insert(entry, 'before.' .. before[k]) table.insert(entry, before[k + 1]) end redis.call('XADD', ARGV[6], 'MAXLEN', '~', ARGV[7], '*', table_unpack(entry)) end for _, prefix in ipairs(related) do redis.call('UNLINK', prefix .. id) end for _, zset in ipairs(zsets) do redis.call('ZREM', zset, id) end redis.call('UNLINK', key) end end end return {result[1], #result[2], matched}";
+
+/// Extracts the filter of `delete_where()` from its keyword arguments, the values the fields of the records
+/// to delete should be equal to. At least one is needed so that all records are not deleted by mistake
+pub(crate) fn extract_field_filters(
+    meta: &CollectionMeta,
+    field_filters: Option<&PyDict>,
+) -> PyResult<Vec<(String, String)>> {
+    let field_filters: HashMap<String, Py<PyAny>> = match field_filters {
+        Some(v) if !v.is_empty() => v.extract()?,
+        _ => {
+            return Err(PyValueError::new_err(
+                "at least one field filter is needed e.g. delete_where(author=\"Jane\")",
+            ))
+        }
+    };
+    meta.extract_filter(Some(field_filters))
+}
+
+/// Extracts the filter of `update_where()`, the values the fields of the records to update should be equal to.
+/// At least one is needed so that all records are not updated by mistake
+pub(crate) fn extract_filters(
+    meta: &CollectionMeta,
+    filters: HashMap<String, Py<PyAny>>,
+) -> PyResult<Vec<(String, String)>> {
+    if filters.is_empty() {
+        return Err(PyValueError::new_err(
+            "filters should have at least one field e.g. {\"author\": \"Jane\"}",
+        ));
+    }
+    meta.extract_filter(Some(filters))
+}
+
+/// Extracts the changes of `update_where()`, the new values of some non-nested fields of the records, as the values
+/// saved in redis. The primary key and counter fields cannot be changed, and the constraints of the fields are checked
+pub(crate) fn extract_changes(
+    collection_name: &str,
+    meta: &CollectionMeta,
+    changes: HashMap<String, Py<PyAny>>,
+) -> PyResult<Vec<(String, String)>> {
+    if changes.is_empty() {
+        return Err(PyValueError::new_err(
+            "changes should have at least one field e.g. {\"in_stock\": False}",
+        ));
+    }
+
+    let changes = changes
+        .iter()
+        .map(|(field, value)| {
+            meta.ensure_scalar_field(field)?;
+            if field == &meta.primary_key_field {
+                return Err(PyValueError::new_err(format!(
+                    "{:?} is the primary key field yet primary keys cannot be changed",
+                    field
+                )));
+            }
+            if meta.counter_fields.iter().any(|v| v == field) {
+                return Err(PyValueError::new_err(format!(
+                    "{:?} is a counter field yet counter fields are changed with increment()",
+                    field
+                )));
+            }
+
+            let type_ = meta.schema.get_type(field).unwrap();
+            Ok((field.clone(), utils::field_value_to_redis(type_, value)?))
+        })
+        .collect::<PyResult<Vec<_>>>()?;
+
+    let key = utils::generate_key_prefix(collection_name);
+    constraints::enforce_constraints(collection_name, meta, &[(key, changes.clone())])?;
+    Ok(changes)
+}
+
+/// Checks whether the records of the given collection can be deleted by `DELETE_MATCHING_RECORDS_SCRIPT` itself.
+/// They cannot if deleting them means updating field indexes, list indexes, unique values, null indexes or quotas,
+/// whose scripts are run for each record in the pipeline of `utils::generate_delete_pipeline()` instead
+#[inline]
+pub(crate) fn is_deleted_in_script(meta: &CollectionMeta) -> bool {
+    meta.indexed_fields.is_empty()
+        && meta.indexed_list_fields.is_empty()
+        && meta.compound_indexes.is_empty()
+        && meta.unique_fields.is_empty()
+        && meta.namespace.is_none()
+        && meta.get_null_indexes().is_empty()
+}
+
+/// Checks whether the given changes to the records of the given collection can be made by
+/// `UPDATE_MATCHING_RECORDS_SCRIPT` itself. They cannot if they mean updating field indexes, list indexes,
+/// unique values, range indexes, null indexes, packed vectors, copies in views or quotas, which are updated
+/// in the pipeline of `utils::generate_insert_pipeline()` instead. The script updates the cardinality trackers
+pub(crate) fn is_updated_in_script(meta: &CollectionMeta, changes: &[(String, String)]) -> bool {
+    let range_indexes = meta.get_range_indexes();
+    let null_indexes = meta.get_null_indexes();
+    meta.views.is_empty()
+        && meta.namespace.is_none()
+        && !vectors::has_vector_fields(meta)
+        && changes.iter().all(|(field, _)| {
+            !meta.indexed_fields.contains(field)
+                && !meta.indexed_list_fields.contains(field)
+                && !meta.compound_indexes.iter().any(|v| v.contains(field))
+                && !meta.unique_fields.contains(field)
+                && !range_indexes.contains(field)
+                && !null_indexes.contains(field)
+        })
+}
+
+/// Generates the command that scans the given collection for the records whose fields are equal to the values
+/// in the given filter, returning their ids. The records are deleted by the command too, with their counters,
+/// copies in views, packed vectors and entries in range indexes and the insertion order index,
+/// if `is_deleted_in_script()`
+pub(crate) fn generate_delete_matching_cmd(
+    collection_name: &str,
+    meta: &CollectionMeta,
+    filter: &[(String, String)],
+) -> redis::Cmd {
+    let is_deleted = is_deleted_in_script(meta);
+    let (related, zsets) = get_related_keys(collection_name, meta);
+    let change_stream = match meta.track_changes {
+        true => utils::generate_change_stream_key(collection_name),
+        false => "".to_string(),
+    };
+
+    let mut cmd = redis::cmd("EVAL");
+    cmd.arg(DELETE_MATCHING_RECORDS_SCRIPT)
+        .arg(0)
+        .arg(utils::generate_collection_key_pattern(collection_name))
+        .arg(utils::generate_key_prefix(collection_name))
+        .arg(if is_deleted { "1" } else { "0" })
+        .arg(change_stream)
+        .arg(utils::CHANGE_STREAM_MAX_LENGTH)
+        .arg(related.len())
+        .arg(related)
+        .arg(zsets.len())
+        .arg(zsets);
+    for (field, value) in filter {
+        cmd.arg(field).arg(value);
+    }
+    cmd
+}
+
+/// Generates the command that gets the ids of the records of the given collection whose ids match the given glob
+/// pattern e.g. "order-2023-*", in the SCAN batch of the given count at the given cursor, replying with the next
+/// cursor, the number of keys scanned and the ids. The records are unlinked by the command too, with their
+/// counters, copies in views, packed vectors and entries in range indexes and the insertion order index,
+/// if `is_deleted_in_script()`
+pub(crate) fn generate_delete_by_id_pattern_cmd(
+    collection_name: &str,
+    meta: &CollectionMeta,
+    id_pattern: &str,
+    cursor: &str,
+    count: usize,
+) -> redis::Cmd {
+    let (related, zsets) = get_related_keys(collection_name, meta);
+    let change_stream = match meta.track_changes {
+        true => utils::generate_change_stream_key(collection_name),
+        false => "".to_string(),
+    };
+
+    let mut cmd = redis::cmd("EVAL");
+    cmd.arg(DELETE_BY_ID_PATTERN_SCRIPT)
+        .arg(0)
+        .arg(cursor)
+        .arg(count)
+        .arg(utils::generate_hash_key(
+            collection_name,
+            id_pattern,
+            meta.case_insensitive_keys,
+        ))
+        .arg(utils::generate_key_prefix(collection_name))
+        .arg(if is_deleted_in_script(meta) { "1" } else { "0" })
+        .arg(change_stream)
+        .arg(utils::CHANGE_STREAM_MAX_LENGTH)
+        .arg(related.len())
+        .arg(related)
+        .arg(zsets.len())
+        .arg(zsets);
+    cmd
+}
+
+/// Gets the prefixes of the keys that are deleted along with the records of the given collection i.e. those of
+/// their counters, copies in views and packed vectors, followed by the keys of the sorted sets the ids of the
+/// records are removed from i.e. their range indexes with their unscored ids and the insertion order index
+fn get_related_keys(collection_name: &str, meta: &CollectionMeta) -> (Vec<String>, Vec<String>) {
+    let mut related: Vec<String> = meta
+        .counter_fields
+        .iter()
+        .map(|field| utils::generate_counter_key(collection_name, field, "", false))
+        .chain(
+            meta.views
+                .iter()
+                .map(|view| utils::generate_key_prefix(&view.get_key_space(collection_name))),
+        )
+        .collect();
+    if vectors::has_vector_fields(meta) {
+        related.push(vectors::generate_vector_key(collection_name, "", false));
+    }
+    let mut zsets: Vec<String> = meta
+        .get_range_indexes()
+        .iter()
+        .flat_map(|field| {
+            [
+                utils::generate_range_index_key(collection_name, field),
+                utils::generate_unscored_ids_key(collection_name, field),
+            ]
+        })
+        .collect();
+    if meta.track_insertion_order {
+        zsets.push(utils::generate_insertion_order_key(collection_name));
+    }
+    (related, zsets)
+}
+
+/// Generates the command that scans the given collection for the records whose fields are equal to the values
+/// in the given filter, returning their ids. The given changes are made to the records by the command too,
+/// compressing the values that are compressed and adding those of the tracked fields to their HyperLogLogs,
+/// if `is_updated_in_script()`
+pub(crate) fn generate_update_matching_cmd(
+    collection_name: &str,
+    meta: &CollectionMeta,
+    filter: &[(String, String)],
+    changes: &[(String, String)],
+) -> redis::Cmd {
+    let is_updated = is_updated_in_script(meta, changes);
+    let change_stream = match meta.track_changes {
+        true => utils::generate_change_stream_key(collection_name),
+        false => "".to_string(),
+    };
+
+    let mut cmd = redis::cmd("EVAL");
+    cmd.arg(UPDATE_MATCHING_RECORDS_SCRIPT)
+        .arg(0)
+        .arg(utils::generate_collection_key_pattern(collection_name))
+        .arg(utils::generate_key_prefix(collection_name))
+        .arg(if is_updated { "1" } else { "0" })
+        .arg(change_stream)
+        .arg(utils::CHANGE_STREAM_MAX_LENGTH)
+        .arg(utils::generate_cardinality_fields_key(collection_name))
+        .arg(utils::generate_cardinality_key(collection_name, ""));
+    if is_updated {
+        let changes: Vec<(&String, &String)> = changes.iter().map(|(k, v)| (k, v)).collect();
+        match meta.compression.get_dictionary() {
+            Some(dictionary) => cmd
+                .arg(changes.len())
+                .arg(dictionary.compress_fields(meta, &changes)),
+            None => cmd.arg(changes.len()).arg(changes),
+        };
+    } else {
+        cmd.arg(0);
+    }
+    for (field, value) in filter {
+        cmd.arg(field).arg(value);
+    }
+    cmd
+}
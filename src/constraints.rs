@@ -0,0 +1,181 @@
+use std::collections::HashMap;
+
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+
+use crate::schema::Schema;
+use crate::store::CollectionMeta;
+use crate::utils;
+
+/// The constraints of the values of a field as given in its JSON schema by a constrained type
+/// e.g. `conint(ge=0)` or `constr(max_length=10)`
+#[derive(Clone, Debug, Default)]
+pub(crate) struct FieldConstraints {
+    minimum: Option<f64>,
+    exclusive_minimum: Option<f64>,
+    maximum: Option<f64>,
+    exclusive_maximum: Option<f64>,
+    min_length: Option<usize>,
+    max_length: Option<usize>,
+    pattern: Option<(String, Py<PyAny>)>,
+}
+
+/// The constraints of the fields of a collection, by field
+pub(crate) type ConstraintRules = HashMap<String, FieldConstraints>;
+
+impl FieldConstraints {
+    /// Extracts the constraints of a field from its JSON schema property, or None if it has none
+    fn from_py_schema(prop: &PyDict) -> PyResult<Option<Self>> {
+        let pattern = match prop.get_item("pattern") {
+            None => None,
+            Some(pattern) => {
+                let pattern: String = pattern.extract()?;
+                // patterns are matched as pydantic matches them, from the start of the value
+                let regex = PyModule::import(prop.py(), "re")?
+                    .getattr("compile")?
+                    .call1((&pattern,))?;
+                Some((pattern, regex.into()))
+            }
+        };
+
+        let constraints = Self {
+            minimum: extract_item(prop, "minimum")?,
+            exclusive_minimum: extract_item(prop, "exclusiveMinimum")?,
+            maximum: extract_item(prop, "maximum")?,
+            exclusive_maximum: extract_item(prop, "exclusiveMaximum")?,
+            min_length: extract_item(prop, "minLength")?,
+            max_length: extract_item(prop, "maxLength")?,
+            pattern,
+        };
+
+        let is_constrained = constraints.minimum.is_some()
+            || constraints.exclusive_minimum.is_some()
+            || constraints.maximum.is_some()
+            || constraints.exclusive_maximum.is_some()
+            || constraints.min_length.is_some()
+            || constraints.max_length.is_some()
+            || constraints.pattern.is_some();
+        Ok(is_constrained.then_some(constraints))
+    }
+
+    /// Checks the given value of the given field, as it is saved in redis, against these constraints,
+    /// returning a ValueError that says which constraint it breaks if any
+    fn check(&self, field: &str, value: &str) -> PyResult<()> {
+        match self.find_violation(value)? {
+            None => Ok(()),
+            Some(violation) => Err(PyValueError::new_err(format!(
+                "{:?} is not a valid value of the field {:?}: {}",
+                value, field, violation
+            ))),
+        }
+    }
+
+    /// Finds the constraint the given value breaks, described as it would be in an error
+    fn find_violation(&self, value: &str) -> PyResult<Option<String>> {
+        let has_bounds = self.minimum.is_some()
+            || self.exclusive_minimum.is_some()
+            || self.maximum.is_some()
+            || self.exclusive_maximum.is_some();
+        if has_bounds {
+            let number = match value.parse::<f64>() {
+                Ok(number) => number,
+                Err(_) => return Ok(Some("it should be a number".to_string())),
+            };
+
+            if let Some(minimum) = self.minimum.filter(|v| number < *v) {
+                return Ok(Some(format!("it should be at least {}", minimum)));
+            }
+            if let Some(minimum) = self.exclusive_minimum.filter(|v| number <= *v) {
+                return Ok(Some(format!("it should be greater than {}", minimum)));
+            }
+            if let Some(maximum) = self.maximum.filter(|v| number > *v) {
+                return Ok(Some(format!("it should be at most {}", maximum)));
+            }
+            if let Some(maximum) = self.exclusive_maximum.filter(|v| number >= *v) {
+                return Ok(Some(format!("it should be less than {}", maximum)));
+            }
+        }
+
+        let length = value.chars().count();
+        if let Some(min_length) = self.min_length.filter(|v| length < *v) {
+            return Ok(Some(format!(
+                "it should have at least {} characters",
+                min_length
+            )));
+        }
+        if let Some(max_length) = self.max_length.filter(|v| length > *v) {
+            return Ok(Some(format!(
+                "it should have at most {} characters",
+                max_length
+            )));
+        }
+
+        if let Some((pattern, regex)) = &self.pattern {
+            let is_match = Python::with_gil(|py| -> PyResult<bool> {
+                Ok(!regex.call_method1(py, "match", (value,))?.is_none(py))
+            })?;
+            if !is_match {
+                return Ok(Some(format!("it should match the pattern {:?}", pattern)));
+            }
+        }
+
+        Ok(None)
+    }
+}
+
+/// Extracts the constraints of the non-nested fields of the given schema from the JSON schema of its model
+pub(crate) fn extract_constraint_rules(
+    py_schema: &PyAny,
+    schema: &Schema,
+) -> PyResult<ConstraintRules> {
+    let mut rules = ConstraintRules::new();
+    let props = match py_schema.get_item("properties") {
+        Ok(props) => props.downcast::<PyDict>()?,
+        Err(_) => return Ok(rules),
+    };
+
+    for (field, type_) in &schema.mapping {
+        if type_.is_nested() {
+            continue;
+        }
+
+        if let Some(prop) = props.get_item(field) {
+            if let Some(constraints) = FieldConstraints::from_py_schema(prop.downcast()?)? {
+                rules.insert(field.clone(), constraints);
+            }
+        }
+    }
+
+    Ok(rules)
+}
+
+/// Checks the values of the given records of the given collection against the constraints of their fields,
+/// so that writes that bypass the validation of the model e.g. partial updates, cannot save invalid values.
+/// Nested records are skipped since their collections are not written to directly, and so are None values
+pub(crate) fn enforce_constraints(
+    collection_name: &str,
+    meta: &CollectionMeta,
+    records: &[(String, Vec<(String, String)>)],
+) -> PyResult<()> {
+    if meta.constraints.is_empty() {
+        return Ok(());
+    }
+
+    let key_prefix = utils::generate_key_prefix(collection_name);
+    for (_, record) in records.iter().filter(|(pk, _)| pk.starts_with(&key_prefix)) {
+        for (field, value) in record {
+            match meta.constraints.get(field) {
+                Some(constraints) if value != "None" => constraints.check(field, value)?,
+                _ => {}
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Extracts the item of the given key of a JSON schema property if it is set
+fn extract_item<'a, T: FromPyObject<'a>>(prop: &'a PyDict, key: &str) -> PyResult<Option<T>> {
+    prop.get_item(key).map(|v| v.extract()).transpose()
+}
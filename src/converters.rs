@@ -0,0 +1,210 @@
+use std::sync::Arc;
+
+use pyo3::prelude::*;
+use pyo3::types::PyString;
+
+use crate::coercions::{self, Coercion, CoercionRules};
+use crate::compression;
+use crate::field_types::FieldType;
+use crate::parsers;
+use crate::schema::Schema;
+use crate::utils;
+
+/// Converts a value of the collection of the given name read from redis into the python value of a field
+/// of the given type, applying the given coercions
+type ConvertFn = fn(Python, &str, &FieldType, &redis::Value, &[Coercion]) -> PyResult<Py<PyAny>>;
+
+/// The converter of a field of a collection, picked once for the field's type and coercions
+#[derive(Clone, Debug)]
+struct FieldConverter {
+    name: String,
+    key: Py<PyString>,
+    field_type: FieldType,
+    coercions: Vec<Coercion>,
+    convert: ConvertFn,
+}
+
+/// The converters of the fields of a collection, sorted by field name, with which the records read
+/// from redis are converted into python values without matching on the type of each of their fields
+#[derive(Clone, Debug, Default)]
+pub(crate) struct ConverterTable(Arc<Vec<FieldConverter>>);
+
+impl ConverterTable {
+    /// Creates the converters of the fields of the given schema, with the given coercion rules
+    pub(crate) fn new(schema: &Schema, coercion_rules: &CoercionRules) -> Self {
+        Python::with_gil(|py| {
+            let mut converters: Vec<FieldConverter> = schema
+                .mapping
+                .iter()
+                .map(|(name, field_type)| {
+                    let coercions = coercion_rules.get(name).cloned().unwrap_or_default();
+                    let convert = pick_convert_fn(field_type, &coercions);
+                    FieldConverter {
+                        name: name.clone(),
+                        key: schema.get_field_name(py, name),
+                        field_type: field_type.clone(),
+                        coercions,
+                        convert,
+                    }
+                })
+                .collect();
+            converters.sort_unstable_by(|a, b| a.name.cmp(&b.name));
+            Self(Arc::new(converters))
+        })
+    }
+
+    /// Gets a copy of this table whose converters read nested records, and the records nested in them,
+    /// as dictionaries instead of instances of their models
+    pub(crate) fn with_nested_as_dicts(&self) -> Self {
+        let converters = self
+            .0
+            .iter()
+            .map(|converter| FieldConverter {
+                convert: if converter.field_type.is_nested() {
+                    convert_nested_as_dict
+                } else {
+                    converter.convert
+                },
+                ..converter.clone()
+            })
+            .collect();
+        Self(Arc::new(converters))
+    }
+
+    /// Converts the given value of the given field of the collection of the given name into its python value,
+    /// returning it with the interned name of the field, or None if the field is not in the schema
+    pub(crate) fn convert<'a>(
+        &'a self,
+        py: Python,
+        collection_name: &str,
+        field: &str,
+        value: &redis::Value,
+    ) -> Option<PyResult<(&'a Py<PyString>, Py<PyAny>)>> {
+        let index = self
+            .0
+            .binary_search_by(|c| c.name.as_str().cmp(field))
+            .ok()?;
+        let converter = &self.0[index];
+        Some(
+            (converter.convert)(
+                py,
+                collection_name,
+                &converter.field_type,
+                value,
+                &converter.coercions,
+            )
+            .map(|v| (&converter.key, v)),
+        )
+    }
+}
+
+/// Picks the function that converts the values of a field of the given type and coercions
+fn pick_convert_fn(field_type: &FieldType, coercions: &[Coercion]) -> ConvertFn {
+    if !coercions.is_empty() {
+        return convert_coerced;
+    }
+
+    match field_type {
+        FieldType::Str => convert_str,
+        FieldType::Int => convert_int,
+        FieldType::Float => convert_float,
+        FieldType::Bool => convert_bool,
+        FieldType::Datetime => convert_datetime,
+        FieldType::Date => convert_date,
+        _ => convert_any,
+    }
+}
+
+fn convert_str(
+    py: Python,
+    collection_name: &str,
+    _: &FieldType,
+    data: &redis::Value,
+    _: &[Coercion],
+) -> PyResult<Py<PyAny>> {
+    Ok(compression::redis_to_string(collection_name, data)?.into_py(py))
+}
+
+fn convert_int(
+    py: Python,
+    _: &str,
+    _: &FieldType,
+    data: &redis::Value,
+    _: &[Coercion],
+) -> PyResult<Py<PyAny>> {
+    Ok(parsers::redis_to_py::<i64>(data)?.into_py(py))
+}
+
+fn convert_float(
+    py: Python,
+    _: &str,
+    _: &FieldType,
+    data: &redis::Value,
+    _: &[Coercion],
+) -> PyResult<Py<PyAny>> {
+    Ok(parsers::redis_to_py::<f64>(data)?.into_py(py))
+}
+
+fn convert_bool(
+    py: Python,
+    _: &str,
+    _: &FieldType,
+    data: &redis::Value,
+    _: &[Coercion],
+) -> PyResult<Py<PyAny>> {
+    let data = parsers::redis_to_py::<String>(data)?;
+    Ok(parsers::parse_str::<bool>(&data)?.into_py(py))
+}
+
+fn convert_datetime(
+    _: Python,
+    _: &str,
+    _: &FieldType,
+    data: &redis::Value,
+    _: &[Coercion],
+) -> PyResult<Py<PyAny>> {
+    let data = parsers::redis_to_py::<String>(data)?;
+    utils::timestamp_to_py_datetime(parsers::parse_datetime_to_timestamp(&data)?)
+}
+
+fn convert_date(
+    _: Python,
+    _: &str,
+    _: &FieldType,
+    data: &redis::Value,
+    _: &[Coercion],
+) -> PyResult<Py<PyAny>> {
+    let data = parsers::redis_to_py::<String>(data)?;
+    utils::timestamp_to_py_date(parsers::parse_date_to_timestamp(&data)?)
+}
+
+/// Converts the values of nested, dict, list, tuple and None fields, which need their types
+fn convert_any(
+    _: Python,
+    collection_name: &str,
+    field_type: &FieldType,
+    data: &redis::Value,
+    _: &[Coercion],
+) -> PyResult<Py<PyAny>> {
+    field_type.redis_to_py(collection_name, data)
+}
+
+fn convert_nested_as_dict(
+    _: Python,
+    collection_name: &str,
+    field_type: &FieldType,
+    data: &redis::Value,
+    _: &[Coercion],
+) -> PyResult<Py<PyAny>> {
+    field_type.redis_to_py_dict(collection_name, data)
+}
+
+fn convert_coerced(
+    _: Python,
+    collection_name: &str,
+    field_type: &FieldType,
+    data: &redis::Value,
+    coercions: &[Coercion],
+) -> PyResult<Py<PyAny>> {
+    coercions::redis_to_py(collection_name, field_type, data, coercions)
+}
@@ -0,0 +1,110 @@
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use once_cell::sync::OnceCell;
+use pyo3::create_exception;
+use pyo3::exceptions::{PyTimeoutError, PyValueError};
+use pyo3::prelude::*;
+use pyo3::types::{IntoPyDict, PyDateTime};
+
+use crate::asyncio;
+
+// Raised when the deadline set in `orredis.deadline` passes before a call gets a connection to redis.
+// It is a TimeoutError so that code handling the timeouts of a request also handles it
+create_exception!(orredis, DeadlineExceededError, PyTimeoutError);
+
+/// The context variable holding the deadline of the calls made in its context, if any
+static DEADLINE_VAR: OnceCell<PyObject> = OnceCell::new();
+
+/// Gets the `deadline` context variable, creating it the first time, with None as its default value
+pub(crate) fn get_deadline_var<'py>(py: Python<'py>) -> PyResult<&'py PyAny> {
+    DEADLINE_VAR
+        .get_or_try_init(|| -> PyResult<PyObject> {
+            let kwargs = [("default", py.None())].into_py_dict(py);
+            Ok(py
+                .import("contextvars")?
+                .getattr("ContextVar")?
+                .call(("deadline",), Some(kwargs))?
+                .into())
+        })
+        .map(|var| var.as_ref(py))
+}
+
+/// The time by which a call should be done, as set by the caller in the `deadline` context variable
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct Deadline(SystemTime);
+
+impl Deadline {
+    /// Gets the deadline of the current sync call, if any
+    pub(crate) fn current() -> PyResult<Option<Self>> {
+        Python::with_gil(|py| Self::from_py(get_deadline_var(py)?.call_method0("get")?))
+    }
+
+    /// Gets the deadline of the current async call, if any, from the context variables of its caller.
+    /// Futures that are not run for an async call have no deadline
+    pub(crate) fn current_async() -> PyResult<Option<Self>> {
+        Python::with_gil(|py| {
+            let locals = match asyncio::async_std::get_current_locals(py) {
+                Ok(locals) => locals,
+                Err(_) => return Ok(None),
+            };
+            let deadline = locals
+                .context(py)
+                .call_method1("run", (get_deadline_var(py)?.getattr("get")?,))?;
+            Self::from_py(deadline)
+        })
+    }
+
+    /// Converts the value of the `deadline` context variable, a datetime or unix timestamp in seconds,
+    /// into a deadline. Naive datetimes are in the local timezone
+    fn from_py(value: &PyAny) -> PyResult<Option<Self>> {
+        if value.is_none() {
+            return Ok(None);
+        }
+
+        let timestamp: f64 = if value.is_instance_of::<PyDateTime>()? {
+            value.call_method0("timestamp")?.extract()?
+        } else {
+            value.extract().map_err(|_| {
+                PyValueError::new_err(format!(
+                    "expected the deadline to be a datetime or unix timestamp, got {}",
+                    value
+                ))
+            })?
+        };
+
+        if !timestamp.is_finite() || timestamp < 0.0 {
+            return Err(PyValueError::new_err(format!(
+                "expected the deadline to be after the unix epoch, got {}",
+                value
+            )));
+        }
+
+        Ok(Some(Self(UNIX_EPOCH + Duration::from_secs_f64(timestamp))))
+    }
+
+    /// Gets the time left before this deadline, failing with a DeadlineExceededError if it has passed
+    pub(crate) fn remaining(&self) -> PyResult<Duration> {
+        match self.0.duration_since(SystemTime::now()) {
+            Ok(remaining) if !remaining.is_zero() => Ok(remaining),
+            _ => Err(self.exceeded_error()),
+        }
+    }
+
+    /// Whether this deadline has passed
+    #[inline]
+    pub(crate) fn has_passed(&self) -> bool {
+        self.remaining().is_err()
+    }
+
+    /// Creates the DeadlineExceededError of a call whose deadline passed before it got a connection
+    pub(crate) fn exceeded_error(&self) -> PyErr {
+        let passed_ms = SystemTime::now()
+            .duration_since(self.0)
+            .unwrap_or_default()
+            .as_millis();
+        DeadlineExceededError::new_err(format!(
+            "the deadline of the call passed {}ms ago, before it got a connection to redis",
+            passed_ms
+        ))
+    }
+}
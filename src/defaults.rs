@@ -0,0 +1,81 @@
+use std::collections::HashMap;
+use std::time::Duration;
+
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+
+use crate::id_generators::{self, IdGenerator};
+use crate::parsers;
+
+/// The options of create_collection() that can be given once for all collections of a store.
+/// Options given to create_collection() override them
+#[derive(Clone, Debug)]
+pub(crate) struct CollectionDefaults {
+    pub(crate) id_generator: Option<IdGenerator>,
+    pub(crate) snowflake_worker_id: Option<u64>,
+    pub(crate) retention: Option<Duration>,
+    pub(crate) bloom_filter: bool,
+    pub(crate) track_changes: bool,
+    pub(crate) register_nested_models: bool,
+    pub(crate) nested_depth: usize,
+    pub(crate) namespace: Option<String>,
+    pub(crate) enforce_constraints: bool,
+}
+
+impl Default for CollectionDefaults {
+    fn default() -> Self {
+        Self {
+            id_generator: None,
+            snowflake_worker_id: None,
+            retention: None,
+            bloom_filter: false,
+            track_changes: false,
+            register_nested_models: false,
+            nested_depth: 3,
+            namespace: None,
+            enforce_constraints: false,
+        }
+    }
+}
+
+impl CollectionDefaults {
+    /// Creates the defaults from the python dict of options, validating them
+    pub(crate) fn from_py(defaults: Option<HashMap<String, &PyAny>>) -> PyResult<Self> {
+        let mut result = Self::default();
+        let mut id_generator: Option<String> = None;
+        for (option, value) in defaults.unwrap_or_default() {
+            match option.as_str() {
+                "id_generator" => id_generator = value.extract()?,
+                "snowflake_worker_id" => {
+                    result.snowflake_worker_id =
+                        id_generators::validate_snowflake_worker_id(value.extract()?)?
+                }
+                "retention" if value.is_none() => result.retention = None,
+                "retention" => result.retention = Some(parsers::extract_duration(value)?),
+                "bloom_filter" => result.bloom_filter = value.extract()?,
+                "track_changes" => result.track_changes = value.extract()?,
+                "register_nested_models" => result.register_nested_models = value.extract()?,
+                "nested_depth" => result.nested_depth = value.extract()?,
+                "namespace" => result.namespace = value.extract()?,
+                "enforce_constraints" => result.enforce_constraints = value.extract()?,
+                _ => {
+                    return Err(PyValueError::new_err(format!(
+                    "{:?} is not an option that can be set for all collections. Expected one of \
+                        id_generator, snowflake_worker_id, retention, bloom_filter, track_changes, \
+                        register_nested_models, nested_depth, namespace or enforce_constraints",
+                    option
+                )))
+                }
+            }
+        }
+
+        if result.nested_depth == 0 {
+            return Err(PyValueError::new_err("nested_depth should be at least 1"));
+        }
+        result.id_generator = id_generator
+            .map(|name| IdGenerator::from_name(&name, result.snowflake_worker_id))
+            .transpose()?;
+
+        Ok(result)
+    }
+}
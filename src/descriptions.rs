@@ -0,0 +1,117 @@
+use std::collections::HashMap;
+
+use pyo3::prelude::*;
+use pyo3::types::{IntoPyDict, PyDict, PyList};
+
+use crate::field_types::FieldType;
+use crate::links::LinkDefinition;
+use crate::store::CollectionMeta;
+use crate::views::ViewDefinition;
+
+/// Describes the collections, views and links of a store as a python dictionary with the keys "collections",
+/// "views" and "links", each a dictionary of their descriptions by name, in the order of their names
+pub(crate) fn describe_store(
+    collections_meta: &HashMap<String, CollectionMeta>,
+    views: &HashMap<String, (String, ViewDefinition)>,
+    links: &HashMap<String, LinkDefinition>,
+) -> PyResult<Py<PyAny>> {
+    Python::with_gil(|py| {
+        let collections = PyDict::new(py);
+        for name in sorted_keys(collections_meta) {
+            collections.set_item(name, describe_collection(py, &collections_meta[name])?)?;
+        }
+
+        let views_description = PyDict::new(py);
+        for name in sorted_keys(views) {
+            let (source, view) = &views[name];
+            let description = PyDict::new(py);
+            description.set_item("source", source)?;
+            description.set_item("projection", &view.projection)?;
+            description.set_item("filter", view.filter.clone().into_py_dict(py))?;
+            views_description.set_item(name, description)?;
+        }
+
+        let links_description = PyDict::new(py);
+        for name in sorted_keys(links) {
+            let description = PyDict::new(py);
+            description.set_item("left", &links[name].left)?;
+            description.set_item("right", &links[name].right)?;
+            links_description.set_item(name, description)?;
+        }
+
+        let description = PyDict::new(py);
+        description.set_item("collections", collections)?;
+        description.set_item("views", views_description)?;
+        description.set_item("links", links_description)?;
+        Ok(description.into_py(py))
+    })
+}
+
+/// Describes a collection as a python dictionary with the keys: "primary_key_field", "fields", a dictionary
+/// of field to the description of its type, "counter_fields", "indexed_fields", "indexed_list_fields",
+/// "compound_indexes", "range_indexes", "unique_fields", "relationships", a list of dictionaries with the keys
+/// "field", "kind" and "collection", "version" and "namespace", the last two being None if they are not set
+fn describe_collection<'a>(py: Python<'a>, meta: &CollectionMeta) -> PyResult<&'a PyDict> {
+    let fields = PyDict::new(py);
+    for field in sorted_keys(&meta.schema.mapping) {
+        fields.set_item(field, meta.schema.mapping[field].describe())?;
+    }
+
+    let mut range_indexes: Vec<String> = meta.get_range_indexes().into_iter().collect();
+    range_indexes.sort_unstable();
+
+    let description = PyDict::new(py);
+    description.set_item("primary_key_field", &meta.primary_key_field)?;
+    description.set_item("fields", fields)?;
+    description.set_item("counter_fields", &meta.counter_fields)?;
+    description.set_item("indexed_fields", &meta.indexed_fields)?;
+    description.set_item("indexed_list_fields", &meta.indexed_list_fields)?;
+    description.set_item("compound_indexes", &meta.compound_indexes)?;
+    description.set_item("range_indexes", range_indexes)?;
+    description.set_item("unique_fields", &meta.unique_fields)?;
+    description.set_item("relationships", describe_relationships(py, meta)?)?;
+    description.set_item("version", meta.versioning.as_ref().map(|v| v.version))?;
+    description.set_item("namespace", &meta.namespace)?;
+    Ok(description)
+}
+
+/// Describes the relationships of a collection to other collections i.e. its nested models, whose kind is
+/// "nested", its fields annotated with `Reference[Model]`, whose kind is "reference", and its foreign keys,
+/// whose kind is "foreign_key" and whose collection is None since it is not declared
+fn describe_relationships<'a>(py: Python<'a>, meta: &CollectionMeta) -> PyResult<&'a PyList> {
+    let mut relationships: Vec<(String, &str, Option<String>)> = meta
+        .schema
+        .mapping
+        .iter()
+        .filter_map(|(field, type_)| {
+            let kind = match type_ {
+                FieldType::Reference { .. } => "reference",
+                _ => "nested",
+            };
+            let collection = type_.get_nested_model_name()?;
+            Some((field.clone(), kind, Some(collection.to_string())))
+        })
+        .collect();
+
+    for field in &meta.foreign_keys {
+        relationships.push((field.clone(), "foreign_key", None));
+    }
+    relationships.sort_unstable();
+
+    let list = PyList::empty(py);
+    for (field, kind, collection) in relationships {
+        let relationship = PyDict::new(py);
+        relationship.set_item("field", field)?;
+        relationship.set_item("kind", kind)?;
+        relationship.set_item("collection", collection)?;
+        list.append(relationship)?;
+    }
+    Ok(list)
+}
+
+/// Gets the keys of the given map in order
+fn sorted_keys<V>(map: &HashMap<String, V>) -> Vec<&String> {
+    let mut keys: Vec<&String> = map.keys().collect();
+    keys.sort_unstable();
+    keys
+}
@@ -0,0 +1,142 @@
+use pyo3::exceptions::{PyConnectionError, PyValueError};
+use pyo3::prelude::*;
+use redis::IntoConnectionInfo;
+use trust_dns_resolver::error::ResolveErrorKind;
+use trust_dns_resolver::Resolver;
+
+/// The suffix of the schemes of urls whose host is the name of a DNS SRV record of the redis
+/// endpoints e.g. redis+srv://_redis._tcp.example.com/0
+const SRV_SCHEME_SUFFIX: &str = "+srv";
+/// The service and protocol labels prepended to the host of srv urls that do not have them
+const DEFAULT_SRV_PREFIX: &str = "_redis._tcp.";
+
+/// A redis endpoint as found in a DNS SRV record
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct SrvRecord {
+    priority: u16,
+    weight: u16,
+    port: u16,
+    target: String,
+}
+
+/// Opens the redis client of the given url. IPv6 literal hosts e.g. redis://[::1]:6379 are supported,
+/// as are urls with a "+srv" scheme e.g. redis+srv://_redis._tcp.example.com/0 whose endpoint is
+/// looked up in the DNS SRV records of the host once, when the client is opened, without holding the GIL
+pub(crate) fn open_client(py: Python, url: &str) -> PyResult<redis::Client> {
+    let url = py.allow_threads(|| resolve_srv_url(url))?;
+    let mut info = url
+        .as_str()
+        .into_connection_info()
+        .map_err(|e| PyConnectionError::new_err(e.to_string()))?;
+
+    // IPv6 hosts are kept in their brackets by the url parser but cannot be connected to with them
+    match &mut info.addr {
+        redis::ConnectionAddr::Tcp(host, _) | redis::ConnectionAddr::TcpTls { host, .. } => {
+            if let Some(ip) = host.strip_prefix('[').and_then(|h| h.strip_suffix(']')) {
+                *host = ip.to_string();
+            }
+        }
+        redis::ConnectionAddr::Unix(_) => {}
+    }
+
+    redis::Client::open(info).map_err(|e| PyConnectionError::new_err(e.to_string()))
+}
+
+/// Replaces the host of a url with a "+srv" scheme with the endpoint in its DNS SRV records,
+/// returning any other url as is
+fn resolve_srv_url(url: &str) -> PyResult<String> {
+    let (scheme, rest) = match url.split_once("://") {
+        Some((scheme, rest)) => (scheme, rest),
+        None => return Ok(url.to_string()),
+    };
+    let scheme = match scheme.strip_suffix(SRV_SCHEME_SUFFIX) {
+        Some(scheme) => scheme,
+        None => return Ok(url.to_string()),
+    };
+
+    let mut parsed = redis::parse_redis_url(&format!("{}://{}", scheme, rest))
+        .ok_or_else(|| PyValueError::new_err(format!("{} is not a valid redis url", url)))?;
+    if parsed.port().is_some() {
+        return Err(PyValueError::new_err(format!(
+            "{} should not have a port; it is got from the SRV record",
+            url
+        )));
+    }
+
+    let host = parsed
+        .host_str()
+        .ok_or_else(|| PyValueError::new_err(format!("{} has no SRV record name", url)))?;
+    let name = if host.starts_with('_') {
+        host.to_string()
+    } else {
+        format!("{}{}", DEFAULT_SRV_PREFIX, host)
+    };
+
+    let record = pick_srv_record(lookup_srv(&name)?).ok_or_else(|| {
+        PyConnectionError::new_err(format!("no redis endpoint found in {}", name))
+    })?;
+    parsed
+        .set_host(Some(&record.target))
+        .map_err(|e| PyValueError::new_err(e.to_string()))?;
+    parsed
+        .set_port(Some(record.port))
+        .map_err(|_| PyValueError::new_err(format!("{} cannot have a port", url)))?;
+    Ok(parsed.to_string())
+}
+
+/// Picks the record to connect to as RFC 2782 recommends i.e. one of those with the lowest
+/// priority, chosen at random with a probability proportional to its weight
+fn pick_srv_record(records: Vec<SrvRecord>) -> Option<SrvRecord> {
+    let priority = records.iter().map(|r| r.priority).min()?;
+    let candidates: Vec<SrvRecord> = records
+        .into_iter()
+        .filter(|r| r.priority == priority)
+        .collect();
+
+    let total_weight: u32 = candidates.iter().map(|r| r.weight as u32).sum();
+    if total_weight == 0 {
+        return candidates.into_iter().next();
+    }
+
+    let mut pick = fastrand::u32(0..total_weight);
+    candidates.into_iter().find(|r| {
+        if pick < r.weight as u32 {
+            return true;
+        }
+        pick -= r.weight as u32;
+        false
+    })
+}
+
+/// Looks up the SRV records of the given name with the resolver configured in /etc/resolv.conf,
+/// which uses its nameservers in turn, its search domains and ndots, and TCP for truncated responses
+fn lookup_srv(name: &str) -> PyResult<Vec<SrvRecord>> {
+    let resolver = Resolver::from_system_conf().map_err(|e| {
+        PyConnectionError::new_err(format!("failed to read the DNS configuration: {}", e))
+    })?;
+    let lookup = resolver.srv_lookup(name).map_err(|e| match e.kind() {
+        ResolveErrorKind::NoRecordsFound { .. } => {
+            PyConnectionError::new_err(format!("no SRV records found for {}", name))
+        }
+        _ => PyConnectionError::new_err(format!(
+            "failed to look up the SRV records of {}: {}",
+            name, e
+        )),
+    })?;
+
+    let records = lookup
+        .iter()
+        .filter_map(|srv| {
+            let target = srv.target().to_utf8();
+            let target = target.trim_end_matches('.');
+            // a target of "." means the service is not available at this name
+            (!target.is_empty()).then(|| SrvRecord {
+                priority: srv.priority(),
+                weight: srv.weight(),
+                port: srv.port(),
+                target: target.to_string(),
+            })
+        })
+        .collect();
+    Ok(records)
+}
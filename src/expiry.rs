@@ -0,0 +1,67 @@
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use pyo3::types::PyDateTime;
+
+use crate::parsers;
+use crate::utils;
+
+/// When records written to redis expire, as given by a ttl
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum Expiry {
+    /// The time-to-live of the records from the time they are written
+    After(Duration),
+    /// The unix time in milliseconds at which the records expire
+    At(u64),
+}
+
+impl Expiry {
+    /// Creates a new expiry from a python datetime at which records expire, or from a timedelta
+    /// or number of seconds after which they expire. Naive datetimes are in the local timezone
+    pub(crate) fn from_py(value: &PyAny) -> PyResult<Self> {
+        if value.is_instance_of::<PyDateTime>()? {
+            let timestamp: f64 = value.call_method0("timestamp")?.extract()?;
+            if !timestamp.is_finite() || timestamp < 0.0 {
+                return Err(PyValueError::new_err(format!(
+                    "expected a datetime after the unix epoch, got {}",
+                    value
+                )));
+            }
+
+            return Ok(Expiry::At((timestamp * 1000.0).ceil() as u64));
+        }
+
+        Ok(Expiry::After(parsers::extract_duration(value)?))
+    }
+
+    /// Adds the command that sets this expiry on the given key to the pipeline, with millisecond precision
+    pub(crate) fn add_cmd(&self, pipe: &mut redis::Pipeline, key: &str) {
+        match self {
+            Expiry::After(ttl) => pipe.pexpire(key, utils::duration_in_millis(ttl)),
+            Expiry::At(timestamp) => pipe.pexpire_at(key, *timestamp as usize),
+        };
+    }
+
+    /// Gets the name and the argument of the command that sets this expiry on a key, for scripts that set it
+    pub(crate) fn to_cmd_args(self) -> (&'static str, usize) {
+        match self {
+            Expiry::After(ttl) => ("PEXPIRE", utils::duration_in_millis(&ttl)),
+            Expiry::At(timestamp) => ("PEXPIREAT", timestamp as usize),
+        }
+    }
+
+    /// Gets the earliest of this expiry and the given duration from now
+    pub(crate) fn min(self, ttl: Duration) -> Self {
+        match self {
+            Expiry::After(v) => Expiry::After(v.min(ttl)),
+            Expiry::At(timestamp) => {
+                let now = SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .unwrap_or_default();
+                let latest = utils::duration_in_millis(&(now + ttl)) as u64;
+                Expiry::At(timestamp.min(latest))
+            }
+        }
+    }
+}
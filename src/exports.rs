@@ -0,0 +1,224 @@
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter, Read, Write};
+
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+
+use crate::batching::InsertRecords;
+
+/// The bytes every export starts with, followed by the version of its format
+const EXPORT_MAGIC: &[u8; 8] = b"ORREDISX";
+/// The version of the format of exports
+const FORMAT_VERSION: u8 = 1;
+/// The size of the pieces in which an export is written into its sink
+pub(crate) const EXPORT_BUFFER_SIZE: usize = 8 * 1024 * 1024;
+/// The suffix of the path of the file in which the progress of an import is recorded
+const PROGRESS_SUFFIX: &str = ".progress";
+
+/// Writes the records of a collection into an export, a file made of a header with the name of the collection
+/// followed by chunks of records, each with the SHA-1 checksum of its records, and an empty chunk marking its end
+pub(crate) struct ExportWriter<W: Write> {
+    file: BufWriter<W>,
+}
+
+impl<W: Write> ExportWriter<W> {
+    /// Starts the export of the given collection into the given sink e.g. a file, which is written
+    /// into in pieces of at most `buffer_size` bytes
+    pub(crate) fn new(sink: W, collection_name: &str, buffer_size: usize) -> PyResult<Self> {
+        let mut file = BufWriter::with_capacity(buffer_size, sink);
+        file.write_all(EXPORT_MAGIC)?;
+        file.write_all(&[FORMAT_VERSION])?;
+        write_bytes(&mut file, collection_name.as_bytes())?;
+        Ok(Self { file })
+    }
+
+    /// Writes the given records, as (id, fields) tuples, as one chunk. Nothing is written if there are none,
+    /// as an empty chunk marks the end of the export
+    pub(crate) fn write_chunk(&mut self, records: &InsertRecords) -> PyResult<()> {
+        if records.is_empty() {
+            return Ok(());
+        }
+
+        let mut payload = Vec::new();
+        for (id, fields) in records {
+            write_bytes(&mut payload, id.as_bytes())?;
+            payload.write_all(&(fields.len() as u32).to_le_bytes())?;
+            for (field, value) in fields {
+                write_bytes(&mut payload, field.as_bytes())?;
+                write_bytes(&mut payload, value.as_bytes())?;
+            }
+        }
+
+        self.file.write_all(&(records.len() as u32).to_le_bytes())?;
+        self.file.write_all(&(payload.len() as u64).to_le_bytes())?;
+        self.file
+            .write_all(&sha1::Sha1::from(&payload).digest().bytes())?;
+        self.file.write_all(&payload)?;
+        Ok(())
+    }
+
+    /// Marks the end of the export, flushing it into its sink, which is returned
+    pub(crate) fn finish(mut self) -> PyResult<W> {
+        self.file.write_all(&0u32.to_le_bytes())?;
+        self.file.write_all(&0u64.to_le_bytes())?;
+        self.file.write_all(&sha1::Sha1::new().digest().bytes())?;
+        let mut sink = self.file.into_inner().map_err(|e| e.into_error())?;
+        sink.flush()?;
+        Ok(sink)
+    }
+}
+
+/// Reads the chunks of records of an export written by an ExportWriter, verifying their checksums
+pub(crate) struct ExportReader {
+    file: BufReader<File>,
+    chunks_read: u64,
+}
+
+impl ExportReader {
+    /// Opens the export at the given path, ensuring it is an export of the given collection
+    pub(crate) fn open(path: &str, collection_name: &str) -> PyResult<Self> {
+        let mut file = BufReader::new(File::open(path)?);
+        let mut magic = [0u8; 9];
+        match file.read_exact(&mut magic) {
+            Ok(()) if magic[..8] == EXPORT_MAGIC[..] => {}
+            Ok(()) | Err(_) => {
+                return Err(PyValueError::new_err(format!(
+                    "{:?} is not an export of orredis",
+                    path
+                )))
+            }
+        }
+        if magic[8] != FORMAT_VERSION {
+            return Err(PyValueError::new_err(format!(
+                "the export is of version {} of the format yet only version {} is supported",
+                magic[8], FORMAT_VERSION
+            )));
+        }
+
+        let exported_name = String::from_utf8_lossy(&read_bytes(&mut file)?).to_string();
+        if exported_name != collection_name {
+            return Err(PyValueError::new_err(format!(
+                "the export is of the collection {:?}, not {:?}",
+                exported_name, collection_name
+            )));
+        }
+
+        Ok(Self {
+            file,
+            chunks_read: 0,
+        })
+    }
+
+    /// Reads the next chunk of records as (id, fields) tuples, or None once the end of the export is reached.
+    /// The records are not parsed if `skip` is true, as when they were imported before an interruption
+    pub(crate) fn read_chunk(&mut self, skip: bool) -> PyResult<Option<InsertRecords>> {
+        let mut header = [0u8; 32];
+        self.file
+            .read_exact(&mut header)
+            .map_err(|e| self.to_py_err(e))?;
+        let count = u32::from_le_bytes(header[..4].try_into().unwrap()) as usize;
+        let length = u64::from_le_bytes(header[4..12].try_into().unwrap());
+        if count == 0 {
+            return Ok(None);
+        }
+
+        let mut payload = Vec::new();
+        (&mut self.file).take(length).read_to_end(&mut payload)?;
+        if payload.len() as u64 != length {
+            return Err(self.to_py_err(io::ErrorKind::UnexpectedEof.into()));
+        }
+        self.chunks_read += 1;
+        if skip {
+            return Ok(Some(vec![]));
+        }
+        if sha1::Sha1::from(&payload).digest().bytes()[..] != header[12..] {
+            return Err(PyValueError::new_err(format!(
+                "chunk {} of the export is corrupted: its checksum does not match its records",
+                self.chunks_read
+            )));
+        }
+
+        let mut payload = &payload[..];
+        let mut records = Vec::with_capacity(count);
+        for _ in 0..count {
+            let id = read_string(&mut payload)?;
+            let mut fields = Vec::new();
+            for _ in 0..read_u32(&mut payload)? {
+                fields.push((read_string(&mut payload)?, read_string(&mut payload)?));
+            }
+            records.push((id, fields));
+        }
+        Ok(Some(records))
+    }
+
+    /// Gets the number of chunks of records read so far
+    #[inline]
+    pub(crate) fn chunks_read(&self) -> u64 {
+        self.chunks_read
+    }
+
+    /// Converts the given error of a read into a python error, saying the export is truncated if it ended early
+    fn to_py_err(&self, err: io::Error) -> PyErr {
+        match err.kind() {
+            io::ErrorKind::UnexpectedEof => PyValueError::new_err(format!(
+                "the export is truncated after chunk {}; it was not completely written",
+                self.chunks_read
+            )),
+            _ => err.into(),
+        }
+    }
+}
+
+/// Gets the number of chunks of the export at the given path that were imported before its import was
+/// interrupted, or 0 if none was
+pub(crate) fn read_progress(path: &str) -> PyResult<u64> {
+    match fs::read_to_string(format!("{}{}", path, PROGRESS_SUFFIX)) {
+        Ok(progress) => progress.trim().parse::<u64>().map_err(|_| {
+            PyValueError::new_err(format!(
+                "the progress of the import of {:?} is corrupted; import it with resume=False",
+                path
+            ))
+        }),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(0),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Records that the given number of chunks of the export at the given path were imported
+pub(crate) fn save_progress(path: &str, chunks: u64) -> PyResult<()> {
+    fs::write(format!("{}{}", path, PROGRESS_SUFFIX), chunks.to_string())?;
+    Ok(())
+}
+
+/// Removes the record of the progress of the import of the export at the given path, once it is complete
+pub(crate) fn clear_progress(path: &str) -> PyResult<()> {
+    match fs::remove_file(format!("{}{}", path, PROGRESS_SUFFIX)) {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e.into()),
+        _ => Ok(()),
+    }
+}
+
+/// Writes the given bytes prefixed with their length
+fn write_bytes(writer: &mut impl Write, bytes: &[u8]) -> io::Result<()> {
+    writer.write_all(&(bytes.len() as u32).to_le_bytes())?;
+    writer.write_all(bytes)
+}
+
+/// Reads bytes prefixed with their length
+fn read_bytes(reader: &mut impl Read) -> PyResult<Vec<u8>> {
+    let mut bytes = vec![0u8; read_u32(reader)? as usize];
+    reader.read_exact(&mut bytes)?;
+    Ok(bytes)
+}
+
+/// Reads a string prefixed with its length
+fn read_string(reader: &mut impl Read) -> PyResult<String> {
+    String::from_utf8(read_bytes(reader)?).map_err(|e| PyValueError::new_err(e.to_string()))
+}
+
+/// Reads a little-endian u32
+fn read_u32(reader: &mut impl Read) -> PyResult<u32> {
+    let mut bytes = [0u8; 4];
+    reader.read_exact(&mut bytes)?;
+    Ok(u32::from_le_bytes(bytes))
+}
@@ -0,0 +1,193 @@
+use std::collections::HashSet;
+use std::ops::{Range, RangeInclusive};
+
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use pyo3::types::{PyDict, PyTuple};
+
+use crate::batching::InsertRecords;
+use crate::field_types::FieldType;
+use crate::schema::Schema;
+use crate::store::CollectionMeta;
+use crate::{utils, versioning};
+
+/// The number of fake records inserted in each round trip to redis
+pub(crate) const FAKE_BATCH_SIZE: usize = 1_000;
+/// The range of the lengths of generated strings
+const STR_LENGTHS: RangeInclusive<usize> = 8..=16;
+/// The range of the numbers of items in generated lists and dicts
+const COLLECTION_LENGTHS: RangeInclusive<usize> = 0..=3;
+/// The range of generated ints
+const INT_RANGE: Range<i64> = 0..1_000_000;
+/// The range of generated dates and datetimes as timestamps, from 2000-01-01 to 2030-01-01 UTC.
+/// It is fixed so that records generated with the same seed are the same whenever they are generated
+const TIMESTAMP_RANGE: Range<i64> = 946_684_800..1_893_456_000;
+/// The number of times a primary key is generated again if it was already generated
+const MAX_PRIMARY_KEY_ATTEMPTS: usize = 100;
+
+/// Generates records of random but valid values for the fields of a model, e.g. to fill a collection
+/// for load tests or demos. Generators created with the same seed generate the same records
+pub(crate) struct FakeRecords {
+    rng: fastrand::Rng,
+    nested_depth: usize,
+    primary_keys: HashSet<String>,
+}
+
+impl FakeRecords {
+    /// Creates a generator whose records have nested records at most `nested_depth` levels deep.
+    /// Nested fields deeper than this are None
+    pub(crate) fn new(seed: Option<u64>, nested_depth: usize) -> Self {
+        Self {
+            rng: seed.map_or_else(fastrand::Rng::new, fastrand::Rng::with_seed),
+            nested_depth,
+            primary_keys: Default::default(),
+        }
+    }
+
+    /// Generates `n` records of the given collection, with their nested records, ready to be inserted
+    pub(crate) fn generate_batch(
+        &mut self,
+        py: Python,
+        collection_name: &str,
+        meta: &CollectionMeta,
+        n: usize,
+    ) -> PyResult<InsertRecords> {
+        let mut records: InsertRecords = Vec::with_capacity(2 * n);
+        for _ in 0..n {
+            let item = self.generate(py, meta)?;
+            let mut records_to_insert = utils::prepare_record_to_insert(
+                collection_name,
+                &meta.schema,
+                &item,
+                &meta.primary_key_field,
+                None,
+                &meta.id_generator,
+                meta.case_insensitive_keys,
+            )?;
+            records.append(&mut records_to_insert);
+        }
+        versioning::stamp_versions(&mut records, &meta.record_versions);
+        Ok(records)
+    }
+
+    /// Generates an instance of the model of the given collection whose primary key is unlike that
+    /// of any instance generated before it
+    fn generate(&mut self, py: Python, meta: &CollectionMeta) -> PyResult<Py<PyAny>> {
+        let primary_key_field = meta.primary_key_field.as_str();
+        let record = self.generate_fields(py, &meta.schema, self.nested_depth)?;
+
+        if let Some(field_type) = meta.schema.get_type(primary_key_field) {
+            let mut attempts = 0;
+            loop {
+                let primary_key = record
+                    .get_item(primary_key_field)
+                    .map_or(Ok(String::new()), |v| v.str().map(|v| v.to_string()))?;
+                if self.primary_keys.insert(primary_key) {
+                    break;
+                }
+
+                attempts += 1;
+                if attempts == MAX_PRIMARY_KEY_ATTEMPTS {
+                    return Err(PyValueError::new_err(format!(
+                        "could not generate a unique value of the primary key field {:?} after {} attempts",
+                        primary_key_field, MAX_PRIMARY_KEY_ATTEMPTS
+                    )));
+                }
+                record.set_item(primary_key_field, self.generate_value(py, field_type, 0)?)?;
+            }
+        }
+
+        meta.model_type.call(py, (), Some(record))
+    }
+
+    /// Generates the dictionary of the fields of a record of the given schema, in the order of their names
+    /// so that the same seed generates the same values
+    fn generate_fields<'a>(
+        &mut self,
+        py: Python<'a>,
+        schema: &Schema,
+        depth: usize,
+    ) -> PyResult<&'a PyDict> {
+        let mut fields: Vec<(&String, &FieldType)> = schema.mapping.iter().collect();
+        fields.sort_unstable_by_key(|(name, _)| *name);
+
+        let record = PyDict::new(py);
+        for (name, field_type) in fields {
+            record.set_item(name, self.generate_value(py, field_type, depth)?)?;
+        }
+        Ok(record)
+    }
+
+    /// Generates a value of the given type whose nested records are at most `depth` levels deep
+    fn generate_value(
+        &mut self,
+        py: Python,
+        field_type: &FieldType,
+        depth: usize,
+    ) -> PyResult<Py<PyAny>> {
+        match field_type {
+            FieldType::Nested { .. }
+            | FieldType::Reference { .. }
+            | FieldType::Projection { .. } => {
+                if depth == 0 {
+                    return Ok(py.None());
+                }
+
+                match field_type.get_nested_model()? {
+                    Some(model) => {
+                        let record = self.generate_fields(py, &model.schema, depth - 1)?;
+                        model.model_type.call(py, (), Some(record))
+                    }
+                    None => Ok(py.None()),
+                }
+            }
+            FieldType::Dict { key, value } => {
+                let dict = PyDict::new(py);
+                for _ in 0..self.rng.usize(COLLECTION_LENGTHS) {
+                    dict.set_item(
+                        self.generate_value(py, key, depth)?,
+                        self.generate_value(py, value, depth)?,
+                    )?;
+                }
+                Ok(dict.into_py(py))
+            }
+            FieldType::List { items } => {
+                let list = (0..self.rng.usize(COLLECTION_LENGTHS))
+                    .map(|_| self.generate_value(py, items, depth))
+                    .collect::<PyResult<Vec<Py<PyAny>>>>()?;
+                Ok(list.into_py(py))
+            }
+            FieldType::Tuple { items } => {
+                let tuple = items
+                    .iter()
+                    .map(|item| self.generate_value(py, item, depth))
+                    .collect::<PyResult<Vec<Py<PyAny>>>>()?;
+                Ok(PyTuple::new(py, tuple).into_py(py))
+            }
+            FieldType::VarTuple { items } => {
+                let tuple = (0..self.rng.usize(COLLECTION_LENGTHS))
+                    .map(|_| self.generate_value(py, items, depth))
+                    .collect::<PyResult<Vec<Py<PyAny>>>>()?;
+                Ok(PyTuple::new(py, tuple).into_py(py))
+            }
+            FieldType::Vector { dimension } => {
+                let vector: Vec<f64> = (0..*dimension).map(|_| self.rng.f64()).collect();
+                Ok(vector.into_py(py))
+            }
+            FieldType::Str => Ok(self.generate_str().into_py(py)),
+            FieldType::Int => Ok(self.rng.i64(INT_RANGE).into_py(py)),
+            FieldType::Float => Ok((self.rng.f64() * INT_RANGE.end as f64).into_py(py)),
+            FieldType::Bool => Ok(self.rng.bool().into_py(py)),
+            FieldType::Datetime => utils::timestamp_to_py_datetime(self.rng.i64(TIMESTAMP_RANGE)),
+            FieldType::Date => utils::timestamp_to_py_date(self.rng.i64(TIMESTAMP_RANGE)),
+            FieldType::None => Ok(py.None()),
+        }
+    }
+
+    /// Generates a string of random letters and digits
+    fn generate_str(&mut self) -> String {
+        (0..self.rng.usize(STR_LENGTHS))
+            .map(|_| self.rng.alphanumeric())
+            .collect()
+    }
+}
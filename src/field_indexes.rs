@@ -0,0 +1,229 @@
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+
+use crate::store::CollectionMeta;
+use crate::utils;
+
+const UPDATE_FIELD_INDEXES_SCRIPT: &str = r"local table_unpack = table.unpack or unpack local id, prefix, mode = ARGV[1], ARGV[2], ARGV[3] local written = {} local i = 5 + 2 * tonumber(ARGV[4]) for j = 5, i - 1, 2 do written[ARGV[j]] = ARGV[j + 1] end while i <= #ARGV do local n = tonumber(ARGV[i]) local fields = { table_unpack(ARGV, i + 1, i + n) } i = i + n + 1 local current = redis.call('HMGET', KEYS[1], table_unpack(fields)) local old, new = {}, {} local has_old, has_new = true, mode == 'set' for j, field in ipairs(fields) do if current[j] then old[j] = normalize(field, current[j]) else has_old = false end local value = written[field] or current[j] if value then new[j] = normalize(field, value) else has_new = false end end local name = table.concat(fields, ',') .. '_%&_' local old_key, new_key = false, false if has_old then old_key = prefix .. name .. table.concat(old, '_%&_') end if has_new then new_key = prefix .. name .. table.concat(new, '_%&_') end if old_key and old_key ~= new_key then redis.call('SREM', old_key, id) end if new_key then redis.call('SADD', new_key, id) end end";
+const GET_IDS_BY_NORMALIZED_VALUES_SCRIPT: &str = r"local values = {} for i = 2, #ARGV, 2 do table.insert(values, normalize(ARGV[i], ARGV[i + 1])) end return redis.call('SMEMBERS', ARGV[1] .. table.concat(values, '_%&_'))";
+
+/// Gets the fields of a collection that are indexed i.e. its foreign keys, which are indexed so that the children
+/// of a record can be found, followed by the other given fields
+pub(crate) fn merge_indexed_fields(
+    foreign_keys: &[String],
+    indexed_fields: Option<Vec<String>>,
+) -> Vec<String> {
+    let mut fields = foreign_keys.to_vec();
+    for field in indexed_fields.unwrap_or_default() {
+        if !fields.contains(&field) {
+            fields.push(field);
+        }
+    }
+    fields
+}
+
+/// Ensures the given fields can be indexed i.e. they are non-nested, non-counter fields of the collection
+pub(crate) fn validate_indexed_fields(meta: &CollectionMeta, fields: &[String]) -> PyResult<()> {
+    for field in fields {
+        meta.ensure_scalar_field(field)?;
+        if meta.counter_fields.iter().any(|v| v == field) {
+            return Err(PyValueError::new_err(format!(
+                "{:?} is a counter field yet counter fields cannot be indexed",
+                field
+            )));
+        }
+    }
+
+    Ok(())
+}
+
+/// Ensures the given compound indexes can be created i.e. each has at least one field, and its fields are distinct
+/// non-nested, non-counter fields of the collection
+pub(crate) fn validate_compound_indexes(
+    meta: &CollectionMeta,
+    indexes: &[Vec<String>],
+) -> PyResult<()> {
+    for fields in indexes {
+        if fields.is_empty() {
+            return Err(PyValueError::new_err(
+                "a compound index should have at least one field",
+            ));
+        }
+
+        if let Some(field) = fields
+            .iter()
+            .enumerate()
+            .find_map(|(i, field)| fields[..i].contains(field).then_some(field))
+        {
+            return Err(PyValueError::new_err(format!(
+                "{:?} appears more than once in the compound index {:?}",
+                field, fields
+            )));
+        }
+        validate_indexed_fields(meta, fields)?;
+    }
+
+    Ok(())
+}
+
+/// Gets the fields of the index of the given collection with the given fields, in any order, with the given
+/// values of the fields in the order of the index, as saved in redis
+pub(crate) fn to_index_fields_and_values(
+    meta: &CollectionMeta,
+    fields: &[String],
+    values: &[Py<PyAny>],
+) -> PyResult<(Vec<String>, Vec<String>)> {
+    if fields.len() != values.len() {
+        return Err(PyValueError::new_err(format!(
+            "got {} values for the {} fields {:?}",
+            values.len(),
+            fields.len(),
+            fields
+        )));
+    }
+
+    let index = get_indexes(meta)
+        .into_iter()
+        .find(|index| index.len() == fields.len() && index.iter().all(|v| fields.contains(v)))
+        .ok_or_else(|| {
+            PyValueError::new_err(format!(
+                "{:?} is not a compound index of the collection. Add it to compound_indexes in create_collection()",
+                fields
+            ))
+        })?;
+    let values = index
+        .iter()
+        .map(|field| {
+            let i = fields.iter().position(|v| v == field).unwrap();
+            utils::field_value_to_redis(meta.schema.get_type(field).unwrap(), &values[i])
+        })
+        .collect::<PyResult<Vec<String>>>()?;
+
+    Ok((index.to_vec(), values))
+}
+
+/// Generates the command that gets the ids of the records of the given collection in the index of the given fields
+/// whose values, as saved in redis, are the given ones, normalizing them first if the fields have normalizers
+pub(crate) fn generate_lookup_cmd(
+    collection_name: &str,
+    meta: &CollectionMeta,
+    fields: &[String],
+    values: &[String],
+) -> redis::Cmd {
+    let name = fields.join(",");
+    if !meta.normalizers.contains_any(fields) {
+        let mut cmd = redis::cmd("SMEMBERS");
+        cmd.arg(generate_index_key(
+            collection_name,
+            &name,
+            &values.join("_%&_"),
+        ));
+        return cmd;
+    }
+
+    let mut cmd = redis::cmd("EVAL");
+    cmd.arg(meta.normalizers.wrap(GET_IDS_BY_NORMALIZED_VALUES_SCRIPT))
+        .arg(0)
+        .arg(generate_index_key(collection_name, &name, ""));
+    for (field, value) in fields.iter().zip(values) {
+        cmd.arg(field).arg(value);
+    }
+    cmd
+}
+
+/// Constructs the key of the set of the ids of the records of the given collection whose indexed field
+/// has the given value, as saved in redis, or whose compound index has the given name and value
+#[inline]
+fn generate_index_key(collection_name: &str, field: &str, value: &str) -> String {
+    utils::generate_auxiliary_key(collection_name, "index", &format!("{}_%&_{}", field, value))
+}
+
+/// Constructs the prefix of the keys of the field indexes of the given collection
+#[inline]
+fn generate_index_key_prefix(collection_name: &str) -> String {
+    utils::generate_auxiliary_key(collection_name, "index", "")
+}
+
+/// Gets the fields of each index of the given collection i.e. those of its compound indexes and the field
+/// of each of its indexed fields
+fn get_indexes(meta: &CollectionMeta) -> Vec<&[String]> {
+    meta.indexed_fields
+        .iter()
+        .map(std::slice::from_ref)
+        .chain(meta.compound_indexes.iter().map(Vec::as_slice))
+        .collect()
+}
+
+/// Adds the given indexes to the arguments of the last command of the pipeline, each as the number of its
+/// fields followed by the fields
+fn add_index_args(pipe: &mut redis::Pipeline, indexes: &[&[String]]) {
+    for fields in indexes {
+        pipe.arg(fields.len()).arg(*fields);
+    }
+}
+
+/// Adds to the pipeline the command that moves the record of the given id to the indexes of the values
+/// of its indexed fields, for the indexes with any of the given fields, before the fields are written
+pub(crate) fn add_update_cmd(
+    pipe: &mut redis::Pipeline,
+    collection_name: &str,
+    meta: &CollectionMeta,
+    id: &str,
+    fields: &[(&String, &String)],
+) {
+    let indexes: Vec<&[String]> = get_indexes(meta)
+        .into_iter()
+        .filter(|index| fields.iter().any(|(field, _)| index.contains(field)))
+        .collect();
+    if indexes.is_empty() {
+        return;
+    }
+
+    let written: Vec<&(&String, &String)> = fields
+        .iter()
+        .filter(|(field, _)| indexes.iter().any(|index| index.contains(field)))
+        .collect();
+    pipe.cmd("EVAL")
+        .arg(meta.normalizers.wrap(UPDATE_FIELD_INDEXES_SCRIPT))
+        .arg(1)
+        .arg(utils::generate_hash_key(
+            collection_name,
+            id,
+            meta.case_insensitive_keys,
+        ))
+        .arg(id)
+        .arg(generate_index_key_prefix(collection_name))
+        .arg("set")
+        .arg(written.len())
+        .arg(written);
+    add_index_args(pipe, &indexes);
+}
+
+/// Adds to the pipeline the command that removes the record of the given id from the indexes of the values
+/// of its indexed fields, before it is deleted
+pub(crate) fn add_remove_cmd(
+    pipe: &mut redis::Pipeline,
+    collection_name: &str,
+    meta: &CollectionMeta,
+    id: &str,
+) {
+    let indexes = get_indexes(meta);
+    if indexes.is_empty() {
+        return;
+    }
+
+    pipe.cmd("EVAL")
+        .arg(meta.normalizers.wrap(UPDATE_FIELD_INDEXES_SCRIPT))
+        .arg(1)
+        .arg(utils::generate_hash_key(
+            collection_name,
+            id,
+            meta.case_insensitive_keys,
+        ))
+        .arg(id)
+        .arg(generate_index_key_prefix(collection_name))
+        .arg("remove")
+        .arg(0);
+    add_index_args(pipe, &indexes);
+    pipe.ignore();
+}
@@ -1,10 +1,12 @@
-use std::collections::HashMap;
+use std::sync::Arc;
 
 use pyo3::exceptions::{PyKeyError, PyValueError};
 use pyo3::prelude::*;
-use pyo3::types::{IntoPyDict, PyDict, PyList, PyType};
+use pyo3::types::{IntoPyDict, PyDict, PyList, PyTuple, PyType};
 
-use crate::schema::Schema;
+use crate::compression;
+use crate::schema::{Schema, SchemaContext, SchemaRegistry};
+use crate::versioning::VERSION_FIELD;
 use crate::{parsers, utils};
 
 macro_rules! py_key_error {
@@ -25,15 +27,39 @@ macro_rules! to_py {
     };
 }
 
+/// A model nested in another model. Its records are saved in its own collection
+#[derive(Debug)]
+pub(crate) struct NestedModel {
+    pub(crate) model_name: String,
+    pub(crate) schema: Schema,
+    pub(crate) primary_key_field: String,
+    pub(crate) model_type: Py<PyType>,
+    pub(crate) case_insensitive_keys: bool,
+}
+
 #[derive(Clone, Debug)]
 pub(crate) enum FieldType {
     Nested {
+        model: Arc<NestedModel>,
+    },
+    /// A model nested in another model whose schema was still being extracted when the field was found
+    /// e.g. a model nested in itself. Its model is got from the registry when it is used
+    Reference {
         model_name: String,
+        registry: SchemaRegistry,
+    },
+    /// Some fields of a model nested in another model, requested by their dotted paths e.g. "address.city"
+    /// in a partial read. They are read as a dictionary since they are not a whole model
+    Projection {
+        model: Arc<NestedModel>,
         schema: Box<Schema>,
-        primary_key_field: String,
-        model_type: Py<PyType>,
+        as_model: bool,
     },
+    /// A dictionary whose keys are strings unless their type is known from the model e.g. `Dict[int, str]`,
+    /// since the keys of JSON objects are always strings. Its values are of the type given in its schema
+    /// e.g. `Dict[str, List[int]]`, or strings if they are of any type
     Dict {
+        key: Box<FieldType>,
         value: Box<FieldType>,
     },
     List {
@@ -42,6 +68,16 @@ pub(crate) enum FieldType {
     Tuple {
         items: Vec<FieldType>,
     },
+    /// A tuple of any number of items of the same type e.g. `Tuple[int, ...]`, whose JSON schema is that
+    /// of a list yet whose values are saved as tuples
+    VarTuple {
+        items: Box<FieldType>,
+    },
+    /// A list of floats of a fixed dimension annotated as `Vector[N]`, read like a list of floats.
+    /// Its values are also saved as packed 32-bit floats for the vector indexes of RediSearch
+    Vector {
+        dimension: usize,
+    },
     Str,
     Int,
     Float,
@@ -52,36 +88,125 @@ pub(crate) enum FieldType {
 }
 
 impl FieldType {
-    /// Converts data got from redis into a FieldType.
+    /// Whether this field holds a model nested in another model
+    #[inline]
+    pub(crate) fn is_nested(&self) -> bool {
+        matches!(
+            self,
+            FieldType::Nested { .. } | FieldType::Reference { .. } | FieldType::Projection { .. }
+        )
+    }
+
+    /// Gets the name of the model held by this field if it is a nested model
+    pub(crate) fn get_nested_model_name(&self) -> Option<&str> {
+        match self {
+            FieldType::Nested { model } | FieldType::Projection { model, .. } => {
+                Some(&model.model_name)
+            }
+            FieldType::Reference { model_name, .. } => Some(model_name),
+            _ => None,
+        }
+    }
+
+    /// Gets the model held by this field if it is a nested model, resolving references from the registry
+    pub(crate) fn get_nested_model(&self) -> PyResult<Option<Arc<NestedModel>>> {
+        match self {
+            FieldType::Nested { model } | FieldType::Projection { model, .. } => {
+                Ok(Some(model.clone()))
+            }
+            FieldType::Reference {
+                model_name,
+                registry,
+            } => registry.get(model_name).map(Some),
+            _ => Ok(None),
+        }
+    }
+
+    /// Describes this type as it would be written in a python type hint e.g. "list[int]", with nested models
+    /// given by their names. Equal types have equal descriptions
+    pub(crate) fn describe(&self) -> String {
+        match self {
+            FieldType::Nested { .. }
+            | FieldType::Reference { .. }
+            | FieldType::Projection { .. } => {
+                self.get_nested_model_name().unwrap_or_default().to_string()
+            }
+            FieldType::Dict { key, value } => {
+                format!("dict[{}, {}]", key.describe(), value.describe())
+            }
+            FieldType::List { items } => format!("list[{}]", items.describe()),
+            FieldType::Tuple { items } => format!(
+                "tuple[{}]",
+                items
+                    .iter()
+                    .map(FieldType::describe)
+                    .collect::<Vec<String>>()
+                    .join(", ")
+            ),
+            FieldType::VarTuple { items } => format!("tuple[{}, ...]", items.describe()),
+            FieldType::Vector { dimension } => format!("vector[{}]", dimension),
+            FieldType::Str => "str".to_string(),
+            FieldType::Int => "int".to_string(),
+            FieldType::Float => "float".to_string(),
+            FieldType::Bool => "bool".to_string(),
+            FieldType::Datetime => "datetime".to_string(),
+            FieldType::Date => "date".to_string(),
+            FieldType::None => "None".to_string(),
+        }
+    }
+
+    /// Converts data of the collection of the given name got from redis into a FieldType.
     /// This is useful when getting data from redis to return it in python
-    pub(crate) fn redis_to_py(&self, data: &redis::Value) -> PyResult<Py<PyAny>> {
+    pub(crate) fn redis_to_py(
+        &self,
+        collection_name: &str,
+        data: &redis::Value,
+    ) -> PyResult<Py<PyAny>> {
         match self {
-            FieldType::Nested {
-                schema, model_type, ..
-            } => match data.as_map_iter() {
+            FieldType::Nested { .. } | FieldType::Reference { .. } => match data.as_map_iter() {
+                // nested records that were not read e.g. those beyond the nesting depth, are None
                 None => Ok(Python::with_gil(|py| py.None())),
                 Some(data) => {
-                    let nested_data = data
-                        .map(|(k, v)| {
-                            let key = parsers::redis_to_py::<String>(k)?;
-                            let value = match schema.get_type(&key) {
-                                Some(type_) => type_.redis_to_py(v),
-                                None => {
-                                    Err(py_value_error!(&key, "unexpected field in nested object"))
-                                }
-                            }?;
-                            Ok((key, value))
-                        })
-                        .collect::<PyResult<HashMap<String, Py<PyAny>>>>()?;
+                    let model = self.get_nested_model()?.unwrap();
                     Python::with_gil(|py| {
-                        model_type.call(py, (), Some(nested_data.into_py_dict(py)))
+                        let nested_data = Self::nested_record_to_py_dict(py, &model, data, false)?;
+                        model.model_type.call(py, (), Some(nested_data))
                     })
                 }
             },
-            FieldType::Dict { value: type_, .. } => {
+            FieldType::Projection {
+                model,
+                schema,
+                as_model,
+            } => match data.as_map_iter() {
+                None => Ok(Python::with_gil(|py| py.None())),
+                Some(data) => Python::with_gil(|py| {
+                    let projected_data = PyDict::new(py);
+                    for (k, v) in data {
+                        let key = parsers::redis_to_py::<String>(k)?;
+                        // the whole nested record is read if the collection is versioned
+                        if let Some(type_) = schema.get_type(&key) {
+                            projected_data.set_item(
+                                schema.get_field_name(py, &key),
+                                type_.redis_to_py(&model.model_name, v)?,
+                            )?;
+                        }
+                    }
+
+                    if !as_model {
+                        return Ok(projected_data.into());
+                    }
+
+                    // the other fields are missing so the model is constructed without validation
+                    model
+                        .model_type
+                        .call_method(py, "construct", (), Some(projected_data))
+                }),
+            },
+            FieldType::Dict { key, value } => {
                 let data = parsers::redis_to_py::<String>(data)?;
-                let data: HashMap<String, Py<PyAny>> = Self::parse_dict_str(&data, type_)?;
-                to_py!(data)
+                let data = Self::parse_dict_str(&data, key, value)?;
+                Python::with_gil(|py| Ok(data.into_py_dict(py).into()))
             }
             FieldType::List { items: type_, .. } => {
                 let data = parsers::redis_to_py::<String>(data)?;
@@ -102,8 +227,18 @@ impl FieldType {
                         .extract::<Py<PyAny>>()
                 })
             }
+            FieldType::VarTuple { items: type_ } => {
+                let data = parsers::redis_to_py::<String>(data)?;
+                let data: Vec<Py<PyAny>> = FieldType::parse_var_tuple_str(&data, type_)?;
+                Python::with_gil(|py| Ok(PyTuple::new(py, data).into_py(py)))
+            }
+            FieldType::Vector { .. } => {
+                let data = parsers::redis_to_py::<String>(data)?;
+                let data: Vec<Py<PyAny>> = Self::parse_list_str(&data, &FieldType::Float)?;
+                to_py!(data)
+            }
             FieldType::Str => {
-                let v = parsers::redis_to_py::<String>(data)?;
+                let v = compression::redis_to_string(collection_name, data)?;
                 to_py!(v)
             }
             FieldType::Int => {
@@ -133,19 +268,69 @@ impl FieldType {
         }
     }
 
-    /// Parses a string representation of a dictionary into a hashmap of py objects
-    pub fn parse_dict_str(value: &str, type_: &FieldType) -> PyResult<HashMap<String, Py<PyAny>>> {
-        let mut v: HashMap<String, Py<PyAny>> = Default::default();
-        let items = parsers::extract_str_portions(value, "{", "}", ",");
+    /// Converts data got from redis into a FieldType like redis_to_py, except that nested records, and the
+    /// records nested in them, are converted into dictionaries instead of instances of their models
+    pub(crate) fn redis_to_py_dict(
+        &self,
+        collection_name: &str,
+        data: &redis::Value,
+    ) -> PyResult<Py<PyAny>> {
+        match self {
+            FieldType::Nested { .. } | FieldType::Reference { .. } => match data.as_map_iter() {
+                None => Ok(Python::with_gil(|py| py.None())),
+                Some(data) => {
+                    let model = self.get_nested_model()?.unwrap();
+                    Python::with_gil(|py| {
+                        Ok(Self::nested_record_to_py_dict(py, &model, data, true)?.into())
+                    })
+                }
+            },
+            _ => self.redis_to_py(collection_name, data),
+        }
+    }
+
+    /// Converts the fields of a record of the given nested model got from redis into a dictionary keyed by the
+    /// interned names of the fields. The records nested in it are dictionaries if as_dicts is true, else instances
+    /// of their models
+    fn nested_record_to_py_dict<'py, 'a>(
+        py: Python<'py>,
+        model: &NestedModel,
+        data: impl Iterator<Item = (&'a redis::Value, &'a redis::Value)>,
+        as_dicts: bool,
+    ) -> PyResult<&'py PyDict> {
+        let nested_data = PyDict::new(py);
+        for (k, v) in data {
+            let key = parsers::redis_to_py::<String>(k)?;
+            // nested records are read as they are saved, without their format version
+            if key == VERSION_FIELD {
+                continue;
+            }
 
-        for item in items {
-            let kv_items = parsers::extract_str_portions(item, "", "", ":");
+            let value = match model.schema.get_type(&key) {
+                Some(type_) if as_dicts => type_.redis_to_py_dict(&model.model_name, v),
+                Some(type_) => type_.redis_to_py(&model.model_name, v),
+                None => Err(py_value_error!(&key, "unexpected field in nested object")),
+            }?;
+            nested_data.set_item(model.schema.get_field_name(py, &key), value)?;
+        }
+        Ok(nested_data)
+    }
 
-            if kv_items.len() == 2 {
-                let (key, value) = (kv_items[0], kv_items[1]);
-                let value = FieldType::str_to_py(value, type_)?;
+    /// Parses a string representation of a dictionary into the pairs of py objects of its keys and values
+    pub fn parse_dict_str(
+        value: &str,
+        key_type: &FieldType,
+        value_type: &FieldType,
+    ) -> PyResult<Vec<(Py<PyAny>, Py<PyAny>)>> {
+        let mut v: Vec<(Py<PyAny>, Py<PyAny>)> = Vec::new();
+        let items = parsers::extract_str_portions(value, '{', '}', ',');
 
-                v.insert(key.to_string(), value);
+        for item in items {
+            if let [key, value] = parsers::split_top_level(item, ':')[..] {
+                let key = FieldType::str_to_py(key, key_type)?;
+                let value = FieldType::str_to_py(value, value_type)?;
+
+                v.push((key, value));
             }
         }
 
@@ -154,7 +339,7 @@ impl FieldType {
 
     /// Converts a string that represents a list (a python list) into a FieldType
     pub fn parse_list_str(value: &str, type_: &FieldType) -> PyResult<Vec<Py<PyAny>>> {
-        let items = parsers::extract_str_portions(value, "[", "]", ",");
+        let items = parsers::extract_str_portions(value, '[', ']', ',');
         items
             .into_iter()
             .map(|item| FieldType::str_to_py(item, type_))
@@ -163,7 +348,7 @@ impl FieldType {
 
     /// Converts a string that represents a tuple (a python tuple) into a FieldType
     pub fn parse_tuple_str(value: &str, types_: &Vec<FieldType>) -> PyResult<Vec<Py<PyAny>>> {
-        let items = parsers::extract_str_portions(value, "(", ")", ",");
+        let items = parsers::extract_str_portions(value, '(', ')', ',');
         items
             .into_iter()
             .zip(types_)
@@ -171,15 +356,53 @@ impl FieldType {
             .collect()
     }
 
-    /// Converts a string into a Py<PyAny>
+    /// Converts a string that represents a tuple of any number of items of the same type into a FieldType
+    pub fn parse_var_tuple_str(value: &str, type_: &FieldType) -> PyResult<Vec<Py<PyAny>>> {
+        let items = parsers::extract_str_portions(value, '(', ')', ',');
+        items
+            .into_iter()
+            .map(|item| FieldType::str_to_py(item, type_))
+            .collect()
+    }
+
+    /// Converts a string representation of a model, as it is saved within a collection e.g. in a dictionary,
+    /// into an instance of the model. Its fields that are not in the model's schema are ignored
+    pub fn parse_model_str(value: &str, model: &NestedModel) -> PyResult<Py<PyAny>> {
+        let items = parsers::extract_str_portions(value, '{', '}', ',');
+
+        Python::with_gil(|py| {
+            let data = PyDict::new(py);
+            for item in items {
+                if let [key, value] = parsers::split_top_level(item, ':')[..] {
+                    let key = parsers::unquote(key);
+                    if let Some(type_) = model.schema.get_type(key) {
+                        data.set_item(
+                            model.schema.get_field_name(py, key),
+                            FieldType::str_to_py(value, type_)?,
+                        )?;
+                    }
+                }
+            }
+            model.model_type.call(py, (), Some(data))
+        })
+    }
+
+    /// Converts a string, as it is represented within a collection e.g. a list, into a Py<PyAny>
     pub(crate) fn str_to_py(data: &str, type_: &FieldType) -> PyResult<Py<PyAny>> {
+        if data == "None" {
+            return Ok(Python::with_gil(|py| py.None()));
+        }
+
+        let data = parsers::unquote(data);
         match type_ {
-            FieldType::Nested { .. } => {
-                to_py!(data.to_string())
+            FieldType::Nested { .. } | FieldType::Reference { .. } => {
+                let model = type_.get_nested_model()?.unwrap();
+                Self::parse_model_str(data, &model)
             }
-            FieldType::Dict { value, .. } => {
-                let data = Self::parse_dict_str(data, value)?;
-                to_py!(data)
+            FieldType::Projection { .. } => to_py!(data.to_string()),
+            FieldType::Dict { key, value } => {
+                let data = Self::parse_dict_str(data, key, value)?;
+                Python::with_gil(|py| Ok(data.into_py_dict(py).into()))
             }
             FieldType::List { items, .. } => {
                 let data = Self::parse_list_str(data, items)?;
@@ -189,6 +412,14 @@ impl FieldType {
                 let data = Self::parse_tuple_str(data, items)?;
                 to_py!(data)
             }
+            FieldType::VarTuple { items } => {
+                let data = Self::parse_var_tuple_str(data, items)?;
+                to_py!(data)
+            }
+            FieldType::Vector { .. } => {
+                let data = Self::parse_list_str(data, &FieldType::Float)?;
+                to_py!(data)
+            }
             FieldType::Str => to_py!(data.to_string()),
             FieldType::Int => {
                 let data = parsers::parse_str::<i64>(data)?;
@@ -199,7 +430,7 @@ impl FieldType {
                 to_py!(data)
             }
             FieldType::Bool => {
-                let data = parsers::parse_str::<bool>(data)?;
+                let data = parsers::parse_str::<bool>(&data.to_lowercase())?;
                 to_py!(data)
             }
             FieldType::Datetime => {
@@ -214,15 +445,31 @@ impl FieldType {
         }
     }
 
-    /// Given a schema property and a hashmap of definitions, this method extracts the right FieldType
-    /// for that property. It is used when creating a representation of the python-generated schema
-    /// within rust
-    pub(crate) fn extract_from_py_schema(
-        prop: &PyAny,
-        definitions: &HashMap<String, Py<PyAny>>,
-        primary_key_field_map: &HashMap<String, String>,
-        model_type_map: &HashMap<String, Py<PyType>>,
-    ) -> PyResult<Self> {
+    /// Gets the type of the keys of a dictionary from the python type of the keys of its field in the model.
+    /// Only int and float keys are converted on read, other keys being strings
+    pub(crate) fn from_py_key_type(key_type: &PyAny) -> PyResult<Self> {
+        let key_type: &PyType = match key_type.downcast() {
+            Ok(key_type) => key_type,
+            Err(_) => return Ok(Self::Str),
+        };
+
+        let builtins = PyModule::import(key_type.py(), "builtins")?;
+        // bools are ints in python yet they are saved as True and False
+        if key_type.is_subclass(builtins.getattr("bool")?)? {
+            Ok(Self::Str)
+        } else if key_type.is_subclass(builtins.getattr("int")?)? {
+            Ok(Self::Int)
+        } else if key_type.is_subclass(builtins.getattr("float")?)? {
+            Ok(Self::Float)
+        } else {
+            Ok(Self::Str)
+        }
+    }
+
+    /// Given a schema property, this method extracts the right FieldType for that property, getting
+    /// the models it refers to from the context. It is used when creating a representation of
+    /// the python-generated schema within rust
+    pub(crate) fn extract_from_py_schema(prop: &PyAny, context: &SchemaContext) -> PyResult<Self> {
         // https://pydantic-docs.helpmanual.io/usage/schema/#json-schema-types
         let prop: &PyDict = prop.downcast()?;
         if let Some(data_type) = prop.get_item("type") {
@@ -244,32 +491,44 @@ impl FieldType {
                 "number" => Ok(Self::Float),
                 "integer" => Ok(Self::Int),
                 "object" => Ok(Self::Dict {
-                    value: Box::new(Self::Str),
+                    key: match prop.get_item("propertyNames") {
+                        Some(names) => Box::new(Self::extract_from_py_schema(names, context)?),
+                        None => Box::new(Self::Str),
+                    },
+                    value: match prop.get_item("additionalProperties") {
+                        Some(values) if values.downcast::<PyDict>().is_ok() => {
+                            Box::new(Self::extract_from_py_schema(values, context)?)
+                        }
+                        _ => Box::new(Self::Str),
+                    },
                 }),
+                "array"
+                    if prop.get_item("format").map(|v| v.to_string()).as_deref()
+                        == Some("vector") =>
+                {
+                    let dimension = match prop.get_item("dimension") {
+                        Some(dimension) => dimension.extract()?,
+                        None => {
+                            return Err(py_value_error!(
+                                "vector fields should be annotated with their dimension e.g. Vector[384]",
+                                prop
+                            ))
+                        }
+                    };
+                    Ok(Self::Vector { dimension })
+                }
                 "array" => {
                     if let Some(items) = prop.get_item("items") {
                         match items.downcast::<PyList>() {
                             Ok(type_list) => {
                                 let items = type_list
                                     .into_iter()
-                                    .map(|v| {
-                                        Self::extract_from_py_schema(
-                                            v,
-                                            definitions,
-                                            primary_key_field_map,
-                                            model_type_map,
-                                        )
-                                    })
+                                    .map(|v| Self::extract_from_py_schema(v, context))
                                     .collect::<PyResult<Vec<FieldType>>>()?;
                                 Ok(Self::Tuple { items })
                             }
                             Err(_) => Ok(Self::List {
-                                items: Box::new(Self::extract_from_py_schema(
-                                    items,
-                                    definitions,
-                                    primary_key_field_map,
-                                    model_type_map,
-                                )?),
+                                items: Box::new(Self::extract_from_py_schema(items, context)?),
                             }),
                         }
                     } else {
@@ -288,47 +547,25 @@ impl FieldType {
                 None => Err(py_value_error!("model name missing", schema_ref)),
                 Some(v) => Ok(v.to_string()),
             }?;
-            let schema = match definitions.get(&model_name) {
-                None => Ok(Schema::empty()),
-                Some(v) => Python::with_gil(|py| {
-                    let v = v.as_ref(py);
-                    match v.get_item("properties") {
-                        Ok(props) => Schema::from_py_any(
-                            props,
-                            definitions,
-                            primary_key_field_map,
-                            model_type_map,
-                        ),
-                        Err(_) => Ok(Schema::empty()),
-                    }
-                }),
-            }?;
-            let primary_key_field = match primary_key_field_map.get(&model_name) {
-                Some(k) => Ok(k.to_string()),
+            // a model whose collection is still being created e.g. one nested in itself, is got lazily
+            if context.is_resolving(&model_name) {
+                return Ok(Self::Reference {
+                    model_name,
+                    registry: context.registry.clone(),
+                });
+            }
+
+            match context.registry.find(&model_name) {
+                Some(model) => Ok(Self::Nested { model }),
                 None => Err(py_key_error!(
                     &model_name,
                     format!(
-                        "model name missing in primary key field map. \
-                    Try to create the {} collection first",
+                        "model has no collection. \
+                    Try to create the {} collection first or set register_nested_models=True",
                         &model_name
                     )
                 )),
-            }?;
-
-            let model_type = match model_type_map.get(&model_name) {
-                Some(k) => Ok(k.to_owned()),
-                None => Err(py_key_error!(
-                    &model_name,
-                    "model name missing in model type map"
-                )),
-            }?;
-
-            Ok(Self::Nested {
-                model_name,
-                schema: Box::new(schema),
-                primary_key_field,
-                model_type,
-            })
+            }
         } else {
             Ok(Self::Str)
         }
@@ -0,0 +1,105 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use pyo3::prelude::*;
+
+use crate::verification;
+
+/// The time it takes the access count of a key to halve if the key is not accessed again
+const HALF_LIFE: Duration = Duration::from_secs(60);
+/// The number of keys tracked above which the coldest half of them are dropped
+const MAX_TRACKED_KEYS: usize = 10_000;
+
+/// The access frequencies of the primary keys of a collection, counted on a sample of the accesses
+/// and shared by all handles of the collection
+#[derive(Clone, Debug)]
+pub(crate) struct HotKeys {
+    sample: f64,
+    counters: Arc<Mutex<HashMap<String, DecayingCounter>>>,
+}
+
+/// A count of accesses that halves every HALF_LIFE, so that keys that used to be hot cool down
+#[derive(Clone, Copy, Debug)]
+struct DecayingCounter {
+    count: f64,
+    updated_at: Instant,
+}
+
+impl DecayingCounter {
+    /// Gets the count as it has decayed by the given instant
+    fn value_at(&self, now: Instant) -> f64 {
+        let half_lives =
+            now.saturating_duration_since(self.updated_at).as_secs_f64() / HALF_LIFE.as_secs_f64();
+        self.count * 0.5_f64.powf(half_lives)
+    }
+}
+
+impl HotKeys {
+    /// Creates the tracker of the hot keys of a collection, recording each access with a probability of `sample`
+    pub(crate) fn new(sample: f64) -> PyResult<Self> {
+        Ok(Self {
+            sample: verification::validate_sample_rate(sample)?,
+            counters: Default::default(),
+        })
+    }
+
+    /// Records an access of each of the given ids that is sampled
+    pub(crate) fn record<'a>(&self, ids: impl IntoIterator<Item = &'a str>) {
+        let now = Instant::now();
+        let mut counters = self.counters.lock().unwrap_or_else(|e| e.into_inner());
+
+        for id in ids {
+            if !verification::is_sampled(self.sample) {
+                continue;
+            }
+
+            match counters.get_mut(id) {
+                Some(counter) => {
+                    counter.count = counter.value_at(now) + 1.0;
+                    counter.updated_at = now;
+                }
+                None => {
+                    counters.insert(
+                        id.to_string(),
+                        DecayingCounter {
+                            count: 1.0,
+                            updated_at: now,
+                        },
+                    );
+                }
+            }
+        }
+
+        if counters.len() > MAX_TRACKED_KEYS {
+            drop_coldest(&mut counters, now);
+        }
+    }
+
+    /// Gets the given number of the most accessed ids, hottest first, with their estimated number
+    /// of recent accesses
+    pub(crate) fn top(&self, top: usize) -> Vec<(String, f64)> {
+        let now = Instant::now();
+        let counters = self.counters.lock().unwrap_or_else(|e| e.into_inner());
+
+        let mut hot_keys: Vec<(String, f64)> = counters
+            .iter()
+            .map(|(id, counter)| (id.clone(), counter.value_at(now) / self.sample))
+            .collect();
+        hot_keys.sort_unstable_by(|a, b| b.1.total_cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        hot_keys.truncate(top);
+        hot_keys
+    }
+}
+
+/// Drops the counters of the coldest half of the tracked keys, so that the memory they use is bounded
+fn drop_coldest(counters: &mut HashMap<String, DecayingCounter>, now: Instant) {
+    let mut values: Vec<(String, f64)> = counters
+        .iter()
+        .map(|(id, counter)| (id.clone(), counter.value_at(now)))
+        .collect();
+    values.select_nth_unstable_by(MAX_TRACKED_KEYS / 2, |a, b| b.1.total_cmp(&a.1));
+    for (id, _) in &values[MAX_TRACKED_KEYS / 2..] {
+        counters.remove(id);
+    }
+}
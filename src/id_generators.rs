@@ -0,0 +1,187 @@
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use once_cell::sync::Lazy;
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+
+const CROCKFORD_BASE32: &[u8; 32] = b"0123456789ABCDEFGHJKMNPQRSTVWXYZ";
+const BASE62: &[u8; 62] = b"0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz";
+/// The KSUID epoch, 2014-05-13T16:53:20Z, in seconds since the UNIX epoch
+const KSUID_EPOCH: u64 = 1_400_000_000;
+/// The snowflake epoch, 2020-01-01T00:00:00Z, in milliseconds since the UNIX epoch
+const SNOWFLAKE_EPOCH: u64 = 1_577_836_800_000;
+const SNOWFLAKE_SEQUENCE_BITS: u64 = 12;
+const SNOWFLAKE_WORKER_BITS: u64 = 10;
+const MAX_SNOWFLAKE_WORKER_ID: u64 = (1 << SNOWFLAKE_WORKER_BITS) - 1;
+
+/// The (last timestamp in milliseconds, sequence) state shared by all snowflake generators
+/// in this process
+static SNOWFLAKE_STATE: Lazy<Mutex<(u64, u64)>> = Lazy::new(|| Mutex::new((0, 0)));
+/// The worker id of this process if none is configured, hashed from the host name and the process id so that
+/// processes on different hosts, or in different containers, rarely share it. With only 1024 worker ids, some
+/// processes of a large deployment still do, and may then generate the same ids in the same millisecond
+static DEFAULT_SNOWFLAKE_WORKER_ID: Lazy<u64> = Lazy::new(|| {
+    let mut hasher = DefaultHasher::new();
+    get_host_name().hash(&mut hasher);
+    std::process::id().hash(&mut hasher);
+    hasher.finish() & MAX_SNOWFLAKE_WORKER_ID
+});
+
+/// The algorithms that can be used to generate primary keys for records that have none
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) enum IdGenerator {
+    Uuid4,
+    Ulid,
+    Snowflake { worker_id: u64 },
+    Ksuid,
+}
+
+impl IdGenerator {
+    /// Gets the id generator that corresponds to the given name. Snowflake ids are generated with the given worker id,
+    /// or one hashed from the host name and the process id if none is given, which other generators ignore
+    pub(crate) fn from_name(name: &str, snowflake_worker_id: Option<u64>) -> PyResult<Self> {
+        match name {
+            "uuid4" => Ok(Self::Uuid4),
+            "ulid" => Ok(Self::Ulid),
+            "snowflake" => Ok(Self::Snowflake {
+                worker_id: validate_snowflake_worker_id(snowflake_worker_id)?
+                    .unwrap_or(*DEFAULT_SNOWFLAKE_WORKER_ID),
+            }),
+            "ksuid" => Ok(Self::Ksuid),
+            _ => Err(PyValueError::new_err(format!(
+                "unknown id_generator {:?}. Expected one of 'uuid4', 'ulid', 'snowflake', 'ksuid'",
+                name
+            ))),
+        }
+    }
+
+    /// Generates a new unique id. All generators apart from uuid4 produce ids that
+    /// are lexicographically sortable by creation time
+    pub(crate) fn generate(&self) -> String {
+        match self {
+            IdGenerator::Uuid4 => generate_uuid4(),
+            IdGenerator::Ulid => generate_ulid(),
+            IdGenerator::Snowflake { worker_id } => generate_snowflake(*worker_id),
+            IdGenerator::Ksuid => generate_ksuid(),
+        }
+    }
+}
+
+/// Ensures the given snowflake worker id, if any, fits in the 10 bits of snowflake ids for it
+pub(crate) fn validate_snowflake_worker_id(worker_id: Option<u64>) -> PyResult<Option<u64>> {
+    match worker_id {
+        Some(worker_id) if worker_id > MAX_SNOWFLAKE_WORKER_ID => {
+            Err(PyValueError::new_err(format!(
+                "snowflake_worker_id should be at most {}",
+                MAX_SNOWFLAKE_WORKER_ID
+            )))
+        }
+        _ => Ok(worker_id),
+    }
+}
+
+/// Gets the name of this host, or an empty string if it is unknown
+fn get_host_name() -> String {
+    std::fs::read_to_string("/proc/sys/kernel/hostname")
+        .or_else(|_| std::fs::read_to_string("/etc/hostname"))
+        .or_else(|_| std::env::var("HOSTNAME"))
+        .or_else(|_| std::env::var("COMPUTERNAME"))
+        .map(|name| name.trim().to_string())
+        .unwrap_or_default()
+}
+
+/// Returns the number of milliseconds since the UNIX epoch
+#[inline]
+fn now_in_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+/// Generates a random version 4 UUID in its hyphenated form
+fn generate_uuid4() -> String {
+    let mut bytes: [u8; 16] = fastrand::u128(..).to_be_bytes();
+    bytes[6] = (bytes[6] & 0x0f) | 0x40;
+    bytes[8] = (bytes[8] & 0x3f) | 0x80;
+    let hex: String = bytes.iter().map(|b| format!("{:02x}", b)).collect();
+    format!(
+        "{}-{}-{}-{}-{}",
+        &hex[0..8],
+        &hex[8..12],
+        &hex[12..16],
+        &hex[16..20],
+        &hex[20..32]
+    )
+}
+
+/// Generates a ULID i.e. a 48-bit millisecond timestamp followed by 80 random bits,
+/// encoded as 26 characters of Crockford's base32
+fn generate_ulid() -> String {
+    let timestamp = (now_in_millis() as u128) & ((1 << 48) - 1);
+    let randomness = fastrand::u128(..) & ((1 << 80) - 1);
+    let value = (timestamp << 80) | randomness;
+
+    (0..26)
+        .rev()
+        .map(|i| CROCKFORD_BASE32[((value >> (i * 5)) & 0x1f) as usize] as char)
+        .collect()
+}
+
+/// Generates a snowflake id i.e. a 41-bit millisecond timestamp, the 10-bit worker id
+/// and a 12-bit sequence, zero-padded to 19 digits so that it sorts lexicographically
+fn generate_snowflake(worker_id: u64) -> String {
+    let max_sequence = (1 << SNOWFLAKE_SEQUENCE_BITS) - 1;
+    let mut state = SNOWFLAKE_STATE.lock().unwrap_or_else(|e| e.into_inner());
+    let (last_timestamp, sequence) = *state;
+    let mut timestamp = now_in_millis().saturating_sub(SNOWFLAKE_EPOCH);
+
+    let sequence = if timestamp <= last_timestamp {
+        // the clock has not moved (or moved backwards) so keep using the last timestamp
+        timestamp = last_timestamp;
+        let next = (sequence + 1) & max_sequence;
+        if next == 0 {
+            // sequence exhausted for this millisecond; borrow from the next one
+            timestamp += 1;
+        }
+        next
+    } else {
+        0
+    };
+    *state = (timestamp, sequence);
+
+    let value = (timestamp << (SNOWFLAKE_WORKER_BITS + SNOWFLAKE_SEQUENCE_BITS))
+        | (worker_id << SNOWFLAKE_SEQUENCE_BITS)
+        | sequence;
+    format!("{:019}", value)
+}
+
+/// Generates a KSUID i.e. a 32-bit timestamp in seconds since the KSUID epoch followed by
+/// 128 random bits, encoded as 27 characters of base62
+fn generate_ksuid() -> String {
+    let timestamp = (now_in_millis() / 1000).saturating_sub(KSUID_EPOCH) as u32;
+    let mut bytes: Vec<u8> = Vec::with_capacity(20);
+    bytes.extend_from_slice(&timestamp.to_be_bytes());
+    bytes.extend_from_slice(&fastrand::u128(..).to_be_bytes());
+
+    // long division of the big-endian byte array by 62, collecting the remainders
+    let mut digits: Vec<u8> = Vec::with_capacity(27);
+    while bytes.iter().any(|b| *b != 0) {
+        let mut remainder: u32 = 0;
+        for byte in bytes.iter_mut() {
+            let accumulator = (remainder << 8) | (*byte as u32);
+            *byte = (accumulator / 62) as u8;
+            remainder = accumulator % 62;
+        }
+        digits.push(BASE62[remainder as usize]);
+    }
+
+    while digits.len() < 27 {
+        digits.push(BASE62[0]);
+    }
+
+    digits.iter().rev().map(|b| *b as char).collect()
+}
@@ -0,0 +1,11 @@
+use std::borrow::Cow;
+
+/// Normalizes the given id of a record, lowercasing it if its collection was created with
+/// `case_insensitive_keys=True`. It is applied wherever keys are generated from ids,
+/// so the same keys are got on both writes and reads
+pub(crate) fn normalize_id(id: &str, is_case_insensitive: bool) -> Cow<'_, str> {
+    match is_case_insensitive && id.chars().any(char::is_uppercase) {
+        true => Cow::Owned(id.to_lowercase()),
+        false => Cow::Borrowed(id),
+    }
+}
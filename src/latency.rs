@@ -0,0 +1,51 @@
+use std::time::Duration;
+
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+
+/// The statistics of the round-trip latencies of PINGs to redis
+#[derive(Debug)]
+pub(crate) struct LatencyStats {
+    checkout: Duration,
+    samples: Vec<Duration>,
+}
+
+impl LatencyStats {
+    /// Creates the statistics of the given latencies of PINGs, made on a connection that
+    /// took the given time to check out of the pool
+    pub(crate) fn new(checkout: Duration, mut samples: Vec<Duration>) -> Self {
+        samples.sort();
+        Self { checkout, samples }
+    }
+
+    /// Gets the latency that the given fraction of the samples do not exceed, by the nearest-rank method
+    fn percentile(&self, fraction: f64) -> Duration {
+        let rank = (fraction * self.samples.len() as f64).ceil() as usize;
+        self.samples
+            .get(rank.max(1) - 1)
+            .copied()
+            .unwrap_or_default()
+    }
+
+    /// Converts the statistics into a python dictionary with the keys: "samples", the number of PINGs;
+    /// "min_ms", "avg_ms", "p99_ms" and "max_ms", their latencies in milliseconds; and "checkout_ms",
+    /// the time in milliseconds the connection took to check out of the pool
+    pub(crate) fn to_py(&self) -> PyResult<Py<PyAny>> {
+        let total: Duration = self.samples.iter().sum();
+        let avg = total
+            .checked_div(self.samples.len() as u32)
+            .unwrap_or_default();
+        let in_millis = |d: Duration| d.as_secs_f64() * 1000.0;
+
+        Python::with_gil(|py| {
+            let stats = PyDict::new(py);
+            stats.set_item("samples", self.samples.len())?;
+            stats.set_item("min_ms", in_millis(self.percentile(0.0)))?;
+            stats.set_item("avg_ms", in_millis(avg))?;
+            stats.set_item("p99_ms", in_millis(self.percentile(0.99)))?;
+            stats.set_item("max_ms", in_millis(self.percentile(1.0)))?;
+            stats.set_item("checkout_ms", in_millis(self.checkout))?;
+            Ok(stats.into_py(py))
+        })
+    }
+}
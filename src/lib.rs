@@ -1,24 +1,105 @@
 use pyo3::prelude::*;
 
-use async_store::{AsyncCollection, AsyncStore};
-use store::{Collection, Store};
+use async_store::{AsyncCollection, AsyncLink, AsyncStore, AsyncView};
+use caches::{Cache, KeyValueNamespace};
+use circuit_breakers::CircuitOpenError;
+use deadlines::DeadlineExceededError;
+use pools::PoolExhaustedError;
+use quotas::QuotaExceededError;
+use store::{Collection, Link, RecordIterator, Store, View};
+use unique_fields::UniqueViolationError;
 
+mod aggregations;
 mod async_store;
 mod async_utils;
 mod asyncio;
+mod background;
+mod backup_schedules;
+mod backups;
+mod batching;
+mod bloom_filters;
+mod caches;
+mod circuit_breakers;
+mod coercions;
+mod compaction;
+mod compression;
+mod conditional_writes;
+mod constraints;
+mod converters;
+mod deadlines;
+mod defaults;
+mod descriptions;
+mod endpoints;
+mod expiry;
+mod exports;
+mod fake_data;
+mod field_indexes;
 mod field_types;
+mod hot_keys;
+mod id_generators;
+mod key_normalization;
+mod latency;
+mod links;
+mod list_indexes;
+mod lookups;
+mod memory_usage;
+mod metrics;
+mod migrations;
+mod mirrors;
 mod mobc_redis;
+mod nested_updates;
+mod normalizers;
+mod null_indexes;
+mod options;
+mod pagination;
 mod parsers;
+mod pools;
+mod profiling;
+mod quotas;
+mod range_indexes;
+mod read_errors;
+mod registries;
+mod repairs;
+mod retention;
 mod schema;
+mod snapshots;
 mod store;
+mod throttle;
+mod ttl_audit;
+mod unique_fields;
 mod utils;
+mod vectors;
+mod verification;
+mod versioning;
+mod views;
+mod write_buffer;
 
 /// A Python module implemented in Rust.
 #[pymodule]
-fn orredis(_py: Python, m: &PyModule) -> PyResult<()> {
+fn orredis(py: Python, m: &PyModule) -> PyResult<()> {
     m.add_class::<Store>()?;
     m.add_class::<Collection>()?;
     m.add_class::<AsyncStore>()?;
     m.add_class::<AsyncCollection>()?;
+    m.add_class::<View>()?;
+    m.add_class::<AsyncView>()?;
+    m.add_class::<Link>()?;
+    m.add_class::<AsyncLink>()?;
+    m.add_class::<RecordIterator>()?;
+    m.add_class::<Cache>()?;
+    m.add_class::<KeyValueNamespace>()?;
+    m.add("PoolExhaustedError", py.get_type::<PoolExhaustedError>())?;
+    m.add("QuotaExceededError", py.get_type::<QuotaExceededError>())?;
+    m.add(
+        "UniqueViolationError",
+        py.get_type::<UniqueViolationError>(),
+    )?;
+    m.add(
+        "DeadlineExceededError",
+        py.get_type::<DeadlineExceededError>(),
+    )?;
+    m.add("deadline", deadlines::get_deadline_var(py)?)?;
+    m.add("CircuitOpenError", py.get_type::<CircuitOpenError>())?;
+    m.add_function(wrap_pyfunction!(backups::register_backup_sink, m)?)?;
     Ok(())
 }
@@ -0,0 +1,56 @@
+use crate::utils;
+
+/// The definition of a many-to-many link between the records of two collections e.g. students and
+/// the courses they attend. Each record has a set of the ids of the records it is linked to on the other
+/// side, and both sets are updated together so that either side is found in a single read
+#[derive(Clone, Debug)]
+pub(crate) struct LinkDefinition {
+    pub(crate) name: String,
+    pub(crate) left: String,
+    pub(crate) right: String,
+}
+
+impl LinkDefinition {
+    /// Creates the definition of the link of the given name between the given left and right collections
+    pub(crate) fn new(name: String, left: String, right: String) -> Self {
+        Self { name, left, right }
+    }
+
+    /// Constructs the key of the set of the ids of the left records linked to the right record of the given id
+    #[inline]
+    pub(crate) fn generate_left_key(&self, right_id: &str) -> String {
+        utils::generate_auxiliary_key(&self.name, "link", &format!("left_%&_{}", right_id))
+    }
+
+    /// Constructs the key of the set of the ids of the right records linked to the left record of the given id
+    #[inline]
+    pub(crate) fn generate_right_key(&self, left_id: &str) -> String {
+        utils::generate_auxiliary_key(&self.name, "link", &format!("right_%&_{}", left_id))
+    }
+
+    /// Generates the pipeline that links the given left and right records in a transaction
+    pub(crate) fn generate_link_pipeline(&self, left_id: &str, right_id: &str) -> redis::Pipeline {
+        let mut pipe = redis::pipe();
+        pipe.atomic()
+            .sadd(self.generate_right_key(left_id), right_id)
+            .ignore()
+            .sadd(self.generate_left_key(right_id), left_id)
+            .ignore();
+        pipe
+    }
+
+    /// Generates the pipeline that unlinks the given left and right records in a transaction
+    pub(crate) fn generate_unlink_pipeline(
+        &self,
+        left_id: &str,
+        right_id: &str,
+    ) -> redis::Pipeline {
+        let mut pipe = redis::pipe();
+        pipe.atomic()
+            .srem(self.generate_right_key(left_id), right_id)
+            .ignore()
+            .srem(self.generate_left_key(right_id), left_id)
+            .ignore();
+        pipe
+    }
+}
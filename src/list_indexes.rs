@@ -0,0 +1,168 @@
+use pyo3::exceptions::{PyKeyError, PyValueError};
+use pyo3::prelude::*;
+
+use crate::field_types::FieldType;
+use crate::key_normalization;
+use crate::parsers;
+use crate::store::CollectionMeta;
+use crate::utils;
+
+const UPDATE_LIST_INDEX_SCRIPT: &str = r"local id, prefix = ARGV[1], ARGV[2] local new = {} for i = 3, #ARGV do new[ARGV[i]] = true end for _, element in ipairs(redis.call('SMEMBERS', KEYS[1])) do if new[element] then new[element] = nil else redis.call('SREM', prefix .. element, id) end end for element in pairs(new) do redis.call('SADD', prefix .. element, id) end redis.call('DEL', KEYS[1]) for i = 3, #ARGV do redis.call('SADD', KEYS[1], ARGV[i]) end";
+
+/// Ensures the given fields can have each of their elements indexed i.e. they are lists of non-nested items
+/// that are not indexed as a whole
+pub(crate) fn validate_indexed_list_fields(
+    meta: &CollectionMeta,
+    fields: &[String],
+) -> PyResult<()> {
+    for field in fields {
+        match meta.schema.get_type(field) {
+            None => {
+                return Err(PyKeyError::new_err(format!(
+                    "{:?} is not a field of the model",
+                    field
+                )))
+            }
+            Some(FieldType::List { items }) if !items.is_nested() => {}
+            Some(type_) => {
+                return Err(PyValueError::new_err(format!(
+                    "{:?} is a {} field yet only lists of non-nested items can have their elements indexed",
+                    field,
+                    type_.describe()
+                )))
+            }
+        }
+
+        if meta.indexed_fields.contains(field) {
+            return Err(PyValueError::new_err(format!(
+                "{:?} is in indexed_fields yet a list field is indexed either as a whole or by its elements",
+                field
+            )));
+        }
+    }
+
+    Ok(())
+}
+
+/// Extracts the elements of the given list, as it is saved in the main hash of its record e.g. "['a', 'b']",
+/// each as it is saved in the index of the elements of its field. None has no elements
+fn to_elements(value: &str) -> Vec<&str> {
+    if value == "None" {
+        return vec![];
+    }
+
+    parsers::extract_str_portions(value, '[', ']', ',')
+        .into_iter()
+        .map(parsers::unquote)
+        .collect()
+}
+
+/// Converts the given value into an element as saved in the index of the elements of a list field,
+/// i.e. as it is represented in the list that is saved in the main hash of the record
+fn to_element(value: &PyAny) -> PyResult<String> {
+    let value = value.repr()?.to_str()?;
+    Ok(parsers::unquote(value).to_string())
+}
+
+/// Constructs the prefix of the keys of the sets of the ids of the records of the given collection whose
+/// list field holds a given element
+#[inline]
+fn generate_index_key_prefix(collection_name: &str, field: &str) -> String {
+    utils::generate_auxiliary_key(collection_name, "list_index", &format!("{}_%&_", field))
+}
+
+/// Constructs the key of the set of the elements of the list field of the record of the given id, as they were
+/// last indexed, so that the elements that are no longer in the list are removed from the index on update
+#[inline]
+fn generate_elements_key(
+    collection_name: &str,
+    field: &str,
+    id: &str,
+    is_case_insensitive: bool,
+) -> String {
+    let id = key_normalization::normalize_id(id, is_case_insensitive);
+    utils::generate_auxiliary_key(
+        collection_name,
+        "list_elements",
+        &format!("{}_%&_{}", field, id),
+    )
+}
+
+/// Adds to the pipeline the command that moves the record of the given id from the indexes of the elements
+/// it no longer has in the given list field, to those of the elements it now has
+fn add_index_cmd(
+    pipe: &mut redis::Pipeline,
+    collection_name: &str,
+    meta: &CollectionMeta,
+    id: &str,
+    field: &str,
+    elements: &[&str],
+) {
+    pipe.cmd("EVAL")
+        .arg(UPDATE_LIST_INDEX_SCRIPT)
+        .arg(1)
+        .arg(generate_elements_key(
+            collection_name,
+            field,
+            id,
+            meta.case_insensitive_keys,
+        ))
+        .arg(id)
+        .arg(generate_index_key_prefix(collection_name, field))
+        .arg(elements)
+        .ignore();
+}
+
+/// Adds to the pipeline the commands that update the indexes of the elements of the indexed list fields
+/// among the given fields of the record of the given id
+pub(crate) fn add_update_cmd(
+    pipe: &mut redis::Pipeline,
+    collection_name: &str,
+    meta: &CollectionMeta,
+    id: &str,
+    fields: &[(&String, &String)],
+) {
+    for (field, value) in fields {
+        if meta.indexed_list_fields.contains(field) {
+            add_index_cmd(pipe, collection_name, meta, id, field, &to_elements(value));
+        }
+    }
+}
+
+/// Adds to the pipeline the commands that remove the record of the given id from the indexes of the elements
+/// of its indexed list fields, before it is deleted
+pub(crate) fn add_remove_cmd(
+    pipe: &mut redis::Pipeline,
+    collection_name: &str,
+    meta: &CollectionMeta,
+    id: &str,
+) {
+    for field in &meta.indexed_list_fields {
+        add_index_cmd(pipe, collection_name, meta, id, field, &[]);
+    }
+}
+
+/// Generates the command that gets the ids of the records of the given collection whose given list field holds
+/// the given value among its elements
+pub(crate) fn generate_lookup_cmd(
+    collection_name: &str,
+    meta: &CollectionMeta,
+    field: &str,
+    value: &PyAny,
+) -> PyResult<redis::Cmd> {
+    if !meta.indexed_list_fields.iter().any(|v| v == field) {
+        return Err(PyValueError::new_err(format!(
+            "{:?} is not an indexed list field of the collection. \
+            Add it to indexed_list_fields in create_collection()",
+            field
+        )));
+    }
+
+    let mut cmd = redis::cmd("SMEMBERS");
+    cmd.arg(format!(
+        "{}{}",
+        generate_index_key_prefix(collection_name, field),
+        to_element(value)?
+    ));
+    Ok(cmd)
+}
@@ -0,0 +1,227 @@
+use std::collections::HashSet;
+
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+
+use crate::field_indexes;
+use crate::key_normalization;
+use crate::list_indexes;
+use crate::null_indexes;
+use crate::store::CollectionMeta;
+use crate::utils;
+
+/// The suffix of the lookups of `find()` that match the records whose list field holds a value
+const CONTAINS_SUFFIX: &str = "__contains";
+/// The suffix of the lookups of `find()` that match the records whose field is null, or not null
+const ISNULL_SUFFIX: &str = "__isnull";
+
+/// A lookup of `find()` as text e.g. "tags__contains='rust'", with the kind of index serving it
+/// and the command that gets the ids of the records in that index for it
+struct Lookup {
+    text: String,
+    index: &'static str,
+    cmd: redis::Cmd,
+}
+
+/// The lookups of `find()` as the commands that get the ids of the records matching them, and those that get
+/// the ids of the records excluded by them e.g. the records whose field is null for `field__isnull=False`
+pub(crate) struct Lookups {
+    matching: Vec<Lookup>,
+    excluding: Vec<Lookup>,
+    /// The lookups as text, in the order of their names, which is the same for identical lookups
+    fingerprint: String,
+    /// Whether the collection has case-insensitive keys, so that the ids are compared lowercased
+    is_case_insensitive: bool,
+    /// The fields looked up by their null indexes, which are checked by `ensure_indexes()` once the registered
+    /// fields of the collection are read again, as the indexes may have been created by other clients
+    null_index_fields: Vec<String>,
+}
+
+impl Lookups {
+    /// Extracts the lookups of the given collection from the keyword arguments of `find()`: `field=value`
+    /// for an indexed field holding the value, `field__contains=value` for an indexed list field holding the value
+    /// among its elements, and `field__isnull=True` or `field__isnull=False` for a field with a null index
+    pub(crate) fn from_py(
+        collection_name: &str,
+        meta: &CollectionMeta,
+        lookups: Option<&PyDict>,
+    ) -> PyResult<Self> {
+        let lookups = lookups.filter(|v| !v.is_empty()).ok_or_else(|| {
+            PyValueError::new_err(
+                "at least one lookup e.g. tags__contains=\"rust\" should be given",
+            )
+        })?;
+
+        let mut matching = Vec::with_capacity(lookups.len());
+        let mut excluding = vec![];
+        let mut fingerprint = Vec::with_capacity(lookups.len());
+        let mut null_index_fields = vec![];
+        for (lookup, value) in lookups {
+            let lookup: String = lookup.extract()?;
+            let text = format!("{}={}", lookup, value.repr()?);
+            fingerprint.push(text.clone());
+            if let Some(field) = lookup.strip_suffix(CONTAINS_SUFFIX) {
+                matching.push(Lookup {
+                    text,
+                    index: "list",
+                    cmd: list_indexes::generate_lookup_cmd(collection_name, meta, field, value)?,
+                });
+            } else if let Some(field) = lookup.strip_suffix(ISNULL_SUFFIX) {
+                null_indexes::ensure_indexable_field(meta, field)?;
+                null_index_fields.push(field.to_string());
+                let lookup = Lookup {
+                    text,
+                    index: "null",
+                    cmd: null_indexes::generate_lookup_cmd(collection_name, field),
+                };
+                match value.extract::<bool>()? {
+                    true => matching.push(lookup),
+                    false => excluding.push(lookup),
+                }
+            } else {
+                meta.ensure_indexed_field(&lookup)?;
+                let type_ = meta.schema.get_type(&lookup).unwrap();
+                let value = utils::field_value_to_redis(type_, &value.into())?;
+                matching.push(Lookup {
+                    text,
+                    index: "field",
+                    cmd: field_indexes::generate_lookup_cmd(
+                        collection_name,
+                        meta,
+                        &[lookup],
+                        &[value],
+                    ),
+                });
+            }
+        }
+
+        fingerprint.sort_unstable();
+        Ok(Self {
+            matching,
+            excluding,
+            fingerprint: fingerprint.join("&"),
+            is_case_insensitive: meta.case_insensitive_keys,
+            null_index_fields,
+        })
+    }
+
+    /// Ensures the fields looked up by their null indexes have null indexes that are fully built
+    pub(crate) fn ensure_indexes(&self, meta: &CollectionMeta) -> PyResult<()> {
+        for field in &self.null_index_fields {
+            meta.ensure_null_index(field)?;
+        }
+        Ok(())
+    }
+
+    /// Constructs the key of the list of the ids of the records of the given collection that matched these
+    /// lookups when they were last cached
+    #[inline]
+    pub(crate) fn generate_cache_key(&self, collection_name: &str) -> String {
+        utils::generate_auxiliary_key(collection_name, "find_cache", &self.fingerprint)
+    }
+
+    /// Whether the ids of all the records of the collection are needed, as the lookups only exclude records
+    #[inline]
+    pub(crate) fn needs_all_ids(&self) -> bool {
+        self.matching.is_empty()
+    }
+
+    /// Generates the pipeline that gets the ids of the records matching each lookup, followed by those of
+    /// the records excluded by each lookup
+    pub(crate) fn to_pipeline(&self) -> redis::Pipeline {
+        let mut pipe = redis::pipe();
+        for lookup in self.matching.iter().chain(&self.excluding) {
+            pipe.add_command(lookup.cmd.clone());
+        }
+        pipe
+    }
+
+    /// Chooses the list of ids, among the given ids of the records matching each lookup, that the others
+    /// are checked against i.e. the smallest one, so that the fewest candidates are checked
+    fn choose_driving_ids(ids: &[Vec<String>]) -> usize {
+        ids.iter()
+            .enumerate()
+            .min_by_key(|(_, ids)| ids.len())
+            .map_or(0, |(i, _)| i)
+    }
+
+    /// Gets the ids of the records of the given collection that match all the lookups, in the order of
+    /// the smallest list of the ids matching a lookup, from the replies of the pipeline of `to_pipeline()`,
+    /// preceded by the ids of all the records if `needs_all_ids()`. The ids are compared as they are
+    /// in the keys of the records
+    pub(crate) fn filter_ids(&self, mut ids: Vec<Vec<String>>) -> Vec<String> {
+        if ids.is_empty() {
+            return vec![];
+        }
+
+        let to_set = |ids: Vec<String>| -> HashSet<String> {
+            ids.iter()
+                .map(|id| {
+                    key_normalization::normalize_id(id, self.is_case_insensitive).into_owned()
+                })
+                .collect()
+        };
+        let excluded: HashSet<String> = ids
+            .split_off(ids.len() - self.excluding.len())
+            .into_iter()
+            .flat_map(to_set)
+            .collect();
+        let first = ids.swap_remove(Self::choose_driving_ids(&ids));
+        let others: Vec<HashSet<String>> = ids.into_iter().map(to_set).collect();
+        first
+            .into_iter()
+            .filter(|id| {
+                let id = key_normalization::normalize_id(id, self.is_case_insensitive);
+                !excluded.contains(id.as_ref()) && others.iter().all(|v| v.contains(id.as_ref()))
+            })
+            .collect()
+    }
+
+    /// Converts the plan of `find()` for these lookups into a python dictionary, from the same ids as
+    /// `filter_ids()`, with the keys: "lookups", the lookups as dictionaries of their "lookup" as text,
+    /// the kind of "index" serving them i.e. "field", "list" or "null", their "role" i.e. "match" or "exclude",
+    /// and the number of "candidates" their index holds; "scan", whether the ids of all the records are scanned
+    /// as the lookups only exclude records; "chosen_index", the lookup whose candidates are checked against
+    /// the others i.e. the one with the fewest, or None if the records are scanned; "candidates", the number
+    /// of candidates checked; and "matches", the number of records matching all the lookups
+    pub(crate) fn explain(&self, ids: Vec<Vec<String>>) -> PyResult<Py<PyAny>> {
+        let scan = self.needs_all_ids();
+        let matching_ids = &ids[..ids.len() - self.excluding.len()];
+        let driving = Self::choose_driving_ids(matching_ids);
+        let candidates = matching_ids.get(driving).map_or(0, Vec::len);
+        let chosen_index = match scan {
+            true => None,
+            false => self.matching.get(driving).map(|v| v.text.clone()),
+        };
+        let counts: Vec<usize> = ids[usize::from(scan)..].iter().map(Vec::len).collect();
+        let roles = self
+            .matching
+            .iter()
+            .map(|v| (v, "match"))
+            .chain(self.excluding.iter().map(|v| (v, "exclude")));
+        let matches = self.filter_ids(ids).len();
+
+        Python::with_gil(|py| {
+            let lookups = roles
+                .zip(counts)
+                .map(|((lookup, role), count)| {
+                    let item = PyDict::new(py);
+                    item.set_item("lookup", &lookup.text)?;
+                    item.set_item("index", lookup.index)?;
+                    item.set_item("role", role)?;
+                    item.set_item("candidates", count)?;
+                    Ok(item.into_py(py))
+                })
+                .collect::<PyResult<Vec<Py<PyAny>>>>()?;
+
+            let plan = PyDict::new(py);
+            plan.set_item("lookups", lookups)?;
+            plan.set_item("scan", scan)?;
+            plan.set_item("chosen_index", chosen_index)?;
+            plan.set_item("candidates", candidates)?;
+            plan.set_item("matches", matches)?;
+            Ok(plan.into_py(py))
+        })
+    }
+}
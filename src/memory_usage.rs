@@ -0,0 +1,94 @@
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+
+use crate::utils;
+
+/// The memory used by a random sample of the records of a collection, from which that of the whole
+/// collection is estimated
+#[derive(Debug)]
+pub(crate) struct MemoryUsage {
+    records: usize,
+    sampled: Vec<(String, u64)>,
+}
+
+impl MemoryUsage {
+    /// Creates the report of a collection of the given number of records, given the ids of the records
+    /// sampled and the number of bytes each uses, as got by MEMORY USAGE. Records that were deleted
+    /// after they were sampled, and thus have no memory usage, are left out
+    pub(crate) fn new(records: usize, ids: Vec<String>, usages: Vec<Option<u64>>) -> Self {
+        let mut sampled: Vec<(String, u64)> = ids
+            .into_iter()
+            .zip(usages)
+            .filter_map(|(id, bytes)| bytes.map(|bytes| (id, bytes)))
+            .collect();
+        sampled.sort_unstable_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        Self { records, sampled }
+    }
+
+    /// Converts the report into a python dictionary with the keys: "records", the number of records;
+    /// "sampled", the number of records sampled; "sampled_bytes" and "avg_bytes", the total and average
+    /// memory used by the sampled records; "estimated_total_bytes", the memory the whole collection is
+    /// estimated to use; and "largest", the given number of largest sampled records as dictionaries of
+    /// their "id" and "bytes"
+    pub(crate) fn to_py(&self, top: usize) -> PyResult<Py<PyAny>> {
+        let sampled_bytes: u64 = self.sampled.iter().map(|(_, bytes)| bytes).sum();
+        let avg_bytes = if self.sampled.is_empty() {
+            0.0
+        } else {
+            sampled_bytes as f64 / self.sampled.len() as f64
+        };
+
+        Python::with_gil(|py| {
+            let largest = self
+                .sampled
+                .iter()
+                .take(top)
+                .map(|(id, bytes)| {
+                    let item = PyDict::new(py);
+                    item.set_item("id", id)?;
+                    item.set_item("bytes", bytes)?;
+                    Ok(item.into_py(py))
+                })
+                .collect::<PyResult<Vec<Py<PyAny>>>>()?;
+
+            let usage = PyDict::new(py);
+            usage.set_item("records", self.records)?;
+            usage.set_item("sampled", self.sampled.len())?;
+            usage.set_item("sampled_bytes", sampled_bytes)?;
+            usage.set_item("avg_bytes", avg_bytes)?;
+            usage.set_item(
+                "estimated_total_bytes",
+                (avg_bytes * self.records as f64).round() as u64,
+            )?;
+            usage.set_item("largest", largest)?;
+            Ok(usage.into_py(py))
+        })
+    }
+}
+
+/// Picks the given number of the given ids at random, or all of them if there are not as many
+pub(crate) fn sample_ids(mut ids: Vec<String>, sample: usize) -> Vec<String> {
+    if ids.len() > sample {
+        fastrand::shuffle(&mut ids);
+        ids.truncate(sample);
+    }
+    ids
+}
+
+/// Generates the pipeline that gets the number of bytes the records of the given ids use in redis.
+/// All the fields of each record are counted, rather than a sample of them as redis does by default
+pub(crate) fn generate_memory_usage_pipeline(
+    collection_name: &str,
+    ids: &[String],
+) -> redis::Pipeline {
+    let mut pipe = redis::pipe();
+    // the ids are got from the keys of the records, so they are already normalized
+    for id in ids {
+        pipe.cmd("MEMORY")
+            .arg("USAGE")
+            .arg(utils::generate_hash_key(collection_name, id, false))
+            .arg("SAMPLES")
+            .arg(0);
+    }
+    pipe
+}
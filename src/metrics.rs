@@ -0,0 +1,55 @@
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+
+/// The counts of the operations on a collection, shared by all handles of the collection
+#[derive(Clone, Debug, Default)]
+pub(crate) struct Metrics(Arc<Mutex<BTreeMap<&'static str, OperationCounts>>>);
+
+/// The number of times an operation was called and the number of records it read or wrote
+#[derive(Clone, Copy, Debug, Default)]
+struct OperationCounts {
+    calls: u64,
+    records: u64,
+}
+
+impl Metrics {
+    /// Records a successful call of the given operation that read or wrote the given number of records
+    pub(crate) fn record(&self, operation: &'static str, records: usize) {
+        let mut operations = self.0.lock().unwrap_or_else(|e| e.into_inner());
+        let counts = operations.entry(operation).or_default();
+        counts.calls += 1;
+        counts.records += records as u64;
+    }
+
+    /// Records the given result of the given operation, counting the records in it if it succeeded
+    pub(crate) fn record_result<T>(
+        &self,
+        operation: &'static str,
+        result: PyResult<Vec<T>>,
+    ) -> PyResult<Vec<T>> {
+        if let Ok(records) = &result {
+            self.record(operation, records.len());
+        }
+        result
+    }
+
+    /// Converts the metrics into a python dictionary of each operation called to a dictionary with the keys:
+    /// "calls", the number of successful calls, and "records", the number of records they read or wrote
+    pub(crate) fn to_py(&self) -> PyResult<Py<PyAny>> {
+        let operations = self.0.lock().unwrap_or_else(|e| e.into_inner()).clone();
+
+        Python::with_gil(|py| {
+            let metrics = PyDict::new(py);
+            for (operation, counts) in operations {
+                let item = PyDict::new(py);
+                item.set_item("calls", counts.calls)?;
+                item.set_item("records", counts.records)?;
+                metrics.set_item(operation, item)?;
+            }
+            Ok(metrics.into_py(py))
+        })
+    }
+}
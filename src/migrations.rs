@@ -0,0 +1,95 @@
+use std::collections::BTreeMap;
+
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+
+use crate::schema::Schema;
+use crate::utils;
+
+/// The fields of the schema of a collection mapped to the descriptions of their types,
+/// as saved in redis to be compared with later versions of the schema
+pub(crate) type SchemaSnapshot = BTreeMap<String, String>;
+
+/// Takes the snapshot of the given schema
+pub(crate) fn take_snapshot(schema: &Schema) -> SchemaSnapshot {
+    schema
+        .mapping
+        .iter()
+        .map(|(field, type_)| (field.clone(), type_.describe()))
+        .collect()
+}
+
+/// Generates the pipeline that replaces the saved snapshot of the schema of the given collection
+/// with the snapshot of the given schema
+pub(crate) fn generate_save_snapshot_pipeline(
+    collection_name: &str,
+    schema: &Schema,
+) -> redis::Pipeline {
+    let key = utils::generate_schema_snapshot_key(collection_name);
+    let snapshot: Vec<(String, String)> = take_snapshot(schema).into_iter().collect();
+
+    let mut pipe = redis::pipe();
+    pipe.atomic().del(&key).ignore();
+    if !snapshot.is_empty() {
+        pipe.hset_multiple(&key, &snapshot).ignore();
+    }
+    pipe
+}
+
+/// The changes to the schema of a collection since its snapshot was saved, which the records
+/// saved in the older schema need to be migrated through
+#[derive(Debug, Default)]
+pub(crate) struct MigrationPlan {
+    has_snapshot: bool,
+    added: SchemaSnapshot,
+    removed: SchemaSnapshot,
+    type_changed: BTreeMap<String, (String, String)>,
+}
+
+impl MigrationPlan {
+    /// Compares the given schema with the saved snapshot of the schema. If no snapshot was saved,
+    /// all fields are considered added
+    pub(crate) fn new(schema: &Schema, saved: SchemaSnapshot) -> Self {
+        let current = take_snapshot(schema);
+        let mut plan = Self {
+            has_snapshot: !saved.is_empty(),
+            ..Default::default()
+        };
+
+        for (field, saved_type) in &saved {
+            match current.get(field) {
+                None => {
+                    plan.removed.insert(field.clone(), saved_type.clone());
+                }
+                Some(type_) if type_ != saved_type => {
+                    plan.type_changed
+                        .insert(field.clone(), (saved_type.clone(), type_.clone()));
+                }
+                Some(_) => {}
+            }
+        }
+
+        for (field, type_) in current {
+            if !saved.contains_key(&field) {
+                plan.added.insert(field, type_);
+            }
+        }
+
+        plan
+    }
+
+    /// Converts the plan into a python dictionary with the keys: "collection", "has_snapshot", "added"
+    /// and "removed", each a dictionary of field to type, and "type_changed", a dictionary of
+    /// field to (saved type, current type)
+    pub(crate) fn to_py(&self, collection_name: &str) -> PyResult<Py<PyAny>> {
+        Python::with_gil(|py| {
+            let plan = PyDict::new(py);
+            plan.set_item("collection", collection_name)?;
+            plan.set_item("has_snapshot", self.has_snapshot)?;
+            plan.set_item("added", self.added.clone().into_py(py))?;
+            plan.set_item("removed", self.removed.clone().into_py(py))?;
+            plan.set_item("type_changed", self.type_changed.clone().into_py(py))?;
+            Ok(plan.into_py(py))
+        })
+    }
+}
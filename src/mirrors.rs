@@ -0,0 +1,95 @@
+use std::sync::mpsc::{self, Receiver, SyncSender};
+use std::sync::{Arc, RwLock};
+use std::thread;
+use std::time::Duration;
+
+use pyo3::prelude::*;
+
+use crate::endpoints;
+
+/// The maximum number of writes waiting to be applied to a mirror. Writes beyond this are dropped
+const MIRROR_QUEUE_CAPACITY: usize = 10_000;
+/// The number of times a write is tried on a mirror before it is dropped
+const MIRROR_MAX_ATTEMPTS: u32 = 5;
+/// The delay before the first retry of a failed write on a mirror. It doubles on every retry
+const MIRROR_RETRY_DELAY: Duration = Duration::from_millis(100);
+
+/// The redis instances to which all writes made through a store are additionally applied.
+/// This is shared by the store and all its collections
+#[derive(Clone, Default)]
+pub(crate) struct Mirrors {
+    senders: Arc<RwLock<Vec<SyncSender<redis::Pipeline>>>>,
+}
+
+impl Mirrors {
+    /// Adds the redis instance of the given url as a mirror, starting a background worker
+    /// that applies the queued writes to it in order
+    pub(crate) fn add(&self, py: Python, url: &str) -> PyResult<()> {
+        let client = endpoints::open_client(py, url)?;
+        let (sender, receiver) = mpsc::sync_channel(MIRROR_QUEUE_CAPACITY);
+        thread::spawn(move || run_mirror_worker(client, receiver));
+
+        self.senders
+            .write()
+            .unwrap_or_else(|e| e.into_inner())
+            .push(sender);
+        Ok(())
+    }
+
+    /// Queues the given write for all mirrors. This never blocks; the write is dropped
+    /// for any mirror whose queue is full
+    pub(crate) fn replicate(&self, pipe: &redis::Pipeline) {
+        let senders = self.senders.read().unwrap_or_else(|e| e.into_inner());
+        for sender in senders.iter() {
+            let _ = sender.try_send(pipe.clone());
+        }
+    }
+
+    /// Queues the given single-command write for all mirrors
+    pub(crate) fn replicate_cmd(&self, cmd: &redis::Cmd) {
+        if self.is_empty() {
+            return;
+        }
+
+        let mut pipe = redis::pipe();
+        pipe.add_command(cmd.clone());
+        self.replicate(&pipe);
+    }
+
+    /// Whether there are no mirrors
+    pub(crate) fn is_empty(&self) -> bool {
+        self.senders
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .is_empty()
+    }
+}
+
+/// Applies the writes received on the given channel to the redis instance of the given client,
+/// retrying each with an exponential backoff, until all senders are dropped
+fn run_mirror_worker(client: redis::Client, receiver: Receiver<redis::Pipeline>) {
+    let mut conn: Option<redis::Connection> = None;
+
+    for pipe in receiver {
+        for attempt in 0..MIRROR_MAX_ATTEMPTS {
+            if attempt > 0 {
+                thread::sleep(MIRROR_RETRY_DELAY * 2u32.pow(attempt - 1));
+            }
+
+            let result = match conn.as_mut() {
+                Some(c) => pipe.query::<()>(c),
+                None => client.get_connection().and_then(|mut c| {
+                    let result = pipe.query::<()>(&mut c);
+                    conn = Some(c);
+                    result
+                }),
+            };
+
+            match result {
+                Ok(_) => break,
+                Err(e) if e.is_io_error() || e.is_connection_dropped() => conn = None,
+                Err(_) => {}
+            }
+        }
+    }
+}
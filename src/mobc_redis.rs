@@ -1,3 +1,4 @@
+use async_std::channel::{self, Receiver, Sender};
 use mobc::async_trait;
 use mobc::Manager;
 pub use redis;
@@ -9,11 +10,35 @@ use redis::Client;
 /// implemented here so as to use the same redis version as this project
 pub struct RedisConnectionManager {
     client: Client,
+    connect_permits: Option<(Sender<()>, Receiver<()>)>,
 }
 
 impl RedisConnectionManager {
     pub fn new(c: Client) -> Self {
-        Self { client: c }
+        Self {
+            client: c,
+            connect_permits: None,
+        }
+    }
+
+    /// Limits the number of connections being established at any one time, so that many tasks
+    /// starting at once wait for a few connections to open instead of opening all of theirs together
+    pub fn with_max_connecting(mut self, max_connecting: usize) -> Self {
+        let (sender, receiver) = channel::bounded(max_connecting);
+        for _ in 0..max_connecting {
+            let _ = sender.try_send(());
+        }
+        self.connect_permits = Some((sender, receiver));
+        self
+    }
+}
+
+/// A permit to establish a connection, given back when dropped, even if the connecting is cancelled
+struct ConnectPermit<'a>(&'a Sender<()>);
+
+impl Drop for ConnectPermit<'_> {
+    fn drop(&mut self) {
+        let _ = self.0.try_send(());
     }
 }
 
@@ -23,8 +48,15 @@ impl Manager for RedisConnectionManager {
     type Error = redis::RedisError;
 
     async fn connect(&self) -> Result<Self::Connection, Self::Error> {
-        let c = self.client.get_async_connection().await?;
-        Ok(c)
+        let (sender, receiver) = match &self.connect_permits {
+            Some(permits) => permits,
+            None => return self.client.get_async_connection().await,
+        };
+
+        // the permits are never closed as the manager holds both ends of their channel
+        let _ = receiver.recv().await;
+        let _permit = ConnectPermit(sender);
+        self.client.get_async_connection().await
     }
 
     async fn check(&self, mut conn: Self::Connection) -> Result<Self::Connection, Self::Error> {
@@ -0,0 +1,87 @@
+use std::collections::BTreeMap;
+
+use pyo3::exceptions::{PyKeyError, PyValueError};
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+
+use crate::field_types::FieldType;
+use crate::schema::Schema;
+use crate::store::CollectionMeta;
+use crate::utils;
+
+const UPDATE_NESTED_RECORD_SCRIPT: &str = r"local table_unpack = table.unpack or unpack local key = KEYS[1] local n = tonumber(ARGV[1]) for i = 2, n + 1 do key = redis.call('HGET', key, ARGV[i]) if not key then return 0 end end if redis.call('EXISTS', key) == 0 then return 0 end redis.call('HSET', key, table_unpack(ARGV, n + 2)) return 1";
+
+/// Separates the fields of nested records, addressed by dotted paths e.g. "address.city", from the other
+/// fields in the data of an update of the record of the given id. It returns the other fields, and the
+/// commands that update the nested records directly, without reading or rewriting the rest of them
+pub(crate) fn extract_nested_updates(
+    collection_name: &str,
+    meta: &CollectionMeta,
+    id: &str,
+    data: Py<PyAny>,
+) -> PyResult<(Py<PyAny>, Vec<redis::Cmd>)> {
+    Python::with_gil(|py| {
+        let dict: &PyDict = match data.as_ref(py).downcast() {
+            Ok(dict) => dict,
+            // model instances have no dotted paths
+            Err(_) => return Ok((data, vec![])),
+        };
+
+        let fields = PyDict::new(py);
+        let mut updates: BTreeMap<Vec<String>, Vec<(String, String)>> = BTreeMap::new();
+        for (key, value) in dict.iter() {
+            let key: String = key.extract()?;
+            let mut path: Vec<String> = key.split('.').map(|v| v.to_string()).collect();
+            if path.len() == 1 {
+                fields.set_item(key, value)?;
+                continue;
+            }
+
+            let type_ = get_field_type(&meta.schema, &path)?;
+            let field = path.pop().unwrap_or_default();
+            let value = utils::field_value_to_redis(&type_, &value.into())?;
+            updates.entry(path).or_default().push((field, value));
+        }
+
+        let key = utils::generate_hash_key(collection_name, id, meta.case_insensitive_keys);
+        let cmds = updates
+            .into_iter()
+            .map(|(path, fields)| {
+                let mut cmd = redis::cmd("EVAL");
+                cmd.arg(UPDATE_NESTED_RECORD_SCRIPT)
+                    .arg(1)
+                    .arg(&key)
+                    .arg(path.len())
+                    .arg(path)
+                    .arg(fields);
+                cmd
+            })
+            .collect();
+
+        Ok((fields.into_py(py), cmds))
+    })
+}
+
+/// Gets the type of the non-nested field at the given path of nested fields in the given schema
+fn get_field_type(schema: &Schema, path: &[String]) -> PyResult<FieldType> {
+    let (field, rest) = match path.split_first() {
+        Some(v) => v,
+        None => return Err(PyValueError::new_err("the path of the field is empty")),
+    };
+    let type_ = schema
+        .get_type(field)
+        .ok_or_else(|| PyKeyError::new_err(format!("{:?} is not a field of the model", field)))?;
+
+    match (type_.get_nested_model()?, rest.is_empty()) {
+        (None, true) => Ok(type_.clone()),
+        (Some(model), false) => get_field_type(&model.schema, rest),
+        (Some(_), true) => Err(PyValueError::new_err(format!(
+            "{:?} is a nested field yet only non-nested fields can be updated by their paths",
+            field
+        ))),
+        (None, false) => Err(PyValueError::new_err(format!(
+            "{:?} is not a nested field so it has no fields",
+            field
+        ))),
+    }
+}
@@ -0,0 +1,121 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+
+use crate::store::CollectionMeta;
+
+/// The normalizers that can be given by name, as the lua statements that normalize `value`
+const BUILT_IN_NORMALIZERS: [(&str, &str); 2] = [
+    ("lower", "value = string.lower(value)"),
+    ("strip", "value = string.match(value, '^%s*(.-)%s*$')"),
+];
+/// The lua code defining `normalize(field, value)` for collections without normalizers
+const IDENTITY_PRELUDE: &str = "local function normalize(field, value) return value end ";
+/// The lua code defining `normalize(field, value)` with the `normalizers` table of the functions of the fields
+const NORMALIZE_SCRIPT: &str = r"local function normalize(field, value) local f = normalizers[field] if f and value then return (f(value)) end return value end ";
+
+/// The normalizers of the indexed and unique fields of a collection, applied to their values before they are
+/// indexed or looked up e.g. so that "Email@X.com" and "email@x.com" are the same unique value. They are run
+/// in the scripts that update and read the indexes, so that the values read from redis are normalized alike
+#[derive(Clone, Debug)]
+pub(crate) struct Normalizers {
+    fields: Vec<String>,
+    /// The lua code defining `normalize(field, value)`, prepended to the scripts of the indexes
+    prelude: Arc<str>,
+}
+
+impl Default for Normalizers {
+    fn default() -> Self {
+        Self {
+            fields: vec![],
+            prelude: Arc::from(IDENTITY_PRELUDE),
+        }
+    }
+}
+
+impl Normalizers {
+    /// Creates the normalizers of the given collection from a map of field to normalizer: "lower", "strip",
+    /// a comma-separated list of them applied in order e.g. "strip,lower", or the body of a lua function
+    /// of `value` that returns the normalized value e.g. "return string.gsub(value, '%.', '')".
+    /// Only the indexed, compound-indexed and unique fields can be normalized
+    pub(crate) fn new(
+        meta: &CollectionMeta,
+        normalizers: HashMap<String, String>,
+    ) -> PyResult<Self> {
+        if normalizers.is_empty() {
+            return Ok(Default::default());
+        }
+
+        let mut normalizers: Vec<(String, String)> = normalizers.into_iter().collect();
+        normalizers.sort_unstable();
+        let mut prelude = "local normalizers = {} ".to_string();
+        for (field, normalizer) in &normalizers {
+            let is_indexed = meta.indexed_fields.contains(field)
+                || meta.unique_fields.contains(field)
+                || meta.compound_indexes.iter().any(|v| v.contains(field));
+            if !is_indexed {
+                return Err(PyValueError::new_err(format!(
+                    "{:?} is neither indexed nor unique yet only the values of indexes can be normalized",
+                    field
+                )));
+            }
+
+            prelude.push_str(&format!(
+                "normalizers[{:?}] = function(value) {} end ",
+                field,
+                to_lua(normalizer)?
+            ));
+        }
+        prelude.push_str(NORMALIZE_SCRIPT);
+
+        Ok(Self {
+            fields: normalizers.into_iter().map(|(field, _)| field).collect(),
+            prelude: Arc::from(prelude),
+        })
+    }
+
+    /// Checks whether the values of the given field are normalized
+    #[inline]
+    pub(crate) fn contains(&self, field: &str) -> bool {
+        self.fields.iter().any(|v| v == field)
+    }
+
+    /// Checks whether the values of any of the given fields are normalized
+    #[inline]
+    pub(crate) fn contains_any(&self, fields: &[String]) -> bool {
+        fields.iter().any(|field| self.contains(field))
+    }
+
+    /// Prepends the definition of `normalize(field, value)` to the given script
+    #[inline]
+    pub(crate) fn wrap(&self, script: &str) -> String {
+        format!("{}{}", self.prelude, script)
+    }
+}
+
+/// Converts the given normalizer into the body of a lua function of `value`
+fn to_lua(normalizer: &str) -> PyResult<String> {
+    if normalizer.contains("return") {
+        return Ok(normalizer.to_string());
+    }
+
+    let mut body = String::new();
+    for name in normalizer.split(',').map(str::trim) {
+        let (_, statement) = BUILT_IN_NORMALIZERS
+            .iter()
+            .find(|(v, _)| *v == name)
+            .ok_or_else(|| {
+                PyValueError::new_err(format!(
+                    "unknown normalizer {:?}. Expected \"lower\", \"strip\", a comma-separated list of them \
+                    or the body of a lua function of `value` returning the normalized value",
+                    name
+                ))
+            })?;
+        body.push_str(statement);
+        body.push(' ');
+    }
+    body.push_str("return value");
+    Ok(body)
+}
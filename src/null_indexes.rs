@@ -0,0 +1,56 @@
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+
+use crate::store::CollectionMeta;
+use crate::utils;
+
+const UPDATE_NULL_INDEXES_SCRIPT: &str = r"local id = ARGV[1] local nulls = 0 if redis.call('EXISTS', KEYS[1]) == 0 then return nulls end for i = 2, #ARGV do local value = redis.call('HGET', KEYS[1], ARGV[i]) if not value or value == 'None' then redis.call('SADD', KEYS[i], id) nulls = nulls + 1 else redis.call('SREM', KEYS[i], id) end end return nulls";
+
+/// Ensures the given field can have a null index i.e. it is a non-nested, non-counter field of the collection
+pub(crate) fn ensure_indexable_field(meta: &CollectionMeta, field: &str) -> PyResult<()> {
+    meta.ensure_scalar_field(field)?;
+    if meta.counter_fields.iter().any(|v| v == field) {
+        return Err(PyValueError::new_err(format!(
+            "{:?} is a counter field yet counter fields cannot be null",
+            field
+        )));
+    }
+
+    Ok(())
+}
+
+/// Constructs the key of the set of the ids of the records of the given collection that have no value
+/// for the given field
+#[inline]
+pub(crate) fn generate_index_key(collection_name: &str, field: &str) -> String {
+    utils::generate_auxiliary_key(collection_name, "null_index", field)
+}
+
+/// Adds to the pipeline the command that adds the record of the given id to the null indexes of the given fields
+/// it has no value for, and removes it from the others, after its fields are written. The values are read by
+/// the command itself, so it is also used to build an index while the record may be written or deleted.
+/// It replies the number of the fields the record has no value for, or 0 if the record does not exist
+pub(crate) fn add_update_cmd(
+    pipe: &mut redis::Pipeline,
+    collection_name: &str,
+    fields: &[String],
+    pk: &str,
+    id: &str,
+) {
+    pipe.cmd("EVAL")
+        .arg(UPDATE_NULL_INDEXES_SCRIPT)
+        .arg(fields.len() + 1)
+        .arg(pk);
+    for field in fields {
+        pipe.arg(generate_index_key(collection_name, field));
+    }
+    pipe.arg(id).arg(fields);
+}
+
+/// Generates the command that gets the ids of the records of the given collection that have no value
+/// for the given field
+pub(crate) fn generate_lookup_cmd(collection_name: &str, field: &str) -> redis::Cmd {
+    let mut cmd = redis::cmd("SMEMBERS");
+    cmd.arg(generate_index_key(collection_name, field));
+    cmd
+}
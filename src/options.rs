@@ -0,0 +1,263 @@
+use std::collections::HashMap;
+use std::time::Duration;
+
+use pyo3::exceptions::{PyTypeError, PyValueError};
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+
+use crate::batching::BatchSizer;
+use crate::circuit_breakers::CircuitBreaker;
+use crate::defaults::CollectionDefaults;
+use crate::hot_keys::HotKeys;
+use crate::id_generators::{self, IdGenerator};
+use crate::parsers;
+use crate::versioning::Versioning;
+
+/// The options of stores that are given as keyword arguments after the options of their pools of connections
+pub(crate) struct StoreOptions {
+    pub(crate) defaults: CollectionDefaults,
+    pub(crate) write_buffer: Option<usize>,
+    pub(crate) write_buffer_overflow: String,
+    pub(crate) circuit_breaker: Option<CircuitBreaker>,
+    pub(crate) blocking_pool_size: Option<u32>,
+    pub(crate) max_batch_bytes: usize,
+    pub(crate) lazy: bool,
+    pub(crate) max_connecting: Option<usize>,
+}
+
+impl StoreOptions {
+    /// Creates the options from the python dict of keyword arguments of the constructor of a store, validating
+    /// them. `lazy` is only an option of the sync store and `max_connecting` only one of the async store
+    pub(crate) fn from_py(options: Option<&PyDict>, is_async: bool) -> PyResult<Self> {
+        let mut defaults = None;
+        let (mut circuit_breaker_threshold, mut circuit_breaker_reset) = (None, None);
+        let mut max_batch_bytes = None;
+        let mut result = Self {
+            defaults: Default::default(),
+            write_buffer: None,
+            write_buffer_overflow: "drop_oldest".to_string(),
+            circuit_breaker: None,
+            blocking_pool_size: None,
+            max_batch_bytes: 0,
+            lazy: false,
+            max_connecting: None,
+        };
+
+        for (option, value) in options.into_iter().flatten() {
+            let option: &str = option.extract()?;
+            match option {
+                "defaults" => defaults = value.extract()?,
+                "write_buffer" => result.write_buffer = value.extract()?,
+                "write_buffer_overflow" => result.write_buffer_overflow = value.extract()?,
+                "circuit_breaker_threshold" => circuit_breaker_threshold = value.extract()?,
+                "circuit_breaker_reset" => circuit_breaker_reset = value.extract()?,
+                "blocking_pool_size" => result.blocking_pool_size = value.extract()?,
+                "max_batch_bytes" => max_batch_bytes = value.extract()?,
+                "lazy" if !is_async => result.lazy = value.extract()?,
+                "max_connecting" if is_async => result.max_connecting = value.extract()?,
+                _ => {
+                    return Err(PyTypeError::new_err(format!(
+                        "{}() got an unexpected keyword argument {:?}",
+                        if is_async { "AsyncStore" } else { "Store" },
+                        option
+                    )))
+                }
+            }
+        }
+
+        if result.blocking_pool_size == Some(0) {
+            return Err(PyValueError::new_err(
+                "blocking_pool_size should be at least 1",
+            ));
+        }
+        if result.max_connecting == Some(0) {
+            return Err(PyValueError::new_err("max_connecting should be at least 1"));
+        }
+        result.defaults = CollectionDefaults::from_py(defaults)?;
+        result.circuit_breaker =
+            CircuitBreaker::from_options(circuit_breaker_threshold, circuit_breaker_reset)?;
+        result.max_batch_bytes = BatchSizer::extract_max_bytes(max_batch_bytes)?;
+
+        Ok(result)
+    }
+}
+
+/// The options of create_collection(), given to it as keyword arguments. The options that are not given
+/// fall back to the defaults of the store
+pub(crate) struct CollectionOptions {
+    pub(crate) id_generator: Option<IdGenerator>,
+    pub(crate) retention: Option<Duration>,
+    pub(crate) retention_field: Option<String>,
+    pub(crate) bloom_filter: bool,
+    pub(crate) track_changes: bool,
+    pub(crate) coercions: HashMap<String, Vec<String>>,
+    pub(crate) versioning: Option<Versioning>,
+    pub(crate) register_nested_models: bool,
+    pub(crate) nested_depth: usize,
+    pub(crate) hot_keys: Option<HotKeys>,
+    pub(crate) namespace: Option<String>,
+    pub(crate) foreign_keys: Vec<String>,
+    pub(crate) indexed_fields: Option<Vec<String>>,
+    pub(crate) unique_fields: Vec<String>,
+    pub(crate) compound_indexes: Vec<Vec<String>>,
+    pub(crate) enforce_constraints: bool,
+    pub(crate) index_normalizers: HashMap<String, String>,
+    pub(crate) case_insensitive_keys: bool,
+    pub(crate) track_insertion_order: bool,
+    pub(crate) indexed_list_fields: Vec<String>,
+}
+
+impl Default for CollectionOptions {
+    fn default() -> Self {
+        Self::from_defaults(&CollectionDefaults::default())
+    }
+}
+
+impl CollectionOptions {
+    /// Creates the options from the python dict of keyword arguments of create_collection(), validating them
+    pub(crate) fn from_py(
+        options: Option<&PyDict>,
+        defaults: &CollectionDefaults,
+    ) -> PyResult<Self> {
+        let mut result = Self::from_defaults(defaults);
+        let (mut version, mut upgrades, mut persist_upgrades) = (None, None, false);
+        let mut id_generator: Option<String> = None;
+        let mut snowflake_worker_id = None;
+        let options = match options {
+            None => return Ok(result),
+            Some(options) => options,
+        };
+
+        for (option, value) in options {
+            let option: &str = option.extract()?;
+            match option {
+                "id_generator" => id_generator = value.extract()?,
+                "snowflake_worker_id" => snowflake_worker_id = value.extract()?,
+                "retention" if !value.is_none() => {
+                    result.retention = Some(parsers::extract_duration(value)?)
+                }
+                "retention" => {}
+                "retention_field" => result.retention_field = value.extract()?,
+                "bloom_filter" => {
+                    result.bloom_filter = value
+                        .extract::<Option<bool>>()?
+                        .unwrap_or(defaults.bloom_filter)
+                }
+                "track_changes" => {
+                    result.track_changes = value
+                        .extract::<Option<bool>>()?
+                        .unwrap_or(defaults.track_changes)
+                }
+                "coercions" => result.coercions = value.extract::<Option<_>>()?.unwrap_or_default(),
+                "version" => version = value.extract()?,
+                "upgrades" => upgrades = value.extract()?,
+                "persist_upgrades" => persist_upgrades = value.extract()?,
+                "register_nested_models" => {
+                    result.register_nested_models = value
+                        .extract::<Option<bool>>()?
+                        .unwrap_or(defaults.register_nested_models)
+                }
+                "nested_depth" => {
+                    result.nested_depth = value
+                        .extract::<Option<usize>>()?
+                        .unwrap_or(defaults.nested_depth)
+                }
+                "hot_key_sampling" => {
+                    result.hot_keys = value
+                        .extract::<Option<f64>>()?
+                        .map(HotKeys::new)
+                        .transpose()?
+                }
+                "namespace" => {
+                    if let Some(namespace) = value.extract::<Option<String>>()? {
+                        result.namespace = Some(namespace);
+                    }
+                }
+                "foreign_keys" => {
+                    result.foreign_keys = value.extract::<Option<_>>()?.unwrap_or_default()
+                }
+                "indexed_fields" => result.indexed_fields = value.extract()?,
+                "unique_fields" => {
+                    result.unique_fields = value.extract::<Option<_>>()?.unwrap_or_default()
+                }
+                "compound_indexes" => {
+                    result.compound_indexes = value.extract::<Option<_>>()?.unwrap_or_default()
+                }
+                "enforce_constraints" => {
+                    result.enforce_constraints = value
+                        .extract::<Option<bool>>()?
+                        .unwrap_or(defaults.enforce_constraints)
+                }
+                "index_normalizers" => {
+                    result.index_normalizers = value.extract::<Option<_>>()?.unwrap_or_default()
+                }
+                "case_insensitive_keys" => result.case_insensitive_keys = value.extract()?,
+                "track_insertion_order" => result.track_insertion_order = value.extract()?,
+                "indexed_list_fields" => {
+                    result.indexed_list_fields = value.extract::<Option<_>>()?.unwrap_or_default()
+                }
+                _ => {
+                    return Err(PyTypeError::new_err(format!(
+                        "create_collection() got an unexpected keyword argument {:?}",
+                        option
+                    )))
+                }
+            }
+        }
+
+        if result.nested_depth == 0 {
+            return Err(PyValueError::new_err("nested_depth should be at least 1"));
+        }
+        result.versioning = Versioning::from_py(version, upgrades, persist_upgrades)?;
+        let snowflake_worker_id = id_generators::validate_snowflake_worker_id(snowflake_worker_id)?;
+        match (id_generator, &mut result.id_generator) {
+            (Some(name), _) => {
+                let worker_id = snowflake_worker_id.or(defaults.snowflake_worker_id);
+                result.id_generator = Some(IdGenerator::from_name(&name, worker_id)?);
+            }
+            // the worker id given overrides that of the snowflake id generator of the store
+            (None, Some(IdGenerator::Snowflake { worker_id })) => {
+                *worker_id = snowflake_worker_id.unwrap_or(*worker_id)
+            }
+            (None, _) => {}
+        }
+
+        Ok(result)
+    }
+
+    /// Creates the options of the collection of a nested model registered along with the model nesting it,
+    /// which is kept in the same namespace and read to the same depth
+    pub(crate) fn for_nested_model(&self, defaults: &CollectionDefaults) -> CollectionOptions {
+        let mut result = Self::from_defaults(defaults);
+        result.register_nested_models = false;
+        result.nested_depth = self.nested_depth;
+        result.namespace = self.namespace.clone();
+        result
+    }
+
+    /// Creates the options of a collection for which no option is given
+    fn from_defaults(defaults: &CollectionDefaults) -> Self {
+        Self {
+            id_generator: defaults.id_generator.clone(),
+            retention: defaults.retention,
+            retention_field: None,
+            bloom_filter: defaults.bloom_filter,
+            track_changes: defaults.track_changes,
+            coercions: Default::default(),
+            versioning: None,
+            register_nested_models: defaults.register_nested_models,
+            nested_depth: defaults.nested_depth,
+            hot_keys: None,
+            namespace: defaults.namespace.clone(),
+            foreign_keys: Default::default(),
+            indexed_fields: None,
+            unique_fields: Default::default(),
+            compound_indexes: Default::default(),
+            enforce_constraints: defaults.enforce_constraints,
+            index_normalizers: Default::default(),
+            case_insensitive_keys: false,
+            track_insertion_order: false,
+            indexed_list_fields: Default::default(),
+        }
+    }
+}
@@ -0,0 +1,299 @@
+use pyo3::exceptions::{PyKeyError, PyValueError};
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+
+use crate::field_types::FieldType;
+use crate::key_normalization;
+use crate::range_indexes;
+use crate::registries::Registry;
+use crate::store::CollectionMeta;
+use crate::utils;
+
+pub(crate) const BROWSE_RECORDS_SCRIPT: &str = r"local cursor = ARGV[2] local skip = tonumber(ARGV[3]) local limit = tonumber(ARGV[4]) local page = {} local next_cursor = '' local total = 0 repeat local result = redis.call('SCAN', cursor, 'MATCH', ARGV[1], 'COUNT', limit) local hashes = {} for _, key in ipairs(result[2]) do if redis.call('TYPE', key).ok == 'hash' then table.insert(hashes, key) end end for i = skip + 1, #hashes do table.insert(page, hashes[i]) if #page == limit then if i < #hashes then next_cursor = cursor .. ':' .. i elseif result[1] ~= '0' then next_cursor = result[1] end break end end skip = 0 cursor = result[1] until (cursor == '0' or #page == limit) cursor = '0' repeat local result = redis.call('SCAN', cursor, 'MATCH', ARGV[1], 'COUNT', 1000) for _, key in ipairs(result[2]) do if redis.call('TYPE', key).ok == 'hash' then total = total + 1 end end cursor = result[1] until (cursor == '0') return {next_cursor, total, page}";
+const ORDERED_IDS_SCRIPT: &str = r"local table_unpack = table.unpack or unpack local prefix = ARGV[1] local is_numeric, desc = ARGV[3] == '1', ARGV[4] == '1' local skip, limit = tonumber(ARGV[5]), tonumber(ARGV[6]) local fields, filter_values = { ARGV[2] }, {} local records, ids = {}, {} if limit == 0 then return ids end for i = 7, #ARGV, 2 do table.insert(fields, ARGV[i]) table.insert(filter_values, ARGV[i + 1]) end local cursor = '0' repeat local result = redis.call('SCAN', cursor, 'MATCH', prefix .. '*') for _, key in ipairs(result[2]) do if redis.call('TYPE', key).ok == 'hash' then local values = redis.call('HMGET', key, table_unpack(fields)) local matches = true for i, v in ipairs(filter_values) do if values[i + 1] ~= v then matches = false break end end if matches then local value = values[1] if value == 'None' then value = false elseif value and is_numeric then value = tonumber(value) or false end table.insert(records, { string.sub(key, #prefix + 1), value }) end end end cursor = result[1] until cursor == '0' table.sort(records, function(a, b) if a[2] == b[2] then return a[1] < b[1] elseif not a[2] or not b[2] then return not b[2] elseif desc then return a[2] > b[2] end return a[2] < b[2] end) local last = #records if limit > 0 then last = math.min(last, skip + limit) end for i = skip + 1, last do table.insert(ids, records[i][1]) end return ids";
+const INDEXED_IDS_SCRIPT: &str = r"local table_unpack = table.unpack or unpack local desc = ARGV[2] == '1' local skip, limit = tonumber(ARGV[3]), tonumber(ARGV[4]) local ids = {} if limit == 0 then return ids end local function scored_range(first, stop) if not desc then return redis.call('ZRANGE', KEYS[1], first, stop) end local scored = redis.call('ZCARD', KEYS[1]) if stop < 0 or stop >= scored then stop = scored - 1 end local range = {} local rank = first while rank <= stop do local score = redis.call('ZREVRANGE', KEYS[1], rank, rank, 'WITHSCORES')[2] local before = redis.call('ZCOUNT', KEYS[1], '(' .. score, '+inf') local ties = redis.call('ZRANGEBYSCORE', KEYS[1], score, score, 'LIMIT', rank - before, stop - rank + 1) if #ties == 0 then break end for _, id in ipairs(ties) do table.insert(range, id) end rank = rank + #ties end return range end local function unscored_range(first, stop) return redis.call('ZRANGE', KEYS[2], first, stop) end if #ARGV == 4 then local scored = redis.call('ZCARD', KEYS[1]) local last = limit > 0 and skip + limit - 1 or -1 if skip < scored then ids = scored_range(skip, last >= 0 and math.min(last, scored - 1) or -1) end if last < 0 or last >= scored then local stop = last >= 0 and last - scored or -1 for _, id in ipairs(unscored_range(math.max(skip - scored, 0), stop)) do table.insert(ids, id) end end return ids end local fields, values = {}, {} for i = 5, #ARGV, 2 do table.insert(fields, ARGV[i]) table.insert(values, ARGV[i + 1]) end local matched = 0 for _, range in ipairs({ scored_range, unscored_range }) do local start = 0 repeat local batch = range(start, start + 999) for _, id in ipairs(batch) do local record = redis.call('HMGET', ARGV[1] .. id, table_unpack(fields)) local matches = true for i, v in ipairs(values) do if record[i] ~= v then matches = false break end end if matches then matched = matched + 1 if matched > skip then table.insert(ids, id) if #ids == limit then return ids end end end end start = start + 1000 until #batch < 1000 end return ids";
+const KEYSET_PAGE_SCRIPT: &str = r"local function sorts_after(a, b) for i = 1, math.min(#a, #b) do local x, y = string.byte(a, i), string.byte(b, i) if x ~= y then return x > y end end return #a > #b end local first = 0 if ARGV[1] ~= '' then first = redis.call('ZCOUNT', KEYS[1], '-inf', ARGV[1]) end if ARGV[2] ~= '' then local last = first first = redis.call('ZCOUNT', KEYS[1], '-inf', '(' .. ARGV[1]) while first < last do local middle = math.floor((first + last) / 2) if sorts_after(redis.call('ZRANGE', KEYS[1], middle, middle)[1], ARGV[2]) then last = middle else first = middle + 1 end end end return redis.call('ZRANGE', KEYS[1], first, first + tonumber(ARGV[3]) - 1)";
+
+/// A page of the records of a collection i.e. at most `limit` records, or all of them if there is
+/// no limit, after the first `skip` records. Records are in the order in which SCAN finds them,
+/// which is stable as long as the collection is not changed, unless they are ordered by a field
+#[derive(Clone, Copy, Debug, Default)]
+pub(crate) struct Page {
+    skip: usize,
+    limit: Option<usize>,
+}
+
+impl Page {
+    /// Creates the page that skips the given number of records and has at most the limit of records
+    pub(crate) fn new(skip: usize, limit: Option<usize>) -> Self {
+        Self { skip, limit }
+    }
+
+    /// Adds the number of records to skip and the limit, which is -1 if there is none, to the
+    /// arguments of the last command of the pipeline i.e. a script that scans a collection
+    pub(crate) fn add_args(self, pipe: &mut redis::Pipeline) {
+        pipe.arg(self.skip)
+            .arg(self.limit.map_or(-1, |limit| limit as i64));
+    }
+}
+
+/// The order of the records of a collection by their values of a field, descending if desc. The values of int and
+/// float fields are compared as numbers and the others as saved in redis, which orders dates and datetimes, saved
+/// in UTC, in time. Records with the same value are in the order of their ids, and the records without a value
+/// for the field come last, in the order of their ids. If the field has a range index that has all the records,
+/// the records are read in its order instead of being sorted
+#[derive(Clone, Debug)]
+pub(crate) struct Ordering {
+    field: String,
+    is_numeric: bool,
+    desc: bool,
+}
+
+impl Ordering {
+    /// Creates the ordering from the field and direction got from python, the records not being ordered
+    /// if no field is given
+    pub(crate) fn new(
+        meta: &CollectionMeta,
+        order_by: Option<String>,
+        desc: bool,
+    ) -> PyResult<Option<Self>> {
+        match order_by {
+            Some(field) => {
+                meta.ensure_scalar_field(&field)?;
+                let is_numeric = matches!(
+                    meta.schema.get_type(&field),
+                    Some(FieldType::Int | FieldType::Float)
+                );
+                Ok(Some(Self {
+                    field,
+                    is_numeric,
+                    desc,
+                }))
+            }
+            None if desc => Err(PyValueError::new_err(
+                "desc should only be given with order_by",
+            )),
+            None => Ok(None),
+        }
+    }
+
+    /// Generates the command that gets the ids of the given page of the records of the given collection whose
+    /// fields are equal to the values in the given filter, in this order, so that only the ids of the page
+    /// are transferred. If the field has a range index that is built, the page is read straight from the index,
+    /// and from the unscored ids after it, when there is no filter, or else the index is walked till the page is
+    /// full. Otherwise, e.g. while the index is still being built, the records are sorted in a script after
+    /// scanning the whole collection, so that none of them is left out
+    pub(crate) fn generate_ids_cmd(
+        &self,
+        collection_name: &str,
+        meta: &CollectionMeta,
+        filter: &[(String, String)],
+        page: Page,
+    ) -> redis::Cmd {
+        let mut cmd = redis::cmd("EVAL");
+        if meta
+            .registered_fields
+            .contains(Registry::BuiltRangeIndexes, &self.field)
+        {
+            cmd.arg(INDEXED_IDS_SCRIPT)
+                .arg(2)
+                .arg(utils::generate_range_index_key(
+                    collection_name,
+                    &self.field,
+                ))
+                .arg(utils::generate_unscored_ids_key(
+                    collection_name,
+                    &self.field,
+                ))
+                .arg(utils::generate_key_prefix(collection_name))
+                .arg(if self.desc { "1" } else { "0" })
+                .arg(page.skip)
+                .arg(page.limit.map_or(-1, |limit| limit as i64));
+            for (field, value) in filter {
+                cmd.arg(field).arg(value);
+            }
+            return cmd;
+        }
+
+        cmd.arg(ORDERED_IDS_SCRIPT)
+            .arg(0)
+            .arg(utils::generate_key_prefix(collection_name))
+            .arg(&self.field)
+            .arg(if self.is_numeric { "1" } else { "0" })
+            .arg(if self.desc { "1" } else { "0" })
+            .arg(page.skip)
+            .arg(page.limit.map_or(-1, |limit| limit as i64));
+        for (field, value) in filter {
+            cmd.arg(field).arg(value);
+        }
+        cmd
+    }
+}
+
+/// The position of a page of the records of a collection being browsed i.e. the SCAN cursor of the batch
+/// in which the page starts, with the number of records of that batch that were on earlier pages.
+/// It is given to python as "{cursor}" or "{cursor}:{skip}"
+#[derive(Clone, Copy, Debug, Default)]
+pub(crate) struct BrowseCursor {
+    scan_cursor: u64,
+    skip: usize,
+}
+
+impl BrowseCursor {
+    /// Parses the cursor got from python, the first page being at the cursor None
+    pub(crate) fn parse(cursor: Option<&str>) -> PyResult<Self> {
+        let cursor = match cursor {
+            None => return Ok(Self::default()),
+            Some(cursor) => cursor,
+        };
+
+        let (scan_cursor, skip) = cursor.split_once(':').unwrap_or((cursor, "0"));
+        match (scan_cursor.parse(), skip.parse()) {
+            (Ok(scan_cursor), Ok(skip)) => Ok(Self { scan_cursor, skip }),
+            _ => Err(PyValueError::new_err(format!(
+                "{:?} is not a cursor returned by browse()",
+                cursor
+            ))),
+        }
+    }
+
+    /// Generates the command that gets the ids of the page of at most `limit` records of the given
+    /// collection at this cursor, with the cursor of the next page and the total number of records
+    pub(crate) fn generate_browse_cmd(&self, collection_name: &str, limit: usize) -> redis::Cmd {
+        let mut cmd = redis::cmd("EVAL");
+        cmd.arg(BROWSE_RECORDS_SCRIPT)
+            .arg(0)
+            .arg(utils::generate_collection_key_pattern(collection_name))
+            .arg(self.scan_cursor)
+            .arg(self.skip)
+            .arg(limit);
+        cmd
+    }
+}
+
+/// The position after which a page of the records of a collection, in the order of their values of a field
+/// with a range index, starts i.e. the value and id of the last record of the previous page. Records with
+/// the same value are in the order of their ids, as in the index, so that each record is on exactly one page
+#[derive(Clone, Debug, Default)]
+pub(crate) struct KeysetCursor {
+    score: Option<String>,
+    id: Option<String>,
+}
+
+impl KeysetCursor {
+    /// Creates the cursor from the value and id got from python, the first page being after the value None.
+    /// The id is only used with a value, to order the records that have the same value
+    pub(crate) fn new(
+        meta: &CollectionMeta,
+        field: &str,
+        after_value: Option<Py<PyAny>>,
+        after_id: Option<&str>,
+    ) -> PyResult<Self> {
+        if after_value.is_none() && after_id.is_some() {
+            return Err(PyValueError::new_err(
+                "after_id should be given with after_value, the value of the field of the record with that id",
+            ));
+        }
+
+        Ok(Self {
+            score: after_value
+                .map(|v| range_indexes::bound_to_score(meta, field, Some(v), "-inf"))
+                .transpose()?,
+            id: after_id.map(|v| {
+                key_normalization::normalize_id(v, meta.case_insensitive_keys).into_owned()
+            }),
+        })
+    }
+
+    /// Generates the command that gets the ids of the page of at most `limit` records of the given
+    /// collection after this cursor, in the order of their values of the given field
+    pub(crate) fn generate_page_cmd(
+        &self,
+        collection_name: &str,
+        field: &str,
+        limit: usize,
+    ) -> redis::Cmd {
+        let mut cmd = redis::cmd("EVAL");
+        cmd.arg(KEYSET_PAGE_SCRIPT)
+            .arg(1)
+            .arg(utils::generate_range_index_key(collection_name, field))
+            .arg(self.score.as_deref().unwrap_or(""))
+            .arg(self.id.as_deref().unwrap_or(""))
+            .arg(limit);
+        cmd
+    }
+}
+
+/// A page of the records of a collection being browsed, as got by the command generated by
+/// `BrowseCursor::generate_browse_cmd`
+#[derive(Clone, Debug)]
+pub(crate) struct BrowsedPage {
+    pub(crate) ids: Vec<String>,
+    next_cursor: Option<String>,
+    total: usize,
+}
+
+impl BrowsedPage {
+    /// Creates the page from the reply of the browse command of the records of the given collection
+    pub(crate) fn from_reply(collection_name: &str, reply: (String, usize, Vec<String>)) -> Self {
+        let (next_cursor, total, keys) = reply;
+        let key_prefix = utils::generate_key_prefix(collection_name);
+        Self {
+            ids: keys
+                .iter()
+                .filter_map(|key| key.strip_prefix(&key_prefix).map(|id| id.to_string()))
+                .collect(),
+            next_cursor: Some(next_cursor).filter(|v| !v.is_empty()),
+            total,
+        }
+    }
+
+    /// Converts the page with its records into a python dictionary with the keys "records", "total",
+    /// the number of records in the collection, and "next_cursor", None if this is the last page
+    pub(crate) fn to_py(&self, records: Vec<Py<PyAny>>) -> PyResult<Py<PyAny>> {
+        Python::with_gil(|py| {
+            let page = PyDict::new(py);
+            page.set_item("records", records)?;
+            page.set_item("total", self.total)?;
+            page.set_item("next_cursor", &self.next_cursor)?;
+            Ok(page.into_py(py))
+        })
+    }
+}
+
+/// Ensures a page of records to browse, or to get after a keyset cursor, has at least one record
+pub(crate) fn validate_browse_limit(limit: usize) -> PyResult<usize> {
+    if limit == 0 {
+        return Err(PyValueError::new_err("limit should be greater than 0"));
+    }
+    Ok(limit)
+}
+
+/// Gets the fields of the records of the given collection to browse, which are all its fields, in the
+/// order of their names, if none are given. Nested fields may be given as dotted paths e.g. "address.city"
+pub(crate) fn resolve_browse_fields(
+    meta: &CollectionMeta,
+    fields: Option<Vec<String>>,
+) -> PyResult<Vec<String>> {
+    let fields = match fields {
+        None => {
+            let mut fields: Vec<String> = meta.schema.mapping.keys().cloned().collect();
+            fields.sort_unstable();
+            return Ok(fields);
+        }
+        Some(fields) => fields,
+    };
+
+    for field in &fields {
+        let head = field
+            .split_once('.')
+            .map_or(field.as_str(), |(head, _)| head);
+        if meta.schema.get_type(head).is_none() {
+            return Err(PyKeyError::new_err(format!(
+                "{:?} is not a field of the model",
+                field
+            )));
+        }
+    }
+    Ok(fields)
+}
@@ -1,8 +1,9 @@
 use std::str::FromStr;
+use std::time::Duration;
 
 use chrono::{NaiveDate, TimeZone, Utc};
 use pyo3::exceptions::PyValueError;
-use pyo3::PyResult;
+use pyo3::prelude::*;
 use redis::FromRedisValue;
 
 /// Parses datetime strings into timestamps using the "%Y-%m-%d %H:%M:%S.6%f%:z" format which was the default format
@@ -26,22 +27,65 @@ pub fn parse_date_to_timestamp(value: &str) -> PyResult<i64> {
     Ok(datetime.timestamp())
 }
 
-/// Extracts the portions of string from a string representation of a given value
-pub(crate) fn extract_str_portions<'a>(
-    value: &'a str,
-    start_char: &'a str,
-    end_char: &'a str,
-    separator: &'a str,
-) -> Vec<&'a str> {
-    value
-        .trim_start_matches(start_char)
-        .trim_end_matches(end_char)
-        .split(separator)
+/// Extracts the portions of string from a string representation of a given value e.g. the items of a list,
+/// stripping the given start and end characters and dropping the empty portions e.g. that of an empty list.
+/// Items that are themselves collections or strings are kept whole even if they hold the separator
+pub(crate) fn extract_str_portions(
+    value: &str,
+    start_char: char,
+    end_char: char,
+    separator: char,
+) -> Vec<&str> {
+    let value = value.trim();
+    let value = value.strip_prefix(start_char).unwrap_or(value);
+    let value = value.strip_suffix(end_char).unwrap_or(value);
+    split_top_level(value, separator)
         .into_iter()
-        .map(|v| v.trim().trim_end_matches("'").trim_start_matches("'"))
+        .filter(|v| !v.is_empty())
         .collect()
 }
 
+/// Splits a string at the separators that are neither within brackets nor within quotes, trimming the
+/// whitespace around each portion
+pub(crate) fn split_top_level(value: &str, separator: char) -> Vec<&str> {
+    let mut portions: Vec<&str> = Vec::new();
+    let mut depth = 0usize;
+    let mut quote: Option<char> = None;
+    let mut is_escaped = false;
+    let mut start = 0;
+
+    for (i, c) in value.char_indices() {
+        match quote {
+            Some(_) if is_escaped => is_escaped = false,
+            Some(_) if c == '\\' => is_escaped = true,
+            Some(q) if c == q => quote = None,
+            Some(_) => {}
+            None => match c {
+                '\'' | '"' => quote = Some(c),
+                '[' | '(' | '{' => depth += 1,
+                ']' | ')' | '}' => depth = depth.saturating_sub(1),
+                _ if c == separator && depth == 0 => {
+                    portions.push(value[start..i].trim());
+                    start = i + c.len_utf8();
+                }
+                _ => {}
+            },
+        }
+    }
+
+    portions.push(value[start..].trim());
+    portions
+}
+
+/// Removes the quotes around a string as it is represented in python e.g. 'foo' or "foo's"
+#[inline]
+pub(crate) fn unquote(value: &str) -> &str {
+    ['\'', '"']
+        .into_iter()
+        .find_map(|q| value.strip_prefix(q).and_then(|v| v.strip_suffix(q)))
+        .unwrap_or(value)
+}
+
 /// Redis value to pyresult type
 #[inline]
 pub(crate) fn redis_to_py<T>(v: &redis::Value) -> PyResult<T>
@@ -66,3 +110,30 @@ where
     data.parse::<T>()
         .map_err(|e| PyValueError::new_err(e.to_string()))
 }
+
+/// Extracts a duration from a python timedelta or a number of seconds
+///
+/// # Errors
+///
+/// [PyValueError](PyValueError) is returned if the value is neither or is negative
+///
+pub(crate) fn extract_duration(value: &PyAny) -> PyResult<Duration> {
+    let secs: f64 = match value.getattr("total_seconds") {
+        Ok(total_seconds) => total_seconds.call0()?.extract()?,
+        Err(_) => value.extract().map_err(|_| {
+            PyValueError::new_err(format!(
+                "expected a timedelta or number of seconds, got {}",
+                value
+            ))
+        })?,
+    };
+
+    if secs.is_finite() && secs >= 0.0 {
+        Ok(Duration::from_secs_f64(secs))
+    } else {
+        Err(PyValueError::new_err(format!(
+            "expected a non-negative duration, got {}",
+            value
+        )))
+    }
+}
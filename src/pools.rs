@@ -0,0 +1,275 @@
+use std::ops::Deref;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Instant;
+
+use async_std::future;
+
+use pyo3::create_exception;
+use pyo3::exceptions::PyConnectionError;
+use pyo3::prelude::*;
+
+use crate::circuit_breakers::CircuitBreaker;
+use crate::deadlines::Deadline;
+use crate::mobc_redis::RedisConnectionManager;
+
+// Raised when no connection of the pool of a store becomes free before the timeout. It is a
+// ConnectionError so that code handling the errors of redis connections also handles it
+create_exception!(orredis, PoolExhaustedError, PyConnectionError);
+
+const POOL_EXHAUSTED_SUGGESTION: &str =
+    "Try increasing pool_size or timeout when creating the store, or making fewer concurrent calls";
+
+/// The pool of connections of a store, guarded by the circuit breaker of the store if it has one.
+/// Blocking and long operations e.g. throttled scans, get their connections from the blocking pool,
+/// if the store has one, so that they cannot starve the other calls of connections
+#[derive(Clone)]
+pub(crate) struct RedisPool {
+    pool: r2d2::Pool<redis::Client>,
+    blocking_pool: Option<r2d2::Pool<redis::Client>>,
+    pub(crate) circuit_breaker: Option<CircuitBreaker>,
+    is_closed: Arc<AtomicBool>,
+}
+
+impl RedisPool {
+    pub(crate) fn new(
+        pool: r2d2::Pool<redis::Client>,
+        blocking_pool: Option<r2d2::Pool<redis::Client>>,
+        circuit_breaker: Option<CircuitBreaker>,
+    ) -> Self {
+        Self {
+            pool,
+            blocking_pool,
+            circuit_breaker,
+            is_closed: Default::default(),
+        }
+    }
+
+    /// Closes this pool for all handles of the store, so that no more connections can be checked out of it
+    #[inline]
+    pub(crate) fn close(&self) {
+        self.is_closed.store(true, Ordering::Relaxed);
+    }
+}
+
+impl Deref for RedisPool {
+    type Target = r2d2::Pool<redis::Client>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.pool
+    }
+}
+
+/// The pool of connections of an async store, guarded by the circuit breaker of the store if it has one.
+/// Blocking and long operations e.g. throttled scans, get their connections from the blocking pool,
+/// if the store has one, so that they cannot starve the other calls of connections
+#[derive(Clone)]
+pub(crate) struct AsyncRedisPool {
+    pool: mobc::Pool<RedisConnectionManager>,
+    blocking_pool: Option<mobc::Pool<RedisConnectionManager>>,
+    pub(crate) circuit_breaker: Option<CircuitBreaker>,
+    is_closed: Arc<AtomicBool>,
+}
+
+impl AsyncRedisPool {
+    pub(crate) fn new(
+        pool: mobc::Pool<RedisConnectionManager>,
+        blocking_pool: Option<mobc::Pool<RedisConnectionManager>>,
+        circuit_breaker: Option<CircuitBreaker>,
+    ) -> Self {
+        Self {
+            pool,
+            blocking_pool,
+            circuit_breaker,
+            is_closed: Default::default(),
+        }
+    }
+
+    /// Closes this pool for all handles of the store, so that no more connections can be checked out of it.
+    /// Its idle connections are closed at once, and those in use are closed as they are returned to it
+    pub(crate) async fn close(&self) {
+        self.is_closed.store(true, Ordering::Relaxed);
+        self.pool.set_max_idle_conns(0).await;
+        if let Some(blocking_pool) = &self.blocking_pool {
+            blocking_pool.set_max_idle_conns(0).await;
+        }
+    }
+}
+
+impl Deref for AsyncRedisPool {
+    type Target = mobc::Pool<RedisConnectionManager>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.pool
+    }
+}
+
+/// Checks out a connection from the given pool, failing with a PoolExhaustedError if all
+/// connections of the pool stayed in use till the timeout, with a DeadlineExceededError if
+/// the deadline of the call, if any, passed first, with a CircuitOpenError if the circuit
+/// breaker of the pool is open, or with a ConnectionError if the pool was closed
+#[inline]
+pub(crate) fn get_connection(pool: &RedisPool) -> PyResult<r2d2::PooledConnection<redis::Client>> {
+    ensure_open(&pool.is_closed)?;
+    checkout(&pool.pool, &pool.circuit_breaker)
+}
+
+/// Checks out a connection for a blocking or long operation from the blocking pool of the given pool,
+/// or from the pool itself if it has no blocking pool, failing like `get_connection()`
+#[inline]
+pub(crate) fn get_blocking_connection(
+    pool: &RedisPool,
+) -> PyResult<r2d2::PooledConnection<redis::Client>> {
+    ensure_open(&pool.is_closed)?;
+    let blocking_pool = pool.blocking_pool.as_ref().unwrap_or(&pool.pool);
+    checkout(blocking_pool, &pool.circuit_breaker)
+}
+
+/// Checks out a connection from the given async pool, failing with a PoolExhaustedError if all
+/// connections of the pool stayed in use till the timeout, with a DeadlineExceededError if
+/// the deadline of the call, if any, passed first, with a CircuitOpenError if the circuit
+/// breaker of the pool is open, or with a ConnectionError if the pool was closed
+#[inline]
+pub(crate) async fn get_connection_async(
+    pool: &AsyncRedisPool,
+) -> PyResult<mobc::Connection<RedisConnectionManager>> {
+    ensure_open(&pool.is_closed)?;
+    checkout_async(&pool.pool, &pool.circuit_breaker).await
+}
+
+/// Checks out a connection for a blocking or long operation from the blocking pool of the given async pool,
+/// or from the pool itself if it has no blocking pool, failing like `get_connection_async()`
+#[inline]
+pub(crate) async fn get_blocking_connection_async(
+    pool: &AsyncRedisPool,
+) -> PyResult<mobc::Connection<RedisConnectionManager>> {
+    ensure_open(&pool.is_closed)?;
+    let blocking_pool = pool.blocking_pool.as_ref().unwrap_or(&pool.pool);
+    checkout_async(blocking_pool, &pool.circuit_breaker).await
+}
+
+/// Fails with a ConnectionError if the pool, whose closed flag is given, was closed with its store
+#[inline]
+fn ensure_open(is_closed: &AtomicBool) -> PyResult<()> {
+    match is_closed.load(Ordering::Relaxed) {
+        true => Err(PyConnectionError::new_err("the store is closed")),
+        false => Ok(()),
+    }
+}
+
+/// Checks out a connection from the given pool, recording the outcome in the given circuit breaker, if any
+fn checkout(
+    pool: &r2d2::Pool<redis::Client>,
+    circuit_breaker: &Option<CircuitBreaker>,
+) -> PyResult<r2d2::PooledConnection<redis::Client>> {
+    let deadline = Deadline::current()?;
+    if let Some(circuit_breaker) = circuit_breaker {
+        circuit_breaker.acquire()?;
+    }
+
+    let start = Instant::now();
+    let conn = match &deadline {
+        Some(deadline) => pool.get_timeout(deadline.remaining()?.min(pool.connection_timeout())),
+        None => pool.get(),
+    };
+
+    match conn {
+        Ok(conn) => {
+            if let Some(circuit_breaker) = circuit_breaker {
+                circuit_breaker.record_success();
+            }
+            Ok(conn)
+        }
+        Err(e) => {
+            if let Some(deadline) = deadline.filter(Deadline::has_passed) {
+                return Err(deadline.exceeded_error());
+            }
+
+            let state = pool.state();
+            let in_use = state.connections - state.idle_connections;
+            let pool_size = pool.max_size();
+            // a pool whose connections are all in use says nothing about whether redis is up
+            if let (Some(circuit_breaker), true) = (circuit_breaker, in_use < pool_size) {
+                circuit_breaker.record_failure();
+            }
+            Err(generate_checkout_error(
+                pool_size as u64,
+                in_use as u64,
+                start.elapsed().as_millis(),
+                e.to_string(),
+            ))
+        }
+    }
+}
+
+/// Checks out a connection from the given async pool, recording the outcome in the given circuit breaker, if any
+async fn checkout_async(
+    pool: &mobc::Pool<RedisConnectionManager>,
+    circuit_breaker: &Option<CircuitBreaker>,
+) -> PyResult<mobc::Connection<RedisConnectionManager>> {
+    let deadline = Deadline::current_async()?;
+    if let Some(circuit_breaker) = circuit_breaker {
+        circuit_breaker.acquire()?;
+    }
+
+    let start = Instant::now();
+    let conn = match &deadline {
+        // the wait is cut short by the deadline but is still bounded by the timeout of the pool
+        Some(deadline) => future::timeout(deadline.remaining()?, pool.get())
+            .await
+            .map_err(|_| deadline.exceeded_error())?,
+        None => pool.get().await,
+    };
+
+    match conn {
+        Ok(conn) => {
+            if let Some(circuit_breaker) = circuit_breaker {
+                circuit_breaker.record_success();
+            }
+            Ok(conn)
+        }
+        Err(e) => {
+            let waited_ms = start.elapsed().as_millis();
+            let state = pool.state().await;
+            // a pool whose connections are all in use says nothing about whether redis is up
+            if let (Some(circuit_breaker), true) = (circuit_breaker, state.in_use < state.max_open)
+            {
+                circuit_breaker.record_failure();
+            }
+            Err(generate_checkout_error(
+                state.max_open,
+                state.in_use,
+                waited_ms,
+                e.to_string(),
+            ))
+        }
+    }
+}
+
+/// Generates the error for a failed checkout of a connection from a pool. It is a PoolExhaustedError,
+/// with the pool_size, in_use, waited_ms and suggestion attributes, if all connections were in use,
+/// otherwise the connections could not be opened and it is a ConnectionError
+fn generate_checkout_error(pool_size: u64, in_use: u64, waited_ms: u128, reason: String) -> PyErr {
+    if in_use < pool_size {
+        return PyConnectionError::new_err(reason);
+    }
+
+    let err = PoolExhaustedError::new_err(format!(
+        "no connection became free after waiting {}ms: all {} connections of the pool are in use. {}",
+        waited_ms, pool_size, POOL_EXHAUSTED_SUGGESTION
+    ));
+    Python::with_gil(|py| {
+        let value = err.value(py);
+        let context = [
+            ("pool_size", pool_size.into_py(py)),
+            ("in_use", in_use.into_py(py)),
+            ("waited_ms", waited_ms.into_py(py)),
+            ("suggestion", POOL_EXHAUSTED_SUGGESTION.into_py(py)),
+        ];
+        for (name, v) in context {
+            // the message still has the context if an attribute cannot be set
+            let _ = value.setattr(name, v);
+        }
+    });
+    err
+}
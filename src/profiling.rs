@@ -0,0 +1,162 @@
+use std::cell::RefCell;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+
+thread_local! {
+    /// The profile of the call running on this thread, if it is profiled
+    static CURRENT: RefCell<Option<Profile>> = const { RefCell::new(None) };
+}
+
+/// The phases of a call whose times are measured when the call is profiled
+#[derive(Clone, Copy, Debug)]
+pub(crate) enum Phase {
+    /// Converting models into the commands sent to redis
+    Serialize = 0,
+    /// Checking out connections and waiting for the replies of redis
+    Network = 1,
+    /// Parsing the replies of redis into the values of the fields
+    Parse = 2,
+    /// Constructing models from the values of their fields
+    Construct = 3,
+}
+
+/// The times, in nanoseconds, spent in each phase of a call, by phase
+#[derive(Debug, Default)]
+struct Timings([AtomicU64; 4]);
+
+/// The timing breakdown of a call made with `profile=True`. It is shared by all the futures and threads of
+/// the call, so that the time they spend in each phase adds up
+#[derive(Clone, Debug)]
+pub(crate) struct Profile {
+    timings: Arc<Timings>,
+    started: Instant,
+}
+
+impl Profile {
+    /// Starts the profile of a call if it is to be profiled
+    pub(crate) fn start(is_enabled: bool) -> Option<Self> {
+        is_enabled.then(|| Self {
+            timings: Default::default(),
+            started: Instant::now(),
+        })
+    }
+
+    /// Adds the given time to that spent in the given phase
+    fn add(&self, phase: Phase, elapsed: Duration) {
+        self.timings.0[phase as usize].fetch_add(elapsed.as_nanos() as u64, Ordering::Relaxed);
+    }
+
+    /// Runs the given function with this profile as the profile of the current thread
+    fn run<T>(&self, f: impl FnOnce() -> T) -> T {
+        let _previous = Restore(CURRENT.with(|current| current.replace(Some(self.clone()))));
+        f()
+    }
+
+    /// Converts the profile into a python dictionary with the keys: "serialize_ms", "network_ms", "parse_ms"
+    /// and "construct_ms", the times in milliseconds spent in each phase; and "total_ms", the time since
+    /// the call started. The rest of the total is spent in orredis itself e.g. in generating keys
+    fn to_py(&self) -> PyResult<Py<PyAny>> {
+        let in_millis = |phase: Phase| {
+            self.timings.0[phase as usize].load(Ordering::Relaxed) as f64 / 1_000_000.0
+        };
+
+        Python::with_gil(|py| {
+            let profile = PyDict::new(py);
+            profile.set_item("serialize_ms", in_millis(Phase::Serialize))?;
+            profile.set_item("network_ms", in_millis(Phase::Network))?;
+            profile.set_item("parse_ms", in_millis(Phase::Parse))?;
+            profile.set_item("construct_ms", in_millis(Phase::Construct))?;
+            profile.set_item("total_ms", self.started.elapsed().as_secs_f64() * 1000.0)?;
+            Ok(profile.into_py(py))
+        })
+    }
+}
+
+/// Restores the profile of the current thread when dropped, even if the function run with another panicked
+struct Restore(Option<Profile>);
+
+impl Drop for Restore {
+    fn drop(&mut self) {
+        CURRENT.with(|current| *current.borrow_mut() = self.0.take());
+    }
+}
+
+/// A future that is polled with a profile as the profile of the thread polling it
+struct Profiled<F> {
+    profile: Profile,
+    inner: Pin<Box<F>>,
+}
+
+impl<F: Future> Future for Profiled<F> {
+    type Output = F::Output;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.get_mut();
+        let inner = &mut this.inner;
+        this.profile.run(|| inner.as_mut().poll(cx))
+    }
+}
+
+/// Runs the given function of a sync call, measuring the phases of the call in the given profile if any
+pub(crate) fn run<T>(profile: &Option<Profile>, f: impl FnOnce() -> T) -> T {
+    match profile {
+        None => f(),
+        Some(profile) => profile.run(f),
+    }
+}
+
+/// Awaits the given future of an async call, measuring the phases of the call in the given profile if any
+pub(crate) async fn run_async<F: Future>(profile: &Option<Profile>, future: F) -> F::Output {
+    match profile {
+        None => future.await,
+        Some(profile) => {
+            Profiled {
+                profile: profile.clone(),
+                inner: Box::pin(future),
+            }
+            .await
+        }
+    }
+}
+
+/// Runs the given function, adding its time to the given phase of the profiled call it is part of, if any
+pub(crate) fn measure<T>(phase: Phase, f: impl FnOnce() -> T) -> T {
+    match CURRENT.with(|current| current.borrow().clone()) {
+        None => f(),
+        Some(profile) => {
+            let started = Instant::now();
+            let result = f();
+            profile.add(phase, started.elapsed());
+            result
+        }
+    }
+}
+
+/// Awaits the given future, adding the time till it is ready to the given phase of the profiled call
+/// it is part of, if any
+pub(crate) async fn measure_async<F: Future>(phase: Phase, future: F) -> F::Output {
+    let started = Instant::now();
+    let result = future.await;
+    if let Some(profile) = CURRENT.with(|current| current.borrow().clone()) {
+        profile.add(phase, started.elapsed());
+    }
+    result
+}
+
+/// Returns the result of a call, paired with its timing breakdown as (result, profile) if it was profiled
+pub(crate) fn attach<T: IntoPy<Py<PyAny>>>(
+    profile: Option<Profile>,
+    result: T,
+) -> PyResult<Py<PyAny>> {
+    Python::with_gil(|py| match profile {
+        None => Ok(result.into_py(py)),
+        Some(profile) => Ok((result.into_py(py), profile.to_py()?).into_py(py)),
+    })
+}
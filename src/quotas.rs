@@ -0,0 +1,130 @@
+use pyo3::create_exception;
+use pyo3::exceptions::PyException;
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+
+// Raised when an insert or update would take the usage of the quota of a namespace beyond its limits
+create_exception!(orredis, QuotaExceededError, PyException);
+
+const UPDATE_QUOTA_USAGE_SCRIPT: &str = r"local records, bytes = 0, 0 local pos = 2 for i = 4, #KEYS do local n = tonumber(ARGV[pos]) pos = pos + 1 if n > 0 and redis.call('EXISTS', KEYS[i]) == 0 then records = records + 1 end for _ = 1, n do local field, length = ARGV[pos], tonumber(ARGV[pos + 1]) pos = pos + 2 if redis.call('HEXISTS', KEYS[i], field) == 1 then bytes = bytes + length - redis.call('HSTRLEN', KEYS[i], field) else bytes = bytes + #field + length end end end local quota = redis.call('HMGET', KEYS[1], 'records', 'bytes', 'max_records', 'max_bytes') local new_records = (tonumber(quota[1]) or 0) + records local new_bytes = (tonumber(quota[2]) or 0) + bytes if (quota[3] and records > 0 and new_records > tonumber(quota[3])) or (quota[4] and bytes > 0 and new_bytes > tonumber(quota[4])) then redis.call('SET', KEYS[2], 1, 'PX', 60000) return redis.error_reply('QUOTAEXCEEDED the write would exceed the quota of namespace ' .. string.format('%q', ARGV[1]) .. ', taking it to ' .. new_records .. ' records (max: ' .. (quota[3] or 'no limit') .. ') and ' .. new_bytes .. ' bytes (max: ' .. (quota[4] or 'no limit') .. ')') end redis.call('HINCRBY', KEYS[1], 'records', records) redis.call('HINCRBY', KEYS[1], 'bytes', bytes) redis.call('HSET', KEYS[3], 'quota', KEYS[1], 'records', records, 'bytes', bytes) redis.call('PEXPIRE', KEYS[3], 60000) return 1";
+/// The code of the error replied by `UPDATE_QUOTA_USAGE_SCRIPT` when a write would exceed the quota
+const QUOTA_EXCEEDED_CODE: &str = "QUOTAEXCEEDED";
+const RELEASE_QUOTA_USAGE_SCRIPT: &str = r"local records, bytes = 0, 0 for i = 2, #KEYS do local fields = redis.call('HGETALL', KEYS[i]) if #fields > 0 then records = records + 1 for j = 1, #fields do bytes = bytes + #fields[j] end end end redis.call('HINCRBY', KEYS[1], 'records', -records) redis.call('HINCRBY', KEYS[1], 'bytes', -bytes)";
+
+/// The usage of the quota of a namespace i.e. the number of records in its collections and the total length
+/// of their field names and values, with its limits if any
+#[derive(Clone, Copy, Debug, Default)]
+pub(crate) struct Quota {
+    records: i64,
+    bytes: i64,
+    max_records: Option<u64>,
+    max_bytes: Option<u64>,
+}
+
+impl Quota {
+    /// Creates the quota from the reply of the command generated by `generate_get_cmd`
+    pub(crate) fn from_reply(reply: (Option<i64>, Option<i64>, Option<u64>, Option<u64>)) -> Self {
+        let (records, bytes, max_records, max_bytes) = reply;
+        Self {
+            records: records.unwrap_or_default(),
+            bytes: bytes.unwrap_or_default(),
+            max_records,
+            max_bytes,
+        }
+    }
+
+    /// Converts the quota into a python dictionary with the keys: "records", "bytes", "max_records"
+    /// and "max_bytes", the last two being None if there is no such limit
+    pub(crate) fn to_py(self) -> PyResult<Py<PyAny>> {
+        Python::with_gil(|py| {
+            let quota = PyDict::new(py);
+            quota.set_item("records", self.records)?;
+            quota.set_item("bytes", self.bytes)?;
+            quota.set_item("max_records", self.max_records)?;
+            quota.set_item("max_bytes", self.max_bytes)?;
+            Ok(quota.into_py(py))
+        })
+    }
+}
+
+/// Generates the command that adds to the usage of the quota at the given key the change made by writing the given
+/// fields to the hashmaps of the given keys, unless it would take the usage beyond the limits of the quota of the
+/// given namespace. It is run right before the transaction of the write, whose guard keys are given, so that
+/// the check and the change are atomic. If the quota would be exceeded, the command sets the guard key, aborting
+/// the transaction, and fails with the error converted by `to_quota_exceeded_error()`. Otherwise, the change is
+/// saved at the undo key so that the checks of the write made after it can take it back if they fail
+pub(crate) fn generate_usage_cmd(
+    quota_key: &str,
+    guard_key: &str,
+    undo_key: &str,
+    namespace: &str,
+    records: &[(&str, Vec<(&String, &String)>)],
+) -> redis::Cmd {
+    let mut cmd = redis::cmd("EVAL");
+    cmd.arg(UPDATE_QUOTA_USAGE_SCRIPT)
+        .arg(records.len() + 3)
+        .arg(quota_key)
+        .arg(guard_key)
+        .arg(undo_key);
+    for (key, _) in records {
+        cmd.arg(key);
+    }
+
+    cmd.arg(namespace);
+    for (_, fields) in records {
+        cmd.arg(fields.len());
+        for (name, value) in fields {
+            cmd.arg(name).arg(value.len());
+        }
+    }
+    cmd
+}
+
+/// Generates the command that subtracts the records of the given keys, before they are deleted,
+/// from the usage of the quota at the given key
+pub(crate) fn generate_release_cmd(quota_key: &str, keys: &[String]) -> redis::Cmd {
+    let mut cmd = redis::cmd("EVAL");
+    cmd.arg(RELEASE_QUOTA_USAGE_SCRIPT)
+        .arg(keys.len() + 1)
+        .arg(quota_key)
+        .arg(keys);
+    cmd
+}
+
+/// Generates the command that gets the usage and the limits of the quota at the given key
+pub(crate) fn generate_get_cmd(quota_key: &str) -> redis::Cmd {
+    let mut cmd = redis::cmd("HMGET");
+    cmd.arg(quota_key)
+        .arg("records")
+        .arg("bytes")
+        .arg("max_records")
+        .arg("max_bytes");
+    cmd
+}
+
+/// Generates the pipeline that sets the limits of the quota at the given key, removing those not given
+pub(crate) fn generate_set_pipeline(
+    quota_key: &str,
+    max_records: Option<u64>,
+    max_bytes: Option<u64>,
+) -> redis::Pipeline {
+    let mut pipe = redis::pipe();
+    for (field, limit) in [("max_records", max_records), ("max_bytes", max_bytes)] {
+        match limit {
+            Some(limit) => pipe.hset(quota_key, field, limit).ignore(),
+            None => pipe.hdel(quota_key, field).ignore(),
+        };
+    }
+    pipe
+}
+
+/// Converts the error of the command of `generate_usage_cmd()` when the write would exceed the quota into
+/// a QuotaExceededError, or returns None if the given error is another one
+pub(crate) fn to_quota_exceeded_error(e: &redis::RedisError) -> Option<PyErr> {
+    match (e.code(), e.detail()) {
+        (Some(QUOTA_EXCEEDED_CODE), Some(detail)) => {
+            Some(QuotaExceededError::new_err(detail.to_string()))
+        }
+        _ => None,
+    }
+}
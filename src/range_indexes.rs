@@ -0,0 +1,122 @@
+use chrono::{DateTime, NaiveDate};
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+
+use crate::field_types::FieldType;
+use crate::store::CollectionMeta;
+use crate::utils;
+
+const BACKFILL_RANGE_INDEX_SCRIPT: &str = r"if redis.call('EXISTS', KEYS[1]) == 0 then return 0 end if redis.call('HGET', KEYS[1], ARGV[1]) ~= (ARGV[4] or false) then return 0 end if ARGV[3] == '' then redis.call('ZREM', KEYS[2], ARGV[2]) redis.call('ZADD', KEYS[3], 0, ARGV[2]) else redis.call('ZADD', KEYS[2], ARGV[3], ARGV[2]) redis.call('ZREM', KEYS[3], ARGV[2]) end return 1";
+
+/// The number of records read and indexed in each round trip to redis when an index is built
+pub(crate) const INDEX_BATCH_SIZE: usize = 1_000;
+
+/// Ensures the given field can have a range index i.e. it is an int, float, date or datetime field
+/// that is not a counter field
+pub(crate) fn ensure_indexable_field(meta: &CollectionMeta, field: &str) -> PyResult<()> {
+    meta.ensure_scalar_field(field)?;
+    if meta.counter_fields.iter().any(|v| v == field) {
+        return Err(PyValueError::new_err(format!(
+            "{:?} is a counter field yet counter fields cannot be indexed",
+            field
+        )));
+    }
+
+    match meta.schema.get_type(field) {
+        Some(FieldType::Int | FieldType::Float | FieldType::Date | FieldType::Datetime) => Ok(()),
+        _ => Err(PyValueError::new_err(format!(
+            "{:?} is not an int, float, date or datetime field yet only such fields can be indexed",
+            field
+        ))),
+    }
+}
+
+/// Converts the value of a field of the given type, as saved in redis, into the score of its record
+/// in the range index of the field. Dates and datetimes are scored by their timestamps. It returns None
+/// if the value has no score e.g. it is None
+pub(crate) fn to_score(type_: &FieldType, value: &str) -> Option<f64> {
+    match type_ {
+        FieldType::Int | FieldType::Float => value.parse::<f64>().ok(),
+        FieldType::Datetime => DateTime::parse_from_str(value, "%Y-%m-%d %H:%M:%S%.f%:z")
+            .ok()
+            .map(|v| v.timestamp() as f64 + v.timestamp_subsec_micros() as f64 / 1e6),
+        FieldType::Date => NaiveDate::parse_from_str(value, "%Y-%m-%d")
+            .ok()
+            .map(|v| v.and_hms(0, 0, 0).timestamp() as f64),
+        _ => None,
+    }
+}
+
+/// Converts the given bound of a range of values of the given field into a score, an unbounded range
+/// being given as None
+pub(crate) fn bound_to_score(
+    meta: &CollectionMeta,
+    field: &str,
+    bound: Option<Py<PyAny>>,
+    unbounded: &'static str,
+) -> PyResult<String> {
+    let bound = match bound {
+        None => return Ok(unbounded.to_string()),
+        Some(bound) => bound,
+    };
+
+    let type_ = meta.schema.get_type(field).unwrap();
+    let value = utils::field_value_to_redis(type_, &bound)?;
+    to_score(type_, &value)
+        .map(|score| score.to_string())
+        .ok_or_else(|| {
+            PyValueError::new_err(format!(
+                "{} is not a valid bound of the field {:?}, whose type is {}",
+                value,
+                field,
+                type_.describe()
+            ))
+        })
+}
+
+/// Adds to the pipeline the commands that update the score of the record of the given id in the range
+/// index of the given field to that of the given value. If the value has no score, the record is moved
+/// from the index to the unscored ids of the field instead, so that every record is in one of them
+pub(crate) fn add_update_cmd(
+    pipe: &mut redis::Pipeline,
+    collection_name: &str,
+    field: &str,
+    type_: &FieldType,
+    id: &str,
+    value: &str,
+) {
+    let index_key = utils::generate_range_index_key(collection_name, field);
+    let unscored_key = utils::generate_unscored_ids_key(collection_name, field);
+    match to_score(type_, value) {
+        Some(score) => pipe.zadd(index_key, id, score).zrem(unscored_key, id),
+        None => pipe.zrem(index_key, id).zadd(unscored_key, id, 0),
+    };
+}
+
+/// Adds to the pipeline the command that updates the range index of the given field for the record of the given
+/// key and id like `add_update_cmd()`, from the value of the field read from the record, or None if the record
+/// had no such field. The index is left as it is if the record has been deleted or its value has changed since,
+/// as the write that changed it updated the index itself
+pub(crate) fn add_backfill_cmd(
+    pipe: &mut redis::Pipeline,
+    collection_name: &str,
+    field: &str,
+    type_: &FieldType,
+    pk: &str,
+    id: &str,
+    value: Option<&str>,
+) {
+    let score = value
+        .and_then(|v| to_score(type_, v))
+        .map_or(String::new(), |v| v.to_string());
+    pipe.cmd("EVAL")
+        .arg(BACKFILL_RANGE_INDEX_SCRIPT)
+        .arg(3)
+        .arg(pk)
+        .arg(utils::generate_range_index_key(collection_name, field))
+        .arg(utils::generate_unscored_ids_key(collection_name, field))
+        .arg(field)
+        .arg(id)
+        .arg(score)
+        .arg(value);
+}
@@ -0,0 +1,73 @@
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+
+/// What a bulk read does with the records that cannot be read e.g. because they are corrupt
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum OnError {
+    /// The read fails with the error of the first record that cannot be read
+    Raise,
+    /// The records that cannot be read are left out
+    Skip,
+    /// The records that cannot be read are left out, and returned separately with their errors
+    Collect,
+}
+
+/// A record that could not be read, as (its id if it was got, the error)
+pub(crate) type ReadError = (Option<String>, PyErr);
+
+impl OnError {
+    /// Gets the on_error of the given name
+    pub(crate) fn from_name(name: &str) -> PyResult<Self> {
+        match name {
+            "raise" => Ok(Self::Raise),
+            "skip" => Ok(Self::Skip),
+            "collect" => Ok(Self::Collect),
+            _ => Err(PyValueError::new_err(format!(
+                "unknown on_error {:?}. Expected one of \"raise\", \"skip\" or \"collect\"",
+                name
+            ))),
+        }
+    }
+
+    /// Handles the error of reading the record of the given id, failing with it if it is to be raised
+    pub(crate) fn handle(
+        self,
+        errors: &mut Vec<ReadError>,
+        id: Option<String>,
+        err: PyErr,
+    ) -> PyResult<()> {
+        match self {
+            Self::Raise => return Err(err),
+            Self::Skip => {}
+            Self::Collect => errors.push((id, err)),
+        }
+        Ok(())
+    }
+
+    /// Converts the records read and the errors of those that were not into the python value returned
+    /// by the read i.e. (records, errors) if the errors are collected, otherwise the records. Each error is
+    /// a dictionary of the "id" of the record, None if it is unknown, and the "error" raised reading it
+    pub(crate) fn to_py(
+        self,
+        records: Vec<Py<PyAny>>,
+        errors: Vec<ReadError>,
+    ) -> PyResult<Py<PyAny>> {
+        Python::with_gil(|py| {
+            if self != Self::Collect {
+                return Ok(records.into_py(py));
+            }
+
+            let errors = errors
+                .into_iter()
+                .map(|(id, err)| {
+                    let item = PyDict::new(py);
+                    item.set_item("id", id)?;
+                    item.set_item("error", err.into_py(py))?;
+                    Ok(item.into_py(py))
+                })
+                .collect::<PyResult<Vec<Py<PyAny>>>>()?;
+            Ok((records, errors).into_py(py))
+        })
+    }
+}
@@ -0,0 +1,152 @@
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
+
+use crate::utils;
+
+/// The time for which the registered fields of a collection, once read from redis, are used as they are.
+/// Fields registered by other clients are thus taken into account by the writes of this client within it
+pub(crate) const REFRESH_INTERVAL: Duration = Duration::from_millis(500);
+/// The time waited after a field is first registered before the data derived from it is built from the records
+/// already saved, twice the `REFRESH_INTERVAL`, by when the writes of all clients update the data too
+pub(crate) const REGISTRATION_DELAY: Duration = Duration::from_millis(1_000);
+
+/// A set in redis of the fields of a collection whose auxiliary data is updated on every write.
+/// Fields are registered in it by any client of the collection, and read back by all of them
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub(crate) enum Registry {
+    /// The fields whose distinct values are counted, registered by `track_cardinality()`
+    Cardinality,
+    /// The fields whose range indexes are updated on every write, registered by `create_index()`
+    RangeIndexes,
+    /// The fields whose range indexes have been built from the records saved before they were registered,
+    /// and thus have all the records of the collection
+    BuiltRangeIndexes,
+    /// The fields whose null indexes are updated on every write, registered by `create_null_index()`
+    NullIndexes,
+    /// The fields whose null indexes have been built from the records saved before they were registered
+    BuiltNullIndexes,
+}
+
+impl Registry {
+    /// All the registries, in the order in which they are read by `generate_refresh_pipeline()`
+    const ALL: [Registry; 5] = [
+        Registry::Cardinality,
+        Registry::RangeIndexes,
+        Registry::BuiltRangeIndexes,
+        Registry::NullIndexes,
+        Registry::BuiltNullIndexes,
+    ];
+
+    /// Constructs the key of the set of the fields in this registry for the given collection
+    pub(crate) fn generate_key(&self, collection_name: &str) -> String {
+        match self {
+            Registry::Cardinality => utils::generate_cardinality_fields_key(collection_name),
+            Registry::RangeIndexes => {
+                utils::generate_auxiliary_key(collection_name, "range_indexes", "fields")
+            }
+            Registry::BuiltRangeIndexes => {
+                utils::generate_auxiliary_key(collection_name, "range_indexes", "built")
+            }
+            Registry::NullIndexes => {
+                utils::generate_auxiliary_key(collection_name, "null_indexes", "fields")
+            }
+            Registry::BuiltNullIndexes => {
+                utils::generate_auxiliary_key(collection_name, "null_indexes", "built")
+            }
+        }
+    }
+
+    /// Generates the command that adds the given field to this registry for the given collection,
+    /// replying whether the field was not in it yet
+    pub(crate) fn generate_register_cmd(&self, collection_name: &str, field: &str) -> redis::Cmd {
+        let mut cmd = redis::cmd("SADD");
+        cmd.arg(self.generate_key(collection_name)).arg(field);
+        cmd
+    }
+}
+
+/// The registered fields of a collection as last read from redis, and when they were read
+#[derive(Default)]
+struct Snapshot {
+    fields: HashMap<Registry, Vec<String>>,
+    read_at: Option<Instant>,
+}
+
+/// The registered fields of a collection as last read from redis, read again by `utils::refresh_registered_fields()`
+/// once they are older than `REFRESH_INTERVAL`. It is shared by all collections got from the same store
+#[derive(Clone, Default)]
+pub(crate) struct RegisteredFields {
+    snapshot: Arc<RwLock<Snapshot>>,
+}
+
+impl RegisteredFields {
+    /// Whether the registered fields should be read from redis again before they are used
+    pub(crate) fn is_stale(&self) -> bool {
+        let snapshot = self.snapshot.read().unwrap_or_else(|e| e.into_inner());
+        snapshot
+            .read_at
+            .map_or(true, |read_at| read_at.elapsed() >= REFRESH_INTERVAL)
+    }
+
+    /// Gets the fields in the given registry
+    pub(crate) fn get(&self, registry: Registry) -> Vec<String> {
+        let snapshot = self.snapshot.read().unwrap_or_else(|e| e.into_inner());
+        snapshot.fields.get(&registry).cloned().unwrap_or_default()
+    }
+
+    /// Whether the given field is in the given registry
+    pub(crate) fn contains(&self, registry: Registry, field: &str) -> bool {
+        let snapshot = self.snapshot.read().unwrap_or_else(|e| e.into_inner());
+        snapshot
+            .fields
+            .get(&registry)
+            .map_or(false, |v| v.iter().any(|f| f == field))
+    }
+
+    /// Whether the given registry has any fields
+    pub(crate) fn has_any(&self, registry: Registry) -> bool {
+        let snapshot = self.snapshot.read().unwrap_or_else(|e| e.into_inner());
+        snapshot
+            .fields
+            .get(&registry)
+            .map_or(false, |v| !v.is_empty())
+    }
+
+    /// Adds the given field to the given registry once it has been added to the registry in redis,
+    /// so that the writes of this client take it into account without waiting for the next refresh
+    pub(crate) fn add(&self, registry: Registry, field: &str) {
+        let mut snapshot = self.snapshot.write().unwrap_or_else(|e| e.into_inner());
+        let fields = snapshot.fields.entry(registry).or_default();
+        if !fields.iter().any(|v| v == field) {
+            fields.push(field.to_string());
+        }
+    }
+
+    /// Replaces the registered fields with those read by the pipeline of `generate_refresh_pipeline()`
+    pub(crate) fn update(&self, fields: Vec<Vec<String>>) {
+        let mut snapshot = self.snapshot.write().unwrap_or_else(|e| e.into_inner());
+        snapshot.fields = Registry::ALL.into_iter().zip(fields).collect();
+        snapshot.read_at = Some(Instant::now());
+    }
+}
+
+/// Constructs the keys of all the registries of the given collection
+pub(crate) fn generate_keys(collection_name: &str) -> Vec<String> {
+    Registry::ALL
+        .into_iter()
+        .map(|registry| registry.generate_key(collection_name))
+        .collect()
+}
+
+/// Generates the pipeline that reads the fields in all the registries of the given collection,
+/// each sorted so that the fields are used in the same order by all clients
+pub(crate) fn generate_refresh_pipeline(collection_name: &str) -> redis::Pipeline {
+    let mut pipe = redis::pipe();
+    for registry in Registry::ALL {
+        pipe.cmd("SORT")
+            .arg(registry.generate_key(collection_name))
+            .arg("ALPHA");
+    }
+    pipe
+}
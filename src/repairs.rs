@@ -0,0 +1,162 @@
+use std::collections::HashMap;
+
+use chrono::{DateTime, NaiveDateTime, TimeZone, Utc};
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+
+use crate::field_types::FieldType;
+use crate::parsers;
+use crate::schema::Schema;
+
+pub(crate) const REPAIR_RECORD_SCRIPT: &str = r"local repaired = 0 local n = tonumber(ARGV[1]) for i = 2, 2 * n, 2 do if redis.call('HGET', KEYS[1], ARGV[i]) == ARGV[i + 1] then redis.call('HDEL', KEYS[1], ARGV[i]) repaired = 1 end end for i = 2 * n + 2, #ARGV, 3 do if redis.call('HGET', KEYS[1], ARGV[i]) == ARGV[i + 1] then redis.call('HSET', KEYS[1], ARGV[i], ARGV[i + 2]) repaired = 1 end end return repaired";
+/// The number of records read and repaired in each round trip to redis
+pub(crate) const REPAIR_BATCH_SIZE: usize = 500;
+
+/// The formats of datetimes with a utc offset, other than the one orredis writes, that are repaired.
+/// A "Z" suffix is read as a "+00:00" offset
+const LEGACY_DATETIME_FORMATS: [&str; 2] = ["%Y-%m-%dT%H:%M:%S%.f%z", "%Y-%m-%d %H:%M:%S%.f%z"];
+/// The formats of datetimes without a utc offset that are repaired. They are taken to be in UTC
+const LEGACY_NAIVE_DATETIME_FORMATS: [&str; 3] = [
+    "%Y-%m-%dT%H:%M:%S%.f",
+    "%Y-%m-%d %H:%M:%S%.f",
+    "%Y-%m-%d %H:%M",
+];
+
+/// A known pattern of broken or legacy values saved in redis, that repair() rewrites in the format
+/// orredis writes
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum Repair {
+    /// Booleans in any casing other than lowercase e.g. "True" or "FALSE" are lowercased, for bool fields
+    BoolCase,
+    /// Datetimes in ISO 8601 formats e.g. "2022-10-01T08:30:00Z", or without a utc offset, are rewritten
+    /// as UTC datetimes in the format orredis writes, for datetime fields. Those without an offset are taken to be in UTC
+    DatetimeFormat,
+    /// "None", "null", "nil" in any casing, and empty strings are removed so that the fields get their defaults
+    /// when read, for fields that are neither strings nor nested models
+    NoneSentinel,
+}
+
+impl Repair {
+    /// Gets the repair of the given name
+    pub(crate) fn from_name(name: &str) -> PyResult<Self> {
+        match name {
+            "bool_case" => Ok(Self::BoolCase),
+            "datetime_format" => Ok(Self::DatetimeFormat),
+            "none_sentinel" => Ok(Self::NoneSentinel),
+            _ => Err(PyValueError::new_err(format!(
+                "unknown fix {:?}. Expected one of \"bool_case\", \"datetime_format\" or \"none_sentinel\"",
+                name
+            ))),
+        }
+    }
+
+    /// Gets the repaired value of a field of the given type whose value saved in redis is the given one,
+    /// if it is in the broken format this repair handles. The repaired value is None if the field is to be removed
+    fn apply(&self, type_: &FieldType, value: &str) -> Option<Option<String>> {
+        match self {
+            Self::BoolCase if matches!(type_, FieldType::Bool) => {
+                let lowercase = value.to_lowercase();
+                let is_bool = lowercase == "true" || lowercase == "false";
+                (is_bool && lowercase != value).then_some(Some(lowercase))
+            }
+            Self::DatetimeFormat if matches!(type_, FieldType::Datetime) => {
+                repair_datetime(value).map(Some)
+            }
+            Self::NoneSentinel if !matches!(type_, FieldType::Str) && !type_.is_nested() => {
+                matches!(value.to_lowercase().as_str(), "none" | "null" | "nil" | "")
+                    .then_some(None)
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Gets the repairs of the given names, or all repairs if no names are given
+pub(crate) fn extract_repairs(names: Option<Vec<String>>) -> PyResult<Vec<Repair>> {
+    match names {
+        None => Ok(vec![
+            Repair::BoolCase,
+            Repair::DatetimeFormat,
+            Repair::NoneSentinel,
+        ]),
+        Some(names) => names.iter().map(|name| Repair::from_name(name)).collect(),
+    }
+}
+
+/// Generates the command that applies the given repairs to the given fields of the record of the given key,
+/// or None if none of the fields is broken. Each field is repaired by the first of the repairs that handles its value.
+/// Fields that are changed after they were read are left as they are
+pub(crate) fn generate_repair_cmd(
+    key: &str,
+    schema: &Schema,
+    repairs: &[Repair],
+    fields: &HashMap<String, String>,
+) -> Option<redis::Cmd> {
+    let mut removed: Vec<(&str, &str)> = vec![];
+    let mut rewritten: Vec<(&str, &str, String)> = vec![];
+    for (field, value) in fields {
+        let type_ = match schema.get_type(field) {
+            Some(type_) => type_,
+            None => continue,
+        };
+
+        match repairs.iter().find_map(|repair| repair.apply(type_, value)) {
+            Some(Some(repaired)) => rewritten.push((field, value, repaired)),
+            Some(None) => removed.push((field, value)),
+            None => {}
+        }
+    }
+
+    if removed.is_empty() && rewritten.is_empty() {
+        return None;
+    }
+
+    let mut cmd = redis::cmd("EVAL");
+    cmd.arg(REPAIR_RECORD_SCRIPT)
+        .arg(1)
+        .arg(key)
+        .arg(removed.len());
+    for (field, value) in removed {
+        cmd.arg(field).arg(value);
+    }
+    for (field, value, repaired) in rewritten {
+        cmd.arg(field).arg(value).arg(repaired);
+    }
+    Some(cmd)
+}
+
+/// Rewrites a datetime saved in one of the legacy formats as a UTC datetime in the format orredis writes
+/// i.e. that of python's str(), or returns None if it is not in a legacy format
+fn repair_datetime(value: &str) -> Option<String> {
+    if parsers::parse_datetime_to_timestamp(value).is_ok() {
+        return None;
+    }
+
+    let value = value.trim();
+    let with_offset = match value.strip_suffix(['Z', 'z']) {
+        Some(v) => format!("{}+00:00", v),
+        None => value.to_string(),
+    };
+    let datetime = LEGACY_DATETIME_FORMATS
+        .iter()
+        .find_map(|f| DateTime::parse_from_str(&with_offset, f).ok())
+        .map(|v| v.with_timezone(&Utc))
+        .or_else(|| {
+            LEGACY_NAIVE_DATETIME_FORMATS
+                .iter()
+                .find_map(|f| NaiveDateTime::parse_from_str(value, f).ok())
+                .map(|v| Utc.from_utc_datetime(&v))
+        })?;
+
+    // python leaves out the fraction of seconds if it is zero
+    let micros = datetime.timestamp_subsec_micros();
+    if micros == 0 {
+        Some(datetime.format("%Y-%m-%d %H:%M:%S+00:00").to_string())
+    } else {
+        Some(format!(
+            "{}.{:06}+00:00",
+            datetime.format("%Y-%m-%d %H:%M:%S"),
+            micros
+        ))
+    }
+}
@@ -0,0 +1,76 @@
+use std::time::Duration;
+
+use chrono::{Timelike, Utc};
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+
+use crate::expiry::Expiry;
+use crate::field_types::FieldType;
+use crate::schema::Schema;
+
+pub(crate) const REMOVE_RECORDS_OLDER_THAN_SCRIPT: &str = r"local result = redis.call('SCAN', ARGV[1], 'MATCH', ARGV[3], 'COUNT', ARGV[2]) local removed = 0 for _, key in ipairs(result[2]) do if redis.call('TYPE', key).ok == 'hash' then local value = redis.call('HGET', key, ARGV[4]) if value then local is_expired = false if ARGV[6] == 'number' then local num = tonumber(value) is_expired = num ~= nil and num < tonumber(ARGV[5]) else is_expired = value < ARGV[5] end if is_expired then redis.call('DEL', key) removed = removed + 1 end end end end return {result[1], #result[2], removed}";
+pub(crate) const CAP_RECORDS_TTL_SCRIPT: &str = r"local result = redis.call('SCAN', ARGV[1], 'MATCH', ARGV[3], 'COUNT', ARGV[2]) local max_ttl = tonumber(ARGV[4]) local capped = 0 for _, key in ipairs(result[2]) do if redis.call('TYPE', key).ok == 'hash' then local ttl = redis.call('PTTL', key) if ttl == -1 or ttl > max_ttl then redis.call('PEXPIRE', key, max_ttl) capped = capped + 1 end end end return {result[1], #result[2], capped}";
+
+/// The policy for how long records of a given collection are kept in redis
+#[derive(Clone, Debug)]
+pub(crate) struct RetentionPolicy {
+    pub(crate) period: Duration,
+    pub(crate) field: Option<(String, FieldType)>,
+}
+
+impl RetentionPolicy {
+    /// Creates a new retention policy for the given period, validating that the timestamp field,
+    /// if any, is a date, datetime, int or float field of the schema
+    pub(crate) fn new(period: Duration, field: Option<String>, schema: &Schema) -> PyResult<Self> {
+        let field = match field {
+            None => None,
+            Some(field) => match schema.get_type(&field) {
+                Some(
+                    type_ @ (FieldType::Datetime
+                    | FieldType::Date
+                    | FieldType::Int
+                    | FieldType::Float),
+                ) => Some((field, type_.clone())),
+                _ => {
+                    return Err(PyValueError::new_err(format!(
+                        "retention_field {:?} should be a date, datetime, int or float field of the model",
+                        field
+                    )))
+                }
+            },
+        };
+
+        Ok(Self { period, field })
+    }
+
+    /// Caps the given ttl to the retention period if the policy has no timestamp field,
+    /// in which case a record's age is measured from the time it was last written
+    pub(crate) fn cap_ttl(&self, ttl: Option<Expiry>) -> Option<Expiry> {
+        match self.field {
+            Some(_) => ttl,
+            None => Some(ttl.map_or(Expiry::After(self.period), |v| v.min(self.period))),
+        }
+    }
+
+    /// Returns the (cutoff, kind of comparison) pair such that records whose timestamp field
+    /// is less than the cutoff are older than this policy allows
+    pub(crate) fn get_cutoff(&self) -> Option<(String, &'static str)> {
+        let (_, type_) = self.field.as_ref()?;
+        let period = chrono::Duration::from_std(self.period).unwrap_or(chrono::Duration::zero());
+        let cutoff = Utc::now() - period;
+
+        match type_ {
+            // the same format as python's str(datetime) for UTC datetimes
+            FieldType::Datetime if cutoff.nanosecond() / 1000 == 0 => Some((
+                cutoff.format("%Y-%m-%d %H:%M:%S+00:00").to_string(),
+                "string",
+            )),
+            FieldType::Datetime => Some((
+                cutoff.format("%Y-%m-%d %H:%M:%S%.6f+00:00").to_string(),
+                "string",
+            )),
+            FieldType::Date => Some((cutoff.format("%Y-%m-%d").to_string(), "string")),
+            _ => Some((cutoff.timestamp().to_string(), "number")),
+        }
+    }
+}
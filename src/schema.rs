@@ -1,31 +1,36 @@
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt;
+use std::sync::{Arc, RwLock};
 
-use pyo3::exceptions::PyValueError;
+use once_cell::sync::OnceCell;
+use pyo3::exceptions::{PyKeyError, PyNameError, PyValueError};
 use pyo3::prelude::*;
-use pyo3::types::{PyDict, PyType};
+use pyo3::types::{PyDict, PyString, PyType};
 
-use crate::field_types::FieldType;
+use crate::field_types::{FieldType, NestedModel};
 
 #[derive(Clone, Debug)]
 pub(crate) struct Schema {
     pub mapping: HashMap<String, FieldType>,
+    /// The interned python strings of the names of the fields, used as the keys of the records read
+    field_names: OnceCell<HashMap<String, Py<PyString>>>,
 }
 
 impl Schema {
-    pub(crate) fn from_py_schema(
-        ob: Py<PyAny>,
-        primary_key_field_map: &HashMap<String, String>,
-        model_type_map: &HashMap<String, Py<PyType>>,
-    ) -> PyResult<Self> {
+    /// Creates a new schema of the given fields
+    pub(crate) fn new(mapping: HashMap<String, FieldType>) -> Self {
+        Self {
+            mapping,
+            field_names: OnceCell::new(),
+        }
+    }
+
+    pub(crate) fn from_py_schema(ob: Py<PyAny>, context: &SchemaContext) -> PyResult<Self> {
         Python::with_gil(|py| {
             let ob = ob.into_py(py);
             let ob: &PyDict = ob.extract(py)?;
             if let Some(props) = ob.get_item("properties") {
-                let definitions: HashMap<String, Py<PyAny>> = match ob.get_item("definitions") {
-                    None => Default::default(),
-                    Some(def) => def.extract()?,
-                };
-                Schema::from_py_any(props, &definitions, primary_key_field_map, model_type_map)
+                Schema::from_py_any(props, context)
             } else {
                 Err(PyValueError::new_err(
                     "Invalid schema. No 'properties' found",
@@ -34,12 +39,47 @@ impl Schema {
         })
     }
 
+    /// Refines the types of the fields of the given model that their JSON schema, from which this schema was
+    /// extracted, does not tell apart i.e. tuples of any length e.g. `Tuple[int, ...]`, whose JSON schema is that
+    /// of a list, and the keys of dictionaries e.g. `Dict[int, str]`, which are strings in JSON
+    pub(crate) fn refine_with_model_fields(&mut self, model: &PyAny) -> PyResult<()> {
+        let py = model.py();
+        let var_tuple_shape =
+            PyModule::import(py, "pydantic.fields")?.getattr("SHAPE_TUPLE_ELLIPSIS")?;
+        let fields: &PyDict = model.getattr("__fields__")?.downcast()?;
+        for field in fields.values() {
+            // the properties of the schema are keyed by the aliases of the fields
+            let alias: String = field.getattr("alias")?.extract()?;
+            let type_ = match self.mapping.get_mut(&alias) {
+                None => continue,
+                Some(type_) => type_,
+            };
+
+            match type_ {
+                FieldType::List { items } if field.getattr("shape")?.eq(var_tuple_shape)? => {
+                    *type_ = FieldType::VarTuple {
+                        items: items.clone(),
+                    };
+                }
+                FieldType::Dict { key, .. } => {
+                    let key_field = field.getattr("key_field")?;
+                    if !key_field.is_none() {
+                        **key = FieldType::from_py_key_type(key_field.getattr("type_")?)?;
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        Ok(())
+    }
+
     /// Extracts all nested fields in this schema instance
     pub(crate) fn extract_nested_fields(&self) -> Vec<String> {
         self.mapping
             .iter()
             .filter_map(|(k, v)| {
-                if let FieldType::Nested { .. } = v {
+                if v.is_nested() {
                     Some(k.to_string())
                 } else {
                     None
@@ -48,27 +88,129 @@ impl Schema {
             .collect()
     }
 
+    /// Extracts the fields marked as counters i.e. with `counter=True` in the given python schema,
+    /// ensuring that they are int or float fields of this schema
+    pub(crate) fn extract_counter_fields(&self, ob: &PyAny) -> PyResult<Vec<String>> {
+        let ob: &PyDict = ob.downcast()?;
+        let props: &PyDict = match ob.get_item("properties") {
+            None => return Ok(vec![]),
+            Some(props) => props.downcast()?,
+        };
+
+        let mut counter_fields: Vec<String> = Vec::new();
+        for (key, value) in props.iter() {
+            let value: &PyDict = value.downcast()?;
+            let is_counter = match value.get_item("counter") {
+                None => false,
+                Some(v) => v.extract()?,
+            };
+
+            if is_counter {
+                let key: String = key.extract()?;
+                match self.get_type(&key) {
+                    Some(FieldType::Int | FieldType::Float) => counter_fields.push(key),
+                    _ => {
+                        return Err(PyValueError::new_err(format!(
+                            "counter field {:?} should be an int or float field",
+                            key
+                        )))
+                    }
+                }
+            }
+        }
+
+        Ok(counter_fields)
+    }
+
+    /// Gets a copy of this schema in which the nested fields requested only through the dotted paths among
+    /// the given fields e.g. "address.city", are projections of those paths. The projections are read as
+    /// instances of their models if as_models is true, otherwise as dictionaries
+    pub(crate) fn with_projections(&self, fields: &[String], as_models: bool) -> PyResult<Self> {
+        let paths: Vec<&str> = fields
+            .iter()
+            .filter(|f| matches!(f.split_once('.'), Some((head, _)) if !fields.iter().any(|v| v == head)))
+            .map(|f| f.as_str())
+            .collect();
+        let mut mapping = self.mapping.clone();
+        mapping.extend(self.project(&paths, as_models)?.mapping);
+        Ok(Self::new(mapping))
+    }
+
+    /// Gets the schema of only the fields at the given paths, in which nested fields requested
+    /// through dotted paths are projections of those paths
+    fn project(&self, paths: &[&str], as_models: bool) -> PyResult<Self> {
+        let mut mapping: HashMap<String, FieldType> = HashMap::new();
+        let mut nested_paths: BTreeMap<&str, Vec<&str>> = BTreeMap::new();
+        for path in paths {
+            match path.split_once('.') {
+                Some((head, rest)) => nested_paths.entry(head).or_default().push(rest),
+                None => {
+                    mapping.insert(path.to_string(), self.get_existing_type(path)?.clone());
+                }
+            }
+        }
+
+        for (field, paths) in nested_paths {
+            // the whole nested record is requested
+            if mapping.contains_key(field) {
+                continue;
+            }
+
+            let model = self
+                .get_existing_type(field)?
+                .get_nested_model()?
+                .ok_or_else(|| {
+                    PyValueError::new_err(format!(
+                        "{:?} is not a nested field so it has no fields",
+                        field
+                    ))
+                })?;
+            let schema = model.schema.project(&paths, as_models)?;
+            mapping.insert(
+                field.to_string(),
+                FieldType::Projection {
+                    model,
+                    schema: Box::new(schema),
+                    as_model: as_models,
+                },
+            );
+        }
+
+        Ok(Self::new(mapping))
+    }
+
+    /// Gets the FieldType of the given field, failing if it is not in this schema
+    fn get_existing_type(&self, field_name: &str) -> PyResult<&FieldType> {
+        self.get_type(field_name).ok_or_else(|| {
+            PyKeyError::new_err(format!("{:?} is not a field of the model", field_name))
+        })
+    }
+
     /// Gets the FieldType corresponding to the given field_name
     #[inline]
     pub(crate) fn get_type(&self, field_name: &str) -> Option<&FieldType> {
         self.mapping.get(field_name)
     }
 
-    /// Creates an empty schema
-    pub(crate) fn empty() -> Self {
-        Self {
-            mapping: Default::default(),
+    /// Gets the interned python string of the given field name. Those of all the fields are made once
+    /// so that reading many records does not make a new string for every field of every record
+    pub(crate) fn get_field_name(&self, py: Python, field_name: &str) -> Py<PyString> {
+        let field_names = self.field_names.get_or_init(|| {
+            self.mapping
+                .keys()
+                .map(|k| (k.clone(), PyString::intern(py, k).into()))
+                .collect()
+        });
+
+        match field_names.get(field_name) {
+            Some(name) => name.clone_ref(py),
+            None => PyString::intern(py, field_name).into(),
         }
     }
 
     /// Converts a PyAny dictionary like object into a schema. e.g.
     ///  {'title': 'A', 'type': 'object', 'properties': {'height': {'title': 'Height', 'type': 'integer'}}
-    pub(crate) fn from_py_any(
-        props: &PyAny,
-        definitions: &HashMap<String, Py<PyAny>>,
-        primary_key_field_map: &HashMap<String, String>,
-        model_type_map: &HashMap<String, Py<PyType>>,
-    ) -> PyResult<Self> {
+    pub(crate) fn from_py_any(props: &PyAny, context: &SchemaContext) -> PyResult<Self> {
         let props: &PyDict = props.downcast()?;
         let keys = props.keys();
         let mapping = keys
@@ -76,15 +218,219 @@ impl Schema {
             .map(|key| {
                 let value = props.get_item(key).unwrap();
                 let key: String = key.extract()?;
-                let value: FieldType = FieldType::extract_from_py_schema(
-                    value,
-                    definitions,
-                    primary_key_field_map,
-                    model_type_map,
-                )?;
+                let value: FieldType = FieldType::extract_from_py_schema(value, context)?;
                 Ok((key, value))
             })
             .collect::<PyResult<HashMap<String, FieldType>>>()?;
-        Ok(Self { mapping })
+        Ok(Self::new(mapping))
+    }
+}
+
+/// The models of the collections of a store, by the names with which schemas refer to them.
+/// It is shared by the store and all its collections so that fields referring to models whose schemas
+/// were still being extracted e.g. self-referencing models, can get their models when they are used
+#[derive(Clone, Default)]
+pub(crate) struct SchemaRegistry {
+    models: Arc<RwLock<HashMap<String, Arc<NestedModel>>>>,
+}
+
+impl SchemaRegistry {
+    /// Adds the model of a collection under the name with which schemas refer to it
+    pub(crate) fn register(&self, name: String, model: NestedModel) {
+        self.models
+            .write()
+            .unwrap_or_else(|e| e.into_inner())
+            .insert(name, Arc::new(model));
+    }
+
+    /// Gets the model of the given name if its collection was created
+    pub(crate) fn find(&self, name: &str) -> Option<Arc<NestedModel>> {
+        self.models
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .get(name)
+            .cloned()
     }
+
+    /// Gets the model of the given name, failing if its collection was never created
+    pub(crate) fn get(&self, name: &str) -> PyResult<Arc<NestedModel>> {
+        self.find(name).ok_or_else(|| {
+            PyKeyError::new_err(format!(
+                "nested model {} has no collection. Try to create the {} collection first",
+                name, name
+            ))
+        })
+    }
+}
+
+// The models may refer to themselves through this registry so only their names are printed
+impl fmt::Debug for SchemaRegistry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let models = self.models.read().unwrap_or_else(|e| e.into_inner());
+        f.debug_set().entries(models.keys()).finish()
+    }
+}
+
+/// What is needed to resolve the models nested in the schema being extracted
+pub(crate) struct SchemaContext<'a> {
+    pub(crate) registry: &'a SchemaRegistry,
+    /// The names of the models whose collections are being created. Fields holding these models
+    /// are references, which breaks reference cycles
+    resolving: Vec<String>,
+}
+
+impl<'a> SchemaContext<'a> {
+    pub(crate) fn new(registry: &'a SchemaRegistry, resolving: Vec<String>) -> Self {
+        Self {
+            registry,
+            resolving,
+        }
+    }
+
+    /// Whether the collection of the model of the given name is being created
+    pub(crate) fn is_resolving(&self, model_name: &str) -> bool {
+        self.resolving.iter().any(|v| v == model_name)
+    }
+}
+
+/// Resolves the string type hints of the given model and of the models nested in it e.g. those in modules
+/// using `from __future__ import annotations` or those referring to models defined after them. The hints are
+/// resolved against the modules of the models and the models of the store's collections
+pub(crate) fn resolve_forward_refs(
+    model: &PyType,
+    model_type_map: &HashMap<String, Py<PyType>>,
+) -> PyResult<()> {
+    let py = model.py();
+    let localns = PyDict::new(py);
+    for model_type in model_type_map.values() {
+        let model_type = model_type.as_ref(py);
+        localns.set_item(model_type.getattr("__name__")?, model_type)?;
+    }
+
+    let mut seen: HashSet<String> = HashSet::new();
+    update_forward_refs(model, localns, &mut seen)
+}
+
+/// Updates the forward references of the given model and, depth first, of the models nested in it
+fn update_forward_refs(
+    model: &PyType,
+    localns: &PyDict,
+    seen: &mut HashSet<String>,
+) -> PyResult<()> {
+    let py = model.py();
+    let model_name: String = model.getattr("__qualname__")?.extract()?;
+    if !seen.insert(model_name.clone()) {
+        return Ok(());
+    }
+
+    localns.set_item(model.getattr("__name__")?, model)?;
+    model
+        .call_method("update_forward_refs", (), Some(localns))
+        .map_err(|e| {
+            if e.is_instance_of::<PyNameError>(py) {
+                PyKeyError::new_err(format!(
+                    "the type hints of {} could not be resolved: {}. Define the models it refers to in its module \
+                    or create their collections first",
+                    model_name,
+                    e.value(py)
+                ))
+            } else {
+                e
+            }
+        })?;
+
+    let base_model = PyModule::import(py, "pydantic")?.getattr("BaseModel")?;
+    let fields: &PyDict = model.getattr("__fields__")?.downcast()?;
+    for field in fields.values() {
+        if let Ok(type_) = field.getattr("type_")?.downcast::<PyType>() {
+            if type_.is_subclass(base_model)? {
+                update_forward_refs(type_, localns, seen)?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Finds the models nested in the given model, directly or indirectly, that have no collection yet,
+/// ordered such that each model comes after those nested in it. Each comes with its primary key field,
+/// which is got from the model's schema: the `primary_key_field` key of its schema extra, or the field
+/// marked with `primary_key=True`
+pub(crate) fn find_unregistered_nested_models(
+    model: &PyType,
+    primary_key_field_map: &HashMap<String, String>,
+) -> PyResult<Vec<(Py<PyType>, String)>> {
+    let mut nested_models: Vec<(Py<PyType>, String)> = Vec::new();
+    // the model itself is skipped when it nests itself, directly or indirectly
+    let mut seen: HashSet<String> = HashSet::from([model.getattr("__qualname__")?.extract()?]);
+    collect_unregistered_nested_models(
+        model,
+        primary_key_field_map,
+        &mut seen,
+        &mut nested_models,
+    )?;
+    Ok(nested_models)
+}
+
+/// Adds the unregistered models nested in the given model to the given list, depth first
+fn collect_unregistered_nested_models(
+    model: &PyType,
+    primary_key_field_map: &HashMap<String, String>,
+    seen: &mut HashSet<String>,
+    nested_models: &mut Vec<(Py<PyType>, String)>,
+) -> PyResult<()> {
+    let py = model.py();
+    let base_model = PyModule::import(py, "pydantic")?.getattr("BaseModel")?;
+    let fields: &PyDict = model.getattr("__fields__")?.downcast()?;
+
+    for field in fields.values() {
+        let nested_model = match field.getattr("type_")?.downcast::<PyType>() {
+            Ok(type_) if type_.is_subclass(base_model)? => type_,
+            _ => continue,
+        };
+        let model_name: String = nested_model.getattr("__qualname__")?.extract()?;
+        if primary_key_field_map.contains_key(&model_name) || !seen.insert(model_name) {
+            continue;
+        }
+
+        collect_unregistered_nested_models(
+            nested_model,
+            primary_key_field_map,
+            seen,
+            nested_models,
+        )?;
+        let primary_key_field = extract_primary_key_field(nested_model)?;
+        nested_models.push((nested_model.into(), primary_key_field));
+    }
+
+    Ok(())
+}
+
+/// Extracts the primary key field of the given model from its schema
+fn extract_primary_key_field(model: &PyType) -> PyResult<String> {
+    let schema: &PyDict = model.call_method0("schema")?.downcast()?;
+    if let Some(field) = schema.get_item("primary_key_field") {
+        return field.extract();
+    }
+
+    if let Some(props) = schema.get_item("properties") {
+        let props: &PyDict = props.downcast()?;
+        for (key, value) in props.iter() {
+            let value: &PyDict = value.downcast()?;
+            if let Some(true) = value
+                .get_item("primary_key")
+                .map(|v| v.is_true())
+                .transpose()?
+            {
+                return key.extract();
+            }
+        }
+    }
+
+    let model_name: String = model.getattr("__qualname__")?.extract()?;
+    Err(PyKeyError::new_err(format!(
+        "no primary key found for nested model {}. Mark one of its fields with `primary_key=True` \
+        or create the {} collection first",
+        model_name, model_name
+    )))
 }
@@ -0,0 +1,73 @@
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+
+use crate::utils;
+use crate::views::CLEAR_VIEW_SCRIPT;
+
+const SNAPSHOT_RECORDS_SCRIPT: &str = r"local table_unpack = table.unpack or unpack local result = redis.call('SCAN', ARGV[1], 'MATCH', ARGV[3], 'COUNT', ARGV[2]) local count = 0 for _, key in ipairs(result[2]) do if redis.call('TYPE', key).ok == 'hash' then local fields = redis.call('HGETALL', key) if #fields > 0 then redis.call('HSET', ARGV[5] .. string.sub(key, #ARGV[4] + 1), table_unpack(fields)) count = count + 1 end end end return {result[1], #result[2], count}";
+
+/// The characters a snapshot tag cannot have since they would make the key pattern of its snapshot
+/// match the keys of other snapshots
+const FORBIDDEN_TAG_CHARS: [char; 6] = ['*', '?', '[', ']', '\\', '%'];
+
+/// Ensures the given snapshot tag is not empty and has none of the forbidden characters
+pub(crate) fn validate_tag(tag: &str) -> PyResult<()> {
+    if tag.is_empty() || tag.contains(FORBIDDEN_TAG_CHARS) {
+        return Err(PyValueError::new_err(format!(
+            "snapshot tag should be non-empty and have none of the characters {:?}, got {:?}",
+            FORBIDDEN_TAG_CHARS.iter().collect::<String>(),
+            tag
+        )));
+    }
+
+    Ok(())
+}
+
+/// Gets the name of the collection-like key space in which the snapshot of the given tag of the given
+/// collection is saved
+#[inline]
+pub(crate) fn get_key_space(collection_name: &str, tag: &str) -> String {
+    utils::generate_auxiliary_key(collection_name, "snapshot", tag)
+}
+
+/// Generates the command that removes the keys of the snapshot of the given tag in the SCAN batch
+/// of the given count at the given cursor, before it is taken again or when it is deleted
+pub(crate) fn generate_clear_cmd(
+    collection_name: &str,
+    tag: &str,
+    cursor: &str,
+    count: usize,
+) -> redis::Cmd {
+    let mut cmd = redis::cmd("EVAL");
+    cmd.arg(CLEAR_VIEW_SCRIPT)
+        .arg(0)
+        .arg(cursor)
+        .arg(count)
+        .arg(utils::generate_collection_key_pattern(&get_key_space(
+            collection_name,
+            tag,
+        )));
+    cmd
+}
+
+/// Generates the command that copies the records of the collection in the SCAN batch of the given
+/// count at the given cursor into the snapshot of the given tag
+pub(crate) fn generate_copy_cmd(
+    collection_name: &str,
+    tag: &str,
+    cursor: &str,
+    count: usize,
+) -> redis::Cmd {
+    let mut cmd = redis::cmd("EVAL");
+    cmd.arg(SNAPSHOT_RECORDS_SCRIPT)
+        .arg(0)
+        .arg(cursor)
+        .arg(count)
+        .arg(utils::generate_collection_key_pattern(collection_name))
+        .arg(utils::generate_key_prefix(collection_name))
+        .arg(utils::generate_key_prefix(&get_key_space(
+            collection_name,
+            tag,
+        )));
+    cmd
+}
@@ -2,25 +2,88 @@ extern crate pyo3;
 extern crate r2d2;
 extern crate redis;
 
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet, VecDeque};
 use std::ops::DerefMut;
+use std::sync::atomic;
 use std::time::Duration;
 
-use pyo3::exceptions::{PyConnectionError, PyKeyError};
+use async_std::task;
+use pyo3::exceptions::{PyConnectionError, PyKeyError, PyValueError};
 use pyo3::prelude::*;
-use pyo3::types::PyType;
+use pyo3::types::{PyDict, PyType};
 
-use crate::schema::Schema;
+use crate::aggregations::{self, AggregateOp};
+use crate::background::BackgroundTasks;
+use crate::backup_schedules::{self, BackupJob, BackupPool, BackupSchedules};
+use crate::backups::BackupTarget;
+use crate::batching::{BatchSizer, InsertRecords};
+use crate::bloom_filters::{self, BloomFilter};
+use crate::caches::{Cache, KeyValueNamespace};
+use crate::circuit_breakers::CircuitBreaker;
+use crate::coercions::{self, Coercion, CoercionRules};
+use crate::compaction;
+use crate::compression::{self, Compression};
+use crate::conditional_writes;
+use crate::constraints::{self, ConstraintRules};
+use crate::converters::ConverterTable;
+use crate::defaults::CollectionDefaults;
+use crate::descriptions;
+use crate::endpoints;
+use crate::expiry::Expiry;
+use crate::fake_data::{FakeRecords, FAKE_BATCH_SIZE};
+use crate::field_indexes;
+use crate::field_types::NestedModel;
+use crate::hot_keys::HotKeys;
+use crate::id_generators::IdGenerator;
+use crate::links::LinkDefinition;
+use crate::list_indexes;
+use crate::lookups::Lookups;
+use crate::metrics::Metrics;
+use crate::migrations::MigrationPlan;
+use crate::mirrors::Mirrors;
+use crate::nested_updates;
+use crate::normalizers::Normalizers;
+use crate::null_indexes;
+use crate::options::{CollectionOptions, StoreOptions};
+use crate::pagination::{self, BrowseCursor, KeysetCursor, Ordering, Page};
+use crate::pools;
+use crate::profiling::{self, Phase, Profile};
+use crate::range_indexes;
+use crate::read_errors::OnError;
+use crate::registries::{RegisteredFields, Registry};
+use crate::repairs;
+use crate::retention::RetentionPolicy;
+use crate::schema::{self, Schema, SchemaContext, SchemaRegistry};
+use crate::snapshots;
+use crate::throttle::Throttle;
+use crate::ttl_audit::TtlThresholds;
+use crate::unique_fields;
 use crate::utils;
+use crate::vectors;
+use crate::verification;
+use crate::versioning::{self, Versioning};
+use crate::views::ViewDefinition;
+use crate::write_buffer::WriteBuffer;
 
 #[pyclass(subclass)]
 pub(crate) struct Store {
     collections_meta: HashMap<String, CollectionMeta>,
     primary_key_field_map: HashMap<String, String>,
     model_type_map: HashMap<String, Py<PyType>>,
-    pool: r2d2::Pool<redis::Client>,
-    default_ttl: Option<u64>,
+    pool: pools::RedisPool,
+    default_ttl: Option<Expiry>,
+    defaults: CollectionDefaults,
     is_in_use: bool,
+    views: HashMap<String, (String, ViewDefinition)>,
+    links: HashMap<String, LinkDefinition>,
+    client: redis::Client,
+    mirrors: Mirrors,
+    write_buffer: WriteBuffer,
+    max_batch_bytes: usize,
+    schema_registry: SchemaRegistry,
+    pending_models: Vec<String>,
+    backup_schedules: BackupSchedules,
+    background_tasks: BackgroundTasks,
 }
 
 #[derive(Clone)]
@@ -30,49 +93,118 @@ pub(crate) struct CollectionMeta {
     pub(crate) model_type: Py<PyType>,
     pub(crate) primary_key_field: String,
     pub(crate) nested_fields: Vec<String>,
+    pub(crate) id_generator: Option<IdGenerator>,
+    pub(crate) retention: Option<RetentionPolicy>,
+    pub(crate) bloom_filter: Option<BloomFilter>,
+    pub(crate) bloom_filtered_collections: HashSet<String>,
+    pub(crate) counter_fields: Vec<String>,
+    pub(crate) views: Vec<ViewDefinition>,
+    pub(crate) track_changes: bool,
+    pub(crate) track_insertion_order: bool,
+    pub(crate) case_insensitive_keys: bool,
+    pub(crate) mirrors: Mirrors,
+    pub(crate) write_buffer: WriteBuffer,
+    pub(crate) coercions: CoercionRules,
+    pub(crate) versioning: Option<Versioning>,
+    pub(crate) record_versions: HashMap<String, u32>,
+    pub(crate) nested_depth: usize,
+    pub(crate) metrics: Metrics,
+    pub(crate) converters: ConverterTable,
+    pub(crate) hot_keys: Option<HotKeys>,
+    pub(crate) namespace: Option<String>,
+    pub(crate) foreign_keys: Vec<String>,
+    pub(crate) indexed_fields: Vec<String>,
+    pub(crate) indexed_list_fields: Vec<String>,
+    pub(crate) compound_indexes: Vec<Vec<String>>,
+    pub(crate) unique_fields: Vec<String>,
+    pub(crate) normalizers: Normalizers,
+    pub(crate) constraints: ConstraintRules,
+    pub(crate) batch_sizer: BatchSizer,
+    pub(crate) compression: Compression,
+    pub(crate) registered_fields: RegisteredFields,
+    pub(crate) background_tasks: BackgroundTasks,
 }
 
 #[pymethods]
 impl Store {
-    /// Initializes the Store
+    /// Initializes the Store, with the options other than those of its pool given as keyword arguments
+    /// e.g. write_buffer or lazy
     #[args(
         url,
         pool_size = 5,
         default_ttl = "None",
         timeout = "None",
-        max_lifetime = "None"
+        max_lifetime = "None",
+        options = "**"
     )]
     #[new]
     pub fn new(
+        py: Python,
         url: String,
         pool_size: u32,
-        default_ttl: Option<u64>,
+        default_ttl: Option<&PyAny>,
         timeout: Option<u64>,
         max_lifetime: Option<u64>,
+        options: Option<&PyDict>,
     ) -> PyResult<Self> {
-        let client =
-            redis::Client::open(url).map_err(|e| PyConnectionError::new_err(e.to_string()))?;
-        let mut pool = r2d2::Pool::builder().max_size(pool_size);
+        let default_ttl = default_ttl.map(Expiry::from_py).transpose()?;
+        let StoreOptions {
+            defaults,
+            write_buffer,
+            write_buffer_overflow,
+            circuit_breaker,
+            blocking_pool_size,
+            max_batch_bytes,
+            lazy,
+            ..
+        } = StoreOptions::from_py(options, false)?;
+        let client = endpoints::open_client(py, &url)?;
+        let write_buffer = WriteBuffer::new(client.clone(), write_buffer, &write_buffer_overflow)?;
 
-        if let Some(timeout) = timeout {
-            pool = pool.connection_timeout(Duration::from_millis(timeout));
-        }
+        let build_pool = |size: u32| {
+            let mut pool = r2d2::Pool::builder().max_size(size);
 
-        if let Some(max_lifetime) = max_lifetime {
-            pool = pool.max_lifetime(Some(Duration::from_millis(max_lifetime)));
-        }
+            if let Some(timeout) = timeout {
+                pool = pool.connection_timeout(Duration::from_millis(timeout));
+            }
 
-        let pool = pool
-            .build(client)
-            .map_err(|e| PyConnectionError::new_err(e.to_string()))?;
+            if let Some(max_lifetime) = max_lifetime {
+                pool = pool.max_lifetime(Some(Duration::from_millis(max_lifetime)));
+            }
+
+            // a lazy pool opens its connections as they are needed, so redis need not be up yet
+            match lazy {
+                true => Ok(pool.build_unchecked(client.clone())),
+                false => pool
+                    .build(client.clone())
+                    .map_err(|e| PyConnectionError::new_err(e.to_string())),
+            }
+        };
+        let pool = pools::RedisPool::new(
+            build_pool(pool_size)?,
+            blocking_pool_size.map(build_pool).transpose()?,
+            circuit_breaker,
+        );
 
+        let background_tasks = BackgroundTasks::default();
         Ok(Store {
             collections_meta: Default::default(),
             pool,
             default_ttl,
+            defaults,
             primary_key_field_map: Default::default(),
             model_type_map: Default::default(),
             is_in_use: false,
+            views: Default::default(),
+            links: Default::default(),
+            client,
+            mirrors: Default::default(),
+            write_buffer,
+            max_batch_bytes,
+            schema_registry: Default::default(),
+            pending_models: Default::default(),
+            backup_schedules: BackupSchedules::new(background_tasks.clone()),
+            background_tasks,
         })
     }
 
@@ -80,10 +212,7 @@ impl Store {
     #[args(asynchronous = "false")]
     #[pyo3(text_signature = "($self, asynchronous)")]
     pub fn clear(&mut self, asynchronous: bool) -> PyResult<()> {
-        let mut conn = self
-            .pool
-            .get()
-            .map_err(|e| PyConnectionError::new_err(e.to_string()))?;
+        let mut conn = pools::get_connection(&self.pool)?;
         let arg = if asynchronous { "ASYNC" } else { "SYNC" };
 
         redis::cmd("FLUSHALL")
@@ -92,36 +221,313 @@ impl Store {
             .or_else(|e| Err(PyConnectionError::new_err(e.to_string())))
     }
 
-    /// Creates a new collection for the given model and adds it to the store instance
+    /// Measures the round-trip latency of `samples` PINGs to redis on one connection of the pool, returning
+    /// its statistics and the time the connection took to check out of the pool, all in milliseconds
+    #[args(samples = 10)]
+    #[pyo3(text_signature = "($self, samples)")]
+    pub fn latency(&self, samples: usize) -> PyResult<Py<PyAny>> {
+        if samples == 0 {
+            return Err(PyValueError::new_err("samples should be at least 1"));
+        }
+
+        utils::measure_latency(&self.pool, samples)?.to_py()
+    }
+
+    /// Checks out a connection and PINGs redis on it, failing with a ConnectionError if redis cannot be reached
+    /// e.g. on the startup of an app whose store was created with `lazy=True`
+    pub fn connect(&self) -> PyResult<()> {
+        let mut conn = pools::get_connection(&self.pool)?;
+        redis::cmd("PING")
+            .query(conn.deref_mut())
+            .map_err(|e| PyConnectionError::new_err(e.to_string()))
+    }
+
+    /// Closes this store and all its collections, cancelling its background tasks and scheduled backups. Any later
+    /// call that needs a connection to redis fails with a ConnectionError. The connections are closed once they
+    /// are dropped
+    pub fn close(&self, py: Python) -> PyResult<()> {
+        self.pool.close();
+        self.backup_schedules.cancel_all(py)?;
+        self.background_tasks.cancel_all(py)
+    }
+
+    /// Returns the counts of the calls of each operation on each collection of this store,
+    /// and of the records they read or wrote, since the collections were created
+    pub fn metrics(&self) -> PyResult<HashMap<String, Py<PyAny>>> {
+        self.collections_meta
+            .iter()
+            .map(|(name, meta)| Ok((name.clone(), meta.metrics.to_py()?)))
+            .collect()
+    }
+
+    /// Describes the state of the circuit breaker of this store, set with `circuit_breaker_threshold`,
+    /// or returns None if it has none
+    pub fn circuit_breaker(&self) -> PyResult<Option<Py<PyAny>>> {
+        self.pool
+            .circuit_breaker
+            .as_ref()
+            .map(CircuitBreaker::to_py)
+            .transpose()
+    }
+
+    /// Counts the records of each collection of this store that have no ttl, whose ttl is below the threshold
+    /// (an hour by default), and whose ttl is below the imminent expiry (a minute by default). Both are
+    /// timedeltas or numbers of seconds. At most `max_ops_per_sec` records are scanned per second if given
+    #[args(threshold = "None", imminent = "None", max_ops_per_sec = "None")]
+    pub fn ttl_audit(
+        &self,
+        py: Python,
+        threshold: Option<&PyAny>,
+        imminent: Option<&PyAny>,
+        max_ops_per_sec: Option<f64>,
+    ) -> PyResult<HashMap<String, Py<PyAny>>> {
+        let thresholds = TtlThresholds::from_py(threshold, imminent)?;
+        let mut throttle = Throttle::new(max_ops_per_sec)?;
+        // the GIL is released so that other threads are not blocked while the scan is throttled
+        let audits = py.allow_threads(|| {
+            self.collections_meta
+                .keys()
+                .map(|name| {
+                    let audit = utils::audit_ttls(&self.pool, name, &thresholds, &mut throttle)?;
+                    Ok((name.clone(), audit))
+                })
+                .collect::<PyResult<Vec<_>>>()
+        })?;
+        audits
+            .into_iter()
+            .map(|(name, audit)| Ok((name, audit.to_py()?)))
+            .collect()
+    }
+
+    /// Applies the retention policies of all collections, removing records that are older than their
+    /// policy allows. It returns the number of records affected in each collection that has a retention policy.
+    /// At most `max_ops_per_sec` records are scanned per second if given
+    #[args(max_ops_per_sec = "None")]
+    pub fn apply_retention(
+        &self,
+        py: Python,
+        max_ops_per_sec: Option<f64>,
+    ) -> PyResult<HashMap<String, u64>> {
+        let mut throttle = Throttle::new(max_ops_per_sec)?;
+        // the GIL is released so that other threads are not blocked while the scan is throttled
+        py.allow_threads(|| {
+            self.collections_meta
+                .iter()
+                .filter_map(|(name, meta)| meta.retention.as_ref().map(|policy| (name, policy)))
+                .map(|(name, policy)| {
+                    Ok((
+                        name.clone(),
+                        utils::apply_retention(&self.pool, name, policy, &mut throttle)?,
+                    ))
+                })
+                .collect()
+        })
+    }
+
+    /// Backs all the collections created so far up every `interval`, a timedelta, a number of seconds or
+    /// a cron-like shorthand e.g. "@daily", into a new directory under `target`, a local directory or a url
+    /// e.g. "s3://bucket/path", named after the time of the backup. Each collection is exported as by
+    /// `Collection.backup()` into a file named after it. Only the `keep` latest backups made by the schedule are kept.
+    /// The backups are made in a background task until `cancel_backup_schedule()` is called, and their status
+    /// is got with `backup_schedules()`
+    #[args(interval, target, keep = 7, chunk_size = 1000)]
+    pub fn schedule_backup(
+        &self,
+        py: Python,
+        interval: &PyAny,
+        target: String,
+        keep: usize,
+        chunk_size: usize,
+    ) -> PyResult<()> {
+        let interval = backup_schedules::extract_interval(interval)?;
+        let job = BackupJob {
+            pool: BackupPool::Sync(self.pool.clone()),
+            collections: self
+                .collections_meta
+                .iter()
+                .map(|(name, meta)| (name.clone(), meta.clone()))
+                .collect(),
+            target,
+            chunk_size,
+        };
+        self.backup_schedules.spawn(py, job, interval, keep)
+    }
+
+    /// Returns the status of each of the scheduled backups of this store by target
+    pub fn backup_schedules(&self, py: Python) -> PyResult<Py<PyAny>> {
+        self.backup_schedules.to_py(py)
+    }
+
+    /// Cancels the scheduled backup into the given target, returning whether there was such a schedule
+    #[pyo3(text_signature = "($self, target)")]
+    pub fn cancel_backup_schedule(&self, py: Python, target: &str) -> PyResult<bool> {
+        self.backup_schedules.cancel(py, target)
+    }
+
+    /// Returns the status of each of the background tasks of this store by name e.g. the compactions
+    /// started with `compact(background=True)`
+    pub fn background_tasks(&self, py: Python) -> PyResult<Py<PyAny>> {
+        self.background_tasks.to_py(py)
+    }
+
+    /// Cancels the background task of the given name, returning whether there was such a task
+    #[pyo3(text_signature = "($self, task_name)")]
+    pub fn cancel_background(&self, py: Python, task_name: &str) -> PyResult<bool> {
+        self.background_tasks.cancel(py, task_name)
+    }
+
+    /// Adds the redis instance of the given url as a mirror to which every write made through this
+    /// store is also applied, in the background and on a best-effort basis
+    #[pyo3(text_signature = "($self, url)")]
+    pub fn add_mirror(&self, py: Python, url: &str) -> PyResult<()> {
+        self.mirrors.add(py, url)
+    }
+
+    /// Gets the number of writes made while redis was unreachable that are still waiting in the write
+    /// buffer to be applied. It is always 0 if the store has no write buffer
+    #[pyo3(text_signature = "($self)")]
+    pub fn pending_writes(&self) -> usize {
+        self.write_buffer.pending()
+    }
+
+    /// Takes the errors of the buffered writes that failed when they were applied once redis was reachable
+    /// again e.g. because they exceeded a quota, and were thus dropped, oldest first
+    #[pyo3(text_signature = "($self)")]
+    pub fn take_failed_writes(&self) -> Vec<String> {
+        self.write_buffer.take_failures()
+    }
+
+    /// Sets the limits of the quota of the given namespace i.e. the maximum number of records in the collections
+    /// created with that namespace and the maximum total length of their field names and values. The limits not
+    /// given are removed. Quotas are saved in redis so that they apply to all stores of the namespace
+    #[args(namespace, max_records = "None", max_bytes = "None")]
+    #[pyo3(text_signature = "($self, namespace, max_records, max_bytes)")]
+    pub fn set_quota(
+        &self,
+        namespace: &str,
+        max_records: Option<u64>,
+        max_bytes: Option<u64>,
+    ) -> PyResult<()> {
+        utils::set_quota(&self.pool, namespace, max_records, max_bytes)
+    }
+
+    /// Gets the usage of the quota of the given namespace, with its limits
+    #[pyo3(text_signature = "($self, namespace)")]
+    pub fn get_quota(&self, namespace: &str) -> PyResult<Py<PyAny>> {
+        utils::get_quota(&self.pool, namespace)?.to_py()
+    }
+
+    /// Compares a random sample of the records of the collection of the given model in this store
+    /// with those in the other store field by field, returning a report of the drift between them.
+    /// Records are sampled from both stores with a probability of `sample` each
+    #[args(other, collection, sample = "0.01")]
+    pub fn verify_against(
+        &self,
+        other: PyRef<Store>,
+        collection: Py<PyType>,
+        sample: f64,
+    ) -> PyResult<Py<PyAny>> {
+        let sample = verification::validate_sample_rate(sample)?;
+        let model_name: String =
+            Python::with_gil(|py| collection.getattr(py, "__qualname__")?.extract(py))?;
+        let meta = self.collections_meta.get(&model_name).ok_or_else(|| {
+            PyKeyError::new_err(format!(
+                "{} has not yet been created on the store",
+                model_name
+            ))
+        })?;
+
+        let ids = verification::merge_sampled_ids(
+            utils::sample_record_ids(&self.pool, &model_name, sample)?,
+            utils::sample_record_ids(&other.pool, &model_name, sample)?,
+        );
+        let records = utils::get_raw_records(&self.pool, &model_name, meta, &ids)?;
+        let other_records = utils::get_raw_records(&other.pool, &model_name, meta, &ids)?;
+        verification::generate_drift_report(&model_name, meta, &ids, &records, &other_records)
+    }
+
+    /// Describes the collections, views and links of this store i.e. the fields of each collection with their
+    /// types, its indexes and its relationships to other collections, e.g. to render admin UIs
+    #[pyo3(text_signature = "($self)")]
+    pub fn describe(&self) -> PyResult<Py<PyAny>> {
+        descriptions::describe_store(&self.collections_meta, &self.views, &self.links)
+    }
+
+    /// Gets a page of at most `limit` records of the collection of the given model as dictionaries of only
+    /// the given fields, or of all fields if none are given, e.g. those a user of an admin panel may see.
+    /// It returns a dictionary of the "records", the "total" number of records in the collection and the
+    /// "next_cursor" to pass to get the next page, None if this is the last page. The first page is got
+    /// without a cursor. Records are in the order in which SCAN finds them
+    #[args(collection, cursor = "None", limit = 20, fields = "None")]
+    pub fn browse(
+        &self,
+        collection: Py<PyType>,
+        cursor: Option<&str>,
+        limit: usize,
+        fields: Option<Vec<String>>,
+    ) -> PyResult<Py<PyAny>> {
+        let model_name: String =
+            Python::with_gil(|py| collection.getattr(py, "__qualname__")?.extract(py))?;
+        let meta = self.collections_meta.get(&model_name).ok_or_else(|| {
+            PyKeyError::new_err(format!(
+                "{} has not yet been created on the store",
+                model_name
+            ))
+        })?;
+        let cursor = BrowseCursor::parse(cursor)?;
+        let limit = pagination::validate_browse_limit(limit)?;
+        let fields = pagination::resolve_browse_fields(meta, fields)?;
+
+        let (page, records) =
+            utils::browse_records(&self.pool, &model_name, meta, cursor, limit, &fields)?;
+        meta.metrics.record("browse", records.len());
+        page.to_py(records)
+    }
+
+    /// Compares the schema of the collection of the given model with the snapshot of it last saved by
+    /// save_schema_snapshot(), returning the plan of the migration of the records from the saved schema
+    #[pyo3(text_signature = "($self, model)")]
+    pub fn plan_migration(&self, model: Py<PyType>) -> PyResult<Py<PyAny>> {
+        let model_name: String =
+            Python::with_gil(|py| model.getattr(py, "__qualname__")?.extract(py))?;
+        let meta = self.collections_meta.get(&model_name).ok_or_else(|| {
+            PyKeyError::new_err(format!(
+                "{} has not yet been created on the store",
+                model_name
+            ))
+        })?;
+
+        let snapshot = utils::get_schema_snapshot(&self.pool, &model_name)?;
+        MigrationPlan::new(&meta.schema, snapshot).to_py(&model_name)
+    }
+
+    /// Saves the snapshot of the current schema of the collection of the given model in redis, replacing
+    /// the one plan_migration() compares with. It is meant to be called once the records are migrated
+    #[pyo3(text_signature = "($self, model)")]
+    pub fn save_schema_snapshot(&self, model: Py<PyType>) -> PyResult<()> {
+        let model_name: String =
+            Python::with_gil(|py| model.getattr(py, "__qualname__")?.extract(py))?;
+        let meta = self.collections_meta.get(&model_name).ok_or_else(|| {
+            PyKeyError::new_err(format!(
+                "{} has not yet been created on the store",
+                model_name
+            ))
+        })?;
+
+        utils::save_schema_snapshot(&self.pool, &model_name, meta)
+    }
+
+    /// Creates a new collection for the given model and adds it to the store instance, with the options given
+    /// as keyword arguments e.g. id_generator or indexed_fields
+    #[args(model, primary_key_field, options = "**")]
     pub(crate) fn create_collection(
         &mut self,
         model: Py<PyType>,
         primary_key_field: String,
+        options: Option<&PyDict>,
     ) -> PyResult<()> {
-        if self.is_in_use {
-            return Err(PyConnectionError::new_err(
-                "a call to 'create_collection()' cannot come after a call to 'get_collection()'.",
-            ));
-        }
-
-        Python::with_gil(|py| {
-            let schema = model.getattr(py, "schema")?.call0(py)?;
-            let schema =
-                Schema::from_py_schema(schema, &self.primary_key_field_map, &self.model_type_map)?;
-            let nested_fields = schema.extract_nested_fields();
-            let model_name: String = model.getattr(py, "__qualname__")?.extract(py)?;
-            let meta = CollectionMeta::new(
-                Box::new(schema),
-                model.clone(),
-                primary_key_field.clone(),
-                nested_fields,
-            );
-            self.collections_meta.insert(model_name.clone(), meta);
-            self.primary_key_field_map
-                .insert(model_name.clone(), primary_key_field);
-            self.model_type_map.insert(model_name, model);
-            Ok(())
-        })
+        let options = CollectionOptions::from_py(options, &self.defaults)?;
+        self.add_collection(model, primary_key_field, options)
     }
 
     /// Instantiates an independent collection from the store for the given model
@@ -144,130 +550,1547 @@ impl Store {
             )))
         }
     }
+
+    /// Creates a materialized view of the collection of the given model i.e. the records that match
+    /// the filter, with only the fields in the projection. The view is kept up to date on every write
+    /// to the collection
+    #[args(name, source_collection, projection, filter = "None")]
+    pub(crate) fn create_view(
+        &mut self,
+        name: String,
+        source_collection: Py<PyType>,
+        projection: Vec<String>,
+        filter: Option<HashMap<String, Py<PyAny>>>,
+    ) -> PyResult<()> {
+        if self.is_in_use {
+            return Err(PyConnectionError::new_err(
+                "a call to 'create_view()' cannot come after a call to 'get_collection()' or 'get_view()'.",
+            ));
+        }
+
+        if self.views.contains_key(&name) {
+            return Err(PyValueError::new_err(format!(
+                "view {:?} already exists",
+                name
+            )));
+        }
+
+        let model_name: String =
+            Python::with_gil(|py| source_collection.getattr(py, "__qualname__")?.extract(py))?;
+        let meta = self.collections_meta.get_mut(&model_name).ok_or_else(|| {
+            PyKeyError::new_err(format!(
+                "{} has not yet been created on the store",
+                model_name
+            ))
+        })?;
+
+        let view = ViewDefinition::from_py(name.clone(), meta, projection, filter)?;
+        meta.views.push(view.clone());
+        self.views.insert(name, (model_name, view));
+        Ok(())
+    }
+
+    /// Instantiates a read-only handle on the view of the given name
+    pub(crate) fn get_view(&mut self, name: &str) -> PyResult<View> {
+        if let Some((source, view)) = self.views.get(name) {
+            self.is_in_use = true;
+            let meta = &self.collections_meta[source];
+            Ok(View::new(
+                source.clone(),
+                view.clone(),
+                view.generate_read_meta(meta)?,
+                self.pool.clone(),
+            ))
+        } else {
+            Err(PyKeyError::new_err(format!(
+                "view {:?} has not yet been created on the store",
+                name
+            )))
+        }
+    }
+
+    /// Creates a many-to-many link between the records of the collections of the given models e.g. students
+    /// and courses. Unlike views, links can be created at any time since they do not change the collections
+    pub(crate) fn create_link(
+        &mut self,
+        name: String,
+        left_collection: Py<PyType>,
+        right_collection: Py<PyType>,
+    ) -> PyResult<()> {
+        if self.links.contains_key(&name) {
+            return Err(PyValueError::new_err(format!(
+                "link {:?} already exists",
+                name
+            )));
+        }
+
+        let (left, right) = Python::with_gil(|py| -> PyResult<(String, String)> {
+            Ok((
+                left_collection.getattr(py, "__qualname__")?.extract(py)?,
+                right_collection.getattr(py, "__qualname__")?.extract(py)?,
+            ))
+        })?;
+        for model_name in [&left, &right] {
+            if !self.collections_meta.contains_key(model_name) {
+                return Err(PyKeyError::new_err(format!(
+                    "{} has not yet been created on the store",
+                    model_name
+                )));
+            }
+        }
+
+        self.links
+            .insert(name.clone(), LinkDefinition::new(name, left, right));
+        Ok(())
+    }
+
+    /// Instantiates a handle on the link of the given name
+    pub(crate) fn get_link(&self, name: &str) -> PyResult<Link> {
+        if let Some(link) = self.links.get(name) {
+            Ok(Link::new(
+                link.clone(),
+                self.collections_meta[&link.left].clone(),
+                self.collections_meta[&link.right].clone(),
+                self.pool.clone(),
+            ))
+        } else {
+            Err(PyKeyError::new_err(format!(
+                "link {:?} has not yet been created on the store",
+                name
+            )))
+        }
+    }
+
+    /// Instantiates a binary-safe key-value cache whose keys start with the given prefix, sharing the pool
+    /// of the collections of this store e.g. as the cache or session backend of a web framework
+    #[args(prefix = "\"\"")]
+    pub(crate) fn get_cache(&self, prefix: &str) -> Cache {
+        Cache::new(self.pool.clone(), prefix.to_string())
+    }
+
+    /// Instantiates a raw key-value namespace whose keys are the given namespace followed by the keys given
+    /// to it, sharing the pool of the collections of this store e.g. as the result backend of Celery
+    pub(crate) fn kv(&self, namespace: &str) -> KeyValueNamespace {
+        KeyValueNamespace::new(self.pool.clone(), namespace.to_string())
+    }
+}
+
+impl Store {
+    /// Creates the collection of the given model with the given options, and those of its nested models
+    /// if they are to be registered with it
+    fn add_collection(
+        &mut self,
+        model: Py<PyType>,
+        primary_key_field: String,
+        options: CollectionOptions,
+    ) -> PyResult<()> {
+        if self.is_in_use {
+            return Err(PyConnectionError::new_err(
+                "a call to 'create_collection()' cannot come after a call to 'get_collection()'.",
+            ));
+        }
+
+        Python::with_gil(|py| {
+            schema::resolve_forward_refs(model.as_ref(py), &self.model_type_map)
+        })?;
+
+        // schemas refer to models by their names, not their qualified names
+        let schema_name: String =
+            Python::with_gil(|py| model.getattr(py, "__name__")?.extract(py))?;
+
+        if options.register_nested_models {
+            let nested_models = Python::with_gil(|py| {
+                schema::find_unregistered_nested_models(
+                    model.as_ref(py),
+                    &self.primary_key_field_map,
+                )
+            })?;
+            // the nested models may refer back to this model, whose collection does not exist yet
+            self.pending_models.push(schema_name.clone());
+            let result =
+                nested_models
+                    .into_iter()
+                    .try_for_each(|(nested_model, primary_key_field)| {
+                        let nested_options = options.for_nested_model(&self.defaults);
+                        self.add_collection(nested_model, primary_key_field, nested_options)
+                    });
+            self.pending_models.pop();
+            result?;
+        }
+
+        let enforce_constraints = options.enforce_constraints;
+        Python::with_gil(|py| {
+            let py_schema = model.getattr(py, "schema")?.call0(py)?;
+            let mut resolving = self.pending_models.clone();
+            resolving.push(schema_name.clone());
+            let context = SchemaContext::new(&self.schema_registry, resolving);
+            let mut schema = Schema::from_py_schema(py_schema.clone_ref(py), &context)?;
+            schema.refine_with_model_fields(model.as_ref(py))?;
+            let nested_fields = schema.extract_nested_fields();
+            let counter_fields = schema.extract_counter_fields(py_schema.as_ref(py))?;
+            let model_name: String = model.getattr(py, "__qualname__")?.extract(py)?;
+            let mut meta = CollectionMeta::new(
+                Box::new(schema),
+                model.clone(),
+                primary_key_field.clone(),
+                nested_fields,
+                counter_fields,
+                options,
+            )?;
+            meta.bloom_filtered_collections = bloom_filters::get_bloom_filtered_collections(
+                &model_name,
+                &meta,
+                &self.collections_meta,
+            );
+            if enforce_constraints {
+                meta.constraints =
+                    constraints::extract_constraint_rules(py_schema.as_ref(py), &meta.schema)?;
+            }
+            meta.record_versions =
+                versioning::get_record_versions(&model_name, &meta, &self.collections_meta);
+            meta.mirrors = self.mirrors.clone();
+            meta.write_buffer = self.write_buffer.clone();
+            meta.background_tasks = self.background_tasks.clone();
+            meta.batch_sizer = BatchSizer::new(self.max_batch_bytes);
+            compression::register_source(&model_name, &self.client);
+            self.schema_registry.register(
+                schema_name,
+                NestedModel {
+                    model_name: model_name.clone(),
+                    schema: (*meta.schema).clone(),
+                    primary_key_field: primary_key_field.clone(),
+                    model_type: model.clone(),
+                    case_insensitive_keys: meta.case_insensitive_keys,
+                },
+            );
+            self.collections_meta.insert(model_name.clone(), meta);
+            self.primary_key_field_map
+                .insert(model_name.clone(), primary_key_field);
+            self.model_type_map.insert(model_name, model);
+            Ok(())
+        })
+    }
 }
 
 impl CollectionMeta {
-    /// Instantiates a new collection meta
+    /// Instantiates a new collection meta with the given options, validating them against the schema
     pub(crate) fn new(
         schema: Box<Schema>,
         model_type: Py<PyType>,
         primary_key_field: String,
         nested_fields: Vec<String>,
-    ) -> Self {
-        CollectionMeta {
+        counter_fields: Vec<String>,
+        options: CollectionOptions,
+    ) -> PyResult<Self> {
+        let retention = match (options.retention, options.retention_field) {
+            (None, None) => None,
+            (Some(period), field) => Some(RetentionPolicy::new(period, field, &schema)?),
+            (None, Some(_)) => {
+                return Err(PyValueError::new_err(
+                    "retention_field cannot be set without a retention period",
+                ))
+            }
+        };
+        let coercions = coercions::extract_coercion_rules(options.coercions, &schema)?;
+        let converters = ConverterTable::new(&schema, &coercions);
+        let mut meta = CollectionMeta {
             schema,
             model_type,
             primary_key_field,
             nested_fields,
-        }
+            id_generator: options.id_generator,
+            retention,
+            bloom_filter: options.bloom_filter.then(BloomFilter::default),
+            bloom_filtered_collections: Default::default(),
+            counter_fields,
+            views: Default::default(),
+            track_changes: options.track_changes,
+            track_insertion_order: options.track_insertion_order,
+            case_insensitive_keys: options.case_insensitive_keys,
+            mirrors: Default::default(),
+            write_buffer: Default::default(),
+            coercions,
+            versioning: options.versioning,
+            record_versions: Default::default(),
+            nested_depth: options.nested_depth,
+            metrics: Default::default(),
+            converters,
+            hot_keys: options.hot_keys,
+            namespace: options.namespace,
+            foreign_keys: options.foreign_keys,
+            indexed_fields: Default::default(),
+            indexed_list_fields: options.indexed_list_fields,
+            compound_indexes: options.compound_indexes,
+            unique_fields: options.unique_fields,
+            normalizers: Default::default(),
+            constraints: Default::default(),
+            batch_sizer: Default::default(),
+            compression: Default::default(),
+            registered_fields: Default::default(),
+            background_tasks: Default::default(),
+        };
+
+        meta.indexed_fields =
+            field_indexes::merge_indexed_fields(&meta.foreign_keys, options.indexed_fields);
+        field_indexes::validate_indexed_fields(&meta, &meta.indexed_fields)?;
+        field_indexes::validate_compound_indexes(&meta, &meta.compound_indexes)?;
+        list_indexes::validate_indexed_list_fields(&meta, &meta.indexed_list_fields)?;
+        unique_fields::validate_unique_fields(&meta, &meta.unique_fields)?;
+        meta.normalizers = Normalizers::new(&meta, options.index_normalizers)?;
+        Ok(meta)
     }
-}
 
-#[pyclass(subclass)]
-pub(crate) struct Collection {
-    pub(crate) name: String,
-    pub(crate) meta: CollectionMeta,
-    pub(crate) pool: r2d2::Pool<redis::Client>,
-    pub(crate) default_ttl: Option<u64>,
-}
+    /// Sets the coercion rules applied when the fields of this collection are read
+    pub(crate) fn set_coercions(&mut self, coercions: CoercionRules) {
+        self.converters = ConverterTable::new(&self.schema, &coercions);
+        self.coercions = coercions;
+    }
 
-#[pymethods]
-impl Collection {
-    /// inserts one model instance into the redis store for this collection
-    pub(crate) fn add_one(&self, item: Py<PyAny>, ttl: Option<u64>) -> PyResult<()> {
-        let records = utils::prepare_record_to_insert(
-            &self.name,
-            &self.meta.schema,
-            &item,
-            &self.meta.primary_key_field,
-            None,
-        )?;
-        let ttl = match ttl {
-            None => self.default_ttl,
-            Some(v) => Some(v),
-        };
-        utils::insert_records(&self.pool, &records, &ttl)
+    /// Ensures the given field is a non-nested field of this collection's schema
+    pub(crate) fn ensure_scalar_field(&self, field: &str) -> PyResult<()> {
+        match self.schema.get_type(field) {
+            None => Err(PyKeyError::new_err(format!(
+                "{:?} is not a field of the model",
+                field
+            ))),
+            Some(type_) if type_.is_nested() => Err(PyValueError::new_err(format!(
+                "{:?} is a nested field yet only non-nested fields are supported",
+                field
+            ))),
+            Some(_) => Ok(()),
+        }
     }
 
-    /// Inserts many model instances into the redis store for this collection all in a batch.
-    /// This is more efficient than repeatedly calling add_one() because only one network request is made to redis
-    pub(crate) fn add_many(&self, items: Vec<Py<PyAny>>, ttl: Option<u64>) -> PyResult<()> {
-        let mut records: Vec<(String, Vec<(String, String)>)> = Vec::with_capacity(2 * items.len());
-        for item in items {
-            let mut records_to_insert = utils::prepare_record_to_insert(
-                &self.name,
-                &self.meta.schema,
-                &item,
-                &self.meta.primary_key_field,
-                None,
-            )?;
-            records.append(&mut records_to_insert);
+    /// Ensures the given field is a counter field of this collection
+    pub(crate) fn ensure_counter_field(&self, field: &str) -> PyResult<()> {
+        if self.counter_fields.iter().any(|v| v == field) {
+            Ok(())
+        } else {
+            Err(PyValueError::new_err(format!(
+                "{:?} is not a counter field of the model",
+                field
+            )))
         }
+    }
 
-        let ttl = match ttl {
-            None => self.default_ttl,
-            Some(v) => Some(v),
-        };
+    /// Converts the given filter, a dictionary of the values the fields of a record should be equal to,
+    /// into pairs of fields and the values as saved in redis. Only non-nested, non-counter fields can be filtered on,
+    /// and none of those compressed if a compression dictionary was trained
+    pub(crate) fn extract_filter(
+        &self,
+        filter: Option<HashMap<String, Py<PyAny>>>,
+    ) -> PyResult<Vec<(String, String)>> {
+        filter
+            .unwrap_or_default()
+            .iter()
+            .map(|(field, value)| {
+                self.ensure_scalar_field(field)?;
+                if self.counter_fields.iter().any(|v| v == field) {
+                    return Err(PyValueError::new_err(format!(
+                        "{:?} is a counter field yet counter fields cannot be filtered on",
+                        field
+                    )));
+                }
+                if self.compression.is_enabled() && compression::is_compressible(self, field) {
+                    return Err(PyValueError::new_err(format!(
+                        "{:?} is compressed yet compressed fields cannot be filtered on. Add it to indexed_fields \
+                        in create_collection() to keep it uncompressed",
+                        field
+                    )));
+                }
 
-        utils::insert_records(&self.pool, &records, &ttl)
+                let type_ = self.schema.get_type(field).unwrap();
+                Ok((field.clone(), utils::field_value_to_redis(type_, value)?))
+            })
+            .collect()
     }
 
-    /// Updates the record of the given id with the provided data
-    pub(crate) fn update_one(&self, id: &str, data: Py<PyAny>, ttl: Option<u64>) -> PyResult<()> {
-        let records = utils::prepare_record_to_insert(
-            &self.name,
-            &self.meta.schema,
-            &data,
-            &self.meta.primary_key_field,
-            Some(id),
-        )?;
+    /// Ensures the given field is a foreign key field of this collection, set in create_collection()
+    pub(crate) fn ensure_foreign_key(&self, field: &str) -> PyResult<()> {
+        if self.foreign_keys.iter().any(|v| v == field) {
+            Ok(())
+        } else {
+            Err(PyValueError::new_err(format!(
+                "{:?} is not a foreign key of the collection. Add it to foreign_keys in create_collection()",
+                field
+            )))
+        }
+    }
 
-        let ttl = match ttl {
-            None => self.default_ttl,
-            Some(v) => Some(v),
-        };
+    /// Ensures the given field is indexed in this collection i.e. it is among the indexed_fields or the foreign_keys
+    /// set in create_collection()
+    pub(crate) fn ensure_indexed_field(&self, field: &str) -> PyResult<()> {
+        if self.indexed_fields.iter().any(|v| v == field) {
+            Ok(())
+        } else {
+            Err(PyValueError::new_err(format!(
+                "{:?} is not indexed in the collection. Add it to indexed_fields in create_collection()",
+                field
+            )))
+        }
+    }
 
-        utils::insert_records(&self.pool, &records, &ttl)
+    /// Ensures that the changes to the records of this collection are recorded in its change stream
+    pub(crate) fn ensure_tracks_changes(&self) -> PyResult<()> {
+        if self.track_changes {
+            Ok(())
+        } else {
+            Err(PyValueError::new_err(
+                "the collection does not track changes. Set track_changes=True in create_collection()",
+            ))
+        }
     }
 
-    /// Deletes the records that correspond to the given ids for this collection
-    pub(crate) fn delete_many(&self, ids: Vec<String>) -> PyResult<()> {
-        let primary_keys: Vec<String> = ids
-            .iter()
-            .map(|id| utils::generate_hash_key(&self.name, id))
-            .collect();
-        utils::remove_records(&self.pool, &primary_keys)
+    /// Ensures that the order in which the records of this collection are inserted is kept in its insertion order index
+    pub(crate) fn ensure_tracks_insertion_order(&self) -> PyResult<()> {
+        if self.track_insertion_order {
+            Ok(())
+        } else {
+            Err(PyValueError::new_err(
+                "the collection does not track insertion order. Set track_insertion_order=True in create_collection()",
+            ))
+        }
     }
 
-    /// Gets the record that corresponds to the given id
-    pub(crate) fn get_one(&self, id: &str) -> PyResult<Py<PyAny>> {
-        let mut records: Vec<Py<PyAny>> =
-            utils::get_records_by_id(&self.pool, &self.name, &self.meta, &vec![id.to_string()])?;
-        match records.pop() {
-            None => Python::with_gil(|py| Ok(py.None())),
-            Some(record) => Ok(record),
+    /// Records an access of each of the given ids, if the hot keys of this collection are tracked
+    pub(crate) fn record_accesses<'a>(&self, ids: impl IntoIterator<Item = &'a str>) {
+        if let Some(hot_keys) = &self.hot_keys {
+            hot_keys.record(ids);
         }
     }
 
-    /// Returns all the records found in this collection; returning them as models
-    pub(crate) fn get_all(&self) -> PyResult<Vec<Py<PyAny>>> {
-        utils::get_all_records_in_collection(&self.pool, &self.name, &self.meta)
+    /// Gets the tracker of the hot keys of this collection
+    pub(crate) fn get_hot_keys(&self) -> PyResult<&HotKeys> {
+        self.hot_keys.as_ref().ok_or_else(|| {
+            PyValueError::new_err(
+                "the collection does not track hot keys. Set hot_key_sampling in create_collection()",
+            )
+        })
     }
 
-    /// Returns the records whose ids are as given for this collection
-    pub(crate) fn get_many(&self, ids: Vec<String>) -> PyResult<Vec<Py<PyAny>>> {
-        utils::get_records_by_id(&self.pool, &self.name, &self.meta, &ids)
+    /// Gets the coercion rules applied when the given field is read
+    pub(crate) fn get_coercions(&self, field: &str) -> &[Coercion] {
+        self.coercions.get(field).map_or(&[], |v| v.as_slice())
     }
 
-    /// Returns the record that corresponds to the given id in this collection
-    /// returning it as a dictionary with only the fields specified
-    pub(crate) fn get_one_partially(&self, id: &str, fields: Vec<String>) -> PyResult<Py<PyAny>> {
-        let mut records: Vec<Py<PyAny>> = utils::get_partial_records_by_id(
-            &self.pool,
-            &self.name,
+    /// Gets the fields whose range indexes are updated on every write, as registered in redis by `create_index()`
+    pub(crate) fn get_range_indexes(&self) -> HashSet<String> {
+        self.registered_fields
+            .get(Registry::RangeIndexes)
+            .into_iter()
+            .collect()
+    }
+
+    /// Gets the fields whose null indexes are updated on every write, as registered in redis by `create_null_index()`
+    pub(crate) fn get_null_indexes(&self) -> Vec<String> {
+        self.registered_fields.get(Registry::NullIndexes)
+    }
+
+    /// Ensures the given field has a null index, created with `create_null_index()`, that has all the records
+    /// of the collection i.e. is not still being built
+    pub(crate) fn ensure_null_index(&self, field: &str) -> PyResult<()> {
+        if self
+            .registered_fields
+            .contains(Registry::BuiltNullIndexes, field)
+        {
+            Ok(())
+        } else if self
+            .registered_fields
+            .contains(Registry::NullIndexes, field)
+        {
+            Err(PyValueError::new_err(format!(
+                "the null index of {:?} is still being built. Try again once create_null_index() returns",
+                field
+            )))
+        } else {
+            Err(PyValueError::new_err(format!(
+                "{:?} has no null index. Call create_null_index() first",
+                field
+            )))
+        }
+    }
+
+    /// Ensures the given field has a range index, created with `create_index()`, that has all the records
+    /// of the collection i.e. is not still being built
+    pub(crate) fn ensure_range_index(&self, field: &str) -> PyResult<()> {
+        if self
+            .registered_fields
+            .contains(Registry::BuiltRangeIndexes, field)
+        {
+            Ok(())
+        } else if self
+            .registered_fields
+            .contains(Registry::RangeIndexes, field)
+        {
+            Err(PyValueError::new_err(format!(
+                "the index of {:?} is still being built. Try again once create_index() returns",
+                field
+            )))
+        } else {
+            Err(no_range_index_error(field))
+        }
+    }
+
+    /// Ensures the given field can have a range index, failing as `ensure_range_index()` does if it cannot.
+    /// It is checked before the registered fields are read again to check that the field has an index
+    pub(crate) fn ensure_range_indexable(&self, field: &str) -> PyResult<()> {
+        range_indexes::ensure_indexable_field(self, field).map_err(|_| no_range_index_error(field))
+    }
+}
+
+/// Constructs the error raised when the given field is used as if it had a range index, yet has none
+fn no_range_index_error(field: &str) -> PyErr {
+    PyValueError::new_err(format!(
+        "{:?} has no index. Call create_index() first",
+        field
+    ))
+}
+
+#[pyclass(subclass)]
+pub(crate) struct Collection {
+    pub(crate) name: String,
+    pub(crate) meta: CollectionMeta,
+    pub(crate) pool: pools::RedisPool,
+    pub(crate) default_ttl: Option<Expiry>,
+}
+
+#[pymethods]
+impl Collection {
+    /// inserts one model instance into the redis store for this collection, returning its primary key e.g.
+    /// the one generated by the id_generator. If profile is true, it returns the timing breakdown of the call
+    /// as (primary key, profile)
+    #[args(item, ttl = "None", profile = "false")]
+    pub(crate) fn add_one(
+        &self,
+        item: Py<PyAny>,
+        ttl: Option<&PyAny>,
+        profile: bool,
+    ) -> PyResult<Py<PyAny>> {
+        let ttl = ttl.map(Expiry::from_py).transpose()?;
+        let profile = Profile::start(profile);
+        let id = profiling::run(&profile, || {
+            let mut records = profiling::measure(Phase::Serialize, || {
+                utils::prepare_record_to_insert(
+                    &self.name,
+                    &self.meta.schema,
+                    &item,
+                    &self.meta.primary_key_field,
+                    None,
+                    &self.meta.id_generator,
+                    self.meta.case_insensitive_keys,
+                )
+            })?;
+            versioning::stamp_versions(&mut records, &self.meta.record_versions);
+            let ttl = utils::resolve_ttl(ttl, self.default_ttl, &self.meta.retention);
+            utils::insert_records(&self.pool, &self.name, &self.meta, &records, &[], &ttl)?;
+            Ok::<_, PyErr>(utils::get_inserted_id(&self.name, &records))
+        })?;
+        self.meta.metrics.record("add_one", 1);
+        profiling::attach(profile, id)
+    }
+
+    /// Inserts one model instance into the redis store for this collection only if no record has its primary key,
+    /// returning whether it was inserted. Of many concurrent calls for the same primary key, only one inserts it,
+    /// so create-only flows need no get_one() before the insert
+    #[args(item, ttl = "None")]
+    pub(crate) fn add_one_if_not_exists(
+        &self,
+        item: Py<PyAny>,
+        ttl: Option<&PyAny>,
+    ) -> PyResult<bool> {
+        let ttl = ttl.map(Expiry::from_py).transpose()?;
+        let mut records = utils::prepare_record_to_insert(
+            &self.name,
+            &self.meta.schema,
+            &item,
+            &self.meta.primary_key_field,
+            None,
+            &self.meta.id_generator,
+            self.meta.case_insensitive_keys,
+        )?;
+        versioning::stamp_versions(&mut records, &self.meta.record_versions);
+        let ttl = utils::resolve_ttl(ttl, self.default_ttl, &self.meta.retention);
+
+        let is_inserted =
+            utils::insert_record_if_missing(&self.pool, &self.name, &self.meta, &records, &ttl)?;
+        self.meta
+            .metrics
+            .record("add_one_if_not_exists", is_inserted as usize);
+        Ok(is_inserted)
+    }
+
+    /// Inserts many model instances into the redis store for this collection in batches of at most the
+    /// `max_batch_bytes` of the store, each written in one network request. This is more efficient than repeatedly
+    /// calling add_one(). It returns their primary keys, in the order of the items. If profile is true,
+    /// it returns the timing breakdown of the call as (primary keys, profile)
+    #[args(items, ttl = "None", profile = "false")]
+    pub(crate) fn add_many(
+        &self,
+        items: Vec<Py<PyAny>>,
+        ttl: Option<&PyAny>,
+        profile: bool,
+    ) -> PyResult<Py<PyAny>> {
+        let ttl = ttl.map(Expiry::from_py).transpose()?;
+        let number_of_items = items.len();
+        let profile = Profile::start(profile);
+        let ids = profiling::run(&profile, || {
+            let mut records: Vec<InsertRecords> = Vec::with_capacity(items.len());
+            for item in items {
+                records.push(profiling::measure(Phase::Serialize, || {
+                    utils::prepare_record_to_insert(
+                        &self.name,
+                        &self.meta.schema,
+                        &item,
+                        &self.meta.primary_key_field,
+                        None,
+                        &self.meta.id_generator,
+                        self.meta.case_insensitive_keys,
+                    )
+                })?);
+            }
+
+            let ttl = utils::resolve_ttl(ttl, self.default_ttl, &self.meta.retention);
+            let ids: Vec<String> = records
+                .iter()
+                .map(|v| utils::get_inserted_id(&self.name, v))
+                .collect();
+
+            for mut batch in self.meta.batch_sizer.split_writes(records) {
+                versioning::stamp_versions(&mut batch, &self.meta.record_versions);
+                utils::insert_records(&self.pool, &self.name, &self.meta, &batch, &[], &ttl)?;
+            }
+            Ok::<_, PyErr>(ids)
+        })?;
+        self.meta.metrics.record("add_many", number_of_items);
+        profiling::attach(profile, ids)
+    }
+
+    /// Inserts `n` records of random but valid values generated from the schema of this collection, with their
+    /// nested records, e.g. for load tests or demos. Records generated with the same seed are the same
+    #[args(n, seed = "None", ttl = "None")]
+    pub(crate) fn generate_fake(
+        &self,
+        py: Python,
+        n: usize,
+        seed: Option<u64>,
+        ttl: Option<&PyAny>,
+    ) -> PyResult<()> {
+        let ttl = ttl.map(Expiry::from_py).transpose()?;
+        let ttl = utils::resolve_ttl(ttl, self.default_ttl, &self.meta.retention);
+        let mut generator = FakeRecords::new(seed, self.meta.nested_depth);
+
+        let mut remaining = n;
+        while remaining > 0 {
+            let batch_size = remaining.min(FAKE_BATCH_SIZE);
+            let records = generator.generate_batch(py, &self.name, &self.meta, batch_size)?;
+            utils::insert_records(&self.pool, &self.name, &self.meta, &records, &[], &ttl)?;
+            remaining -= batch_size;
+        }
+
+        self.meta.metrics.record("generate_fake", n);
+        Ok(())
+    }
+
+    /// Updates the record of the given id with the provided data
+    pub(crate) fn update_one(
+        &self,
+        id: &str,
+        data: Py<PyAny>,
+        ttl: Option<&PyAny>,
+    ) -> PyResult<()> {
+        self.meta.record_accesses([id]);
+        let ttl = ttl.map(Expiry::from_py).transpose()?;
+        let (data, nested_updates) =
+            nested_updates::extract_nested_updates(&self.name, &self.meta, id, data)?;
+        let mut records = utils::prepare_record_to_insert(
+            &self.name,
+            &self.meta.schema,
+            &data,
+            &self.meta.primary_key_field,
+            Some(id),
+            &self.meta.id_generator,
+            self.meta.case_insensitive_keys,
+        )?;
+        // the record being updated keeps its version as only some of its fields may be updated
+        let nested_records = records.len() - 1;
+        versioning::stamp_versions(&mut records[..nested_records], &self.meta.record_versions);
+
+        let ttl = utils::resolve_ttl(ttl, self.default_ttl, &self.meta.retention);
+
+        utils::insert_records(
+            &self.pool,
+            &self.name,
             &self.meta,
-            &vec![id.to_string()],
-            &fields,
+            &records,
+            &nested_updates,
+            &ttl,
+        )?;
+        self.meta.metrics.record("update_one", 1);
+        Ok(())
+    }
+
+    /// Updates the records of the given ids with the provided data, by id, in one network request.
+    /// This is more efficient than repeatedly calling update_one()
+    #[args(updates, ttl = "None")]
+    pub(crate) fn update_many(&self, updates: &PyDict, ttl: Option<&PyAny>) -> PyResult<()> {
+        let ttl = ttl.map(Expiry::from_py).transpose()?;
+        let updates = updates
+            .iter()
+            .map(|(id, data)| Ok((id.extract::<String>()?, data.into())))
+            .collect::<PyResult<Vec<(String, Py<PyAny>)>>>()?;
+        let number_of_updates = updates.len();
+        let (records, nested_updates) =
+            utils::prepare_records_to_update(&self.name, &self.meta, updates)?;
+        let ttl = utils::resolve_ttl(ttl, self.default_ttl, &self.meta.retention);
+
+        utils::insert_records(
+            &self.pool,
+            &self.name,
+            &self.meta,
+            &records,
+            &nested_updates,
+            &ttl,
+        )?;
+        self.meta.metrics.record("update_many", number_of_updates);
+        Ok(())
+    }
+
+    /// Starts maintaining a HyperLogLog of the distinct values of the given field on every write,
+    /// populating it with the values of the records already in this collection, scanning at most
+    /// `max_ops_per_sec` records per second if given
+    #[args(field, max_ops_per_sec = "None")]
+    pub(crate) fn track_cardinality(
+        &self,
+        py: Python,
+        field: &str,
+        max_ops_per_sec: Option<f64>,
+    ) -> PyResult<()> {
+        self.meta.ensure_scalar_field(field)?;
+        let mut throttle = Throttle::new(max_ops_per_sec)?;
+        // the GIL is released so that other threads are not blocked while the scan is throttled
+        py.allow_threads(|| {
+            utils::track_cardinality(&self.pool, &self.name, &self.meta, field, &mut throttle)
+        })
+    }
+
+    /// Returns the approximate number of distinct values the given field has had in this collection
+    pub(crate) fn approx_distinct(&self, field: &str) -> PyResult<u64> {
+        self.meta.ensure_scalar_field(field)?;
+        utils::count_distinct(&self.pool, &self.name, field)
+    }
+
+    /// Returns the distinct values of the given field in this collection, in no particular order. They are
+    /// collected by a script as it scans the collection in redis, so only the distinct values are sent back
+    pub(crate) fn distinct(&self, field: &str) -> PyResult<Vec<Py<PyAny>>> {
+        self.meta.ensure_scalar_field(field)?;
+        self.meta.metrics.record_result(
+            "distinct",
+            utils::get_distinct_values(&self.pool, &self.name, &self.meta, field),
+        )
+    }
+
+    /// Returns the sum, average, minimum or maximum, as op is "sum", "avg", "min" or "max", of the values of
+    /// the given int or float field in this collection. They are aggregated by a script as it scans the collection
+    /// in redis, so only the result is sent back. Records whose value of the field is None are left out
+    pub(crate) fn aggregate(&self, field: &str, op: &str) -> PyResult<Py<PyAny>> {
+        aggregations::ensure_numeric_field(&self.meta, field)?;
+        let op = AggregateOp::from_name(op)?;
+
+        let (count, result) = utils::aggregate_field(&self.pool, &self.name, field, op)?;
+        self.meta.metrics.record("aggregate", count);
+        aggregations::to_py(self.meta.schema.get_type(field).unwrap(), op, &result)
+    }
+
+    /// Returns a dictionary of each value of the given field in this collection to the number of records
+    /// that have it e.g. to get a histogram of statuses. The records are counted by a script as it scans the
+    /// collection in redis, so only the counts are sent back
+    pub(crate) fn count_by(&self, field: &str) -> PyResult<Py<PyAny>> {
+        aggregations::ensure_groupable_field(&self.meta, field)?;
+
+        let counts = utils::count_by_field(&self.pool, &self.name, field)?;
+        self.meta
+            .metrics
+            .record("count_by", counts.iter().map(|(_, count)| count).sum());
+        aggregations::counts_to_py(
+            &self.name,
+            self.meta.schema.get_type(field).unwrap(),
+            &counts,
+        )
+    }
+
+    /// Returns the number of records of this collection whose fields are equal to the given values
+    /// e.g. `count_where(author="Jane")`, or of all its records if none is given. The records are counted by
+    /// a script as it scans the collection in redis, so only the count is sent back
+    #[args(filters = "**")]
+    pub(crate) fn count_where(&self, filters: Option<&PyDict>) -> PyResult<usize> {
+        let filters = filters.map(|v| v.extract()).transpose()?;
+        let filter = self.meta.extract_filter(filters)?;
+
+        let count = utils::count_matching_records(&self.pool, &self.name, &filter)?;
+        self.meta.metrics.record("count_where", count);
+        Ok(count)
+    }
+
+    /// Starts maintaining an index of the records of this collection by the values of the given int, float,
+    /// date or datetime field on every write, building it from the records already in this collection and
+    /// scanning at most `max_ops_per_sec` records per second if given. It returns the number of records indexed
+    #[args(field, max_ops_per_sec = "None")]
+    pub(crate) fn create_index(
+        &self,
+        py: Python,
+        field: &str,
+        max_ops_per_sec: Option<f64>,
+    ) -> PyResult<u64> {
+        range_indexes::ensure_indexable_field(&self.meta, field)?;
+        let mut throttle = Throttle::new(max_ops_per_sec)?;
+        // the GIL is released so that other threads are not blocked while the scan is throttled
+        py.allow_threads(|| {
+            utils::build_range_index(&self.pool, &self.name, &self.meta, field, &mut throttle)
+        })
+    }
+
+    /// Starts maintaining an index of the records of this collection that have no value for the given field,
+    /// i.e. that lack it or whose value is None, on every write, building it from the records already in this
+    /// collection and scanning at most `max_ops_per_sec` records per second if given. It returns the number of
+    /// records without a value, which are found with find(field__isnull=True) e.g. to backfill the field
+    #[args(field, max_ops_per_sec = "None")]
+    pub(crate) fn create_null_index(
+        &self,
+        py: Python,
+        field: &str,
+        max_ops_per_sec: Option<f64>,
+    ) -> PyResult<u64> {
+        null_indexes::ensure_indexable_field(&self.meta, field)?;
+        let mut throttle = Throttle::new(max_ops_per_sec)?;
+        // the GIL is released so that other threads are not blocked while the scan is throttled
+        py.allow_threads(|| {
+            utils::build_null_index(&self.pool, &self.name, &self.meta, field, &mut throttle)
+        })
+    }
+
+    /// Returns the records of this collection whose values of the given indexed field are between `lo` and `hi`,
+    /// inclusive, in the order of those values. A bound that is None leaves the range open on its side
+    pub(crate) fn find_between(
+        &self,
+        field: &str,
+        lo: Option<Py<PyAny>>,
+        hi: Option<Py<PyAny>>,
+    ) -> PyResult<Vec<Py<PyAny>>> {
+        utils::refresh_registered_fields(&self.pool, &self.name, &self.meta)?;
+        self.meta.ensure_range_index(field)?;
+        let min = range_indexes::bound_to_score(&self.meta, field, lo, "-inf")?;
+        let max = range_indexes::bound_to_score(&self.meta, field, hi, "+inf")?;
+
+        let ids = utils::get_ids_in_range(&self.pool, &self.name, field, &min, &max)?;
+        if ids.is_empty() {
+            self.meta.metrics.record("find_between", 0);
+            return Ok(vec![]);
+        }
+
+        self.meta.metrics.record_result(
+            "find_between",
+            utils::get_records_by_id(&self.pool, &self.name, &self.meta, &ids),
+        )
+    }
+
+    /// Returns a page of at most `limit` records of this collection in the order of their values of the given
+    /// indexed field, ties being in the order of their ids, starting after the record whose value and id are
+    /// `after_value` and `after_id`, usually the last record of the previous page. Unlike skipping records,
+    /// getting a page costs the same however deep it is, and records inserted meanwhile do not shift the pages
+    #[args(field, limit = 20, after_value = "None", after_id = "None")]
+    pub(crate) fn get_page(
+        &self,
+        field: &str,
+        limit: usize,
+        after_value: Option<Py<PyAny>>,
+        after_id: Option<&str>,
+    ) -> PyResult<Vec<Py<PyAny>>> {
+        utils::refresh_registered_fields(&self.pool, &self.name, &self.meta)?;
+        self.meta.ensure_range_index(field)?;
+        let limit = pagination::validate_browse_limit(limit)?;
+        let cursor = KeysetCursor::new(&self.meta, field, after_value, after_id)?;
+
+        let ids = utils::get_page_ids(&self.pool, &self.name, field, &cursor, limit)?;
+        if ids.is_empty() {
+            self.meta.metrics.record("get_page", 0);
+            return Ok(vec![]);
+        }
+
+        self.meta.metrics.record_result(
+            "get_page",
+            utils::get_records_by_id(&self.pool, &self.name, &self.meta, &ids),
+        )
+    }
+
+    /// Returns the `n` records of this collection inserted last, latest first. Records that expired,
+    /// or were removed otherwise than with the methods of this collection, are left out
+    #[args(n = 10)]
+    pub(crate) fn get_latest(&self, n: usize) -> PyResult<Vec<Py<PyAny>>> {
+        self.get_by_insertion_order("get_latest", n, true)
+    }
+
+    /// Returns the `n` records of this collection inserted first, oldest first. Records that expired,
+    /// or were removed otherwise than with the methods of this collection, are left out
+    #[args(n = 10)]
+    pub(crate) fn get_oldest(&self, n: usize) -> PyResult<Vec<Py<PyAny>>> {
+        self.get_by_insertion_order("get_oldest", n, false)
+    }
+
+    /// Returns the `k` records of this collection whose values of the given vector field are nearest to
+    /// `query_vector` by cosine distance, nearest first. It needs the RediSearch module of redis, and creates
+    /// the index of the field the first time it is searched, after which records already saved are indexed
+    /// in the background
+    #[args(field, query_vector, k = 10)]
+    pub(crate) fn knn(
+        &self,
+        field: &str,
+        query_vector: Vec<f32>,
+        k: usize,
+    ) -> PyResult<Vec<Py<PyAny>>> {
+        let dimension = vectors::ensure_vector_field(&self.meta, field)?;
+        let vector = vectors::pack_query_vector(field, dimension, query_vector)?;
+        if k == 0 {
+            self.meta.metrics.record("knn", 0);
+            return Ok(vec![]);
+        }
+
+        let ids = utils::knn(&self.pool, &self.name, field, dimension, &vector, k)?;
+        if ids.is_empty() {
+            self.meta.metrics.record("knn", 0);
+            return Ok(vec![]);
+        }
+
+        self.meta.metrics.record_result(
+            "knn",
+            utils::get_records_by_id(&self.pool, &self.name, &self.meta, &ids),
+        )
+    }
+
+    /// Measures the memory used in redis by `sample` records of this collection picked at random,
+    /// estimating from them that of the whole collection and listing the `top` largest of them
+    #[args(sample = 1000, top = 10)]
+    pub(crate) fn memory_usage(&self, sample: usize, top: usize) -> PyResult<Py<PyAny>> {
+        if sample == 0 {
+            return Err(PyValueError::new_err("sample should be at least 1"));
+        }
+
+        utils::measure_memory_usage(&self.pool, &self.name, sample)?.to_py(top)
+    }
+
+    /// Trains a zstd dictionary on the string values of `sample` records of this collection picked at random,
+    /// saving it in redis and compressing the string fields of the records written from then on with it.
+    /// Records written before are left as they are. It returns the statistics of the training
+    #[args(sample = 1000)]
+    pub(crate) fn train_compression(&self, sample: usize) -> PyResult<Py<PyAny>> {
+        if sample == 0 {
+            return Err(PyValueError::new_err("sample should be at least 1"));
+        }
+
+        utils::train_compression(&self.pool, &self.name, &self.meta, sample)?.to_py()
+    }
+
+    /// Loads the compression dictionaries of this collection saved in redis e.g. after a restart, so that
+    /// the values compressed with them can be read and new values are compressed with the latest one.
+    /// It returns whether a dictionary was trained for this collection
+    pub(crate) fn load_compression(&self) -> PyResult<bool> {
+        utils::load_compression(&self.pool, &self.name, &self.meta)
+    }
+
+    /// Returns the `top` most accessed ids of this collection, hottest first, with their estimated number
+    /// of recent accesses. The accesses are counted only if hot_key_sampling was set in create_collection()
+    #[args(top = 20)]
+    pub(crate) fn hot_keys(&self, top: usize) -> PyResult<Vec<(String, f64)>> {
+        Ok(self.meta.get_hot_keys()?.top(top))
+    }
+
+    /// Atomically increments the given counter field of the record of the given id by the given amount,
+    /// returning the new value
+    #[args(id, field, amount = "None")]
+    pub(crate) fn increment(
+        &self,
+        id: &str,
+        field: &str,
+        amount: Option<&PyAny>,
+    ) -> PyResult<Py<PyAny>> {
+        self.meta.record_accesses([id]);
+        let cmd = utils::generate_increment_cmd(&self.name, &self.meta, id, field, amount)?;
+        let value = utils::increment_counter(&self.pool, &self.name, &self.meta, field, &cmd)?;
+        self.meta.metrics.record("increment", 1);
+        Ok(value)
+    }
+
+    /// Deletes the records that correspond to the given ids for this collection, returning the number of them
+    /// that existed. If strict, it raises a KeyError if any of them did not exist, after deleting the others
+    #[args(ids, strict = "false")]
+    pub(crate) fn delete_many(&self, ids: Vec<String>, strict: bool) -> PyResult<usize> {
+        utils::refresh_registered_fields(&self.pool, &self.name, &self.meta)?;
+        let pipe = utils::generate_delete_pipeline(&self.name, &self.meta, &ids);
+        let deleted = utils::remove_records(&self.pool, &self.meta, &pipe)?;
+        self.meta.metrics.record("delete_many", ids.len());
+        utils::to_deleted_count(&self.name, &self.meta, &ids, deleted, strict)
+    }
+
+    /// Deletes the records of this collection whose fields are equal to the given values e.g.
+    /// `delete_where(author="Jane")`, returning the number deleted. The records are found and deleted in redis
+    /// by one script that scans the collection, so none of them is read first
+    #[args(field_filters = "**")]
+    pub(crate) fn delete_where(&self, field_filters: Option<&PyDict>) -> PyResult<usize> {
+        let filter = conditional_writes::extract_field_filters(&self.meta, field_filters)?;
+        let deleted = utils::delete_matching_records(&self.pool, &self.name, &self.meta, &filter)?;
+        self.meta.metrics.record("delete_where", deleted);
+        Ok(deleted)
+    }
+
+    /// Deletes all the records of this collection, with their indexes and the other data derived from them,
+    /// leaving the other collections of the redis database as they are, unlike `Store.clear()`. The keys are
+    /// scanned and deleted a batch at a time, at most `max_ops_per_sec` keys per second if given.
+    /// It returns the number of records deleted
+    #[args(max_ops_per_sec = "None")]
+    pub(crate) fn clear(&self, py: Python, max_ops_per_sec: Option<f64>) -> PyResult<u64> {
+        let mut throttle = Throttle::new(max_ops_per_sec)?;
+        // the GIL is released so that other threads are not blocked while the scan is throttled
+        let deleted = py.allow_threads(|| {
+            utils::clear_collection(&self.pool, &self.name, &self.meta, &mut throttle)
+        })?;
+        self.meta.metrics.record("clear", deleted as usize);
+        Ok(deleted)
+    }
+
+    /// Deletes the records of this collection whose ids match the given glob pattern e.g. "order-2023-*",
+    /// returning the number deleted. The keys are scanned and the matching records deleted a batch at a time,
+    /// at most `max_ops_per_sec` keys per second if given
+    #[args(id_pattern, max_ops_per_sec = "None")]
+    pub(crate) fn delete_matching(
+        &self,
+        py: Python,
+        id_pattern: &str,
+        max_ops_per_sec: Option<f64>,
+    ) -> PyResult<usize> {
+        if id_pattern.is_empty() {
+            return Err(PyValueError::new_err("id_pattern should not be empty"));
+        }
+
+        let mut throttle = Throttle::new(max_ops_per_sec)?;
+        // the GIL is released so that other threads are not blocked while the scan is throttled
+        let deleted = py.allow_threads(|| {
+            utils::delete_records_by_id_pattern(
+                &self.pool,
+                &self.name,
+                &self.meta,
+                id_pattern,
+                &mut throttle,
+            )
+        })?;
+        self.meta.metrics.record("delete_matching", deleted);
+        Ok(deleted)
+    }
+
+    /// Makes the given changes, the new values of some non-nested fields, to the records of this collection
+    /// whose fields are equal to the values in the given filters e.g.
+    /// `update_where({"author": "Jane"}, {"in_stock": False})`, returning the number updated. The records are
+    /// found and updated in redis by one script that scans the collection
+    pub(crate) fn update_where(
+        &self,
+        filters: HashMap<String, Py<PyAny>>,
+        changes: HashMap<String, Py<PyAny>>,
+    ) -> PyResult<usize> {
+        let filter = conditional_writes::extract_filters(&self.meta, filters)?;
+        let changes = conditional_writes::extract_changes(&self.name, &self.meta, changes)?;
+        let updated =
+            utils::update_matching_records(&self.pool, &self.name, &self.meta, &filter, &changes)?;
+        self.meta.metrics.record("update_where", updated);
+        Ok(updated)
+    }
+
+    /// Returns the events in the change stream of this collection that come after the event
+    /// of the given id, oldest first
+    #[args(after = "\"0-0\"", count = 100)]
+    pub(crate) fn get_changes(&self, after: &str, count: usize) -> PyResult<Vec<Py<PyAny>>> {
+        self.meta.ensure_tracks_changes()?;
+        utils::get_changes(&self.pool, &self.name, &self.meta, after, count)
+    }
+
+    /// Checks whether the record of the given id exists in this collection
+    pub(crate) fn exists(&self, id: &str) -> PyResult<bool> {
+        self.meta.record_accesses([id]);
+        utils::record_exists(&self.pool, &self.name, &self.meta, id)
+    }
+
+    /// Checks whether the records of the given ids exist in this collection, returning a boolean per id in order
+    pub(crate) fn exists_many(&self, ids: Vec<String>) -> PyResult<Vec<bool>> {
+        self.meta.record_accesses(ids.iter().map(String::as_str));
+        utils::records_exist(&self.pool, &self.name, &self.meta, &ids)
+    }
+
+    /// Gets the record that corresponds to the given id.
+    /// If profile is true, it returns the timing breakdown of the call as (record, profile)
+    #[args(id, profile = "false")]
+    pub(crate) fn get_one(&self, id: &str, profile: bool) -> PyResult<Py<PyAny>> {
+        self.meta.record_accesses([id]);
+        let profile = Profile::start(profile);
+        let record = profiling::run(&profile, || {
+            let might_exist = profiling::measure(Phase::Network, || {
+                utils::might_exist(&self.pool, &self.name, &self.meta, id)
+            })?;
+            if !might_exist {
+                self.meta.metrics.record("get_one", 0);
+                return Ok(None);
+            }
+
+            let mut records: Vec<Py<PyAny>> = self.meta.metrics.record_result(
+                "get_one",
+                utils::get_records_by_id(&self.pool, &self.name, &self.meta, &vec![id.to_string()]),
+            )?;
+            Ok::<_, PyErr>(records.pop())
+        })?;
+        profiling::attach(profile, record)
+    }
+
+    /// Returns the record of this collection that the given reference i.e. the value of a field annotated with
+    /// `Reference[Model]` in another collection, refers to, or None if there is no such record or no reference.
+    /// Referenced records are thus only read when they are needed, unlike nested records
+    pub(crate) fn resolve(&self, reference: Option<&PyAny>) -> PyResult<Py<PyAny>> {
+        match reference {
+            None => Python::with_gil(|py| Ok(py.None())),
+            Some(reference) => {
+                self.get_one(&utils::extract_reference_id(&self.name, reference)?, false)
+            }
+        }
+    }
+
+    /// Returns the records of the given child collection whose foreign key field `fk_field` holds the given id
+    /// of a record of this collection e.g. all the orders of a user, in no particular order. They are found with
+    /// the index of the foreign key, which is updated on every write to the child collection, so the cost of
+    /// the call depends on the number of children rather than on the size of the child collection
+    pub(crate) fn children_of(
+        &self,
+        parent_id: &str,
+        child_collection: PyRef<Collection>,
+        fk_field: &str,
+    ) -> PyResult<Vec<Py<PyAny>>> {
+        child_collection.meta.ensure_foreign_key(fk_field)?;
+
+        let ids = utils::get_ids_by_value(
+            &child_collection.pool,
+            &child_collection.name,
+            &child_collection.meta,
+            &[fk_field.to_string()],
+            &[parent_id.to_string()],
+        )?;
+        if ids.is_empty() {
+            child_collection.meta.metrics.record("children_of", 0);
+            return Ok(vec![]);
+        }
+
+        child_collection.meta.metrics.record_result(
+            "children_of",
+            utils::get_records_by_id(
+                &child_collection.pool,
+                &child_collection.name,
+                &child_collection.meta,
+                &ids,
+            ),
+        )
+    }
+
+    /// Returns the records of this collection whose indexed field holds the given value, in no particular order.
+    /// They are found with the index of the field, set with indexed_fields in create_collection(), so the cost
+    /// of the call depends on the number of matching records rather than on the size of the collection
+    pub(crate) fn find_by(&self, field: &str, value: Py<PyAny>) -> PyResult<Vec<Py<PyAny>>> {
+        self.meta.ensure_indexed_field(field)?;
+
+        let value = utils::field_value_to_redis(self.meta.schema.get_type(field).unwrap(), &value)?;
+        let ids = utils::get_ids_by_value(
+            &self.pool,
+            &self.name,
+            &self.meta,
+            &[field.to_string()],
+            &[value],
+        )?;
+        if ids.is_empty() {
+            self.meta.metrics.record("find_by", 0);
+            return Ok(vec![]);
+        }
+
+        self.meta.metrics.record_result(
+            "find_by",
+            utils::get_records_by_id(&self.pool, &self.name, &self.meta, &ids),
+        )
+    }
+
+    /// Returns the records of this collection whose fields hold the given values e.g. a country and a city,
+    /// in no particular order, using the compound index of the fields set with compound_indexes in create_collection().
+    /// The fields may be given in any order, each value being that of the field at the same position
+    pub(crate) fn find_by_index(
+        &self,
+        fields: Vec<String>,
+        values: Vec<Py<PyAny>>,
+    ) -> PyResult<Vec<Py<PyAny>>> {
+        let (index, values) =
+            field_indexes::to_index_fields_and_values(&self.meta, &fields, &values)?;
+        let ids = utils::get_ids_by_value(&self.pool, &self.name, &self.meta, &index, &values)?;
+        if ids.is_empty() {
+            self.meta.metrics.record("find_by_index", 0);
+            return Ok(vec![]);
+        }
+
+        self.meta.metrics.record_result(
+            "find_by_index",
+            utils::get_records_by_id(&self.pool, &self.name, &self.meta, &ids),
+        )
+    }
+
+    /// Returns the records of this collection that match all the given lookups, in no particular order:
+    /// `field=value` for an indexed field holding the value, `field__contains=value` for a list field,
+    /// set with indexed_list_fields in create_collection(), holding the value among its elements
+    /// e.g. find(tags__contains="rust"), and `field__isnull=True` (or False) for a field with a null index,
+    /// made with create_null_index(), that is missing or None (or not). The ids matching the lookups
+    /// are got in one network request, or, if `cache_ttl` is given, from the ids cached by an identical call
+    /// within that time, the records themselves being read afresh
+    #[args(cache_ttl = "None", lookups = "**")]
+    pub(crate) fn find(
+        &self,
+        cache_ttl: Option<&PyAny>,
+        lookups: Option<&PyDict>,
+    ) -> PyResult<Vec<Py<PyAny>>> {
+        let cache_ttl = cache_ttl.map(Expiry::from_py).transpose()?;
+        let lookups = Lookups::from_py(&self.name, &self.meta, lookups)?;
+        utils::refresh_registered_fields(&self.pool, &self.name, &self.meta)?;
+        lookups.ensure_indexes(&self.meta)?;
+        let ids = match &cache_ttl {
+            None => utils::get_ids_by_lookups(&self.pool, &self.name, &lookups)?,
+            Some(cache_ttl) => {
+                utils::get_cached_ids_by_lookups(&self.pool, &self.name, &lookups, cache_ttl)?
+            }
+        };
+        if ids.is_empty() {
+            self.meta.metrics.record("find", 0);
+            return Ok(vec![]);
+        }
+
+        self.meta.metrics.record_result(
+            "find",
+            utils::get_records_by_id(&self.pool, &self.name, &self.meta, &ids),
+        )
+    }
+
+    /// Explains how `find()` gets the ids of the records matching the given lookups, to diagnose slow ones:
+    /// the number of candidates in the index of each lookup, the index chosen to drive the matching i.e. the one
+    /// with the fewest candidates, and whether all the ids are scanned instead as the lookups only exclude
+    /// records. The records themselves are not read
+    #[args(lookups = "**")]
+    pub(crate) fn explain_find(&self, lookups: Option<&PyDict>) -> PyResult<Py<PyAny>> {
+        let lookups = Lookups::from_py(&self.name, &self.meta, lookups)?;
+        utils::refresh_registered_fields(&self.pool, &self.name, &self.meta)?;
+        lookups.ensure_indexes(&self.meta)?;
+        let ids = utils::get_lookup_candidates(&self.pool, &self.name, &lookups)?;
+        lookups.explain(ids)
+    }
+
+    /// Returns the ids of all the records in this collection, scanning its keys without reading the records,
+    /// so that they can be listed cheaply before deciding which to get. Their order is arbitrary
+    pub(crate) fn ids(&self) -> PyResult<Vec<String>> {
+        self.meta
+            .metrics
+            .record_result("ids", utils::get_record_ids(&self.pool, &self.name))
+    }
+
+    /// Returns `n` records of this collection picked at random, or all of them in a random order if there are
+    /// not more than `n`. The ids of all the records are scanned without reading the records, and only the records
+    /// picked are read, e.g. to spot-check the quality of the data of a big collection
+    pub(crate) fn sample(&self, n: usize) -> PyResult<Vec<Py<PyAny>>> {
+        let ids = verification::pick_random_ids(utils::get_record_ids(&self.pool, &self.name)?, n);
+        if ids.is_empty() {
+            self.meta.metrics.record("sample", 0);
+            return Ok(vec![]);
+        }
+
+        self.meta.metrics.record_result(
+            "sample",
+            utils::get_records_by_id(&self.pool, &self.name, &self.meta, &ids),
+        )
+    }
+
+    /// Returns all the records found in this collection; returning them as models.
+    /// Records that cannot be read e.g. because they are corrupt, fail the call if on_error is "raise",
+    /// are left out if it is "skip", or are returned separately with their errors as (records, errors)
+    /// if it is "collect". If a filter is given, only the records whose fields are equal to its values are
+    /// returned, the filtering being done in redis. At most `limit` records, or all of them if
+    /// no limit is given, are returned after skipping the first `skip` records, in the order in which they are scanned,
+    /// or in the order of their values of the field `order_by`, descending if desc, the records without a value
+    /// for it coming last. If profile is true, it returns the timing breakdown of the call as (records, profile)
+    #[args(
+        on_error = "\"raise\"",
+        filter = "None",
+        skip = 0,
+        limit = "None",
+        profile = "false",
+        order_by = "None",
+        desc = "false"
+    )]
+    #[allow(clippy::too_many_arguments)]
+    pub(crate) fn get_all(
+        &self,
+        on_error: &str,
+        filter: Option<HashMap<String, Py<PyAny>>>,
+        skip: usize,
+        limit: Option<usize>,
+        profile: bool,
+        order_by: Option<String>,
+        desc: bool,
+    ) -> PyResult<Py<PyAny>> {
+        let on_error = OnError::from_name(on_error)?;
+        let filter = self.meta.extract_filter(filter)?;
+        let ordering = Ordering::new(&self.meta, order_by, desc)?;
+        let page = Page::new(skip, limit);
+        let profile = Profile::start(profile);
+        let (records, errors) = profiling::run(&profile, || match &ordering {
+            None => utils::get_all_records_in_collection(
+                &self.pool, &self.name, &self.meta, &filter, page, on_error,
+            ),
+            Some(ordering) => utils::get_ordered_records_in_collection(
+                &self.pool, &self.name, &self.meta, ordering, &filter, page, on_error,
+            ),
+        })?;
+        self.meta.metrics.record("get_all", records.len());
+        profiling::attach(profile, on_error.to_py(records, errors)?)
+    }
+
+    /// Takes a snapshot of this collection under the given tag i.e. copies all its records as they currently are,
+    /// in place of any snapshot of the same tag, so that they can be read later with `read_snapshot()` e.g. to debug
+    /// or compare them with later records. Snapshots do not expire and are kept until `delete_snapshot()` is called.
+    /// It returns the number of records in the snapshot. At most `max_ops_per_sec` records are scanned per second if given
+    #[args(max_ops_per_sec = "None")]
+    pub(crate) fn snapshot(
+        &self,
+        py: Python,
+        tag: &str,
+        max_ops_per_sec: Option<f64>,
+    ) -> PyResult<u64> {
+        snapshots::validate_tag(tag)?;
+        let mut throttle = Throttle::new(max_ops_per_sec)?;
+        // the GIL is released so that other threads are not blocked while the copy is throttled
+        let copied =
+            py.allow_threads(|| utils::take_snapshot(&self.pool, &self.name, tag, &mut throttle))?;
+        self.meta.metrics.record("snapshot", copied as usize);
+        Ok(copied)
+    }
+
+    /// Returns the records of the snapshot of the given tag of this collection as models, as they were when
+    /// the snapshot was taken. Only the records of this collection are copied into a snapshot, so their nested
+    /// records are as they currently are. Records that cannot be read are handled as in `get_all()`
+    #[args(on_error = "\"raise\"")]
+    pub(crate) fn read_snapshot(&self, tag: &str, on_error: &str) -> PyResult<Py<PyAny>> {
+        snapshots::validate_tag(tag)?;
+        let on_error = OnError::from_name(on_error)?;
+        let (records, errors) = utils::get_all_records_in_collection(
+            &self.pool,
+            &snapshots::get_key_space(&self.name, tag),
+            &self.meta,
+            &[],
+            Page::default(),
+            on_error,
+        )?;
+        self.meta.metrics.record("read_snapshot", records.len());
+        on_error.to_py(records, errors)
+    }
+
+    /// Deletes the snapshot of the given tag of this collection, returning the number of records it had
+    pub(crate) fn delete_snapshot(&self, py: Python, tag: &str) -> PyResult<u64> {
+        snapshots::validate_tag(tag)?;
+        let mut throttle = Throttle::new(None)?;
+        py.allow_threads(|| utils::delete_snapshot(&self.pool, &self.name, tag, &mut throttle))
+    }
+
+    /// Exports the records of this collection, with their counters, into a file at the given path in chunks of
+    /// `chunk_size` records, each with a checksum, so that they can be restored with `import_from()` e.g. into
+    /// another store. It returns the number of records exported
+    #[args(path, chunk_size = 1000)]
+    pub(crate) fn export_to(&self, py: Python, path: &str, chunk_size: usize) -> PyResult<u64> {
+        if chunk_size == 0 {
+            return Err(PyValueError::new_err("chunk_size should be at least 1"));
+        }
+
+        let exported = BackupTarget::File(path.to_string()).write(py, |sink| {
+            utils::export_records(&self.pool, &self.name, &self.meta, sink, chunk_size)
+                .map(|(exported, _)| exported)
+        })?;
+        self.meta.metrics.record("export_to", exported as usize);
+        Ok(exported)
+    }
+
+    /// Backs this collection up into the given destination i.e. exports its records as `export_to()` does,
+    /// writing the export in pieces into a file-like object, the sink opened for a url e.g. "s3://bucket/path",
+    /// or a local file. It returns the number of records backed up
+    #[args(dest, chunk_size = 1000)]
+    pub(crate) fn backup(&self, py: Python, dest: &PyAny, chunk_size: usize) -> PyResult<u64> {
+        if chunk_size == 0 {
+            return Err(PyValueError::new_err("chunk_size should be at least 1"));
+        }
+
+        let backed_up = BackupTarget::from_py(dest)?.write(py, |sink| {
+            utils::export_records(&self.pool, &self.name, &self.meta, sink, chunk_size)
+                .map(|(backed_up, _)| backed_up)
+        })?;
+        self.meta.metrics.record("backup", backed_up as usize);
+        Ok(backed_up)
+    }
+
+    /// Imports the records of the export at the given path, written by `export_to()`, into this collection one
+    /// chunk at a time, verifying the checksum of each chunk before it is imported. If `resume` is true, an import
+    /// that was interrupted carries on after the last chunk it imported. It returns the number of records imported
+    #[args(path, resume = "true")]
+    pub(crate) fn import_from(&self, py: Python, path: &str, resume: bool) -> PyResult<u64> {
+        let ttl = utils::resolve_ttl(None, self.default_ttl, &self.meta.retention);
+        let imported = py.allow_threads(|| {
+            utils::import_records(&self.pool, &self.name, &self.meta, path, resume, &ttl)
+        })?;
+        self.meta.metrics.record("import_from", imported as usize);
+        Ok(imported)
+    }
+
+    /// Returns an iterator over the records of this collection as models, which scans the collection one batch of
+    /// about `batch_size` records at a time as it is iterated, so that only a batch is held in memory at a time.
+    /// Records are in the order in which SCAN finds them; those changed during the iteration may be missed or
+    /// returned twice
+    #[args(batch_size = 100)]
+    pub(crate) fn iter_all(&self, batch_size: usize) -> PyResult<RecordIterator> {
+        if batch_size == 0 {
+            return Err(PyValueError::new_err("batch_size should be at least 1"));
+        }
+
+        Ok(RecordIterator {
+            name: self.name.clone(),
+            meta: self.meta.clone(),
+            pool: self.pool.clone(),
+            batch_size,
+            scan_cursor: Some(0),
+            batch: Default::default(),
+        })
+    }
+
+    /// Returns all the records found in this collection as models, reading them in parallel across
+    /// the given number of workers, each on its own connection. The order of the records is arbitrary
+    #[args(workers = 4)]
+    pub(crate) fn parallel_iter(&self, py: Python, workers: usize) -> PyResult<Vec<Py<PyAny>>> {
+        if workers == 0 {
+            return Err(PyValueError::new_err("workers should be at least 1"));
+        }
+
+        self.meta.metrics.record_result(
+            "parallel_iter",
+            utils::get_all_records_in_parallel(py, &self.pool, &self.name, &self.meta, workers),
+        )
+    }
+
+    /// Returns the records whose ids are as given for this collection.
+    /// If profile is true, it returns the timing breakdown of the call as (records, profile)
+    #[args(ids, profile = "false")]
+    pub(crate) fn get_many(&self, ids: Vec<String>, profile: bool) -> PyResult<Py<PyAny>> {
+        self.meta.record_accesses(ids.iter().map(String::as_str));
+        let profile = Profile::start(profile);
+        let records = self.meta.metrics.record_result(
+            "get_many",
+            profiling::run(&profile, || {
+                utils::get_records_by_id(&self.pool, &self.name, &self.meta, &ids)
+            }),
+        )?;
+        profiling::attach(profile, records)
+    }
+
+    /// Rewrites the values of the records of this collection that are in known broken or legacy formats
+    /// in the format orredis writes, with the given fixes: "bool_case", "datetime_format" and "none_sentinel",
+    /// or all of them if none are given. It returns the number of records repaired. At most `max_ops_per_sec`
+    /// records are read per second if given
+    #[args(fix = "None", max_ops_per_sec = "None")]
+    pub(crate) fn repair(
+        &self,
+        py: Python,
+        fix: Option<Vec<String>>,
+        max_ops_per_sec: Option<f64>,
+    ) -> PyResult<u64> {
+        let repairs = repairs::extract_repairs(fix)?;
+        let mut throttle = Throttle::new(max_ops_per_sec)?;
+        // the GIL is released so that other threads are not blocked while the repair is throttled
+        let repaired = py.allow_threads(|| {
+            utils::repair_records(&self.pool, &self.name, &self.meta, &repairs, &mut throttle)
+        })?;
+        self.meta.metrics.record("repair", repaired as usize);
+        Ok(repaired)
+    }
+
+    /// Removes the fields that are no longer in the model of this collection from its records e.g. after
+    /// schema changes, compacting at most `max_ops_per_sec` records per second if given. It returns the number of records
+    /// compacted, or None if `background` is set, in which case the records are compacted in a background task
+    /// of the store named "compact:<collection>", whose status and error are got with `background_tasks()`
+    #[args(max_ops_per_sec = "None", background = "false")]
+    pub(crate) fn compact(
+        &self,
+        py: Python,
+        max_ops_per_sec: Option<f64>,
+        background: bool,
+    ) -> PyResult<Option<u64>> {
+        let mut throttle = Throttle::new(max_ops_per_sec)?;
+
+        if background {
+            let pool = self.pool.clone();
+            let name = self.name.clone();
+            let meta = self.meta.clone();
+            let task_name = compaction::get_task_name(&self.name);
+            // the records are compacted in a blocking task, not to block the executor
+            self.meta
+                .background_tasks
+                .spawn_native(py, task_name, |runs| async move {
+                    task::spawn_blocking(move || {
+                        let compacted = utils::compact_records(&pool, &name, &meta, &mut throttle)?;
+                        meta.metrics.record("compact", compacted as usize);
+                        PyResult::Ok(())
+                    })
+                    .await?;
+                    runs.fetch_add(1, atomic::Ordering::Relaxed);
+                    Ok(())
+                })?;
+            return Ok(None);
+        }
+
+        // the GIL is released so that other threads are not blocked while the compaction is throttled
+        let compacted = py.allow_threads(|| {
+            utils::compact_records(&self.pool, &self.name, &self.meta, &mut throttle)
+        })?;
+        self.meta.metrics.record("compact", compacted as usize);
+        Ok(Some(compacted))
+    }
+
+    /// Returns the records whose ids are as given for this collection as dictionaries, with the records
+    /// nested in them also as dictionaries, without constructing any models
+    pub(crate) fn get_many_raw(&self, ids: Vec<String>) -> PyResult<Vec<Py<PyAny>>> {
+        self.meta.record_accesses(ids.iter().map(String::as_str));
+        self.meta.metrics.record_result(
+            "get_many_raw",
+            utils::get_raw_records_by_id(&self.pool, &self.name, &self.meta, &ids),
+        )
+    }
+
+    /// Returns the record that corresponds to the given id in this collection
+    /// returning it as a dictionary with only the fields specified
+    #[args(id, fields, nested_as_models = "false")]
+    pub(crate) fn get_one_partially(
+        &self,
+        id: &str,
+        fields: Vec<String>,
+        nested_as_models: bool,
+    ) -> PyResult<Py<PyAny>> {
+        self.meta.record_accesses([id]);
+        let mut records: Vec<Py<PyAny>> = self.meta.metrics.record_result(
+            "get_one_partially",
+            utils::get_partial_records_by_id(
+                &self.pool,
+                &self.name,
+                &self.meta,
+                &vec![id.to_string()],
+                &fields,
+                nested_as_models,
+            ),
         )?;
         match records.pop() {
             None => Python::with_gil(|py| Ok(py.None())),
@@ -275,20 +2098,90 @@ impl Collection {
         }
     }
 
+    /// Returns the value of the given non-nested field of the record of the given id in this collection,
+    /// or None if the record or the value is not in redis. Only that one value is read, with HGET
+    pub(crate) fn get_field(&self, id: &str, field: &str) -> PyResult<Option<Py<PyAny>>> {
+        self.meta.ensure_scalar_field(field)?;
+        self.meta.record_accesses([id]);
+        let value = utils::get_field_value(&self.pool, &self.name, &self.meta, id, field)?;
+        self.meta
+            .metrics
+            .record("get_field", value.is_some() as usize);
+        Ok(value)
+    }
+
     /// Retrieves the all records in this collection, only returning the specified fields
-    /// for each given record
-    pub(crate) fn get_all_partially(&self, fields: Vec<String>) -> PyResult<Vec<Py<PyAny>>> {
-        utils::get_all_partial_records_in_collection(&self.pool, &self.name, &self.meta, &fields)
+    /// for each given record. At most `limit` records, or all of them if
+    /// no limit is given, are returned after skipping the first `skip` records, in the order in which they are scanned,
+    /// or in the order of their values of the field `order_by`, descending if desc, as in `get_all()`
+    #[args(
+        fields,
+        nested_as_models = "false",
+        skip = 0,
+        limit = "None",
+        order_by = "None",
+        desc = "false"
+    )]
+    pub(crate) fn get_all_partially(
+        &self,
+        fields: Vec<String>,
+        nested_as_models: bool,
+        skip: usize,
+        limit: Option<usize>,
+        order_by: Option<String>,
+        desc: bool,
+    ) -> PyResult<Vec<Py<PyAny>>> {
+        let ordering = Ordering::new(&self.meta, order_by, desc)?;
+        let page = Page::new(skip, limit);
+        let records = match &ordering {
+            None => utils::get_all_partial_records_in_collection(
+                &self.pool,
+                &self.name,
+                &self.meta,
+                &fields,
+                nested_as_models,
+                page,
+            ),
+            Some(ordering) => {
+                utils::get_ordered_ids(&self.pool, &self.name, &self.meta, ordering, &[], page)
+                    .and_then(|ids| {
+                        utils::get_partial_records_by_id(
+                            &self.pool,
+                            &self.name,
+                            &self.meta,
+                            &ids,
+                            &fields,
+                            nested_as_models,
+                        )
+                    })
+            }
+        };
+        self.meta
+            .metrics
+            .record_result("get_all_partially", records)
     }
 
     /// Retrieves the records with the given ids in this collection, only returning
     /// the specified fields for each record
+    #[args(ids, fields, nested_as_models = "false")]
     pub(crate) fn get_many_partially(
         &self,
         ids: Vec<String>,
         fields: Vec<String>,
+        nested_as_models: bool,
     ) -> PyResult<Vec<Py<PyAny>>> {
-        utils::get_partial_records_by_id(&self.pool, &self.name, &self.meta, &ids, &fields)
+        self.meta.record_accesses(ids.iter().map(String::as_str));
+        self.meta.metrics.record_result(
+            "get_many_partially",
+            utils::get_partial_records_by_id(
+                &self.pool,
+                &self.name,
+                &self.meta,
+                &ids,
+                &fields,
+                nested_as_models,
+            ),
+        )
     }
 }
 
@@ -297,9 +2190,9 @@ impl Collection {
     /// cannot be directly instantiated in python
     pub(crate) fn new(
         name: String,
-        pool: r2d2::Pool<redis::Client>,
+        pool: pools::RedisPool,
         meta: CollectionMeta,
-        default_ttl: Option<u64>,
+        default_ttl: Option<Expiry>,
     ) -> Self {
         Collection {
             name,
@@ -308,4 +2201,225 @@ impl Collection {
             default_ttl,
         }
     }
+
+    /// Gets the `n` records of this collection inserted last if `latest`, or else first, for the given operation
+    fn get_by_insertion_order(
+        &self,
+        op: &'static str,
+        n: usize,
+        latest: bool,
+    ) -> PyResult<Vec<Py<PyAny>>> {
+        self.meta.ensure_tracks_insertion_order()?;
+        if n == 0 {
+            self.meta.metrics.record(op, 0);
+            return Ok(vec![]);
+        }
+
+        let ids = utils::get_ids_by_insertion_order(&self.pool, &self.name, n, latest)?;
+        if ids.is_empty() {
+            self.meta.metrics.record(op, 0);
+            return Ok(vec![]);
+        }
+
+        self.meta.metrics.record_result(
+            op,
+            utils::get_records_by_id(&self.pool, &self.name, &self.meta, &ids),
+        )
+    }
+}
+
+/// The iterator returned by `Collection.iter_all()`
+#[pyclass]
+pub(crate) struct RecordIterator {
+    name: String,
+    meta: CollectionMeta,
+    pool: pools::RedisPool,
+    batch_size: usize,
+    /// The SCAN cursor of the next batch, None once the scan is complete
+    scan_cursor: Option<u64>,
+    batch: VecDeque<Py<PyAny>>,
+}
+
+#[pymethods]
+impl RecordIterator {
+    fn __iter__(slf: PyRef<'_, Self>) -> PyRef<'_, Self> {
+        slf
+    }
+
+    /// Returns the next record, reading the next batch of records when the current one is exhausted.
+    /// Batches in which SCAN finds no records are skipped
+    fn __next__(&mut self) -> PyResult<Option<Py<PyAny>>> {
+        loop {
+            if let Some(record) = self.batch.pop_front() {
+                return Ok(Some(record));
+            }
+
+            let scan_cursor = match self.scan_cursor {
+                None => return Ok(None),
+                Some(scan_cursor) => scan_cursor,
+            };
+            let (next_cursor, ids) =
+                utils::scan_record_ids(&self.pool, &self.name, scan_cursor, self.batch_size)?;
+            self.scan_cursor = (next_cursor != 0).then_some(next_cursor);
+            if !ids.is_empty() {
+                let records = utils::get_records_by_id(&self.pool, &self.name, &self.meta, &ids)?;
+                self.meta.metrics.record("iter_all", records.len());
+                self.batch.extend(records);
+            }
+        }
+    }
+}
+
+#[pyclass(subclass)]
+pub(crate) struct View {
+    pub(crate) source: String,
+    pub(crate) view: ViewDefinition,
+    pub(crate) meta: CollectionMeta,
+    pub(crate) pool: pools::RedisPool,
+}
+
+#[pymethods]
+impl View {
+    /// Gets the record of the view that corresponds to the given id as a dictionary
+    pub(crate) fn get_one(&self, id: &str) -> PyResult<Py<PyAny>> {
+        let mut records: Vec<Py<PyAny>> = utils::get_partial_records_by_id(
+            &self.pool,
+            &self.view.get_key_space(&self.source),
+            &self.meta,
+            &vec![id.to_string()],
+            &self.view.projection,
+            false,
+        )?;
+        match records.pop() {
+            None => Python::with_gil(|py| Ok(py.None())),
+            Some(record) => Ok(record),
+        }
+    }
+
+    /// Returns the records of the view whose ids are as given, as dictionaries
+    pub(crate) fn get_many(&self, ids: Vec<String>) -> PyResult<Vec<Py<PyAny>>> {
+        utils::get_partial_records_by_id(
+            &self.pool,
+            &self.view.get_key_space(&self.source),
+            &self.meta,
+            &ids,
+            &self.view.projection,
+            false,
+        )
+    }
+
+    /// Returns all the records in the view, as dictionaries
+    pub(crate) fn get_all(&self) -> PyResult<Vec<Py<PyAny>>> {
+        utils::get_all_partial_records_in_collection(
+            &self.pool,
+            &self.view.get_key_space(&self.source),
+            &self.meta,
+            &self.view.projection,
+            false,
+            Page::default(),
+        )
+    }
+
+    /// Rebuilds the view from all the records of its source collection, returning the number of
+    /// records in the view. This is needed for records that were added before the view was created.
+    /// At most `max_ops_per_sec` records are scanned per second if given
+    #[args(max_ops_per_sec = "None")]
+    pub(crate) fn rebuild(&self, py: Python, max_ops_per_sec: Option<f64>) -> PyResult<u64> {
+        let mut throttle = Throttle::new(max_ops_per_sec)?;
+        // the GIL is released so that other threads are not blocked while the scan is throttled
+        py.allow_threads(|| {
+            utils::rebuild_view(&self.pool, &self.source, &self.view, &mut throttle)
+        })
+    }
+}
+
+impl View {
+    /// Instantiates a new view. This is not accessible to python and thus a view
+    /// cannot be directly instantiated in python
+    pub(crate) fn new(
+        source: String,
+        view: ViewDefinition,
+        meta: CollectionMeta,
+        pool: pools::RedisPool,
+    ) -> Self {
+        View {
+            source,
+            view,
+            meta,
+            pool,
+        }
+    }
+}
+
+#[pyclass(subclass)]
+pub(crate) struct Link {
+    pub(crate) link: LinkDefinition,
+    pub(crate) left_meta: CollectionMeta,
+    pub(crate) right_meta: CollectionMeta,
+    pub(crate) pool: pools::RedisPool,
+}
+
+#[pymethods]
+impl Link {
+    /// Links the record of the given id in the left collection to that of the given id in the right
+    /// collection. Linking records that are already linked does nothing
+    pub(crate) fn link(&self, left_id: &str, right_id: &str) -> PyResult<()> {
+        let pipe = self.link.generate_link_pipeline(left_id, right_id);
+        utils::update_links(&self.pool, &self.left_meta, &pipe)
+    }
+
+    /// Unlinks the record of the given id in the left collection from that of the given id in the right
+    /// collection. Unlinking records that are not linked does nothing
+    pub(crate) fn unlink(&self, left_id: &str, right_id: &str) -> PyResult<()> {
+        let pipe = self.link.generate_unlink_pipeline(left_id, right_id);
+        utils::update_links(&self.pool, &self.left_meta, &pipe)
+    }
+
+    /// Returns the records of the left collection linked to the record of the given id in the right
+    /// collection, in no particular order. Linked records that no longer exist are skipped
+    pub(crate) fn left_of(&self, right_id: &str) -> PyResult<Vec<Py<PyAny>>> {
+        let ids = utils::get_linked_ids(&self.pool, &self.link.generate_left_key(right_id))?;
+        if ids.is_empty() {
+            self.left_meta.metrics.record("left_of", 0);
+            return Ok(vec![]);
+        }
+
+        self.left_meta.metrics.record_result(
+            "left_of",
+            utils::get_records_by_id(&self.pool, &self.link.left, &self.left_meta, &ids),
+        )
+    }
+
+    /// Returns the records of the right collection linked to the record of the given id in the left
+    /// collection, in no particular order. Linked records that no longer exist are skipped
+    pub(crate) fn right_of(&self, left_id: &str) -> PyResult<Vec<Py<PyAny>>> {
+        let ids = utils::get_linked_ids(&self.pool, &self.link.generate_right_key(left_id))?;
+        if ids.is_empty() {
+            self.right_meta.metrics.record("right_of", 0);
+            return Ok(vec![]);
+        }
+
+        self.right_meta.metrics.record_result(
+            "right_of",
+            utils::get_records_by_id(&self.pool, &self.link.right, &self.right_meta, &ids),
+        )
+    }
+}
+
+impl Link {
+    /// Instantiates a new link handle. This is not accessible to python and thus a link
+    /// cannot be directly instantiated in python
+    pub(crate) fn new(
+        link: LinkDefinition,
+        left_meta: CollectionMeta,
+        right_meta: CollectionMeta,
+        pool: pools::RedisPool,
+    ) -> Self {
+        Link {
+            link,
+            left_meta,
+            right_meta,
+            pool,
+        }
+    }
 }
@@ -0,0 +1,58 @@
+use std::time::{Duration, Instant};
+
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+
+/// Limits the number of operations per second of a maintenance job e.g. retention or compaction,
+/// so that it can run against a busy redis without hurting the latency of other clients. Each record
+/// scanned, read or written by the job is an operation
+#[derive(Clone, Debug)]
+pub(crate) struct Throttle {
+    max_ops_per_sec: Option<f64>,
+    started_at: Instant,
+    ops: u64,
+}
+
+impl Throttle {
+    /// Creates a throttle of the given maximum number of operations per second, or one that
+    /// does not throttle if none is given
+    pub(crate) fn new(max_ops_per_sec: Option<f64>) -> PyResult<Self> {
+        if let Some(max_ops_per_sec) = max_ops_per_sec {
+            if !(max_ops_per_sec > 0.0 && max_ops_per_sec.is_finite()) {
+                return Err(PyValueError::new_err(format!(
+                    "max_ops_per_sec should be a number greater than 0, got {}",
+                    max_ops_per_sec
+                )));
+            }
+        }
+
+        Ok(Self {
+            max_ops_per_sec,
+            started_at: Instant::now(),
+            ops: 0,
+        })
+    }
+
+    /// Starts counting the operations afresh e.g. for another run of a periodic job
+    pub(crate) fn restart(&mut self) {
+        self.started_at = Instant::now();
+        self.ops = 0;
+    }
+
+    /// Gets the number of operations to do in each batch, no more than the given default
+    /// nor than are allowed per second
+    pub(crate) fn batch_size(&self, default: usize) -> usize {
+        self.max_ops_per_sec.map_or(default, |max_ops_per_sec| {
+            (max_ops_per_sec.ceil() as usize).clamp(1, default)
+        })
+    }
+
+    /// Counts the given number of operations as done, returning how long to wait before doing
+    /// any more so that the maximum rate is not exceeded
+    pub(crate) fn throttle(&mut self, ops: usize) -> Option<Duration> {
+        let max_ops_per_sec = self.max_ops_per_sec?;
+        self.ops += ops as u64;
+        let due = Duration::from_secs_f64(self.ops as f64 / max_ops_per_sec);
+        due.checked_sub(self.started_at.elapsed())
+    }
+}
@@ -0,0 +1,89 @@
+use std::time::Duration;
+
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+
+use crate::parsers;
+use crate::utils;
+
+const AUDIT_TTLS_SCRIPT: &str = r"local result = redis.call('SCAN', ARGV[1], 'MATCH', ARGV[2], 'COUNT', ARGV[3]) local threshold = tonumber(ARGV[4]) local imminent = tonumber(ARGV[5]) local records = 0 local no_ttl = 0 local below_threshold = 0 local imminent_expiry = 0 for _, key in ipairs(result[2]) do if redis.call('TYPE', key).ok == 'hash' then records = records + 1 local ttl = redis.call('PTTL', key) if ttl == -1 then no_ttl = no_ttl + 1 elseif ttl >= 0 then if ttl < threshold then below_threshold = below_threshold + 1 end if ttl < imminent then imminent_expiry = imminent_expiry + 1 end end end end return {result[1], records, no_ttl, below_threshold, imminent_expiry}";
+const DEFAULT_THRESHOLD: Duration = Duration::from_secs(60 * 60);
+const DEFAULT_IMMINENT_EXPIRY: Duration = Duration::from_secs(60);
+
+/// The counts of the records of a collection by their ttls, as got by ttl_audit()
+#[derive(Clone, Copy, Debug, Default)]
+pub(crate) struct TtlAudit {
+    records: u64,
+    no_ttl: u64,
+    below_threshold: u64,
+    imminent_expiry: u64,
+}
+
+/// The ttls below which records are counted by ttl_audit()
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct TtlThresholds {
+    threshold: Duration,
+    imminent_expiry: Duration,
+}
+
+impl TtlThresholds {
+    /// Creates the thresholds from the python timedeltas or numbers of seconds, if given
+    pub(crate) fn from_py(threshold: Option<&PyAny>, imminent: Option<&PyAny>) -> PyResult<Self> {
+        Ok(Self {
+            threshold: threshold
+                .map(parsers::extract_duration)
+                .transpose()?
+                .unwrap_or(DEFAULT_THRESHOLD),
+            imminent_expiry: imminent
+                .map(parsers::extract_duration)
+                .transpose()?
+                .unwrap_or(DEFAULT_IMMINENT_EXPIRY),
+        })
+    }
+
+    /// Generates the command that audits the ttls of the records of the given collection in the SCAN batch
+    /// of the given count at the given cursor. It returns the next cursor followed by the counts of the batch
+    pub(crate) fn generate_audit_cmd(
+        &self,
+        collection_name: &str,
+        cursor: &str,
+        count: usize,
+    ) -> redis::Cmd {
+        let mut cmd = redis::cmd("EVAL");
+        cmd.arg(AUDIT_TTLS_SCRIPT)
+            .arg(0)
+            .arg(cursor)
+            .arg(utils::generate_collection_key_pattern(collection_name))
+            .arg(count)
+            .arg(utils::duration_in_millis(&self.threshold))
+            .arg(utils::duration_in_millis(&self.imminent_expiry));
+        cmd
+    }
+}
+
+impl TtlAudit {
+    /// Adds the counts of a SCAN batch, as returned by the audit command after the cursor
+    pub(crate) fn add(
+        &mut self,
+        (records, no_ttl, below_threshold, imminent_expiry): (u64, u64, u64, u64),
+    ) {
+        self.records += records;
+        self.no_ttl += no_ttl;
+        self.below_threshold += below_threshold;
+        self.imminent_expiry += imminent_expiry;
+    }
+
+    /// Converts the audit into a python dictionary with the keys: "records", the number of records;
+    /// "no_ttl", the number that never expire; "below_threshold", the number whose ttl is below the threshold;
+    /// and "imminent_expiry", the number whose ttl is below the imminent expiry
+    pub(crate) fn to_py(self) -> PyResult<Py<PyAny>> {
+        Python::with_gil(|py| {
+            let audit = PyDict::new(py);
+            audit.set_item("records", self.records)?;
+            audit.set_item("no_ttl", self.no_ttl)?;
+            audit.set_item("below_threshold", self.below_threshold)?;
+            audit.set_item("imminent_expiry", self.imminent_expiry)?;
+            Ok(audit.into_py(py))
+        })
+    }
+}
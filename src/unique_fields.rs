@@ -0,0 +1,216 @@
+use pyo3::create_exception;
+use pyo3::exceptions::{PyException, PyValueError};
+use pyo3::prelude::*;
+
+use crate::expiry::Expiry;
+use crate::store::CollectionMeta;
+use crate::utils;
+
+// Raised when an insert or update would give a unique field of a record a value that another record already has
+create_exception!(orredis, UniqueViolationError, PyException);
+
+/// The code of the error replied by `UPDATE_UNIQUE_VALUES_SCRIPT` when a write would give a record a value
+/// that another record has
+const UNIQUE_VIOLATION_CODE: &str = "UNIQUEVIOLATION";
+
+const EXPIRE_RESERVATIONS_SCRIPT: &str = r"for i = 4, #ARGV, 2 do redis.call(ARGV[2], ARGV[1] .. ARGV[i] .. '_%&_' .. normalize(ARGV[i], ARGV[i + 1]), ARGV[3]) end";
+const UPDATE_UNIQUE_VALUES_SCRIPT: &str = r"local prefix, mode = ARGV[1], ARGV[2] local first, step = 3, 3 if mode == 'remove' then step = 2 end if mode == 'reserve' then first = 4 local guard, undo = KEYS[#KEYS - 1], KEYS[#KEYS] if redis.call('EXISTS', guard) == 1 then return {} end local pending = {} for i = first, #ARGV, step do local id, field, value = ARGV[i], ARGV[i + 1], ARGV[i + 2] local key = prefix .. field .. '_%&_' .. normalize(field, value) local owner = pending[key] or redis.call('GET', key) if owner and owner ~= id then local usage = redis.call('HMGET', undo, 'quota', 'records', 'bytes') if usage[1] then redis.call('HINCRBY', usage[1], 'records', -tonumber(usage[2])) redis.call('HINCRBY', usage[1], 'bytes', -tonumber(usage[3])) end redis.call('SET', guard, 1, 'PX', 60000) return redis.error_reply(string.format('UNIQUEVIOLATION the value %q of the unique field %q is already taken by the record %q of %s', value, field, owner, ARGV[3])) end pending[key] = id end end local j = 0 for i = first, #ARGV, step do j = j + 1 local id, field = ARGV[i], ARGV[i + 1] local new = false if mode ~= 'remove' then new = normalize(field, ARGV[i + 2]) end local old = normalize(field, redis.call('HGET', KEYS[j], field)) if old and old ~= new then local old_key = prefix .. field .. '_%&_' .. old if redis.call('GET', old_key) == id then redis.call('DEL', old_key) end end if new then local key = prefix .. field .. '_%&_' .. new if redis.call('GET', key) ~= id then redis.call('SET', key, id) local ttl = redis.call('PTTL', KEYS[j]) if ttl > 0 then redis.call('PEXPIRE', key, ttl) end end end end return {}";
+
+/// Ensures the given fields can be unique i.e. they are non-nested, non-counter fields of the collection
+pub(crate) fn validate_unique_fields(meta: &CollectionMeta, fields: &[String]) -> PyResult<()> {
+    for field in fields {
+        meta.ensure_scalar_field(field)?;
+        if meta.counter_fields.iter().any(|v| v == field) {
+            return Err(PyValueError::new_err(format!(
+                "{:?} is a counter field yet counter fields cannot be unique",
+                field
+            )));
+        }
+    }
+
+    Ok(())
+}
+
+/// Constructs the key of the reservation of the given value of the given unique field of the given collection
+#[inline]
+pub(crate) fn generate_reservation_key(collection_name: &str, field: &str, value: &str) -> String {
+    utils::generate_auxiliary_key(
+        collection_name,
+        "unique",
+        &format!("{}_%&_{}", field, value),
+    )
+}
+
+/// Constructs the prefix of the keys of the reservations of the unique values of the given collection
+#[inline]
+fn generate_reservation_key_prefix(collection_name: &str) -> String {
+    utils::generate_auxiliary_key(collection_name, "unique", "")
+}
+
+/// Extracts the (primary key, id, field, value) entries of the values of the unique fields of the given records
+/// of the given collection. Nested records are skipped since their collections are not written to directly
+fn extract_entries<'a>(
+    collection_name: &str,
+    meta: &'a CollectionMeta,
+    records: &'a [(String, Vec<(String, String)>)],
+) -> Vec<(&'a String, &'a str, &'a String, &'a String)> {
+    if meta.unique_fields.is_empty() {
+        return vec![];
+    }
+
+    let key_prefix = utils::generate_key_prefix(collection_name);
+    records
+        .iter()
+        .filter_map(|(pk, record)| Some((pk, pk.strip_prefix(&key_prefix)?, record)))
+        .filter(|(_, id, _)| !id.is_empty())
+        .flat_map(|(pk, id, record)| {
+            record
+                .iter()
+                .filter(|(field, _)| meta.unique_fields.contains(field))
+                .map(move |(field, value)| (pk, id, field, value))
+        })
+        .collect()
+}
+
+/// Checks whether the given records of the given collection have values of unique fields, which writing them
+/// reserves, in which case the write cannot be buffered
+pub(crate) fn has_unique_values(
+    collection_name: &str,
+    meta: &CollectionMeta,
+    records: &[(String, Vec<(String, String)>)],
+) -> bool {
+    !extract_entries(collection_name, meta, records).is_empty()
+}
+
+/// Generates the command that reserves the values of the unique fields of the given records of the given
+/// collection, or None if the records have no unique values. It is run right before the transaction of the write,
+/// whose guard keys are given, after the other checks of the write, doing nothing if any of them failed. If any of
+/// the values is reserved by another record, the command takes back the changes of the other checks saved at
+/// the undo key, sets the guard key, aborting the transaction, and fails with the error converted by
+/// `to_unique_violation_error()`
+pub(crate) fn generate_reserve_cmd(
+    collection_name: &str,
+    meta: &CollectionMeta,
+    records: &[(String, Vec<(String, String)>)],
+    guard_key: &str,
+    undo_key: &str,
+) -> Option<redis::Cmd> {
+    let entries = extract_entries(collection_name, meta, records);
+    if entries.is_empty() {
+        return None;
+    }
+
+    let mut cmd = redis::cmd("EVAL");
+    cmd.arg(meta.normalizers.wrap(UPDATE_UNIQUE_VALUES_SCRIPT))
+        .arg(entries.len() + 2);
+    for (pk, _, _, _) in &entries {
+        cmd.arg(pk);
+    }
+
+    cmd.arg(guard_key)
+        .arg(undo_key)
+        .arg(generate_reservation_key_prefix(collection_name))
+        .arg("reserve")
+        .arg(collection_name);
+    for (_, id, field, value) in &entries {
+        cmd.arg(id).arg(field).arg(value);
+    }
+    Some(cmd)
+}
+
+/// Adds to the pipeline the command that moves the reservations of the record of the given id to the values
+/// of its unique fields among the given fields, before the fields are written, with the given ttl if any.
+/// The values have been checked and reserved by the command of `generate_reserve_cmd()` right before
+/// the transaction, so they are reserved as they are
+pub(crate) fn add_update_cmd(
+    pipe: &mut redis::Pipeline,
+    collection_name: &str,
+    meta: &CollectionMeta,
+    id: &str,
+    fields: &[(&String, &String)],
+    ttl: &Option<Expiry>,
+) {
+    let unique: Vec<&(&String, &String)> = fields
+        .iter()
+        .filter(|(field, _)| meta.unique_fields.contains(field))
+        .collect();
+    if unique.is_empty() {
+        return;
+    }
+
+    let record_key = utils::generate_hash_key(collection_name, id, meta.case_insensitive_keys);
+    pipe.cmd("EVAL")
+        .arg(meta.normalizers.wrap(UPDATE_UNIQUE_VALUES_SCRIPT))
+        .arg(unique.len());
+    for _ in &unique {
+        pipe.arg(&record_key);
+    }
+
+    pipe.arg(generate_reservation_key_prefix(collection_name))
+        .arg("set");
+    for (field, value) in &unique {
+        pipe.arg(id).arg(field).arg(value);
+    }
+
+    if let Some(expiry) = ttl {
+        // the keys of the reservations of normalized values are only known to the scripts
+        let (normalized, plain): (Vec<_>, Vec<_>) = unique
+            .into_iter()
+            .partition(|(field, _)| meta.normalizers.contains(field));
+        for (field, value) in plain {
+            expiry.add_cmd(
+                pipe,
+                &generate_reservation_key(collection_name, field, value),
+            );
+        }
+        if !normalized.is_empty() {
+            let (cmd, time) = expiry.to_cmd_args();
+            pipe.cmd("EVAL")
+                .arg(meta.normalizers.wrap(EXPIRE_RESERVATIONS_SCRIPT))
+                .arg(0)
+                .arg(generate_reservation_key_prefix(collection_name))
+                .arg(cmd)
+                .arg(time)
+                .arg(normalized);
+        }
+    }
+}
+
+/// Adds to the pipeline the command that releases the reservations of the values of the unique fields
+/// of the record of the given id, before it is deleted
+pub(crate) fn add_remove_cmd(
+    pipe: &mut redis::Pipeline,
+    collection_name: &str,
+    meta: &CollectionMeta,
+    id: &str,
+) {
+    if meta.unique_fields.is_empty() {
+        return;
+    }
+
+    let record_key = utils::generate_hash_key(collection_name, id, meta.case_insensitive_keys);
+    pipe.cmd("EVAL")
+        .arg(meta.normalizers.wrap(UPDATE_UNIQUE_VALUES_SCRIPT))
+        .arg(meta.unique_fields.len());
+    for _ in &meta.unique_fields {
+        pipe.arg(&record_key);
+    }
+
+    pipe.arg(generate_reservation_key_prefix(collection_name))
+        .arg("remove");
+    for field in &meta.unique_fields {
+        pipe.arg(id).arg(field);
+    }
+    pipe.ignore();
+}
+
+/// Converts the error of the command of `generate_reserve_cmd()` when a value is reserved by another record into
+/// a UniqueViolationError, or returns None if the given error is another one
+pub(crate) fn to_unique_violation_error(e: &redis::RedisError) -> Option<PyErr> {
+    match (e.code(), e.detail()) {
+        (Some(UNIQUE_VIOLATION_CODE), Some(detail)) => {
+            Some(UniqueViolationError::new_err(detail.to_string()))
+        }
+        _ => None,
+    }
+}
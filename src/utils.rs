@@ -1,19 +1,87 @@
-use std::collections::HashMap;
+use std::borrow::Cow;
+use std::collections::{HashMap, HashSet};
+use std::io::Write;
 use std::ops::DerefMut;
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
-use pyo3::exceptions::{PyConnectionError, PyKeyError, PyValueError};
+use pyo3::exceptions::{PyConnectionError, PyKeyError, PyRuntimeError, PyValueError};
 use pyo3::prelude::*;
-use pyo3::types::{timezone_utc, IntoPyDict, PyDate, PyDateTime};
+use pyo3::types::{timezone_utc, PyDate, PyDateTime, PyDict};
 
+use crate::aggregations::{self, AggregateOp};
+use crate::batching::InsertRecords;
+use crate::bloom_filters::{self, ADD_TO_BLOOM_FILTER_SCRIPT};
+use crate::coercions;
+use crate::compaction::{self, COMPACT_BATCH_SIZE};
+use crate::compression::{self, TrainingStats};
+use crate::conditional_writes;
+use crate::constraints;
+use crate::converters::ConverterTable;
+use crate::expiry::Expiry;
+use crate::exports::{self, ExportReader, ExportWriter, EXPORT_BUFFER_SIZE};
+use crate::field_indexes;
 use crate::field_types::FieldType;
+use crate::id_generators::IdGenerator;
+use crate::key_normalization;
+use crate::latency::LatencyStats;
+use crate::list_indexes;
+use crate::lookups::Lookups;
+use crate::memory_usage::{self, MemoryUsage};
+use crate::migrations::{self, SchemaSnapshot};
+use crate::nested_updates;
+use crate::null_indexes;
+use crate::pagination::{BrowseCursor, BrowsedPage, KeysetCursor, Ordering, Page};
 use crate::parsers::redis_to_py;
+use crate::pools;
+use crate::profiling::{self, Phase};
+use crate::quotas::{self, Quota};
+use crate::range_indexes::{self, INDEX_BATCH_SIZE};
+use crate::read_errors::{OnError, ReadError};
+use crate::registries::{self, Registry};
+use crate::repairs::{self, Repair, REPAIR_BATCH_SIZE};
+use crate::retention::{self, RetentionPolicy};
 use crate::schema::Schema;
+use crate::snapshots;
 use crate::store::CollectionMeta;
+use crate::throttle::Throttle;
+use crate::ttl_audit::{TtlAudit, TtlThresholds};
+use crate::unique_fields;
+use crate::vectors;
+use crate::verification;
+use crate::versioning::{self, RawFields, VERSION_FIELD};
+use crate::views::ViewDefinition;
+use crate::write_buffer::WriteError;
 
-const SELECT_SOME_FIELDS_FOR_ALL_IDS_SCRIPT: &str = r"local filtered = {} local cursor = '0' local table_unpack = table.unpack or unpack local columns = {} local nested_columns = {} local args_tracker = {} for i, k in ipairs(ARGV) do if i > 1 then if args_tracker[k] then nested_columns[k] = true else  table.insert(columns, k) args_tracker[k] = true end end end repeat local result = redis.call('SCAN', cursor, 'MATCH', ARGV[1]) for _, key in ipairs(result[2]) do if redis.call('TYPE', key).ok == 'hash' then  local data = redis.call('HMGET', key, table_unpack(columns)) local parsed_data = {} for i, v in ipairs(data) do table.insert(parsed_data, columns[i]) if nested_columns[columns[i]] then v = redis.call('HGETALL', v) end table.insert(parsed_data, v) end table.insert(filtered, parsed_data) end end cursor = result[1] until (cursor == '0') return filtered";
-const SELECT_ALL_FIELDS_FOR_ALL_IDS_SCRIPT: &str = r"local filtered = {} local cursor = '0' local nested_fields = {} for i, key in ipairs(ARGV) do if i > 1 then nested_fields[key] = true end end repeat local result = redis.call('SCAN', cursor, 'MATCH', ARGV[1]) for _, key in ipairs(result[2]) do if redis.call('TYPE', key).ok == 'hash' then local parent = redis.call('HGETALL', key) for i, k in ipairs(parent) do if nested_fields[k] then local nested = redis.call('HGETALL', parent[i + 1]) parent[i + 1] = nested end end table.insert(filtered, parent) end end cursor = result[1] until (cursor == '0') return filtered";
+const SELECT_SOME_FIELDS_FOR_ALL_IDS_SCRIPT: &str = r"local filtered = {} local cursor = '0' local skip = tonumber(ARGV[2]) local limit = tonumber(ARGV[3]) local table_unpack = table.unpack or unpack local columns = {  } local nested_columns = {} local args_tracker = {} local projections = {} local function project(key, paths) local fields = {} local is_field = {} local nested = {} for _, path in ipairs(paths) do local head, rest = string.match(path, '^([^.]+)%.(.+)$') if head then if not nested[head] then nested[head] = {} end table.insert(nested[head], rest) else table.insert(fields, path) is_field[path] = true end end local projected = {} if #fields > 0 then local values = redis.call('HMGET', key, table_unpack(fields)) for i, v in ipairs(values) do if v then table.insert(projected, fields[i]) table.insert(projected, v) end end end for head, rest in pairs(nested) do local nested_key = redis.call('HGET', key, head) if nested_key and not is_field[head] then table.insert(projected, head) table.insert(projected, project(nested_key, rest)) end end return projected end if limit == 0 then return filtered end for i, k in ipairs(ARGV) do if i > 3 then local head, rest = string.match(k, '^([^.]+)%.(.+)$') if head then if not projections[head] then projections[head] = {} end table.insert(projections[head], rest) elseif args_tracker[k] then nested_columns[k] = true else table.insert(columns, k) args_tracker[k] = true end end end for head, _ in pairs(projections) do if not args_tracker[head] then table.insert(columns, head) args_tracker[head] = true end end repeat local result = redis.call('SCAN', cursor, 'MATCH', ARGV[1]) for _, key in ipairs(result[2]) do if redis.call('TYPE', key).ok == 'hash' then if skip > 0 then skip = skip - 1 else local data = redis.call('HMGET', key, table_unpack(columns)) local parsed_data = {} for i, v in ipairs(data) do table.insert(parsed_data, columns[i]) if nested_columns[columns[i]] and v then v = redis.call('HGETALL', v) elseif projections[columns[i]] and v then v = project(v, projections[columns[i]]) end table.insert(parsed_data, v) end table.insert(filtered, parsed_data) if #filtered == limit then return filtered end end end end cursor = result[1] until (cursor == '0') return filtered";
+const SELECT_ALL_FIELDS_FOR_ALL_IDS_SCRIPT: &str = r"local filtered = {} local cursor = '0' local skip = tonumber(ARGV[2]) local limit = tonumber(ARGV[3]) local nested_fields = {} if limit == 0 then return filtered end for i, key in ipairs(ARGV) do if i > 3 then nested_fields[key] = true end end repeat local result = redis.call('SCAN', cursor, 'MATCH', ARGV[1]) for _, key in ipairs(result[2]) do if redis.call('TYPE', key).ok == 'hash' then if skip > 0 then skip = skip - 1 else local parent = redis.call('HGETALL', key) for i, k in ipairs(parent) do if nested_fields[k] then local nested = redis.call('HGETALL', parent[i + 1]) parent[i + 1] = nested end end table.insert(filtered, parent) if #filtered == limit then return filtered end end end end cursor = result[1] until (cursor == '0') return filtered";
+const SELECT_MATCHING_RECORDS_SCRIPT: &str = r"local filtered = {} local cursor = '0' local table_unpack = table.unpack or unpack local skip = tonumber(ARGV[2]) local limit = tonumber(ARGV[3]) local n = tonumber(ARGV[4]) local nested_fields = {} local filter_fields = {} local filter_values = {} if limit == 0 then return filtered end for i = 5, n + 4 do nested_fields[ARGV[i]] = true end for i = n + 5, #ARGV, 2 do table.insert(filter_fields, ARGV[i]) table.insert(filter_values, ARGV[i + 1]) end repeat local result = redis.call('SCAN', cursor, 'MATCH', ARGV[1]) for _, key in ipairs(result[2]) do if redis.call('TYPE', key).ok == 'hash' then local values = redis.call('HMGET', key, table_unpack(filter_fields)) local matches = true for i, v in ipairs(filter_values) do if values[i] ~= v then matches = false break end end if matches and skip > 0 then skip = skip - 1 elseif matches then local parent = redis.call('HGETALL', key) for i, k in ipairs(parent) do if nested_fields[k] then local nested = redis.call('HGETALL', parent[i + 1]) parent[i + 1] = nested end end table.insert(filtered, parent) if #filtered == limit then return filtered end end end end cursor = result[1] until (cursor == '0') return filtered";
 const SELECT_ALL_FIELDS_FOR_SOME_IDS_SCRIPT: &str = r"local result = {} local nested_fields = {} for _, key in ipairs(ARGV) do nested_fields[key] = true end for _, key in ipairs(KEYS) do local parent = redis.call('HGETALL', key) for i, k in ipairs(parent) do if nested_fields[k] then local nested = redis.call('HGETALL', parent[i + 1]) parent[i + 1] = nested end end table.insert(result, parent) end return result";
-const SELECT_SOME_FIELDS_FOR_SOME_IDS_SCRIPT: &str = r"local result = {} local table_unpack = table.unpack or unpack local columns = { } local nested_columns = {} local args_tracker = {} for i, k in ipairs(ARGV) do if args_tracker[k] then nested_columns[k] = true else table.insert(columns, k) args_tracker[k] = true end end for _, key in ipairs(KEYS) do local data = redis.call('HMGET', key, table_unpack(columns)) local parsed_data = {} for i, v in ipairs(data) do if v then table.insert(parsed_data, columns[i]) if nested_columns[columns[i]] then v = redis.call('HGETALL', v) end table.insert(parsed_data, v) end end table.insert(result, parsed_data) end return result";
+const SELECT_SOME_FIELDS_FOR_SOME_IDS_SCRIPT: &str = r"local result = {} local table_unpack = table.unpack or unpack local columns = {  } local nested_columns = {} local args_tracker = {} local projections = {} local function project(key, paths) local fields = {} local is_field = {} local nested = {} for _, path in ipairs(paths) do local head, rest = string.match(path, '^([^.]+)%.(.+)$') if head then if not nested[head] then nested[head] = {} end table.insert(nested[head], rest) else table.insert(fields, path) is_field[path] = true end end local projected = {} if #fields > 0 then local values = redis.call('HMGET', key, table_unpack(fields)) for i, v in ipairs(values) do if v then table.insert(projected, fields[i]) table.insert(projected, v) end end end for head, rest in pairs(nested) do local nested_key = redis.call('HGET', key, head) if nested_key and not is_field[head] then table.insert(projected, head) table.insert(projected, project(nested_key, rest)) end end return projected end for i, k in ipairs(ARGV) do local head, rest = string.match(k, '^([^.]+)%.(.+)$') if head then if not projections[head] then projections[head] = {} end table.insert(projections[head], rest) elseif args_tracker[k] then nested_columns[k] = true else table.insert(columns, k) args_tracker[k] = true end end for head, _ in pairs(projections) do if not args_tracker[head] then table.insert(columns, head) args_tracker[head] = true end end for _, key in ipairs(KEYS) do local data = redis.call('HMGET', key, table_unpack(columns)) local parsed_data = {} for i, v in ipairs(data) do if v then table.insert(parsed_data, columns[i]) if nested_columns[columns[i]] then v = redis.call('HGETALL', v) elseif projections[columns[i]] then v = project(v, projections[columns[i]]) end table.insert(parsed_data, v) end end table.insert(result, parsed_data) end return result";
+const TRACK_CARDINALITY_SCRIPT: &str = r"redis.call('SADD', KEYS[2], ARGV[4]) local result = redis.call('SCAN', ARGV[1], 'MATCH', ARGV[3], 'COUNT', ARGV[2]) local added = 0 for _, key in ipairs(result[2]) do if redis.call('TYPE', key).ok == 'hash' then local value = redis.call('HGET', key, ARGV[4]) if value then redis.call('PFADD', KEYS[1], value) added = added + 1 end end end return {result[1], #result[2], added}";
+const ADD_TO_CARDINALITY_TRACKERS_SCRIPT: &str = r"local fields = redis.call('SMEMBERS', KEYS[1]) if #fields == 0 then return 0 end local table_unpack = table.unpack or unpack for i = 2, #KEYS do local values = redis.call('HMGET', KEYS[i], table_unpack(fields)) for j, field in ipairs(fields) do if values[j] then redis.call('PFADD', ARGV[1] .. field, values[j]) end end end return 1";
+const SELECT_DISTINCT_VALUES_SCRIPT: &str = r"local distinct = {} local seen = {} local cursor = '0' repeat local result = redis.call('SCAN', cursor, 'MATCH', ARGV[1]) for _, key in ipairs(result[2]) do if redis.call('TYPE', key).ok == 'hash' then local value = redis.call('HGET', key, ARGV[2]) if value and not seen[value] then seen[value] = true table.insert(distinct, value) end end end cursor = result[1] until (cursor == '0') return distinct";
+const RECORD_CHANGE_SCRIPT: &str = r"local table_unpack = table.unpack or unpack local before = redis.call('HGETALL', KEYS[1]) redis.call('HSET', KEYS[1], table_unpack(ARGV, 3)) local after = redis.call('HGETALL', KEYS[1]) local op = 'update' if #before == 0 then op = 'insert' end local entry = {'op', op, 'id', ARGV[1]} for i = 1, #before, 2 do table.insert(entry, 'before.' .. before[i]) table.insert(entry, before[i + 1]) end for i = 1, #after, 2 do table.insert(entry, 'after.' .. after[i]) table.insert(entry, after[i + 1]) end return redis.call('XADD', KEYS[2], 'MAXLEN', '~', ARGV[2], '*', table_unpack(entry))";
+const RECORD_DELETION_SCRIPT: &str = r"local table_unpack = table.unpack or unpack local before = redis.call('HGETALL', KEYS[1]) if #before == 0 then return false end redis.call('DEL', KEYS[1]) local entry = {'op', 'delete', 'id', ARGV[1]} for i = 1, #before, 2 do table.insert(entry, 'before.' .. before[i]) table.insert(entry, before[i + 1]) end return redis.call('XADD', KEYS[2], 'MAXLEN', '~', ARGV[2], '*', table_unpack(entry))";
+const CLAIM_RECORD_CREATION_SCRIPT: &str = r"if redis.call('EXISTS', KEYS[1]) == 1 then return 0 end if redis.call('SET', KEYS[2], '1', 'NX', 'PX', ARGV[1]) then return 1 end return 0";
+const CLEAR_COLLECTION_SCRIPT: &str = r"local result = redis.call('SCAN', ARGV[1], 'MATCH', ARGV[3], 'COUNT', ARGV[2]) local removed, bytes = 0, 0 for _, key in ipairs(result[2]) do local kept = false for i = 6, #ARGV do if string.sub(key, 1, #ARGV[i]) == ARGV[i] then kept = true break end end if not kept then if string.sub(key, 1, #ARGV[4]) == ARGV[4] and redis.call('TYPE', key).ok == 'hash' then removed = removed + 1 if ARGV[5] ~= '' then local fields = redis.call('HGETALL', key) for j = 1, #fields do bytes = bytes + #fields[j] end end end redis.call('DEL', key) end end if ARGV[5] ~= '' and removed > 0 then redis.call('HINCRBY', ARGV[5], 'records', -removed) redis.call('HINCRBY', ARGV[5], 'bytes', -bytes) end return {result[1], #result[2], removed}";
+/// The approximate maximum number of events kept in the change stream of a collection
+pub(crate) const CHANGE_STREAM_MAX_LENGTH: u64 = 100_000;
+/// The maximum number of records converted into python objects in one hold of the gil, so that
+/// other threads e.g. that of the event loop, get the gil in between on large reads
+const RECORDS_CONVERTED_PER_GIL_HOLD: usize = 256;
+/// The number of records each worker of a parallel read reads in one script call, so that no
+/// single call blocks redis for long
+pub(crate) const PARALLEL_READ_BATCH_SIZE: usize = 500;
+/// The number of keys each run of a maintenance script asks SCAN for, unless it is throttled to fewer
+pub(crate) const SCAN_BATCH_SIZE: usize = 1000;
+/// The time in milliseconds after which the claim to create a record lapses if it is not released
+/// e.g. because the process creating the record crashed
+const CREATION_CLAIM_TIMEOUT_MILLIS: u64 = 10_000;
+
+/// A record got from redis as (its id if its primary key field was got, its fields), with the fields
+/// not yet converted into python objects
+pub(crate) type ParsedRecord<'a> = (Option<String>, RawFields<'a>);
 
 macro_rules! py_value_error {
     ($v:expr, $det:expr) => {
@@ -27,61 +95,2224 @@ macro_rules! py_key_error {
     };
 }
 
-/// Inserts the (primary key, record) tuples passed to it in a batch into the redis store
+/// Inserts the (primary key, record) tuples passed to it in a batch into the redis store,
+/// along with the given updates of nested records
 pub(crate) fn insert_records(
-    pool: &r2d2::Pool<redis::Client>,
+    pool: &pools::RedisPool,
+    collection_name: &str,
+    meta: &CollectionMeta,
     records: &Vec<(String, Vec<(String, String)>)>,
-    ttl: &Option<u64>,
+    nested_updates: &[redis::Cmd],
+    ttl: &Option<Expiry>,
 ) -> PyResult<()> {
-    let mut conn = pool
-        .get()
-        .map_err(|e| PyConnectionError::new_err(e.to_string()))?;
+    constraints::enforce_constraints(collection_name, meta, records)?;
+    refresh_registered_fields(pool, collection_name, meta)?;
+
+    let pipe = profiling::measure(Phase::Serialize, || {
+        generate_insert_pipeline(collection_name, meta, records, nested_updates, ttl)
+    });
+    if unique_fields::has_unique_values(collection_name, meta, records) {
+        apply_unbuffered_write(pool, meta, &pipe)
+    } else {
+        apply_write(pool, meta, &pipe)
+    }
+}
+
+/// Inserts the given (primary key, record) tuples of a record and its nested records, the record being the last,
+/// only if the record does not exist yet, returning whether it was created. The creation of the record is claimed
+/// atomically first, as in HSETNX, so that of many concurrent calls for the same record, only one creates it
+pub(crate) fn insert_record_if_missing(
+    pool: &pools::RedisPool,
+    collection_name: &str,
+    meta: &CollectionMeta,
+    records: &Vec<(String, Vec<(String, String)>)>,
+    ttl: &Option<Expiry>,
+) -> PyResult<bool> {
+    let (claim_cmd, release_cmd) = generate_creation_claim_cmds(collection_name, records);
+    let is_claimed: bool = {
+        let mut conn = pools::get_connection(pool)?;
+        claim_cmd
+            .query(conn.deref_mut())
+            .map_err(|e| PyConnectionError::new_err(e.to_string()))?
+    };
+    if !is_claimed {
+        return Ok(false);
+    }
+
+    // the claim is released in the transaction that creates the record, or at once if the creation fails
+    let release_cmds = [release_cmd];
+    insert_records(pool, collection_name, meta, records, &release_cmds, ttl).map_err(|e| {
+        if let Ok(mut conn) = pools::get_connection(pool) {
+            let _ = release_cmds[0].query::<()>(conn.deref_mut());
+        }
+        e
+    })?;
+    Ok(true)
+}
+
+/// Generates the command that claims the creation of the last of the given records, succeeding only if the record
+/// does not exist and no other creation of it is claimed, and the command that releases the claim
+pub(crate) fn generate_creation_claim_cmds(
+    collection_name: &str,
+    records: &[(String, Vec<(String, String)>)],
+) -> (redis::Cmd, redis::Cmd) {
+    let pk = records
+        .last()
+        .map(|(pk, _)| pk.as_str())
+        .unwrap_or_default();
+    let id = pk
+        .strip_prefix(&generate_key_prefix(collection_name))
+        .unwrap_or_default();
+    let claim_key = generate_auxiliary_key(collection_name, "claim", id);
+
+    let mut claim_cmd = redis::cmd("EVAL");
+    claim_cmd
+        .arg(CLAIM_RECORD_CREATION_SCRIPT)
+        .arg(2)
+        .arg(pk)
+        .arg(&claim_key)
+        .arg(CREATION_CLAIM_TIMEOUT_MILLIS);
+    let mut release_cmd = redis::cmd("DEL");
+    release_cmd.arg(claim_key);
+    (claim_cmd, release_cmd)
+}
+
+/// Generates the command that adds the change in the usage of the quota of the namespace of the given collection
+/// made by inserting the given records, aborting the transaction of the write, whose guard keys are given, if the
+/// quota would be exceeded, or None if the collection has no namespace. Nested records count toward the namespace
+/// of the collection they are inserted through
+fn generate_quota_usage_cmd(
+    collection_name: &str,
+    meta: &CollectionMeta,
+    records: &[(String, Vec<(String, String)>)],
+    guard_key: &str,
+    undo_key: &str,
+) -> Option<redis::Cmd> {
+    let namespace = meta.namespace.as_ref()?;
+    let key_prefix = generate_key_prefix(collection_name);
+    let hash_fields: Vec<(&str, Vec<(&String, &String)>)> = records
+        .iter()
+        .map(|(pk, record)| {
+            let id = pk.strip_prefix(&key_prefix).unwrap_or_default();
+            let fields = record
+                .iter()
+                .filter(|(k, _)| id.is_empty() || !meta.counter_fields.contains(k))
+                .map(|(k, v)| (k, v))
+                .collect();
+            (pk.as_str(), fields)
+        })
+        .collect();
+
+    Some(quotas::generate_usage_cmd(
+        &generate_quota_key(namespace),
+        guard_key,
+        undo_key,
+        namespace,
+        &hash_fields,
+    ))
+}
+
+/// Generates the pipeline that inserts the (primary key, record) tuples passed to it in a transaction,
+/// updating any auxiliary data of the collection e.g. cardinality trackers. The given updates of
+/// nested records e.g. those generated by `nested_updates::extract_nested_updates`, are part of the transaction.
+/// The quota of the namespace of the collection and the unique values of the records, if any, are checked right
+/// before the transaction, which is aborted if the quota would be exceeded or another record has any of the values
+pub(crate) fn generate_insert_pipeline(
+    collection_name: &str,
+    meta: &CollectionMeta,
+    records: &Vec<(String, Vec<(String, String)>)>,
+    nested_updates: &[redis::Cmd],
+    ttl: &Option<Expiry>,
+) -> redis::Pipeline {
     let mut pipe = redis::pipe();
+    let key_prefix = generate_key_prefix(collection_name);
+    let mut own_keys: Vec<&String> = Vec::with_capacity(records.len());
+    let range_indexes = meta.get_range_indexes();
+    let null_indexes = meta.get_null_indexes();
+    let has_vector_fields = vectors::has_vector_fields(meta);
+    let dictionary = meta.compression.get_dictionary();
+    // the records of a batch get consecutive scores so that they keep their order
+    let mut inserted_at = match meta.track_insertion_order {
+        true => Some(get_timestamp_in_micros()),
+        false => None,
+    };
+
+    // the checks of the write are made right before its transaction, which they abort if they fail
+    let guard_keys = match meta.namespace.is_some() || !meta.unique_fields.is_empty() {
+        true => Some(generate_write_guard_keys(collection_name)),
+        false => None,
+    };
+    if let Some((guard_key, undo_key)) = &guard_keys {
+        pipe.cmd("WATCH").arg(guard_key);
+        let quota_cmd =
+            generate_quota_usage_cmd(collection_name, meta, records, guard_key, undo_key);
+        let reserve_cmd = unique_fields::generate_reserve_cmd(
+            collection_name,
+            meta,
+            records,
+            guard_key,
+            undo_key,
+        );
+        for cmd in quota_cmd.into_iter().chain(reserve_cmd) {
+            pipe.add_command(cmd);
+        }
+    }
 
     // start transaction
     pipe.cmd("MULTI");
+
     for (pk, record) in records {
-        pipe.hset_multiple(pk, &record);
+        let id = pk.strip_prefix(&key_prefix).unwrap_or_default();
+        let (counters, fields): (Vec<_>, Vec<_>) = record
+            .iter()
+            .map(|(k, v)| (k, v))
+            .partition(|(k, _)| !id.is_empty() && meta.counter_fields.contains(k));
+
+        if !fields.is_empty() {
+            if !id.is_empty() {
+                field_indexes::add_update_cmd(&mut pipe, collection_name, meta, id, &fields);
+                list_indexes::add_update_cmd(&mut pipe, collection_name, meta, id, &fields);
+                unique_fields::add_update_cmd(&mut pipe, collection_name, meta, id, &fields, ttl);
+                if has_vector_fields {
+                    vectors::add_update_cmd(&mut pipe, collection_name, meta, id, &fields, ttl);
+                }
+            }
+
+            // only the records of this collection are compressed, not those nested in them
+            match dictionary.as_ref().filter(|_| !id.is_empty()) {
+                Some(dictionary) => {
+                    let fields = dictionary.compress_fields(meta, &fields);
+                    add_hset_cmd(&mut pipe, collection_name, meta, pk, id, &fields);
+                }
+                None => add_hset_cmd(&mut pipe, collection_name, meta, pk, id, &fields),
+            }
+
+            if !null_indexes.is_empty() && !id.is_empty() {
+                null_indexes::add_update_cmd(&mut pipe, collection_name, &null_indexes, pk, id);
+            }
+        }
+
+        if let Some(expiry) = ttl {
+            expiry.add_cmd(&mut pipe, pk);
+        }
+
+        for (field, value) in counters {
+            let key = generate_counter_key(collection_name, field, id, meta.case_insensitive_keys);
+            pipe.set(&key, value);
+
+            if let Some(expiry) = ttl {
+                expiry.add_cmd(&mut pipe, &key);
+            }
+        }
+
+        if !id.is_empty() {
+            for view in &meta.views {
+                view.add_refresh_cmd(&mut pipe, collection_name, id, meta.case_insensitive_keys);
+            }
+        }
+
+        if !meta.bloom_filtered_collections.is_empty() {
+            if let Some((name, _)) = pk.split_once("_%&_") {
+                if meta.bloom_filtered_collections.contains(name) {
+                    pipe.cmd("EVAL")
+                        .arg(ADD_TO_BLOOM_FILTER_SCRIPT)
+                        .arg(1)
+                        .arg(generate_bloom_filter_key(name))
+                        .arg(pk);
+                }
+            }
+        }
+
+        if !id.is_empty() {
+            own_keys.push(pk);
+        }
+
+        if !range_indexes.is_empty() && !id.is_empty() {
+            for (field, value) in record {
+                if let (true, Some(type_)) =
+                    (range_indexes.contains(field), meta.schema.get_type(field))
+                {
+                    range_indexes::add_update_cmd(
+                        &mut pipe,
+                        collection_name,
+                        field,
+                        type_,
+                        id,
+                        value,
+                    );
+                }
+            }
+        }
 
-        if let Some(life_span) = ttl {
-            pipe.expire(pk, *life_span as usize);
+        // records that are already in the index keep the time they were first inserted
+        if let (Some(score), false) = (inserted_at.as_mut(), id.is_empty()) {
+            pipe.cmd("ZADD")
+                .arg(generate_insertion_order_key(collection_name))
+                .arg("NX")
+                .arg(*score)
+                .arg(id);
+            *score += 1;
         }
     }
+
+    // the tracked fields themselves are read in the transaction, as they may have changed since they were cached
+    if !own_keys.is_empty() && meta.registered_fields.has_any(Registry::Cardinality) {
+        pipe.cmd("EVAL")
+            .arg(ADD_TO_CARDINALITY_TRACKERS_SCRIPT)
+            .arg(1 + own_keys.len())
+            .arg(generate_cardinality_fields_key(collection_name))
+            .arg(own_keys)
+            .arg(generate_cardinality_key(collection_name, ""));
+    }
+
+    for cmd in nested_updates {
+        pipe.add_command(cmd.clone());
+    }
     // end transaction
     pipe.cmd("EXEC");
 
-    pipe.query(conn.deref_mut())
+    if let Some((guard_key, undo_key)) = &guard_keys {
+        pipe.del(&[guard_key, undo_key]).ignore();
+    }
+
+    pipe
+}
+
+/// Adds to the pipeline the command that sets the given fields of the record of the given key, recording
+/// the change in the change stream of the collection if it tracks changes and the record is one of its own
+fn add_hset_cmd<V: redis::ToRedisArgs>(
+    pipe: &mut redis::Pipeline,
+    collection_name: &str,
+    meta: &CollectionMeta,
+    pk: &str,
+    id: &str,
+    fields: &[(&String, V)],
+) {
+    if !id.is_empty() && meta.track_changes {
+        pipe.cmd("EVAL")
+            .arg(RECORD_CHANGE_SCRIPT)
+            .arg(2)
+            .arg(pk)
+            .arg(generate_change_stream_key(collection_name))
+            .arg(id)
+            .arg(CHANGE_STREAM_MAX_LENGTH)
+            .arg(fields);
+    } else {
+        pipe.hset_multiple(pk, fields);
+    }
+}
+
+/// Applies the retention policy of the given collection, removing records that are older than
+/// the policy allows or capping the ttl of records to the retention period if the policy
+/// has no timestamp field. It returns the number of records affected
+pub(crate) fn apply_retention(
+    pool: &pools::RedisPool,
+    collection_name: &str,
+    policy: &RetentionPolicy,
+    throttle: &mut Throttle,
+) -> PyResult<u64> {
+    let cutoff = policy.get_cutoff();
+    run_scan_script(pool, throttle, |cursor, count| {
+        generate_retention_cmd(collection_name, policy, &cutoff, cursor, count)
+    })
+}
+
+/// Generates the command that applies the retention policy of the given collection, with the given cutoff,
+/// to the SCAN batch of the given count at the given cursor
+pub(crate) fn generate_retention_cmd(
+    collection_name: &str,
+    policy: &RetentionPolicy,
+    cutoff: &Option<(String, &'static str)>,
+    cursor: &str,
+    count: usize,
+) -> redis::Cmd {
+    let pattern = generate_collection_key_pattern(collection_name);
+    let mut cmd = redis::cmd("EVAL");
+
+    match (&policy.field, cutoff) {
+        (Some((field, _)), Some((cutoff, comparison))) => {
+            cmd.arg(retention::REMOVE_RECORDS_OLDER_THAN_SCRIPT)
+                .arg(0)
+                .arg(cursor)
+                .arg(count)
+                .arg(pattern)
+                .arg(field)
+                .arg(cutoff)
+                .arg(*comparison);
+        }
+        _ => {
+            cmd.arg(retention::CAP_RECORDS_TTL_SCRIPT)
+                .arg(0)
+                .arg(cursor)
+                .arg(count)
+                .arg(pattern)
+                .arg(duration_in_millis(&policy.period));
+        }
+    }
+
+    cmd
+}
+
+/// Runs a maintenance script on one SCAN batch at a time, so that redis is not blocked for long, until
+/// the scan is complete, doing no more operations per second than the throttle allows. The command of
+/// each batch is generated from its cursor and SCAN count, and returns the next cursor, the number of
+/// keys scanned and the number of records affected. It returns the total number of records affected
+pub(crate) fn run_scan_script(
+    pool: &pools::RedisPool,
+    throttle: &mut Throttle,
+    generate_cmd: impl Fn(&str, usize) -> redis::Cmd,
+) -> PyResult<u64> {
+    let mut conn = pools::get_blocking_connection(pool)?;
+    let mut affected: u64 = 0;
+    let mut cursor = "0".to_string();
+
+    loop {
+        let (next_cursor, scanned, count): (String, usize, u64) =
+            generate_cmd(&cursor, throttle.batch_size(SCAN_BATCH_SIZE))
+                .query(conn.deref_mut())
+                .map_err(|e| PyConnectionError::new_err(e.to_string()))?;
+        affected += count;
+        cursor = next_cursor;
+        if cursor == "0" {
+            return Ok(affected);
+        }
+
+        if let Some(delay) = throttle.throttle(scanned) {
+            thread::sleep(delay);
+        }
+    }
+}
+
+/// Resolves the ttl to use when inserting records, falling back to the default ttl
+/// and capping it to the retention period if need be
+#[inline]
+pub(crate) fn resolve_ttl(
+    ttl: Option<Expiry>,
+    default_ttl: Option<Expiry>,
+    retention: &Option<RetentionPolicy>,
+) -> Option<Expiry> {
+    let ttl = ttl.or(default_ttl);
+    match retention {
+        None => ttl,
+        Some(policy) => policy.cap_ttl(ttl),
+    }
+}
+
+/// Converts the given duration e.g. a ttl, into the whole milliseconds expected by PEXPIRE,
+/// rounding up so that a non-zero duration never expires a key immediately
+#[inline]
+pub(crate) fn duration_in_millis(duration: &Duration) -> usize {
+    ((duration.as_nanos() + 999_999) / 1_000_000) as usize
+}
+
+/// Adds the given field to the tracked fields of the collection in redis, so that every write updates
+/// its HyperLogLog, and populates the HyperLogLog with the values of all records in the collection
+pub(crate) fn track_cardinality(
+    pool: &pools::RedisPool,
+    collection_name: &str,
+    meta: &CollectionMeta,
+    field: &str,
+    throttle: &mut Throttle,
+) -> PyResult<()> {
+    run_scan_script(pool, throttle, |cursor, count| {
+        generate_track_cardinality_cmd(collection_name, field, cursor, count)
+    })?;
+    meta.registered_fields.add(Registry::Cardinality, field);
+    Ok(())
+}
+
+/// Generates the command that populates the HyperLogLog of the given field with the values
+/// of the records in the SCAN batch of the given count at the given cursor, adding the field
+/// to the tracked fields of the collection if it is not yet among them
+pub(crate) fn generate_track_cardinality_cmd(
+    collection_name: &str,
+    field: &str,
+    cursor: &str,
+    count: usize,
+) -> redis::Cmd {
+    let mut cmd = redis::cmd("EVAL");
+    cmd.arg(TRACK_CARDINALITY_SCRIPT)
+        .arg(2)
+        .arg(generate_cardinality_key(collection_name, field))
+        .arg(generate_cardinality_fields_key(collection_name))
+        .arg(cursor)
+        .arg(count)
+        .arg(generate_collection_key_pattern(collection_name))
+        .arg(field);
+    cmd
+}
+
+/// Removes all the records of the given collection, with their indexes, counters, copies in views and the other
+/// data derived from them, a SCAN batch at a time at no more operations per second than the throttle allows,
+/// leaving the other collections as they are. It returns the number of records removed
+pub(crate) fn clear_collection(
+    pool: &pools::RedisPool,
+    collection_name: &str,
+    meta: &CollectionMeta,
+    throttle: &mut Throttle,
+) -> PyResult<u64> {
+    run_scan_script(pool, throttle, |cursor, count| {
+        generate_clear_collection_cmd(collection_name, meta, cursor, count)
+    })
+}
+
+/// Generates the command that removes the keys of the given collection in the SCAN batch of the given count
+/// at the given cursor, releasing the quota used by its records. The snapshots, the change stream, the snapshot
+/// of the schema, the compression dictionaries and the registered fields of the collection are kept
+pub(crate) fn generate_clear_collection_cmd(
+    collection_name: &str,
+    meta: &CollectionMeta,
+    cursor: &str,
+    count: usize,
+) -> redis::Cmd {
+    let mut cmd = redis::cmd("EVAL");
+    cmd.arg(CLEAR_COLLECTION_SCRIPT)
+        .arg(0)
+        .arg(cursor)
+        .arg(count)
+        .arg(format!("{}_%&*", collection_name))
+        .arg(generate_key_prefix(collection_name))
+        .arg(
+            meta.namespace
+                .as_deref()
+                .map_or(String::new(), generate_quota_key),
+        )
+        .arg(snapshots::get_key_space(collection_name, ""))
+        .arg(generate_change_stream_key(collection_name))
+        .arg(generate_schema_snapshot_key(collection_name))
+        .arg(compression::generate_dictionaries_key(collection_name))
+        .arg(registries::generate_keys(collection_name));
+    cmd
+}
+
+/// Gets the approximate number of distinct values the given field has had in the collection
+pub(crate) fn count_distinct(
+    pool: &pools::RedisPool,
+    collection_name: &str,
+    field: &str,
+) -> PyResult<u64> {
+    let mut conn = pools::get_connection(pool)?;
+
+    redis::cmd("PFCOUNT")
+        .arg(generate_cardinality_key(collection_name, field))
+        .query(conn.deref_mut())
         .map_err(|e| PyConnectionError::new_err(e.to_string()))
 }
 
-/// Removes the given keys from the redis store
-pub(crate) fn remove_records(pool: &r2d2::Pool<redis::Client>, keys: &Vec<String>) -> PyResult<()> {
-    let mut conn = pool
-        .get()
+/// Generates the command that gets the distinct values of the given field of the records in the given collection,
+/// as saved in redis
+pub(crate) fn generate_select_distinct_cmd(collection_name: &str, field: &str) -> redis::Cmd {
+    let mut cmd = redis::cmd("EVAL");
+    cmd.arg(SELECT_DISTINCT_VALUES_SCRIPT)
+        .arg(0)
+        .arg(generate_collection_key_pattern(collection_name))
+        .arg(field);
+    cmd
+}
+
+/// Gets the distinct values of the given field of the records in the given collection, in no particular order
+pub(crate) fn get_distinct_values(
+    pool: &pools::RedisPool,
+    collection_name: &str,
+    meta: &CollectionMeta,
+    field: &str,
+) -> PyResult<Vec<Py<PyAny>>> {
+    let mut conn = pools::get_connection(pool)?;
+
+    let values: Vec<redis::Value> = generate_select_distinct_cmd(collection_name, field)
+        .query(conn.deref_mut())
+        .map_err(|e| PyConnectionError::new_err(e.to_string()))?;
+    distinct_values_to_py(collection_name, meta, field, &values)
+}
+
+/// Converts the distinct values of the given field of the given collection, as got from redis, into python objects
+pub(crate) fn distinct_values_to_py(
+    collection_name: &str,
+    meta: &CollectionMeta,
+    field: &str,
+    values: &[redis::Value],
+) -> PyResult<Vec<Py<PyAny>>> {
+    let type_ = meta.schema.get_type(field).unwrap();
+    values
+        .iter()
+        .map(|v| type_.redis_to_py(collection_name, v))
+        .collect()
+}
+
+/// Aggregates the values of the given field of the records in the given collection with the given operation,
+/// returning the number of values aggregated and the result as saved in redis, empty if there were no values
+pub(crate) fn aggregate_field(
+    pool: &pools::RedisPool,
+    collection_name: &str,
+    field: &str,
+    op: AggregateOp,
+) -> PyResult<(usize, String)> {
+    let mut conn = pools::get_connection(pool)?;
+
+    aggregations::generate_aggregate_cmd(collection_name, field, op)
+        .query(conn.deref_mut())
+        .map_err(|e| PyConnectionError::new_err(e.to_string()))
+}
+
+/// Counts the records in the given collection by the values of the given field, returning each value
+/// as saved in redis with its count
+pub(crate) fn count_by_field(
+    pool: &pools::RedisPool,
+    collection_name: &str,
+    field: &str,
+) -> PyResult<Vec<(redis::Value, usize)>> {
+    let mut conn = pools::get_connection(pool)?;
+
+    aggregations::generate_count_by_cmd(collection_name, field)
+        .query(conn.deref_mut())
+        .map_err(|e| PyConnectionError::new_err(e.to_string()))
+}
+
+/// Counts the records in the given collection whose fields are equal to the values in the given filter
+pub(crate) fn count_matching_records(
+    pool: &pools::RedisPool,
+    collection_name: &str,
+    filter: &[(String, String)],
+) -> PyResult<usize> {
+    let mut conn = pools::get_connection(pool)?;
+
+    aggregations::generate_count_where_cmd(collection_name, filter)
+        .query(conn.deref_mut())
+        .map_err(|e| PyConnectionError::new_err(e.to_string()))
+}
+
+/// Gets the ids of the records of the given collection whose indexed fields have the given values, as saved
+/// in redis, using the index of the fields
+pub(crate) fn get_ids_by_value(
+    pool: &pools::RedisPool,
+    collection_name: &str,
+    meta: &CollectionMeta,
+    fields: &[String],
+    values: &[String],
+) -> PyResult<Vec<String>> {
+    let mut conn = pools::get_connection(pool)?;
+
+    field_indexes::generate_lookup_cmd(collection_name, meta, fields, values)
+        .query(conn.deref_mut())
+        .map_err(|e| PyConnectionError::new_err(e.to_string()))
+}
+
+/// Gets the ids of the records of the given collection that match all the given lookups of `find()`, using
+/// their indexes in one network request, unless they only exclude records, in which case all ids are scanned first
+pub(crate) fn get_ids_by_lookups(
+    pool: &pools::RedisPool,
+    collection_name: &str,
+    lookups: &Lookups,
+) -> PyResult<Vec<String>> {
+    let ids = get_lookup_candidates(pool, collection_name, lookups)?;
+    Ok(lookups.filter_ids(ids))
+}
+
+/// Gets the ids in the index of each of the given lookups of `find()`, preceded by the ids of all the records
+/// of the given collection if the lookups only exclude records, as expected by `Lookups::filter_ids()`
+pub(crate) fn get_lookup_candidates(
+    pool: &pools::RedisPool,
+    collection_name: &str,
+    lookups: &Lookups,
+) -> PyResult<Vec<Vec<String>>> {
+    let mut ids: Vec<Vec<String>> = match lookups.needs_all_ids() {
+        true => vec![get_record_ids(pool, collection_name)?],
+        false => vec![],
+    };
+    let mut conn = pools::get_connection(pool)?;
+
+    let replies: Vec<Vec<String>> = lookups
+        .to_pipeline()
+        .query(conn.deref_mut())
+        .map_err(|e| PyConnectionError::new_err(e.to_string()))?;
+    ids.extend(replies);
+    Ok(ids)
+}
+
+/// Gets the ids of the records of the given collection that match the given lookups, as cached by an identical
+/// call that is not older than the given ttl, or else as got by `get_ids_by_lookups()`, caching them.
+/// Empty results are not cached
+pub(crate) fn get_cached_ids_by_lookups(
+    pool: &pools::RedisPool,
+    collection_name: &str,
+    lookups: &Lookups,
+    cache_ttl: &Expiry,
+) -> PyResult<Vec<String>> {
+    let cache_key = lookups.generate_cache_key(collection_name);
+    let cached: Vec<String> = redis::cmd("LRANGE")
+        .arg(&cache_key)
+        .arg(0)
+        .arg(-1)
+        .query(pools::get_connection(pool)?.deref_mut())
         .map_err(|e| PyConnectionError::new_err(e.to_string()))?;
+    if !cached.is_empty() {
+        return Ok(cached);
+    }
+
+    let ids = get_ids_by_lookups(pool, collection_name, lookups)?;
+    if !ids.is_empty() {
+        let mut pipe = redis::pipe();
+        pipe.atomic()
+            .del(&cache_key)
+            .ignore()
+            .rpush(&cache_key, &ids)
+            .ignore();
+        cache_ttl.add_cmd(&mut pipe, &cache_key);
+        pipe.ignore()
+            .query::<()>(pools::get_connection(pool)?.deref_mut())
+            .map_err(|e| PyConnectionError::new_err(e.to_string()))?;
+    }
+    Ok(ids)
+}
+
+/// Gets the ids of the records in the set of linked records at the given key
+pub(crate) fn get_linked_ids(pool: &pools::RedisPool, link_key: &str) -> PyResult<Vec<String>> {
+    let mut conn = pools::get_connection(pool)?;
+
+    redis::cmd("SMEMBERS")
+        .arg(link_key)
+        .query(conn.deref_mut())
+        .map_err(|e| PyConnectionError::new_err(e.to_string()))
+}
+
+/// Gets the id of the record of the given collection that the given reference i.e. the value of a field
+/// annotated with `Reference[Model]`, refers to. Plain ids are taken as they are, but a reference to
+/// a model other than that of the collection is rejected
+pub(crate) fn extract_reference_id(collection_name: &str, reference: &PyAny) -> PyResult<String> {
+    if let Ok(model) = reference.getattr("referenced_model") {
+        if !model.is_none() {
+            let model_name: String = model.getattr("__qualname__")?.extract()?;
+            if model_name != collection_name {
+                return Err(PyValueError::new_err(format!(
+                    "{:?} is a reference to a record of {} yet this is the collection of {}",
+                    reference.str()?.to_string(),
+                    model_name,
+                    collection_name
+                )));
+            }
+        }
+    }
+
+    Ok(reference.str()?.to_string())
+}
+
+/// Checks whether the record of the given id might exist in the collection using its bloom filter.
+/// It returns false only if the record certainly does not exist. If the collection has no bloom filter
+/// or the RedisBloom module is not loaded, it always returns true
+pub(crate) fn might_exist(
+    pool: &pools::RedisPool,
+    collection_name: &str,
+    meta: &CollectionMeta,
+    id: &str,
+) -> PyResult<bool> {
+    let bloom_filter = match &meta.bloom_filter {
+        None => return Ok(true),
+        Some(bloom_filter) => bloom_filter,
+    };
+    let mut conn = pools::get_connection(pool)?;
+
+    let is_available = match bloom_filter.is_available() {
+        Some(is_available) => is_available,
+        None => {
+            let is_available: bool = bloom_filters::generate_init_cmd(collection_name)
+                .query(conn.deref_mut())
+                .map_err(|e| PyConnectionError::new_err(e.to_string()))?;
+            bloom_filter.set_available(is_available);
+            is_available
+        }
+    };
+
+    if !is_available {
+        return Ok(true);
+    }
+
+    redis::cmd("BF.EXISTS")
+        .arg(generate_bloom_filter_key(collection_name))
+        .arg(generate_hash_key(
+            collection_name,
+            id,
+            meta.case_insensitive_keys,
+        ))
+        .query(conn.deref_mut())
+        .map_err(|e| PyConnectionError::new_err(e.to_string()))
+}
+
+/// Checks whether the record of the given id exists in the collection
+pub(crate) fn record_exists(
+    pool: &pools::RedisPool,
+    collection_name: &str,
+    meta: &CollectionMeta,
+    id: &str,
+) -> PyResult<bool> {
+    if !might_exist(pool, collection_name, meta, id)? {
+        return Ok(false);
+    }
+
+    let mut conn = pools::get_connection(pool)?;
+
+    redis::cmd("EXISTS")
+        .arg(generate_hash_key(
+            collection_name,
+            id,
+            meta.case_insensitive_keys,
+        ))
+        .query(conn.deref_mut())
+        .map_err(|e| PyConnectionError::new_err(e.to_string()))
+}
+
+/// Generates the pipeline of one EXISTS per record of the given ids in the collection
+pub(crate) fn generate_exists_pipeline(
+    collection_name: &str,
+    meta: &CollectionMeta,
+    ids: &[String],
+) -> redis::Pipeline {
     let mut pipe = redis::pipe();
+    for id in ids {
+        pipe.cmd("EXISTS").arg(generate_hash_key(
+            collection_name,
+            id,
+            meta.case_insensitive_keys,
+        ));
+    }
+    pipe
+}
 
-    pipe.del(keys);
+/// Checks whether the records of the given ids exist in the collection, in one round trip
+pub(crate) fn records_exist(
+    pool: &pools::RedisPool,
+    collection_name: &str,
+    meta: &CollectionMeta,
+    ids: &[String],
+) -> PyResult<Vec<bool>> {
+    if ids.is_empty() {
+        return Ok(vec![]);
+    }
 
-    pipe.query(conn.deref_mut())
+    let mut conn = pools::get_connection(pool)?;
+
+    generate_exists_pipeline(collection_name, meta, ids)
+        .query(conn.deref_mut())
         .map_err(|e| PyConnectionError::new_err(e.to_string()))
 }
 
-/// Gets the records for the given collection name in redis, with the given ids
-pub(crate) fn get_records_by_id(
-    pool: &r2d2::Pool<redis::Client>,
+/// Generates the command that atomically increments the given counter field of the record
+/// of the given id by the given amount, which defaults to 1
+pub(crate) fn generate_increment_cmd(
+    collection_name: &str,
+    meta: &CollectionMeta,
+    id: &str,
+    field: &str,
+    amount: Option<&PyAny>,
+) -> PyResult<redis::Cmd> {
+    meta.ensure_counter_field(field)?;
+    let key = generate_counter_key(collection_name, field, id, meta.case_insensitive_keys);
+
+    let cmd = match meta.schema.get_type(field) {
+        Some(FieldType::Float) => {
+            let amount: f64 = amount.map_or(Ok(1.0), |v| v.extract())?;
+            let mut cmd = redis::cmd("INCRBYFLOAT");
+            cmd.arg(key).arg(amount);
+            cmd
+        }
+        _ => {
+            let amount: i64 = amount.map_or(Ok(1), |v| v.extract())?;
+            let mut cmd = redis::cmd("INCRBY");
+            cmd.arg(key).arg(amount);
+            cmd
+        }
+    };
+
+    Ok(cmd)
+}
+
+/// Increments a counter field using the command generated by `generate_increment_cmd`,
+/// returning the new value
+pub(crate) fn increment_counter(
+    pool: &pools::RedisPool,
+    collection_name: &str,
+    meta: &CollectionMeta,
+    field: &str,
+    cmd: &redis::Cmd,
+) -> PyResult<Py<PyAny>> {
+    let mut conn = pools::get_connection(pool)?;
+
+    let value: redis::Value = cmd
+        .query(conn.deref_mut())
+        .map_err(|e| PyConnectionError::new_err(e.to_string()))?;
+    meta.mirrors.replicate_cmd(cmd);
+    parse_counter_value(collection_name, meta, field, &value)
+}
+
+/// Converts the value of a counter field of the given collection got from redis into a python int or float
+pub(crate) fn parse_counter_value(
+    collection_name: &str,
+    meta: &CollectionMeta,
+    field: &str,
+    value: &redis::Value,
+) -> PyResult<Py<PyAny>> {
+    match meta.schema.get_type(field) {
+        Some(field_type) => field_type.redis_to_py(collection_name, value),
+        None => Err(py_key_error!(field, "counter field not in schema")),
+    }
+}
+
+/// Generates the keys of the counters of the given records, in the order of the records
+/// and then of the counter fields. Records without ids are skipped
+pub(crate) fn generate_counter_keys(
+    collection_name: &str,
+    meta: &CollectionMeta,
+    records: &[ParsedRecord],
+) -> Vec<String> {
+    records
+        .iter()
+        .filter_map(|(id, _)| id.as_ref())
+        .flat_map(|id| {
+            meta.counter_fields.iter().map(move |field| {
+                generate_counter_key(collection_name, field, id, meta.case_insensitive_keys)
+            })
+        })
+        .collect()
+}
+
+/// Merges the values of counters got from the keys generated by `generate_counter_keys`
+/// into the given records
+pub(crate) fn merge_counter_values(
+    meta: &CollectionMeta,
+    records: &mut [ParsedRecord<'_>],
+    values: &[redis::Value],
+) -> PyResult<()> {
+    let mut values = values.iter();
+    for (_, data) in records.iter_mut().filter(|(id, _)| id.is_some()) {
+        for field in &meta.counter_fields {
+            match values.next() {
+                None | Some(redis::Value::Nil) => {}
+                Some(value) => {
+                    data.retain(|(k, _)| k != field);
+                    data.push((field.clone(), Cow::Owned(value.clone())));
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Generates the keys of the records of the given ids other than those of their main hashes i.e. the keys
+/// of their counters, their copies in views and their packed vectors
+pub(crate) fn generate_secondary_record_keys(
+    collection_name: &str,
+    meta: &CollectionMeta,
+    ids: &[String],
+) -> Vec<String> {
+    let has_vector_fields = vectors::has_vector_fields(meta);
+    ids.iter()
+        .flat_map(|id| {
+            meta.counter_fields
+                .iter()
+                .map(move |field| {
+                    generate_counter_key(collection_name, field, id, meta.case_insensitive_keys)
+                })
+                .chain(meta.views.iter().map(move |view| {
+                    generate_hash_key(
+                        &view.get_key_space(collection_name),
+                        id,
+                        meta.case_insensitive_keys,
+                    )
+                }))
+                .chain(has_vector_fields.then(|| {
+                    vectors::generate_vector_key(collection_name, id, meta.case_insensitive_keys)
+                }))
+        })
+        .collect()
+}
+
+/// Gets the fields to request from redis for a partial record. The primary key field is added
+/// if any counter field is requested, since it is needed to get the counter values
+pub(crate) fn get_fields_to_request(meta: &CollectionMeta, fields: &[String]) -> Vec<String> {
+    let mut fields_to_request = fields.to_vec();
+    if fields.iter().any(|f| meta.counter_fields.contains(f))
+        && !fields.contains(&meta.primary_key_field)
+    {
+        fields_to_request.push(meta.primary_key_field.clone());
+    }
+
+    fields_to_request
+}
+
+/// Generates the meta with which the given fields of the records of a collection are read. Nested fields
+/// requested only through dotted paths e.g. "address.city" are read as dictionaries of those fields,
+/// or as instances of their models, constructed without validation, if nested_as_models is true
+pub(crate) fn generate_partial_read_meta<'a>(
+    meta: &'a CollectionMeta,
+    fields: &[String],
+    nested_as_models: bool,
+) -> PyResult<Cow<'a, CollectionMeta>> {
+    if !fields.iter().any(|f| f.contains('.')) {
+        return Ok(Cow::Borrowed(meta));
+    }
+
+    let mut read_meta = meta.clone();
+    read_meta.schema = Box::new(meta.schema.with_projections(fields, nested_as_models)?);
+    read_meta.converters = ConverterTable::new(&read_meta.schema, &read_meta.coercions);
+    Ok(Cow::Owned(read_meta))
+}
+
+/// Generates the meta with which the records of a collection are read as dictionaries, with the records
+/// nested in them also as dictionaries
+pub(crate) fn generate_raw_read_meta(meta: &CollectionMeta) -> CollectionMeta {
+    let mut read_meta = meta.clone();
+    read_meta.converters = meta.converters.with_nested_as_dicts();
+    read_meta
+}
+
+/// Whether the given field of a record is among the given requested fields, directly or as the start
+/// of a dotted path
+pub(crate) fn is_requested(fields: &[String], field: &str) -> bool {
+    fields
+        .iter()
+        .any(|f| f == field || matches!(f.split_once('.'), Some((head, _)) if head == field))
+}
+
+/// Rebuilds the given view of the given collection from all its records, returning the number
+/// of records in the view
+pub(crate) fn rebuild_view(
+    pool: &pools::RedisPool,
+    collection_name: &str,
+    view: &ViewDefinition,
+    throttle: &mut Throttle,
+) -> PyResult<u64> {
+    run_scan_script(pool, throttle, |cursor, count| {
+        view.generate_clear_cmd(collection_name, cursor, count)
+    })?;
+    run_scan_script(pool, throttle, |cursor, count| {
+        view.generate_rebuild_cmd(collection_name, cursor, count)
+    })
+}
+
+/// Takes the snapshot of the given tag of the given collection, copying all its records, with their fields
+/// as they currently are, in place of those of any snapshot of the same tag. It returns the number of records
+/// in the snapshot
+pub(crate) fn take_snapshot(
+    pool: &pools::RedisPool,
+    collection_name: &str,
+    tag: &str,
+    throttle: &mut Throttle,
+) -> PyResult<u64> {
+    delete_snapshot(pool, collection_name, tag, throttle)?;
+    run_scan_script(pool, throttle, |cursor, count| {
+        snapshots::generate_copy_cmd(collection_name, tag, cursor, count)
+    })
+}
+
+/// Deletes the snapshot of the given tag of the given collection, returning the number of records it had
+pub(crate) fn delete_snapshot(
+    pool: &pools::RedisPool,
+    collection_name: &str,
+    tag: &str,
+    throttle: &mut Throttle,
+) -> PyResult<u64> {
+    run_scan_script(pool, throttle, |cursor, count| {
+        snapshots::generate_clear_cmd(collection_name, tag, cursor, count)
+    })
+}
+
+/// Removes records from the redis store using the pipeline generated by `generate_delete_pipeline`,
+/// returning the number of them that existed, or None if the removal was buffered
+pub(crate) fn remove_records(
+    pool: &pools::RedisPool,
+    meta: &CollectionMeta,
+    pipe: &redis::Pipeline,
+) -> PyResult<Option<usize>> {
+    let reply: Option<(usize,)> = apply_write_with_reply(pool, meta, pipe)?;
+    Ok(reply.map(|(deleted,)| deleted))
+}
+
+/// Gets the number of the records of the given ids that were deleted, from the number replied by redis,
+/// or the number of distinct ids if the deletion was buffered. If strict, it fails if any of them did not exist,
+/// after the others were deleted
+pub(crate) fn to_deleted_count(
+    collection_name: &str,
+    meta: &CollectionMeta,
+    ids: &[String],
+    deleted: Option<usize>,
+    strict: bool,
+) -> PyResult<usize> {
+    let expected = ids
+        .iter()
+        .map(|id| generate_hash_key(collection_name, id, meta.case_insensitive_keys))
+        .collect::<HashSet<String>>()
+        .len();
+    let deleted = deleted.unwrap_or(expected);
+    if strict && deleted < expected {
+        return Err(PyKeyError::new_err(format!(
+            "{} of the {} ids have no record in the collection {:?}, the others were deleted",
+            expected - deleted,
+            expected,
+            collection_name
+        )));
+    }
+    Ok(deleted)
+}
+
+/// Deletes the records of the given collection whose fields are equal to the values in the given filter,
+/// returning the number deleted. They are found, and deleted if they can be, by one script, or else deleted
+/// with their auxiliary data in the pipeline of `generate_delete_pipeline()` right after
+pub(crate) fn delete_matching_records(
+    pool: &pools::RedisPool,
+    collection_name: &str,
+    meta: &CollectionMeta,
+    filter: &[(String, String)],
+) -> PyResult<usize> {
+    refresh_registered_fields(pool, collection_name, meta)?;
+    let cmd = conditional_writes::generate_delete_matching_cmd(collection_name, meta, filter);
+    let mut conn = pools::get_blocking_connection(pool)?;
+    let ids: Vec<String> = cmd
+        .query(conn.deref_mut())
+        .map_err(|e| PyConnectionError::new_err(e.to_string()))?;
+    drop(conn);
+
+    if conditional_writes::is_deleted_in_script(meta) {
+        meta.mirrors.replicate_cmd(&cmd);
+    } else if !ids.is_empty() {
+        remove_records(
+            pool,
+            meta,
+            &generate_delete_pipeline(collection_name, meta, &ids),
+        )?;
+    }
+    Ok(ids.len())
+}
+
+/// Deletes the records of the given collection whose ids match the given glob pattern, returning the number
+/// deleted. The keys are scanned a batch at a time, at no more operations per second than the throttle allows,
+/// each batch being unlinked by the script that scans it if it can be, or else deleted with its auxiliary data
+/// in the pipeline of `generate_delete_pipeline()` right after
+pub(crate) fn delete_records_by_id_pattern(
+    pool: &pools::RedisPool,
+    collection_name: &str,
+    meta: &CollectionMeta,
+    id_pattern: &str,
+    throttle: &mut Throttle,
+) -> PyResult<usize> {
+    refresh_registered_fields(pool, collection_name, meta)?;
+    let is_deleted_in_script = conditional_writes::is_deleted_in_script(meta);
+    let mut deleted = 0;
+    let mut cursor = "0".to_string();
+
+    loop {
+        let cmd = conditional_writes::generate_delete_by_id_pattern_cmd(
+            collection_name,
+            meta,
+            id_pattern,
+            &cursor,
+            throttle.batch_size(SCAN_BATCH_SIZE),
+        );
+        let (next_cursor, scanned, ids): (String, usize, Vec<String>) = cmd
+            .query(pools::get_blocking_connection(pool)?.deref_mut())
+            .map_err(|e| PyConnectionError::new_err(e.to_string()))?;
+
+        // the cursor of the scan is only valid on this redis, so the mirrors delete the ids instead
+        if is_deleted_in_script && !ids.is_empty() && !meta.mirrors.is_empty() {
+            meta.mirrors
+                .replicate(&generate_delete_pipeline(collection_name, meta, &ids));
+        } else if !is_deleted_in_script && !ids.is_empty() {
+            remove_records(
+                pool,
+                meta,
+                &generate_delete_pipeline(collection_name, meta, &ids),
+            )?;
+        }
+        deleted += ids.len();
+
+        cursor = next_cursor;
+        if cursor == "0" {
+            return Ok(deleted);
+        }
+
+        if let Some(delay) = throttle.throttle(scanned) {
+            thread::sleep(delay);
+        }
+    }
+}
+
+/// Makes the given changes to the records of the given collection whose fields are equal to the values in
+/// the given filter, returning the number updated. They are found, and updated if they can be, by one script,
+/// or else updated with their auxiliary data in the pipeline of `generate_insert_pipeline()` right after.
+/// The ttls of the records are left as they are
+pub(crate) fn update_matching_records(
+    pool: &pools::RedisPool,
+    collection_name: &str,
+    meta: &CollectionMeta,
+    filter: &[(String, String)],
+    changes: &[(String, String)],
+) -> PyResult<usize> {
+    refresh_registered_fields(pool, collection_name, meta)?;
+    let cmd =
+        conditional_writes::generate_update_matching_cmd(collection_name, meta, filter, changes);
+    let mut conn = pools::get_blocking_connection(pool)?;
+    let ids: Vec<String> = cmd
+        .query(conn.deref_mut())
+        .map_err(|e| PyConnectionError::new_err(e.to_string()))?;
+    drop(conn);
+
+    if conditional_writes::is_updated_in_script(meta, changes) {
+        meta.mirrors.replicate_cmd(&cmd);
+    } else if !ids.is_empty() {
+        let records: InsertRecords = ids
+            .iter()
+            .map(|id| {
+                (
+                    generate_hash_key(collection_name, id, meta.case_insensitive_keys),
+                    changes.to_vec(),
+                )
+            })
+            .collect();
+        insert_records(pool, collection_name, meta, &records, &[], &None)?;
+    }
+    Ok(ids.len())
+}
+
+/// Links or unlinks records using the pipeline generated by `LinkDefinition::generate_link_pipeline`
+/// or `LinkDefinition::generate_unlink_pipeline`
+pub(crate) fn update_links(
+    pool: &pools::RedisPool,
+    meta: &CollectionMeta,
+    pipe: &redis::Pipeline,
+) -> PyResult<()> {
+    apply_write(pool, meta, pipe)
+}
+
+/// Reads the fields registered in redis for the given collection again if they are stale, so that the writes
+/// of this client update the auxiliary data of the fields registered by other clients too. If the store has
+/// a write buffer, the fields as last read are used while redis is unreachable
+pub(crate) fn refresh_registered_fields(
+    pool: &pools::RedisPool,
+    collection_name: &str,
+    meta: &CollectionMeta,
+) -> PyResult<()> {
+    if !meta.registered_fields.is_stale() {
+        return Ok(());
+    }
+
+    let pipe = registries::generate_refresh_pipeline(collection_name);
+    let fields = pools::get_connection(pool).and_then(|mut conn| {
+        pipe.query(conn.deref_mut())
+            .map_err(|e| PyConnectionError::new_err(e.to_string()))
+    });
+    match fields {
+        Ok(fields) => meta.registered_fields.update(fields),
+        Err(e) if !meta.write_buffer.is_enabled() => return Err(e),
+        Err(_) => {}
+    }
+    Ok(())
+}
+
+/// Applies the given write to redis and queues it for the mirrors. If the store has a write buffer,
+/// the write is buffered instead when redis is unreachable or earlier writes are still buffered
+fn apply_write(
+    pool: &pools::RedisPool,
+    meta: &CollectionMeta,
+    pipe: &redis::Pipeline,
+) -> PyResult<()> {
+    apply_write_with_reply::<()>(pool, meta, pipe).map(|_| ())
+}
+
+/// Applies the given write to redis and queues it for the mirrors, failing instead of being buffered e.g. when
+/// redis is unreachable, since the write has checks whose failure should be raised to the caller e.g. of unique values
+fn apply_unbuffered_write(
+    pool: &pools::RedisPool,
+    meta: &CollectionMeta,
+    pipe: &redis::Pipeline,
+) -> PyResult<()> {
+    meta.write_buffer.ensure_none_pending()?;
+    profiling::measure(Phase::Network, || {
+        pools::get_connection(pool).and_then(|mut conn| {
+            pipe.query::<()>(conn.deref_mut())
+                .map_err(|e| WriteError::from_redis(e).into_err())
+        })
+    })?;
+    meta.mirrors.replicate(pipe);
+    Ok(())
+}
+
+/// Applies the given write like `apply_write()`, returning the reply of redis, or None if the write was buffered
+fn apply_write_with_reply<T: redis::FromRedisValue>(
+    pool: &pools::RedisPool,
+    meta: &CollectionMeta,
+    pipe: &redis::Pipeline,
+) -> PyResult<Option<T>> {
+    let reply = if meta.write_buffer.has_pending() {
+        meta.write_buffer.push(pipe)?;
+        None
+    } else {
+        profiling::measure(Phase::Network, || {
+            pools::get_connection(pool)
+                .map_err(WriteError::from_checkout)
+                .and_then(|mut conn| pipe.query(conn.deref_mut()).map_err(WriteError::from_redis))
+        })
+        .map(Some)
+        .or_else(|e| meta.write_buffer.handle_failure(pipe, e).map(|_| None))?
+    };
+    meta.mirrors.replicate(pipe);
+    Ok(reply)
+}
+
+/// Generates the pipeline that removes the records of the given ids in a transaction,
+/// together with their auxiliary data, recording the deletions in the change stream if the
+/// collection tracks changes
+pub(crate) fn generate_delete_pipeline(
+    collection_name: &str,
+    meta: &CollectionMeta,
+    ids: &[String],
+) -> redis::Pipeline {
+    let mut pipe = redis::pipe();
+    pipe.atomic();
+
+    if let Some(namespace) = &meta.namespace {
+        let keys: Vec<String> = ids
+            .iter()
+            .map(|id| generate_hash_key(collection_name, id, meta.case_insensitive_keys))
+            .collect();
+        pipe.add_command(quotas::generate_release_cmd(
+            &generate_quota_key(namespace),
+            &keys,
+        ))
+        .ignore();
+    }
+
+    for id in ids {
+        field_indexes::add_remove_cmd(&mut pipe, collection_name, meta, id);
+        list_indexes::add_remove_cmd(&mut pipe, collection_name, meta, id);
+        unique_fields::add_remove_cmd(&mut pipe, collection_name, meta, id);
+    }
+
+    if meta.track_changes {
+        for id in ids {
+            pipe.cmd("EVAL")
+                .arg(RECORD_DELETION_SCRIPT)
+                .arg(2)
+                .arg(generate_hash_key(
+                    collection_name,
+                    id,
+                    meta.case_insensitive_keys,
+                ))
+                .arg(generate_change_stream_key(collection_name))
+                .arg(id)
+                .arg(CHANGE_STREAM_MAX_LENGTH)
+                .ignore();
+        }
+    }
+
+    // the sorted sets hold the ids as they are in the keys of the records
+    let members: Vec<String> = ids
+        .iter()
+        .map(|id| key_normalization::normalize_id(id, meta.case_insensitive_keys).into_owned())
+        .collect();
+    for field in meta.get_range_indexes() {
+        pipe.zrem(generate_range_index_key(collection_name, &field), &members)
+            .ignore()
+            .zrem(generate_unscored_ids_key(collection_name, &field), &members)
+            .ignore();
+    }
+    for field in meta.get_null_indexes() {
+        pipe.srem(
+            null_indexes::generate_index_key(collection_name, &field),
+            &members,
+        )
+        .ignore();
+    }
+    if meta.track_insertion_order {
+        pipe.zrem(generate_insertion_order_key(collection_name), &members)
+            .ignore();
+    }
+
+    // the reply of the deletion of the main hashes of the records is the number of records that existed
+    let keys: Vec<String> = ids
+        .iter()
+        .map(|id| generate_hash_key(collection_name, id, meta.case_insensitive_keys))
+        .collect();
+    let other_keys = generate_secondary_record_keys(collection_name, meta, ids);
+    pipe.del(keys);
+    if !other_keys.is_empty() {
+        pipe.del(other_keys).ignore();
+    }
+    pipe
+}
+
+/// Gets the ids of all the records of the given collection, scanning its keys with SCAN.
+/// Each id is returned once even if SCAN finds its key more than once
+pub(crate) fn get_record_ids(
+    pool: &pools::RedisPool,
+    collection_name: &str,
+) -> PyResult<Vec<String>> {
+    let mut conn = pools::get_blocking_connection(pool)?;
+    let key_prefix = generate_key_prefix(collection_name);
+
+    let mut cmd = redis::cmd("SCAN");
+    cmd.cursor_arg(0)
+        .arg("MATCH")
+        .arg(generate_collection_key_pattern(collection_name));
+
+    let keys = cmd
+        .iter::<String>(conn.deref_mut())
+        .map_err(|e| PyConnectionError::new_err(e.to_string()))?;
+
+    let mut seen: HashSet<String> = HashSet::new();
+    Ok(keys
+        .filter_map(|key| key.strip_prefix(&key_prefix).map(|id| id.to_string()))
+        .filter(|id| seen.insert(id.clone()))
+        .collect())
+}
+
+/// Gets the ids of the records of the given collection found by one SCAN call from the given cursor,
+/// which looks at about `count` keys, with the cursor of the next call, 0 once the scan is complete
+pub(crate) fn scan_record_ids(
+    pool: &pools::RedisPool,
+    collection_name: &str,
+    cursor: u64,
+    count: usize,
+) -> PyResult<(u64, Vec<String>)> {
+    let mut conn = pools::get_connection(pool)?;
+    let (next_cursor, keys): (u64, Vec<String>) =
+        generate_scan_record_keys_cmd(collection_name, cursor, count)
+            .query(conn.deref_mut())
+            .map_err(|e| PyConnectionError::new_err(e.to_string()))?;
+    Ok((next_cursor, keys_to_ids(collection_name, keys)))
+}
+
+/// Generates the SCAN command that gets the keys of the records of the given collection from the given cursor,
+/// looking at about `count` keys
+pub(crate) fn generate_scan_record_keys_cmd(
+    collection_name: &str,
+    cursor: u64,
+    count: usize,
+) -> redis::Cmd {
+    let mut cmd = redis::cmd("SCAN");
+    cmd.arg(cursor)
+        .arg("MATCH")
+        .arg(generate_collection_key_pattern(collection_name))
+        .arg("COUNT")
+        .arg(count);
+    cmd
+}
+
+/// Converts the given keys of the records of the given collection into their ids
+pub(crate) fn keys_to_ids(collection_name: &str, keys: Vec<String>) -> Vec<String> {
+    let key_prefix = generate_key_prefix(collection_name);
+    keys.iter()
+        .filter_map(|key| key.strip_prefix(&key_prefix).map(|id| id.to_string()))
+        .collect()
+}
+
+/// Applies the given repairs to the records of the given collection whose values are in the broken formats
+/// they handle, reading and repairing the records in batches at no more operations per second than the
+/// throttle allows. It returns the number of records repaired
+pub(crate) fn repair_records(
+    pool: &pools::RedisPool,
+    collection_name: &str,
+    meta: &CollectionMeta,
+    repairs: &[Repair],
+    throttle: &mut Throttle,
+) -> PyResult<u64> {
+    let ids = get_record_ids(pool, collection_name)?;
+    let mut conn = pools::get_blocking_connection(pool)?;
+    let mut repaired: u64 = 0;
+
+    for batch in ids.chunks(throttle.batch_size(REPAIR_BATCH_SIZE)) {
+        let keys: Vec<String> = batch
+            .iter()
+            .map(|id| generate_hash_key(collection_name, id, meta.case_insensitive_keys))
+            .collect();
+        let mut read_pipe = redis::pipe();
+        for key in &keys {
+            read_pipe.hgetall(key);
+        }
+        let records: Vec<HashMap<String, String>> = read_pipe
+            .query(conn.deref_mut())
+            .map_err(|e| PyConnectionError::new_err(e.to_string()))?;
+
+        let cmds: Vec<redis::Cmd> = keys
+            .iter()
+            .zip(&records)
+            .filter_map(|(key, fields)| {
+                repairs::generate_repair_cmd(key, &meta.schema, repairs, fields)
+            })
+            .collect();
+        if !cmds.is_empty() {
+            let mut repair_pipe = redis::pipe();
+            for cmd in cmds {
+                repair_pipe.add_command(cmd);
+            }
+
+            let results: Vec<u64> = repair_pipe
+                .query(conn.deref_mut())
+                .map_err(|e| PyConnectionError::new_err(e.to_string()))?;
+            meta.mirrors.replicate(&repair_pipe);
+            repaired += results.iter().sum::<u64>();
+        }
+
+        if let Some(delay) = throttle.throttle(batch.len()) {
+            thread::sleep(delay);
+        }
+    }
+
+    Ok(repaired)
+}
+
+/// Removes the fields that are not in the schema of the given collection from its records, reading and
+/// compacting the records in batches at no more operations per second than the throttle allows. It returns
+/// the number of records compacted
+pub(crate) fn compact_records(
+    pool: &pools::RedisPool,
+    collection_name: &str,
+    meta: &CollectionMeta,
+    throttle: &mut Throttle,
+) -> PyResult<u64> {
+    let ids = get_record_ids(pool, collection_name)?;
+    let mut conn = pools::get_blocking_connection(pool)?;
+    let mut compacted: u64 = 0;
+
+    for batch in ids.chunks(throttle.batch_size(COMPACT_BATCH_SIZE)) {
+        let keys: Vec<String> = batch
+            .iter()
+            .map(|id| generate_hash_key(collection_name, id, meta.case_insensitive_keys))
+            .collect();
+        let mut read_pipe = redis::pipe();
+        for key in &keys {
+            read_pipe.hkeys(key);
+        }
+        let fields: Vec<Vec<String>> = read_pipe
+            .query(conn.deref_mut())
+            .map_err(|e| PyConnectionError::new_err(e.to_string()))?;
+
+        if let Some((pipe, count)) =
+            compaction::generate_compact_pipeline(&meta.schema, &keys, &fields)
+        {
+            pipe.query(conn.deref_mut())
+                .map_err(|e| PyConnectionError::new_err(e.to_string()))?;
+            meta.mirrors.replicate(&pipe);
+            compacted += count;
+        }
+
+        if let Some(delay) = throttle.throttle(batch.len()) {
+            thread::sleep(delay);
+        }
+    }
+
+    Ok(compacted)
+}
+
+/// Registers the range index of the given field in redis, so that the writes of all clients update it, and builds
+/// it from the values of the records of the given collection, reading them in batches at no more operations per
+/// second than the throttle allows. The index is marked as built once all the records are in it, an index that
+/// was registered earlier being brought up to date. It returns the number of records indexed
+pub(crate) fn build_range_index(
+    pool: &pools::RedisPool,
+    collection_name: &str,
+    meta: &CollectionMeta,
+    field: &str,
+    throttle: &mut Throttle,
+) -> PyResult<u64> {
+    let type_ = meta.schema.get_type(field).unwrap();
+    let mut conn = pools::get_blocking_connection(pool)?;
+    let mut indexed: u64 = 0;
+
+    let register_cmd = Registry::RangeIndexes.generate_register_cmd(collection_name, field);
+    let is_new: bool = register_cmd
+        .query(conn.deref_mut())
+        .map_err(|e| PyConnectionError::new_err(e.to_string()))?;
+    meta.mirrors.replicate_cmd(&register_cmd);
+    meta.registered_fields.add(Registry::RangeIndexes, field);
+    if is_new {
+        // any index left over from before the field was registered is stale
+        let mut cmd = redis::cmd("DEL");
+        cmd.arg(generate_range_index_key(collection_name, field))
+            .arg(generate_unscored_ids_key(collection_name, field))
+            .query(conn.deref_mut())
+            .map_err(|e| PyConnectionError::new_err(e.to_string()))?;
+        meta.mirrors.replicate_cmd(&cmd);
+        thread::sleep(registries::REGISTRATION_DELAY);
+    }
+
+    let ids = get_record_ids(pool, collection_name)?;
+    for batch in ids.chunks(throttle.batch_size(INDEX_BATCH_SIZE)) {
+        let keys: Vec<String> = batch
+            .iter()
+            .map(|id| generate_hash_key(collection_name, id, meta.case_insensitive_keys))
+            .collect();
+        let mut read_pipe = redis::pipe();
+        for key in &keys {
+            read_pipe.hget(key, field);
+        }
+        let values: Vec<Option<String>> = read_pipe
+            .query(conn.deref_mut())
+            .map_err(|e| PyConnectionError::new_err(e.to_string()))?;
+
+        let mut pipe = redis::pipe();
+        for ((id, key), value) in batch.iter().zip(&keys).zip(values) {
+            // records without the field have no score, like those whose value is None
+            if value.is_some() {
+                indexed += 1;
+            }
+            range_indexes::add_backfill_cmd(
+                &mut pipe,
+                collection_name,
+                field,
+                type_,
+                key,
+                id,
+                value.as_deref(),
+            );
+        }
+        pipe.query(conn.deref_mut())
+            .map_err(|e| PyConnectionError::new_err(e.to_string()))?;
+        meta.mirrors.replicate(&pipe);
+
+        if let Some(delay) = throttle.throttle(batch.len()) {
+            thread::sleep(delay);
+        }
+    }
+
+    let built_cmd = Registry::BuiltRangeIndexes.generate_register_cmd(collection_name, field);
+    built_cmd
+        .query::<()>(conn.deref_mut())
+        .map_err(|e| PyConnectionError::new_err(e.to_string()))?;
+    meta.mirrors.replicate_cmd(&built_cmd);
+    meta.registered_fields
+        .add(Registry::BuiltRangeIndexes, field);
+    Ok(indexed)
+}
+
+/// Registers the null index of the given field in redis, so that the writes of all clients update it, and builds
+/// it from the records of the given collection, checking their values of the field in batches at no more operations
+/// per second than the throttle allows. The index is marked as built once all the records are in it, an index that
+/// was registered earlier being brought up to date. It returns the number of records that have no value for the field
+pub(crate) fn build_null_index(
+    pool: &pools::RedisPool,
+    collection_name: &str,
+    meta: &CollectionMeta,
+    field: &str,
+    throttle: &mut Throttle,
+) -> PyResult<u64> {
+    let mut conn = pools::get_blocking_connection(pool)?;
+    let fields = [field.to_string()];
+
+    let register_cmd = Registry::NullIndexes.generate_register_cmd(collection_name, field);
+    let is_new: bool = register_cmd
+        .query(conn.deref_mut())
+        .map_err(|e| PyConnectionError::new_err(e.to_string()))?;
+    meta.mirrors.replicate_cmd(&register_cmd);
+    meta.registered_fields.add(Registry::NullIndexes, field);
+    if is_new {
+        // any index left over from before the field was registered is stale
+        let mut cmd = redis::cmd("DEL");
+        cmd.arg(null_indexes::generate_index_key(collection_name, field))
+            .query(conn.deref_mut())
+            .map_err(|e| PyConnectionError::new_err(e.to_string()))?;
+        meta.mirrors.replicate_cmd(&cmd);
+        thread::sleep(registries::REGISTRATION_DELAY);
+    }
+
+    let ids = get_record_ids(pool, collection_name)?;
+    let mut indexed: u64 = 0;
+    for batch in ids.chunks(throttle.batch_size(INDEX_BATCH_SIZE)) {
+        let mut pipe = redis::pipe();
+        for id in batch {
+            let pk = generate_hash_key(collection_name, id, meta.case_insensitive_keys);
+            null_indexes::add_update_cmd(&mut pipe, collection_name, &fields, &pk, id);
+        }
+        let nulls: Vec<u64> = pipe
+            .query(conn.deref_mut())
+            .map_err(|e| PyConnectionError::new_err(e.to_string()))?;
+        meta.mirrors.replicate(&pipe);
+        indexed += nulls.iter().sum::<u64>();
+
+        if let Some(delay) = throttle.throttle(batch.len()) {
+            thread::sleep(delay);
+        }
+    }
+
+    let built_cmd = Registry::BuiltNullIndexes.generate_register_cmd(collection_name, field);
+    built_cmd
+        .query::<()>(conn.deref_mut())
+        .map_err(|e| PyConnectionError::new_err(e.to_string()))?;
+    meta.mirrors.replicate_cmd(&built_cmd);
+    meta.registered_fields
+        .add(Registry::BuiltNullIndexes, field);
+    Ok(indexed)
+}
+
+/// Gets the ids of the records of the given collection whose values of the given field are between
+/// the given scores, inclusive, in the order of their values
+pub(crate) fn get_ids_in_range(
+    pool: &pools::RedisPool,
+    collection_name: &str,
+    field: &str,
+    min: &str,
+    max: &str,
+) -> PyResult<Vec<String>> {
+    let mut conn = pools::get_connection(pool)?;
+
+    redis::cmd("ZRANGEBYSCORE")
+        .arg(generate_range_index_key(collection_name, field))
+        .arg(min)
+        .arg(max)
+        .query(conn.deref_mut())
+        .map_err(|e| PyConnectionError::new_err(e.to_string()))
+}
+
+/// Gets the ids of the page of at most `limit` records of the given collection after the given cursor,
+/// in the order of their values of the given field
+pub(crate) fn get_page_ids(
+    pool: &pools::RedisPool,
+    collection_name: &str,
+    field: &str,
+    cursor: &KeysetCursor,
+    limit: usize,
+) -> PyResult<Vec<String>> {
+    let mut conn = pools::get_connection(pool)?;
+
+    cursor
+        .generate_page_cmd(collection_name, field, limit)
+        .query(conn.deref_mut())
+        .map_err(|e| PyConnectionError::new_err(e.to_string()))
+}
+
+/// Gets the ids of the `n` records of the given collection inserted last, latest first, if `latest`,
+/// or else those inserted first, oldest first
+pub(crate) fn get_ids_by_insertion_order(
+    pool: &pools::RedisPool,
+    collection_name: &str,
+    n: usize,
+    latest: bool,
+) -> PyResult<Vec<String>> {
+    let mut conn = pools::get_connection(pool)?;
+
+    generate_insertion_order_range_cmd(collection_name, n, latest)
+        .query(conn.deref_mut())
+        .map_err(|e| PyConnectionError::new_err(e.to_string()))
+}
+
+/// Generates the command that gets the ids of the `n` records of the given collection inserted last
+/// if `latest`, or else first
+pub(crate) fn generate_insertion_order_range_cmd(
+    collection_name: &str,
+    n: usize,
+    latest: bool,
+) -> redis::Cmd {
+    let mut cmd = redis::cmd(if latest { "ZREVRANGE" } else { "ZRANGE" });
+    cmd.arg(generate_insertion_order_key(collection_name))
+        .arg(0)
+        .arg(n as i64 - 1);
+    cmd
+}
+
+/// Gets the current time in microseconds since the unix epoch
+fn get_timestamp_in_micros() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_micros() as u64
+}
+
+/// Gets the ids of the `k` records of the given collection whose given vector field is nearest to the given
+/// packed vector, nearest first. The RediSearch index of the field is created the first time it is searched
+pub(crate) fn knn(
+    pool: &pools::RedisPool,
+    collection_name: &str,
+    field: &str,
+    dimension: usize,
+    vector: &[u8],
+    k: usize,
+) -> PyResult<Vec<String>> {
+    let mut conn = pools::get_connection(pool)?;
+    let search_cmd = vectors::generate_knn_cmd(collection_name, field, vector, k);
+
+    let reply = match search_cmd.query::<redis::Value>(conn.deref_mut()) {
+        Err(e) if vectors::is_missing_index(&e) => {
+            match vectors::generate_create_index_cmd(collection_name, field, dimension)
+                .query::<()>(conn.deref_mut())
+            {
+                Err(e) if !vectors::is_existing_index(&e) => return Err(vectors::to_py_err(e)),
+                _ => search_cmd.query(conn.deref_mut()),
+            }
+        }
+        reply => reply,
+    }
+    .map_err(vectors::to_py_err)?;
+
+    vectors::parse_knn_ids(collection_name, reply)
+}
+
+/// Counts the records of the given collection without a ttl, with a ttl below the thresholds, running
+/// the audit script on one SCAN batch at a time so that redis is not blocked for long, at no more
+/// operations per second than the throttle allows
+pub(crate) fn audit_ttls(
+    pool: &pools::RedisPool,
+    collection_name: &str,
+    thresholds: &TtlThresholds,
+    throttle: &mut Throttle,
+) -> PyResult<TtlAudit> {
+    let mut conn = pools::get_blocking_connection(pool)?;
+    let mut audit = TtlAudit::default();
+    let mut cursor = "0".to_string();
+
+    loop {
+        let (next_cursor, records, no_ttl, below_threshold, imminent_expiry) = thresholds
+            .generate_audit_cmd(
+                collection_name,
+                &cursor,
+                throttle.batch_size(SCAN_BATCH_SIZE),
+            )
+            .query(conn.deref_mut())
+            .map_err(|e| PyConnectionError::new_err(e.to_string()))?;
+        audit.add((records, no_ttl, below_threshold, imminent_expiry));
+        cursor = next_cursor;
+        if cursor == "0" {
+            return Ok(audit);
+        }
+
+        if let Some(delay) = throttle.throttle(records as usize) {
+            thread::sleep(delay);
+        }
+    }
+}
+
+/// Measures the memory used by the given number of records of the given collection picked at random,
+/// with MEMORY USAGE
+pub(crate) fn measure_memory_usage(
+    pool: &pools::RedisPool,
+    collection_name: &str,
+    sample: usize,
+) -> PyResult<MemoryUsage> {
+    let ids = get_record_ids(pool, collection_name)?;
+    let records = ids.len();
+    let ids = memory_usage::sample_ids(ids, sample);
+    let mut conn = pools::get_connection(pool)?;
+
+    let usages: Vec<Option<u64>> =
+        memory_usage::generate_memory_usage_pipeline(collection_name, &ids)
+            .query(conn.deref_mut())
+            .map_err(|e| PyConnectionError::new_err(e.to_string()))?;
+    Ok(MemoryUsage::new(records, ids, usages))
+}
+
+/// Trains the compression dictionary of the given collection on the string values of `sample` of its records
+/// picked at random, saving it in redis and compressing new values with it. It returns the statistics of
+/// the training
+pub(crate) fn train_compression(
+    pool: &pools::RedisPool,
+    collection_name: &str,
+    meta: &CollectionMeta,
+    sample: usize,
+) -> PyResult<TrainingStats> {
+    let ids = memory_usage::sample_ids(get_record_ids(pool, collection_name)?, sample);
+    let mut conn = pools::get_connection(pool)?;
+
+    let records: Vec<HashMap<String, Vec<u8>>> =
+        compression::generate_sample_pipeline(collection_name, &ids)
+            .query(conn.deref_mut())
+            .map_err(|e| PyConnectionError::new_err(e.to_string()))?;
+    let (dictionary, bytes, stats) = compression::train(
+        collection_name,
+        compression::extract_samples(meta, records)?,
+    )?;
+
+    compression::generate_save_cmd(collection_name, dictionary.id(), &bytes)
+        .query(conn.deref_mut())
+        .map_err(|e| PyConnectionError::new_err(e.to_string()))?;
+    meta.compression.set_dictionary(dictionary);
+    Ok(stats)
+}
+
+/// Loads the compression dictionaries of the given collection saved in redis, so that the values compressed
+/// with them can be read and new values are compressed with the current one. It returns whether the
+/// collection has a current dictionary
+pub(crate) fn load_compression(
+    pool: &pools::RedisPool,
+    collection_name: &str,
+    meta: &CollectionMeta,
+) -> PyResult<bool> {
+    let mut conn = pools::get_connection(pool)?;
+
+    let dictionaries: HashMap<String, Vec<u8>> = redis::cmd("HGETALL")
+        .arg(compression::generate_dictionaries_key(collection_name))
+        .query(conn.deref_mut())
+        .map_err(|e| PyConnectionError::new_err(e.to_string()))?;
+    meta.compression.load(collection_name, dictionaries)
+}
+
+/// Gets the ids of a random sample of the records of the given collection, each record
+/// being picked with a probability of `sample`
+pub(crate) fn sample_record_ids(
+    pool: &pools::RedisPool,
+    collection_name: &str,
+    sample: f64,
+) -> PyResult<Vec<String>> {
+    Ok(get_record_ids(pool, collection_name)?
+        .into_iter()
+        .filter(|_| verification::is_sampled(sample))
+        .collect())
+}
+
+/// Gets the records of the given ids as they are saved in redis, for comparison with another store
+pub(crate) fn get_raw_records(
+    pool: &pools::RedisPool,
+    collection_name: &str,
+    meta: &CollectionMeta,
+    ids: &[String],
+) -> PyResult<Vec<verification::RawRecord>> {
+    let mut conn = pools::get_connection(pool)?;
+
+    let values: Vec<redis::Value> =
+        verification::generate_read_pipeline(collection_name, meta, ids)
+            .query(conn.deref_mut())
+            .map_err(|e| PyConnectionError::new_err(e.to_string()))?;
+    verification::parse_raw_records(meta, values)
+}
+
+/// Exports the records of the given collection, with their counters, into the given sink e.g. a file, in chunks
+/// of `chunk_size` records. Compressed values are exported decompressed. It returns the number of records exported
+/// with the sink, once all of the export is written into it
+pub(crate) fn export_records<W: Write>(
+    pool: &pools::RedisPool,
+    collection_name: &str,
+    meta: &CollectionMeta,
+    sink: W,
+    chunk_size: usize,
+) -> PyResult<(u64, W)> {
+    let mut writer = ExportWriter::new(sink, collection_name, EXPORT_BUFFER_SIZE)?;
+    let mut chunk: InsertRecords = Vec::with_capacity(chunk_size);
+    let mut exported = 0;
+    let mut cursor = 0;
+
+    loop {
+        let (next_cursor, ids) = scan_record_ids(pool, collection_name, cursor, chunk_size)?;
+        for ids in ids.chunks(chunk_size) {
+            let records = get_raw_records(pool, collection_name, meta, ids)?;
+            exported += add_to_export(
+                collection_name,
+                &mut writer,
+                &mut chunk,
+                chunk_size,
+                ids,
+                records,
+            )?;
+        }
+
+        if next_cursor == 0 {
+            break;
+        }
+        cursor = next_cursor;
+    }
+
+    writer.write_chunk(&chunk)?;
+    let sink = writer.finish()?;
+    Ok((exported + chunk.len() as u64, sink))
+}
+
+/// Adds the given records of the given ids, as saved in redis, to the chunk of an export, decompressing their values
+/// and writing the chunk with the given writer each time it has `chunk_size` records. It returns the number of
+/// records written
+pub(crate) fn add_to_export<W: Write>(
+    collection_name: &str,
+    writer: &mut ExportWriter<W>,
+    chunk: &mut InsertRecords,
+    chunk_size: usize,
+    ids: &[String],
+    records: Vec<verification::RawRecord>,
+) -> PyResult<u64> {
+    let mut written = 0;
+    // records deleted since they were scanned are empty
+    for (id, record) in ids.iter().zip(records).filter(|(_, v)| !v.is_empty()) {
+        let fields = record
+            .iter()
+            .map(|(k, v)| Ok((k.clone(), compression::redis_to_string(collection_name, v)?)))
+            .collect::<PyResult<Vec<(String, String)>>>()?;
+        chunk.push((id.clone(), fields));
+
+        if chunk.len() == chunk_size {
+            writer.write_chunk(chunk)?;
+            written += chunk.len() as u64;
+            chunk.clear();
+        }
+    }
+    Ok(written)
+}
+
+/// Imports the records of the export at the given path into the given collection one chunk at a time, recording
+/// the number of chunks imported so that, if `resume` is true, an interrupted import carries on after the last
+/// chunk it imported. It returns the number of records imported
+pub(crate) fn import_records(
+    pool: &pools::RedisPool,
+    collection_name: &str,
+    meta: &CollectionMeta,
+    path: &str,
+    resume: bool,
+    ttl: &Option<Expiry>,
+) -> PyResult<u64> {
+    let mut reader = ExportReader::open(path, collection_name)?;
+    let imported_chunks = if resume {
+        exports::read_progress(path)?
+    } else {
+        0
+    };
+    let mut imported = 0;
+
+    while let Some(records) = reader.read_chunk(reader.chunks_read() < imported_chunks)? {
+        if records.is_empty() {
+            continue;
+        }
+
+        let records: InsertRecords = records
+            .into_iter()
+            .map(|(id, fields)| {
+                (
+                    generate_hash_key(collection_name, &id, meta.case_insensitive_keys),
+                    fields,
+                )
+            })
+            .collect();
+        insert_records(pool, collection_name, meta, &records, &[], ttl)?;
+        imported += records.len() as u64;
+        exports::save_progress(path, reader.chunks_read())?;
+    }
+
+    exports::clear_progress(path)?;
+    Ok(imported)
+}
+
+/// Gets the snapshot of the schema of the given collection last saved in redis,
+/// which is empty if none was saved
+pub(crate) fn get_schema_snapshot(
+    pool: &pools::RedisPool,
+    collection_name: &str,
+) -> PyResult<SchemaSnapshot> {
+    let mut conn = pools::get_connection(pool)?;
+
+    redis::cmd("HGETALL")
+        .arg(generate_schema_snapshot_key(collection_name))
+        .query(conn.deref_mut())
+        .map_err(|e| PyConnectionError::new_err(e.to_string()))
+}
+
+/// Saves the snapshot of the given schema of the given collection in redis, replacing any older snapshot
+pub(crate) fn save_schema_snapshot(
+    pool: &pools::RedisPool,
+    collection_name: &str,
+    meta: &CollectionMeta,
+) -> PyResult<()> {
+    let mut conn = pools::get_connection(pool)?;
+
+    let pipe = migrations::generate_save_snapshot_pipeline(collection_name, &meta.schema);
+    pipe.query(conn.deref_mut())
+        .map_err(|e| PyConnectionError::new_err(e.to_string()))?;
+    meta.mirrors.replicate(&pipe);
+    Ok(())
+}
+
+/// Measures the round-trip latencies of the given number of PINGs to redis, made one after the other
+/// on one connection of the pool
+pub(crate) fn measure_latency(pool: &pools::RedisPool, samples: usize) -> PyResult<LatencyStats> {
+    let start = Instant::now();
+    let mut conn = pools::get_connection(pool)?;
+    let checkout = start.elapsed();
+
+    let mut latencies: Vec<Duration> = Vec::with_capacity(samples);
+    for _ in 0..samples {
+        let start = Instant::now();
+        redis::cmd("PING")
+            .query::<()>(conn.deref_mut())
+            .map_err(|e| PyConnectionError::new_err(e.to_string()))?;
+        latencies.push(start.elapsed());
+    }
+
+    Ok(LatencyStats::new(checkout, latencies))
+}
+
+/// Gets the usage and the limits of the quota of the given namespace
+pub(crate) fn get_quota(pool: &pools::RedisPool, namespace: &str) -> PyResult<Quota> {
+    let mut conn = pools::get_connection(pool)?;
+    quotas::generate_get_cmd(&generate_quota_key(namespace))
+        .query(conn.deref_mut())
+        .map(Quota::from_reply)
+        .map_err(|e| PyConnectionError::new_err(e.to_string()))
+}
+
+/// Sets the limits of the quota of the given namespace, removing those that are not given
+pub(crate) fn set_quota(
+    pool: &pools::RedisPool,
+    namespace: &str,
+    max_records: Option<u64>,
+    max_bytes: Option<u64>,
+) -> PyResult<()> {
+    let mut conn = pools::get_connection(pool)?;
+    quotas::generate_set_pipeline(&generate_quota_key(namespace), max_records, max_bytes)
+        .query(conn.deref_mut())
+        .map_err(|e| PyConnectionError::new_err(e.to_string()))
+}
+
+/// Gets the events in the change stream of the given collection that come after the event
+/// of the given id, as dictionaries
+pub(crate) fn get_changes(
+    pool: &pools::RedisPool,
+    collection_name: &str,
+    meta: &CollectionMeta,
+    after: &str,
+    count: usize,
+) -> PyResult<Vec<Py<PyAny>>> {
+    let mut conn = pools::get_connection(pool)?;
+
+    let result: redis::Value = generate_read_changes_cmd(collection_name, after, count)
+        .query(conn.deref_mut())
+        .map_err(|e| PyConnectionError::new_err(e.to_string()))?;
+    parse_change_events(collection_name, meta, &result)
+}
+
+/// Generates the command that reads the events in the change stream of the given collection
+/// that come after the event of the given id
+pub(crate) fn generate_read_changes_cmd(
+    collection_name: &str,
+    after: &str,
+    count: usize,
+) -> redis::Cmd {
+    let mut cmd = redis::cmd("XREAD");
+    cmd.arg("COUNT")
+        .arg(count)
+        .arg("STREAMS")
+        .arg(generate_change_stream_key(collection_name))
+        .arg(after);
+    cmd
+}
+
+/// Converts the response of XREAD on a change stream into a list of dictionaries each with
+/// "event_id", "op", "id", "before" and "after" keys. "before" and "after" are dictionaries
+/// of the non-counter fields of the record before and after the change
+pub(crate) fn parse_change_events(
+    collection_name: &str,
+    meta: &CollectionMeta,
+    result: &redis::Value,
+) -> PyResult<Vec<Py<PyAny>>> {
+    // XREAD returns nil if there are no new events else [[stream_key, [[event_id, [field, value, ...]], ...]]]
+    let entries = match result {
+        redis::Value::Nil => return Ok(vec![]),
+        _ => result
+            .as_sequence()
+            .and_then(|streams| streams.first())
+            .and_then(|stream| stream.as_sequence())
+            .and_then(|stream| stream.get(1))
+            .and_then(|entries| entries.as_sequence())
+            .ok_or_else(|| py_value_error!(result, "Response from redis is of unexpected shape"))?,
+    };
+
+    entries
+        .iter()
+        .map(|entry| {
+            let (event_id, fields) = match entry.as_sequence() {
+                Some(v) if v.len() == 2 => (redis_to_py::<String>(&v[0])?, &v[1]),
+                _ => {
+                    return Err(py_value_error!(
+                        entry,
+                        "change event is of unexpected shape"
+                    ))
+                }
+            };
+            let fields = fields
+                .as_map_iter()
+                .ok_or_else(|| py_value_error!(fields, "change event is of unexpected shape"))?;
+
+            Python::with_gil(|py| {
+                let event = PyDict::new(py);
+                let before = PyDict::new(py);
+                let after = PyDict::new(py);
+                event.set_item("event_id", event_id)?;
+
+                for (k, v) in fields {
+                    let key = redis_to_py::<String>(k)?;
+                    let (data, field) = match key.split_once('.') {
+                        Some(("before", field)) => (before, field),
+                        Some(("after", field)) => (after, field),
+                        _ => {
+                            event.set_item(key, redis_to_py::<String>(v)?)?;
+                            continue;
+                        }
+                    };
+
+                    match meta.schema.get_type(field) {
+                        // nested records are saved as references to their keys
+                        Some(field_type) if field_type.is_nested() => {
+                            data.set_item(field, redis_to_py::<String>(v)?)?
+                        }
+                        Some(field_type) => data.set_item(
+                            field,
+                            coercions::redis_to_py(
+                                collection_name,
+                                field_type,
+                                v,
+                                meta.get_coercions(field),
+                            )?,
+                        )?,
+                        None => {}
+                    }
+                }
+
+                event.set_item("before", before)?;
+                event.set_item("after", after)?;
+                Ok(event.into_py(py))
+            })
+        })
+        .collect()
+}
+
+/// Gets the records for the given collection name in redis, with the given ids
+pub(crate) fn get_records_by_id(
+    pool: &pools::RedisPool,
+    collection_name: &str,
+    meta: &CollectionMeta,
+    ids: &Vec<String>,
+) -> PyResult<Vec<Py<PyAny>>> {
+    let keys: Vec<String> = ids
+        .into_iter()
+        .map(|k| generate_hash_key(collection_name, &k.to_string(), meta.case_insensitive_keys))
+        .collect();
+
+    let mut records: Vec<Py<PyAny>> = Vec::with_capacity(keys.len());
+    for batch in meta.batch_sizer.split_reads(&keys) {
+        records.extend(run_script(
+            pool,
+            collection_name,
+            meta,
+            |pipe| {
+                pipe.cmd("EVAL")
+                    .arg(SELECT_ALL_FIELDS_FOR_SOME_IDS_SCRIPT)
+                    .arg(batch.len())
+                    .arg(batch)
+                    .arg(&meta.nested_fields);
+                Ok(())
+            },
+            |data| Python::with_gil(|py| meta.model_type.call(py, (), Some(data))),
+        )?);
+    }
+    Ok(records)
+}
+
+/// Gets the records in the collection of the given name from redis with the given ids as dictionaries,
+/// with the records nested in them also as dictionaries, without constructing any models
+pub(crate) fn get_raw_records_by_id(
+    pool: &pools::RedisPool,
     collection_name: &str,
     meta: &CollectionMeta,
-    ids: &Vec<String>,
+    ids: &[String],
 ) -> PyResult<Vec<Py<PyAny>>> {
     let ids: Vec<String> = ids
-        .into_iter()
-        .map(|k| generate_hash_key(collection_name, &k.to_string()))
+        .iter()
+        .map(|k| generate_hash_key(collection_name, &k.to_string(), meta.case_insensitive_keys))
         .collect();
 
     run_script(
         pool,
-        meta,
+        collection_name,
+        &generate_raw_read_meta(meta),
         |pipe| {
             pipe.cmd("EVAL")
                 .arg(SELECT_ALL_FIELDS_FOR_SOME_IDS_SCRIPT)
@@ -90,203 +2321,798 @@ pub(crate) fn get_records_by_id(
                 .arg(&meta.nested_fields);
             Ok(())
         },
-        |data| Python::with_gil(|py| meta.model_type.call(py, (), Some(data.into_py_dict(py)))),
+        |data| Ok(data.into()),
     )
 }
 
 /// Gets records in the collection of the given name from redis with the given ids,
 /// returning a vector of dictionaries with only the fields specified for each record
 pub(crate) fn get_partial_records_by_id(
-    pool: &r2d2::Pool<redis::Client>,
+    pool: &pools::RedisPool,
     collection_name: &str,
     meta: &CollectionMeta,
     ids: &Vec<String>,
     fields: &Vec<String>,
+    nested_as_models: bool,
 ) -> PyResult<Vec<Py<PyAny>>> {
     let ids: Vec<String> = ids
         .into_iter()
-        .map(|k| generate_hash_key(collection_name, &k.to_string()))
+        .map(|k| generate_hash_key(collection_name, &k.to_string(), meta.case_insensitive_keys))
         .collect();
 
+    let fields_to_request = get_fields_to_request(meta, fields);
+    let read_meta = generate_partial_read_meta(meta, fields, nested_as_models)?;
+
     run_script(
         pool,
-        meta,
+        collection_name,
+        &read_meta,
         |pipe| {
-            pipe.cmd("EVAL")
-                .arg(SELECT_SOME_FIELDS_FOR_SOME_IDS_SCRIPT)
-                .arg(ids.len())
-                .arg(ids)
-                .arg(fields)
-                .arg(&meta.nested_fields);
+            // records of older format versions are upgraded from all their fields
+            let script = if meta.versioning.is_some() {
+                pipe.cmd("EVAL")
+                    .arg(SELECT_ALL_FIELDS_FOR_SOME_IDS_SCRIPT)
+                    .arg(ids.len())
+                    .arg(ids)
+            } else {
+                pipe.cmd("EVAL")
+                    .arg(SELECT_SOME_FIELDS_FOR_SOME_IDS_SCRIPT)
+                    .arg(ids.len())
+                    .arg(ids)
+                    .arg(&fields_to_request)
+            };
+            script.arg(&meta.nested_fields);
             Ok(())
         },
-        |data| Ok(Python::with_gil(|py| data.into_py(py))),
+        |data| {
+            // the keys are a copy so deleting from the record while going through them is safe
+            for key in data.keys() {
+                if !is_requested(fields, key.extract()?) {
+                    data.del_item(key)?;
+                }
+            }
+            Ok(data.into())
+        },
+    )
+}
+
+/// Gets the value of the given non-nested field of the record of the given id, converted to the type of the field,
+/// or None if the record or its value is not in redis. Records of older format versions are read in full
+/// by `get_partial_records_by_id()` instead so that they are upgraded
+pub(crate) fn get_field_value(
+    pool: &pools::RedisPool,
+    collection_name: &str,
+    meta: &CollectionMeta,
+    id: &str,
+    field: &str,
+) -> PyResult<Option<Py<PyAny>>> {
+    if meta.versioning.is_some() {
+        let ids = vec![id.to_string()];
+        let fields = vec![field.to_string()];
+        let records = get_partial_records_by_id(pool, collection_name, meta, &ids, &fields, false)?;
+        return extract_field_value(records, field);
+    }
+
+    let mut conn = pools::get_connection(pool)?;
+    let values: Vec<redis::Value> = generate_get_field_pipeline(collection_name, meta, id, field)
+        .query(conn.deref_mut())
+        .map_err(|e| PyConnectionError::new_err(e.to_string()))?;
+    convert_field_value(collection_name, meta, id, field, &values)
+}
+
+/// Generates the pipeline that gets the value of the given field of the record of the given id with HGET.
+/// The values of counter fields are kept in their own keys, so for them, it checks that the record exists
+/// and then GETs the value of its counter
+pub(crate) fn generate_get_field_pipeline(
+    collection_name: &str,
+    meta: &CollectionMeta,
+    id: &str,
+    field: &str,
+) -> redis::Pipeline {
+    let mut pipe = redis::pipe();
+    let key = generate_hash_key(collection_name, id, meta.case_insensitive_keys);
+    if meta.counter_fields.iter().any(|v| v == field) {
+        pipe.cmd("EXISTS")
+            .arg(key)
+            .cmd("GET")
+            .arg(generate_counter_key(
+                collection_name,
+                field,
+                id,
+                meta.case_insensitive_keys,
+            ));
+    } else {
+        pipe.cmd("HGET").arg(key).arg(field);
+    }
+    pipe
+}
+
+/// Converts the values got by the pipeline of `generate_get_field_pipeline()` into the value of the field
+pub(crate) fn convert_field_value(
+    collection_name: &str,
+    meta: &CollectionMeta,
+    id: &str,
+    field: &str,
+    values: &[redis::Value],
+) -> PyResult<Option<Py<PyAny>>> {
+    let value = match values {
+        [redis::Value::Nil] | [redis::Value::Int(0), _] | [_, redis::Value::Nil] => {
+            return Ok(None)
+        }
+        [value] | [_, value] => value,
+        _ => {
+            return Err(py_value_error!(
+                values,
+                "Response from redis is of unexpected shape"
+            ))
+        }
+    };
+
+    Python::with_gil(
+        |py| match meta.converters.convert(py, collection_name, field, value) {
+            Some(result) => result
+                .map(|(_, v)| Some(v))
+                .map_err(|e| add_conversion_context(py, e, collection_name, meta, Some(id), field)),
+            None => Err(py_key_error!(field, "key found in data but not in schema")),
+        },
     )
 }
 
-/// Gets all records in the collection of the given name from redis,
+/// Extracts the value of the given field from the partial records of `get_partial_records_by_id()`,
+/// of which there is at most one
+pub(crate) fn extract_field_value(
+    records: Vec<Py<PyAny>>,
+    field: &str,
+) -> PyResult<Option<Py<PyAny>>> {
+    Python::with_gil(|py| match records.into_iter().next() {
+        Some(record) => Ok(record
+            .as_ref(py)
+            .downcast::<PyDict>()?
+            .get_item(field)
+            .map(|v| v.into())),
+        None => Ok(None),
+    })
+}
+
+/// Gets the given page of the records in the collection of the given name from redis,
 /// returning a vector of dictionaries with only the fields specified for each record
 pub(crate) fn get_all_partial_records_in_collection(
-    pool: &r2d2::Pool<redis::Client>,
+    pool: &pools::RedisPool,
     collection_name: &str,
     meta: &CollectionMeta,
     fields: &Vec<String>,
+    nested_as_models: bool,
+    page: Page,
 ) -> PyResult<Vec<Py<PyAny>>> {
+    let fields_to_request = get_fields_to_request(meta, fields);
+    let read_meta = generate_partial_read_meta(meta, fields, nested_as_models)?;
+
     run_script(
         pool,
-        meta,
+        collection_name,
+        &read_meta,
         |pipe| {
+            // records of older format versions are upgraded from all their fields
+            let is_versioned = meta.versioning.is_some();
+            let script = if is_versioned {
+                SELECT_ALL_FIELDS_FOR_ALL_IDS_SCRIPT
+            } else {
+                SELECT_SOME_FIELDS_FOR_ALL_IDS_SCRIPT
+            };
             pipe.cmd("EVAL")
-                .arg(SELECT_SOME_FIELDS_FOR_ALL_IDS_SCRIPT)
+                .arg(script)
                 .arg(0)
-                .arg(generate_collection_key_pattern(collection_name))
-                .arg(fields)
-                .arg(&meta.nested_fields);
+                .arg(generate_collection_key_pattern(collection_name));
+            page.add_args(pipe);
+            if !is_versioned {
+                pipe.arg(&fields_to_request);
+            }
+            pipe.arg(&meta.nested_fields);
             Ok(())
         },
-        |data| Ok(Python::with_gil(|py| data.into_py(py))),
+        |data| {
+            // the keys are a copy so deleting from the record while going through them is safe
+            for key in data.keys() {
+                if !is_requested(fields, key.extract()?) {
+                    data.del_item(key)?;
+                }
+            }
+            Ok(data.into())
+        },
     )
 }
 
-/// Gets all the records that are in the given collection
+/// Gets the page of at most `limit` records of the given collection at the given cursor, with only the given
+/// fields, and the total number of records in the collection. The ids of the page are got in one script call
+/// and their records in another, so the cost of reading records depends on the limit rather than on the size
+/// of the collection
+pub(crate) fn browse_records(
+    pool: &pools::RedisPool,
+    collection_name: &str,
+    meta: &CollectionMeta,
+    cursor: BrowseCursor,
+    limit: usize,
+    fields: &Vec<String>,
+) -> PyResult<(BrowsedPage, Vec<Py<PyAny>>)> {
+    let reply = pools::get_connection(pool).and_then(|mut conn| {
+        cursor
+            .generate_browse_cmd(collection_name, limit)
+            .query(conn.deref_mut())
+            .map_err(|e| PyConnectionError::new_err(e.to_string()))
+    })?;
+    let page = BrowsedPage::from_reply(collection_name, reply);
+    if page.ids.is_empty() {
+        return Ok((page, vec![]));
+    }
+
+    let records = get_partial_records_by_id(pool, collection_name, meta, &page.ids, fields, false)?;
+    Ok((page, records))
+}
+
+/// Adds to the pipeline the command that gets the given page of the records of the given collection whose fields
+/// are equal to the values in the given filter, which are compared as saved in redis. All records are got if the
+/// filter is empty
+pub(crate) fn add_get_all_cmd(
+    pipe: &mut redis::Pipeline,
+    collection_name: &str,
+    meta: &CollectionMeta,
+    filter: &[(String, String)],
+    page: Page,
+) {
+    let pattern = generate_collection_key_pattern(collection_name);
+    if filter.is_empty() {
+        pipe.cmd("EVAL")
+            .arg(SELECT_ALL_FIELDS_FOR_ALL_IDS_SCRIPT)
+            .arg(0)
+            .arg(pattern);
+        page.add_args(pipe);
+        pipe.arg(&meta.nested_fields);
+        return;
+    }
+
+    pipe.cmd("EVAL")
+        .arg(SELECT_MATCHING_RECORDS_SCRIPT)
+        .arg(0)
+        .arg(pattern);
+    page.add_args(pipe);
+    pipe.arg(meta.nested_fields.len()).arg(&meta.nested_fields);
+    for (field, value) in filter {
+        pipe.arg(field).arg(value);
+    }
+}
+
+/// Gets the given page of the records that are in the given collection whose fields are equal to the values in
+/// the given filter, handling those that cannot be read as on_error says. It returns the records read with the errors
+/// of those that were not, if they are collected
 pub(crate) fn get_all_records_in_collection(
-    pool: &r2d2::Pool<redis::Client>,
+    pool: &pools::RedisPool,
     collection_name: &str,
     meta: &CollectionMeta,
-) -> PyResult<Vec<Py<PyAny>>> {
-    run_script(
+    filter: &[(String, String)],
+    page: Page,
+    on_error: OnError,
+) -> PyResult<(Vec<Py<PyAny>>, Vec<ReadError>)> {
+    run_script_with_errors(
+        pool,
+        collection_name,
+        meta,
+        |pipe| {
+            add_get_all_cmd(pipe, collection_name, meta, filter, page);
+            Ok(())
+        },
+        |data| Python::with_gil(|py| meta.model_type.call(py, (), Some(data))),
+        on_error,
+    )
+}
+
+/// Gets the given page of the records of the given collection whose fields are equal to the values in the given
+/// filter, in the order of their values of the given field, descending if desc, handling those that cannot be read
+/// as on_error says. The ids of the page are got in one script call, and their records in another
+pub(crate) fn get_ordered_records_in_collection(
+    pool: &pools::RedisPool,
+    collection_name: &str,
+    meta: &CollectionMeta,
+    ordering: &Ordering,
+    filter: &[(String, String)],
+    page: Page,
+    on_error: OnError,
+) -> PyResult<(Vec<Py<PyAny>>, Vec<ReadError>)> {
+    let keys: Vec<String> = get_ordered_ids(pool, collection_name, meta, ordering, filter, page)?
+        .iter()
+        .map(|id| generate_hash_key(collection_name, id, meta.case_insensitive_keys))
+        .collect();
+
+    run_script_with_errors(
         pool,
+        collection_name,
         meta,
         |pipe| {
             pipe.cmd("EVAL")
-                .arg(SELECT_ALL_FIELDS_FOR_ALL_IDS_SCRIPT)
-                .arg(0)
-                .arg(generate_collection_key_pattern(collection_name))
+                .arg(SELECT_ALL_FIELDS_FOR_SOME_IDS_SCRIPT)
+                .arg(keys.len())
+                .arg(&keys)
                 .arg(&meta.nested_fields);
             Ok(())
         },
-        |data| Python::with_gil(|py| meta.model_type.call(py, (), Some(data.into_py_dict(py)))),
+        |data| Python::with_gil(|py| meta.model_type.call(py, (), Some(data))),
+        on_error,
     )
 }
 
+/// Gets the ids of the given page of the records of the given collection whose fields are equal to the values
+/// in the given filter, in the order of their values of the given field, descending if desc
+pub(crate) fn get_ordered_ids(
+    pool: &pools::RedisPool,
+    collection_name: &str,
+    meta: &CollectionMeta,
+    ordering: &Ordering,
+    filter: &[(String, String)],
+    page: Page,
+) -> PyResult<Vec<String>> {
+    refresh_registered_fields(pool, collection_name, meta)?;
+    let mut conn = pools::get_connection(pool)?;
+
+    ordering
+        .generate_ids_cmd(collection_name, meta, filter, page)
+        .query(conn.deref_mut())
+        .map_err(|e| PyConnectionError::new_err(e.to_string()))
+}
+
+/// Gets all the records in the collection of the given name, splitting the ids got by one SCAN
+/// into a shard for each worker. Each worker reads its shard in batches on its own connection,
+/// so there are at most as many workers as connections in the pool. The records are returned
+/// in the order of the shards
+pub(crate) fn get_all_records_in_parallel(
+    py: Python,
+    pool: &pools::RedisPool,
+    collection_name: &str,
+    meta: &CollectionMeta,
+    workers: usize,
+) -> PyResult<Vec<Py<PyAny>>> {
+    let ids = get_record_ids(pool, collection_name)?;
+    let shard_size = get_shard_size(ids.len(), workers.min(pool.max_size() as usize));
+
+    // the gil is released so that the workers can parse the records they read
+    py.allow_threads(|| {
+        thread::scope(|scope| {
+            let handles: Vec<_> = ids
+                .chunks(shard_size)
+                .map(|shard| {
+                    scope.spawn(move || {
+                        shard
+                            .chunks(PARALLEL_READ_BATCH_SIZE)
+                            .map(|batch| {
+                                get_records_by_id(pool, collection_name, meta, &batch.to_vec())
+                            })
+                            .collect::<PyResult<Vec<Vec<Py<PyAny>>>>>()
+                    })
+                })
+                .collect();
+
+            let mut records: Vec<Py<PyAny>> = Vec::with_capacity(ids.len());
+            for handle in handles {
+                let batches = handle.join().map_err(|_| {
+                    PyRuntimeError::new_err("a worker reading the records stopped unexpectedly")
+                })??;
+                records.extend(batches.into_iter().flatten());
+            }
+            Ok(records)
+        })
+    })
+}
+
+/// Gets the number of ids in each shard when the given number of ids is split across the given
+/// number of workers, the last shard being the smallest
+pub(crate) fn get_shard_size(number_of_ids: usize, workers: usize) -> usize {
+    let workers = workers.max(1);
+    ((number_of_ids + workers - 1) / workers).max(1)
+}
+
 /// Runs a lua script, and handles the response, transforming it into a list of hashmaps which
 /// is then transformed into a list of Py<PyAny> using the item_parser function
 pub(crate) fn run_script<T, F>(
-    pool: &r2d2::Pool<redis::Client>,
+    pool: &pools::RedisPool,
+    collection_name: &str,
     meta: &CollectionMeta,
     script: T,
     item_parser: F,
 ) -> PyResult<Vec<Py<PyAny>>>
 where
     T: FnOnce(&mut redis::Pipeline) -> PyResult<()>,
-    F: FnOnce(HashMap<String, Py<PyAny>>) -> PyResult<Py<PyAny>> + Copy,
+    F: FnOnce(&PyDict) -> PyResult<Py<PyAny>> + Copy,
 {
-    let mut conn = pool
-        .get()
-        .map_err(|e| PyConnectionError::new_err(e.to_string()))?;
+    let (records, _) = run_script_with_errors(
+        pool,
+        collection_name,
+        meta,
+        script,
+        item_parser,
+        OnError::Raise,
+    )?;
+    Ok(records)
+}
+
+/// Runs a lua script like run_script, handling the records that cannot be read as on_error says.
+/// It returns the records read with the errors of those that were not, if they are collected
+pub(crate) fn run_script_with_errors<T, F>(
+    pool: &pools::RedisPool,
+    collection_name: &str,
+    meta: &CollectionMeta,
+    script: T,
+    item_parser: F,
+    on_error: OnError,
+) -> PyResult<(Vec<Py<PyAny>>, Vec<ReadError>)>
+where
+    T: FnOnce(&mut redis::Pipeline) -> PyResult<()>,
+    F: FnOnce(&PyDict) -> PyResult<Py<PyAny>> + Copy,
+{
+    let mut errors: Vec<ReadError> = vec![];
+    let mut conn = profiling::measure(Phase::Network, || pools::get_connection(pool))?;
     let mut pipe = redis::pipe();
 
-    script(&mut pipe)?;
+    profiling::measure(Phase::Serialize, || script(&mut pipe))?;
 
-    let result: redis::Value = pipe
-        .query(conn.deref_mut())
+    let result: redis::Value = profiling::measure(Phase::Network, || pipe.query(conn.deref_mut()))
         .or_else(|e| Err(PyConnectionError::new_err(e.to_string())))?;
 
-    let results = result
-        .as_sequence()
-        .ok_or_else(|| py_value_error!(result, "Response from redis is of unexpected shape"))?
-        .get(0)
-        .ok_or_else(|| py_value_error!(result, "Response from redis is of unexpected shape"))?
-        .as_sequence()
-        .ok_or_else(|| py_value_error!(result, "Response from redis is of unexpected shape"))?;
+    let mut results = profiling::measure(Phase::Parse, || extract_script_results(result))?;
+
+    // the script reads the records nested directly in the records; deeper ones are read level by level
+    for level in 2..=meta.nested_depth {
+        let keys = collect_unhydrated_keys(&mut results, &meta.schema, level)?;
+        if keys.is_empty() {
+            break;
+        }
+
+        let values: Vec<redis::Value> = profiling::measure(Phase::Network, || {
+            generate_hydration_pipeline(&keys).query(conn.deref_mut())
+        })
+        .map_err(|e| PyConnectionError::new_err(e.to_string()))?;
+        fill_unhydrated_records(&mut results, &meta.schema, level, values)?;
+    }
+    meta.batch_sizer.observe(&results);
 
     let empty_value = redis::Value::Bulk(vec![]);
-    let mut list_of_results: Vec<Py<PyAny>> = Vec::with_capacity(results.len());
-
-    for item in results {
-        if *item != empty_value {
-            match item.as_map_iter() {
-                None => return Err(py_value_error!(item, "redis value is not a map")),
-                Some(item) => {
-                    let data = item
-                        .map(|(k, v)| {
-                            let key = redis_to_py::<String>(k)?;
-                            let value = match meta.schema.get_type(&key) {
-                                Some(field_type) => field_type.redis_to_py(v),
-                                None => {
-                                    Err(py_key_error!(&key, "key found in data but not in schema"))
-                                }
-                            }?;
-                            Ok((key, value))
-                        })
-                        .collect::<PyResult<HashMap<String, Py<PyAny>>>>()?;
-                    let data = item_parser(data)?;
-                    list_of_results.push(data);
+    let mut records: Vec<ParsedRecord> = Vec::with_capacity(results.len());
+
+    let mut upgrade_cmds: Vec<redis::Cmd> = vec![];
+
+    profiling::measure(Phase::Parse, || -> PyResult<()> {
+        for item in &results {
+            if *item != empty_value {
+                match parse_record(collection_name, meta, item) {
+                    Ok((record, upgrade_cmd)) => {
+                        upgrade_cmds.extend(upgrade_cmd);
+                        records.push(record);
+                    }
+                    Err(e) => on_error.handle(&mut errors, None, e)?,
+                }
+            }
+        }
+        Ok(())
+    })?;
+
+    if !upgrade_cmds.is_empty() {
+        let mut upgrade_pipe = redis::pipe();
+        for cmd in upgrade_cmds {
+            upgrade_pipe.add_command(cmd).ignore();
+        }
+        profiling::measure(Phase::Network, || {
+            upgrade_pipe.query::<()>(conn.deref_mut())
+        })
+        .map_err(|e| PyConnectionError::new_err(e.to_string()))?;
+        meta.mirrors.replicate(&upgrade_pipe);
+    }
+
+    let counter_keys = generate_counter_keys(collection_name, meta, &records);
+    if !counter_keys.is_empty() {
+        let values: Vec<redis::Value> = profiling::measure(Phase::Network, || {
+            redis::cmd("MGET").arg(counter_keys).query(conn.deref_mut())
+        })
+        .map_err(|e| PyConnectionError::new_err(e.to_string()))?;
+        merge_counter_values(meta, &mut records, &values)?;
+    }
+
+    let records = convert_records(
+        collection_name,
+        meta,
+        records,
+        item_parser,
+        on_error,
+        &mut errors,
+    )?;
+    Ok((records, errors))
+}
+
+/// Converts the parsed records of the collection of the given name into python objects with the item_parser,
+/// handling the records that cannot be converted as on_error says. The records are parsed without the gil,
+/// which is then held for only a bounded number of records at a time
+pub(crate) fn convert_records<F>(
+    collection_name: &str,
+    meta: &CollectionMeta,
+    records: Vec<ParsedRecord<'_>>,
+    item_parser: F,
+    on_error: OnError,
+    errors: &mut Vec<ReadError>,
+) -> PyResult<Vec<Py<PyAny>>>
+where
+    F: FnOnce(&PyDict) -> PyResult<Py<PyAny>> + Copy,
+{
+    let mut converted: Vec<Py<PyAny>> = Vec::with_capacity(records.len());
+    for batch in records.chunks(RECORDS_CONVERTED_PER_GIL_HOLD) {
+        // the conversions of the fields re-enter this hold of the gil instead of each acquiring it
+        Python::with_gil(|py| -> PyResult<()> {
+            for (id, fields) in batch {
+                let data = profiling::measure(Phase::Parse, || {
+                    convert_fields(py, collection_name, meta, id.as_deref(), fields)
+                });
+                match data
+                    .and_then(|data| profiling::measure(Phase::Construct, || item_parser(data)))
+                {
+                    Ok(record) => converted.push(record),
+                    Err(e) => on_error.handle(errors, id.clone(), e)?,
+                }
+            }
+            Ok(())
+        })?;
+    }
+
+    Ok(converted)
+}
+
+/// Converts the fields of a parsed record of the collection of the given name into a dictionary of python objects
+/// of the types in the schema, keyed by the interned names of the fields. Errors say which record and field failed
+fn convert_fields<'py>(
+    py: Python<'py>,
+    collection_name: &str,
+    meta: &CollectionMeta,
+    id: Option<&str>,
+    fields: &RawFields<'_>,
+) -> PyResult<&'py PyDict> {
+    let data = PyDict::new(py);
+    for (key, v) in fields {
+        let (name, value) = match meta.converters.convert(py, collection_name, key, v) {
+            Some(result) => result,
+            None => Err(py_key_error!(key, "key found in data but not in schema")),
+        }
+        .map_err(|e| add_conversion_context(py, e, collection_name, meta, id, key))?;
+        data.set_item(name, value)?;
+    }
+    Ok(data)
+}
+
+/// Adds the collection, the redis key of the record and the field whose conversion failed to the given error,
+/// keeping its type. The original error is the cause of the returned one
+fn add_conversion_context(
+    py: Python,
+    err: PyErr,
+    collection_name: &str,
+    meta: &CollectionMeta,
+    id: Option<&str>,
+    field: &str,
+) -> PyErr {
+    let record = match id {
+        Some(id) => format!(
+            "record {:?}",
+            generate_hash_key(collection_name, id, meta.case_insensitive_keys)
+        ),
+        // e.g. in partial reads that do not request the primary key field
+        None => "a record".to_string(),
+    };
+    let context_err = PyErr::from_type(
+        err.get_type(py),
+        format!(
+            "failed to read field {:?} of {} in collection {:?}: {}",
+            field,
+            record,
+            collection_name,
+            err.value(py)
+        ),
+    );
+    // the message still has the context if the cause cannot be set
+    let _ = context_err.value(py).setattr("__cause__", err.value(py));
+    context_err
+}
+
+/// Gets the records returned by the script that was run in a pipeline, from the pipeline's response
+pub(crate) fn extract_script_results(result: redis::Value) -> PyResult<Vec<redis::Value>> {
+    match result {
+        redis::Value::Bulk(mut items) if !items.is_empty() => match items.swap_remove(0) {
+            redis::Value::Bulk(results) => Ok(results),
+            v => Err(py_value_error!(
+                v,
+                "Response from redis is of unexpected shape"
+            )),
+        },
+        v => Err(py_value_error!(
+            v,
+            "Response from redis is of unexpected shape"
+        )),
+    }
+}
+
+/// Calls the given function on the nested records at the given level of nesting in the given record
+/// that were not yet read i.e. that are still the keys of the records. Level 1 is the records nested
+/// directly in the record
+fn visit_unhydrated_records(
+    record: &mut redis::Value,
+    schema: &Schema,
+    level: usize,
+    f: &mut dyn FnMut(&mut redis::Value),
+) -> PyResult<()> {
+    if let redis::Value::Bulk(items) = record {
+        for pair in items.chunks_mut(2) {
+            if let [k, v] = pair {
+                let model = match schema.get_type(&redis_to_py::<String>(k)?) {
+                    Some(type_) => type_.get_nested_model()?,
+                    None => None,
+                };
+
+                match (model, &v) {
+                    (Some(_), redis::Value::Data(_)) if level == 1 => f(v),
+                    (Some(model), redis::Value::Bulk(_)) if level > 1 => {
+                        visit_unhydrated_records(v, &model.schema, level - 1, f)?
+                    }
+                    _ => {}
                 }
             }
         }
     }
 
-    Ok(list_of_results)
+    Ok(())
+}
+
+/// Collects the keys of the nested records at the given level of nesting in the given records
+/// that were not yet read
+pub(crate) fn collect_unhydrated_keys(
+    records: &mut [redis::Value],
+    schema: &Schema,
+    level: usize,
+) -> PyResult<Vec<Vec<u8>>> {
+    let mut keys: Vec<Vec<u8>> = vec![];
+    for record in records.iter_mut() {
+        visit_unhydrated_records(record, schema, level, &mut |v| {
+            if let redis::Value::Data(key) = v {
+                keys.push(key.clone());
+            }
+        })?;
+    }
+
+    Ok(keys)
+}
+
+/// Generates the pipeline that reads the nested records of the given keys
+pub(crate) fn generate_hydration_pipeline(keys: &[Vec<u8>]) -> redis::Pipeline {
+    let mut pipe = redis::pipe();
+    for key in keys {
+        pipe.hgetall(key);
+    }
+
+    pipe
+}
+
+/// Replaces the keys of the nested records at the given level of nesting in the given records
+/// with the values read by the pipeline generated by `generate_hydration_pipeline`, in the order
+/// in which the keys were collected. Keys of records that no longer exist are left as they are
+pub(crate) fn fill_unhydrated_records(
+    records: &mut [redis::Value],
+    schema: &Schema,
+    level: usize,
+    values: Vec<redis::Value>,
+) -> PyResult<()> {
+    let mut values = values.into_iter();
+    for record in records.iter_mut() {
+        visit_unhydrated_records(record, schema, level, &mut |v| match values.next() {
+            Some(redis::Value::Bulk(fields)) if !fields.is_empty() => {
+                *v = redis::Value::Bulk(fields)
+            }
+            _ => {}
+        })?;
+    }
+
+    Ok(())
+}
+
+/// Parses a record got from redis as a map of fields into its id and its fields, which are converted
+/// into python values later by `convert_records`, upgrading it first if it is of an older format version
+/// than the collection's. It also returns the command that saves the upgraded record if the upgrade is to be persisted
+pub(crate) fn parse_record<'a>(
+    collection_name: &str,
+    meta: &CollectionMeta,
+    item: &'a redis::Value,
+) -> PyResult<(ParsedRecord<'a>, Option<redis::Cmd>)> {
+    let fields = item
+        .as_map_iter()
+        .ok_or_else(|| py_value_error!(item, "redis value is not a map"))?
+        .map(|(k, v)| Ok((redis_to_py::<String>(k)?, Cow::Borrowed(v))))
+        .collect::<PyResult<RawFields>>()?;
+
+    let (fields, upgrade_cmd) = match &meta.versioning {
+        None => (fields, None),
+        Some(versioning) => versioning.upgrade(collection_name, meta, fields)?,
+    };
+
+    let data: RawFields = fields
+        .into_iter()
+        .filter(|(key, _)| key != VERSION_FIELD)
+        .collect();
+    let id = data
+        .iter()
+        .find(|(key, _)| key == &meta.primary_key_field)
+        .map(|(_, v)| redis_to_py::<String>(v))
+        .transpose()?;
+
+    Ok(((id, data), upgrade_cmd))
 }
 
-/// Prepares the records for inserting. It may receive a model instance or a dictionary
+/// Prepares the records for inserting. It may receive a model instance or a dictionary.
+/// If no id is given and the primary key field is missing or None, the id_generator, if any,
+/// is used to generate the primary key. The ids are lowercased in the keys if is_case_insensitive
 pub(crate) fn prepare_record_to_insert(
     collection_name: &str,
-    schema: &Box<Schema>,
+    schema: &Schema,
     obj: &Py<PyAny>,
     primary_key_field: &str,
     id: Option<&str>,
-) -> PyResult<Vec<(String, Vec<(String, String)>)>> {
+    id_generator: &Option<IdGenerator>,
+    is_case_insensitive: bool,
+) -> PyResult<InsertRecords> {
     let obj = Python::with_gil(|py| match obj.extract::<HashMap<String, Py<PyAny>>>(py) {
         Ok(v) => Ok(v),
         Err(_) => obj.getattr(py, "dict")?.call0(py)?.extract(py),
     })?;
 
+    let generated_id = match (id, id_generator) {
+        (None, Some(generator)) => {
+            let has_pk = Python::with_gil(|py| match obj.get(primary_key_field) {
+                None => false,
+                Some(v) => !v.is_none(py),
+            });
+            if has_pk {
+                None
+            } else {
+                Some(generator.generate())
+            }
+        }
+        _ => None,
+    };
+
     let mut results: Vec<(String, Vec<(String, String)>)> = Vec::with_capacity(2);
     let mut parent_record: Vec<(String, String)> = Vec::with_capacity(obj.len());
 
     for (field, type_) in &schema.mapping {
         if let Some(v) = obj.get(field) {
-            match type_ {
-                FieldType::Nested {
-                    model_name,
-                    primary_key_field: nested_pk_field,
-                    schema: nested_schema,
-                    ..
-                } => {
+            match type_.get_nested_model()? {
+                // a nested record that is not set e.g. the end of a chain of self-references, is not saved
+                Some(_) if Python::with_gil(|py| v.is_none(py)) => {}
+                Some(model) => {
                     let mut data = prepare_record_to_insert(
-                        &model_name,
-                        &nested_schema,
+                        &model.model_name,
+                        &model.schema,
                         v,
-                        &nested_pk_field,
+                        &model.primary_key_field,
                         None,
+                        &None,
+                        model.case_insensitive_keys,
                     )?;
                     if let Some((k, _)) = data.last() {
                         parent_record.push((field.clone(), k.clone()));
                         results.append(&mut data);
                     }
                 }
-                FieldType::Datetime => Python::with_gil(|py| -> PyResult<()> {
-                    // convert every datetime into a UTC datetime
-                    let v = v
-                        .getattr(py, "astimezone")?
-                        .call(py, (timezone_utc(py),), None)?;
-                    parent_record.push((field.clone(), v.to_string()));
-                    Ok(())
-                })?,
-                FieldType::Bool => {
-                    let v = v.to_string().to_lowercase();
-                    parent_record.push((field.clone(), v));
-                }
-                _ => {
-                    parent_record.push((field.clone(), v.to_string()));
+                None => {
+                    parent_record.push((field.clone(), field_value_to_redis(type_, v)?));
                 }
             };
         }
     }
 
-    let primary_key = match id {
+    if let Some(generated_id) = &generated_id {
+        parent_record.retain(|(k, _)| k != primary_key_field);
+        parent_record.push((primary_key_field.to_string(), generated_id.clone()));
+    }
+
+    let primary_key = match id.or(generated_id.as_deref()) {
         None => {
             let pk = obj.get(primary_key_field).ok_or_else(|| {
                 py_key_error!(
@@ -294,20 +3120,179 @@ pub(crate) fn prepare_record_to_insert(
                     format!("primary key field missing in {:?}", obj)
                 )
             })?;
-            generate_hash_key(collection_name, &pk.to_string())
+            generate_hash_key(collection_name, &pk.to_string(), is_case_insensitive)
         }
-        Some(id) => generate_hash_key(collection_name, id),
+        Some(id) => generate_hash_key(collection_name, id, is_case_insensitive),
     };
 
     results.push((primary_key, parent_record));
     Ok(results)
 }
 
+/// Gets the primary key of the item whose records, as prepared by `prepare_record_to_insert()`, are given
+/// i.e. the id in the key of its own record, which is the last, as it is saved in redis
+pub(crate) fn get_inserted_id(collection_name: &str, records: &InsertRecords) -> String {
+    let key_prefix = generate_key_prefix(collection_name);
+    records
+        .last()
+        .and_then(|(pk, _)| pk.strip_prefix(&key_prefix))
+        .unwrap_or_default()
+        .to_string()
+}
+
+/// Prepares the records of the updates of the records of the given ids, with the given data by id, for inserting
+/// in one pipeline, with the commands that update their nested records directly. The updated records keep
+/// their versions as only some of their fields may be updated, but their new nested records are stamped
+pub(crate) fn prepare_records_to_update(
+    collection_name: &str,
+    meta: &CollectionMeta,
+    updates: Vec<(String, Py<PyAny>)>,
+) -> PyResult<(InsertRecords, Vec<redis::Cmd>)> {
+    meta.record_accesses(updates.iter().map(|(id, _)| id.as_str()));
+    let mut records = Vec::with_capacity(updates.len());
+    let mut nested_updates = vec![];
+    for (id, data) in updates {
+        let (data, nested) =
+            nested_updates::extract_nested_updates(collection_name, meta, &id, data)?;
+        let mut prepared = prepare_record_to_insert(
+            collection_name,
+            &meta.schema,
+            &data,
+            &meta.primary_key_field,
+            Some(&id),
+            &meta.id_generator,
+            meta.case_insensitive_keys,
+        )?;
+        let nested_records = prepared.len() - 1;
+        versioning::stamp_versions(&mut prepared[..nested_records], &meta.record_versions);
+        records.extend(prepared);
+        nested_updates.extend(nested);
+    }
+    Ok((records, nested_updates))
+}
+
+/// Converts the value of a non-nested field into the string that is saved in redis
+pub(crate) fn field_value_to_redis(type_: &FieldType, v: &Py<PyAny>) -> PyResult<String> {
+    match type_ {
+        FieldType::Datetime => Python::with_gil(|py| {
+            // convert every datetime into a UTC datetime
+            let v = v
+                .getattr(py, "astimezone")?
+                .call(py, (timezone_utc(py),), None)?;
+            Ok(v.to_string())
+        }),
+        FieldType::Bool => Ok(v.to_string().to_lowercase()),
+        _ => Ok(v.to_string()),
+    }
+}
+
 /// Constructs a unique key for saving a hashmap such that it can be distinguished from
-/// hashes of other collections even if they had the same id
+/// hashes of other collections even if they had the same id.
+/// The id is lowercased if the collection has case-insensitive keys
+#[inline]
+pub(crate) fn generate_hash_key(
+    collection_name: &str,
+    id: &str,
+    is_case_insensitive: bool,
+) -> String {
+    let id = key_normalization::normalize_id(id, is_case_insensitive);
+    format!("{}{}", generate_key_prefix(collection_name), id)
+}
+
+/// Constructs the prefix of the keys of the hashmaps of the records of the given collection
+#[inline]
+pub(crate) fn generate_key_prefix(collection_name: &str) -> String {
+    format!("{}_%&_", collection_name)
+}
+
+/// Constructs the key for auxiliary data of a given collection e.g. a tracker or an index
+/// in such a way that it can never match the key pattern of any collection
+#[inline]
+pub(crate) fn generate_auxiliary_key(collection_name: &str, kind: &str, name: &str) -> String {
+    format!("{}_%&{}_%&_{}", collection_name, kind, name)
+}
+
+/// Constructs the key of the HyperLogLog that tracks the distinct values of the given field
+#[inline]
+pub(crate) fn generate_cardinality_key(collection_name: &str, field: &str) -> String {
+    generate_auxiliary_key(collection_name, "hll", field)
+}
+
+/// Constructs the key of the set of the fields of the given collection whose distinct values are tracked,
+/// kept in redis so that the writes of all clients update the HyperLogLogs of the fields
+#[inline]
+pub(crate) fn generate_cardinality_fields_key(collection_name: &str) -> String {
+    generate_auxiliary_key(collection_name, "cardinality", "fields")
+}
+
+/// Constructs the key of the sorted set that indexes the ids of the records of the given collection
+/// by the values of the given field
+#[inline]
+pub(crate) fn generate_range_index_key(collection_name: &str, field: &str) -> String {
+    generate_auxiliary_key(collection_name, "zset", field)
+}
+
+/// Constructs the key of the sorted set of the ids of the records of the given collection that have
+/// no score in the range index of the given field, all with the score 0 so that they are ordered by id
+#[inline]
+pub(crate) fn generate_unscored_ids_key(collection_name: &str, field: &str) -> String {
+    generate_auxiliary_key(collection_name, "unscored", field)
+}
+
+/// Constructs the key of the sorted set that indexes the ids of the records of the given collection
+/// by the time they were inserted, in microseconds since the unix epoch
+#[inline]
+pub(crate) fn generate_insertion_order_key(collection_name: &str) -> String {
+    generate_auxiliary_key(collection_name, "zset", "%&inserted")
+}
+
+/// Constructs the key of the bloom filter of the primary keys of the given collection
+#[inline]
+pub(crate) fn generate_bloom_filter_key(collection_name: &str) -> String {
+    generate_auxiliary_key(collection_name, "bloom", "ids")
+}
+
+/// Constructs the key of the counter of the given field for the record of the given id
+#[inline]
+pub(crate) fn generate_counter_key(
+    collection_name: &str,
+    field: &str,
+    id: &str,
+    is_case_insensitive: bool,
+) -> String {
+    let id = key_normalization::normalize_id(id, is_case_insensitive);
+    generate_auxiliary_key(collection_name, "counter", &format!("{}_%&_{}", field, id))
+}
+
+/// Constructs the key of the stream in which the changes to the records of the given collection are recorded
+#[inline]
+pub(crate) fn generate_change_stream_key(collection_name: &str) -> String {
+    generate_auxiliary_key(collection_name, "changes", "stream")
+}
+
+/// Constructs the key of the hashmap in which the snapshot of the schema of the given collection is saved
+#[inline]
+pub(crate) fn generate_schema_snapshot_key(collection_name: &str) -> String {
+    generate_auxiliary_key(collection_name, "schema", "snapshot")
+}
+
+/// Constructs the keys of the guard of a write of the given collection whose checks e.g. of the quota, are made
+/// right before its transaction: the key watched by the transaction, which a check sets to abort it if it fails,
+/// and the key at which the checks save the changes they make, so that those made before a failed check are taken
+/// back. They are unique to the write so that no other write aborts it
+#[inline]
+pub(crate) fn generate_write_guard_keys(collection_name: &str) -> (String, String) {
+    let write_id = format!("{:032x}", fastrand::u128(..));
+    (
+        generate_auxiliary_key(collection_name, "guard", &write_id),
+        generate_auxiliary_key(collection_name, "undo", &write_id),
+    )
+}
+
+/// Constructs the key of the hashmap of the usage and the limits of the quota of the given namespace
 #[inline]
-pub(crate) fn generate_hash_key(collection_name: &str, id: &str) -> String {
-    format!("{}_%&_{}", collection_name, id)
+pub(crate) fn generate_quota_key(namespace: &str) -> String {
+    generate_auxiliary_key(namespace, "quota", "usage")
 }
 
 /// Constructs a pattern for the keys that belong to a given collection
@@ -0,0 +1,223 @@
+use pyo3::exceptions::{PyConnectionError, PyKeyError, PyValueError};
+use pyo3::prelude::*;
+use redis::FromRedisValue;
+
+use crate::expiry::Expiry;
+use crate::field_types::FieldType;
+use crate::key_normalization;
+use crate::parsers;
+use crate::store::CollectionMeta;
+use crate::utils;
+
+/// The name under which RediSearch returns the distance of each record from the query vector
+const DISTANCE_FIELD: &str = "orredis_distance";
+
+/// Ensures the given field is a vector field of the collection, returning its dimension
+pub(crate) fn ensure_vector_field(meta: &CollectionMeta, field: &str) -> PyResult<usize> {
+    match meta.schema.get_type(field) {
+        None => Err(PyKeyError::new_err(format!(
+            "{:?} is not a field of the model",
+            field
+        ))),
+        Some(FieldType::Vector { dimension }) => Ok(*dimension),
+        Some(type_) => Err(PyValueError::new_err(format!(
+            "{:?} is a {} field yet only vector fields can be searched by their nearest neighbours",
+            field,
+            type_.describe()
+        ))),
+    }
+}
+
+/// Packs the given vector, as it is saved in the main hash of its record e.g. "[0.1, 0.2]", into the
+/// little-endian 32-bit floats that RediSearch indexes. It returns None if the value is not a vector e.g. None
+pub(crate) fn pack(value: &str) -> Option<Vec<u8>> {
+    if value == "None" {
+        return None;
+    }
+
+    let mut packed = Vec::new();
+    for item in parsers::extract_str_portions(value, '[', ']', ',') {
+        packed.extend_from_slice(&item.parse::<f32>().ok()?.to_le_bytes());
+    }
+    Some(packed)
+}
+
+/// Packs the vector to search the nearest neighbours of, ensuring it has the dimension of the field
+pub(crate) fn pack_query_vector(
+    field: &str,
+    dimension: usize,
+    vector: Vec<f32>,
+) -> PyResult<Vec<u8>> {
+    if vector.len() != dimension {
+        return Err(PyValueError::new_err(format!(
+            "the query vector should have {} items like the field {:?}, got {}",
+            dimension,
+            field,
+            vector.len()
+        )));
+    }
+
+    Ok(vector.iter().flat_map(|v| v.to_le_bytes()).collect())
+}
+
+/// Constructs the key of the hash holding the packed vectors of the record of the given id
+#[inline]
+pub(crate) fn generate_vector_key(
+    collection_name: &str,
+    id: &str,
+    is_case_insensitive: bool,
+) -> String {
+    let id = key_normalization::normalize_id(id, is_case_insensitive);
+    utils::generate_auxiliary_key(collection_name, "vector", &id)
+}
+
+/// Constructs the name of the RediSearch index of the given vector field
+#[inline]
+fn generate_index_name(collection_name: &str, field: &str) -> String {
+    utils::generate_auxiliary_key(collection_name, "vector_index", field)
+}
+
+/// Whether the given collection has any vector field
+#[inline]
+pub(crate) fn has_vector_fields(meta: &CollectionMeta) -> bool {
+    meta.schema
+        .mapping
+        .values()
+        .any(|type_| matches!(type_, FieldType::Vector { .. }))
+}
+
+/// Adds to the pipeline the commands that save the packed vectors of the given fields of the record
+/// of the given id, removing those that are not vectors e.g. None, with the same expiry as the record
+pub(crate) fn add_update_cmd(
+    pipe: &mut redis::Pipeline,
+    collection_name: &str,
+    meta: &CollectionMeta,
+    id: &str,
+    fields: &[(&String, &String)],
+    ttl: &Option<Expiry>,
+) {
+    let key = generate_vector_key(collection_name, id, meta.case_insensitive_keys);
+    let mut is_updated = false;
+    for (field, value) in fields {
+        if let Some(FieldType::Vector { .. }) = meta.schema.get_type(field) {
+            match pack(value) {
+                Some(packed) => pipe.hset(&key, *field, packed),
+                None => pipe.hdel(&key, *field),
+            };
+            is_updated = true;
+        }
+    }
+
+    if let (true, Some(expiry)) = (is_updated, ttl) {
+        expiry.add_cmd(pipe, &key);
+    }
+}
+
+/// Generates the command that creates the RediSearch index of the given vector field of the collection,
+/// comparing vectors by their cosine distance
+pub(crate) fn generate_create_index_cmd(
+    collection_name: &str,
+    field: &str,
+    dimension: usize,
+) -> redis::Cmd {
+    let mut cmd = redis::cmd("FT.CREATE");
+    cmd.arg(generate_index_name(collection_name, field))
+        .arg("ON")
+        .arg("HASH")
+        .arg("PREFIX")
+        .arg(1)
+        .arg(generate_vector_key(collection_name, "", false))
+        .arg("SCHEMA")
+        .arg(field)
+        .arg("VECTOR")
+        .arg("FLAT")
+        .arg(6)
+        .arg("TYPE")
+        .arg("FLOAT32")
+        .arg("DIM")
+        .arg(dimension)
+        .arg("DISTANCE_METRIC")
+        .arg("COSINE");
+    cmd
+}
+
+/// Generates the command that gets the keys of the vectors of the `k` records whose given field
+/// is nearest to the given packed vector, nearest first
+pub(crate) fn generate_knn_cmd(
+    collection_name: &str,
+    field: &str,
+    vector: &[u8],
+    k: usize,
+) -> redis::Cmd {
+    let mut cmd = redis::cmd("FT.SEARCH");
+    cmd.arg(generate_index_name(collection_name, field))
+        .arg(format!(
+            "*=>[KNN $k @{} $vector AS {}]",
+            field, DISTANCE_FIELD
+        ))
+        .arg("PARAMS")
+        .arg(4)
+        .arg("k")
+        .arg(k)
+        .arg("vector")
+        .arg(vector)
+        .arg("SORTBY")
+        .arg(DISTANCE_FIELD)
+        .arg("LIMIT")
+        .arg(0)
+        .arg(k)
+        .arg("NOCONTENT")
+        .arg("DIALECT")
+        .arg(2);
+    cmd
+}
+
+/// Whether the given error is that of a search on an index that does not exist yet
+pub(crate) fn is_missing_index(err: &redis::RedisError) -> bool {
+    let message = err.to_string().to_lowercase();
+    message.contains("no such index") || message.contains("unknown index")
+}
+
+/// Whether the given error is that of the creation of an index that already exists e.g. one created
+/// by another process since the search that found it missing
+pub(crate) fn is_existing_index(err: &redis::RedisError) -> bool {
+    err.to_string()
+        .to_lowercase()
+        .contains("index already exists")
+}
+
+/// Converts the given error of a vector search into a python error, saying so if RediSearch is not loaded
+pub(crate) fn to_py_err(err: redis::RedisError) -> PyErr {
+    let message = err.to_string();
+    if message.to_lowercase().contains("unknown command") {
+        PyConnectionError::new_err(format!(
+            "vector searches need the RediSearch module to be loaded in redis: {}",
+            message
+        ))
+    } else {
+        PyConnectionError::new_err(message)
+    }
+}
+
+/// Extracts the ids of the records, nearest first, from the reply of a vector search i.e. the number of
+/// results followed by the keys of their vectors
+pub(crate) fn parse_knn_ids(collection_name: &str, reply: redis::Value) -> PyResult<Vec<String>> {
+    let items = match reply {
+        redis::Value::Bulk(items) => items,
+        _ => return Ok(vec![]),
+    };
+
+    let key_prefix = generate_vector_key(collection_name, "", false);
+    items
+        .iter()
+        .skip(1)
+        .map(|item| {
+            let key = String::from_redis_value(item)
+                .map_err(|e| PyConnectionError::new_err(e.to_string()))?;
+            Ok(key
+                .strip_prefix(&key_prefix)
+                .map(str::to_string)
+                .unwrap_or(key))
+        })
+        .collect()
+}
@@ -0,0 +1,189 @@
+use std::collections::{BTreeSet, HashMap};
+
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+
+use crate::parsers::redis_to_py;
+use crate::store::CollectionMeta;
+use crate::utils;
+
+/// The fields of a record as they are saved in redis, including its counter fields.
+/// It is empty if the record does not exist
+pub(crate) type RawRecord = HashMap<String, redis::Value>;
+
+/// Ensures the given sample rate is a fraction greater than 0 and at most 1
+pub(crate) fn validate_sample_rate(sample: f64) -> PyResult<f64> {
+    if sample > 0.0 && sample <= 1.0 {
+        Ok(sample)
+    } else {
+        Err(PyValueError::new_err(format!(
+            "sample should be greater than 0 and at most 1, got {}",
+            sample
+        )))
+    }
+}
+
+/// Whether a record should be part of a sample of the given rate
+#[inline]
+pub(crate) fn is_sampled(sample: f64) -> bool {
+    fastrand::f64() < sample
+}
+
+/// Picks `n` of the given ids at random, or all of them in a random order if there are not more than `n`
+pub(crate) fn pick_random_ids(mut ids: Vec<String>, n: usize) -> Vec<String> {
+    let n = n.min(ids.len());
+    // a partial Fisher-Yates shuffle moves the picked ids to the front
+    for i in 0..n {
+        let j = fastrand::usize(i..ids.len());
+        ids.swap(i, j);
+    }
+    ids.truncate(n);
+    ids
+}
+
+/// Combines the ids sampled from two stores into one sorted list without duplicates
+pub(crate) fn merge_sampled_ids(ids: Vec<String>, other_ids: Vec<String>) -> Vec<String> {
+    ids.into_iter()
+        .chain(other_ids)
+        .collect::<BTreeSet<String>>()
+        .into_iter()
+        .collect()
+}
+
+/// Generates the pipeline that reads the records of the given ids as they are saved in redis,
+/// each with the values of its counters
+pub(crate) fn generate_read_pipeline(
+    collection_name: &str,
+    meta: &CollectionMeta,
+    ids: &[String],
+) -> redis::Pipeline {
+    let mut pipe = redis::pipe();
+    for id in ids {
+        pipe.hgetall(utils::generate_hash_key(
+            collection_name,
+            id,
+            meta.case_insensitive_keys,
+        ));
+        for field in &meta.counter_fields {
+            pipe.get(utils::generate_counter_key(
+                collection_name,
+                field,
+                id,
+                meta.case_insensitive_keys,
+            ));
+        }
+    }
+
+    pipe
+}
+
+/// Converts the response of the pipeline generated by `generate_read_pipeline` into raw records
+pub(crate) fn parse_raw_records(
+    meta: &CollectionMeta,
+    values: Vec<redis::Value>,
+) -> PyResult<Vec<RawRecord>> {
+    values
+        .chunks(1 + meta.counter_fields.len())
+        .map(|chunk| {
+            let mut record: RawRecord = chunk[0]
+                .as_map_iter()
+                .ok_or_else(|| {
+                    PyValueError::new_err(format!(
+                        "record is of unexpected shape (value was {:?})",
+                        chunk[0]
+                    ))
+                })?
+                .map(|(k, v)| Ok((redis_to_py::<String>(k)?, v.clone())))
+                .collect::<PyResult<RawRecord>>()?;
+
+            // counters of deleted records are removed with them so they only count if the record exists
+            if !record.is_empty() {
+                for (field, value) in meta.counter_fields.iter().zip(&chunk[1..]) {
+                    if *value != redis::Value::Nil {
+                        record.insert(field.clone(), value.clone());
+                    }
+                }
+            }
+
+            Ok(record)
+        })
+        .collect()
+}
+
+/// Compares the records of the given ids in this store and in the other store field by field,
+/// returning a dictionary with the keys:
+/// - "sampled": the number of records compared
+/// - "missing": the ids of the records that are only in this store
+/// - "extra": the ids of the records that are only in the other store
+/// - "mismatched": a dictionary of id to a dictionary of field to (this value, other value)
+pub(crate) fn generate_drift_report(
+    collection_name: &str,
+    meta: &CollectionMeta,
+    ids: &[String],
+    records: &[RawRecord],
+    other_records: &[RawRecord],
+) -> PyResult<Py<PyAny>> {
+    Python::with_gil(|py| {
+        let mut missing: Vec<&str> = vec![];
+        let mut extra: Vec<&str> = vec![];
+        let mismatched = PyDict::new(py);
+
+        for ((id, record), other_record) in ids.iter().zip(records).zip(other_records) {
+            match (record.is_empty(), other_record.is_empty()) {
+                (true, true) => continue,
+                (false, true) => missing.push(id),
+                (true, false) => extra.push(id),
+                (false, false) => {
+                    let fields: BTreeSet<&String> =
+                        record.keys().chain(other_record.keys()).collect();
+                    let diff = PyDict::new(py);
+                    for field in fields {
+                        let (value, other_value) = (record.get(field), other_record.get(field));
+                        if value != other_value {
+                            diff.set_item(
+                                field,
+                                (
+                                    raw_value_to_py(collection_name, meta, field, value)?,
+                                    raw_value_to_py(collection_name, meta, field, other_value)?,
+                                ),
+                            )?;
+                        }
+                    }
+
+                    if !diff.is_empty() {
+                        mismatched.set_item(id, diff)?;
+                    }
+                }
+            }
+        }
+
+        let report = PyDict::new(py);
+        report.set_item("sampled", ids.len())?;
+        report.set_item("missing", missing)?;
+        report.set_item("extra", extra)?;
+        report.set_item("mismatched", mismatched)?;
+        Ok(report.into_py(py))
+    })
+}
+
+/// Converts the raw value of the given field into its python value. Nested records are
+/// saved as references to their keys so they are returned as strings, as are fields
+/// that are not in the schema
+fn raw_value_to_py(
+    collection_name: &str,
+    meta: &CollectionMeta,
+    field: &str,
+    value: Option<&redis::Value>,
+) -> PyResult<Py<PyAny>> {
+    match (value, meta.schema.get_type(field)) {
+        (None, _) => Ok(Python::with_gil(|py| py.None())),
+        (Some(value), Some(field_type)) if !field_type.is_nested() => {
+            field_type.redis_to_py(collection_name, value)
+        }
+        (Some(value), _) => {
+            let value = redis_to_py::<String>(value)?;
+            Ok(Python::with_gil(|py| value.into_py(py)))
+        }
+    }
+}
@@ -0,0 +1,232 @@
+use std::borrow::Cow;
+use std::collections::HashMap;
+
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use pyo3::types::PyString;
+
+use crate::field_types::FieldType;
+use crate::parsers::redis_to_py;
+use crate::schema::Schema;
+use crate::store::CollectionMeta;
+use crate::utils;
+
+/// The field in which the format version of each record is saved. Pydantic models
+/// cannot have fields starting with an underscore so it never clashes with a model's field
+pub(crate) const VERSION_FIELD: &str = "_version";
+pub(crate) const UPGRADE_RECORD_SCRIPT: &str = r"local table_unpack = table.unpack or unpack if redis.call('EXISTS', KEYS[1]) == 0 then return 0 end local current = redis.call('HGET', KEYS[1], ARGV[1]) or '' if current ~= ARGV[2] then return 0 end local n = tonumber(ARGV[3]) for i = 4, n + 3 do redis.call('HDEL', KEYS[1], ARGV[i]) end redis.call('HSET', KEYS[1], table_unpack(ARGV, n + 4)) return 1";
+
+/// The fields of a record got from redis, as (field name, value) pairs
+pub(crate) type RawFields<'a> = Vec<(String, Cow<'a, redis::Value>)>;
+
+/// The format version of the records of a collection, with the python functions that upgrade
+/// records from older versions. Records saved without a version are at version 0
+#[derive(Clone, Debug)]
+pub(crate) struct Versioning {
+    pub(crate) version: u32,
+    upgrades: HashMap<u32, Py<PyAny>>,
+    persist_upgrades: bool,
+}
+
+impl Versioning {
+    /// Creates a new versioning if a version is given, validating that the upgrades are callables
+    /// from versions older than the current one
+    pub(crate) fn from_py(
+        version: Option<u32>,
+        upgrades: Option<HashMap<u32, Py<PyAny>>>,
+        persist_upgrades: bool,
+    ) -> PyResult<Option<Self>> {
+        let version = match version {
+            Some(version) => version,
+            None if upgrades.is_none() && !persist_upgrades => return Ok(None),
+            None => {
+                return Err(PyValueError::new_err(
+                    "upgrades and persist_upgrades cannot be set without a version",
+                ))
+            }
+        };
+
+        let upgrades = upgrades.unwrap_or_default();
+        Python::with_gil(|py| {
+            for (from_version, upgrade) in &upgrades {
+                if *from_version >= version {
+                    return Err(PyValueError::new_err(format!(
+                        "upgrade from version {} should be from a version older than {}",
+                        from_version, version
+                    )));
+                }
+
+                if !upgrade.as_ref(py).is_callable() {
+                    return Err(PyValueError::new_err(format!(
+                        "upgrade from version {} should be callable",
+                        from_version
+                    )));
+                }
+            }
+
+            Ok(())
+        })?;
+
+        Ok(Some(Self {
+            version,
+            upgrades,
+            persist_upgrades,
+        }))
+    }
+
+    /// Upgrades the given fields of a record of the collection to the current version, calling the
+    /// upgrade function of each version from the record's version. Nested fields are left as they are.
+    /// It also returns the command that saves the upgraded record if the upgrades are to be persisted
+    pub(crate) fn upgrade<'a>(
+        &self,
+        collection_name: &str,
+        meta: &CollectionMeta,
+        fields: RawFields<'a>,
+    ) -> PyResult<(RawFields<'a>, Option<redis::Cmd>)> {
+        let saved_version = fields
+            .iter()
+            .find(|(k, _)| k == VERSION_FIELD)
+            .map(|(_, v)| redis_to_py::<String>(v))
+            .transpose()?;
+        let from_version = match &saved_version {
+            None => 0,
+            Some(v) => v.parse::<u32>().unwrap_or(0),
+        };
+
+        if from_version >= self.version {
+            return Ok((fields, None));
+        }
+
+        let (nested, flat): (RawFields, RawFields) = fields
+            .into_iter()
+            .filter(|(k, _)| k != VERSION_FIELD)
+            .partition(
+                |(k, _)| matches!(meta.schema.get_type(k), Some(type_) if type_.is_nested()),
+            );
+        let flat = flat
+            .into_iter()
+            .map(|(k, v)| Ok((k, redis_to_py::<String>(&v)?)))
+            .collect::<PyResult<HashMap<String, String>>>()?;
+
+        let mut data = flat.clone();
+        for version in from_version..self.version {
+            if let Some(upgrade) = self.upgrades.get(&version) {
+                data = call_upgrade(upgrade, &meta.schema, data)?;
+            }
+        }
+
+        let cmd = match data.get(&meta.primary_key_field) {
+            Some(id) if self.persist_upgrades => Some(self.generate_persist_cmd(
+                &utils::generate_hash_key(collection_name, id, meta.case_insensitive_keys),
+                saved_version.as_deref().unwrap_or_default(),
+                &flat,
+                &data,
+            )),
+            _ => None,
+        };
+
+        let mut fields = nested;
+        fields.extend(
+            data.into_iter()
+                .map(|(k, v)| (k, Cow::Owned(redis::Value::Data(v.into_bytes())))),
+        );
+        Ok((fields, cmd))
+    }
+
+    /// Generates the command that saves the upgraded fields of the record of the given key
+    /// at the current version, unless the record has been changed to another version in the meantime
+    fn generate_persist_cmd(
+        &self,
+        key: &str,
+        saved_version: &str,
+        fields: &HashMap<String, String>,
+        upgraded_fields: &HashMap<String, String>,
+    ) -> redis::Cmd {
+        let removed_fields: Vec<&String> = fields
+            .keys()
+            .filter(|k| !upgraded_fields.contains_key(*k))
+            .collect();
+
+        let mut cmd = redis::cmd("EVAL");
+        cmd.arg(UPGRADE_RECORD_SCRIPT)
+            .arg(1)
+            .arg(key)
+            .arg(VERSION_FIELD)
+            .arg(saved_version)
+            .arg(removed_fields.len())
+            .arg(removed_fields)
+            .arg(VERSION_FIELD)
+            .arg(self.version);
+
+        for (k, v) in upgraded_fields {
+            cmd.arg(k).arg(v);
+        }
+
+        cmd
+    }
+}
+
+/// Calls the given upgrade function with the fields of a record as they are saved in redis,
+/// converting the values it returns into the strings saved in redis
+fn call_upgrade(
+    upgrade: &Py<PyAny>,
+    schema: &Schema,
+    data: HashMap<String, String>,
+) -> PyResult<HashMap<String, String>> {
+    Python::with_gil(|py| {
+        let upgraded: HashMap<String, Py<PyAny>> = upgrade.call1(py, (data,))?.extract(py)?;
+        upgraded
+            .into_iter()
+            .map(|(k, v)| {
+                let value = match schema.get_type(&k) {
+                    _ if v.as_ref(py).is_instance_of::<PyString>()? => v.extract(py)?,
+                    Some(type_) => utils::field_value_to_redis(type_, &v)?,
+                    None => v.to_string(),
+                };
+                Ok((k, value))
+            })
+            .collect()
+    })
+}
+
+/// Adds the current format version of each record's collection to the records to be inserted,
+/// given the versions of the collections
+pub(crate) fn stamp_versions(
+    records: &mut [(String, Vec<(String, String)>)],
+    versions: &HashMap<String, u32>,
+) {
+    for (key, record) in records.iter_mut() {
+        let version = key
+            .split_once("_%&_")
+            .and_then(|(collection_name, _)| versions.get(collection_name));
+        if let Some(version) = version {
+            record.push((VERSION_FIELD.to_string(), version.to_string()));
+        }
+    }
+}
+
+/// Gets the format versions of the collections, among this one and those nested in it, whose records
+/// are written whenever a record of this collection is written
+pub(crate) fn get_record_versions(
+    model_name: &str,
+    meta: &CollectionMeta,
+    collections_meta: &HashMap<String, CollectionMeta>,
+) -> HashMap<String, u32> {
+    let mut versions: HashMap<String, u32> = meta
+        .nested_fields
+        .iter()
+        .filter_map(|field| {
+            meta.schema
+                .get_type(field)
+                .and_then(FieldType::get_nested_model_name)
+                .and_then(|model_name| collections_meta.get(model_name))
+        })
+        .flat_map(|nested_meta| nested_meta.record_versions.clone())
+        .collect();
+
+    if let Some(versioning) = &meta.versioning {
+        versions.insert(model_name.to_string(), versioning.version);
+    }
+
+    versions
+}
@@ -0,0 +1,162 @@
+use std::collections::HashMap;
+
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+
+use crate::store::CollectionMeta;
+use crate::utils;
+
+pub(crate) const REFRESH_VIEW_RECORD_SCRIPT: &str = r"local table_unpack = table.unpack or unpack local n = tonumber(ARGV[1]) local matches = redis.call('EXISTS', KEYS[1]) == 1 local i = n + 2 while matches and i < #ARGV do if redis.call('HGET', KEYS[1], ARGV[i]) ~= ARGV[i + 1] then matches = false end i = i + 2 end redis.call('DEL', KEYS[2]) if not matches then return 0 end local args = {} for j = 2, n + 1 do local v = redis.call('HGET', KEYS[1], ARGV[j]) if v then table.insert(args, ARGV[j]) table.insert(args, v) end end if #args == 0 then return 0 end redis.call('HSET', KEYS[2], table_unpack(args)) local ttl = redis.call('PTTL', KEYS[1]) if ttl > 0 then redis.call('PEXPIRE', KEYS[2], ttl) end return 1";
+pub(crate) const CLEAR_VIEW_SCRIPT: &str = r"local result = redis.call('SCAN', ARGV[1], 'MATCH', ARGV[3], 'COUNT', ARGV[2]) for _, key in ipairs(result[2]) do redis.call('DEL', key) end return {result[1], #result[2], #result[2]}";
+const REBUILD_VIEW_SCRIPT: &str = r"local table_unpack = table.unpack or unpack local n = tonumber(ARGV[6]) local result = redis.call('SCAN', ARGV[1], 'MATCH', ARGV[3], 'COUNT', ARGV[2]) local count = 0 for _, key in ipairs(result[2]) do if redis.call('TYPE', key).ok == 'hash' then local matches = true local i = n + 7 while matches and i < #ARGV do if redis.call('HGET', key, ARGV[i]) ~= ARGV[i + 1] then matches = false end i = i + 2 end if matches then local args = {} for j = 7, n + 6 do local v = redis.call('HGET', key, ARGV[j]) if v then table.insert(args, ARGV[j]) table.insert(args, v) end end if #args > 0 then local view_key = ARGV[5] .. string.sub(key, #ARGV[4] + 1) redis.call('HSET', view_key, table_unpack(args)) local ttl = redis.call('PTTL', key) if ttl > 0 then redis.call('PEXPIRE', view_key, ttl) end count = count + 1 end end end end return {result[1], #result[2], count}";
+
+/// The definition of a materialized view of a collection i.e. the records of the collection
+/// that match a filter, with only some of their fields. It is kept up to date on every write
+/// to the collection
+#[derive(Clone, Debug)]
+pub(crate) struct ViewDefinition {
+    pub(crate) name: String,
+    pub(crate) projection: Vec<String>,
+    pub(crate) filter: Vec<(String, String)>,
+}
+
+impl ViewDefinition {
+    /// Creates a new view definition, validating that the projection and the filter only have
+    /// non-nested, non-counter fields of the source collection. The filter is a dictionary of
+    /// the values the fields of a record should be equal to for it to be in the view
+    pub(crate) fn from_py(
+        name: String,
+        meta: &CollectionMeta,
+        projection: Vec<String>,
+        filter: Option<HashMap<String, Py<PyAny>>>,
+    ) -> PyResult<Self> {
+        for field in projection.iter() {
+            ensure_viewable_field(meta, field)?;
+        }
+
+        let filter = filter
+            .unwrap_or_default()
+            .iter()
+            .map(|(field, value)| {
+                ensure_viewable_field(meta, field)?;
+                let type_ = meta.schema.get_type(field).unwrap();
+                Ok((field.clone(), utils::field_value_to_redis(type_, value)?))
+            })
+            .collect::<PyResult<Vec<(String, String)>>>()?;
+
+        Ok(Self {
+            name,
+            projection,
+            filter,
+        })
+    }
+
+    /// Gets the name of the collection-like key space in which the records of this view are saved
+    #[inline]
+    pub(crate) fn get_key_space(&self, collection_name: &str) -> String {
+        utils::generate_auxiliary_key(collection_name, "view", &self.name)
+    }
+
+    /// Adds to the pipeline the command that refreshes this view's copy of the record of the given id
+    pub(crate) fn add_refresh_cmd(
+        &self,
+        pipe: &mut redis::Pipeline,
+        collection_name: &str,
+        id: &str,
+        is_case_insensitive: bool,
+    ) {
+        pipe.cmd("EVAL")
+            .arg(REFRESH_VIEW_RECORD_SCRIPT)
+            .arg(2)
+            .arg(utils::generate_hash_key(
+                collection_name,
+                id,
+                is_case_insensitive,
+            ))
+            .arg(utils::generate_hash_key(
+                &self.get_key_space(collection_name),
+                id,
+                is_case_insensitive,
+            ))
+            .arg(self.projection.len())
+            .arg(&self.projection);
+
+        for (field, value) in &self.filter {
+            pipe.arg(field).arg(value);
+        }
+    }
+
+    /// Generates the command that removes the keys of this view in the SCAN batch of the given count
+    /// at the given cursor, before it is rebuilt
+    pub(crate) fn generate_clear_cmd(
+        &self,
+        collection_name: &str,
+        cursor: &str,
+        count: usize,
+    ) -> redis::Cmd {
+        let mut cmd = redis::cmd("EVAL");
+        cmd.arg(CLEAR_VIEW_SCRIPT)
+            .arg(0)
+            .arg(cursor)
+            .arg(count)
+            .arg(utils::generate_collection_key_pattern(
+                &self.get_key_space(collection_name),
+            ));
+        cmd
+    }
+
+    /// Generates the command that copies the records of the collection in the SCAN batch of the given
+    /// count at the given cursor into this view
+    pub(crate) fn generate_rebuild_cmd(
+        &self,
+        collection_name: &str,
+        cursor: &str,
+        count: usize,
+    ) -> redis::Cmd {
+        let mut cmd = redis::cmd("EVAL");
+        cmd.arg(REBUILD_VIEW_SCRIPT)
+            .arg(0)
+            .arg(cursor)
+            .arg(count)
+            .arg(utils::generate_collection_key_pattern(collection_name))
+            .arg(utils::generate_key_prefix(collection_name))
+            .arg(utils::generate_key_prefix(
+                &self.get_key_space(collection_name),
+            ))
+            .arg(self.projection.len())
+            .arg(&self.projection);
+
+        for (field, value) in &self.filter {
+            cmd.arg(field).arg(value);
+        }
+
+        cmd
+    }
+
+    /// Creates the meta used to read the records of this view from redis as dictionaries
+    pub(crate) fn generate_read_meta(&self, meta: &CollectionMeta) -> PyResult<CollectionMeta> {
+        let mut read_meta = CollectionMeta::new(
+            meta.schema.clone(),
+            meta.model_type.clone(),
+            meta.primary_key_field.clone(),
+            vec![],
+            vec![],
+            Default::default(),
+        )?;
+        read_meta.set_coercions(meta.coercions.clone());
+        Ok(read_meta)
+    }
+}
+
+/// Ensures the given field can be part of a view i.e. it is a non-nested, non-counter field
+fn ensure_viewable_field(meta: &CollectionMeta, field: &str) -> PyResult<()> {
+    meta.ensure_scalar_field(field)?;
+    if meta.counter_fields.iter().any(|v| v == field) {
+        return Err(PyValueError::new_err(format!(
+            "{:?} is a counter field yet counter fields cannot be part of a view",
+            field
+        )));
+    }
+
+    Ok(())
+}
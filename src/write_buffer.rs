@@ -0,0 +1,298 @@
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use pyo3::exceptions::{PyConnectionError, PyValueError};
+use pyo3::prelude::*;
+
+use crate::pools::PoolExhaustedError;
+use crate::{quotas, unique_fields};
+
+/// The delay before the first retry of a buffered write. It doubles on every retry up to WRITE_BUFFER_MAX_RETRY_DELAY
+const WRITE_BUFFER_RETRY_DELAY: Duration = Duration::from_millis(100);
+/// The longest delay between retries of a buffered write while redis is unreachable
+const WRITE_BUFFER_MAX_RETRY_DELAY: Duration = Duration::from_secs(5);
+
+/// What to do with a write when redis is unreachable and the write buffer is full
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum OverflowPolicy {
+    /// Drop the oldest buffered write to make room for the new one
+    DropOldest,
+    /// Drop the new write, keeping the buffered ones
+    DropNewest,
+    /// Raise a ConnectionError to the caller
+    Raise,
+}
+
+impl OverflowPolicy {
+    fn from_str(value: &str) -> PyResult<Self> {
+        match value {
+            "drop_oldest" => Ok(Self::DropOldest),
+            "drop_newest" => Ok(Self::DropNewest),
+            "raise" => Ok(Self::Raise),
+            _ => Err(PyValueError::new_err(format!(
+                "write_buffer_overflow should be one of \"drop_oldest\", \"drop_newest\" or \"raise\", got {:?}",
+                value
+            ))),
+        }
+    }
+}
+
+/// The in-process queue of the writes made through a store while its redis was unreachable, which are
+/// applied in order once it is reachable again. It is meant for non-critical data e.g. telemetry since
+/// buffered writes are lost if the process exits. It is shared by the store and all its collections,
+/// and does nothing unless a capacity is given
+#[derive(Clone, Default)]
+pub(crate) struct WriteBuffer {
+    shared: Option<Arc<Shared>>,
+}
+
+struct Shared {
+    client: redis::Client,
+    capacity: usize,
+    overflow: OverflowPolicy,
+    state: Mutex<State>,
+}
+
+/// The buffered writes, each with its sequence number, and whether a flusher is applying them.
+/// A write stays at the front of the queue until it has been applied. The errors of the writes that failed
+/// when applied, and were thus dropped, are kept until they are taken, the oldest dropped beyond the capacity
+#[derive(Default)]
+struct State {
+    queue: VecDeque<(u64, redis::Pipeline)>,
+    next_seq: u64,
+    is_flushing: bool,
+    failures: VecDeque<String>,
+}
+
+impl WriteBuffer {
+    /// Creates the write buffer of the redis instance of the given client, holding at most `capacity` writes,
+    /// or one that does nothing if there is no capacity
+    pub(crate) fn new(
+        client: redis::Client,
+        capacity: Option<usize>,
+        overflow: &str,
+    ) -> PyResult<Self> {
+        let overflow = OverflowPolicy::from_str(overflow)?;
+        let capacity = match capacity {
+            None => return Ok(Self::default()),
+            Some(0) => return Err(PyValueError::new_err("write_buffer should be at least 1")),
+            Some(capacity) => capacity,
+        };
+
+        Ok(Self {
+            shared: Some(Arc::new(Shared {
+                client,
+                capacity,
+                overflow,
+                state: Default::default(),
+            })),
+        })
+    }
+
+    /// Whether writes are buffered when redis is unreachable
+    pub(crate) fn is_enabled(&self) -> bool {
+        self.shared.is_some()
+    }
+
+    /// Whether some writes are still waiting to be applied, in which case new writes are buffered
+    /// behind them instead of being applied directly so that writes are applied in order
+    pub(crate) fn has_pending(&self) -> bool {
+        self.pending() > 0
+    }
+
+    /// Fails with a ConnectionError if some writes are still waiting to be applied, for the writes that cannot be
+    /// buffered behind them e.g. those with unique values, whose violations should be raised to the caller
+    pub(crate) fn ensure_none_pending(&self) -> PyResult<()> {
+        match self.pending() {
+            0 => Ok(()),
+            pending => Err(PyConnectionError::new_err(format!(
+                "the write has unique values, checked as it is made, so it cannot wait behind {} buffered writes",
+                pending
+            ))),
+        }
+    }
+
+    /// Gets the number of writes waiting to be applied to redis
+    pub(crate) fn pending(&self) -> usize {
+        self.shared.as_ref().map_or(0, |shared| {
+            shared
+                .state
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .queue
+                .len()
+        })
+    }
+
+    /// Takes the errors of the buffered writes that failed when they were applied, oldest first,
+    /// at most as many as the capacity of the buffer
+    pub(crate) fn take_failures(&self) -> Vec<String> {
+        self.shared.as_ref().map_or_else(Vec::new, |shared| {
+            let mut state = shared.state.lock().unwrap_or_else(|e| e.into_inner());
+            state.failures.drain(..).collect()
+        })
+    }
+
+    /// Handles the error of applying a write directly to redis. If redis was unreachable and there is
+    /// a buffer, the write is buffered; otherwise the error is returned
+    pub(crate) fn handle_failure(&self, pipe: &redis::Pipeline, error: WriteError) -> PyResult<()> {
+        match error {
+            WriteError::Unreachable(_) if self.is_enabled() => self.push(pipe),
+            error => Err(error.into_err()),
+        }
+    }
+
+    /// Buffers the given write behind the writes already buffered, starting a flusher if none is running.
+    /// If the buffer is full, its overflow policy decides which write is dropped, if any
+    pub(crate) fn push(&self, pipe: &redis::Pipeline) -> PyResult<()> {
+        let shared = match &self.shared {
+            Some(shared) => shared,
+            None => return Ok(()),
+        };
+        let mut state = shared.state.lock().unwrap_or_else(|e| e.into_inner());
+
+        if state.queue.len() >= shared.capacity {
+            match shared.overflow {
+                OverflowPolicy::DropOldest => {
+                    state.queue.pop_front();
+                }
+                OverflowPolicy::DropNewest => return Ok(()),
+                OverflowPolicy::Raise => {
+                    return Err(PyConnectionError::new_err(format!(
+                        "the write buffer is full with {} writes waiting for redis to be reachable",
+                        shared.capacity
+                    )))
+                }
+            }
+        }
+
+        let seq = state.next_seq;
+        state.next_seq += 1;
+        state.queue.push_back((seq, pipe.clone()));
+
+        if !state.is_flushing {
+            state.is_flushing = true;
+            let shared = shared.clone();
+            thread::spawn(move || run_flusher(shared));
+        }
+        Ok(())
+    }
+}
+
+/// The error of a write that was applied directly to redis
+pub(crate) enum WriteError {
+    /// Redis could not be reached e.g. it is down or the network is partitioned
+    Unreachable(PyErr),
+    /// Any other error e.g. all connections of the pool are in use, a script failed or a check of the write
+    /// e.g. of the quota, aborted it
+    Other(PyErr),
+}
+
+impl WriteError {
+    /// Classifies the given error of running a write on redis
+    pub(crate) fn from_redis(e: redis::RedisError) -> Self {
+        let rejection = quotas::to_quota_exceeded_error(&e)
+            .or_else(|| unique_fields::to_unique_violation_error(&e));
+        if let Some(err) = rejection {
+            return Self::Other(err);
+        }
+
+        let err = PyConnectionError::new_err(e.to_string());
+        if is_unreachable(&e) {
+            Self::Unreachable(err)
+        } else {
+            Self::Other(err)
+        }
+    }
+
+    /// Converts the error into the python exception raised to the caller when the write is not buffered
+    pub(crate) fn into_err(self) -> PyErr {
+        match self {
+            Self::Unreachable(err) | Self::Other(err) => err,
+        }
+    }
+
+    /// Classifies the given error of checking out a connection from a pool. Only a PoolExhaustedError
+    /// means that redis was reachable
+    pub(crate) fn from_checkout(err: PyErr) -> Self {
+        if Python::with_gil(|py| err.is_instance_of::<PoolExhaustedError>(py)) {
+            Self::Other(err)
+        } else {
+            Self::Unreachable(err)
+        }
+    }
+}
+
+/// Whether the given error means the write did not reach redis, which refused or dropped the connection.
+/// Other errors e.g. timeouts may come after redis applied the write, so replaying it could apply it twice
+fn is_unreachable(e: &redis::RedisError) -> bool {
+    e.is_connection_refusal() || e.is_connection_dropped()
+}
+
+/// Applies the buffered writes to redis in order, retrying each with an exponential backoff for as long
+/// as redis is unreachable, until the buffer is empty. A write that fails for any other reason e.g. a quota
+/// it exceeds is dropped since retrying it would fail again, or apply it twice, and its error is kept
+/// to be taken by `take_failures()`
+fn run_flusher(shared: Arc<Shared>) {
+    let mut conn: Option<redis::Connection> = None;
+    let mut delay = WRITE_BUFFER_RETRY_DELAY;
+
+    loop {
+        let (seq, pipe) = {
+            let mut state = shared.state.lock().unwrap_or_else(|e| e.into_inner());
+            match state.queue.front() {
+                Some((seq, pipe)) => (*seq, pipe.clone()),
+                None => {
+                    state.is_flushing = false;
+                    return;
+                }
+            }
+        };
+
+        // no write is sent before a connection is made, so failing to make one always means redis is unreachable
+        let result = match conn.as_mut() {
+            Some(c) => pipe.query::<()>(c),
+            None => match shared.client.get_connection() {
+                Ok(mut c) => {
+                    let result = pipe.query::<()>(&mut c);
+                    conn = Some(c);
+                    result
+                }
+                Err(_) => {
+                    thread::sleep(delay);
+                    delay = (delay * 2).min(WRITE_BUFFER_MAX_RETRY_DELAY);
+                    continue;
+                }
+            },
+        };
+
+        let failure = match result {
+            Err(e) if is_unreachable(&e) => {
+                conn = None;
+                thread::sleep(delay);
+                delay = (delay * 2).min(WRITE_BUFFER_MAX_RETRY_DELAY);
+                continue;
+            }
+            Err(e) => {
+                conn = None;
+                Some(e.to_string())
+            }
+            Ok(_) => None,
+        };
+        delay = WRITE_BUFFER_RETRY_DELAY;
+
+        // the write may have been dropped to make room for newer ones while it was being applied
+        let mut state = shared.state.lock().unwrap_or_else(|e| e.into_inner());
+        if state.queue.front().map(|(s, _)| *s) == Some(seq) {
+            state.queue.pop_front();
+        }
+        if let Some(failure) = failure {
+            if state.failures.len() >= shared.capacity {
+                state.failures.pop_front();
+            }
+            state.failures.push_back(failure);
+        }
+    }
+}
@@ -39,6 +39,9 @@ books = [
          tags=["Classic", "Romance"]),
 ]
 
+# the time in seconds it takes for the timestamp portion of ids from each id_generator to change
+id_generator_resolutions = {"uuid4": 0, "ulid": 0.002, "snowflake": 0, "ksuid": 1.001}
+
 # sync
 redis_store_fixture = [(lazy_fixture("redis_store"))]
 book_collection_fixture = [(lazy_fixture("book_collection"))]
@@ -1,8 +1,21 @@
 """Tests for the asynchronous part of orredis"""
+import asyncio
+import os
+import socket
+import time
+from datetime import date, datetime, timedelta, timezone
+from typing import Dict, List, Optional, Tuple
+
 import pytest
+import redis
+import redislite
+from pydantic import Field, conint, constr
 
-from orredis import AsyncStore
-from test.conftest import Book, async_redis_store_fixture, books, authors, Author
+from orredis import (AsyncStore, CircuitOpenError, DeadlineExceededError, Model, PoolExhaustedError,
+                     QuotaExceededError, Reference, Store, UniqueViolationError, Vector, deadline)
+from orredis.cdc import ChangeConsumer
+from orredis.integrations.fastapi import StoreProvider
+from test.conftest import Book, async_redis_store_fixture, async_book_collection_fixture, books, authors, Author, id_generator_resolutions
 
 
 @pytest.mark.asyncio
@@ -38,6 +51,45 @@ async def test_add_many_async(store):
     assert sorted(books, key=lambda x: x.title) == sorted(books_in_store, key=lambda x: x.title)
 
 
+@pytest.mark.asyncio
+@pytest.mark.parametrize("store", async_redis_store_fixture)
+async def test_get_all_large_result_set_async(store):
+    """get_all() returns all records of a result set converted into models over many holds of the GIL"""
+    author_collection = store.get_collection(Author)
+    many_authors = [Author(name=f"author {i}", active_years=(1900, 1950 + i % 50)) for i in range(1000)]
+    await author_collection.add_many(many_authors)
+
+    ticks = 0
+
+    async def tick():
+        nonlocal ticks
+        while True:
+            ticks += 1
+            await asyncio.sleep(0)
+
+    ticker = asyncio.create_task(tick())
+    response = await author_collection.get_all()
+    ticker.cancel()
+
+    assert sorted(response, key=lambda x: x.name) == sorted(many_authors, key=lambda x: x.name)
+    assert ticks > 0
+
+
+@pytest.mark.asyncio
+@pytest.mark.parametrize("store", async_redis_store_fixture)
+async def test_parallel_iter_async(store):
+    """parallel_iter() returns all the book models read concurrently by the workers"""
+    book_collection = store.get_collection(Book)
+    await book_collection.add_many(books)
+
+    for workers in [1, 3, 10]:
+        response = await book_collection.parallel_iter(workers=workers)
+        assert sorted(response, key=lambda x: x.title) == sorted(books, key=lambda x: x.title)
+
+    with pytest.raises(ValueError):
+        await book_collection.parallel_iter(workers=0)
+
+
 @pytest.mark.asyncio
 @pytest.mark.parametrize("store", async_redis_store_fixture)
 async def test_nested_add_many_async(store):
@@ -103,6 +155,674 @@ async def test_get_all_async(store):
     assert sorted_books == sorted_response
 
 
+@pytest.mark.asyncio
+@pytest.mark.parametrize("store", async_redis_store_fixture)
+async def test_ids_async(store):
+    """ids() returns the primary keys of all the records in the collection without their key prefix"""
+    book_collection = store.get_collection(Book)
+    assert await book_collection.ids() == []
+
+    await book_collection.add_many(books)
+    assert sorted(await book_collection.ids()) == sorted(book.title for book in books)
+
+    await book_collection.delete_many([books[0].title])
+    assert sorted(await book_collection.ids()) == sorted(book.title for book in books[1:])
+
+
+@pytest.mark.asyncio
+@pytest.mark.parametrize("store", async_redis_store_fixture)
+async def test_sample_async(store):
+    """sample() returns the given number of records of the collection picked at random"""
+    book_collection = store.get_collection(Book)
+    assert await book_collection.sample(2) == []
+
+    await book_collection.add_many(books)
+    sampled = await book_collection.sample(2)
+    assert len(sampled) == 2
+    assert len({book.title for book in sampled}) == 2
+    assert all(book in books for book in sampled)
+
+    sampled = await book_collection.sample(len(books) + 1)
+    assert sorted(sampled, key=lambda x: x.title) == sorted(books, key=lambda x: x.title)
+    assert await book_collection.sample(0) == []
+
+
+@pytest.mark.asyncio
+@pytest.mark.parametrize("store", async_redis_store_fixture)
+async def test_resolve_async(store):
+    """resolve() gets the record that a Reference field refers to, which only holds its primary key"""
+
+    class Review(Model):
+        id: str
+        author: Reference[Author]
+        editor: Optional[Reference[Author]] = None
+
+    store.create_collection(Review, primary_key_field="id")
+    review_collection = store.get_collection(Review)
+    author_collection = store.get_collection(Author)
+    await author_collection.add_many(list(authors.values()))
+    await review_collection.add_one(Review(id="1", author=authors["jane"].name))
+
+    review = await review_collection.get_one("1")
+    assert await author_collection.resolve(review.author) == authors["jane"]
+    assert await author_collection.resolve(review.editor) is None
+    assert await author_collection.resolve("Unknown Author") is None
+
+    with pytest.raises(ValueError, match="is a reference to a record of Author"):
+        await store.get_collection(Book).resolve(review.author)
+
+
+@pytest.mark.asyncio
+@pytest.mark.parametrize("store", async_redis_store_fixture)
+async def test_children_of_async(store):
+    """children_of() gets the records of a child collection whose foreign key holds the id of a parent record"""
+
+    class Customer(Model):
+        id: str
+        name: str
+
+    class Order(Model):
+        id: str
+        customer_id: str
+        total: float
+
+    store.create_collection(Customer, primary_key_field="id")
+    store.create_collection(Order, primary_key_field="id", foreign_keys=["customer_id"])
+    customer_collection = store.get_collection(Customer)
+    order_collection = store.get_collection(Order)
+
+    orders = [
+        Order(id="o1", customer_id="c1", total=10.0),
+        Order(id="o2", customer_id="c1", total=25.5),
+        Order(id="o3", customer_id="c2", total=7.0),
+    ]
+    await customer_collection.add_many([Customer(id="c1", name="Ann"), Customer(id="c2", name="Bob")])
+    await order_collection.add_many(orders)
+
+    def by_id(records):
+        return sorted(records, key=lambda x: x.id)
+
+    assert by_id(await customer_collection.children_of("c1", order_collection, "customer_id")) == orders[:2]
+    assert await customer_collection.children_of("c2", order_collection, "customer_id") == orders[2:]
+    assert await customer_collection.children_of("c3", order_collection, "customer_id") == []
+    # the reads are recorded in the metrics of the child collection, whose records they are
+    metrics = store.metrics()
+    assert metrics["Order"]["children_of"] == {"calls": 3, "records": 3}
+    assert "children_of" not in metrics["Customer"]
+
+    # the index follows the changes of the foreign key and deletions
+    await order_collection.update_one("o1", data={"customer_id": "c2"})
+    await order_collection.delete_many(["o3"])
+    assert await customer_collection.children_of("c1", order_collection, "customer_id") == [orders[1]]
+    assert await customer_collection.children_of("c2", order_collection, "customer_id") == [
+        orders[0].with_changes({"customer_id": "c2"})]
+
+    with pytest.raises(ValueError, match="is not a foreign key of the collection"):
+        await customer_collection.children_of("c1", order_collection, "total")
+
+
+@pytest.mark.asyncio
+@pytest.mark.parametrize("store", async_redis_store_fixture)
+async def test_find_by_async(store):
+    """find_by() gets the records whose indexed field holds the given value"""
+
+    class User(Model):
+        id: str
+        email: str
+        is_active: bool
+        team_id: str
+
+    store.create_collection(User, primary_key_field="id", foreign_keys=["team_id"],
+                            indexed_fields=["email", "is_active"])
+    user_collection = store.get_collection(User)
+
+    users = [
+        User(id="u1", email="ann@example.com", is_active=True, team_id="t1"),
+        User(id="u2", email="bob@example.com", is_active=False, team_id="t1"),
+        User(id="u3", email="cid@example.com", is_active=True, team_id="t2"),
+    ]
+    await user_collection.add_many(users)
+
+    def by_id(records):
+        return sorted(records, key=lambda x: x.id)
+
+    assert await user_collection.find_by("email", "bob@example.com") == [users[1]]
+    assert by_id(await user_collection.find_by("is_active", True)) == [users[0], users[2]]
+    assert by_id(await user_collection.find_by("team_id", "t1")) == users[:2]
+    assert await user_collection.find_by("email", "dan@example.com") == []
+
+    # the index follows updates and deletions
+    await user_collection.update_one("u2", data={"is_active": True})
+    await user_collection.delete_many(["u3"])
+    assert await user_collection.find_by("is_active", False) == []
+    assert by_id(await user_collection.find_by("is_active", True)) == [
+        users[0], users[1].with_changes({"is_active": True})]
+    assert await user_collection.find_by("email", "cid@example.com") == []
+
+    with pytest.raises(ValueError, match="is not indexed in the collection"):
+        await user_collection.find_by("id", "u1")
+
+
+@pytest.mark.asyncio
+@pytest.mark.parametrize("store", async_redis_store_fixture)
+async def test_find_by_index_async(store):
+    """find_by_index() gets the records whose fields in a compound index hold the given values"""
+
+    class Shop(Model):
+        id: str
+        name: str
+        country: str
+        city: str
+
+    store.create_collection(Shop, primary_key_field="id", compound_indexes=[("country", "city")])
+    shop_collection = store.get_collection(Shop)
+
+    shops = [
+        Shop(id="s1", name="Mango", country="UG", city="Kampala"),
+        Shop(id="s2", name="Jackfruit", country="UG", city="Kampala"),
+        Shop(id="s3", name="Avocado", country="UG", city="Entebbe"),
+        Shop(id="s4", name="Banana", country="KE", city="Kampala"),
+    ]
+    await shop_collection.add_many(shops)
+
+    def by_id(records):
+        return sorted(records, key=lambda x: x.id)
+
+    assert by_id(await shop_collection.find_by_index(("country", "city"), ("UG", "Kampala"))) == shops[:2]
+    assert await shop_collection.find_by_index(("city", "country"), ("Kampala", "KE")) == [shops[3]]
+    assert await shop_collection.find_by_index(("country", "city"), ("KE", "Nairobi")) == []
+
+    # updating one of the fields moves the record in the index
+    await shop_collection.update_one("s2", data={"city": "Entebbe"})
+    await shop_collection.delete_many(["s3"])
+    assert await shop_collection.find_by_index(("country", "city"), ("UG", "Kampala")) == [shops[0]]
+    assert await shop_collection.find_by_index(("country", "city"), ("UG", "Entebbe")) == [
+        shops[1].with_changes({"city": "Entebbe"})]
+
+    with pytest.raises(ValueError, match="is not a compound index of the collection"):
+        await shop_collection.find_by_index(("country", "name"), ("UG", "Mango"))
+    with pytest.raises(ValueError):
+        await shop_collection.find_by_index(("country", "city"), ("UG",))
+
+
+@pytest.mark.asyncio
+@pytest.mark.parametrize("store", async_redis_store_fixture)
+async def test_unique_fields_async(store):
+    """
+    Inserts and updates that would give a unique field a value another record has raise a UniqueViolationError,
+    values being freed when records change them or are deleted
+    """
+
+    class User(Model):
+        id: str
+        email: str
+        name: str
+
+    store.create_collection(User, primary_key_field="id", unique_fields=["email"])
+    user_collection = store.get_collection(User)
+
+    await user_collection.add_one(User(id="u1", email="ann@example.com", name="Ann"))
+    with pytest.raises(UniqueViolationError):
+        await user_collection.add_one(User(id="u2", email="ann@example.com", name="Annie"))
+    assert await user_collection.get_one("u2") is None
+
+    # a record keeps its own values
+    await user_collection.add_one(User(id="u1", email="ann@example.com", name="Ann B."))
+
+    # duplicates within the same batch are rejected, and nothing is inserted
+    with pytest.raises(UniqueViolationError):
+        await user_collection.add_many([User(id="u3", email="cid@example.com", name="Cid"),
+                                        User(id="u4", email="cid@example.com", name="Cy")])
+    assert await user_collection.get_many(["u3", "u4"]) == []
+
+    await user_collection.update_one("u1", data={"email": "ann.b@example.com"})
+    await user_collection.add_one(User(id="u2", email="ann@example.com", name="Annie"))
+    with pytest.raises(UniqueViolationError):
+        await user_collection.update_one("u2", data={"email": "ann.b@example.com"})
+
+    await user_collection.delete_many(["u1"])
+    await user_collection.add_one(User(id="u5", email="ann.b@example.com", name="Anna"))
+    assert await user_collection.get_one("u5") == User(id="u5", email="ann.b@example.com", name="Anna")
+
+
+@pytest.mark.asyncio
+@pytest.mark.parametrize("store", async_redis_store_fixture)
+async def test_unique_fields_with_quota_async(store, redis_server):
+    """
+    An insert aborted because another record has one of its unique values or because of the quota of its namespace
+    leaves neither reserved values nor usage of the quota behind
+    """
+
+    class User(Model):
+        id: str
+        email: str
+
+    store.create_collection(User, primary_key_field="id", unique_fields=["email"], namespace="team_a")
+    await store.set_quota("team_a", max_records=2)
+    user_collection = store.get_collection(User)
+    await user_collection.add_one(User(id="u1", email="ann@example.com"))
+    quota = await store.get_quota("team_a")
+
+    with pytest.raises(UniqueViolationError, match=r'already taken by the record "u1" of User'):
+        await user_collection.add_one(User(id="u2", email="ann@example.com"))
+    assert await store.get_quota("team_a") == quota
+
+    await user_collection.add_one(User(id="u3", email="bob@example.com"))
+    with pytest.raises(QuotaExceededError):
+        await user_collection.add_one(User(id="u4", email="cid@example.com"))
+    await store.set_quota("team_a", max_records=3)
+    await user_collection.add_one(User(id="u5", email="cid@example.com"))
+    assert await user_collection.get_many(["u2", "u4"]) == []
+    assert (await store.get_quota("team_a"))["records"] == 3
+
+    client = redis.Redis(port=int(redis_server), db=1)
+    assert client.keys("User_%&guard_%&_*") == []
+    assert client.keys("User_%&undo_%&_*") == []
+
+
+@pytest.mark.asyncio
+@pytest.mark.parametrize("store", async_redis_store_fixture)
+async def test_index_normalizers_async(store):
+    """index_normalizers normalize the values of unique and indexed fields before they are indexed or looked up"""
+
+    class Customer(Model):
+        id: str
+        email: str
+        city: str
+        country: str
+
+    store.create_collection(Customer, primary_key_field="id", unique_fields=["email"],
+                            compound_indexes=[("country", "city")],
+                            index_normalizers={"email": "strip,lower", "city": "lower"})
+    customer_collection = store.get_collection(Customer)
+
+    ann = Customer(id="c1", email=" Ann@X.com", city="Kampala", country="UG")
+    await customer_collection.add_one(ann)
+    with pytest.raises(UniqueViolationError):
+        await customer_collection.add_one(Customer(id="c2", email="ann@x.com", city="Entebbe", country="UG"))
+    assert await customer_collection.find_by_index(("country", "city"), ("UG", "KAMPALA")) == [ann]
+    assert await customer_collection.find_by_index(("country", "city"), ("ug", "kampala")) == []
+
+    await customer_collection.delete_many(["c1"])
+    await customer_collection.add_one(Customer(id="c2", email="ann@x.com", city="Entebbe", country="UG"))
+    assert await customer_collection.find_by_index(("city", "country"), ("kampala", "UG")) == []
+
+
+@pytest.mark.asyncio
+@pytest.mark.parametrize("store", async_redis_store_fixture)
+async def test_update_many_async(store):
+    """update_many() updates each of the records of the given ids with its own changes"""
+    book_collection = store.get_collection(Book)
+    author_collection = store.get_collection(Author)
+    await book_collection.add_many(books)
+    new_author = Author(name="John Doe", active_years=(2000, 2009))
+
+    await book_collection.update_many({
+        books[0].title: {"author": new_author, "in_stock": not books[0].in_stock},
+        books[1].title: {"rating": 1.5},
+    })
+
+    assert await book_collection.get_one(books[0].title) == books[0].with_changes({"author": new_author,
+                                                                                   "in_stock": not books[0].in_stock})
+    assert await book_collection.get_one(books[1].title) == books[1].with_changes({"rating": 1.5})
+    assert await book_collection.get_one(books[2].title) == books[2]
+    assert await author_collection.get_one(new_author.name) == new_author
+
+
+@pytest.mark.asyncio
+@pytest.mark.parametrize("store", async_redis_store_fixture)
+async def test_indexed_list_fields_async(store):
+    """indexed_list_fields index each element of list fields so that find() gets the records holding an element"""
+
+    class Post(Model):
+        id: str
+        author: str
+        tags: List[str]
+
+    with pytest.raises(ValueError, match="only lists of non-nested items"):
+        store.create_collection(Post, primary_key_field="id", indexed_list_fields=["author"])
+    with pytest.raises(ValueError, match="indexed either as a whole or by its elements"):
+        store.create_collection(Post, primary_key_field="id", indexed_fields=["tags"], indexed_list_fields=["tags"])
+
+    store.create_collection(Post, primary_key_field="id", indexed_fields=["author"], indexed_list_fields=["tags"])
+    post_collection = store.get_collection(Post)
+    posts = [Post(id="p1", author="jane", tags=["rust", "python"]),
+             Post(id="p2", author="john", tags=["rust", "it's, fine"]),
+             Post(id="p3", author="jane", tags=[])]
+    await post_collection.add_many(posts)
+
+    assert sorted(await post_collection.find(tags__contains="rust"), key=lambda x: x.id) == posts[:2]
+    assert await post_collection.find(tags__contains="it's, fine") == [posts[1]]
+    assert await post_collection.find(tags__contains="rust", author="jane") == [posts[0]]
+    assert await post_collection.find(tags__contains="go") == []
+
+    # the record is moved from the indexes of the elements it no longer has to those it now has
+    await post_collection.update_one("p1", data={"tags": ["go"]})
+    assert await post_collection.find(tags__contains="rust") == [posts[1]]
+    assert await post_collection.find(tags__contains="go") == [posts[0].with_changes({"tags": ["go"]})]
+
+    await post_collection.delete_many(["p1"])
+    assert await post_collection.find(tags__contains="go") == []
+
+    with pytest.raises(ValueError, match="at least one lookup"):
+        await post_collection.find()
+    with pytest.raises(ValueError, match="not an indexed list field"):
+        await post_collection.find(author__contains="jane")
+
+
+@pytest.mark.asyncio
+@pytest.mark.parametrize("store", async_redis_store_fixture)
+async def test_add_one_if_not_exists_async(store):
+    """add_one_if_not_exists() adds a record only if no record has its primary key, returning whether it did"""
+    book_collection = store.get_collection(Book)
+    await book_collection.add_one(books[0])
+
+    assert not await book_collection.add_one_if_not_exists(books[0].with_changes({"rating": 1.5}))
+    assert await book_collection.get_one(books[0].title) == books[0]
+
+    assert await book_collection.add_one_if_not_exists(books[1])
+    assert await book_collection.get_one(books[1].title) == books[1]
+
+    # of many concurrent calls for the same primary key, only one adds the record
+    results = await asyncio.gather(*[book_collection.add_one_if_not_exists(books[2]) for _ in range(10)])
+    assert sorted(results) == [False] * 9 + [True]
+    assert await book_collection.get_one(books[2].title) == books[2]
+
+
+@pytest.mark.asyncio
+@pytest.mark.parametrize("store", async_redis_store_fixture)
+async def test_find_isnull_async(store, redis_server):
+    """find(field__isnull=True) gets the records missing the field or whose value is None, once it has a null index"""
+
+    class Member(Model):
+        name: str
+        email: Optional[str]
+
+    store.create_collection(Member, primary_key_field="name")
+    member_collection = store.get_collection(Member)
+    legacy_client = redis.Redis(port=int(redis_server), db=1)
+    legacy_client.hset("Member_%&_john", mapping={"name": "john"})
+    await member_collection.add_many([Member(name="jane", email="jane@x.com"), Member(name="doe", email=None)])
+
+    with pytest.raises(ValueError):
+        await member_collection.find(email__isnull=True)
+
+    assert await member_collection.create_null_index("email") == 2
+    assert sorted(v.name for v in await member_collection.find(email__isnull=True)) == ["doe", "john"]
+    assert [v.name for v in await member_collection.find(email__isnull=False)] == ["jane"]
+
+    # the index is kept up to date on every write
+    await member_collection.update_one("john", data={"email": "john@x.com"})
+    await member_collection.update_one("jane", data={"email": None})
+    await member_collection.add_one(Member(name="paul", email=None))
+    await member_collection.delete_many(["doe"])
+    assert sorted(v.name for v in await member_collection.find(email__isnull=True)) == ["jane", "paul"]
+    assert [v.name for v in await member_collection.find(email__isnull=False)] == ["john"]
+
+    # the index is registered in redis, so the writes of the collections of other stores update it too
+    other_store = AsyncStore(url=f"redis://localhost:{redis_server}/1")
+    other_store.create_collection(Member, primary_key_field="name")
+    other_member_collection = other_store.get_collection(Member)
+    await other_member_collection.add_one(Member(name="mary", email=None))
+    await other_member_collection.update_one("paul", data={"email": "paul@x.com"})
+    assert sorted(v.name for v in await member_collection.find(email__isnull=True)) == ["jane", "mary"]
+    assert sorted(v.name for v in await other_member_collection.find(email__isnull=False)) == ["john", "paul"]
+
+
+@pytest.mark.asyncio
+@pytest.mark.parametrize("store", async_redis_store_fixture)
+async def test_find_with_cache_ttl_async(store):
+    """
+    find(cache_ttl=...) reuses the ids found by an identical call within the ttl, reading the records afresh
+    """
+
+    class Post(Model):
+        id: str
+        author: str
+        likes: int
+
+    store.create_collection(Post, primary_key_field="id", indexed_fields=["author"])
+    post_collection = store.get_collection(Post)
+    posts = [Post(id="p1", author="jane", likes=0), Post(id="p2", author="john", likes=0)]
+    await post_collection.add_many(posts)
+
+    assert await post_collection.find(cache_ttl=0.5, author="jane") == [posts[0]]
+
+    await post_collection.add_one(Post(id="p3", author="jane", likes=0))
+    await post_collection.update_one("p1", data={"likes": 5})
+    # the cached ids are used, but the records are read afresh
+    assert await post_collection.find(cache_ttl=0.5, author="jane") == [posts[0].with_changes({"likes": 5})]
+    assert sorted(v.id for v in await post_collection.find(author="jane")) == ["p1", "p3"]
+    assert await post_collection.find(cache_ttl=0.5, author="john") == [posts[1]]
+
+    await asyncio.sleep(0.6)
+    assert sorted(v.id for v in await post_collection.find(cache_ttl=0.5, author="jane")) == ["p1", "p3"]
+
+
+@pytest.mark.asyncio
+@pytest.mark.parametrize("store", async_redis_store_fixture)
+async def test_explain_find_async(store):
+    """
+    explain_find() shows the candidates in the index of each lookup of find(), the one chosen to drive the matching
+    i.e. with the fewest candidates, and whether the ids of the whole collection are scanned instead
+    """
+
+    class Post(Model):
+        id: str
+        author: str
+        tags: List[str]
+        editor: Optional[str]
+
+    store.create_collection(Post, primary_key_field="id", indexed_fields=["author"], indexed_list_fields=["tags"])
+    post_collection = store.get_collection(Post)
+    await post_collection.add_many([Post(id=f"p{i}", author="jane" if i < 2 else "john", tags=["rust"],
+                                   editor=None if i % 2 else "ann") for i in range(5)])
+    await post_collection.create_null_index("editor")
+
+    assert await post_collection.explain_find(tags__contains="rust", author="jane") == {
+        "lookups": [
+            {"lookup": "tags__contains='rust'", "index": "list", "role": "match", "candidates": 5},
+            {"lookup": "author='jane'", "index": "field", "role": "match", "candidates": 2},
+        ],
+        "scan": False,
+        "chosen_index": "author='jane'",
+        "candidates": 2,
+        "matches": 2,
+    }
+    assert sorted(v.id for v in await post_collection.find(tags__contains="rust", author="jane")) == ["p0", "p1"]
+
+    plan = await post_collection.explain_find(editor__isnull=False)
+    assert plan["lookups"] == [{"lookup": "editor__isnull=False", "index": "null", "role": "exclude", "candidates": 2}]
+    assert plan["scan"] is True
+    assert plan["chosen_index"] is None
+    assert plan["candidates"] == 5
+    assert plan["matches"] == 3
+
+    with pytest.raises(ValueError):
+        await post_collection.explain_find()
+    with pytest.raises(ValueError):
+        await post_collection.explain_find(id="p1")
+
+
+@pytest.mark.asyncio
+@pytest.mark.parametrize("store", async_redis_store_fixture)
+async def test_snapshot_async(store):
+    """snapshot() copies the current records under a tag, which read_snapshot() returns as they were"""
+    book_collection = store.get_collection(Book)
+    await book_collection.add_many(books)
+    assert await book_collection.snapshot("before") == len(books)
+
+    await book_collection.update_one(id=books[0].title, data={"in_stock": not books[0].in_stock})
+    await book_collection.delete_many([books[1].title])
+
+    snapshot = await book_collection.read_snapshot("before")
+    assert sorted(snapshot, key=lambda x: x.title) == sorted(books, key=lambda x: x.title)
+    assert len(await book_collection.get_all()) == len(books) - 1
+
+    assert await book_collection.delete_snapshot("before") == len(books)
+    assert await book_collection.read_snapshot("before") == []
+
+    with pytest.raises(ValueError, match="snapshot tag should be non-empty"):
+        await book_collection.snapshot("v*")
+
+
+@pytest.mark.asyncio
+@pytest.mark.parametrize("store", async_redis_store_fixture)
+async def test_get_all_with_filter_async(store):
+    """get_all(filter) returns only the book models whose fields are equal to the values of the filter"""
+    book_collection = store.get_collection(Book)
+    await book_collection.add_many(books)
+
+    response = await book_collection.get_all(filter={"in_stock": False})
+    assert sorted(response, key=lambda x: x.title) == [books[2], books[0]]
+    assert await book_collection.get_all(filter={"in_stock": True, "rating": 4.0}) == [books[3]]
+    assert await book_collection.get_all(filter={"title": "Hamlet"}) == []
+
+    with pytest.raises(ValueError, match=r"\"author\" is a nested field"):
+        await book_collection.get_all(filter={"author": authors["jane"]})
+
+
+@pytest.mark.asyncio
+@pytest.mark.parametrize("store", async_redis_store_fixture)
+async def test_get_all_with_skip_and_limit_async(store):
+    """get_all(skip, limit) and get_all_partially(skip, limit) return pages of the records in the order of the scan"""
+    book_collection = store.get_collection(Book)
+    await book_collection.add_many(books)
+
+    first_page = await book_collection.get_all(limit=3)
+    second_page = await book_collection.get_all(skip=3, limit=3)
+    assert len(first_page) == 3
+    assert len(second_page) == 1
+    assert sorted(first_page + second_page, key=lambda x: x.title) == sorted(books, key=lambda x: x.title)
+    assert await book_collection.get_all(limit=0) == []
+
+    titles = [book["title"] for book in await book_collection.get_all_partially(["title"])]
+    page = await book_collection.get_all_partially(["title"], skip=1, limit=2)
+    assert [book["title"] for book in page] == titles[1:3]
+
+
+@pytest.mark.asyncio
+@pytest.mark.parametrize("store", async_redis_store_fixture)
+async def test_get_all_order_by_async(store):
+    """
+    get_all(order_by=...) and get_all_partially(order_by=...) sort the records in redis by a field before skipping
+    and limiting them, comparing numbers as numbers, the records without a value for the field coming last
+    """
+
+    class Player(Model):
+        name: str
+        team: str
+        score: Optional[int]
+
+    store.create_collection(Player, primary_key_field="name")
+    player_collection = store.get_collection(Player)
+    await player_collection.add_many([
+        Player(name="ann", team="red", score=5), Player(name="bob", team="blue", score=30),
+        Player(name="cid", team="red", score=None), Player(name="dan", team="blue", score=1),
+        Player(name="eve", team="red", score=4)])
+
+    assert [v.name for v in await player_collection.get_all(order_by="score")] == ["dan", "eve", "ann", "bob", "cid"]
+    assert [v.name for v in await player_collection.get_all(order_by="score", desc=True)] == [
+        "bob", "ann", "eve", "dan", "cid"]
+    assert [v.name for v in await player_collection.get_all(order_by="score", skip=1, limit=2)] == ["eve", "ann"]
+    assert [v.name for v in await player_collection.get_all(filter={"team": "red"}, order_by="score", desc=True)] == [
+        "ann", "eve", "cid"]
+    assert [v.name for v in await player_collection.get_all(order_by="team", limit=2)] == ["bob", "dan"]
+    assert await player_collection.get_all_partially(["name"], order_by="name", desc=True, limit=2) == [
+        {"name": "eve"}, {"name": "dan"}]
+
+    with pytest.raises(ValueError, match=r"desc should only be given with order_by"):
+        await player_collection.get_all(desc=True)
+    with pytest.raises(KeyError):
+        await player_collection.get_all_partially(["name"], order_by="rank")
+
+
+@pytest.mark.asyncio
+@pytest.mark.parametrize("store", async_redis_store_fixture)
+async def test_get_all_order_by_index_async(store):
+    """
+    get_all(order_by=...) and get_all_partially(order_by=...) read the page straight from the index of the field if it
+    has one made with create_index(), the records without a value for it coming last in the order of their ids,
+    the index being kept up to date as the records change
+    """
+
+    class Player(Model):
+        name: str
+        team: str
+        score: Optional[int]
+
+    store.create_collection(Player, primary_key_field="name")
+    player_collection = store.get_collection(Player)
+    await player_collection.add_many([
+        Player(name="ann", team="red", score=5), Player(name="bob", team="blue", score=30),
+        Player(name="cid", team="red", score=None), Player(name="dan", team="blue", score=1),
+        Player(name="eve", team="red", score=4)])
+    assert await player_collection.create_index("score") == 5
+
+    assert [v.name for v in await player_collection.get_all(order_by="score")] == ["dan", "eve", "ann", "bob", "cid"]
+    assert [v.name for v in await player_collection.get_all(order_by="score", desc=True)] == [
+        "bob", "ann", "eve", "dan", "cid"]
+    assert [v.name for v in await player_collection.get_all(order_by="score", skip=1, limit=2)] == ["eve", "ann"]
+    assert [v.name for v in await player_collection.get_all(order_by="score", skip=3, limit=5)] == ["bob", "cid"]
+    assert await player_collection.get_all(order_by="score", skip=5) == []
+
+    await player_collection.update_one("dan", data={"score": 10})
+    await player_collection.add_one(Player(name="fay", team="red", score=None))
+    await player_collection.delete_many(["ann"])
+
+    assert [v.name for v in await player_collection.get_all(order_by="score")] == [
+        "eve", "dan", "bob", "cid", "fay"]
+    assert [v.name for v in await player_collection.get_all(filter={"team": "red"}, order_by="score", desc=True)] == [
+        "eve", "cid", "fay"]
+    assert await player_collection.get_all_partially(["name"], order_by="score", desc=True, skip=2, limit=2) == [
+        {"name": "eve"}, {"name": "cid"}]
+
+
+@pytest.mark.asyncio
+@pytest.mark.parametrize("store", async_redis_store_fixture)
+async def test_get_all_order_by_index_with_earlier_records_async(store, redis_server):
+    """
+    get_all(order_by=...) returns the records saved before the index of the field was made, by scanning the
+    collection while the index is still being built, and in the same order once it is built, records with the same
+    value being in the order of their ids either way
+    """
+
+    class Player(Model):
+        name: str
+        team: str
+        score: Optional[int]
+
+    store.create_collection(Player, primary_key_field="name")
+    player_collection = store.get_collection(Player)
+    await player_collection.add_many([
+        Player(name="ann", team="red", score=5), Player(name="bob", team="blue", score=3),
+        Player(name="cid", team="red", score=5), Player(name="dan", team="red", score=None),
+        Player(name="eve", team="blue", score=5)])
+
+    # an index registered by another client that has not yet indexed the records saved before it
+    legacy_client = redis.Redis(port=int(redis_server), db=1)
+    legacy_client.sadd("Player_%&range_indexes_%&_fields", "score")
+    await asyncio.sleep(0.6)
+    await player_collection.add_one(Player(name="fay", team="red", score=3))
+    assert [v.name for v in await player_collection.get_all(order_by="score")] == [
+        "bob", "fay", "ann", "cid", "eve", "dan"]
+    assert [v.name for v in await player_collection.get_all(order_by="score", desc=True)] == [
+        "ann", "cid", "eve", "bob", "fay", "dan"]
+
+    assert await player_collection.create_index("score") == 6
+    await player_collection.add_one(Player(name="gus", team="red", score=1))
+    assert [v.name for v in await player_collection.get_all(order_by="score")] == [
+        "gus", "bob", "fay", "ann", "cid", "eve", "dan"]
+    assert [v.name for v in await player_collection.get_all(order_by="score", desc=True)] == [
+        "ann", "cid", "eve", "bob", "fay", "gus", "dan"]
+    assert [v.name for v in await player_collection.get_all(order_by="score", desc=True, skip=1, limit=3)] == [
+        "cid", "eve", "bob"]
+    assert [v.name for v in await player_collection.get_all(order_by="score", desc=True, skip=4, limit=3)] == [
+        "fay", "gus", "dan"]
+    assert [v.name for v in await player_collection.get_all(
+        filter={"team": "red"}, order_by="score", desc=True)] == ["ann", "cid", "fay", "gus", "dan"]
+
+
 @pytest.mark.asyncio
 @pytest.mark.parametrize("store", async_redis_store_fixture)
 async def test_get_all_partially_async(store):
@@ -174,6 +894,20 @@ async def test_get_many_async(store):
     assert response == books[:2]
 
 
+@pytest.mark.asyncio
+@pytest.mark.parametrize("store", async_redis_store_fixture)
+async def test_get_many_raw_async(store):
+    """
+    get_many_raw() returns the records of the given ids as dictionaries, with their nested records also as dictionaries
+    """
+    book_collection = store.get_collection(Book)
+    await book_collection.add_many(books)
+    ids = [book.title for book in books[:2]]
+    response = await book_collection.get_many_raw(ids=ids)
+    assert response == [book.dict() for book in books[:2]]
+    assert all(isinstance(record["author"], dict) for record in response)
+
+
 @pytest.mark.asyncio
 @pytest.mark.parametrize("store", async_redis_store_fixture)
 async def test_get_one_non_existent_id_async(store):
@@ -308,7 +1042,7 @@ async def test_delete_many_async(store):
     ids_to_delete = [book.title for book in books_to_delete]
     ids_to_leave_intact = [book.title for book in books_to_be_left_in_db]
 
-    await book_collection.delete_many(ids=ids_to_delete)
+    assert await book_collection.delete_many(ids=ids_to_delete) == 2
     deleted_books_select_response = await book_collection.get_many(ids=ids_to_delete)
 
     books_left = await book_collection.get_many(ids=ids_to_leave_intact)
@@ -317,3 +1051,2135 @@ async def test_delete_many_async(store):
     assert deleted_books_select_response == []
     assert books_left == books_to_be_left_in_db
     assert authors_left == sorted(authors.values(), key=lambda x: x.name)
+
+
+@pytest.mark.asyncio
+@pytest.mark.parametrize("store", async_redis_store_fixture)
+async def test_delete_many_with_missing_ids_async(store):
+    """
+    delete_many() returns the number of the given ids that had records, raising a KeyError if strict and any had none
+    """
+    book_collection = store.get_collection(Book)
+    await book_collection.add_many(books)
+
+    assert await book_collection.delete_many([books[0].title, books[0].title, "unknown"]) == 1
+    assert await book_collection.delete_many([books[0].title]) == 0
+
+    with pytest.raises(KeyError, match="1 of the 2 ids have no record"):
+        await book_collection.delete_many([books[1].title, "unknown"], strict=True)
+    # the records that existed are deleted all the same
+    assert await book_collection.get_many([books[1].title]) == []
+    assert await book_collection.delete_many([books[2].title, books[3].title], strict=True) == 2
+
+
+@pytest.mark.asyncio
+@pytest.mark.parametrize("store", async_redis_store_fixture)
+async def test_delete_where_async(store):
+    """
+    delete_where() removes the records whose fields are equal to the given values in one script,
+    returning the number removed, and keeps the indexes of indexed fields up to date
+    """
+    book_collection = store.get_collection(Book)
+    author_collection = store.get_collection(Author)
+    await book_collection.add_many(books)
+
+    assert await book_collection.delete_where(in_stock=False) == 2
+    assert sorted(await book_collection.get_all(), key=lambda x: x.title) == sorted(
+        [book for book in books if book.in_stock], key=lambda x: x.title)
+    assert sorted(await author_collection.get_all(), key=lambda x: x.name) == sorted(
+        authors.values(), key=lambda x: x.name)
+    assert await book_collection.delete_where(in_stock=False) == 0
+    assert await book_collection.delete_where(title="Hamlet") == 0
+
+    with pytest.raises(ValueError, match=r"at least one field filter is needed"):
+        await book_collection.delete_where()
+    with pytest.raises(KeyError, match=r"\"isbn\" is not a field of the model"):
+        await book_collection.delete_where(isbn="foo")
+
+    class Review(Model):
+        id: int
+        reviewer: str
+
+    store.create_collection(Review, primary_key_field="id", indexed_fields=["reviewer"])
+    review_collection = store.get_collection(Review)
+    await review_collection.add_many([Review(id=i, reviewer=f"reviewer {i % 3}") for i in range(9)])
+
+    assert await review_collection.delete_where(reviewer="reviewer 0") == 3
+    assert await review_collection.find_by("reviewer", "reviewer 0") == []
+    assert len(await review_collection.get_all()) == 6
+
+
+@pytest.mark.asyncio
+@pytest.mark.parametrize("store", async_redis_store_fixture)
+async def test_collection_clear_async(store):
+    """
+    clear() on a collection removes its records, with their indexes and unique values, returning the number
+    removed, and leaves the other collections as they are
+    """
+    book_collection = store.get_collection(Book)
+    author_collection = store.get_collection(Author)
+    await book_collection.add_many(books)
+
+    assert await book_collection.clear() == len(books)
+    assert await book_collection.get_all() == []
+    assert sorted(await author_collection.get_all(), key=lambda x: x.name) == sorted(
+        authors.values(), key=lambda x: x.name)
+    assert await book_collection.clear() == 0
+
+    class Member(Model):
+        id: int
+        email: str
+        city: str
+
+    store.create_collection(Member, primary_key_field="id", unique_fields=["email"], indexed_fields=["city"])
+    member_collection = store.get_collection(Member)
+    await member_collection.add_many([Member(id=i, email=f"m{i}@example.com", city=f"city {i % 2}") for i in range(6)])
+
+    assert await member_collection.clear(max_ops_per_sec=1000) == 6
+    assert await member_collection.find_by("city", "city 0") == []
+    # the unique values are released along with the records
+    await member_collection.add_one(Member(id=10, email="m0@example.com", city="city 1"))
+    assert await member_collection.find_by("city", "city 1") == [Member(id=10, email="m0@example.com", city="city 1")]
+
+    with pytest.raises(ValueError, match=r"max_ops_per_sec should be a number greater than 0"):
+        await member_collection.clear(max_ops_per_sec=0)
+
+
+@pytest.mark.asyncio
+@pytest.mark.parametrize("store", async_redis_store_fixture)
+async def test_delete_matching_async(store):
+    """
+    delete_matching() removes the records whose ids match a glob pattern, returning the number removed,
+    and keeps the indexes and unique values of the records up to date
+    """
+    book_collection = store.get_collection(Book)
+    author_collection = store.get_collection(Author)
+    await book_collection.add_many(books)
+
+    assert await book_collection.delete_matching("[GW]*") == 2
+    assert sorted(v.title for v in await book_collection.get_all()) == ["Jane Eyre", "Oliver Twist"]
+    assert sorted(await author_collection.get_all(), key=lambda x: x.name) == sorted(
+        authors.values(), key=lambda x: x.name)
+    assert await book_collection.delete_matching("Hamlet*") == 0
+
+    class Order(Model):
+        id: str
+        customer: str
+        reference: str
+
+    store.create_collection(Order, primary_key_field="id", indexed_fields=["customer"], unique_fields=["reference"])
+    order_collection = store.get_collection(Order)
+    await order_collection.add_many([Order(id=f"order-{year}-{i}", customer=f"c{i}", reference=f"ref-{year}-{i}")
+                               for year in (2023, 2024) for i in range(3)])
+
+    assert await order_collection.delete_matching("order-2023-*", max_ops_per_sec=1000) == 3
+    assert sorted(v.id for v in await order_collection.get_all()) == ["order-2024-0", "order-2024-1", "order-2024-2"]
+    assert [v.id for v in await order_collection.find_by("customer", "c0")] == ["order-2024-0"]
+    # the unique values of the records removed are released
+    await order_collection.add_one(Order(id="order-2025-0", customer="c0", reference="ref-2023-0"))
+
+    with pytest.raises(ValueError, match=r"id_pattern should not be empty"):
+        await order_collection.delete_matching("")
+    with pytest.raises(ValueError, match=r"max_ops_per_sec should be a number greater than 0"):
+        await order_collection.delete_matching("*", max_ops_per_sec=-1)
+
+
+@pytest.mark.asyncio
+@pytest.mark.parametrize("store", async_redis_store_fixture)
+async def test_update_where_async(store):
+    """
+    update_where() changes the fields of the records whose fields are equal to the filters in one script,
+    returning the number changed, and keeps the indexes of indexed fields up to date
+    """
+    book_collection = store.get_collection(Book)
+    await book_collection.add_many(books)
+
+    assert await book_collection.update_where({"in_stock": False}, {"in_stock": True, "rating": 5.0}) == 2
+    expected = sorted([book.copy(update={"in_stock": True, "rating": 5.0}) if not book.in_stock else book
+                       for book in books], key=lambda x: x.title)
+    assert sorted(await book_collection.get_all(), key=lambda x: x.title) == expected
+    assert await book_collection.update_where({"in_stock": False}, {"rating": 1.0}) == 0
+
+    with pytest.raises(ValueError, match=r"filters should have at least one field"):
+        await book_collection.update_where({}, {"rating": 1.0})
+    with pytest.raises(ValueError, match=r"changes should have at least one field"):
+        await book_collection.update_where({"in_stock": True}, {})
+    with pytest.raises(ValueError, match=r"primary keys cannot be changed"):
+        await book_collection.update_where({"in_stock": True}, {"title": "Hamlet"})
+    with pytest.raises(ValueError, match=r"\"author\" is a nested field"):
+        await book_collection.update_where({"in_stock": True}, {"author": authors["jane"]})
+
+    class Review(Model):
+        id: int
+        reviewer: str
+
+    store.create_collection(Review, primary_key_field="id", indexed_fields=["reviewer"])
+    review_collection = store.get_collection(Review)
+    await review_collection.add_many([Review(id=i, reviewer=f"reviewer {i % 3}") for i in range(9)])
+
+    assert await review_collection.update_where({"reviewer": "reviewer 0"}, {"reviewer": "reviewer 1"}) == 3
+    assert await review_collection.find_by("reviewer", "reviewer 0") == []
+    assert len(await review_collection.find_by("reviewer", "reviewer 1")) == 6
+
+
+@pytest.mark.asyncio
+@pytest.mark.parametrize("id_generator", ["uuid4", "ulid", "snowflake", "ksuid"])
+@pytest.mark.parametrize("store", async_redis_store_fixture)
+async def test_add_one_with_id_generator_async(store, id_generator):
+    """
+    add_one() generates a primary key for records without one if an id_generator was set on the collection,
+    and returns it
+    """
+
+    class Event(Model):
+        name: str
+        id: Optional[str] = None
+
+    store.create_collection(Event, primary_key_field="id", id_generator=id_generator)
+    event_collection = store.get_collection(Event)
+
+    first_id = await event_collection.add_one(Event(name="first"))
+    # wait long enough for the timestamp portion of the id to change
+    await asyncio.sleep(id_generator_resolutions[id_generator])
+    added_ids = await event_collection.add_many([Event(name="second"), Event(name="third", id="custom-id")])
+
+    events = await event_collection.get_all()
+    ids = {event.name: event.id for event in events}
+
+    assert len(events) == 3
+    assert ids["third"] == "custom-id"
+    assert ids["first"] not in (None, "None")
+    assert [ids["first"], ids["second"], ids["third"]] == [first_id, *added_ids]
+    assert ids["first"] != ids["second"]
+    assert await event_collection.get_one(id=ids["first"]) == Event(name="first", id=ids["first"])
+
+    if id_generator != "uuid4":
+        # ids are lexicographically sortable by creation time
+        first, second = ids["first"], ids["second"]
+        assert len(first) == len(second)
+        assert first < second
+
+
+@pytest.mark.asyncio
+@pytest.mark.parametrize("store", async_redis_store_fixture)
+async def test_apply_retention_async(store):
+    """
+    apply_retention() removes the records whose retention_field is older than the collection's retention period
+    """
+
+    class Log(Model):
+        message: str
+        created_at: datetime
+
+    now = datetime.now(tz=timezone.utc).replace(microsecond=0)
+    old_log = Log(message="old", created_at=now - timedelta(days=31))
+    new_log = Log(message="new", created_at=now - timedelta(days=29))
+
+    store.create_collection(Log, primary_key_field="message", retention=timedelta(days=30),
+                            retention_field="created_at")
+    log_collection = store.get_collection(Log)
+
+    await log_collection.add_many([old_log, new_log])
+
+    assert len(await log_collection.get_all()) == 2
+    assert await store.apply_retention() == {"Log": 1}
+    assert await log_collection.get_all() == [new_log]
+
+
+@pytest.mark.asyncio
+@pytest.mark.parametrize("store", async_redis_store_fixture)
+async def test_apply_retention_with_max_ops_per_sec_async(store):
+    """
+    apply_retention(max_ops_per_sec) scans no more records per second than max_ops_per_sec
+    """
+
+    class Log(Model):
+        message: str
+        created_at: datetime
+
+    now = datetime.now(tz=timezone.utc).replace(microsecond=0)
+    old_logs = [Log(message=f"old {i}", created_at=now - timedelta(days=31)) for i in range(10)]
+    new_log = Log(message="new", created_at=now - timedelta(days=29))
+
+    store.create_collection(Log, primary_key_field="message", retention=timedelta(days=30),
+                            retention_field="created_at")
+    log_collection = store.get_collection(Log)
+    await log_collection.add_many([*old_logs, new_log])
+
+    with pytest.raises(ValueError, match=r"max_ops_per_sec should be a number greater than 0"):
+        await store.apply_retention(max_ops_per_sec=-1)
+
+    started_at = asyncio.get_running_loop().time()
+    assert await store.apply_retention(max_ops_per_sec=10) == {"Log": 10}
+    assert asyncio.get_running_loop().time() - started_at >= 1
+    assert await log_collection.get_all() == [new_log]
+
+
+@pytest.mark.asyncio
+@pytest.mark.parametrize("store", async_redis_store_fixture)
+async def test_ttl_audit_async(store):
+    """
+    ttl_audit() counts the records of each collection that have no ttl, a ttl below the threshold
+    and a ttl below the imminent expiry
+    """
+
+    class Session(Model):
+        token: str
+
+    store.create_collection(Session, primary_key_field="token")
+    session_collection = store.get_collection(Session)
+
+    await session_collection.add_many([Session(token="foo"), Session(token="bar")])
+    await session_collection.add_one(Session(token="expiring"), ttl=30)
+    await session_collection.add_one(Session(token="short"), ttl=timedelta(minutes=30))
+    await session_collection.add_one(Session(token="long"), ttl=timedelta(hours=2))
+
+    audit = await store.ttl_audit()
+    assert audit["Session"] == {"records": 5, "no_ttl": 2, "below_threshold": 2, "imminent_expiry": 1}
+    assert audit["Book"] == {"records": 0, "no_ttl": 0, "below_threshold": 0, "imminent_expiry": 0}
+
+    audit = await store.ttl_audit(threshold=timedelta(hours=3), imminent=10)
+    assert audit["Session"] == {"records": 5, "no_ttl": 2, "below_threshold": 3, "imminent_expiry": 0}
+
+
+@pytest.mark.asyncio
+@pytest.mark.parametrize("store", async_redis_store_fixture)
+async def test_add_mirror_async(store, redis_server):
+    """
+    add_mirror() applies every write made through the async store to the mirror in the background
+    """
+    mirror_store = AsyncStore(url=f"redis://localhost:{redis_server}/2")
+    mirror_store.create_collection(Author, primary_key_field="name")
+    mirror_store.create_collection(Book, primary_key_field="title")
+    mirror_book_collection = mirror_store.get_collection(Book)
+
+    store.add_mirror(f"redis://localhost:{redis_server}/2")
+    book_collection = store.get_collection(Book)
+
+    await book_collection.add_many(books)
+    await book_collection.update_one(books[0].title, data={"in_stock": True})
+    await book_collection.delete_many([books[1].title])
+    await asyncio.sleep(0.3)
+
+    expected = [await book_collection.get_one(books[0].title), books[2], books[3]]
+    got = await mirror_book_collection.get_all()
+    assert sorted(got, key=lambda b: b.title) == sorted(expected, key=lambda b: b.title)
+    assert (await mirror_book_collection.get_one(books[0].title)).in_stock
+
+
+@pytest.mark.asyncio
+@pytest.mark.parametrize("store", async_redis_store_fixture)
+async def test_generate_fake_async(store):
+    """
+    generate_fake() adds the given number of valid records, with their nested records, generated from the schema
+    """
+    book_collection = store.get_collection(Book)
+    await book_collection.generate_fake(1_500, seed=7)
+
+    got = await book_collection.get_all()
+    assert len(got) == 1_500
+    assert all(isinstance(book, Book) and isinstance(book.author, Author) for book in got)
+
+
+@pytest.mark.asyncio
+async def test_write_buffer_async(unused_tcp_port):
+    """
+    Writes made through the async store while redis is unreachable are held in the write buffer,
+    up to its capacity
+    """
+    store = AsyncStore(url=f"redis://localhost:{unused_tcp_port}/1", timeout=100, write_buffer=3)
+    store.create_collection(Author, primary_key_field="name")
+    store.create_collection(Book, primary_key_field="title")
+    book_collection = store.get_collection(Book)
+    assert store.pending_writes() == 0
+
+    for book in books:
+        await book_collection.add_one(book)
+    await book_collection.delete_many([books[0].title])
+
+    assert store.pending_writes() == 3
+    assert store.take_failed_writes() == []
+
+
+@pytest.mark.asyncio
+async def test_write_buffer_failed_writes_async(unused_tcp_port):
+    """
+    Buffered writes of the async store that fail for reasons other than redis being unreachable when they are
+    applied are dropped, their errors got by take_failed_writes()
+    """
+    store = AsyncStore(url=f"redis://localhost:{unused_tcp_port}/1", timeout=100, write_buffer=3)
+    store.create_collection(Author, primary_key_field="name")
+    store.create_collection(Book, primary_key_field="title")
+    book_collection = store.get_collection(Book)
+
+    await book_collection.add_one(books[0])
+    assert store.pending_writes() == 1
+
+    # redis rejects all writes once it uses more memory than the limit
+    server = redislite.Redis(serverconfig={"port": unused_tcp_port, "maxmemory": "1",
+                                           "maxmemory-policy": "noeviction"})
+    for _ in range(50):
+        if store.pending_writes() == 0:
+            break
+        await asyncio.sleep(0.1)
+
+    assert store.pending_writes() == 0
+    failures = store.take_failed_writes()
+    assert len(failures) == 1 and "OOM" in failures[0]
+    assert await book_collection.get_all() == []
+    server.shutdown()
+
+
+@pytest.mark.asyncio
+async def test_write_buffer_with_unique_values_async(unused_tcp_port):
+    """
+    Writes through the async store with values of unique fields are not buffered, raising a ConnectionError while
+    redis is unreachable or other writes are buffered, since another record may have the values
+    """
+    store = AsyncStore(url=f"redis://localhost:{unused_tcp_port}/1", timeout=100, write_buffer=3)
+    store.create_collection(Author, primary_key_field="name")
+    store.create_collection(Book, primary_key_field="title")
+
+    class User(Model):
+        id: str
+        email: str
+
+    store.create_collection(User, primary_key_field="id", unique_fields=["email"])
+    user_collection = store.get_collection(User)
+
+    with pytest.raises(ConnectionError):
+        await user_collection.add_one(User(id="u1", email="ann@example.com"))
+    assert store.pending_writes() == 0
+
+    await store.get_collection(Book).add_one(books[0])
+    with pytest.raises(ConnectionError, match=r"cannot wait behind 1 buffered writes"):
+        await user_collection.add_one(User(id="u1", email="ann@example.com"))
+    assert store.pending_writes() == 1
+
+
+@pytest.mark.asyncio
+async def test_circuit_breaker_async(unused_tcp_port):
+    """
+    Once circuit_breaker_threshold calls in a row through the async store fail to get a connection, calls raise
+    a CircuitOpenError without waiting for one, till a call let through after circuit_breaker_reset gets one
+    """
+    store = AsyncStore(url=f"redis://localhost:{unused_tcp_port}/1", timeout=100, circuit_breaker_threshold=2,
+                       circuit_breaker_reset=500)
+    store.create_collection(Author, primary_key_field="name")
+    store.create_collection(Book, primary_key_field="title")
+    book_collection = store.get_collection(Book)
+    assert store.circuit_breaker()["state"] == "closed"
+
+    for _ in range(2):
+        with pytest.raises(ConnectionError) as exc_info:
+            await book_collection.get_one(books[0].title)
+        assert not isinstance(exc_info.value, CircuitOpenError)
+
+    with pytest.raises(CircuitOpenError):
+        await book_collection.get_one(books[0].title)
+    assert store.circuit_breaker()["state"] == "open"
+
+    server = redislite.Redis(serverconfig={"port": unused_tcp_port})
+    await asyncio.sleep(0.5)
+    await book_collection.add_one(books[0])
+    assert await book_collection.get_one(books[0].title) == books[0]
+    assert store.circuit_breaker() == {
+        "state": "closed", "consecutive_failures": 0, "failure_threshold": 2, "retry_in_ms": None}
+    server.shutdown()
+
+
+@pytest.mark.asyncio
+async def test_blocking_pool_async(redis_server):
+    """
+    Long operations e.g. a throttled create_index(), get their connections from the blocking pool of the async store
+    so that other calls still get connections from the main pool while they run
+    """
+    store = AsyncStore(url=f"redis://localhost:{redis_server}/1", pool_size=1, timeout=200, blocking_pool_size=1)
+    store.create_collection(Author, primary_key_field="name")
+    store.create_collection(Book, primary_key_field="title")
+    book_collection = store.get_collection(Book)
+    await book_collection.add_many(books)
+
+    indexing = asyncio.ensure_future(book_collection.create_index("rating", max_ops_per_sec=2))
+    await asyncio.sleep(0.1)
+    assert not indexing.done()
+    for book in books:
+        assert await book_collection.get_one(book.title) == book
+    await indexing
+
+    indexed = await book_collection.find_between("rating", 4, None)
+    assert sorted(book.title for book in indexed) == sorted(book.title for book in books if book.rating >= 4)
+
+
+@pytest.mark.asyncio
+async def test_max_batch_bytes_async(redis_server):
+    """Records bigger than max_batch_bytes are written and read in many batches, all of them being returned"""
+    store = AsyncStore(url=f"redis://localhost:{redis_server}/1", max_batch_bytes=100)
+    store.create_collection(Author, primary_key_field="name")
+    store.create_collection(Book, primary_key_field="title")
+    book_collection = store.get_collection(Book)
+    await book_collection.add_many(books)
+
+    titles = [book.title for book in books]
+    assert await book_collection.get_many(titles) == books
+    # the second read is batched by the sizes of the records read by the first
+    assert await book_collection.get_many(titles + ["non-existent"]) == books
+    assert await book_collection.get_many([]) == []
+    assert sorted(book.title for book in await book_collection.get_all()) == sorted(titles)
+
+
+@pytest.mark.asyncio
+@pytest.mark.parametrize("store", async_redis_store_fixture)
+async def test_quota_async(store):
+    """
+    Inserts through the collections of a namespace that would take it beyond its quota raise a QuotaExceededError,
+    while updates of existing records and deletes are allowed
+    """
+
+    class Session(Model):
+        token: str
+        user: str
+
+    store.create_collection(Session, primary_key_field="token", namespace="team_a")
+    await store.set_quota("team_a", max_records=2)
+    session_collection = store.get_collection(Session)
+    await session_collection.add_many([Session(token="foo", user="john"), Session(token="bar", user="jane")])
+
+    with pytest.raises(QuotaExceededError):
+        await session_collection.add_one(Session(token="baz", user="jim"))
+    await session_collection.update_one("foo", {"user": "johnny"})
+    assert (await store.get_quota("team_a"))["records"] == 2
+
+    await session_collection.delete_many(["bar"])
+    await session_collection.add_one(Session(token="baz", user="jim"))
+    assert await session_collection.get_one("baz") == Session(token="baz", user="jim")
+    assert (await store.get_quota("team_a"))["max_records"] == 2
+
+
+@pytest.mark.asyncio
+@pytest.mark.parametrize("store", async_redis_store_fixture)
+async def test_quota_concurrent_inserts_async(store):
+    """
+    Of many concurrent inserts through the collections of a namespace, only those within its quota are made,
+    the quota being checked and its usage changed atomically with each insert
+    """
+
+    class Session(Model):
+        token: str
+        user: str
+
+    store.create_collection(Session, primary_key_field="token", namespace="team_a")
+    await store.set_quota("team_a", max_records=5)
+    session_collection = store.get_collection(Session)
+
+    results = await asyncio.gather(
+        *[session_collection.add_one(Session(token=f"token_{i}", user="john")) for i in range(20)],
+        return_exceptions=True)
+    errors = [v for v in results if isinstance(v, Exception)]
+    assert len(errors) == 15
+    assert all(isinstance(e, QuotaExceededError) for e in errors)
+    assert len(await session_collection.get_all()) == 5
+    assert (await store.get_quota("team_a"))["records"] == 5
+
+
+@pytest.mark.asyncio
+@pytest.mark.parametrize("store", async_redis_store_fixture)
+async def test_versioning_async(store, redis_server):
+    """
+    Records of older format versions are upgraded when read asynchronously, and saved if persist_upgrades is True
+    """
+
+    class Member(Model):
+        name: str
+        age: int
+
+    def rename_fullname(data):
+        data["name"] = data.pop("fullname")
+        return data
+
+    store.create_collection(Member, primary_key_field="name", version=1, upgrades={0: rename_fullname},
+                            persist_upgrades=True)
+    member_collection = store.get_collection(Member)
+    legacy_client = redis.Redis(port=int(redis_server), db=1)
+    legacy_client.hset("Member_%&_john", mapping={"fullname": "john", "age": "30"})
+    await member_collection.add_one(Member(name="doe", age=40))
+
+    assert await member_collection.get_one("john") == Member(name="john", age=30)
+    assert await member_collection.get_one("doe") == Member(name="doe", age=40)
+    assert legacy_client.hgetall("Member_%&_john") == {b"name": b"john", b"age": b"30", b"_version": b"1"}
+
+
+@pytest.mark.asyncio
+@pytest.mark.parametrize("store", async_redis_store_fixture)
+async def test_conversion_error_context_async(store, redis_server):
+    """
+    Errors converting the fields of records read asynchronously from redis say which collection, record and field failed
+    """
+
+    class Member(Model):
+        name: str
+        joined_on: date
+
+    store.create_collection(Member, primary_key_field="name")
+    member_collection = store.get_collection(Member)
+    legacy_client = redis.Redis(port=int(redis_server), db=1)
+    legacy_client.hset("Member_%&_john", mapping={"name": "john", "joined_on": "01/10/2022"})
+
+    with pytest.raises(ValueError) as exc_info:
+        await member_collection.get_all()
+
+    message = str(exc_info.value)
+    assert '"joined_on"' in message
+    assert '"Member_%&_john"' in message
+    assert '"Member"' in message
+
+
+@pytest.mark.asyncio
+@pytest.mark.parametrize("store", async_redis_store_fixture)
+async def test_get_all_on_error_async(store, redis_server):
+    """
+    get_all() skips the records that cannot be read, or returns them separately with their errors,
+    as on_error says
+    """
+
+    class Member(Model):
+        name: str
+        joined_on: date
+
+    store.create_collection(Member, primary_key_field="name")
+    member_collection = store.get_collection(Member)
+    await member_collection.add_one(Member(name="jane", joined_on=date(2022, 9, 1)))
+    legacy_client = redis.Redis(port=int(redis_server), db=1)
+    legacy_client.hset("Member_%&_john", mapping={"name": "john", "joined_on": "01/10/2022"})
+
+    assert await member_collection.get_all(on_error="skip") == [Member(name="jane", joined_on=date(2022, 9, 1))]
+
+    records, errors = await member_collection.get_all(on_error="collect")
+    assert records == [Member(name="jane", joined_on=date(2022, 9, 1))]
+    assert [error["id"] for error in errors] == ["john"]
+
+
+@pytest.mark.asyncio
+@pytest.mark.parametrize("store", async_redis_store_fixture)
+async def test_repair_async(store, redis_server):
+    """
+    repair() rewrites the values in known broken formats in the format orredis writes, returning the number of records
+    repaired
+    """
+
+    class Member(Model):
+        name: str
+        age: Optional[int] = None
+        is_active: bool
+
+    store.create_collection(Member, primary_key_field="name")
+    member_collection = store.get_collection(Member)
+    legacy_client = redis.Redis(port=int(redis_server), db=1)
+    legacy_client.hset("Member_%&_john", mapping={"name": "john", "age": "null", "is_active": "FALSE"})
+
+    assert await member_collection.repair() == 1
+    assert await member_collection.get_one("john") == Member(name="john", age=None, is_active=False)
+
+
+@pytest.mark.asyncio
+@pytest.mark.parametrize("store", async_redis_store_fixture)
+async def test_compact_async(store, redis_server):
+    """
+    compact() removes the fields that are no longer in the model from the records, returning the number of records
+    compacted, and can do so in the background
+    """
+
+    class Member(Model):
+        name: str
+        age: int
+
+    store.create_collection(Member, primary_key_field="name", version=1)
+    member_collection = store.get_collection(Member)
+    jane, john = Member(name="jane", age=30), Member(name="john", age=25)
+    await member_collection.add_many([jane, john])
+    client = redis.Redis(port=int(redis_server), db=1)
+    client.hset("Member_%&_john", mapping={"nickname": "johnny", "email": "john@example.com"})
+
+    with pytest.raises(ValueError, match=r"max_ops_per_sec should be a number greater than 0"):
+        await member_collection.compact(max_ops_per_sec=-1)
+
+    assert await member_collection.compact(max_ops_per_sec=100) == 1
+    assert set(client.hkeys("Member_%&_john")) == {b"name", b"age", b"_version"}
+    assert await member_collection.get_many(["jane", "john"]) == [jane, john]
+    assert await member_collection.compact() == 0
+
+    client.hset("Member_%&_jane", "nickname", "janie")
+    assert member_collection.compact(background=True) is None
+    for _ in range(50):
+        if not store.background_tasks()["compact:Member"]["running"]:
+            break
+        await asyncio.sleep(0.1)
+    assert store.background_tasks()["compact:Member"] == {"running": False, "runs": 1, "error": None}
+    assert set(client.hkeys("Member_%&_jane")) == {b"name", b"age", b"_version"}
+
+
+@pytest.mark.asyncio
+@pytest.mark.parametrize("store", async_redis_store_fixture)
+async def test_self_referencing_model_async(store):
+    """
+    Records of models that nest themselves are read asynchronously to the nested_depth given to create_collection()
+    """
+
+    class Employee(Model):
+        name: str
+        manager: Optional["Employee"] = None
+
+    Employee.update_forward_refs(Employee=Employee)
+    store.create_collection(Employee, primary_key_field="name", nested_depth=2)
+    employee_collection = store.get_collection(Employee)
+    bob = Employee(name="Bob", manager=Employee(name="Alice"))
+
+    await employee_collection.add_one(Employee(name="Carol", manager=bob))
+
+    assert await employee_collection.get_one("Bob") == bob
+    assert await employee_collection.get_one("Carol") == Employee(name="Carol", manager=bob)
+    assert sorted(await employee_collection.get_all(), key=lambda v: v.name) == [
+        Employee(name="Alice"), bob, Employee(name="Carol", manager=bob)]
+
+
+@pytest.mark.asyncio
+@pytest.mark.parametrize("store", async_redis_store_fixture)
+async def test_update_one_with_dotted_paths_async(store):
+    """update_one() updates the fields of nested records addressed by dotted paths directly, asynchronously"""
+
+    class Address(Model):
+        street: str
+        city: str
+
+    class Customer(Model):
+        name: str
+        address: Address
+
+    store.create_collection(Address, primary_key_field="street")
+    store.create_collection(Customer, primary_key_field="name")
+    customer_collection = store.get_collection(Customer)
+    address_collection = store.get_collection(Address)
+    await customer_collection.add_one(Customer(name="John", address=Address(street="KN 5", city="Butare")))
+
+    await customer_collection.update_one("John", {"name": "John", "address.city": "Kigali"})
+
+    assert await address_collection.get_one("KN 5") == Address(street="KN 5", city="Kigali")
+    assert await customer_collection.get_one("John") == Customer(
+        name="John", address=Address(street="KN 5", city="Kigali"))
+
+
+@pytest.mark.asyncio
+@pytest.mark.parametrize("store", async_redis_store_fixture)
+async def test_get_partially_with_dotted_paths_async(store):
+    """get_*_partially() return only the fields of nested records given by their dotted paths, asynchronously"""
+
+    class Address(Model):
+        street: str
+        city: str
+
+    class Customer(Model):
+        name: str
+        address: Address
+
+    store.create_collection(Address, primary_key_field="street")
+    store.create_collection(Customer, primary_key_field="name")
+    customer_collection = store.get_collection(Customer)
+    await customer_collection.add_one(Customer(name="John", address=Address(street="KN 5", city="Kigali")))
+    expected = {"name": "John", "address": {"city": "Kigali"}}
+
+    assert await customer_collection.get_one_partially("John", ["name", "address.city"]) == expected
+    assert await customer_collection.get_many_partially(["John"], ["name", "address.city"]) == [expected]
+    assert await customer_collection.get_all_partially(["name", "address.city"]) == [expected]
+
+
+@pytest.mark.asyncio
+@pytest.mark.parametrize("store", async_redis_store_fixture)
+async def test_get_partially_with_nested_as_models_async(store):
+    """get_*_partially() return the nested records given by dotted paths as model instances, asynchronously"""
+
+    class Address(Model):
+        street: str
+        city: str
+
+    class Customer(Model):
+        name: str
+        address: Address
+
+    store.create_collection(Address, primary_key_field="street")
+    store.create_collection(Customer, primary_key_field="name")
+    customer_collection = store.get_collection(Customer)
+    await customer_collection.add_one(Customer(name="John", address=Address(street="KN 5", city="Kigali")))
+    fields = ["name", "address.city"]
+
+    for got in [await customer_collection.get_one_partially("John", fields, nested_as_models=True),
+                *await customer_collection.get_many_partially(["John"], fields, nested_as_models=True),
+                *await customer_collection.get_all_partially(fields, nested_as_models=True)]:
+        assert isinstance(got["address"], Address)
+        assert got["address"].city == "Kigali"
+
+
+@pytest.mark.asyncio
+async def test_pool_exhausted_async(redis_server):
+    """
+    A PoolExhaustedError with the context of the pool is raised if all connections stay in use till the timeout
+    """
+    store = AsyncStore(url=f"redis://localhost:{redis_server}/1", pool_size=1, timeout=10)
+    store.create_collection(Author, primary_key_field="name")
+    store.create_collection(Book, primary_key_field="title")
+    book_collection = store.get_collection(Book)
+    await book_collection.add_many(books)
+
+    with pytest.raises(PoolExhaustedError) as exc_info:
+        await asyncio.gather(*[book_collection.get_all() for _ in range(200)])
+
+    assert isinstance(exc_info.value, ConnectionError)
+    assert exc_info.value.pool_size == 1
+    assert exc_info.value.in_use == 1
+    assert exc_info.value.waited_ms >= 0
+    assert "pool_size" in exc_info.value.suggestion
+
+
+@pytest.mark.asyncio
+@pytest.mark.parametrize("store", async_redis_store_fixture)
+async def test_verify_against_async(store, redis_server):
+    """
+    verify_against() reports the records that are missing from either async store or whose fields differ
+    """
+    other_store = AsyncStore(url=f"redis://localhost:{redis_server}/2")
+    other_store.create_collection(Author, primary_key_field="name")
+    other_store.create_collection(Book, primary_key_field="title")
+    book_collection = store.get_collection(Book)
+    other_book_collection = other_store.get_collection(Book)
+
+    await book_collection.add_many(books)
+    await other_book_collection.add_many(books)
+    await other_book_collection.update_one(books[0].title, data={"rating": 1.5})
+    await other_book_collection.delete_many([books[1].title])
+    await book_collection.delete_many([books[2].title])
+
+    assert await store.verify_against(other_store, Book, sample=1) == {
+        "sampled": 4,
+        "missing": [books[1].title],
+        "extra": [books[2].title],
+        "mismatched": {books[0].title: {"rating": (books[0].rating, 1.5)}},
+    }
+
+
+@pytest.mark.asyncio
+@pytest.mark.parametrize("store", async_redis_store_fixture)
+async def test_latency_async(store):
+    """latency() returns the statistics of the round-trip latencies of PINGs to redis"""
+    stats = await store.latency(samples=20)
+    assert stats["samples"] == 20
+    assert 0 <= stats["min_ms"] <= stats["p99_ms"] <= stats["max_ms"]
+    assert stats["checkout_ms"] >= 0
+
+
+@pytest.mark.asyncio
+async def test_connect_and_aclose_async(redis_server):
+    """connect() PINGs redis and aclose() closes the store, cancelling its background tasks"""
+    store = AsyncStore(url=f"redis://localhost:{redis_server}/1")
+    store.create_collection(Book, primary_key_field="title")
+    book_collection = store.get_collection(Book)
+    await store.connect()
+    await book_collection.add_one(books[0])
+
+    async def job():
+        pass
+
+    store.spawn_background("job", job, 0.01)
+    await store.aclose()
+    assert store.background_tasks() == {}
+    with pytest.raises(ConnectionError, match="the store is closed"):
+        await book_collection.get_one(books[0].title)
+    with pytest.raises(ConnectionError, match="the store is closed"):
+        await store.connect()
+
+
+@pytest.mark.asyncio
+async def test_fastapi_store_provider_async(redis_server):
+    """
+    StoreProvider creates and sets up the store on startup, provides it and its collections as dependencies
+    and closes it on shutdown
+    """
+    provider = StoreProvider(lambda: AsyncStore(url=f"redis://localhost:{redis_server}/1"),
+                             setup=lambda store: store.create_collection(Book, primary_key_field="title"))
+    get_books = provider.collection(Book)
+    with pytest.raises(RuntimeError, match="the store is not started"):
+        provider.get_store()
+
+    async with provider.lifespan(None):
+        book_collection = get_books()
+        assert get_books() is book_collection
+        await book_collection.add_one(books[0])
+        assert await book_collection.get_one(books[0].title) == books[0]
+        store = provider.get_store()
+        assert isinstance(store, AsyncStore)
+
+    with pytest.raises(RuntimeError, match="the store is not started"):
+        get_books()
+    with pytest.raises(ConnectionError, match="the store is closed"):
+        await book_collection.get_one(books[0].title)
+
+
+@pytest.mark.asyncio
+async def test_fastapi_store_provider_with_sync_store_async(redis_server):
+    """StoreProvider wires a lazy sync store in as is, with an async setup, and installs its event handlers"""
+    store = Store(url=f"redis://localhost:{redis_server}/1", lazy=True)
+
+    async def setup(s):
+        s.create_collection(Book, primary_key_field="title")
+
+    class App:
+        def __init__(self):
+            self.handlers = {}
+
+        def add_event_handler(self, event, handler):
+            self.handlers[event] = handler
+
+    app = App()
+    provider = StoreProvider(store, setup=setup)
+    provider.install(app)
+
+    await app.handlers["startup"]()
+    assert provider.get_store() is store
+    book_collection = provider.collection(Book)()
+    book_collection.add_one(books[0])
+    assert book_collection.get_one(books[0].title) == books[0]
+
+    await app.handlers["shutdown"]()
+    with pytest.raises(ConnectionError, match="the store is closed"):
+        book_collection.get_one(books[0].title)
+
+
+@pytest.mark.asyncio
+async def test_max_connecting(redis_server):
+    """Many tasks starting at once all get connections when few connections can be established at a time"""
+    store = AsyncStore(url=f"redis://localhost:{redis_server}/1", pool_size=10, max_connecting=2)
+    store.create_collection(Author, primary_key_field="name")
+    author_collection = store.get_collection(Author)
+    await author_collection.add_many(list(authors.values()))
+
+    results = await asyncio.gather(*[author_collection.get_one(authors["jane"].name) for _ in range(30)])
+    assert results == [authors["jane"]] * 30
+    await store.clear()
+
+
+def test_invalid_max_connecting(redis_server):
+    """Throws a ValueError when max_connecting is less than 1"""
+    with pytest.raises(ValueError):
+        AsyncStore(url=f"redis://localhost:{redis_server}/1", max_connecting=0)
+
+
+@pytest.mark.asyncio
+@pytest.mark.parametrize("store", async_redis_store_fixture)
+async def test_metrics_async(store):
+    """metrics() returns the counts of the operations on each collection and of the records they read or wrote"""
+    book_collection = store.get_collection(Book)
+    await book_collection.add_many(books)
+    await book_collection.get_all()
+    await book_collection.update_one(books[0].title, data={"rating": 1.5})
+
+    assert store.metrics()["Book"] == {
+        "add_many": {"calls": 1, "records": 4},
+        "get_all": {"calls": 1, "records": 4},
+        "update_one": {"calls": 1, "records": 1},
+    }
+
+
+@pytest.mark.asyncio
+async def test_async_store_with_ipv6_host(redis_server):
+    """An async store connects to redis at an IPv6 literal host"""
+    try:
+        socket.create_connection(("::1", int(redis_server)), timeout=1).close()
+    except OSError:
+        pytest.skip("redis is not listening on the IPv6 loopback address")
+
+    store = AsyncStore(url=f"redis://[::1]:{redis_server}/1")
+    store.create_collection(Author, primary_key_field="name")
+    author_collection = store.get_collection(Author)
+    await author_collection.add_one(authors["jane"])
+    assert await author_collection.get_one(authors["jane"].name) == authors["jane"]
+    await store.clear()
+
+
+def test_async_store_with_unresolvable_srv_url():
+    """Throws a ConnectionError when no redis endpoint is found in the SRV records of the url's host"""
+    with pytest.raises(ConnectionError):
+        AsyncStore(url="redis+srv://_redis._tcp.orredis.invalid/0")
+@pytest.mark.asyncio
+@pytest.mark.parametrize("store", async_redis_store_fixture)
+async def test_var_tuple_fields_async(store):
+    """Fields annotated as tuples of any length e.g. Tuple[int, ...] are read back as tuples of their items"""
+
+    class Sensor(Model):
+        name: str
+        readings: Tuple[int, ...]
+        labels: Tuple[str, ...] = ()
+
+    store.create_collection(Sensor, primary_key_field="name")
+    sensor_collection = store.get_collection(Sensor)
+    sensors = [
+        Sensor(name="a", readings=(1, 2, 3), labels=("x", "y")),
+        Sensor(name="b", readings=(4,), labels=("z",)),
+        Sensor(name="c", readings=()),
+    ]
+    await sensor_collection.add_many(sensors)
+
+    for sensor in sensors:
+        got = await sensor_collection.get_one(sensor.name)
+        assert got == sensor
+        assert isinstance(got.readings, tuple)
+
+    assert store.describe()["collections"][Sensor.__qualname__]["fields"]["readings"] == "tuple[int, ...]"
+
+@pytest.mark.asyncio
+@pytest.mark.parametrize("store", async_redis_store_fixture)
+async def test_dict_key_types_async(store):
+    """The keys of dictionaries annotated with int or float keys e.g. Dict[int, str] are read back as ints or floats"""
+
+    class Ledger(Model):
+        name: str
+        entries: Dict[int, str]
+        rates: Dict[float, str] = {}
+        notes: Dict[str, str] = {}
+
+    store.create_collection(Ledger, primary_key_field="name")
+    ledger_collection = store.get_collection(Ledger)
+    ledger = Ledger(name="a", entries={1: "one", 20: "twenty"}, rates={0.5: "half"}, notes={"1": "first"})
+    await ledger_collection.add_one(ledger)
+
+    assert await ledger_collection.get_one("a") == ledger
+    partial = await ledger_collection.get_one_partially("a", fields=["entries", "rates", "notes"])
+    assert partial == {"entries": {1: "one", 20: "twenty"}, "rates": {0.5: "half"}, "notes": {"1": "first"}}
+
+    fields = store.describe()["collections"][Ledger.__qualname__]["fields"]
+    assert fields["entries"] == "dict[int, str]"
+    assert fields["notes"] == "dict[str, str]"
+
+
+@pytest.mark.asyncio
+@pytest.mark.parametrize("store", async_redis_store_fixture)
+async def test_typed_dict_values_async(store):
+    """The values of dictionaries are read back as the types given in their models e.g. Dict[str, List[int]]"""
+
+    class Part(Model):
+        code: str
+        tags: List[str] = []
+
+    class Machine(Model):
+        name: str
+        readings: Dict[str, List[int]]
+        parts: Dict[str, Part] = {}
+        flags: Dict[str, bool] = {}
+
+    store.create_collection(Part, primary_key_field="code")
+    store.create_collection(Machine, primary_key_field="name")
+    machine_collection = store.get_collection(Machine)
+    machine = Machine(name="a", readings={"x": [1, 2], "y": []},
+                      parts={"main": Part(code="p, 1: x", tags=["a", "b, c"])}, flags={"on": True, "off": False})
+    await machine_collection.add_one(machine)
+
+    assert await machine_collection.get_one("a") == machine
+    partial = await machine_collection.get_one_partially("a", fields=["readings", "flags"])
+    assert partial == {"readings": {"x": [1, 2], "y": []}, "flags": {"on": True, "off": False}}
+
+    fields = store.describe()["collections"][Machine.__qualname__]["fields"]
+    assert fields["readings"] == "dict[str, list[int]]"
+    assert fields["parts"] == "dict[str, Part]"
+
+
+@pytest.mark.asyncio
+@pytest.mark.parametrize("store", async_redis_store_fixture)
+async def test_enforce_constraints_async(store):
+    """enforce_constraints checks the values of fields of constrained types on inserts and partial updates"""
+
+    class Account(Model):
+        id: str
+        balance: conint(ge=0)
+        code: constr(max_length=4, regex=r"^[A-Z]+$") = "AB"
+
+    class Draft(Model):
+        id: str
+        balance: conint(ge=0)
+
+    store.create_collection(Account, primary_key_field="id", enforce_constraints=True)
+    store.create_collection(Draft, primary_key_field="id")
+    account_collection = store.get_collection(Account)
+    draft_collection = store.get_collection(Draft)
+    await account_collection.add_one(Account(id="a", balance=10))
+
+    with pytest.raises(ValueError, match="it should be at least 0"):
+        await account_collection.update_one("a", data={"balance": -1})
+    with pytest.raises(ValueError, match="it should have at most 4 characters"):
+        await account_collection.update_one("a", data={"code": "ABCDE"})
+    with pytest.raises(ValueError, match="it should match the pattern"):
+        await account_collection.update_one("a", data={"code": "ab"})
+    assert await account_collection.get_one("a") == Account(id="a", balance=10)
+
+    await account_collection.update_one("a", data={"balance": 0, "code": "XYZ"})
+    assert await account_collection.get_one("a") == Account(id="a", balance=0, code="XYZ")
+
+    # collections that do not enforce constraints let invalid partial updates through
+    await draft_collection.add_one(Draft(id="d", balance=10))
+    await draft_collection.update_one("d", data={"balance": -1})
+    assert await draft_collection.get_one_partially("d", fields=["balance"]) == {"balance": -1}
+
+
+@pytest.mark.asyncio
+@pytest.mark.parametrize("store", async_redis_store_fixture)
+async def test_deadline_async(store):
+    """Calls made once the deadline set in orredis.deadline has passed raise a DeadlineExceededError"""
+    book_collection = store.get_collection(Book)
+    await book_collection.add_many(books)
+
+    token = deadline.set(time.time() + 60)
+    try:
+        assert await book_collection.get_one(books[0].title) == books[0]
+    finally:
+        deadline.reset(token)
+
+    for passed_deadline in [time.time() - 1, datetime.now(tz=timezone.utc) - timedelta(seconds=1)]:
+        token = deadline.set(passed_deadline)
+        try:
+            with pytest.raises(DeadlineExceededError) as exc_info:
+                await book_collection.get_one(books[0].title)
+        finally:
+            deadline.reset(token)
+        assert isinstance(exc_info.value, TimeoutError)
+
+    assert await book_collection.get_one(books[0].title) == books[0]
+
+
+@pytest.mark.asyncio
+@pytest.mark.parametrize("store", async_redis_store_fixture)
+async def test_vector_fields_async(store):
+    """Vector fields are read back as lists of floats and their records are found by knn() if RediSearch is loaded"""
+
+    class Document(Model):
+        name: str
+        embedding: Vector[3]
+        extra: Optional[Vector[3]] = None
+
+    store.create_collection(Document, primary_key_field="name")
+    document_collection = store.get_collection(Document)
+    documents = [
+        Document(name="north", embedding=[0, 1, 0]),
+        Document(name="north-east", embedding=[0.7, 0.7, 0]),
+        Document(name="east", embedding=[1, 0, 0.1], extra=[1, 2, 3]),
+    ]
+    await document_collection.add_many(documents)
+
+    assert await document_collection.get_one("east") == documents[2]
+    north = await document_collection.get_one("north")
+    assert north.embedding == [0.0, 1.0, 0.0] and isinstance(north.embedding[0], float)
+    assert store.describe()["collections"][Document.__qualname__]["fields"]["embedding"] == "vector[3]"
+    with pytest.raises(ValueError):
+        Document(name="south", embedding=[0, -1])
+    with pytest.raises(ValueError):
+        await document_collection.knn("name", [0, 1, 0])
+    with pytest.raises(ValueError):
+        await document_collection.knn("embedding", [0, 1])
+
+    try:
+        nearest = await document_collection.knn("embedding", [0.1, 1, 0], k=2)
+    except ConnectionError as exc:
+        assert "RediSearch" in str(exc)
+    else:
+        assert nearest == documents[:2]
+
+
+@pytest.mark.asyncio
+@pytest.mark.parametrize("store", async_redis_store_fixture)
+async def test_profile_async(store):
+    """With profile=True, calls also return the time they spent in each phase, in milliseconds"""
+    book_collection = store.get_collection(Book)
+    phases = {"serialize_ms", "network_ms", "parse_ms", "construct_ms", "total_ms"}
+
+    result, profile = await book_collection.add_many(books, profile=True)
+    assert result == [book.title for book in books]
+    assert set(profile) == phases
+    assert profile["serialize_ms"] > 0 and profile["network_ms"] > 0
+    assert await book_collection.add_one(books[0]) == books[0].title
+
+    book, profile = await book_collection.get_one(books[0].title, profile=True)
+    assert book == books[0]
+    assert profile["network_ms"] > 0 and profile["parse_ms"] > 0 and profile["construct_ms"] > 0
+    assert sum(profile[phase] for phase in phases - {"total_ms"}) <= profile["total_ms"]
+
+    got, profile = await book_collection.get_many([book.title for book in books[:2]], profile=True)
+    assert got == books[:2]
+    assert set(profile) == phases
+
+    got, profile = await book_collection.get_all(filter={"in_stock": False}, profile=True)
+    assert sorted(book.title for book in got) == sorted(book.title for book in books if not book.in_stock)
+    assert profile["construct_ms"] > 0
+    assert await book_collection.get_many([books[0].title]) == [books[0]]
+
+
+@pytest.mark.asyncio
+@pytest.mark.parametrize("store", async_redis_store_fixture)
+async def test_describe_async(store):
+    """describe() describes the collections, views and links of the async store, in the order of their names"""
+
+    class Tag(Model):
+        name: str
+
+    class Post(Model):
+        id: str
+        title: str
+        tags: List[str]
+
+    store.create_collection(Tag, primary_key_field="name")
+    store.create_collection(Post, primary_key_field="id", indexed_fields=["title"])
+    store.create_link("post_tags", left_collection=Post, right_collection=Tag)
+    store.create_view("books_in_stock", source_collection=Book, projection=["title", "rating"],
+                      filter={"in_stock": True})
+
+    description = store.describe()
+    book = description["collections"]["Book"]
+    assert book["primary_key_field"] == "title"
+    assert book["fields"] == {
+        "title": "str",
+        "author": "Author",
+        "rating": "float",
+        "published_on": "date",
+        "last_updated": "datetime",
+        "tags": "list[str]",
+        "in_stock": "bool",
+    }
+    assert book["relationships"] == [{"field": "author", "kind": "nested", "collection": "Author"}]
+    assert book["version"] is None
+    assert book["namespace"] is None
+    assert description["collections"]["Author"]["fields"] == {"name": "str", "active_years": "tuple[int, int]"}
+
+    post = description["collections"][Post.__qualname__]
+    assert post["primary_key_field"] == "id"
+    assert post["indexed_fields"] == ["title"]
+    assert post["unique_fields"] == []
+    assert post["relationships"] == []
+
+    assert description["views"] == {
+        "books_in_stock": {"source": "Book", "projection": ["title", "rating"], "filter": {"in_stock": "true"}}
+    }
+    assert description["links"] == {"post_tags": {"left": Post.__qualname__, "right": Tag.__qualname__}}
+
+@pytest.mark.asyncio
+@pytest.mark.parametrize("store", async_redis_store_fixture)
+async def test_browse_async(store):
+    """
+    browse() gets the pages of the records of a collection with only the given fields, the total number
+    of records and the cursor of the next page, until all the records have been browsed
+    """
+    book_collection = store.get_collection(Book)
+    page = await store.browse(Book)
+    assert page == {"records": [], "total": 0, "next_cursor": None}
+
+    await book_collection.add_many(books)
+    browsed = []
+    cursor = None
+    for _ in range(len(books)):
+        page = await store.browse(Book, cursor=cursor, limit=3, fields=["title", "author.name"])
+        assert page["total"] == len(books)
+        assert len(page["records"]) <= 3
+        browsed.extend(page["records"])
+        cursor = page["next_cursor"]
+        if cursor is None:
+            break
+
+    expected = [{"title": book.title, "author": {"name": book.author.name}} for book in books]
+    assert sorted(browsed, key=lambda x: x["title"]) == sorted(expected, key=lambda x: x["title"])
+
+    page = await store.browse(Book, limit=len(books))
+    assert page["next_cursor"] is None
+    assert sorted(record["rating"] for record in page["records"]) == sorted(book.rating for book in books)
+
+    with pytest.raises(KeyError):
+        await store.browse(Book, fields=["foo"])
+
+    with pytest.raises(ValueError):
+        await store.browse(Book, limit=0)
+
+    with pytest.raises(ValueError):
+        await store.browse(Book, cursor="foo")
+
+
+@pytest.mark.asyncio
+@pytest.mark.parametrize("store", async_redis_store_fixture)
+async def test_plan_migration_async(store):
+    """plan_migration() reports the changes to the schema of a collection in an async store since its snapshot was saved"""
+    plan = await store.plan_migration(Author)
+    assert plan["has_snapshot"] is False
+    assert plan["added"]["name"] == "str"
+
+    await store.save_schema_snapshot(Author)
+    assert await store.plan_migration(Author) == {
+        "collection": "Author",
+        "has_snapshot": True,
+        "added": {},
+        "removed": {},
+        "type_changed": {},
+    }
+    await store.clear()
+
+
+@pytest.mark.asyncio
+@pytest.mark.parametrize("store", async_redis_store_fixture)
+async def test_change_consumer(store):
+    """
+    ChangeConsumer passes the changes to the records of a collection that tracks changes to the sink in order,
+    resuming from the last event it handled
+    """
+
+    class Task(Model):
+        name: str
+        done: bool = False
+
+    store.create_collection(Task, primary_key_field="name", track_changes=True)
+    task_collection = store.get_collection(Task)
+    events = []
+
+    async def sink(event):
+        events.append(event)
+
+    consumer = ChangeConsumer(task_collection, sink=sink, batch_size=2)
+    await task_collection.add_many([Task(name="foo"), Task(name="bar")])
+    await task_collection.update_one("foo", data={"done": True})
+
+    assert await consumer.consume_once() == 3
+    assert [(e["op"], e["id"], e["before"], e["after"]) for e in events] == [
+        ("insert", "foo", {}, {"name": "foo", "done": False}),
+        ("insert", "bar", {}, {"name": "bar", "done": False}),
+        ("update", "foo", {"name": "foo", "done": False}, {"name": "foo", "done": True}),
+    ]
+    assert consumer.last_event_id == events[-1]["event_id"]
+
+    task = asyncio.ensure_future(consumer.run())
+    await task_collection.delete_many(["bar"])
+    await asyncio.sleep(0.3)
+    task.cancel()
+
+    assert [(e["op"], e["id"]) for e in events[3:]] == [("delete", "bar")]
+
+    replay_events = []
+
+    async def replay_sink(event):
+        replay_events.append(event)
+
+    assert await ChangeConsumer(task_collection, sink=replay_sink).consume_once() == 4
+    assert replay_events == events
+
+
+@pytest.mark.asyncio
+@pytest.mark.parametrize("store", async_redis_store_fixture)
+async def test_create_view_async(store):
+    """
+    create_view() creates a view of the records that match the filter, with only the projected fields,
+    that is kept up to date on every write to the collection
+    """
+    store.create_view("books_in_stock", source_collection=Book, projection=["title", "rating"],
+                      filter={"in_stock": True})
+    book_collection = store.get_collection(Book)
+    await book_collection.add_many(books)
+    view = store.get_view("books_in_stock")
+
+    expected = [{"title": book.title, "rating": book.rating} for book in books if book.in_stock]
+    assert sorted(await view.get_all(), key=lambda x: x["title"]) == sorted(expected, key=lambda x: x["title"])
+    assert await view.get_one(books[0].title) is None
+    assert await view.get_many([books[0].title, books[1].title]) == [
+        {"title": books[1].title, "rating": books[1].rating}]
+
+    await book_collection.update_one(books[1].title, data={"in_stock": False})
+    await book_collection.update_one(books[0].title, data={"in_stock": True})
+    assert await view.get_one(books[1].title) is None
+    assert await view.get_one(books[0].title) == {"title": books[0].title, "rating": books[0].rating}
+
+    await book_collection.delete_many([books[0].title])
+    assert await view.get_one(books[0].title) is None
+    assert await view.rebuild() == 1
+
+
+@pytest.mark.asyncio
+@pytest.mark.parametrize("store", async_redis_store_fixture)
+async def test_create_link_async(store):
+    """
+    create_link() creates a many-to-many link whose records are found from either side,
+    hydrated into the models of their collections
+    """
+
+    class Student(Model):
+        id: str
+        name: str
+
+    class Course(Model):
+        code: str
+        title: str
+
+    store.create_collection(Student, primary_key_field="id")
+    store.create_collection(Course, primary_key_field="code")
+    store.create_link("enrollments", left_collection=Student, right_collection=Course)
+    student_collection = store.get_collection(Student)
+    course_collection = store.get_collection(Course)
+    enrollments = store.get_link("enrollments")
+
+    students = [Student(id="s1", name="Ann"), Student(id="s2", name="Bob")]
+    courses = [Course(code="c1", title="Algebra"), Course(code="c2", title="Biology")]
+    await student_collection.add_many(students)
+    await course_collection.add_many(courses)
+
+    await enrollments.link("s1", "c1")
+    await enrollments.link("s1", "c2")
+    await enrollments.link("s2", "c1")
+    await enrollments.link("s2", "c1")
+
+    assert sorted(await enrollments.right_of("s1"), key=lambda x: x.code) == courses
+    assert await enrollments.right_of("s2") == [courses[0]]
+    assert sorted(await enrollments.left_of("c1"), key=lambda x: x.id) == students
+    assert await enrollments.left_of("c3") == []
+
+    await enrollments.unlink("s1", "c1")
+    await enrollments.unlink("s1", "c3")
+    assert await enrollments.right_of("s1") == [courses[1]]
+    assert await enrollments.left_of("c1") == [students[1]]
+
+    # deleted records are skipped
+    await course_collection.delete_many(["c2"])
+    assert await enrollments.right_of("s1") == []
+
+    with pytest.raises(ValueError):
+        store.create_link("enrollments", left_collection=Student, right_collection=Course)
+    with pytest.raises(KeyError):
+        store.get_link("memberships")
+
+
+@pytest.mark.asyncio
+@pytest.mark.parametrize("store", async_redis_store_fixture)
+async def test_counter_fields_async(store):
+    """
+    Counter fields are stored in their own keys, can be incremented atomically and are merged into records on read
+    """
+
+    class Post(Model):
+        slug: str
+        views: int = Field(0, counter=True)
+        rating: float = Field(0.0, counter=True)
+
+    store.create_collection(Post, primary_key_field="slug")
+    post_collection = store.get_collection(Post)
+    await post_collection.add_many([Post(slug="foo", views=4), Post(slug="bar")])
+
+    assert await post_collection.increment("foo", "views") == 5
+    assert await post_collection.increment("foo", "views", 10) == 15
+    assert await post_collection.increment("bar", "rating", 2.5) == 2.5
+    assert await post_collection.get_one("foo") == Post(slug="foo", views=15)
+    assert sorted(await post_collection.get_all(), key=lambda x: x.slug) == [Post(slug="bar", rating=2.5),
+                                                                            Post(slug="foo", views=15)]
+    assert await post_collection.get_one_partially("foo", fields=["views"]) == {"views": 15}
+
+    await post_collection.update_one("foo", data={"views": 1})
+    assert await post_collection.get_one("foo") == Post(slug="foo", views=1)
+
+    await post_collection.delete_many(["foo"])
+    assert await post_collection.get_one("foo") is None
+
+    with pytest.raises(ValueError):
+        await post_collection.increment("foo", "slug")
+
+
+@pytest.mark.asyncio
+@pytest.mark.parametrize("store", async_redis_store_fixture)
+async def test_bloom_filter_async(store):
+    """
+    Collections with bloom filters still find existing records, including those nested in other records,
+    whether or not the RedisBloom module is loaded
+    """
+
+    class Publisher(Model):
+        name: str
+
+    class Magazine(Model):
+        title: str
+        publisher: Publisher
+
+    store.create_collection(Publisher, primary_key_field="name", bloom_filter=True)
+    store.create_collection(Magazine, primary_key_field="title", bloom_filter=True)
+    publisher_collection = store.get_collection(Publisher)
+    magazine_collection = store.get_collection(Magazine)
+    magazine = Magazine(title="Time", publisher=Publisher(name="Time USA"))
+
+    await magazine_collection.add_one(magazine)
+
+    assert await magazine_collection.exists("Time")
+    assert not await magazine_collection.exists("Vogue")
+    assert await magazine_collection.get_one("Time") == magazine
+    assert await magazine_collection.get_one("Vogue") is None
+    assert await publisher_collection.exists("Time USA")
+    assert await publisher_collection.get_one("Time USA") == magazine.publisher
+    assert await publisher_collection.get_one("Conde Nast") is None
+
+
+@pytest.mark.asyncio
+@pytest.mark.parametrize("book_collection", async_book_collection_fixture)
+async def test_approx_distinct_async(book_collection):
+    """
+    approx_distinct() returns the approximate number of distinct values of a field tracked by track_cardinality(),
+    including those of records added before tracking started
+    """
+    await book_collection.add_many(books[:2])
+    await book_collection.track_cardinality("rating")
+    assert await book_collection.approx_distinct("rating") == 2
+
+    await book_collection.add_many(books[2:])
+    await book_collection.add_one(books[0])
+    assert await book_collection.approx_distinct("rating") == 4
+    assert await book_collection.approx_distinct("in_stock") == 0
+
+    with pytest.raises(KeyError):
+        await book_collection.approx_distinct("foo")
+
+    with pytest.raises(ValueError):
+        await book_collection.track_cardinality("author")
+
+
+@pytest.mark.asyncio
+@pytest.mark.parametrize("store", async_redis_store_fixture)
+async def test_approx_distinct_tracked_by_another_store_async(store, redis_server):
+    """
+    The fields tracked by track_cardinality() are kept in redis, so the writes of the collections of other stores,
+    e.g. in other processes, add to their HyperLogLogs too
+    """
+    other_store = AsyncStore(url=f"redis://localhost:{redis_server}/1")
+    other_store.create_collection(Author, primary_key_field="name")
+    other_store.create_collection(Book, primary_key_field="title")
+    await other_store.get_collection(Book).track_cardinality("rating")
+
+    book_collection = store.get_collection(Book)
+    await book_collection.add_many(books[:2])
+    assert await book_collection.approx_distinct("rating") == 2
+
+    await book_collection.update_one(books[0].title, data={"rating": 0.5})
+    assert await book_collection.approx_distinct("rating") == 3
+
+    assert await book_collection.update_where({"title": books[1].title}, {"rating": 1.5}) == 1
+    assert await book_collection.approx_distinct("rating") == 4
+    assert await book_collection.approx_distinct("in_stock") == 0
+
+    # clearing the collection keeps the fields tracked
+    await book_collection.clear()
+    await book_collection.add_many(books[:2])
+    assert await book_collection.approx_distinct("rating") == 2
+
+@pytest.mark.asyncio
+@pytest.mark.parametrize("book_collection", async_book_collection_fixture)
+async def test_distinct_async(book_collection):
+    """distinct() returns the distinct values of a field across the collection, parsed into their python types"""
+    assert await book_collection.distinct("in_stock") == []
+
+    await book_collection.add_many(books)
+    assert sorted(await book_collection.distinct("in_stock")) == [False, True]
+    assert sorted(await book_collection.distinct("rating")) == sorted(book.rating for book in books)
+    assert sorted(await book_collection.distinct("tags")) == [["Classic"], ["Classic", "Romance"]]
+
+    with pytest.raises(KeyError):
+        await book_collection.distinct("foo")
+
+    with pytest.raises(ValueError):
+        await book_collection.distinct("author")
+
+@pytest.mark.asyncio
+@pytest.mark.parametrize("book_collection", async_book_collection_fixture)
+async def test_aggregate_async(book_collection):
+    """aggregate() computes the sum, average, minimum or maximum of the values of a numeric field"""
+    assert await book_collection.aggregate("rating", "sum") == 0
+    assert await book_collection.aggregate("rating", "avg") is None
+    assert await book_collection.aggregate("rating", "max") is None
+
+    await book_collection.add_many(books)
+    ratings = [book.rating for book in books]
+    assert await book_collection.aggregate("rating", "sum") == pytest.approx(sum(ratings))
+    assert await book_collection.aggregate("rating", "avg") == pytest.approx(sum(ratings) / len(ratings))
+    assert await book_collection.aggregate("rating", "min") == min(ratings)
+    assert await book_collection.aggregate("rating", "max") == max(ratings)
+
+    with pytest.raises(KeyError):
+        await book_collection.aggregate("foo", "sum")
+
+    with pytest.raises(ValueError):
+        await book_collection.aggregate("title", "sum")
+
+    with pytest.raises(ValueError):
+        await book_collection.aggregate("rating", "median")
+
+@pytest.mark.asyncio
+@pytest.mark.parametrize("book_collection", async_book_collection_fixture)
+async def test_count_by_async(book_collection):
+    """count_by() counts the records of the collection by the values of a field"""
+    assert await book_collection.count_by("in_stock") == {}
+
+    await book_collection.add_many(books)
+    assert await book_collection.count_by("in_stock") == {
+        True: len([book for book in books if book.in_stock]),
+        False: len([book for book in books if not book.in_stock]),
+    }
+    assert await book_collection.count_by("title") == {book.title: 1 for book in books}
+
+    with pytest.raises(KeyError):
+        await book_collection.count_by("foo")
+
+    with pytest.raises(ValueError):
+        await book_collection.count_by("tags")
+
+
+@pytest.mark.asyncio
+@pytest.mark.parametrize("book_collection", async_book_collection_fixture)
+async def test_count_where_async(book_collection):
+    """count_where() counts the records of the collection whose fields are equal to the given values"""
+    assert await book_collection.count_where(in_stock=True) == 0
+
+    await book_collection.add_many(books)
+    assert await book_collection.count_where(in_stock=False) == len([book for book in books if not book.in_stock])
+    assert await book_collection.count_where(in_stock=True, rating=4.0) == 1
+    assert await book_collection.count_where(title="Hamlet") == 0
+    assert await book_collection.count_where() == len(books)
+
+    with pytest.raises(KeyError):
+        await book_collection.count_where(foo="bar")
+
+    with pytest.raises(ValueError):
+        await book_collection.count_where(author=authors["jane"])
+
+
+@pytest.mark.asyncio
+@pytest.mark.parametrize("book_collection", async_book_collection_fixture)
+async def test_exists_many_async(book_collection):
+    """exists_many() checks whether the records of the given ids exist, returning a boolean per id in order"""
+    assert await book_collection.exists_many([]) == []
+    await book_collection.add_many(books[:2])
+
+    ids = [books[1].title, "Hamlet", books[0].title, books[3].title]
+    assert await book_collection.exists_many(ids) == [True, False, True, False]
+
+
+@pytest.mark.asyncio
+@pytest.mark.parametrize("store", async_redis_store_fixture)
+async def test_get_field_async(store):
+    """get_field() returns the value of one field of a record converted to its type, or None if it is missing"""
+
+    class Post(Model):
+        slug: str
+        views: int = Field(0, counter=True)
+
+    store.create_collection(Post, primary_key_field="slug")
+    book_collection = store.get_collection(Book)
+    await book_collection.add_many(books)
+
+    for book in books:
+        assert await book_collection.get_field(book.title, "in_stock") == book.in_stock
+        assert await book_collection.get_field(book.title, "published_on") == book.published_on
+        assert await book_collection.get_field(book.title, "tags") == book.tags
+    assert await book_collection.get_field("Hamlet", "rating") is None
+
+    with pytest.raises(KeyError):
+        await book_collection.get_field(books[0].title, "foo")
+    with pytest.raises(ValueError):
+        await book_collection.get_field(books[0].title, "author")
+
+    post_collection = store.get_collection(Post)
+    await post_collection.add_one(Post(slug="foo", views=4))
+    await post_collection.increment("foo", "views")
+    assert await post_collection.get_field("foo", "views") == 5
+    assert await post_collection.get_field("bar", "views") is None
+
+
+@pytest.mark.asyncio
+@pytest.mark.parametrize("store", async_redis_store_fixture)
+async def test_case_insensitive_keys_async(store, redis_server):
+    """
+    create_collection(case_insensitive_keys=True) gets the same record for ids differing only in case.
+    Collections of the same model in other stores are not affected
+    """
+
+    class Member(Model):
+        username: str
+        visits: int = Field(0, counter=True)
+
+    store.create_collection(Member, primary_key_field="username", case_insensitive_keys=True)
+    member_collection = store.get_collection(Member)
+    book_collection = store.get_collection(Book)
+    await member_collection.add_one(Member(username="Alice"))
+    await book_collection.add_one(books[0])
+
+    assert await member_collection.get_one("alice") == Member(username="Alice")
+    assert await member_collection.increment("ALICE", "visits") == 1
+    assert await member_collection.get_one("Alice") == Member(username="Alice", visits=1)
+    assert await member_collection.exists("aLiCe")
+    assert await book_collection.get_one(books[0].title.upper()) is None
+
+    await member_collection.add_one(Member(username="ALICE", visits=5))
+    assert await member_collection.get_all() == [Member(username="ALICE", visits=5)]
+
+    await member_collection.delete_many(["alice"])
+    assert await member_collection.get_one("Alice") is None
+
+    other_store = AsyncStore(url=f"redis://localhost:{redis_server}/2")
+    other_store.create_collection(Member, primary_key_field="username")
+    other_member_collection = other_store.get_collection(Member)
+    await other_member_collection.add_one(Member(username="Bob"))
+    await member_collection.add_one(Member(username="Bob"))
+    assert await other_member_collection.get_one("bob") is None
+    assert await other_member_collection.get_one("Bob") == Member(username="Bob")
+    assert await member_collection.get_one("BOB") == Member(username="Bob")
+
+    await other_store.clear()
+
+
+@pytest.mark.asyncio
+@pytest.mark.parametrize("store", async_redis_store_fixture)
+async def test_get_latest_and_get_oldest_async(store):
+    """get_latest() and get_oldest() return the records inserted last and first if insertion order is tracked"""
+
+    class Event(Model):
+        name: str
+        kind: str = "info"
+
+    store.create_collection(Event, primary_key_field="name", track_insertion_order=True)
+    event_collection = store.get_collection(Event)
+    book_collection = store.get_collection(Book)
+    events = [Event(name=f"event-{i}") for i in range(5)]
+    await event_collection.add_one(events[0])
+    await event_collection.add_many(events[1:])
+
+    assert await event_collection.get_latest(3) == events[:-4:-1]
+    assert await event_collection.get_oldest(2) == events[:2]
+    assert await event_collection.get_oldest() == events
+    assert await event_collection.get_latest(0) == []
+
+    await event_collection.update_one("event-0", data={"kind": "error"})
+    assert await event_collection.get_oldest(1) == [events[0].with_changes({"kind": "error"})]
+
+    await event_collection.delete_many(["event-4"])
+    await event_collection.delete_where(name="event-3")
+    assert await event_collection.get_latest(2) == events[1:3][::-1]
+
+    with pytest.raises(ValueError, match="does not track insertion order"):
+        book_collection.get_latest()
+
+
+@pytest.mark.asyncio
+@pytest.mark.orredis_models((Book, "title"), (Author, "name"))
+@pytest.mark.parametrize("title", ["Emma", "Persuasion"])
+async def test_orredis_async_store_fixture(orredis_async_store, title):
+    """orredis_async_store of the pytest plugin has the collections of the orredis_models marker and starts empty"""
+    book_collection = orredis_async_store.get_collection(Book)
+    book = books[3].with_changes({"title": title})
+
+    assert await book_collection.get_all() == []
+    await book_collection.add_one(book)
+    assert await book_collection.get_all() == [book]
+
+
+@pytest.mark.asyncio
+@pytest.mark.parametrize("book_collection", async_book_collection_fixture)
+async def test_find_between_async(book_collection):
+    """
+    find_between() returns the records whose values of a field indexed by create_index() are in a range,
+    including those of records added before the index was created, in the order of their values
+    """
+    await book_collection.add_many(books[:2])
+    assert await book_collection.create_index("rating") == 2
+    assert await book_collection.create_index("published_on") == 2
+    await book_collection.add_many(books[2:])
+
+    assert await book_collection.find_between("rating", 3, 4.5) == [books[2], books[3]]
+    assert await book_collection.find_between("rating", 6, None) == []
+    assert await book_collection.find_between("published_on", date(year=1216, month=1, day=1), None) == books[1:]
+    assert await book_collection.find_between("published_on", None, date(year=1220, month=4, day=4)) == books[:2]
+
+    await book_collection.update_one(books[0].title, data={"rating": 4.5})
+    await book_collection.delete_many([books[1].title])
+    assert await book_collection.find_between("rating", 4.1, None) == [books[0].with_changes({"rating": 4.5})]
+
+    with pytest.raises(ValueError, match="has no index"):
+        await book_collection.find_between("last_updated", None, None)
+    with pytest.raises(ValueError, match="is not a valid bound"):
+        await book_collection.find_between("rating", "high", None)
+    with pytest.raises(ValueError, match="only such fields can be indexed"):
+        await book_collection.create_index("title")
+    with pytest.raises(ValueError):
+        await book_collection.create_index("author")
+
+
+@pytest.mark.asyncio
+@pytest.mark.parametrize("store", async_redis_store_fixture)
+async def test_find_between_index_created_by_another_store_async(store, redis_server):
+    """
+    The indexes created by create_index() are registered in redis, so the writes of the collections of other stores,
+    e.g. in other processes, update them too and those collections can read them
+    """
+    book_collection = store.get_collection(Book)
+    await book_collection.add_many(books[:2])
+
+    other_store = AsyncStore(url=f"redis://localhost:{redis_server}/1")
+    other_store.create_collection(Author, primary_key_field="name")
+    other_store.create_collection(Book, primary_key_field="title")
+    other_book_collection = other_store.get_collection(Book)
+    assert await other_book_collection.create_index("rating") == 2
+
+    await book_collection.add_many(books[2:])
+    await book_collection.update_one(books[0].title, data={"rating": 4.5})
+    await book_collection.delete_many([books[1].title])
+    assert await other_book_collection.find_between("rating", 4.1, None) == [books[0].with_changes({"rating": 4.5})]
+    assert await book_collection.find_between("rating", 3, 4.5) == [
+        books[2],
+        books[3],
+        books[0].with_changes({"rating": 4.5}),
+    ]
+
+
+@pytest.mark.asyncio
+@pytest.mark.parametrize("store", async_redis_store_fixture)
+async def test_get_page_async(store):
+    """
+    get_page() returns the records after the value and id of the last record of the previous page, in the order
+    of their values of an indexed field then of their ids, without shifting when records are inserted before it
+    """
+
+    class Player(Model):
+        name: str
+        score: int
+
+    store.create_collection(Player, primary_key_field="name")
+    player_collection = store.get_collection(Player)
+    players = [Player(name=f"player{i}", score=i % 3) for i in range(8)]
+    await player_collection.add_many(players)
+    assert await player_collection.create_index("score") == 8
+
+    pages = []
+    last = None
+    while True:
+        if last is None:
+            page = await player_collection.get_page("score", 3)
+        else:
+            page = await player_collection.get_page("score", 3, after_value=last.score, after_id=last.name)
+        if not page:
+            break
+        pages.append(page)
+        last = page[-1]
+        # records inserted before the current page do not shift the next pages
+        await player_collection.add_one(Player(name=f"newcomer{len(pages)}", score=-1))
+
+    assert [len(page) for page in pages] == [3, 3, 2]
+    assert [v for page in pages for v in page] == sorted(players, key=lambda v: (v.score, v.name))
+
+    assert await player_collection.get_page("score", 2, after_value=0) == [players[1], players[4]]
+    with pytest.raises(ValueError, match="has no index"):
+        await player_collection.get_page("name")
+    with pytest.raises(ValueError, match="greater than 0"):
+        await player_collection.get_page("score", 0)
+    with pytest.raises(ValueError, match="after_id should be given with after_value"):
+        await player_collection.get_page("score", after_id="player1")
+
+
+@pytest.mark.asyncio
+@pytest.mark.parametrize("store", async_redis_store_fixture)
+async def test_hot_keys_async(store):
+    """
+    hot_keys() returns the most accessed ids of collections created with hot_key_sampling, hottest first
+    """
+
+    class Session(Model):
+        token: str
+
+    with pytest.raises(ValueError, match=r"sample should be greater than 0"):
+        store.create_collection(Session, primary_key_field="token", hot_key_sampling=0)
+
+    store.create_collection(Session, primary_key_field="token", hot_key_sampling=1)
+    session_collection = store.get_collection(Session)
+    await session_collection.add_many([Session(token="foo"), Session(token="bar"), Session(token="baz")])
+
+    for _ in range(5):
+        await session_collection.get_one("foo")
+    await session_collection.get_many(["foo", "bar"])
+    await session_collection.update_one("bar", {"token": "bar"})
+    await session_collection.exists("baz")
+
+    hot_keys = session_collection.hot_keys(top=2)
+    assert [id for id, _ in hot_keys] == ["foo", "bar"]
+    assert hot_keys[0][1] == pytest.approx(6, rel=0.01)
+    assert hot_keys[1][1] == pytest.approx(2, rel=0.01)
+    assert len(session_collection.hot_keys()) == 3
+
+    with pytest.raises(ValueError, match=r"does not track hot keys"):
+        store.get_collection(Book).hot_keys()
+
+
+@pytest.mark.asyncio
+@pytest.mark.parametrize("book_collection", async_book_collection_fixture)
+async def test_memory_usage_async(book_collection):
+    """
+    memory_usage() measures the memory used by a sample of the records, estimating that of the whole collection
+    and listing the largest records sampled
+    """
+    await book_collection.add_many(books)
+    titles = {book.title for book in books}
+
+    usage = await book_collection.memory_usage()
+    assert usage["records"] == len(books)
+    assert usage["sampled"] == len(books)
+    assert usage["sampled_bytes"] > 0
+    assert usage["avg_bytes"] == usage["sampled_bytes"] / len(books)
+    assert usage["estimated_total_bytes"] == usage["sampled_bytes"]
+    assert {item["id"] for item in usage["largest"]} == titles
+    sizes = [item["bytes"] for item in usage["largest"]]
+    assert sizes == sorted(sizes, reverse=True)
+
+    usage = await book_collection.memory_usage(sample=2, top=1)
+    assert usage["records"] == len(books)
+    assert usage["sampled"] == 2
+    assert usage["estimated_total_bytes"] == round(usage["avg_bytes"] * len(books))
+    assert len(usage["largest"]) == 1
+    assert usage["largest"][0]["id"] in titles
+
+    with pytest.raises(ValueError, match=r"sample should be at least 1"):
+        await book_collection.memory_usage(sample=0)
+
+
+@pytest.mark.asyncio
+@pytest.mark.parametrize("store", async_redis_store_fixture)
+async def test_train_compression_async(store, redis_server):
+    """
+    train_compression() trains a dictionary on the string values of the collection, with which the records written
+    after it are compressed. Other stores read and write them once they load it with load_compression()
+    """
+
+    class Review(Model):
+        id: int
+        reviewer: str
+        body: str
+
+    class Score(Model):
+        name: str
+        value: int
+
+    store.create_collection(Review, primary_key_field="id", indexed_fields=["reviewer"])
+    store.create_collection(Score, primary_key_field="name")
+    review_collection = store.get_collection(Review)
+    reviews = [
+        Review(id=i, reviewer=f"reviewer {i % 5}",
+               body=f"Book number {i} was a gripping read, with a plot that kept me turning its pages late at night")
+        for i in range(300)
+    ]
+
+    with pytest.raises(ValueError, match=r"sample should be at least 1"):
+        await review_collection.train_compression(sample=0)
+    with pytest.raises(ValueError, match=r"no string fields that can be compressed"):
+        await store.get_collection(Score).train_compression()
+    with pytest.raises(ValueError, match=r"could not train a compression dictionary"):
+        await review_collection.train_compression()
+
+    await review_collection.add_many(reviews)
+    stats = await review_collection.train_compression(sample=200)
+    assert stats["samples"] == 200
+    assert stats["dictionary_bytes"] > 0
+    assert stats["compressed_bytes"] < stats["sampled_bytes"]
+    assert stats["ratio"] == stats["sampled_bytes"] / stats["compressed_bytes"]
+
+    new_reviews = [review.copy(update={"id": review.id + 300}) for review in reviews[:10]]
+    await review_collection.add_many(new_reviews)
+    assert await review_collection.get_many([str(review.id) for review in new_reviews]) == new_reviews
+    assert sorted(await review_collection.get_all(), key=lambda v: v.id) == reviews + new_reviews
+    assert sorted(await review_collection.get_all(filter={"reviewer": "reviewer 0"}), key=lambda v: v.id) == [
+        review for review in reviews + new_reviews if review.reviewer == "reviewer 0"
+    ]
+    with pytest.raises(ValueError, match=r"compressed fields cannot be filtered on"):
+        await review_collection.get_all(filter={"body": reviews[0].body})
+
+    other_store = AsyncStore(url=f"redis://localhost:{redis_server}/1")
+    other_store.create_collection(Review, primary_key_field="id", indexed_fields=["reviewer"])
+    other_review_collection = other_store.get_collection(Review)
+    assert await other_review_collection.load_compression()
+    await other_review_collection.add_one(reviews[0].copy(update={"id": 1000}))
+    assert await review_collection.get_one("1000") == reviews[0].copy(update={"id": 1000})
+    assert await other_review_collection.get_one("300") == new_reviews[0]
+    assert not await other_store.get_collection(Score).load_compression()
+
+
+@pytest.mark.asyncio
+@pytest.mark.parametrize("store", async_redis_store_fixture)
+async def test_compression_dictionary_lazily_loaded_async(store, redis_server):
+    """
+    A value compressed with a dictionary of its collection that is not loaded is read by the async store after
+    loading the dictionary from redis. Dictionaries are kept by collection, so that of one is not used for another
+    """
+
+    class Review(Model):
+        id: int
+        body: str
+
+    class Critique(Model):
+        id: int
+        body: str
+
+    store.create_collection(Review, primary_key_field="id")
+    store.create_collection(Critique, primary_key_field="id")
+    review_collection = store.get_collection(Review)
+    critique_collection = store.get_collection(Critique)
+    body = "Book number {} was a gripping read, with a plot that kept me turning its pages late at night"
+    reviews = [Review(id=i, body=body.format(i)) for i in range(300)]
+    await review_collection.add_many(reviews)
+    await review_collection.train_compression(sample=200)
+    await review_collection.add_one(reviews[0].copy(update={"id": 300}))
+
+    # the compressed record is copied to the other collection, whose dictionaries are not in redis yet
+    client = redis.Redis(port=int(redis_server), db=1)
+    client.hset("Critique_%&_300", mapping=client.hgetall("Review_%&_300"))
+    with pytest.raises(ValueError, match=r"not among the dictionaries of Critique in redis"):
+        await critique_collection.get_one("300")
+
+    dictionaries = client.hgetall("Review_%&compression_%&_dictionaries")
+    client.hset("Critique_%&compression_%&_dictionaries", mapping=dictionaries)
+    assert await critique_collection.get_one("300") == Critique(id=300, body=reviews[0].body)
+
+
+@pytest.mark.asyncio
+@pytest.mark.parametrize("store", async_redis_store_fixture)
+async def test_run_retention_async(store):
+    """
+    run_retention() applies the retention policies periodically till it is cancelled
+    """
+
+    class Log(Model):
+        message: str
+        created_at: datetime
+
+    now = datetime.now(tz=timezone.utc).replace(microsecond=0)
+    store.create_collection(Log, primary_key_field="message", retention=timedelta(days=30),
+                            retention_field="created_at")
+    log_collection = store.get_collection(Log)
+
+    task = asyncio.ensure_future(store.run_retention(interval=timedelta(milliseconds=10)))
+    await log_collection.add_one(Log(message="old", created_at=now - timedelta(days=31)))
+    await asyncio.sleep(0.1)
+    task.cancel()
+
+    assert await log_collection.get_all() == []
+
+
+@pytest.mark.asyncio
+@pytest.mark.parametrize("store", async_redis_store_fixture)
+async def test_run_retention_with_collections_created_while_running_async(store):
+    """
+    run_retention() applies the retention policies of collections created after it started running
+    """
+
+    class Log(Model):
+        message: str
+        created_at: datetime
+
+    now = datetime.now(tz=timezone.utc).replace(microsecond=0)
+    task = asyncio.ensure_future(store.run_retention(interval=timedelta(milliseconds=10)))
+    await asyncio.sleep(0.02)
+    store.create_collection(Log, primary_key_field="message", retention=timedelta(days=30),
+                            retention_field="created_at")
+    log_collection = store.get_collection(Log)
+
+    await log_collection.add_one(Log(message="old", created_at=now - timedelta(days=31)))
+    await asyncio.sleep(0.1)
+    task.cancel()
+
+    assert await log_collection.get_all() == []
+
+
+@pytest.mark.asyncio
+@pytest.mark.parametrize("store", async_redis_store_fixture)
+async def test_spawn_background_async(store):
+    """
+    spawn_background() runs the awaitable of the factory periodically till it is cancelled with cancel_background()
+    """
+    runs = []
+
+    async def job():
+        runs.append(len(runs))
+
+    store.spawn_background("job", job, timedelta(milliseconds=10))
+    await asyncio.sleep(0.1)
+
+    with pytest.raises(ValueError, match=r"a background task named \"job\" is already running"):
+        store.spawn_background("job", job, 1)
+
+    status = store.background_tasks()
+    assert list(status.keys()) == ["job"]
+    assert status["job"]["running"] is True
+    assert status["job"]["runs"] >= 2
+    assert status["job"]["error"] is None
+
+    assert store.cancel_background("job") is True
+    assert store.cancel_background("job") is False
+    assert store.background_tasks() == {}
+    await asyncio.sleep(0.05)
+    count = len(runs)
+    await asyncio.sleep(0.05)
+    assert len(runs) == count
+
+
+@pytest.mark.asyncio
+@pytest.mark.parametrize("store", async_redis_store_fixture)
+async def test_spawn_background_with_failing_job_async(store):
+    """
+    spawn_background() stops a task whose awaitable raises an error, which background_tasks() reports
+    """
+
+    async def job():
+        raise RuntimeError("boom")
+
+    store.spawn_background("failing", job, 0.01)
+    await asyncio.sleep(0.05)
+
+    assert store.background_tasks() == {"failing": {"running": False, "runs": 0, "error": "boom"}}
+
+    # a task that has stopped can be replaced
+    store.spawn_background("failing", store.apply_retention, 0.01)
+    await asyncio.sleep(0.05)
+    assert store.background_tasks()["failing"]["running"] is True
+    store.cancel_background("failing")
+
+
+@pytest.mark.asyncio
+@pytest.mark.parametrize("store", async_redis_store_fixture)
+async def test_schedule_backup_async(store, tmp_path):
+    """
+    schedule_backup() backs all the collections up periodically in a background task of the store,
+    keeping only the latest backups, with their status got from backup_schedules()
+    """
+    book_collection = store.get_collection(Book)
+    await book_collection.add_many(books)
+    target = str(tmp_path / "backups")
+
+    store.schedule_backup(0.2, target, keep=2)
+    with pytest.raises(ValueError, match=r"is already scheduled"):
+        store.schedule_backup("@daily", target)
+    await asyncio.sleep(1.5)
+    status = store.backup_schedules()[target]
+    assert status["runs"] >= 3
+    assert status["failures"] == 0
+    assert status["last_error"] is None
+    assert len(status["backups"]) == 2
+    assert store.background_tasks()[f"backup:{target}"]["running"] is True
+    assert store.cancel_backup_schedule(target)
+    assert not store.cancel_backup_schedule(target)
+    assert f"backup:{target}" not in store.background_tasks()
+    await asyncio.sleep(0.5)
+
+    backups = sorted(os.listdir(target))
+    assert len(backups) == 2
+    assert os.path.getsize(os.path.join(target, backups[-1], "Book.export")) > 0
+    assert os.path.exists(os.path.join(target, backups[-1], "Author.export"))
+
+    # cancelling the background task of a schedule cancels the schedule
+    store.schedule_backup("@every 1h", target, keep=3)
+    assert store.backup_schedules()[target]["interval_secs"] == 3600
+    assert store.cancel_background(f"backup:{target}")
+    assert store.backup_schedules() == {}
+
+    with pytest.raises(ValueError, match=r"keep should be at least 1"):
+        store.schedule_backup(60, target, keep=0)
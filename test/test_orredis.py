@@ -1,10 +1,70 @@
 """Tests for the orredis"""
-from datetime import datetime, timezone, timedelta
+import io
+import os
+import socket
+import threading
+import time
+from datetime import date, datetime, timezone, timedelta
+from typing import Dict, List, Optional, Tuple
 
 import pytest
+import redis
+import redislite
+from pydantic import Field, conint, constr
 
-from orredis import Model
-from test.conftest import Book, redis_store_fixture, books, authors, Author
+from orredis import (CircuitOpenError, DeadlineExceededError, Model, QuotaExceededError, Reference, Store,
+                     UniqueViolationError, Vector, deadline, register_backup_sink)
+from test.conftest import Book, redis_store_fixture, book_collection_fixture, books, authors, Author, id_generator_resolutions
+
+
+class Address(Model):
+    street: str = Field(primary_key=True)
+    number: int
+
+
+class Publisher(Model):
+    name: str
+    address: Address
+
+    class Config:
+        schema_extra = {"primary_key_field": "name"}
+
+
+class Shelf(Model):
+    code: str
+    publisher: Publisher
+
+
+class Library(Model):
+    name: str
+
+
+class Employee(Model):
+    name: str
+    manager: Optional["Employee"] = None
+
+
+class Team(Model):
+    name: str
+    captain: Optional["Player"] = None
+
+
+class Player(Model):
+    name: str = Field(primary_key=True)
+    team: Optional[Team] = None
+
+
+class Owner(Model):
+    name: str
+    pet: "Pet"
+
+
+class Pet(Model):
+    name: str = Field(primary_key=True)
+
+
+Employee.update_forward_refs()
+Team.update_forward_refs()
 
 
 def test_model_eq():
@@ -66,6 +126,19 @@ def test_create_collection_without_primary_key(redis_store):
         redis_store.create_collection(ModelWithoutPrimaryKey, primary_key_field=3)
 
 
+def test_create_collection_with_unknown_option(redis_store):
+    """Throws a TypeError when create_collection() is given an option it does not have, or one positionally"""
+
+    class Magazine(Model):
+        title: str
+
+    with pytest.raises(TypeError, match=r"unexpected keyword argument \"index_fields\""):
+        redis_store.create_collection(Magazine, primary_key_field="title", index_fields=["title"])
+
+    with pytest.raises(TypeError):
+        redis_store.create_collection(Magazine, "title", "uuid4")
+
+
 @pytest.mark.parametrize("store", redis_store_fixture)
 def test_store_clear(store):
     """Clears all the keys in the redis store"""
@@ -158,6 +231,898 @@ def test_get_all(store):
     assert sorted_books == sorted_response
 
 
+@pytest.mark.parametrize("store", redis_store_fixture)
+def test_ids(store):
+    """ids() returns the primary keys of all the records in the collection without their key prefix"""
+    book_collection = store.get_collection(Book)
+    assert book_collection.ids() == []
+
+    book_collection.add_many(books)
+    assert sorted(book_collection.ids()) == sorted(book.title for book in books)
+
+    book_collection.delete_many([books[0].title])
+    assert sorted(book_collection.ids()) == sorted(book.title for book in books[1:])
+
+@pytest.mark.parametrize("store", redis_store_fixture)
+def test_sample(store):
+    """sample() returns the given number of records of the collection picked at random"""
+    book_collection = store.get_collection(Book)
+    assert book_collection.sample(2) == []
+
+    book_collection.add_many(books)
+    sampled = book_collection.sample(2)
+    assert len(sampled) == 2
+    assert len({book.title for book in sampled}) == 2
+    assert all(book in books for book in sampled)
+
+    sampled = book_collection.sample(len(books) + 1)
+    assert sorted(sampled, key=lambda x: x.title) == sorted(books, key=lambda x: x.title)
+    assert book_collection.sample(0) == []
+
+
+@pytest.mark.parametrize("store", redis_store_fixture)
+def test_resolve(store):
+    """resolve() gets the record that a Reference field refers to, which only holds its primary key"""
+
+    class Review(Model):
+        id: str
+        author: Reference[Author]
+        editor: Optional[Reference[Author]] = None
+
+    store.create_collection(Review, primary_key_field="id")
+    review_collection = store.get_collection(Review)
+    author_collection = store.get_collection(Author)
+    author_collection.add_many(list(authors.values()))
+    review_collection.add_one(Review(id="1", author=authors["jane"].name))
+
+    review = review_collection.get_one("1")
+    assert review.author == authors["jane"].name
+    assert isinstance(review.author, Reference[Author])
+    assert author_collection.resolve(review.author) == authors["jane"]
+    assert author_collection.resolve(review.editor) is None
+    assert author_collection.resolve(authors["charles"].name) == authors["charles"]
+    assert author_collection.resolve("Unknown Author") is None
+
+    with pytest.raises(ValueError, match="is a reference to a record of Author"):
+        store.get_collection(Book).resolve(review.author)
+    with pytest.raises(ValueError, match="a reference should be the primary key of the record"):
+        Review(id="2", author=authors["jane"])
+
+
+@pytest.mark.parametrize("store", redis_store_fixture)
+def test_children_of(store):
+    """children_of() gets the records of a child collection whose foreign key holds the id of a parent record"""
+
+    class Customer(Model):
+        id: str
+        name: str
+
+    class Order(Model):
+        id: str
+        customer_id: str
+        total: float
+
+    store.create_collection(Customer, primary_key_field="id")
+    store.create_collection(Order, primary_key_field="id", foreign_keys=["customer_id"])
+    customer_collection = store.get_collection(Customer)
+    order_collection = store.get_collection(Order)
+
+    orders = [
+        Order(id="o1", customer_id="c1", total=10.0),
+        Order(id="o2", customer_id="c1", total=25.5),
+        Order(id="o3", customer_id="c2", total=7.0),
+    ]
+    customer_collection.add_many([Customer(id="c1", name="Ann"), Customer(id="c2", name="Bob")])
+    order_collection.add_many(orders)
+
+    def by_id(records):
+        return sorted(records, key=lambda x: x.id)
+
+    assert by_id(customer_collection.children_of("c1", order_collection, "customer_id")) == orders[:2]
+    assert customer_collection.children_of("c2", order_collection, "customer_id") == orders[2:]
+    assert customer_collection.children_of("c3", order_collection, "customer_id") == []
+    # the reads are recorded in the metrics of the child collection, whose records they are
+    metrics = store.metrics()
+    assert metrics["Order"]["children_of"] == {"calls": 3, "records": 3}
+    assert "children_of" not in metrics["Customer"]
+
+    # the index follows the changes of the foreign key and deletions
+    order_collection.update_one("o1", data={"customer_id": "c2"})
+    order_collection.delete_many(["o3"])
+    assert customer_collection.children_of("c1", order_collection, "customer_id") == [orders[1]]
+    assert customer_collection.children_of("c2", order_collection, "customer_id") == [
+        orders[0].with_changes({"customer_id": "c2"})]
+
+    with pytest.raises(ValueError, match="is not a foreign key of the collection"):
+        customer_collection.children_of("c1", order_collection, "total")
+
+
+@pytest.mark.parametrize("store", redis_store_fixture)
+def test_create_collection_with_invalid_foreign_keys(store):
+    """create_collection() rejects foreign keys that are not non-nested fields of the model"""
+    with pytest.raises(KeyError):
+        store.create_collection(Book, primary_key_field="title", foreign_keys=["publisher_id"])
+    with pytest.raises(ValueError, match="nested field"):
+        store.create_collection(Book, primary_key_field="title", foreign_keys=["author"])
+
+
+@pytest.mark.parametrize("store", redis_store_fixture)
+def test_find_by(store):
+    """find_by() gets the records whose indexed field holds the given value"""
+
+    class User(Model):
+        id: str
+        email: str
+        is_active: bool
+        team_id: str
+
+    store.create_collection(User, primary_key_field="id", foreign_keys=["team_id"],
+                            indexed_fields=["email", "is_active"])
+    user_collection = store.get_collection(User)
+
+    users = [
+        User(id="u1", email="ann@example.com", is_active=True, team_id="t1"),
+        User(id="u2", email="bob@example.com", is_active=False, team_id="t1"),
+        User(id="u3", email="cid@example.com", is_active=True, team_id="t2"),
+    ]
+    user_collection.add_many(users)
+
+    def by_id(records):
+        return sorted(records, key=lambda x: x.id)
+
+    assert user_collection.find_by("email", "bob@example.com") == [users[1]]
+    assert by_id(user_collection.find_by("is_active", True)) == [users[0], users[2]]
+    assert by_id(user_collection.find_by("team_id", "t1")) == users[:2]
+    assert user_collection.find_by("email", "dan@example.com") == []
+
+    # the index follows updates and deletions
+    user_collection.update_one("u2", data={"is_active": True})
+    user_collection.delete_many(["u3"])
+    assert user_collection.find_by("is_active", False) == []
+    assert by_id(user_collection.find_by("is_active", True)) == [
+        users[0], users[1].with_changes({"is_active": True})]
+    assert user_collection.find_by("email", "cid@example.com") == []
+
+    with pytest.raises(ValueError, match="is not indexed in the collection"):
+        user_collection.find_by("id", "u1")
+
+
+@pytest.mark.parametrize("store", redis_store_fixture)
+def test_find_by_index(store):
+    """find_by_index() gets the records whose fields in a compound index hold the given values"""
+
+    class Shop(Model):
+        id: str
+        name: str
+        country: str
+        city: str
+
+    store.create_collection(Shop, primary_key_field="id", compound_indexes=[("country", "city")])
+    shop_collection = store.get_collection(Shop)
+
+    shops = [
+        Shop(id="s1", name="Mango", country="UG", city="Kampala"),
+        Shop(id="s2", name="Jackfruit", country="UG", city="Kampala"),
+        Shop(id="s3", name="Avocado", country="UG", city="Entebbe"),
+        Shop(id="s4", name="Banana", country="KE", city="Kampala"),
+    ]
+    shop_collection.add_many(shops)
+
+    def by_id(records):
+        return sorted(records, key=lambda x: x.id)
+
+    assert by_id(shop_collection.find_by_index(("country", "city"), ("UG", "Kampala"))) == shops[:2]
+    assert shop_collection.find_by_index(("city", "country"), ("Kampala", "KE")) == [shops[3]]
+    assert shop_collection.find_by_index(("country", "city"), ("KE", "Nairobi")) == []
+
+    # updating one of the fields moves the record in the index
+    shop_collection.update_one("s2", data={"city": "Entebbe"})
+    shop_collection.delete_many(["s3"])
+    assert shop_collection.find_by_index(("country", "city"), ("UG", "Kampala")) == [shops[0]]
+    assert shop_collection.find_by_index(("country", "city"), ("UG", "Entebbe")) == [
+        shops[1].with_changes({"city": "Entebbe"})]
+
+    with pytest.raises(ValueError, match="is not a compound index of the collection"):
+        shop_collection.find_by_index(("country", "name"), ("UG", "Mango"))
+    with pytest.raises(ValueError):
+        shop_collection.find_by_index(("country", "city"), ("UG",))
+
+
+@pytest.mark.parametrize("store", redis_store_fixture)
+def test_unique_fields(store):
+    """
+    Inserts and updates that would give a unique field a value another record has raise a UniqueViolationError,
+    values being freed when records change them or are deleted
+    """
+
+    class User(Model):
+        id: str
+        email: str
+        name: str
+
+    store.create_collection(User, primary_key_field="id", unique_fields=["email"])
+    user_collection = store.get_collection(User)
+
+    user_collection.add_one(User(id="u1", email="ann@example.com", name="Ann"))
+    with pytest.raises(UniqueViolationError):
+        user_collection.add_one(User(id="u2", email="ann@example.com", name="Annie"))
+    assert user_collection.get_one("u2") is None
+
+    # a record keeps its own values
+    user_collection.add_one(User(id="u1", email="ann@example.com", name="Ann B."))
+
+    # duplicates within the same batch are rejected, and nothing is inserted
+    with pytest.raises(UniqueViolationError):
+        user_collection.add_many([User(id="u3", email="cid@example.com", name="Cid"),
+                                  User(id="u4", email="cid@example.com", name="Cy")])
+    assert user_collection.get_many(["u3", "u4"]) == []
+
+    user_collection.update_one("u1", data={"email": "ann.b@example.com"})
+    user_collection.add_one(User(id="u2", email="ann@example.com", name="Annie"))
+    with pytest.raises(UniqueViolationError):
+        user_collection.update_one("u2", data={"email": "ann.b@example.com"})
+
+    user_collection.delete_many(["u1"])
+    user_collection.add_one(User(id="u5", email="ann.b@example.com", name="Anna"))
+    assert user_collection.get_one("u5") == User(id="u5", email="ann.b@example.com", name="Anna")
+
+
+@pytest.mark.parametrize("store", redis_store_fixture)
+def test_unique_fields_with_quota(store, redis_server):
+    """
+    An insert aborted because another record has one of its unique values or because of the quota of its namespace
+    leaves neither reserved values nor usage of the quota behind
+    """
+
+    class User(Model):
+        id: str
+        email: str
+
+    store.create_collection(User, primary_key_field="id", unique_fields=["email"], namespace="team_a")
+    store.set_quota("team_a", max_records=2)
+    user_collection = store.get_collection(User)
+    user_collection.add_one(User(id="u1", email="ann@example.com"))
+    quota = store.get_quota("team_a")
+
+    with pytest.raises(UniqueViolationError, match=r'already taken by the record "u1" of User'):
+        user_collection.add_one(User(id="u2", email="ann@example.com"))
+    assert store.get_quota("team_a") == quota
+
+    user_collection.add_one(User(id="u3", email="bob@example.com"))
+    with pytest.raises(QuotaExceededError):
+        user_collection.add_one(User(id="u4", email="cid@example.com"))
+    store.set_quota("team_a", max_records=3)
+    user_collection.add_one(User(id="u5", email="cid@example.com"))
+    assert user_collection.get_many(["u2", "u4"]) == []
+    assert store.get_quota("team_a")["records"] == 3
+
+    client = redis.Redis(port=int(redis_server), db=1)
+    assert client.keys("User_%&guard_%&_*") == []
+    assert client.keys("User_%&undo_%&_*") == []
+
+
+@pytest.mark.parametrize("store", redis_store_fixture)
+def test_index_normalizers(store):
+    """index_normalizers normalize the values of unique and indexed fields before they are indexed or looked up"""
+
+    class Customer(Model):
+        id: str
+        email: str
+        city: str
+
+    with pytest.raises(ValueError, match="neither indexed nor unique"):
+        store.create_collection(Customer, primary_key_field="id", index_normalizers={"city": "lower"})
+    with pytest.raises(ValueError, match="unknown normalizer"):
+        store.create_collection(Customer, primary_key_field="id", unique_fields=["email"],
+                                index_normalizers={"email": "upper"})
+
+    store.create_collection(Customer, primary_key_field="id", unique_fields=["email"], indexed_fields=["city"],
+                            index_normalizers={"email": "strip, lower",
+                                               "city": "return (string.gsub(string.lower(value), '[%s-]', ''))"})
+    customer_collection = store.get_collection(Customer)
+
+    ann = Customer(id="c1", email="Ann@X.com ", city="New York")
+    customer_collection.add_one(ann, ttl=60)
+    with pytest.raises(UniqueViolationError):
+        customer_collection.add_one(Customer(id="c2", email="ann@x.com", city="Boston"))
+    assert customer_collection.get_one("c1") == ann
+    assert customer_collection.find_by("city", "new-york") == [ann]
+
+    # a record keeps its value when it changes only by what is normalized
+    customer_collection.update_one("c1", data={"email": "ANN@x.com", "city": "NEWYORK"})
+    customer_collection.update_one("c1", data={"email": "ann@y.com"})
+    customer_collection.add_one(Customer(id="c2", email="ann@x.com", city="Boston"))
+    assert customer_collection.find_by("city", "New York") == [ann.with_changes({"email": "ann@y.com",
+                                                                                 "city": "NEWYORK"})]
+
+
+@pytest.mark.parametrize("store", redis_store_fixture)
+def test_update_many(store):
+    """update_many() updates each of the records of the given ids with its own changes"""
+    book_collection = store.get_collection(Book)
+    author_collection = store.get_collection(Author)
+    book_collection.add_many(books)
+    new_author = Author(name="John Doe", active_years=(2000, 2009))
+
+    book_collection.update_many({
+        books[0].title: {"author": new_author, "in_stock": not books[0].in_stock},
+        books[1].title: {"rating": 1.5},
+    })
+
+    assert book_collection.get_one(books[0].title) == books[0].with_changes({"author": new_author,
+                                                                             "in_stock": not books[0].in_stock})
+    assert book_collection.get_one(books[1].title) == books[1].with_changes({"rating": 1.5})
+    assert book_collection.get_one(books[2].title) == books[2]
+    assert author_collection.get_one(new_author.name) == new_author
+
+    book_collection.update_many({})
+
+
+@pytest.mark.parametrize("store", redis_store_fixture)
+def test_indexed_list_fields(store):
+    """indexed_list_fields index each element of list fields so that find() gets the records holding an element"""
+
+    class Post(Model):
+        id: str
+        author: str
+        tags: List[str]
+
+    with pytest.raises(ValueError, match="only lists of non-nested items"):
+        store.create_collection(Post, primary_key_field="id", indexed_list_fields=["author"])
+    with pytest.raises(ValueError, match="indexed either as a whole or by its elements"):
+        store.create_collection(Post, primary_key_field="id", indexed_fields=["tags"], indexed_list_fields=["tags"])
+
+    store.create_collection(Post, primary_key_field="id", indexed_fields=["author"], indexed_list_fields=["tags"])
+    post_collection = store.get_collection(Post)
+    posts = [Post(id="p1", author="jane", tags=["rust", "python"]),
+             Post(id="p2", author="john", tags=["rust", "it's, fine"]),
+             Post(id="p3", author="jane", tags=[])]
+    post_collection.add_many(posts)
+
+    assert sorted(post_collection.find(tags__contains="rust"), key=lambda x: x.id) == posts[:2]
+    assert post_collection.find(tags__contains="it's, fine") == [posts[1]]
+    assert post_collection.find(tags__contains="rust", author="jane") == [posts[0]]
+    assert post_collection.find(tags__contains="go") == []
+
+    # the record is moved from the indexes of the elements it no longer has to those it now has
+    post_collection.update_one("p1", data={"tags": ["go"]})
+    assert post_collection.find(tags__contains="rust") == [posts[1]]
+    assert post_collection.find(tags__contains="go") == [posts[0].with_changes({"tags": ["go"]})]
+
+    post_collection.delete_many(["p1"])
+    assert post_collection.find(tags__contains="go") == []
+
+    with pytest.raises(ValueError, match="at least one lookup"):
+        post_collection.find()
+    with pytest.raises(ValueError, match="not an indexed list field"):
+        post_collection.find(author__contains="jane")
+
+
+@pytest.mark.parametrize("store", redis_store_fixture)
+def test_add_one_if_not_exists(store):
+    """add_one_if_not_exists() adds a record only if no record has its primary key, returning whether it did"""
+    book_collection = store.get_collection(Book)
+    book_collection.add_one(books[0])
+
+    assert not book_collection.add_one_if_not_exists(books[0].with_changes({"rating": 1.5}))
+    assert book_collection.get_one(books[0].title) == books[0]
+
+    assert book_collection.add_one_if_not_exists(books[1])
+    assert book_collection.get_one(books[1].title) == books[1]
+
+    # of many concurrent calls for the same primary key, only one adds the record
+    results = []
+    threads = [threading.Thread(target=lambda: results.append(book_collection.add_one_if_not_exists(books[2])))
+               for _ in range(10)]
+    for thread in threads:
+        thread.start()
+    for thread in threads:
+        thread.join()
+    assert sorted(results) == [False] * 9 + [True]
+    assert book_collection.get_one(books[2].title) == books[2]
+
+
+@pytest.mark.parametrize("store", redis_store_fixture)
+def test_find_isnull(store, redis_server):
+    """find(field__isnull=True) gets the records missing the field or whose value is None, once it has a null index"""
+
+    class Member(Model):
+        name: str
+        email: Optional[str]
+
+    store.create_collection(Member, primary_key_field="name")
+    member_collection = store.get_collection(Member)
+    legacy_client = redis.Redis(port=int(redis_server), db=1)
+    legacy_client.hset("Member_%&_john", mapping={"name": "john"})
+    member_collection.add_many([Member(name="jane", email="jane@x.com"), Member(name="doe", email=None)])
+
+    with pytest.raises(ValueError):
+        member_collection.find(email__isnull=True)
+
+    assert member_collection.create_null_index("email") == 2
+    assert sorted(v.name for v in member_collection.find(email__isnull=True)) == ["doe", "john"]
+    assert [v.name for v in member_collection.find(email__isnull=False)] == ["jane"]
+
+    # the index is kept up to date on every write
+    member_collection.update_one("john", data={"email": "john@x.com"})
+    member_collection.update_one("jane", data={"email": None})
+    member_collection.add_one(Member(name="paul", email=None))
+    member_collection.delete_many(["doe"])
+    assert sorted(v.name for v in member_collection.find(email__isnull=True)) == ["jane", "paul"]
+    assert [v.name for v in member_collection.find(email__isnull=False)] == ["john"]
+
+    # the index is registered in redis, so the writes of the collections of other stores update it too
+    other_store = Store(url=f"redis://localhost:{redis_server}/1")
+    other_store.create_collection(Member, primary_key_field="name")
+    other_member_collection = other_store.get_collection(Member)
+    other_member_collection.add_one(Member(name="mary", email=None))
+    other_member_collection.update_one("paul", data={"email": "paul@x.com"})
+    assert sorted(v.name for v in member_collection.find(email__isnull=True)) == ["jane", "mary"]
+    assert sorted(v.name for v in other_member_collection.find(email__isnull=False)) == ["john", "paul"]
+
+
+@pytest.mark.parametrize("store", redis_store_fixture)
+def test_find_with_cache_ttl(store):
+    """
+    find(cache_ttl=...) reuses the ids found by an identical call within the ttl, reading the records afresh
+    """
+
+    class Post(Model):
+        id: str
+        author: str
+        likes: int
+
+    store.create_collection(Post, primary_key_field="id", indexed_fields=["author"])
+    post_collection = store.get_collection(Post)
+    posts = [Post(id="p1", author="jane", likes=0), Post(id="p2", author="john", likes=0)]
+    post_collection.add_many(posts)
+
+    assert post_collection.find(cache_ttl=0.5, author="jane") == [posts[0]]
+
+    post_collection.add_one(Post(id="p3", author="jane", likes=0))
+    post_collection.update_one("p1", data={"likes": 5})
+    # the cached ids are used, but the records are read afresh
+    assert post_collection.find(cache_ttl=0.5, author="jane") == [posts[0].with_changes({"likes": 5})]
+    assert sorted(v.id for v in post_collection.find(author="jane")) == ["p1", "p3"]
+    assert post_collection.find(cache_ttl=0.5, author="john") == [posts[1]]
+
+    time.sleep(0.6)
+    assert sorted(v.id for v in post_collection.find(cache_ttl=0.5, author="jane")) == ["p1", "p3"]
+
+
+@pytest.mark.parametrize("store", redis_store_fixture)
+def test_explain_find(store):
+    """
+    explain_find() shows the candidates in the index of each lookup of find(), the one chosen to drive the matching
+    i.e. with the fewest candidates, and whether the ids of the whole collection are scanned instead
+    """
+
+    class Post(Model):
+        id: str
+        author: str
+        tags: List[str]
+        editor: Optional[str]
+
+    store.create_collection(Post, primary_key_field="id", indexed_fields=["author"], indexed_list_fields=["tags"])
+    post_collection = store.get_collection(Post)
+    post_collection.add_many([Post(id=f"p{i}", author="jane" if i < 2 else "john", tags=["rust"],
+                                   editor=None if i % 2 else "ann") for i in range(5)])
+    post_collection.create_null_index("editor")
+
+    assert post_collection.explain_find(tags__contains="rust", author="jane") == {
+        "lookups": [
+            {"lookup": "tags__contains='rust'", "index": "list", "role": "match", "candidates": 5},
+            {"lookup": "author='jane'", "index": "field", "role": "match", "candidates": 2},
+        ],
+        "scan": False,
+        "chosen_index": "author='jane'",
+        "candidates": 2,
+        "matches": 2,
+    }
+    assert sorted(v.id for v in post_collection.find(tags__contains="rust", author="jane")) == ["p0", "p1"]
+
+    plan = post_collection.explain_find(editor__isnull=False)
+    assert plan["lookups"] == [{"lookup": "editor__isnull=False", "index": "null", "role": "exclude", "candidates": 2}]
+    assert plan["scan"] is True
+    assert plan["chosen_index"] is None
+    assert plan["candidates"] == 5
+    assert plan["matches"] == 3
+
+    with pytest.raises(ValueError):
+        post_collection.explain_find()
+    with pytest.raises(ValueError):
+        post_collection.explain_find(id="p1")
+
+
+@pytest.mark.parametrize("store", redis_store_fixture)
+def test_snapshot(store):
+    """snapshot() copies the current records under a tag, which read_snapshot() returns as they were"""
+    book_collection = store.get_collection(Book)
+    book_collection.add_many(books)
+    assert book_collection.snapshot("before") == len(books)
+
+    book_collection.update_one(id=books[0].title, data={"in_stock": not books[0].in_stock})
+    book_collection.delete_many([books[1].title])
+    book_collection.add_one(Book(**{**books[1].dict(), "title": "New Title"}))
+
+    snapshot = book_collection.read_snapshot("before")
+    assert sorted(snapshot, key=lambda x: x.title) == sorted(books, key=lambda x: x.title)
+    assert sorted(book_collection.ids()) != sorted(book.title for book in books)
+
+    # taking a snapshot of the same tag again replaces it
+    assert book_collection.snapshot("before") == len(books)
+    assert sorted(book_collection.read_snapshot("before"), key=lambda x: x.title) == sorted(
+        book_collection.get_all(), key=lambda x: x.title)
+
+    assert book_collection.read_snapshot("unknown") == []
+    assert book_collection.delete_snapshot("before") == len(books)
+    assert book_collection.read_snapshot("before") == []
+
+
+@pytest.mark.parametrize("store", redis_store_fixture)
+@pytest.mark.parametrize("tag", ["", "v*", "v?", "[v]", "v%&"])
+def test_snapshot_with_invalid_tag(store, tag):
+    """snapshot() and read_snapshot() reject tags that would make their keys match those of other snapshots"""
+    book_collection = store.get_collection(Book)
+    with pytest.raises(ValueError, match="snapshot tag should be non-empty"):
+        book_collection.snapshot(tag)
+    with pytest.raises(ValueError, match="snapshot tag should be non-empty"):
+        book_collection.read_snapshot(tag)
+
+
+@pytest.mark.parametrize("store", redis_store_fixture)
+def test_export_and_import(store, tmp_path):
+    """export_to() writes the records into a checksummed file of chunks, which import_from() adds back"""
+    book_collection = store.get_collection(Book)
+    path = str(tmp_path / "books.export")
+    book_collection.add_many(books)
+
+    assert book_collection.export_to(path, chunk_size=3) == len(books)
+    book_collection.delete_many([book.title for book in books])
+    assert book_collection.import_from(path) == len(books)
+    assert sorted(book_collection.get_all(), key=lambda x: x.title) == sorted(books, key=lambda x: x.title)
+    assert not os.path.exists(f"{path}.progress")
+
+    with pytest.raises(ValueError, match=r"chunk_size should be at least 1"):
+        book_collection.export_to(path, chunk_size=0)
+    with pytest.raises(ValueError, match=r"the export is of the collection"):
+        store.get_collection(Author).import_from(path)
+
+
+@pytest.mark.parametrize("store", redis_store_fixture)
+def test_import_with_resume(store, tmp_path):
+    """import_from() carries on after the last chunk imported before an interruption unless resume is False"""
+    book_collection = store.get_collection(Book)
+    path = str(tmp_path / "books.export")
+    book_collection.add_many(books)
+    assert book_collection.export_to(path, chunk_size=1) == len(books)
+    book_collection.delete_many([book.title for book in books])
+
+    with open(f"{path}.progress", "w") as file:
+        file.write("1")
+    assert book_collection.import_from(path) == len(books) - 1
+    assert len(book_collection.get_all()) == len(books) - 1
+    assert not os.path.exists(f"{path}.progress")
+
+    with open(f"{path}.progress", "w") as file:
+        file.write("3")
+    assert book_collection.import_from(path, resume=False) == len(books)
+    assert len(book_collection.get_all()) == len(books)
+
+
+@pytest.mark.parametrize("store", redis_store_fixture)
+def test_import_with_damaged_export(store, tmp_path):
+    """import_from() rejects exports that are corrupted or truncated, keeping the chunks imported before"""
+    book_collection = store.get_collection(Book)
+    path = str(tmp_path / "books.export")
+    book_collection.add_many(books)
+    book_collection.export_to(path, chunk_size=2)
+    with open(path, "rb") as file:
+        data = file.read()
+
+    # the last byte of the last chunk, just before the 32 bytes that mark the end of the export
+    with open(path, "wb") as file:
+        file.write(data[:-33] + bytes([data[-33] ^ 0xFF]) + data[-32:])
+    with pytest.raises(ValueError, match=r"chunk 2 of the export is corrupted"):
+        book_collection.import_from(path)
+    with open(f"{path}.progress") as file:
+        assert file.read() == "1"
+
+    with open(path, "wb") as file:
+        file.write(data[:-32])
+    with pytest.raises(ValueError, match=r"the export is truncated after chunk 2"):
+        book_collection.import_from(path)
+
+    with open(path, "wb") as file:
+        file.write(b"not an export")
+    with pytest.raises(ValueError, match=r"is not an export of orredis"):
+        book_collection.import_from(path)
+
+
+@pytest.mark.parametrize("store", redis_store_fixture)
+def test_backup(store, tmp_path):
+    """
+    backup() streams the export of the collection into a file-like object, the sink registered for the scheme
+    of a url, or a local file, from which import_from() restores it
+    """
+
+    class Upload(io.BytesIO):
+        uploads = {}
+
+        def __init__(self, url):
+            super().__init__()
+            self.url = url
+
+        def close(self):
+            Upload.uploads[self.url] = self.getvalue()
+            super().close()
+
+    book_collection = store.get_collection(Book)
+    path = str(tmp_path / "books.export")
+    book_collection.add_many(books)
+    register_backup_sink("memory", Upload)
+
+    buffer = io.BytesIO()
+    assert book_collection.backup(buffer, chunk_size=3) == len(books)
+    assert not buffer.closed
+    assert book_collection.backup("memory://backups/books") == len(books)
+    assert book_collection.backup(path) == len(books)
+
+    for data in [None, buffer.getvalue(), Upload.uploads["memory://backups/books"]]:
+        if data is not None:
+            with open(path, "wb") as file:
+                file.write(data)
+        book_collection.delete_many([book.title for book in books])
+        assert book_collection.import_from(path) == len(books)
+        assert sorted(book_collection.get_all(), key=lambda x: x.title) == sorted(books, key=lambda x: x.title)
+
+    with pytest.raises(ValueError, match=r"dest should be a path, a url or a writable binary file-like object"):
+        book_collection.backup(42)
+    with pytest.raises(ValueError):
+        book_collection.backup("unknown://backups/books")
+    with pytest.raises(ValueError, match=r"the scheme should be non-empty"):
+        register_backup_sink("", Upload)
+
+
+@pytest.mark.parametrize("store", redis_store_fixture)
+def test_schedule_backup(store, tmp_path):
+    """
+    schedule_backup() backs all the collections up periodically into timestamped directories under the target,
+    keeping only the latest backups, with their status got from backup_schedules()
+    """
+    book_collection = store.get_collection(Book)
+    book_collection.add_many(books)
+    target = str(tmp_path / "backups")
+
+    store.schedule_backup(0.2, target, keep=2)
+    with pytest.raises(ValueError, match=r"is already scheduled"):
+        store.schedule_backup("@daily", target)
+    time.sleep(1.5)
+    status = store.backup_schedules()[target]
+    assert status["runs"] >= 3
+    assert status["failures"] == 0
+    assert status["last_error"] is None
+    assert len(status["backups"]) == 2
+    assert store.cancel_backup_schedule(target)
+    assert not store.cancel_backup_schedule(target)
+    time.sleep(0.5)
+
+    backups = sorted(os.listdir(target))
+    assert len(backups) == 2
+    latest = os.path.join(target, backups[-1], "Book.export")
+    book_collection.delete_many([book.title for book in books])
+    assert book_collection.import_from(latest) == len(books)
+    assert sorted(book_collection.get_all(), key=lambda x: x.title) == sorted(books, key=lambda x: x.title)
+    assert os.path.exists(os.path.join(target, backups[-1], "Author.export"))
+
+    store.schedule_backup("@every 1h", target, keep=3)
+    status = store.backup_schedules()[target]
+    assert status["interval_secs"] == 3600
+    assert status["keep"] == 3
+    assert status["runs"] == 0
+    assert status["backups"] == []
+    assert store.cancel_backup_schedule(target)
+
+    with pytest.raises(ValueError, match=r"expected a timedelta, a number of seconds"):
+        store.schedule_backup("every day", target)
+    with pytest.raises(ValueError, match=r"should be more than zero"):
+        store.schedule_backup(0, target)
+    with pytest.raises(ValueError, match=r"keep should be at least 1"):
+        store.schedule_backup(60, target, keep=0)
+
+
+@pytest.mark.parametrize("store", redis_store_fixture)
+def test_get_all_with_filter(store):
+    """get_all(filter) returns only the book models whose fields are equal to the values of the filter"""
+    book_collection = store.get_collection(Book)
+    book_collection.add_many(books)
+
+    response = book_collection.get_all(filter={"in_stock": False})
+    assert sorted(response, key=lambda x: x.title) == [books[2], books[0]]
+    assert book_collection.get_all(filter={"in_stock": True, "rating": 4.0}) == [books[3]]
+    assert book_collection.get_all(filter={"title": "Hamlet"}) == []
+    assert len(book_collection.get_all(filter={})) == len(books)
+
+    with pytest.raises(KeyError, match=r"\"isbn\" is not a field of the model"):
+        book_collection.get_all(filter={"isbn": "foo"})
+
+    with pytest.raises(ValueError, match=r"\"author\" is a nested field"):
+        book_collection.get_all(filter={"author": authors["jane"]})
+
+
+@pytest.mark.parametrize("store", redis_store_fixture)
+def test_get_all_with_skip_and_limit(store):
+    """get_all(skip, limit) and get_all_partially(skip, limit) return pages of the records in the order of the scan"""
+    book_collection = store.get_collection(Book)
+    book_collection.add_many(books)
+
+    first_page = book_collection.get_all(limit=3)
+    second_page = book_collection.get_all(skip=3, limit=3)
+    assert len(first_page) == 3
+    assert len(second_page) == 1
+    assert sorted(first_page + second_page, key=lambda x: x.title) == sorted(books, key=lambda x: x.title)
+    assert book_collection.get_all(skip=len(books)) == []
+    assert book_collection.get_all(limit=0) == []
+
+    in_stock = book_collection.get_all(filter={"in_stock": True}, skip=1, limit=5)
+    assert len(in_stock) == 1
+    assert in_stock[0].in_stock is True
+
+    titles = [book["title"] for book in book_collection.get_all_partially(["title"])]
+    page = book_collection.get_all_partially(["title"], skip=1, limit=2)
+    assert [book["title"] for book in page] == titles[1:3]
+
+
+@pytest.mark.parametrize("store", redis_store_fixture)
+def test_get_all_order_by(store):
+    """
+    get_all(order_by=...) and get_all_partially(order_by=...) sort the records in redis by a field before skipping
+    and limiting them, comparing numbers as numbers, the records without a value for the field coming last
+    """
+
+    class Player(Model):
+        name: str
+        team: str
+        score: Optional[int]
+
+    store.create_collection(Player, primary_key_field="name")
+    player_collection = store.get_collection(Player)
+    player_collection.add_many([Player(name="ann", team="red", score=5), Player(name="bob", team="blue", score=30),
+                                Player(name="cid", team="red", score=None), Player(name="dan", team="blue", score=1),
+                                Player(name="eve", team="red", score=4)])
+
+    assert [v.name for v in player_collection.get_all(order_by="score")] == ["dan", "eve", "ann", "bob", "cid"]
+    assert [v.name for v in player_collection.get_all(order_by="score", desc=True)] == [
+        "bob", "ann", "eve", "dan", "cid"]
+    assert [v.name for v in player_collection.get_all(order_by="score", skip=1, limit=2)] == ["eve", "ann"]
+    assert [v.name for v in player_collection.get_all(filter={"team": "red"}, order_by="score", desc=True)] == [
+        "ann", "eve", "cid"]
+    assert [v.name for v in player_collection.get_all(order_by="team", limit=2)] == ["bob", "dan"]
+    assert player_collection.get_all_partially(["name"], order_by="name", desc=True, limit=2) == [
+        {"name": "eve"}, {"name": "dan"}]
+
+    with pytest.raises(ValueError, match=r"desc should only be given with order_by"):
+        player_collection.get_all(desc=True)
+    with pytest.raises(KeyError):
+        player_collection.get_all_partially(["name"], order_by="rank")
+
+
+@pytest.mark.parametrize("store", redis_store_fixture)
+def test_get_all_order_by_index(store):
+    """
+    get_all(order_by=...) and get_all_partially(order_by=...) read the page straight from the index of the field if it
+    has one made with create_index(), the records without a value for it coming last in the order of their ids,
+    the index being kept up to date as the records change
+    """
+
+    class Player(Model):
+        name: str
+        team: str
+        score: Optional[int]
+
+    store.create_collection(Player, primary_key_field="name")
+    player_collection = store.get_collection(Player)
+    player_collection.add_many([Player(name="ann", team="red", score=5), Player(name="bob", team="blue", score=30),
+                                Player(name="cid", team="red", score=None), Player(name="dan", team="blue", score=1),
+                                Player(name="eve", team="red", score=4)])
+    assert player_collection.create_index("score") == 5
+
+    assert [v.name for v in player_collection.get_all(order_by="score")] == ["dan", "eve", "ann", "bob", "cid"]
+    assert [v.name for v in player_collection.get_all(order_by="score", desc=True)] == [
+        "bob", "ann", "eve", "dan", "cid"]
+    assert [v.name for v in player_collection.get_all(order_by="score", skip=1, limit=2)] == ["eve", "ann"]
+    assert [v.name for v in player_collection.get_all(order_by="score", skip=3, limit=5)] == ["bob", "cid"]
+    assert player_collection.get_all(order_by="score", skip=5) == []
+
+    player_collection.update_one("dan", data={"score": 10})
+    player_collection.add_one(Player(name="fay", team="red", score=None))
+    player_collection.delete_many(["ann"])
+
+    assert [v.name for v in player_collection.get_all(order_by="score")] == ["eve", "dan", "bob", "cid", "fay"]
+    assert [v.name for v in player_collection.get_all(filter={"team": "red"}, order_by="score", desc=True)] == [
+        "eve", "cid", "fay"]
+    assert player_collection.get_all_partially(["name"], order_by="score", desc=True, skip=2, limit=2) == [
+        {"name": "eve"}, {"name": "cid"}]
+
+
+@pytest.mark.parametrize("store", redis_store_fixture)
+def test_get_all_order_by_index_with_earlier_records(store, redis_server):
+    """
+    get_all(order_by=...) returns the records saved before the index of the field was made, by scanning the
+    collection while the index is still being built, and in the same order once it is built, records with the same
+    value being in the order of their ids either way
+    """
+
+    class Player(Model):
+        name: str
+        team: str
+        score: Optional[int]
+
+    store.create_collection(Player, primary_key_field="name")
+    player_collection = store.get_collection(Player)
+    player_collection.add_many([Player(name="ann", team="red", score=5), Player(name="bob", team="blue", score=3),
+                                Player(name="cid", team="red", score=5), Player(name="dan", team="red", score=None),
+                                Player(name="eve", team="blue", score=5)])
+
+    # an index registered by another client that has not yet indexed the records saved before it
+    legacy_client = redis.Redis(port=int(redis_server), db=1)
+    legacy_client.sadd("Player_%&range_indexes_%&_fields", "score")
+    time.sleep(0.6)
+    player_collection.add_one(Player(name="fay", team="red", score=3))
+    assert [v.name for v in player_collection.get_all(order_by="score")] == ["bob", "fay", "ann", "cid", "eve", "dan"]
+    assert [v.name for v in player_collection.get_all(order_by="score", desc=True)] == [
+        "ann", "cid", "eve", "bob", "fay", "dan"]
+
+    assert player_collection.create_index("score") == 6
+    player_collection.add_one(Player(name="gus", team="red", score=1))
+    assert [v.name for v in player_collection.get_all(order_by="score")] == [
+        "gus", "bob", "fay", "ann", "cid", "eve", "dan"]
+    assert [v.name for v in player_collection.get_all(order_by="score", desc=True)] == [
+        "ann", "cid", "eve", "bob", "fay", "gus", "dan"]
+    assert [v.name for v in player_collection.get_all(order_by="score", desc=True, skip=1, limit=3)] == [
+        "cid", "eve", "bob"]
+    assert [v.name for v in player_collection.get_all(order_by="score", desc=True, skip=4, limit=3)] == [
+        "fay", "gus", "dan"]
+    assert [v.name for v in player_collection.get_all(filter={"team": "red"}, order_by="score", desc=True)] == [
+        "ann", "cid", "fay", "gus", "dan"]
+
+
+@pytest.mark.parametrize("store", redis_store_fixture)
+@pytest.mark.parametrize("workers", [1, 3, 10])
+def test_parallel_iter(store, workers):
+    """parallel_iter() returns all the book models, however many workers read them"""
+    book_collection = store.get_collection(Book)
+    assert book_collection.parallel_iter(workers=workers) == []
+
+    book_collection.add_many(books)
+    response = book_collection.parallel_iter(workers=workers)
+    assert sorted(response, key=lambda x: x.title) == sorted(books, key=lambda x: x.title)
+
+    with pytest.raises(ValueError):
+        book_collection.parallel_iter(workers=0)
+
+
+@pytest.mark.parametrize("store", redis_store_fixture)
+@pytest.mark.parametrize("batch_size", [1, 3, 100])
+def test_iter_all(store, batch_size):
+    """iter_all() yields all the book models one scan batch at a time, whatever the batch size"""
+    book_collection = store.get_collection(Book)
+    assert list(book_collection.iter_all(batch_size=batch_size)) == []
+
+    book_collection.add_many(books)
+    records = book_collection.iter_all(batch_size=batch_size)
+    assert iter(records) is records
+    first = next(records)
+    assert first in books
+    response = [first, *records]
+    assert sorted(response, key=lambda x: x.title) == sorted(books, key=lambda x: x.title)
+    with pytest.raises(StopIteration):
+        next(records)
+
+    with pytest.raises(ValueError):
+        book_collection.iter_all(batch_size=0)
+
+
 @pytest.mark.parametrize("store", redis_store_fixture)
 def test_get_all_partially(store):
     """
@@ -227,17 +1192,30 @@ def test_get_many(store):
 
 
 @pytest.mark.parametrize("store", redis_store_fixture)
-def test_get_one_non_existent_id(store):
+def test_get_many_raw(store):
     """
-    get_one() for a non-existent id returns None
+    get_many_raw() returns the records of the given ids as dictionaries, with their nested records also as dictionaries
     """
     book_collection = store.get_collection(Book)
     book_collection.add_many(books)
-    response = book_collection.get_one(id="Some strange book")
-    assert response is None
-
+    ids = [book.title for book in books[:2]]
+    response = book_collection.get_many_raw(ids=ids)
+    assert response == [book.dict() for book in books[:2]]
+    assert all(isinstance(record["author"], dict) for record in response)
 
-# FIXME: add test for non-existent columns for both single and multiple record retrieval
+
+@pytest.mark.parametrize("store", redis_store_fixture)
+def test_get_one_non_existent_id(store):
+    """
+    get_one() for a non-existent id returns None
+    """
+    book_collection = store.get_collection(Book)
+    book_collection.add_many(books)
+    response = book_collection.get_one(id="Some strange book")
+    assert response is None
+
+
+# FIXME: add test for non-existent columns for both single and multiple record retrieval
 
 @pytest.mark.parametrize("store", redis_store_fixture)
 def test_get_one_partially_non_existent_id_with_existent_columns(store):
@@ -353,7 +1331,7 @@ def test_delete_many(store):
     ids_to_delete = [book.title for book in books_to_delete]
     ids_to_leave_intact = [book.title for book in books_to_be_left_in_db]
 
-    book_collection.delete_many(ids=ids_to_delete)
+    assert book_collection.delete_many(ids=ids_to_delete) == 2
     deleted_books_select_response = book_collection.get_many(ids=ids_to_delete)
 
     books_left = book_collection.get_many(ids=ids_to_leave_intact)
@@ -362,3 +1340,2573 @@ def test_delete_many(store):
     assert deleted_books_select_response == []
     assert books_left == books_to_be_left_in_db
     assert authors_left == sorted(authors.values(), key=lambda x: x.name)
+
+
+@pytest.mark.parametrize("store", redis_store_fixture)
+def test_delete_many_with_missing_ids(store):
+    """
+    delete_many() returns the number of the given ids that had records, raising a KeyError if strict and any had none
+    """
+    book_collection = store.get_collection(Book)
+    book_collection.add_many(books)
+
+    assert book_collection.delete_many([books[0].title, books[0].title, "unknown"]) == 1
+    assert book_collection.delete_many([books[0].title]) == 0
+
+    with pytest.raises(KeyError, match="1 of the 2 ids have no record"):
+        book_collection.delete_many([books[1].title, "unknown"], strict=True)
+    # the records that existed are deleted all the same
+    assert book_collection.get_many([books[1].title]) == []
+    assert book_collection.delete_many([books[2].title, books[3].title], strict=True) == 2
+
+
+@pytest.mark.parametrize("store", redis_store_fixture)
+def test_delete_where(store):
+    """
+    delete_where() removes the records whose fields are equal to the given values in one script,
+    returning the number removed, and keeps the indexes of indexed fields up to date
+    """
+    book_collection = store.get_collection(Book)
+    author_collection = store.get_collection(Author)
+    book_collection.add_many(books)
+
+    assert book_collection.delete_where(in_stock=False) == 2
+    assert sorted(book_collection.get_all(), key=lambda x: x.title) == sorted(
+        [book for book in books if book.in_stock], key=lambda x: x.title)
+    assert sorted(author_collection.get_all(), key=lambda x: x.name) == sorted(authors.values(), key=lambda x: x.name)
+    assert book_collection.delete_where(in_stock=False) == 0
+    assert book_collection.delete_where(title="Hamlet") == 0
+
+    with pytest.raises(ValueError, match=r"at least one field filter is needed"):
+        book_collection.delete_where()
+    with pytest.raises(KeyError, match=r"\"isbn\" is not a field of the model"):
+        book_collection.delete_where(isbn="foo")
+
+    class Review(Model):
+        id: int
+        reviewer: str
+
+    store.create_collection(Review, primary_key_field="id", indexed_fields=["reviewer"])
+    review_collection = store.get_collection(Review)
+    review_collection.add_many([Review(id=i, reviewer=f"reviewer {i % 3}") for i in range(9)])
+
+    assert review_collection.delete_where(reviewer="reviewer 0") == 3
+    assert review_collection.find_by("reviewer", "reviewer 0") == []
+    assert len(review_collection.get_all()) == 6
+
+
+@pytest.mark.parametrize("store", redis_store_fixture)
+def test_collection_clear(store):
+    """
+    clear() on a collection removes its records, with their indexes and unique values, returning the number
+    removed, and leaves the other collections as they are
+    """
+    book_collection = store.get_collection(Book)
+    author_collection = store.get_collection(Author)
+    book_collection.add_many(books)
+
+    assert book_collection.clear() == len(books)
+    assert book_collection.get_all() == []
+    assert sorted(author_collection.get_all(), key=lambda x: x.name) == sorted(authors.values(), key=lambda x: x.name)
+    assert book_collection.clear() == 0
+
+    class Member(Model):
+        id: int
+        email: str
+        city: str
+
+    store.create_collection(Member, primary_key_field="id", unique_fields=["email"], indexed_fields=["city"])
+    member_collection = store.get_collection(Member)
+    member_collection.add_many([Member(id=i, email=f"m{i}@example.com", city=f"city {i % 2}") for i in range(6)])
+
+    assert member_collection.clear(max_ops_per_sec=1000) == 6
+    assert member_collection.find_by("city", "city 0") == []
+    # the unique values are released along with the records
+    member_collection.add_one(Member(id=10, email="m0@example.com", city="city 1"))
+    assert member_collection.find_by("city", "city 1") == [Member(id=10, email="m0@example.com", city="city 1")]
+
+    with pytest.raises(ValueError, match=r"max_ops_per_sec should be a number greater than 0"):
+        member_collection.clear(max_ops_per_sec=0)
+
+
+@pytest.mark.parametrize("store", redis_store_fixture)
+def test_delete_matching(store):
+    """
+    delete_matching() removes the records whose ids match a glob pattern, returning the number removed,
+    and keeps the indexes and unique values of the records up to date
+    """
+    book_collection = store.get_collection(Book)
+    author_collection = store.get_collection(Author)
+    book_collection.add_many(books)
+
+    assert book_collection.delete_matching("[GW]*") == 2
+    assert sorted(v.title for v in book_collection.get_all()) == ["Jane Eyre", "Oliver Twist"]
+    assert sorted(author_collection.get_all(), key=lambda x: x.name) == sorted(authors.values(), key=lambda x: x.name)
+    assert book_collection.delete_matching("Hamlet*") == 0
+
+    class Order(Model):
+        id: str
+        customer: str
+        reference: str
+
+    store.create_collection(Order, primary_key_field="id", indexed_fields=["customer"], unique_fields=["reference"])
+    order_collection = store.get_collection(Order)
+    order_collection.add_many([Order(id=f"order-{year}-{i}", customer=f"c{i}", reference=f"ref-{year}-{i}")
+                               for year in (2023, 2024) for i in range(3)])
+
+    assert order_collection.delete_matching("order-2023-*", max_ops_per_sec=1000) == 3
+    assert sorted(v.id for v in order_collection.get_all()) == ["order-2024-0", "order-2024-1", "order-2024-2"]
+    assert [v.id for v in order_collection.find_by("customer", "c0")] == ["order-2024-0"]
+    # the unique values of the records removed are released
+    order_collection.add_one(Order(id="order-2025-0", customer="c0", reference="ref-2023-0"))
+
+    with pytest.raises(ValueError, match=r"id_pattern should not be empty"):
+        order_collection.delete_matching("")
+    with pytest.raises(ValueError, match=r"max_ops_per_sec should be a number greater than 0"):
+        order_collection.delete_matching("*", max_ops_per_sec=-1)
+
+
+@pytest.mark.parametrize("store", redis_store_fixture)
+def test_update_where(store):
+    """
+    update_where() changes the fields of the records whose fields are equal to the filters in one script,
+    returning the number changed, and keeps the indexes of indexed fields up to date
+    """
+    book_collection = store.get_collection(Book)
+    book_collection.add_many(books)
+
+    assert book_collection.update_where({"in_stock": False}, {"in_stock": True, "rating": 5.0}) == 2
+    expected = sorted([book.copy(update={"in_stock": True, "rating": 5.0}) if not book.in_stock else book
+                       for book in books], key=lambda x: x.title)
+    assert sorted(book_collection.get_all(), key=lambda x: x.title) == expected
+    assert book_collection.update_where({"in_stock": False}, {"rating": 1.0}) == 0
+
+    with pytest.raises(ValueError, match=r"filters should have at least one field"):
+        book_collection.update_where({}, {"rating": 1.0})
+    with pytest.raises(ValueError, match=r"changes should have at least one field"):
+        book_collection.update_where({"in_stock": True}, {})
+    with pytest.raises(ValueError, match=r"primary keys cannot be changed"):
+        book_collection.update_where({"in_stock": True}, {"title": "Hamlet"})
+    with pytest.raises(ValueError, match=r"\"author\" is a nested field"):
+        book_collection.update_where({"in_stock": True}, {"author": authors["jane"]})
+
+    class Review(Model):
+        id: int
+        reviewer: str
+
+    store.create_collection(Review, primary_key_field="id", indexed_fields=["reviewer"])
+    review_collection = store.get_collection(Review)
+    review_collection.add_many([Review(id=i, reviewer=f"reviewer {i % 3}") for i in range(9)])
+
+    assert review_collection.update_where({"reviewer": "reviewer 0"}, {"reviewer": "reviewer 1"}) == 3
+    assert review_collection.find_by("reviewer", "reviewer 0") == []
+    assert len(review_collection.find_by("reviewer", "reviewer 1")) == 6
+
+
+@pytest.mark.parametrize("id_generator", ["uuid4", "ulid", "snowflake", "ksuid"])
+@pytest.mark.parametrize("store", redis_store_fixture)
+def test_add_one_with_id_generator(store, id_generator):
+    """
+    add_one() generates a primary key for records without one if an id_generator was set on the collection,
+    and returns it
+    """
+
+    class Event(Model):
+        name: str
+        id: Optional[str] = None
+
+    store.create_collection(Event, primary_key_field="id", id_generator=id_generator)
+    event_collection = store.get_collection(Event)
+
+    first_id = event_collection.add_one(Event(name="first"))
+    # wait long enough for the timestamp portion of the id to change
+    time.sleep(id_generator_resolutions[id_generator])
+    added_ids = event_collection.add_many([Event(name="second"), Event(name="third", id="custom-id")])
+
+    events = event_collection.get_all()
+    ids = {event.name: event.id for event in events}
+
+    assert len(events) == 3
+    assert ids["third"] == "custom-id"
+    assert ids["first"] not in (None, "None")
+    assert [ids["first"], ids["second"], ids["third"]] == [first_id, *added_ids]
+    assert ids["first"] != ids["second"]
+    assert event_collection.get_one(id=ids["first"]) == Event(name="first", id=ids["first"])
+
+    if id_generator != "uuid4":
+        # ids are lexicographically sortable by creation time
+        first, second = ids["first"], ids["second"]
+        assert len(first) == len(second)
+        assert first < second
+
+
+@pytest.mark.parametrize("store", redis_store_fixture)
+def test_add_one_with_snowflake_worker_id(store):
+    """
+    The snowflake id_generator puts the snowflake_worker_id of the collection in the ids it generates, so that
+    processes with different worker ids never generate the same ids. Worker ids have 10 bits
+    """
+
+    class Event(Model):
+        name: str
+        id: Optional[str] = None
+
+    with pytest.raises(ValueError, match=r"snowflake_worker_id should be at most 1023"):
+        store.create_collection(Event, primary_key_field="id", id_generator="snowflake", snowflake_worker_id=1024)
+
+    store.create_collection(Event, primary_key_field="id", id_generator="snowflake", snowflake_worker_id=1000)
+    event_collection = store.get_collection(Event)
+
+    ids = event_collection.add_many([Event(name="first"), Event(name="second")])
+    assert [(int(v) >> 12) & 1023 for v in ids] == [1000, 1000]
+
+
+def test_create_collection_with_unknown_id_generator(redis_store):
+    """Throws error when a collection is created with an unknown id_generator"""
+
+    class Event(Model):
+        name: str
+        id: Optional[str] = None
+
+    with pytest.raises(ValueError, match=r"unknown id_generator"):
+        redis_store.create_collection(Event, primary_key_field="id", id_generator="foo")
+
+
+@pytest.mark.parametrize("store", redis_store_fixture)
+def test_apply_retention(store):
+    """
+    apply_retention() removes the records whose retention_field is older than the collection's retention period
+    """
+
+    class Log(Model):
+        message: str
+        created_at: datetime
+
+    class Session(Model):
+        token: str
+
+    now = datetime.now(tz=timezone.utc).replace(microsecond=0)
+    old_log = Log(message="old", created_at=now - timedelta(days=31))
+    new_log = Log(message="new", created_at=now - timedelta(days=29))
+
+    store.create_collection(Log, primary_key_field="message", retention=timedelta(days=30),
+                            retention_field="created_at")
+    store.create_collection(Session, primary_key_field="token", retention=3600)
+    log_collection = store.get_collection(Log)
+    session_collection = store.get_collection(Session)
+
+    log_collection.add_many([old_log, new_log])
+    session_collection.add_one(Session(token="foo"))
+
+    assert len(log_collection.get_all()) == 2
+    # the session already got its ttl capped on insert
+    assert store.apply_retention() == {"Log": 1, "Session": 0}
+    assert log_collection.get_all() == [new_log]
+    assert session_collection.get_all() == [Session(token="foo")]
+
+
+@pytest.mark.parametrize("store", redis_store_fixture)
+def test_apply_retention_with_max_ops_per_sec(store):
+    """
+    apply_retention(max_ops_per_sec) scans no more records per second than max_ops_per_sec
+    """
+
+    class Log(Model):
+        message: str
+        created_at: datetime
+
+    now = datetime.now(tz=timezone.utc).replace(microsecond=0)
+    old_logs = [Log(message=f"old {i}", created_at=now - timedelta(days=31)) for i in range(10)]
+    new_log = Log(message="new", created_at=now - timedelta(days=29))
+
+    store.create_collection(Log, primary_key_field="message", retention=timedelta(days=30),
+                            retention_field="created_at")
+    log_collection = store.get_collection(Log)
+    log_collection.add_many([*old_logs, new_log])
+
+    with pytest.raises(ValueError, match=r"max_ops_per_sec should be a number greater than 0"):
+        store.apply_retention(max_ops_per_sec=0)
+
+    started_at = time.monotonic()
+    assert store.apply_retention(max_ops_per_sec=10) == {"Log": 10}
+    assert time.monotonic() - started_at >= 1
+    assert log_collection.get_all() == [new_log]
+
+
+@pytest.mark.parametrize("store", redis_store_fixture)
+def test_ttl_audit(store):
+    """
+    ttl_audit() counts the records of each collection that have no ttl, a ttl below the threshold
+    and a ttl below the imminent expiry
+    """
+
+    class Session(Model):
+        token: str
+
+    store.create_collection(Session, primary_key_field="token")
+    session_collection = store.get_collection(Session)
+
+    session_collection.add_many([Session(token="foo"), Session(token="bar")])
+    session_collection.add_one(Session(token="expiring"), ttl=30)
+    session_collection.add_one(Session(token="short"), ttl=timedelta(minutes=30))
+    session_collection.add_one(Session(token="long"), ttl=timedelta(hours=2))
+
+    audit = store.ttl_audit()
+    assert audit["Session"] == {"records": 5, "no_ttl": 2, "below_threshold": 2, "imminent_expiry": 1}
+    assert audit["Book"] == {"records": 0, "no_ttl": 0, "below_threshold": 0, "imminent_expiry": 0}
+
+    audit = store.ttl_audit(threshold=timedelta(hours=3), imminent=10)
+    assert audit["Session"] == {"records": 5, "no_ttl": 2, "below_threshold": 3, "imminent_expiry": 0}
+
+
+@pytest.mark.parametrize("book_collection", book_collection_fixture)
+def test_approx_distinct(book_collection):
+    """
+    approx_distinct() returns the approximate number of distinct values of a field tracked by track_cardinality(),
+    including those of records added before tracking started
+    """
+    book_collection.add_many(books[:2])
+    book_collection.track_cardinality("rating")
+    assert book_collection.approx_distinct("rating") == 2
+
+    book_collection.add_many(books[2:])
+    book_collection.add_one(books[0])
+    assert book_collection.approx_distinct("rating") == 4
+    assert book_collection.approx_distinct("in_stock") == 0
+
+    with pytest.raises(KeyError):
+        book_collection.approx_distinct("foo")
+
+    with pytest.raises(ValueError):
+        book_collection.track_cardinality("author")
+
+
+@pytest.mark.parametrize("store", redis_store_fixture)
+def test_approx_distinct_tracked_by_another_store(store, redis_server):
+    """
+    The fields tracked by track_cardinality() are kept in redis, so the writes of the collections of other stores,
+    e.g. in other processes, add to their HyperLogLogs too
+    """
+    other_store = Store(url=f"redis://localhost:{redis_server}/1")
+    other_store.create_collection(Author, primary_key_field="name")
+    other_store.create_collection(Book, primary_key_field="title")
+    other_store.get_collection(Book).track_cardinality("rating")
+
+    book_collection = store.get_collection(Book)
+    book_collection.add_many(books[:2])
+    assert book_collection.approx_distinct("rating") == 2
+
+    book_collection.update_one(books[0].title, data={"rating": 0.5})
+    assert book_collection.approx_distinct("rating") == 3
+
+    assert book_collection.update_where({"title": books[1].title}, {"rating": 1.5}) == 1
+    assert book_collection.approx_distinct("rating") == 4
+    assert book_collection.approx_distinct("in_stock") == 0
+
+    # clearing the collection keeps the fields tracked
+    book_collection.clear()
+    book_collection.add_many(books[:2])
+    assert book_collection.approx_distinct("rating") == 2
+
+@pytest.mark.parametrize("book_collection", book_collection_fixture)
+def test_distinct(book_collection):
+    """distinct() returns the distinct values of a field across the collection, parsed into their python types"""
+    assert book_collection.distinct("in_stock") == []
+
+    book_collection.add_many(books)
+    assert sorted(book_collection.distinct("in_stock")) == [False, True]
+    assert sorted(book_collection.distinct("rating")) == sorted(book.rating for book in books)
+    assert sorted(book_collection.distinct("tags")) == [["Classic"], ["Classic", "Romance"]]
+
+    with pytest.raises(KeyError):
+        book_collection.distinct("foo")
+
+    with pytest.raises(ValueError):
+        book_collection.distinct("author")
+
+@pytest.mark.parametrize("book_collection", book_collection_fixture)
+def test_aggregate(book_collection):
+    """aggregate() computes the sum, average, minimum or maximum of the values of a numeric field"""
+    assert book_collection.aggregate("rating", "sum") == 0
+    assert book_collection.aggregate("rating", "avg") is None
+    assert book_collection.aggregate("rating", "max") is None
+
+    book_collection.add_many(books)
+    ratings = [book.rating for book in books]
+    assert book_collection.aggregate("rating", "sum") == pytest.approx(sum(ratings))
+    assert book_collection.aggregate("rating", "avg") == pytest.approx(sum(ratings) / len(ratings))
+    assert book_collection.aggregate("rating", "min") == min(ratings)
+    assert book_collection.aggregate("rating", "max") == max(ratings)
+
+    with pytest.raises(KeyError):
+        book_collection.aggregate("foo", "sum")
+
+    with pytest.raises(ValueError):
+        book_collection.aggregate("title", "sum")
+
+    with pytest.raises(ValueError):
+        book_collection.aggregate("rating", "median")
+
+@pytest.mark.parametrize("book_collection", book_collection_fixture)
+def test_count_by(book_collection):
+    """count_by() counts the records of the collection by the values of a field"""
+    assert book_collection.count_by("in_stock") == {}
+
+    book_collection.add_many(books)
+    assert book_collection.count_by("in_stock") == {
+        True: len([book for book in books if book.in_stock]),
+        False: len([book for book in books if not book.in_stock]),
+    }
+    assert book_collection.count_by("title") == {book.title: 1 for book in books}
+
+    with pytest.raises(KeyError):
+        book_collection.count_by("foo")
+
+    with pytest.raises(ValueError):
+        book_collection.count_by("tags")
+
+
+@pytest.mark.parametrize("book_collection", book_collection_fixture)
+def test_count_where(book_collection):
+    """count_where() counts the records of the collection whose fields are equal to the given values"""
+    assert book_collection.count_where(in_stock=True) == 0
+
+    book_collection.add_many(books)
+    assert book_collection.count_where(in_stock=False) == len([book for book in books if not book.in_stock])
+    assert book_collection.count_where(in_stock=True, rating=4.0) == 1
+    assert book_collection.count_where(title="Hamlet") == 0
+    assert book_collection.count_where() == len(books)
+
+    with pytest.raises(KeyError):
+        book_collection.count_where(foo="bar")
+
+    with pytest.raises(ValueError):
+        book_collection.count_where(author=authors["jane"])
+
+
+@pytest.mark.parametrize("book_collection", book_collection_fixture)
+def test_exists_many(book_collection):
+    """exists_many() checks whether the records of the given ids exist, returning a boolean per id in order"""
+    assert book_collection.exists_many([]) == []
+    book_collection.add_many(books[:2])
+
+    ids = [books[1].title, "Hamlet", books[0].title, books[3].title]
+    assert book_collection.exists_many(ids) == [True, False, True, False]
+
+
+@pytest.mark.parametrize("store", redis_store_fixture)
+def test_get_field(store):
+    """get_field() returns the value of one field of a record converted to its type, or None if it is missing"""
+
+    class Post(Model):
+        slug: str
+        views: int = Field(0, counter=True)
+
+    store.create_collection(Post, primary_key_field="slug")
+    book_collection = store.get_collection(Book)
+    book_collection.add_many(books)
+
+    for book in books:
+        assert book_collection.get_field(book.title, "in_stock") == book.in_stock
+        assert book_collection.get_field(book.title, "published_on") == book.published_on
+        assert book_collection.get_field(book.title, "tags") == book.tags
+    assert book_collection.get_field("Hamlet", "rating") is None
+
+    with pytest.raises(KeyError):
+        book_collection.get_field(books[0].title, "foo")
+    with pytest.raises(ValueError):
+        book_collection.get_field(books[0].title, "author")
+
+    post_collection = store.get_collection(Post)
+    post_collection.add_one(Post(slug="foo", views=4))
+    post_collection.increment("foo", "views")
+    assert post_collection.get_field("foo", "views") == 5
+    assert post_collection.get_field("bar", "views") is None
+
+
+@pytest.mark.parametrize("store", redis_store_fixture)
+def test_case_insensitive_keys(store, redis_server):
+    """
+    create_collection(case_insensitive_keys=True) gets the same record for ids differing only in case.
+    Collections of the same model in other stores are not affected
+    """
+
+    class Member(Model):
+        username: str
+        visits: int = Field(0, counter=True)
+
+    store.create_collection(Member, primary_key_field="username", case_insensitive_keys=True)
+    member_collection = store.get_collection(Member)
+    book_collection = store.get_collection(Book)
+    member_collection.add_one(Member(username="Alice"))
+    book_collection.add_one(books[0])
+
+    assert member_collection.get_one("alice") == Member(username="Alice")
+    assert member_collection.increment("ALICE", "visits") == 1
+    assert member_collection.get_one("Alice") == Member(username="Alice", visits=1)
+    assert member_collection.exists("aLiCe")
+    assert book_collection.get_one(books[0].title.upper()) is None
+
+    member_collection.add_one(Member(username="ALICE", visits=5))
+    assert member_collection.get_all() == [Member(username="ALICE", visits=5)]
+
+    member_collection.delete_many(["alice"])
+    assert member_collection.get_one("Alice") is None
+
+    other_store = Store(url=f"redis://localhost:{redis_server}/2")
+    other_store.create_collection(Member, primary_key_field="username")
+    other_member_collection = other_store.get_collection(Member)
+    other_member_collection.add_one(Member(username="Bob"))
+    member_collection.add_one(Member(username="Bob"))
+    assert other_member_collection.get_one("bob") is None
+    assert other_member_collection.get_one("Bob") == Member(username="Bob")
+    assert member_collection.get_one("BOB") == Member(username="Bob")
+
+    other_store.clear()
+
+
+@pytest.mark.parametrize("store", redis_store_fixture)
+def test_get_latest_and_get_oldest(store):
+    """get_latest() and get_oldest() return the records inserted last and first if insertion order is tracked"""
+
+    class Event(Model):
+        name: str
+        kind: str = "info"
+
+    store.create_collection(Event, primary_key_field="name", track_insertion_order=True)
+    event_collection = store.get_collection(Event)
+    book_collection = store.get_collection(Book)
+    events = [Event(name=f"event-{i}") for i in range(5)]
+    event_collection.add_one(events[0])
+    event_collection.add_many(events[1:])
+
+    assert event_collection.get_latest(3) == events[:-4:-1]
+    assert event_collection.get_oldest(2) == events[:2]
+    assert event_collection.get_oldest() == events
+    assert event_collection.get_latest(0) == []
+
+    event_collection.update_one("event-0", data={"kind": "error"})
+    assert event_collection.get_oldest(1) == [events[0].with_changes({"kind": "error"})]
+
+    event_collection.delete_many(["event-4"])
+    event_collection.delete_where(name="event-3")
+    assert event_collection.get_latest(2) == events[1:3][::-1]
+
+    with pytest.raises(ValueError, match="does not track insertion order"):
+        book_collection.get_latest()
+
+
+@pytest.mark.orredis_models(Publisher, (Author, "name"))
+@pytest.mark.parametrize("name", ["Penguin", "Vintage"])
+def test_orredis_store_fixture(orredis_store, name):
+    """orredis_store of the pytest plugin has the collections of the orredis_models marker and starts empty"""
+    publisher_collection = orredis_store.get_collection(Publisher)
+    author_collection = orredis_store.get_collection(Author)
+    publisher = Publisher(name=name, address=Address(street="Strand", number=80))
+
+    assert publisher_collection.get_all() == []
+    publisher_collection.add_one(publisher)
+    author_collection.add_one(authors["jane"])
+    assert publisher_collection.get_all() == [publisher]
+    assert author_collection.get_one(authors["jane"].name) == authors["jane"]
+    with pytest.raises(KeyError):
+        orredis_store.get_collection(Book)
+
+
+@pytest.mark.parametrize("key", ["first", "second"])
+def test_orredis_url_fixture(orredis_url, key):
+    """orredis_url of the pytest plugin is a database of its own on the redislite server, empty at the start"""
+    client = redis.Redis.from_url(orredis_url)
+
+    assert client.dbsize() == 0
+    client.set(key, "value")
+    assert client.get(key) == b"value"
+    client.close()
+
+
+@pytest.mark.parametrize("store", redis_store_fixture)
+def test_get_cache(store):
+    """get_cache() returns a binary-safe key-value cache with ttls and versioned keys beside the collections"""
+    book_collection = store.get_collection(Book)
+    book_collection.add_one(books[0])
+    cache = store.get_cache(prefix="app")
+    value = bytes(range(256))
+
+    assert cache.get("foo") is None
+    assert cache.set("foo", value)
+    assert cache.get("foo") == value
+    assert cache.get("foo", version=2) is None
+    assert not cache.set("foo", b"bar", only_if_missing=True)
+    assert cache.set("foo", b"bar", version=2, only_if_missing=True)
+    assert cache.get("foo", version=2) == b"bar"
+    assert cache.has_key("foo")
+
+    cache.set_many({"a": b"1", "b": b"2"}, ttl=timedelta(milliseconds=100))
+    assert cache.get_many(["a", "b", "c"]) == {"a": b"1", "b": b"2"}
+    assert cache.touch("a", ttl=None)
+    assert not cache.touch("c", ttl=10)
+    time.sleep(0.2)
+    assert cache.get_many(["a", "b"]) == {"a": b"1"}
+
+    assert not cache.set("a", b"3", ttl=0)
+    assert not cache.has_key("a")
+    assert cache.delete("foo")
+    assert not cache.delete("foo")
+    assert cache.delete_many(["foo", "b"], version=2) == 1
+
+    cache.set_many({"x": b"1", "y": b"2"})
+    assert cache.clear() == 2
+    assert cache.get_many(["x", "y"]) == {}
+    assert book_collection.get_one(books[0].title) == books[0]
+
+
+@pytest.mark.parametrize("store", redis_store_fixture)
+def test_django_cache(store):
+    """OrredisCache is a Django cache backend that shares the pool of the given store"""
+    pytest.importorskip("django")
+    from django.conf import settings
+    if not settings.configured:
+        settings.configure()
+    from orredis.integrations.django import OrredisCache
+
+    cache = OrredisCache("", {"KEY_PREFIX": "app", "OPTIONS": {"STORE": store}})
+    assert cache.get("foo", "missing") == "missing"
+    cache.set("foo", {"a": [1, 2]})
+    assert cache.get("foo") == {"a": [1, 2]}
+    assert not cache.add("foo", 1)
+    assert cache.add("bar", 1)
+    assert cache.incr("bar", 2) == 3
+    assert cache.get_many(["foo", "bar", "baz"]) == {"foo": {"a": [1, 2]}, "bar": 3}
+    assert store.get_cache(prefix="app").has_key("foo", version=1)
+
+    cache.set("short", 1, timeout=0)
+    assert not cache.has_key("short")
+    assert cache.delete("foo")
+    cache.clear()
+    assert cache.get("bar") is None
+
+
+@pytest.mark.parametrize("store", redis_store_fixture)
+def test_kv(store):
+    """kv() returns a raw key-value namespace whose keys are its namespace followed by the keys given to it"""
+    kv = store.kv("celery-task-meta-")
+    value = bytes(range(256))
+
+    assert kv.get("foo") is None
+    kv.set("foo", value)
+    assert kv.get("foo") == value
+    assert store.kv("").get("celery-task-meta-foo") == value
+    kv.set("bar", b"1", ttl=timedelta(milliseconds=100))
+    assert kv.mget(["foo", "bar", "baz"]) == [value, b"1", None]
+    time.sleep(0.2)
+    assert kv.get("bar") is None
+
+    assert kv.incr("count") == 1
+    assert kv.incr("count", 2) == 3
+    assert kv.expire("count", 0.1)
+    assert not kv.expire("baz", 10)
+    time.sleep(0.2)
+    assert kv.get("count") is None
+
+    assert kv.delete("foo")
+    assert not kv.delete("foo")
+    with pytest.raises(ValueError, match="ttl should be more than zero"):
+        kv.set("foo", b"1", ttl=0)
+
+
+@pytest.mark.parametrize("store", redis_store_fixture)
+def test_celery_result_backend(store):
+    """OrredisBackend is a Celery result backend that shares the pool of the given store"""
+    celery = pytest.importorskip("celery")
+    from orredis.integrations.celery import OrredisBackend
+
+    app = celery.Celery(set_as_current=False)
+    app.conf.orredis_store = store
+    backend = OrredisBackend(app=app)
+
+    backend.store_result("abc", {"a": [1, 2]}, "SUCCESS")
+    assert backend.get_task_meta("abc")["result"] == {"a": [1, 2]}
+    assert backend.get_result("abc") == {"a": [1, 2]}
+    assert store.kv("celery-task-meta-").get("abc") is not None
+
+    backend.forget("abc")
+    assert store.kv("celery-task-meta-").get("abc") is None
+
+
+@pytest.mark.parametrize("book_collection", book_collection_fixture)
+def test_find_between(book_collection):
+    """
+    find_between() returns the records whose values of a field indexed by create_index() are in a range,
+    including those of records added before the index was created, in the order of their values
+    """
+    book_collection.add_many(books[:2])
+    assert book_collection.create_index("rating") == 2
+    assert book_collection.create_index("published_on") == 2
+    book_collection.add_many(books[2:])
+
+    assert book_collection.find_between("rating", 3, 4.5) == [books[2], books[3]]
+    assert book_collection.find_between("rating", 6, None) == []
+    assert book_collection.find_between("published_on", date(year=1216, month=1, day=1), None) == books[1:]
+    assert book_collection.find_between("published_on", None, date(year=1220, month=4, day=4)) == books[:2]
+
+    book_collection.update_one(books[0].title, data={"rating": 4.5})
+    book_collection.delete_many([books[1].title])
+    assert book_collection.find_between("rating", 4.1, None) == [books[0].with_changes({"rating": 4.5})]
+
+    with pytest.raises(ValueError, match="has no index"):
+        book_collection.find_between("last_updated", None, None)
+    with pytest.raises(ValueError, match="is not a valid bound"):
+        book_collection.find_between("rating", "high", None)
+    with pytest.raises(ValueError, match="only such fields can be indexed"):
+        book_collection.create_index("title")
+    with pytest.raises(ValueError):
+        book_collection.create_index("author")
+
+
+@pytest.mark.parametrize("store", redis_store_fixture)
+def test_find_between_index_created_by_another_store(store, redis_server):
+    """
+    The indexes created by create_index() are registered in redis, so the writes of the collections of other stores,
+    e.g. in other processes, update them too and those collections can read them
+    """
+    book_collection = store.get_collection(Book)
+    book_collection.add_many(books[:2])
+
+    other_store = Store(url=f"redis://localhost:{redis_server}/1")
+    other_store.create_collection(Author, primary_key_field="name")
+    other_store.create_collection(Book, primary_key_field="title")
+    other_book_collection = other_store.get_collection(Book)
+    assert other_book_collection.create_index("rating") == 2
+
+    book_collection.add_many(books[2:])
+    book_collection.update_one(books[0].title, data={"rating": 4.5})
+    book_collection.delete_many([books[1].title])
+    assert other_book_collection.find_between("rating", 4.1, None) == [books[0].with_changes({"rating": 4.5})]
+    assert book_collection.find_between("rating", 3, 4.5) == [
+        books[2],
+        books[3],
+        books[0].with_changes({"rating": 4.5}),
+    ]
+
+
+@pytest.mark.parametrize("store", redis_store_fixture)
+def test_get_page(store):
+    """
+    get_page() returns the records after the value and id of the last record of the previous page, in the order
+    of their values of an indexed field then of their ids, without shifting when records are inserted before it
+    """
+
+    class Player(Model):
+        name: str
+        score: int
+
+    store.create_collection(Player, primary_key_field="name")
+    player_collection = store.get_collection(Player)
+    players = [Player(name=f"player{i}", score=i % 3) for i in range(8)]
+    player_collection.add_many(players)
+    assert player_collection.create_index("score") == 8
+
+    pages = []
+    last = None
+    while True:
+        if last is None:
+            page = player_collection.get_page("score", 3)
+        else:
+            page = player_collection.get_page("score", 3, after_value=last.score, after_id=last.name)
+        if not page:
+            break
+        pages.append(page)
+        last = page[-1]
+        # records inserted before the current page do not shift the next pages
+        player_collection.add_one(Player(name=f"newcomer{len(pages)}", score=-1))
+
+    assert [len(page) for page in pages] == [3, 3, 2]
+    assert [v for page in pages for v in page] == sorted(players, key=lambda v: (v.score, v.name))
+
+    assert player_collection.get_page("score", 2, after_value=0) == [players[1], players[4]]
+    with pytest.raises(ValueError, match="has no index"):
+        player_collection.get_page("name")
+    with pytest.raises(ValueError, match="greater than 0"):
+        player_collection.get_page("score", 0)
+    with pytest.raises(ValueError, match="after_id should be given with after_value"):
+        player_collection.get_page("score", after_id="player1")
+
+
+@pytest.mark.parametrize("store", redis_store_fixture)
+def test_hot_keys(store):
+    """
+    hot_keys() returns the most accessed ids of collections created with hot_key_sampling, hottest first
+    """
+
+    class Session(Model):
+        token: str
+
+    with pytest.raises(ValueError, match=r"sample should be greater than 0"):
+        store.create_collection(Session, primary_key_field="token", hot_key_sampling=0)
+
+    store.create_collection(Session, primary_key_field="token", hot_key_sampling=1)
+    session_collection = store.get_collection(Session)
+    session_collection.add_many([Session(token="foo"), Session(token="bar"), Session(token="baz")])
+
+    for _ in range(5):
+        session_collection.get_one("foo")
+    session_collection.get_many(["foo", "bar"])
+    session_collection.update_one("bar", {"token": "bar"})
+    session_collection.exists("baz")
+
+    hot_keys = session_collection.hot_keys(top=2)
+    assert [id for id, _ in hot_keys] == ["foo", "bar"]
+    assert hot_keys[0][1] == pytest.approx(6, rel=0.01)
+    assert hot_keys[1][1] == pytest.approx(2, rel=0.01)
+    assert len(session_collection.hot_keys()) == 3
+
+    with pytest.raises(ValueError, match=r"does not track hot keys"):
+        store.get_collection(Book).hot_keys()
+
+
+@pytest.mark.parametrize("store", redis_store_fixture)
+def test_quota(store, redis_server):
+    """
+    Inserts through the collections of a namespace that would take it beyond the maximum number of records of its quota
+    raise a QuotaExceededError, while updates of existing records and deletes are allowed
+    """
+
+    class Session(Model):
+        token: str
+        user: str
+
+    store.create_collection(Session, primary_key_field="token", namespace="team_a")
+    store.set_quota("team_a", max_records=2)
+    session_collection = store.get_collection(Session)
+    session_collection.add_many([Session(token="foo", user="john"), Session(token="bar", user="jane")])
+
+    with pytest.raises(QuotaExceededError):
+        session_collection.add_one(Session(token="baz", user="jim"))
+    session_collection.update_one("foo", {"user": "johnny"})
+
+    client = redis.Redis(port=int(redis_server), db=1)
+    expected_bytes = sum(
+        len(k) + len(v) for token in ("foo", "bar") for k, v in client.hgetall(f"Session_%&_{token}").items())
+    assert session_collection.get_one("baz") is None
+    assert store.get_quota("team_a") == {"records": 2, "bytes": expected_bytes, "max_records": 2, "max_bytes": None}
+
+    session_collection.delete_many(["bar"])
+    session_collection.add_one(Session(token="baz", user="jim"))
+    assert session_collection.get_one("baz") == Session(token="baz", user="jim")
+    assert store.get_quota("team_a")["records"] == 2
+
+
+@pytest.mark.parametrize("store", redis_store_fixture)
+def test_quota_max_bytes(store):
+    """
+    Inserts that would take the total length of the field names and values of the records of a namespace beyond
+    the maximum number of bytes of its quota raise a QuotaExceededError. Limits that are not given are removed
+    """
+
+    class Session(Model):
+        token: str
+        user: str
+
+    store.create_collection(Session, primary_key_field="token", namespace="team_a")
+    session_collection = store.get_collection(Session)
+    session_collection.add_one(Session(token="foo", user="john"))
+    record_bytes = store.get_quota("team_a")["bytes"]
+    assert record_bytes > 0
+
+    store.set_quota("team_a", max_records=10, max_bytes=2 * record_bytes)
+    session_collection.add_one(Session(token="bar", user="jane"))
+    with pytest.raises(QuotaExceededError):
+        session_collection.add_one(Session(token="baz", user="jim"))
+
+    store.set_quota("team_a")
+    assert store.get_quota("team_a") == {
+        "records": 2, "bytes": 2 * record_bytes, "max_records": None, "max_bytes": None}
+    session_collection.add_one(Session(token="baz", user="jim"))
+    assert store.get_quota("team_a")["records"] == 3
+    assert store.get_quota("team_b") == {"records": 0, "bytes": 0, "max_records": None, "max_bytes": None}
+
+
+@pytest.mark.parametrize("store", redis_store_fixture)
+def test_quota_concurrent_inserts(store):
+    """
+    Of many concurrent inserts through the collections of a namespace, only those within its quota are made,
+    the quota being checked and its usage changed atomically with each insert
+    """
+
+    class Session(Model):
+        token: str
+        user: str
+
+    store.create_collection(Session, primary_key_field="token", namespace="team_a")
+    store.set_quota("team_a", max_records=5)
+    session_collection = store.get_collection(Session)
+
+    errors = []
+
+    def add_session(token):
+        try:
+            session_collection.add_one(Session(token=token, user="john"))
+        except QuotaExceededError as e:
+            errors.append(e)
+
+    threads = [threading.Thread(target=add_session, args=(f"token_{i}",)) for i in range(20)]
+    for thread in threads:
+        thread.start()
+    for thread in threads:
+        thread.join()
+
+    assert len(errors) == 15
+    assert all('the quota of namespace "team_a"' in str(e) for e in errors)
+    assert len(session_collection.get_all()) == 5
+    assert store.get_quota("team_a")["records"] == 5
+
+
+@pytest.mark.parametrize("book_collection", book_collection_fixture)
+def test_memory_usage(book_collection):
+    """
+    memory_usage() measures the memory used by a sample of the records, estimating that of the whole collection
+    and listing the largest records sampled
+    """
+    book_collection.add_many(books)
+    titles = {book.title for book in books}
+
+    usage = book_collection.memory_usage()
+    assert usage["records"] == len(books)
+    assert usage["sampled"] == len(books)
+    assert usage["sampled_bytes"] > 0
+    assert usage["avg_bytes"] == usage["sampled_bytes"] / len(books)
+    assert usage["estimated_total_bytes"] == usage["sampled_bytes"]
+    assert {item["id"] for item in usage["largest"]} == titles
+    sizes = [item["bytes"] for item in usage["largest"]]
+    assert sizes == sorted(sizes, reverse=True)
+
+    usage = book_collection.memory_usage(sample=2, top=1)
+    assert usage["records"] == len(books)
+    assert usage["sampled"] == 2
+    assert usage["estimated_total_bytes"] == round(usage["avg_bytes"] * len(books))
+    assert len(usage["largest"]) == 1
+    assert usage["largest"][0]["id"] in titles
+
+    with pytest.raises(ValueError, match=r"sample should be at least 1"):
+        book_collection.memory_usage(sample=0)
+
+
+@pytest.mark.parametrize("store", redis_store_fixture)
+def test_train_compression(store, redis_server):
+    """
+    train_compression() trains a dictionary on the string values of the collection, with which the records written
+    after it are compressed. Other stores read and write them once they load it with load_compression()
+    """
+
+    class Review(Model):
+        id: int
+        reviewer: str
+        body: str
+
+    class Score(Model):
+        name: str
+        value: int
+
+    store.create_collection(Review, primary_key_field="id", indexed_fields=["reviewer"])
+    store.create_collection(Score, primary_key_field="name")
+    review_collection = store.get_collection(Review)
+    reviews = [
+        Review(id=i, reviewer=f"reviewer {i % 5}",
+               body=f"Book number {i} was a gripping read, with a plot that kept me turning its pages late at night")
+        for i in range(300)
+    ]
+
+    with pytest.raises(ValueError, match=r"sample should be at least 1"):
+        review_collection.train_compression(sample=0)
+    with pytest.raises(ValueError, match=r"no string fields that can be compressed"):
+        store.get_collection(Score).train_compression()
+    with pytest.raises(ValueError, match=r"could not train a compression dictionary"):
+        review_collection.train_compression()
+
+    review_collection.add_many(reviews)
+    stats = review_collection.train_compression(sample=200)
+    assert stats["samples"] == 200
+    assert stats["dictionary_bytes"] > 0
+    assert stats["compressed_bytes"] < stats["sampled_bytes"]
+    assert stats["ratio"] == stats["sampled_bytes"] / stats["compressed_bytes"]
+
+    new_reviews = [review.copy(update={"id": review.id + 300}) for review in reviews[:10]]
+    review_collection.add_many(new_reviews)
+    assert review_collection.get_many([str(review.id) for review in new_reviews]) == new_reviews
+    assert sorted(review_collection.get_all(), key=lambda v: v.id) == reviews + new_reviews
+    assert sorted(review_collection.get_all(filter={"reviewer": "reviewer 0"}), key=lambda v: v.id) == [
+        review for review in reviews + new_reviews if review.reviewer == "reviewer 0"
+    ]
+    with pytest.raises(ValueError, match=r"compressed fields cannot be filtered on"):
+        review_collection.get_all(filter={"body": reviews[0].body})
+
+    other_store = Store(url=f"redis://localhost:{redis_server}/1")
+    other_store.create_collection(Review, primary_key_field="id", indexed_fields=["reviewer"])
+    other_review_collection = other_store.get_collection(Review)
+    assert other_review_collection.load_compression()
+    other_review_collection.add_one(reviews[0].copy(update={"id": 1000}))
+    assert review_collection.get_one("1000") == reviews[0].copy(update={"id": 1000})
+    assert other_review_collection.get_one("300") == new_reviews[0]
+    assert not other_store.get_collection(Score).load_compression()
+
+
+@pytest.mark.parametrize("store", redis_store_fixture)
+def test_compression_dictionary_lazily_loaded(store, redis_server):
+    """
+    A value compressed with a dictionary of its collection that is not loaded is read after loading the dictionary
+    from redis. Dictionaries are kept by collection, so the dictionary of one collection is not used for another
+    """
+
+    class Review(Model):
+        id: int
+        body: str
+
+    class Critique(Model):
+        id: int
+        body: str
+
+    store.create_collection(Review, primary_key_field="id")
+    store.create_collection(Critique, primary_key_field="id")
+    review_collection = store.get_collection(Review)
+    critique_collection = store.get_collection(Critique)
+    body = "Book number {} was a gripping read, with a plot that kept me turning its pages late at night"
+    reviews = [Review(id=i, body=body.format(i)) for i in range(300)]
+    review_collection.add_many(reviews)
+    review_collection.train_compression(sample=200)
+    review_collection.add_one(reviews[0].copy(update={"id": 300}))
+
+    # the compressed record is copied to the other collection, whose dictionaries are not in redis yet
+    client = redis.Redis(port=int(redis_server), db=1)
+    client.hset("Critique_%&_300", mapping=client.hgetall("Review_%&_300"))
+    with pytest.raises(ValueError, match=r"not among the dictionaries of Critique in redis"):
+        critique_collection.get_one("300")
+
+    dictionaries = client.hgetall("Review_%&compression_%&_dictionaries")
+    client.hset("Critique_%&compression_%&_dictionaries", mapping=dictionaries)
+    assert critique_collection.get_one("300") == Critique(id=300, body=reviews[0].body)
+    assert review_collection.get_one("300") == reviews[0].copy(update={"id": 300})
+
+
+@pytest.mark.parametrize("store", redis_store_fixture)
+def test_bloom_filter(store):
+    """
+    Collections with bloom filters still find existing records, including those nested in other records,
+    whether or not the RedisBloom module is loaded
+    """
+
+    class Publisher(Model):
+        name: str
+
+    class Magazine(Model):
+        title: str
+        publisher: Publisher
+
+    store.create_collection(Publisher, primary_key_field="name", bloom_filter=True)
+    store.create_collection(Magazine, primary_key_field="title", bloom_filter=True)
+    publisher_collection = store.get_collection(Publisher)
+    magazine_collection = store.get_collection(Magazine)
+    magazine = Magazine(title="Time", publisher=Publisher(name="Time USA"))
+
+    magazine_collection.add_one(magazine)
+
+    assert magazine_collection.exists("Time")
+    assert not magazine_collection.exists("Vogue")
+    assert magazine_collection.get_one("Time") == magazine
+    assert magazine_collection.get_one("Vogue") is None
+    assert publisher_collection.exists("Time USA")
+    assert publisher_collection.get_one("Time USA") == magazine.publisher
+    assert publisher_collection.get_one("Conde Nast") is None
+
+
+@pytest.mark.parametrize("store", redis_store_fixture)
+def test_counter_fields(store):
+    """
+    Counter fields are stored in their own keys, can be incremented atomically and are merged into records on read
+    """
+
+    class Post(Model):
+        slug: str
+        views: int = Field(0, counter=True)
+        rating: float = Field(0.0, counter=True)
+
+    store.create_collection(Post, primary_key_field="slug")
+    post_collection = store.get_collection(Post)
+    post_collection.add_many([Post(slug="foo", views=4), Post(slug="bar")])
+
+    assert post_collection.increment("foo", "views") == 5
+    assert post_collection.increment("foo", "views", 10) == 15
+    assert post_collection.increment("bar", "rating", 2.5) == 2.5
+    assert post_collection.get_one("foo") == Post(slug="foo", views=15)
+    assert sorted(post_collection.get_all(), key=lambda x: x.slug) == [Post(slug="bar", rating=2.5),
+                                                                      Post(slug="foo", views=15)]
+    assert post_collection.get_one_partially("foo", fields=["views"]) == {"views": 15}
+    assert post_collection.get_all_partially(fields=["rating"]) in ([{"rating": 0.0}, {"rating": 2.5}],
+                                                                    [{"rating": 2.5}, {"rating": 0.0}])
+
+    post_collection.update_one("foo", data={"views": 1})
+    assert post_collection.get_one("foo") == Post(slug="foo", views=1)
+
+    post_collection.delete_many(["foo"])
+    assert post_collection.get_one("foo") is None
+    assert post_collection.increment("foo", "views") == 1
+
+    with pytest.raises(ValueError):
+        post_collection.increment("foo", "slug")
+
+
+def test_create_collection_with_invalid_counter_field(redis_store):
+    """Throws error when a counter field is not an int or float field"""
+
+    class Post(Model):
+        slug: str
+        author: str = Field("", counter=True)
+
+    with pytest.raises(ValueError, match=r"counter field"):
+        redis_store.create_collection(Post, primary_key_field="slug")
+
+
+@pytest.mark.parametrize("store", redis_store_fixture)
+def test_create_view(store):
+    """
+    create_view() creates a view of the records that match the filter, with only the projected fields,
+    that is kept up to date on every write to the collection
+    """
+    store.create_view("books_in_stock", source_collection=Book, projection=["title", "rating"],
+                      filter={"in_stock": True})
+    book_collection = store.get_collection(Book)
+    book_collection.add_many(books)
+    view = store.get_view("books_in_stock")
+
+    expected = [{"title": book.title, "rating": book.rating} for book in books if book.in_stock]
+    assert sorted(view.get_all(), key=lambda x: x["title"]) == sorted(expected, key=lambda x: x["title"])
+    assert view.get_one(books[0].title) is None
+    assert view.get_many([books[0].title, books[1].title]) == [{"title": books[1].title, "rating": books[1].rating}]
+
+    book_collection.update_one(books[1].title, data={"in_stock": False})
+    book_collection.update_one(books[0].title, data={"in_stock": True})
+    assert view.get_one(books[1].title) is None
+    assert view.get_one(books[0].title) == {"title": books[0].title, "rating": books[0].rating}
+
+    book_collection.delete_many([books[0].title])
+    assert view.get_one(books[0].title) is None
+    assert view.rebuild() == 1
+
+
+def test_create_view_with_invalid_fields(redis_store):
+    """Throws error when the view's fields are nested or its source collection has not been created"""
+
+    class Magazine(Model):
+        title: str
+
+    with pytest.raises(ValueError):
+        redis_store.create_view("authored_books", source_collection=Book, projection=["title", "author"])
+
+    with pytest.raises(KeyError):
+        redis_store.create_view("magazines", source_collection=Magazine, projection=["title"])
+
+    with pytest.raises(KeyError):
+        redis_store.get_view("magazines")
+
+
+@pytest.mark.parametrize("store", redis_store_fixture)
+def test_create_link(store):
+    """
+    create_link() creates a many-to-many link whose records are found from either side,
+    hydrated into the models of their collections
+    """
+
+    class Student(Model):
+        id: str
+        name: str
+
+    class Course(Model):
+        code: str
+        title: str
+
+    store.create_collection(Student, primary_key_field="id")
+    store.create_collection(Course, primary_key_field="code")
+    store.create_link("enrollments", left_collection=Student, right_collection=Course)
+    student_collection = store.get_collection(Student)
+    course_collection = store.get_collection(Course)
+    enrollments = store.get_link("enrollments")
+
+    students = [Student(id="s1", name="Ann"), Student(id="s2", name="Bob")]
+    courses = [Course(code="c1", title="Algebra"), Course(code="c2", title="Biology")]
+    student_collection.add_many(students)
+    course_collection.add_many(courses)
+
+    enrollments.link("s1", "c1")
+    enrollments.link("s1", "c2")
+    enrollments.link("s2", "c1")
+    enrollments.link("s2", "c1")
+
+    assert sorted(enrollments.right_of("s1"), key=lambda x: x.code) == courses
+    assert enrollments.right_of("s2") == [courses[0]]
+    assert sorted(enrollments.left_of("c1"), key=lambda x: x.id) == students
+    assert enrollments.left_of("c3") == []
+
+    enrollments.unlink("s1", "c1")
+    enrollments.unlink("s1", "c3")
+    assert enrollments.right_of("s1") == [courses[1]]
+    assert enrollments.left_of("c1") == [students[1]]
+
+    # deleted records are skipped
+    course_collection.delete_many(["c2"])
+    assert enrollments.right_of("s1") == []
+
+    with pytest.raises(ValueError):
+        store.create_link("enrollments", left_collection=Student, right_collection=Course)
+    with pytest.raises(KeyError):
+        store.get_link("memberships")
+
+
+@pytest.mark.parametrize("store", redis_store_fixture)
+def test_get_changes(store):
+    """
+    get_changes() returns the ordered changes to the records of a collection that tracks changes,
+    with the fields of the records before and after each change
+    """
+
+    class Task(Model):
+        name: str
+        done: bool = False
+
+    store.create_collection(Task, primary_key_field="name", track_changes=True)
+    task_collection = store.get_collection(Task)
+    book_collection = store.get_collection(Book)
+
+    task_collection.add_one(Task(name="foo"))
+    task_collection.update_one("foo", data={"done": True})
+    task_collection.delete_many(["foo", "bar"])
+
+    changes = task_collection.get_changes()
+    assert [(c["op"], c["id"], c["before"], c["after"]) for c in changes] == [
+        ("insert", "foo", {}, {"name": "foo", "done": False}),
+        ("update", "foo", {"name": "foo", "done": False}, {"name": "foo", "done": True}),
+        ("delete", "foo", {"name": "foo", "done": True}, {}),
+    ]
+    assert task_collection.get_changes(after=changes[0]["event_id"], count=1) == changes[1:2]
+    assert task_collection.get_changes(after=changes[-1]["event_id"]) == []
+
+    with pytest.raises(ValueError):
+        book_collection.get_changes()
+
+
+@pytest.mark.parametrize("store", redis_store_fixture)
+def test_add_mirror(store, redis_server):
+    """
+    add_mirror() applies every write made through the store to the mirror in the background
+    """
+    mirror_store = Store(url=f"redis://localhost:{redis_server}/2")
+    mirror_store.create_collection(Author, primary_key_field="name")
+    mirror_store.create_collection(Book, primary_key_field="title")
+    mirror_book_collection = mirror_store.get_collection(Book)
+
+    store.add_mirror(f"redis://localhost:{redis_server}/2")
+    book_collection = store.get_collection(Book)
+
+    book_collection.add_many(books)
+    book_collection.update_one(books[0].title, data={"in_stock": True})
+    book_collection.delete_many([books[1].title])
+    time.sleep(0.3)
+
+    expected = [book_collection.get_one(books[0].title), books[2], books[3]]
+    assert sorted(mirror_book_collection.get_all(), key=lambda b: b.title) == sorted(expected, key=lambda b: b.title)
+    assert mirror_book_collection.get_one(books[0].title).in_stock
+
+
+@pytest.mark.parametrize("store", redis_store_fixture)
+def test_generate_fake(store, redis_server):
+    """
+    generate_fake() adds the given number of valid records, with their nested records, generated from the schema.
+    The same seed generates the same records
+    """
+    other_store = Store(url=f"redis://localhost:{redis_server}/2")
+    other_store.create_collection(Author, primary_key_field="name")
+    other_store.create_collection(Book, primary_key_field="title")
+    book_collection = store.get_collection(Book)
+    other_book_collection = other_store.get_collection(Book)
+
+    book_collection.generate_fake(25, seed=42)
+    other_book_collection.generate_fake(25, seed=42)
+
+    got = sorted(book_collection.get_all(), key=lambda b: b.title)
+    assert len(got) == 25
+    assert all(isinstance(book, Book) and isinstance(book.author, Author) for book in got)
+    assert len(store.get_collection(Author).get_all()) == 25
+    assert sorted(other_book_collection.get_all(), key=lambda b: b.title) == got
+
+    other_store.clear()
+
+
+@pytest.mark.parametrize("store", redis_store_fixture)
+def test_verify_against(store, redis_server):
+    """
+    verify_against() reports the records that are missing from either store or whose fields differ
+    """
+    other_store = Store(url=f"redis://localhost:{redis_server}/2")
+    other_store.create_collection(Author, primary_key_field="name")
+    other_store.create_collection(Book, primary_key_field="title")
+    book_collection = store.get_collection(Book)
+    other_book_collection = other_store.get_collection(Book)
+
+    book_collection.add_many(books)
+    other_book_collection.add_many(books)
+    assert store.verify_against(other_store, Book, sample=1) == {
+        "sampled": 4, "missing": [], "extra": [], "mismatched": {},
+    }
+
+    other_book_collection.update_one(books[0].title, data={"rating": 1.5})
+    other_book_collection.delete_many([books[1].title])
+    book_collection.delete_many([books[2].title])
+
+    assert store.verify_against(other_store, Book, sample=1) == {
+        "sampled": 4,
+        "missing": [books[1].title],
+        "extra": [books[2].title],
+        "mismatched": {books[0].title: {"rating": (books[0].rating, 1.5)}},
+    }
+
+    with pytest.raises(ValueError):
+        store.verify_against(other_store, Book, sample=0)
+
+
+def test_add_mirror_with_invalid_url(redis_store):
+    """add_mirror() raises a ConnectionError if the url is not a valid redis url"""
+    with pytest.raises(ConnectionError):
+        redis_store.add_mirror("foo://localhost:6379")
+
+
+def test_write_buffer(unused_tcp_port):
+    """
+    Writes made while redis is unreachable are held in the write buffer and applied in order once it is reachable again
+    """
+    server = redislite.Redis(serverconfig={"port": unused_tcp_port})
+    store = Store(url=f"redis://localhost:{unused_tcp_port}/1", timeout=100, write_buffer=10)
+    store.create_collection(Author, primary_key_field="name")
+    store.create_collection(Book, primary_key_field="title")
+    book_collection = store.get_collection(Book)
+    assert store.pending_writes() == 0
+
+    server.shutdown()
+    book_collection.add_many(books)
+    book_collection.update_one(books[0].title, data={"in_stock": True})
+    book_collection.delete_many([books[1].title])
+    assert store.pending_writes() == 3
+
+    server = redislite.Redis(serverconfig={"port": unused_tcp_port})
+    for _ in range(50):
+        if store.pending_writes() == 0:
+            break
+        time.sleep(0.1)
+
+    assert store.pending_writes() == 0
+    assert book_collection.get_one(books[0].title).in_stock
+    assert book_collection.get_one(books[1].title) is None
+    assert book_collection.get_one(books[2].title) == books[2]
+    server.shutdown()
+
+
+@pytest.mark.parametrize("overflow, kept", [("drop_oldest", books[-2:]), ("drop_newest", books[:2])])
+def test_write_buffer_overflow(unused_tcp_port, overflow, kept):
+    """
+    When the write buffer is full, the oldest or newest write is dropped depending on write_buffer_overflow
+    """
+    server = redislite.Redis(serverconfig={"port": unused_tcp_port})
+    store = Store(url=f"redis://localhost:{unused_tcp_port}/1", timeout=100, write_buffer=2,
+                  write_buffer_overflow=overflow)
+    store.create_collection(Author, primary_key_field="name")
+    store.create_collection(Book, primary_key_field="title")
+    book_collection = store.get_collection(Book)
+
+    server.shutdown()
+    for book in books:
+        book_collection.add_one(book)
+    assert store.pending_writes() == 2
+
+    server = redislite.Redis(serverconfig={"port": unused_tcp_port})
+    for _ in range(50):
+        if store.pending_writes() == 0:
+            break
+        time.sleep(0.1)
+
+    assert sorted(book_collection.get_all(), key=lambda b: b.title) == sorted(kept, key=lambda b: b.title)
+    server.shutdown()
+
+
+def test_write_buffer_overflow_raise(unused_tcp_port):
+    """
+    When the write buffer is full and write_buffer_overflow is "raise", writes raise a ConnectionError
+    """
+    server = redislite.Redis(serverconfig={"port": unused_tcp_port})
+    store = Store(url=f"redis://localhost:{unused_tcp_port}/1", timeout=100, write_buffer=2,
+                  write_buffer_overflow="raise")
+    store.create_collection(Author, primary_key_field="name")
+    store.create_collection(Book, primary_key_field="title")
+    book_collection = store.get_collection(Book)
+
+    server.shutdown()
+    book_collection.add_one(books[0])
+    book_collection.add_one(books[1])
+    with pytest.raises(ConnectionError):
+        book_collection.add_one(books[2])
+
+    assert store.pending_writes() == 2
+
+
+def test_write_buffer_with_unique_values(unused_tcp_port):
+    """
+    Writes with values of unique fields are not buffered, raising a ConnectionError while redis is unreachable or
+    other writes are buffered, since another record may have the values
+    """
+    server = redislite.Redis(serverconfig={"port": unused_tcp_port})
+    store = Store(url=f"redis://localhost:{unused_tcp_port}/1", timeout=100, write_buffer=10)
+    store.create_collection(Author, primary_key_field="name")
+    store.create_collection(Book, primary_key_field="title")
+
+    class User(Model):
+        id: str
+        email: str
+
+    store.create_collection(User, primary_key_field="id", unique_fields=["email"])
+    user_collection = store.get_collection(User)
+
+    server.shutdown()
+    with pytest.raises(ConnectionError):
+        user_collection.add_one(User(id="u1", email="ann@example.com"))
+    assert store.pending_writes() == 0
+
+    store.get_collection(Book).add_one(books[0])
+    with pytest.raises(ConnectionError, match=r"cannot wait behind 1 buffered writes"):
+        user_collection.add_one(User(id="u1", email="ann@example.com"))
+    assert store.pending_writes() == 1
+
+
+def test_write_buffer_failed_writes(unused_tcp_port):
+    """
+    Buffered writes that fail for reasons other than redis being unreachable when they are applied are dropped,
+    their errors got by take_failed_writes()
+    """
+    server = redislite.Redis(serverconfig={"port": unused_tcp_port})
+    store = Store(url=f"redis://localhost:{unused_tcp_port}/1", timeout=100, write_buffer=10)
+    store.create_collection(Author, primary_key_field="name")
+    store.create_collection(Book, primary_key_field="title")
+    book_collection = store.get_collection(Book)
+    assert store.take_failed_writes() == []
+
+    server.shutdown()
+    book_collection.add_one(books[0])
+    book_collection.add_one(books[1])
+    assert store.pending_writes() == 2
+
+    # redis rejects all writes once it uses more memory than the limit
+    server = redislite.Redis(serverconfig={"port": unused_tcp_port, "maxmemory": "1",
+                                           "maxmemory-policy": "noeviction"})
+    for _ in range(50):
+        if store.pending_writes() == 0:
+            break
+        time.sleep(0.1)
+
+    assert store.pending_writes() == 0
+    failures = store.take_failed_writes()
+    assert len(failures) == 2
+    assert all("OOM" in failure for failure in failures)
+    assert store.take_failed_writes() == []
+    assert book_collection.get_all() == []
+    server.shutdown()
+
+
+@pytest.mark.parametrize("options", [dict(write_buffer=0), dict(write_buffer=10, write_buffer_overflow="block")])
+def test_write_buffer_with_invalid_options(redis_server, options):
+    """A ValueError is raised if the capacity of the write buffer is 0 or its overflow policy is unknown"""
+    with pytest.raises(ValueError):
+        Store(url=f"redis://localhost:{redis_server}/1", **options)
+
+
+def test_without_write_buffer(unused_tcp_port):
+    """Without a write buffer, writes made while redis is unreachable raise a ConnectionError"""
+    server = redislite.Redis(serverconfig={"port": unused_tcp_port})
+    store = Store(url=f"redis://localhost:{unused_tcp_port}/1", timeout=100)
+    store.create_collection(Author, primary_key_field="name")
+    store.create_collection(Book, primary_key_field="title")
+    book_collection = store.get_collection(Book)
+
+    server.shutdown()
+    with pytest.raises(ConnectionError):
+        book_collection.add_one(books[0])
+
+    assert store.pending_writes() == 0
+
+
+def test_circuit_breaker(unused_tcp_port):
+    """
+    Once circuit_breaker_threshold calls in a row fail to get a connection, calls raise a CircuitOpenError
+    without waiting for one, till a call let through after circuit_breaker_reset gets one
+    """
+    server = redislite.Redis(serverconfig={"port": unused_tcp_port})
+    store = Store(url=f"redis://localhost:{unused_tcp_port}/1", timeout=100, circuit_breaker_threshold=2,
+                  circuit_breaker_reset=500)
+    store.create_collection(Author, primary_key_field="name")
+    store.create_collection(Book, primary_key_field="title")
+    book_collection = store.get_collection(Book)
+    book_collection.add_one(books[0])
+    assert store.circuit_breaker() == {
+        "state": "closed", "consecutive_failures": 0, "failure_threshold": 2, "retry_in_ms": None}
+    assert Store(url=f"redis://localhost:{unused_tcp_port}/1").circuit_breaker() is None
+
+    server.shutdown()
+    for _ in range(2):
+        with pytest.raises(ConnectionError) as exc_info:
+            book_collection.get_one(books[0].title)
+        assert not isinstance(exc_info.value, CircuitOpenError)
+
+    with pytest.raises(CircuitOpenError) as exc_info:
+        book_collection.get_one(books[0].title)
+    assert isinstance(exc_info.value, ConnectionError)
+    state = store.circuit_breaker()
+    assert state["state"] == "open" and state["consecutive_failures"] == 2 and 0 < state["retry_in_ms"] <= 500
+
+    server = redislite.Redis(serverconfig={"port": unused_tcp_port})
+    time.sleep(0.5)
+    book_collection.add_one(books[1])
+    assert book_collection.get_one(books[1].title) == books[1]
+    assert store.circuit_breaker()["state"] == "closed"
+    server.shutdown()
+
+
+def test_circuit_breaker_with_invalid_threshold(redis_server):
+    """A ValueError is raised if circuit_breaker_threshold is 0"""
+    with pytest.raises(ValueError):
+        Store(url=f"redis://localhost:{redis_server}/1", circuit_breaker_threshold=0)
+
+
+def test_blocking_pool(redis_server):
+    """
+    Long operations e.g. a throttled create_index(), get their connections from the blocking pool so that
+    other calls still get connections from the main pool while they run
+    """
+    store = Store(url=f"redis://localhost:{redis_server}/1", pool_size=1, timeout=200, blocking_pool_size=1)
+    store.create_collection(Author, primary_key_field="name")
+    store.create_collection(Book, primary_key_field="title")
+    book_collection = store.get_collection(Book)
+    book_collection.add_many(books)
+
+    indexing = threading.Thread(target=book_collection.create_index, args=("rating",), kwargs={"max_ops_per_sec": 2})
+    indexing.start()
+    time.sleep(0.1)
+    assert indexing.is_alive()
+    for book in books:
+        assert book_collection.get_one(book.title) == book
+    indexing.join()
+
+    indexed = book_collection.find_between("rating", 4, None)
+    assert sorted(book.title for book in indexed) == sorted(book.title for book in books if book.rating >= 4)
+
+
+def test_blocking_pool_with_invalid_size(redis_server):
+    """A ValueError is raised if blocking_pool_size is 0"""
+    with pytest.raises(ValueError):
+        Store(url=f"redis://localhost:{redis_server}/1", blocking_pool_size=0)
+
+
+def test_max_batch_bytes(redis_server):
+    """Records bigger than max_batch_bytes are written and read in many batches, all of them being returned"""
+    store = Store(url=f"redis://localhost:{redis_server}/1", max_batch_bytes=100)
+    store.create_collection(Author, primary_key_field="name")
+    store.create_collection(Book, primary_key_field="title")
+    book_collection = store.get_collection(Book)
+    book_collection.add_many(books)
+
+    titles = [book.title for book in books]
+    assert book_collection.get_many(titles) == books
+    # the second read is batched by the sizes of the records read by the first
+    assert book_collection.get_many(titles + ["non-existent"]) == books
+    assert book_collection.get_many([]) == []
+    assert sorted(book.title for book in book_collection.get_all()) == sorted(titles)
+
+
+def test_max_batch_bytes_with_invalid_value(redis_server):
+    """A ValueError is raised if max_batch_bytes is 0"""
+    with pytest.raises(ValueError):
+        Store(url=f"redis://localhost:{redis_server}/1", max_batch_bytes=0)
+
+
+@pytest.mark.parametrize("store", redis_store_fixture)
+def test_coercions(store, redis_server):
+    """
+    The coercions of a collection are applied to the values of its fields when they are read
+    """
+
+    class Member(Model):
+        name: str
+        age: Optional[int]
+        visits: int
+        is_active: bool
+        joined_on: date
+
+    store.create_collection(Member, primary_key_field="name", coercions={
+        "age": ["empty_to_none"],
+        "visits": ["float_to_int"],
+        "is_active": ["loose_bool"],
+        "joined_on": ["timestamp_to_datetime"],
+    })
+    member_collection = store.get_collection(Member)
+    legacy_client = redis.Redis(port=int(redis_server), db=1)
+    legacy_client.hset("Member_%&_john", mapping={
+        "name": "john", "age": "", "visits": "7.0", "is_active": "Yes", "joined_on": "1664582400",
+    })
+    member_collection.add_one(Member(name="jane", age=30, visits=2, is_active=False, joined_on=date(2022, 9, 1)))
+
+    assert member_collection.get_one("john") == Member(
+        name="john", age=None, visits=7, is_active=True, joined_on=date(2022, 10, 1))
+    assert member_collection.get_one_partially("john", ["visits"]) == {"visits": 7}
+    assert member_collection.get_one("jane") == Member(
+        name="jane", age=30, visits=2, is_active=False, joined_on=date(2022, 9, 1))
+
+
+@pytest.mark.parametrize("store", redis_store_fixture)
+def test_conversion_error_context(store, redis_server):
+    """
+    Errors converting the fields of records read from redis say which collection, record and field failed
+    """
+
+    class Member(Model):
+        name: str
+        joined_on: date
+
+    store.create_collection(Member, primary_key_field="name")
+    member_collection = store.get_collection(Member)
+    legacy_client = redis.Redis(port=int(redis_server), db=1)
+    legacy_client.hset("Member_%&_john", mapping={"name": "john", "joined_on": "01/10/2022"})
+
+    with pytest.raises(ValueError) as exc_info:
+        member_collection.get_one("john")
+
+    message = str(exc_info.value)
+    assert '"joined_on"' in message
+    assert '"Member_%&_john"' in message
+    assert '"Member"' in message
+    assert isinstance(exc_info.value.__cause__, ValueError)
+
+
+@pytest.mark.parametrize("store", redis_store_fixture)
+def test_get_all_on_error(store, redis_server):
+    """
+    get_all() skips the records that cannot be read, or returns them separately with their errors,
+    as on_error says, and raises their errors by default
+    """
+
+    class Member(Model):
+        name: str
+        joined_on: date
+
+    store.create_collection(Member, primary_key_field="name")
+    member_collection = store.get_collection(Member)
+    member_collection.add_one(Member(name="jane", joined_on=date(2022, 9, 1)))
+    legacy_client = redis.Redis(port=int(redis_server), db=1)
+    legacy_client.hset("Member_%&_john", mapping={"name": "john", "joined_on": "01/10/2022"})
+
+    with pytest.raises(ValueError):
+        member_collection.get_all()
+
+    assert member_collection.get_all(on_error="skip") == [Member(name="jane", joined_on=date(2022, 9, 1))]
+
+    records, errors = member_collection.get_all(on_error="collect")
+    assert records == [Member(name="jane", joined_on=date(2022, 9, 1))]
+    assert len(errors) == 1
+    assert errors[0]["id"] == "john"
+    assert isinstance(errors[0]["error"], ValueError)
+
+    with pytest.raises(ValueError):
+        member_collection.get_all(on_error="ignore")
+
+
+@pytest.mark.parametrize("store", redis_store_fixture)
+def test_repair(store, redis_server):
+    """
+    repair() rewrites the values in known broken formats in the format orredis writes, returning the number of records
+    repaired
+    """
+
+    class Member(Model):
+        name: str
+        age: Optional[int] = None
+        is_active: bool
+        joined_at: datetime
+
+    store.create_collection(Member, primary_key_field="name")
+    member_collection = store.get_collection(Member)
+    jane = Member(name="jane", age=30, is_active=False, joined_at=datetime(2022, 9, 1, tzinfo=timezone.utc))
+    member_collection.add_one(jane)
+    legacy_client = redis.Redis(port=int(redis_server), db=1)
+    legacy_client.hset("Member_%&_john", mapping={
+        "name": "john", "age": "None", "is_active": "True", "joined_at": "2022-10-01T08:30:00Z",
+    })
+
+    assert member_collection.repair(fix=["bool_case"]) == 1
+    assert legacy_client.hget("Member_%&_john", "is_active") == b"true"
+
+    assert member_collection.repair() == 1
+    assert member_collection.get_one("john") == Member(
+        name="john", age=None, is_active=True, joined_at=datetime(2022, 10, 1, 8, 30, tzinfo=timezone.utc))
+    assert member_collection.get_one("jane") == jane
+    assert member_collection.repair() == 0
+
+    with pytest.raises(ValueError):
+        member_collection.repair(fix=["unknown"])
+
+
+@pytest.mark.parametrize("store", redis_store_fixture)
+def test_compact(store, redis_server):
+    """
+    compact() removes the fields that are no longer in the model from the records, returning the number of records
+    compacted, and can do so in the background
+    """
+
+    class Member(Model):
+        name: str
+        age: int
+
+    store.create_collection(Member, primary_key_field="name", version=1)
+    member_collection = store.get_collection(Member)
+    jane, john = Member(name="jane", age=30), Member(name="john", age=25)
+    member_collection.add_many([jane, john])
+    client = redis.Redis(port=int(redis_server), db=1)
+    client.hset("Member_%&_john", mapping={"nickname": "johnny", "email": "john@example.com"})
+
+    with pytest.raises(ValueError, match=r"max_ops_per_sec should be a number greater than 0"):
+        member_collection.compact(max_ops_per_sec=0)
+
+    assert member_collection.compact() == 1
+    assert set(client.hkeys("Member_%&_john")) == {b"name", b"age", b"_version"}
+    assert member_collection.get_many(["jane", "john"]) == [jane, john]
+    assert member_collection.compact(max_ops_per_sec=100) == 0
+
+    client.hset("Member_%&_jane", "nickname", "janie")
+    assert member_collection.compact(background=True) is None
+    for _ in range(50):
+        if not store.background_tasks()["compact:Member"]["running"]:
+            break
+        time.sleep(0.1)
+    assert store.background_tasks()["compact:Member"] == {"running": False, "runs": 1, "error": None}
+    assert set(client.hkeys("Member_%&_jane")) == {b"name", b"age", b"_version"}
+
+
+def test_compact_in_background_with_error(unused_tcp_port):
+    """
+    The error that stops a compaction in the background is got with background_tasks(), and another compaction
+    of the collection cannot start while one is running
+    """
+    server = redislite.Redis(serverconfig={"port": unused_tcp_port})
+    store = Store(url=f"redis://localhost:{unused_tcp_port}/1", timeout=100)
+    store.create_collection(Author, primary_key_field="name")
+    store.create_collection(Book, primary_key_field="title")
+    book_collection = store.get_collection(Book)
+    book_collection.add_many(books)
+
+    assert book_collection.compact(max_ops_per_sec=1, background=True) is None
+    with pytest.raises(ValueError, match=r"\"compact:Book\" is already running"):
+        book_collection.compact(background=True)
+    assert store.cancel_background("compact:Book")
+
+    server.shutdown()
+    assert book_collection.compact(background=True) is None
+    for _ in range(50):
+        if not store.background_tasks()["compact:Book"]["running"]:
+            break
+        time.sleep(0.1)
+
+    status = store.background_tasks()["compact:Book"]
+    assert status["running"] is False and status["runs"] == 0
+    assert status["error"] is not None
+
+
+@pytest.mark.parametrize("coercions", [
+    {"foo": ["empty_to_none"]},
+    {"title": ["float_to_int"]},
+    {"rating": ["unknown"]},
+])
+def test_create_collection_with_invalid_coercions(redis_store, coercions):
+    """create_collection() raises a ValueError if a coercion is unknown or cannot be applied to its field"""
+
+    class Magazine(Model):
+        title: str
+        rating: float
+
+    with pytest.raises(ValueError):
+        redis_store.create_collection(Magazine, primary_key_field="title", coercions=coercions)
+
+
+@pytest.mark.parametrize("store", redis_store_fixture)
+def test_versioning(store, redis_server):
+    """
+    Records of older format versions are upgraded when read, and records written are saved with the current version
+    """
+
+    class Member(Model):
+        name: str
+        age: int
+        is_adult: bool
+
+    def rename_fullname(data):
+        data["name"] = data.pop("fullname")
+        return data
+
+    def add_is_adult(data):
+        return {**data, "is_adult": int(data["age"]) >= 18}
+
+    store.create_collection(Member, primary_key_field="name", version=2,
+                            upgrades={0: rename_fullname, 1: add_is_adult})
+    member_collection = store.get_collection(Member)
+    legacy_client = redis.Redis(port=int(redis_server), db=1)
+    legacy_client.hset("Member_%&_john", mapping={"fullname": "john", "age": "30"})
+    legacy_client.hset("Member_%&_jane", mapping={"name": "jane", "age": "12", "_version": "1"})
+    member_collection.add_one(Member(name="doe", age=40, is_adult=False))
+
+    assert member_collection.get_one("john") == Member(name="john", age=30, is_adult=True)
+    assert member_collection.get_one("jane") == Member(name="jane", age=12, is_adult=False)
+    assert member_collection.get_one("doe") == Member(name="doe", age=40, is_adult=False)
+    assert member_collection.get_one_partially("john", ["is_adult"]) == {"is_adult": True}
+    assert legacy_client.hget("Member_%&_doe", "_version") == b"2"
+    assert legacy_client.hgetall("Member_%&_john") == {b"fullname": b"john", b"age": b"30"}
+
+
+@pytest.mark.parametrize("store", redis_store_fixture)
+def test_versioning_with_persisted_upgrades(store, redis_server):
+    """
+    Records upgraded on read are saved in the current format version if persist_upgrades is True
+    """
+
+    class Member(Model):
+        name: str
+        age: int
+
+    calls = []
+
+    def rename_fullname(data):
+        calls.append(data)
+        data["name"] = data.pop("fullname")
+        return data
+
+    store.create_collection(Member, primary_key_field="name", version=1, upgrades={0: rename_fullname},
+                            persist_upgrades=True)
+    member_collection = store.get_collection(Member)
+    legacy_client = redis.Redis(port=int(redis_server), db=1)
+    legacy_client.hset("Member_%&_john", mapping={"fullname": "john", "age": "30"})
+
+    assert member_collection.get_all() == [Member(name="john", age=30)]
+    assert member_collection.get_all() == [Member(name="john", age=30)]
+    assert len(calls) == 1
+    assert legacy_client.hgetall("Member_%&_john") == {b"name": b"john", b"age": b"30", b"_version": b"1"}
+
+
+@pytest.mark.parametrize("options", [
+    {"upgrades": {0: lambda data: data}},
+    {"version": 1, "upgrades": {1: lambda data: data}},
+    {"version": 1, "upgrades": {0: "foo"}},
+])
+def test_create_collection_with_invalid_versioning(redis_store, options):
+    """create_collection() raises a ValueError if the upgrades are not from versions older than the version"""
+
+    class Magazine(Model):
+        title: str
+
+    with pytest.raises(ValueError):
+        redis_store.create_collection(Magazine, primary_key_field="title", **options)
+
+
+@pytest.mark.parametrize("store", redis_store_fixture)
+def test_create_collection_with_register_nested_models(store):
+    """
+    create_collection() with register_nested_models=True creates the collections of the nested models that have none,
+    getting their primary keys from their schemas
+    """
+    store.create_collection(Shelf, primary_key_field="code", register_nested_models=True)
+    shelf_collection = store.get_collection(Shelf)
+    publisher_collection = store.get_collection(Publisher)
+    address_collection = store.get_collection(Address)
+    shelf = Shelf(code="A1", publisher=Publisher(name="Penguin", address=Address(street="Strand", number=80)))
+
+    shelf_collection.add_one(shelf)
+
+    assert shelf_collection.get_one("A1") == shelf
+    assert publisher_collection.get_one("Penguin") == shelf.publisher
+    assert address_collection.get_one("Strand") == shelf.publisher.address
+
+
+def test_create_collection_with_unregistered_nested_models(redis_store):
+    """
+    create_collection() raises a KeyError if a nested model has no collection and either register_nested_models
+    is False or the nested model has no primary key in its schema
+    """
+
+    class Shelf(Model):
+        code: str
+        library: Library
+
+    with pytest.raises(KeyError):
+        redis_store.create_collection(Shelf, primary_key_field="code")
+
+    with pytest.raises(KeyError):
+        redis_store.create_collection(Shelf, primary_key_field="code", register_nested_models=True)
+
+
+@pytest.mark.parametrize("store", redis_store_fixture)
+def test_self_referencing_model(store):
+    """
+    Records of models that nest themselves are saved with references to their nested records, which are read
+    to the nested_depth given to create_collection()
+    """
+    store.create_collection(Employee, primary_key_field="name", nested_depth=2)
+    employee_collection = store.get_collection(Employee)
+    alice = Employee(name="Alice")
+    bob = Employee(name="Bob", manager=alice)
+    carol = Employee(name="Carol", manager=bob)
+
+    employee_collection.add_one(Employee(name="Dave", manager=carol))
+
+    assert employee_collection.get_one("Alice") == alice
+    assert employee_collection.get_one("Bob") == bob
+    assert employee_collection.get_one("Carol") == carol
+    assert employee_collection.get_one("Dave") == Employee(
+        name="Dave", manager=Employee(name="Carol", manager=Employee(name="Bob")))
+
+
+@pytest.mark.parametrize("store", redis_store_fixture)
+def test_mutually_referencing_models(store):
+    """
+    create_collection() with register_nested_models=True creates collections for models that refer to each other
+    """
+    store.create_collection(Team, primary_key_field="name", register_nested_models=True, nested_depth=2)
+    team_collection = store.get_collection(Team)
+    player_collection = store.get_collection(Player)
+    team = Team(name="Reds", captain=Player(name="Ann", team=Team(name="Reds")))
+
+    team_collection.add_one(team)
+
+    assert team_collection.get_one("Reds") == team
+    assert player_collection.get_one("Ann") == Player(name="Ann", team=Team(name="Reds", captain=Player(name="Ann")))
+
+
+@pytest.mark.parametrize("store", redis_store_fixture)
+def test_create_collection_with_forward_refs(store):
+    """create_collection() resolves nested models given as string type hints against the model's module"""
+    store.create_collection(Owner, primary_key_field="name", register_nested_models=True)
+    owner_collection = store.get_collection(Owner)
+    owner = Owner(name="John", pet=Pet(name="Rex"))
+
+    owner_collection.add_one(owner)
+
+    assert owner_collection.get_one("John") == owner
+    assert store.get_collection(Pet).get_one("Rex") == owner.pet
+
+
+def test_create_collection_with_local_forward_refs(redis_store):
+    """
+    create_collection() resolves nested models given as string type hints against the models of the store's
+    collections, raising a KeyError if they cannot be resolved
+    """
+
+    class Person(Model):
+        name: str
+        cat: "Cat"
+
+    class Cat(Model):
+        name: str
+
+    with pytest.raises(KeyError):
+        redis_store.create_collection(Person, primary_key_field="name")
+
+    redis_store.create_collection(Cat, primary_key_field="name")
+    redis_store.create_collection(Person, primary_key_field="name")
+    person_collection = redis_store.get_collection(Person)
+    person = Person(name="John", cat=Cat(name="Tom"))
+
+    person_collection.add_one(person)
+
+    assert person_collection.get_one("John") == person
+
+
+@pytest.mark.parametrize("store", redis_store_fixture)
+def test_update_one_with_dotted_paths(store):
+    """update_one() updates the fields of nested records addressed by dotted paths directly in the nested records"""
+    store.create_collection(Shelf, primary_key_field="code", register_nested_models=True)
+    shelf_collection = store.get_collection(Shelf)
+    address_collection = store.get_collection(Address)
+    shelf = Shelf(code="A1", publisher=Publisher(name="Penguin", address=Address(street="Strand", number=80)))
+    shelf_collection.add_one(shelf)
+
+    shelf_collection.update_one("A1", {"publisher.address.number": 81})
+
+    assert address_collection.get_one("Strand") == Address(street="Strand", number=81)
+    assert shelf_collection.get_one("A1").publisher.address.number == 81
+
+    with pytest.raises(KeyError):
+        shelf_collection.update_one("A1", {"publisher.country": "UK"})
+
+    with pytest.raises(ValueError):
+        shelf_collection.update_one("A1", {"code.length": 2})
+
+    with pytest.raises(ValueError):
+        shelf_collection.update_one("A1", {"publisher.address": {"street": "Fleet", "number": 1}})
+
+
+@pytest.mark.parametrize("store", redis_store_fixture)
+def test_get_partially_with_dotted_paths(store):
+    """get_*_partially() return only the fields of nested records given by their dotted paths, as typed dicts"""
+    store.create_collection(Shelf, primary_key_field="code", register_nested_models=True)
+    shelf_collection = store.get_collection(Shelf)
+    shelf = Shelf(code="A1", publisher=Publisher(name="Penguin", address=Address(street="Strand", number=80)))
+    shelf_collection.add_one(shelf)
+    expected = {"code": "A1", "publisher": {"name": "Penguin", "address": {"number": 80}}}
+
+    fields = ["code", "publisher.name", "publisher.address.number"]
+
+    assert shelf_collection.get_one_partially("A1", fields) == expected
+    assert shelf_collection.get_many_partially(["A1"], fields) == [expected]
+    assert shelf_collection.get_all_partially(fields) == [expected]
+    assert shelf_collection.get_one_partially("A1", ["publisher", "publisher.name"]) == {"publisher": shelf.publisher}
+
+    with pytest.raises(KeyError):
+        shelf_collection.get_one_partially("A1", ["publisher.country"])
+
+
+def test_get_partially_with_nested_as_models(store):
+    """get_*_partially() return the nested records given by dotted paths as model instances if nested_as_models"""
+    store.create_collection(Shelf, primary_key_field="code", register_nested_models=True)
+    shelf_collection = store.get_collection(Shelf)
+    shelf_collection.add_one(
+        Shelf(code="A1", publisher=Publisher(name="Penguin", address=Address(street="Strand", number=80))))
+    fields = ["code", "publisher.name", "publisher.address.number"]
+
+    for got in [shelf_collection.get_one_partially("A1", fields, nested_as_models=True),
+                *shelf_collection.get_many_partially(["A1"], fields, nested_as_models=True),
+                *shelf_collection.get_all_partially(fields, nested_as_models=True)]:
+        assert got["code"] == "A1"
+        assert isinstance(got["publisher"], Publisher)
+        assert got["publisher"].name == "Penguin"
+        assert isinstance(got["publisher"].address, Address)
+        assert got["publisher"].address.number == 80
+        assert got["publisher"].__fields_set__ == {"name", "address"}
+
+
+def test_ttl_in_milliseconds(redis_server):
+    """ttl and default_ttl can be timedeltas or float seconds, applied with millisecond precision"""
+    store = Store(url=f"redis://localhost:{redis_server}/1", default_ttl=timedelta(milliseconds=1500))
+    store.create_collection(Author, primary_key_field="name")
+    store.create_collection(Book, primary_key_field="title")
+    book_collection = store.get_collection(Book)
+    client = redis.Redis(port=int(redis_server), db=1)
+
+    # books of different authors so that no nested record gets the shorter ttl of another book
+    book_collection.add_one(books[0])
+    book_collection.add_one(books[3], ttl=0.25)
+    book_collection.update_one(books[2].title, {"rating": 4}, ttl=timedelta(seconds=2.5))
+
+    assert 1000 < client.pttl(f"Book_%&_{books[0].title}") <= 1500
+    assert 0 < client.pttl(f"Book_%&_{books[3].title}") <= 250
+    assert 2000 < client.pttl(f"Book_%&_{books[2].title}") <= 2500
+
+    time.sleep(0.3)
+    assert book_collection.get_one(books[3].title) is None
+    assert book_collection.get_one(books[0].title) == books[0]
+    store.clear()
+
+
+def test_ttl_as_datetime(redis_store, redis_server):
+    """ttl can be a datetime at which the records expire"""
+    book_collection = redis_store.get_collection(Book)
+    client = redis.Redis(port=int(redis_server), db=1)
+    expires_at = datetime.now(tz=timezone.utc) + timedelta(seconds=2)
+
+    book_collection.add_one(books[0], ttl=expires_at)
+    # naive datetimes are in the local timezone
+    book_collection.add_many(books[3:], ttl=datetime.now() + timedelta(seconds=2))
+
+    assert 1000 < client.pttl(f"Book_%&_{books[0].title}") <= 2000
+    assert 1000 < client.pttl(f"Book_%&_{books[3].title}") <= 2000
+
+    with pytest.raises(ValueError):
+        book_collection.add_one(books[0], ttl="tomorrow")
+
+
+def test_store_defaults(redis_server):
+    """The defaults of a store apply to all its collections unless create_collection() is given other options"""
+    store = Store(url=f"redis://localhost:{redis_server}/1", defaults=dict(track_changes=True, id_generator="uuid4"))
+
+    class Note(Model):
+        id: Optional[str] = None
+        text: str
+
+    store.create_collection(Note, primary_key_field="id")
+    store.create_collection(Author, primary_key_field="name", track_changes=False, id_generator=None)
+    note_collection = store.get_collection(Note)
+    author_collection = store.get_collection(Author)
+
+    note_collection.add_one(Note(text="hello"))
+    author_collection.add_one(authors["jane"])
+
+    assert [change["after"]["text"] for change in note_collection.get_changes()] == ["hello"]
+    with pytest.raises(ValueError):
+        author_collection.get_changes()
+    store.clear()
+
+
+@pytest.mark.parametrize("defaults", [
+    {"foo": True},
+    {"id_generator": "bar"},
+    {"nested_depth": 0},
+    {"retention": "forever"},
+])
+def test_store_with_invalid_defaults(redis_server, defaults):
+    """Throws a ValueError when the defaults of a store are not valid options of all its collections"""
+    with pytest.raises(ValueError):
+        Store(url=f"redis://localhost:{redis_server}/1", defaults=defaults)
+
+
+def test_store_with_unknown_option(redis_server):
+    """Throws a TypeError when a store is given an option it does not have e.g. one only the async store has"""
+    with pytest.raises(TypeError, match=r"unexpected keyword argument \"max_connecting\""):
+        Store(url=f"redis://localhost:{redis_server}/1", max_connecting=2)
+
+
+@pytest.mark.parametrize("store", redis_store_fixture)
+def test_latency(store):
+    """latency() returns the statistics of the round-trip latencies of PINGs to redis"""
+    stats = store.latency(samples=20)
+    assert stats["samples"] == 20
+    assert 0 <= stats["min_ms"] <= stats["avg_ms"] <= stats["max_ms"]
+    assert stats["min_ms"] <= stats["p99_ms"] <= stats["max_ms"]
+    assert stats["checkout_ms"] >= 0
+
+    assert store.latency()["samples"] == 10
+    with pytest.raises(ValueError):
+        store.latency(samples=0)
+
+
+def test_lazy_store_connect_and_close(redis_server):
+    """A lazy store is created before redis is up, connect() PINGs redis and close() closes the store"""
+    sock = socket.socket(socket.AF_INET, socket.SOCK_STREAM)
+    sock.bind(("", 0))
+    unreachable_port = sock.getsockname()[1]
+    sock.close()
+
+    unreachable_store = Store(url=f"redis://localhost:{unreachable_port}/1", lazy=True, timeout=200)
+    with pytest.raises(ConnectionError):
+        unreachable_store.connect()
+
+    store = Store(url=f"redis://localhost:{redis_server}/1", lazy=True)
+    store.create_collection(Book, primary_key_field="title")
+    book_collection = store.get_collection(Book)
+    store.connect()
+    book_collection.add_one(books[0])
+
+    store.close()
+    with pytest.raises(ConnectionError, match="the store is closed"):
+        book_collection.get_one(books[0].title)
+    with pytest.raises(ConnectionError, match="the store is closed"):
+        store.connect()
+
+
+@pytest.mark.parametrize("store", redis_store_fixture)
+def test_metrics(store):
+    """metrics() returns the counts of the operations on each collection and of the records they read or wrote"""
+    book_collection = store.get_collection(Book)
+    book_collection.add_many(books)
+    book_collection.get_many([books[0].title, books[1].title, "nonexistent"])
+    book_collection.get_one(books[0].title)
+    book_collection.get_one("nonexistent")
+    book_collection.delete_many([books[0].title])
+
+    metrics = store.metrics()
+    assert metrics["Book"] == {
+        "add_many": {"calls": 1, "records": 4},
+        "get_many": {"calls": 1, "records": 2},
+        "get_one": {"calls": 2, "records": 1},
+        "delete_many": {"calls": 1, "records": 1},
+    }
+    assert metrics["Author"] == {}
+
+
+def test_store_with_ipv6_host(redis_server):
+    """A store connects to redis at an IPv6 literal host"""
+    try:
+        socket.create_connection(("::1", int(redis_server)), timeout=1).close()
+    except OSError:
+        pytest.skip("redis is not listening on the IPv6 loopback address")
+
+    store = Store(url=f"redis://[::1]:{redis_server}/1")
+    store.create_collection(Author, primary_key_field="name")
+    author_collection = store.get_collection(Author)
+    author_collection.add_one(authors["jane"])
+    assert author_collection.get_one(authors["jane"].name) == authors["jane"]
+    store.clear()
+
+
+@pytest.mark.parametrize("url", [
+    "redis+srv://_redis._tcp.orredis.invalid/0",
+    "redis+srv://orredis.invalid",
+])
+def test_store_with_unresolvable_srv_url(url):
+    """Throws a ConnectionError when no redis endpoint is found in the SRV records of the url's host"""
+    with pytest.raises(ConnectionError):
+        Store(url=url)
+
+
+def test_store_with_srv_url_with_port():
+    """Throws a ValueError when a srv url has a port, as the port is got from the SRV records"""
+    with pytest.raises(ValueError):
+        Store(url="redis+srv://_redis._tcp.example.com:6379/0")
+@pytest.mark.parametrize("store", redis_store_fixture)
+def test_var_tuple_fields(store):
+    """Fields annotated as tuples of any length e.g. Tuple[int, ...] are read back as tuples of their items"""
+
+    class Sensor(Model):
+        name: str
+        readings: Tuple[int, ...]
+        labels: Tuple[str, ...] = ()
+
+    store.create_collection(Sensor, primary_key_field="name")
+    sensor_collection = store.get_collection(Sensor)
+    sensors = [
+        Sensor(name="a", readings=(1, 2, 3), labels=("x", "y")),
+        Sensor(name="b", readings=(4,), labels=("z",)),
+        Sensor(name="c", readings=()),
+    ]
+    sensor_collection.add_many(sensors)
+
+    for sensor in sensors:
+        got = sensor_collection.get_one(sensor.name)
+        assert got == sensor
+        assert isinstance(got.readings, tuple)
+
+    assert store.describe()["collections"][Sensor.__qualname__]["fields"]["readings"] == "tuple[int, ...]"
+
+@pytest.mark.parametrize("store", redis_store_fixture)
+def test_dict_key_types(store):
+    """The keys of dictionaries annotated with int or float keys e.g. Dict[int, str] are read back as ints or floats"""
+
+    class Ledger(Model):
+        name: str
+        entries: Dict[int, str]
+        rates: Dict[float, str] = {}
+        notes: Dict[str, str] = {}
+
+    store.create_collection(Ledger, primary_key_field="name")
+    ledger_collection = store.get_collection(Ledger)
+    ledger = Ledger(name="a", entries={1: "one", 20: "twenty"}, rates={0.5: "half"}, notes={"1": "first"})
+    ledger_collection.add_one(ledger)
+
+    assert ledger_collection.get_one("a") == ledger
+    partial = ledger_collection.get_one_partially("a", fields=["entries", "rates", "notes"])
+    assert partial == {"entries": {1: "one", 20: "twenty"}, "rates": {0.5: "half"}, "notes": {"1": "first"}}
+
+    fields = store.describe()["collections"][Ledger.__qualname__]["fields"]
+    assert fields["entries"] == "dict[int, str]"
+    assert fields["notes"] == "dict[str, str]"
+
+
+@pytest.mark.parametrize("store", redis_store_fixture)
+def test_typed_dict_values(store):
+    """The values of dictionaries are read back as the types given in their models e.g. Dict[str, List[int]]"""
+
+    class Part(Model):
+        code: str
+        tags: List[str] = []
+
+    class Machine(Model):
+        name: str
+        readings: Dict[str, List[int]]
+        parts: Dict[str, Part] = {}
+        flags: Dict[str, bool] = {}
+
+    store.create_collection(Part, primary_key_field="code")
+    store.create_collection(Machine, primary_key_field="name")
+    machine_collection = store.get_collection(Machine)
+    machine = Machine(name="a", readings={"x": [1, 2], "y": []},
+                      parts={"main": Part(code="p, 1: x", tags=["a", "b, c"])}, flags={"on": True, "off": False})
+    machine_collection.add_one(machine)
+
+    assert machine_collection.get_one("a") == machine
+    partial = machine_collection.get_one_partially("a", fields=["readings", "flags"])
+    assert partial == {"readings": {"x": [1, 2], "y": []}, "flags": {"on": True, "off": False}}
+
+    fields = store.describe()["collections"][Machine.__qualname__]["fields"]
+    assert fields["readings"] == "dict[str, list[int]]"
+    assert fields["parts"] == "dict[str, Part]"
+
+
+@pytest.mark.parametrize("store", redis_store_fixture)
+def test_enforce_constraints(store):
+    """enforce_constraints checks the values of fields of constrained types on inserts and partial updates"""
+
+    class Account(Model):
+        id: str
+        balance: conint(ge=0)
+        code: constr(max_length=4, regex=r"^[A-Z]+$") = "AB"
+
+    class Draft(Model):
+        id: str
+        balance: conint(ge=0)
+
+    store.create_collection(Account, primary_key_field="id", enforce_constraints=True)
+    store.create_collection(Draft, primary_key_field="id")
+    account_collection = store.get_collection(Account)
+    draft_collection = store.get_collection(Draft)
+    account_collection.add_one(Account(id="a", balance=10))
+
+    with pytest.raises(ValueError, match="it should be at least 0"):
+        account_collection.update_one("a", data={"balance": -1})
+    with pytest.raises(ValueError, match="it should have at most 4 characters"):
+        account_collection.update_one("a", data={"code": "ABCDE"})
+    with pytest.raises(ValueError, match="it should match the pattern"):
+        account_collection.update_one("a", data={"code": "ab"})
+    assert account_collection.get_one("a") == Account(id="a", balance=10)
+
+    account_collection.update_one("a", data={"balance": 0, "code": "XYZ"})
+    assert account_collection.get_one("a") == Account(id="a", balance=0, code="XYZ")
+
+    # collections that do not enforce constraints let invalid partial updates through
+    draft_collection.add_one(Draft(id="d", balance=10))
+    draft_collection.update_one("d", data={"balance": -1})
+    assert draft_collection.get_one_partially("d", fields=["balance"]) == {"balance": -1}
+
+
+@pytest.mark.parametrize("store", redis_store_fixture)
+def test_deadline(store):
+    """Calls made once the deadline set in orredis.deadline has passed raise a DeadlineExceededError"""
+    book_collection = store.get_collection(Book)
+    book_collection.add_many(books)
+
+    token = deadline.set(time.time() + 60)
+    try:
+        assert book_collection.get_one(books[0].title) == books[0]
+    finally:
+        deadline.reset(token)
+
+    for passed_deadline in [time.time() - 1, datetime.now(tz=timezone.utc) - timedelta(seconds=1)]:
+        token = deadline.set(passed_deadline)
+        try:
+            with pytest.raises(DeadlineExceededError) as exc_info:
+                book_collection.get_one(books[0].title)
+        finally:
+            deadline.reset(token)
+        assert isinstance(exc_info.value, TimeoutError)
+
+    assert book_collection.get_one(books[0].title) == books[0]
+
+
+@pytest.mark.parametrize("store", redis_store_fixture)
+def test_vector_fields(store):
+    """Vector fields are read back as lists of floats and their records are found by knn() if RediSearch is loaded"""
+
+    class Document(Model):
+        name: str
+        embedding: Vector[3]
+        extra: Optional[Vector[3]] = None
+
+    store.create_collection(Document, primary_key_field="name")
+    document_collection = store.get_collection(Document)
+    documents = [
+        Document(name="north", embedding=[0, 1, 0]),
+        Document(name="north-east", embedding=[0.7, 0.7, 0]),
+        Document(name="east", embedding=[1, 0, 0.1], extra=[1, 2, 3]),
+    ]
+    document_collection.add_many(documents)
+
+    assert document_collection.get_one("east") == documents[2]
+    north = document_collection.get_one("north")
+    assert north.embedding == [0.0, 1.0, 0.0] and isinstance(north.embedding[0], float)
+    assert store.describe()["collections"][Document.__qualname__]["fields"]["embedding"] == "vector[3]"
+    with pytest.raises(ValueError):
+        Document(name="south", embedding=[0, -1])
+    with pytest.raises(ValueError):
+        document_collection.knn("name", [0, 1, 0])
+    with pytest.raises(ValueError):
+        document_collection.knn("embedding", [0, 1])
+
+    try:
+        nearest = document_collection.knn("embedding", [0.1, 1, 0], k=2)
+    except ConnectionError as exc:
+        assert "RediSearch" in str(exc)
+    else:
+        assert nearest == documents[:2]
+
+
+@pytest.mark.parametrize("store", redis_store_fixture)
+def test_profile(store):
+    """With profile=True, calls also return the time they spent in each phase, in milliseconds"""
+    book_collection = store.get_collection(Book)
+    phases = {"serialize_ms", "network_ms", "parse_ms", "construct_ms", "total_ms"}
+
+    result, profile = book_collection.add_many(books, profile=True)
+    assert result == [book.title for book in books]
+    assert set(profile) == phases
+    assert profile["serialize_ms"] > 0 and profile["network_ms"] > 0
+    assert book_collection.add_one(books[0]) == books[0].title
+
+    book, profile = book_collection.get_one(books[0].title, profile=True)
+    assert book == books[0]
+    assert profile["network_ms"] > 0 and profile["parse_ms"] > 0 and profile["construct_ms"] > 0
+    assert sum(profile[phase] for phase in phases - {"total_ms"}) <= profile["total_ms"]
+
+    got, profile = book_collection.get_many([book.title for book in books[:2]], profile=True)
+    assert got == books[:2]
+    assert set(profile) == phases
+
+    got, profile = book_collection.get_all(filter={"in_stock": False}, profile=True)
+    assert sorted(book.title for book in got) == sorted(book.title for book in books if not book.in_stock)
+    assert profile["construct_ms"] > 0
+    assert book_collection.get_many([books[0].title]) == [books[0]]
+
+
+@pytest.mark.parametrize("store", redis_store_fixture)
+def test_describe(store):
+    """describe() describes the collections, views and links of the store, in the order of their names"""
+
+    class Tag(Model):
+        name: str
+
+    class Post(Model):
+        id: str
+        title: str
+        tags: List[str]
+
+    store.create_collection(Tag, primary_key_field="name")
+    store.create_collection(Post, primary_key_field="id", indexed_fields=["title"])
+    store.create_link("post_tags", left_collection=Post, right_collection=Tag)
+    store.create_view("books_in_stock", source_collection=Book, projection=["title", "rating"],
+                      filter={"in_stock": True})
+
+    description = store.describe()
+    book = description["collections"]["Book"]
+    assert book["primary_key_field"] == "title"
+    assert book["fields"] == {
+        "title": "str",
+        "author": "Author",
+        "rating": "float",
+        "published_on": "date",
+        "last_updated": "datetime",
+        "tags": "list[str]",
+        "in_stock": "bool",
+    }
+    assert book["relationships"] == [{"field": "author", "kind": "nested", "collection": "Author"}]
+    assert book["version"] is None
+    assert book["namespace"] is None
+    assert description["collections"]["Author"]["fields"] == {"name": "str", "active_years": "tuple[int, int]"}
+
+    post = description["collections"][Post.__qualname__]
+    assert post["primary_key_field"] == "id"
+    assert post["indexed_fields"] == ["title"]
+    assert post["unique_fields"] == []
+    assert post["relationships"] == []
+
+    assert description["views"] == {
+        "books_in_stock": {"source": "Book", "projection": ["title", "rating"], "filter": {"in_stock": "true"}}
+    }
+    assert description["links"] == {"post_tags": {"left": Post.__qualname__, "right": Tag.__qualname__}}
+
+@pytest.mark.parametrize("store", redis_store_fixture)
+def test_browse(store):
+    """
+    browse() gets the pages of the records of a collection with only the given fields, the total number
+    of records and the cursor of the next page, until all the records have been browsed
+    """
+    book_collection = store.get_collection(Book)
+    page = store.browse(Book)
+    assert page == {"records": [], "total": 0, "next_cursor": None}
+
+    book_collection.add_many(books)
+    browsed = []
+    cursor = None
+    for _ in range(len(books)):
+        page = store.browse(Book, cursor=cursor, limit=3, fields=["title", "author.name"])
+        assert page["total"] == len(books)
+        assert len(page["records"]) <= 3
+        browsed.extend(page["records"])
+        cursor = page["next_cursor"]
+        if cursor is None:
+            break
+
+    expected = [{"title": book.title, "author": {"name": book.author.name}} for book in books]
+    assert sorted(browsed, key=lambda x: x["title"]) == sorted(expected, key=lambda x: x["title"])
+
+    page = store.browse(Book, limit=len(books))
+    assert page["next_cursor"] is None
+    assert sorted(record["rating"] for record in page["records"]) == sorted(book.rating for book in books)
+
+    with pytest.raises(KeyError):
+        store.browse(Book, fields=["foo"])
+
+    with pytest.raises(ValueError):
+        store.browse(Book, limit=0)
+
+    with pytest.raises(ValueError):
+        store.browse(Book, cursor="foo")
+
+
+def test_plan_migration(redis_server):
+    """plan_migration() reports the changes to the schema of a collection since its snapshot was saved"""
+
+    def create_store(model):
+        store = Store(url=f"redis://localhost:{redis_server}/1")
+        store.create_collection(model, primary_key_field="id")
+        return store
+
+    class Item(Model):
+        id: str
+        price: int
+        tags: List[str]
+
+    store = create_store(Item)
+    assert store.plan_migration(Item) == {
+        "collection": "test_plan_migration.<locals>.Item",
+        "has_snapshot": False,
+        "added": {"id": "str", "price": "int", "tags": "list[str]"},
+        "removed": {},
+        "type_changed": {},
+    }
+    store.save_schema_snapshot(Item)
+
+    class Item(Model):
+        id: str
+        price: float
+        name: str
+
+    store = create_store(Item)
+    assert store.plan_migration(Item) == {
+        "collection": "test_plan_migration.<locals>.Item",
+        "has_snapshot": True,
+        "added": {"name": "str"},
+        "removed": {"tags": "list[str]"},
+        "type_changed": {"price": ("int", "float")},
+    }
+
+    store.save_schema_snapshot(Item)
+    plan = store.plan_migration(Item)
+    assert (plan["added"], plan["removed"], plan["type_changed"]) == ({}, {}, {})
+
+    with pytest.raises(KeyError):
+        store.plan_migration(Author)
+    store.clear()
+
+
+def test_create_collection_with_invalid_nested_depth(redis_store):
+    """create_collection() raises a ValueError if nested_depth is less than 1"""
+    with pytest.raises(ValueError):
+        redis_store.create_collection(Employee, primary_key_field="name", nested_depth=0)
+
+
+def test_create_collection_with_invalid_retention_field(redis_store):
+    """Throws error when the retention_field is not a timestamp field or there is no retention period"""
+
+    class Log(Model):
+        message: str
+        created_at: datetime
+
+    with pytest.raises(ValueError, match=r"retention_field"):
+        redis_store.create_collection(Log, primary_key_field="message", retention=timedelta(days=30),
+                                      retention_field="message")
+
+    with pytest.raises(ValueError, match=r"retention_field"):
+        redis_store.create_collection(Log, primary_key_field="message", retention_field="created_at")